@@ -0,0 +1,50 @@
+/* C API tree_man (cargo rustc --release --features ffi --crate-type cdylib)
+ *
+ * Функции возвращают TM_OK при успехе и TM_ERROR при ошибке; текст ошибки -
+ * tm_last_error(), действителен до следующего вызова в том же потоке.
+ * После успешного вызова tm_last_error() возвращает NULL.
+ */
+#ifndef TREE_MAN_H
+#define TREE_MAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TM_OK 0
+#define TM_ERROR (-1)
+
+typedef struct TmColumns TmColumns;
+typedef struct TmData TmData;
+
+const char *tm_last_error(void);
+
+/* Колонки до построения данных */
+TmColumns *tm_columns_new(void);
+int32_t tm_columns_add_i64(TmColumns *columns, const char *name, const int64_t *values, size_t len);
+int32_t tm_columns_add_f64(TmColumns *columns, const char *name, const double *values, size_t len);
+int32_t tm_columns_add_str(TmColumns *columns, const char *name, const char *const *values, size_t len);
+void tm_columns_free(TmColumns *columns);
+
+/* Построить данные из колонок (columns освобождается) */
+TmData *tm_data_from_columns(TmColumns *columns);
+void tm_data_free(TmData *data);
+
+int32_t tm_data_create_index(TmData *data, const char *name);
+int32_t tm_data_query_json(TmData *data, const char *query);
+int32_t tm_data_reset(TmData *data);
+size_t tm_data_len(const TmData *data);
+
+/* Номера строк текущей выборки; NULL и *len = 0 для пустой выборки.
+ * Освобождается через tm_ids_free с тем же len. */
+uint64_t *tm_data_result_ids(const TmData *data, size_t *len);
+void tm_ids_free(uint64_t *ids, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TREE_MAN_H */
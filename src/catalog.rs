@@ -0,0 +1,340 @@
+use super::{
+    errors::{CatalogError, GLobalError},
+    filter::FilterData,
+    index::logic::NullMode,
+    result::GlobalResult,
+};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::{mem::size_of, sync::Arc};
+
+/// Настройки набора данных в каталоге
+///
+/// Применяются к FilterData при создании и при `Catalog::update_config`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatasetConfig {
+    pub description: Option<String>,
+    pub null_mode: NullMode,
+    // См. FilterData::set_history_compaction
+    pub history_compaction: Option<usize>,
+}
+
+impl DatasetConfig {
+    pub(crate) fn apply<T: Send + Sync + 'static>(&self, data: &FilterData<T>) {
+        data.set_null_mode(self.null_mode);
+        data.set_history_compaction(self.history_compaction);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetInfo {
+    pub name: String,
+    pub rows: usize,
+    pub indexes: Vec<String>,
+    pub estimated_bytes: usize,
+    pub config: DatasetConfig,
+}
+
+struct CatalogEntry<T>
+where
+    T: Send + Sync + 'static,
+{
+    data: Arc<FilterData<T>>,
+    config: DatasetConfig,
+}
+
+/// Реестр именованных наборов данных
+///
+/// Общий лимит памяти проверяется при создании набора: оценка - строки
+/// исходных данных (вместе с heap-частью, если задан `with_heap_size`),
+/// сохраненные уровни drill-down и память индексов. Для `create` лимит
+/// проверяется по строкам до построения FilterData.
+///
+/// # Example
+///
+/// let catalog = Catalog::new().with_memory_limit(512 * 1024 * 1024);
+/// catalog.create("orders", orders, DatasetConfig::default())?;
+/// let orders = catalog.open("orders")?;
+/// orders.create_field_index("status", |o: &Order| o.status.clone())?;
+/// for info in catalog.list() {
+///     println!("{}: {} rows, ~{} bytes", info.name, info.rows, info.estimated_bytes);
+/// }
+///
+pub struct Catalog<T>
+where
+    T: Send + Sync + 'static,
+{
+    datasets: DashMap<String, CatalogEntry<T>>,
+    memory_limit: Option<usize>,
+    // Heap-память строки (String, Vec и т.п. внутри T) для оценки
+    heap_size: Option<HeapSize<T>>,
+    // Проверка наличия, лимита и вставка - атомарно относительно других create
+    create_lock: Mutex<()>,
+}
+
+type HeapSize<T> = Arc<dyn Fn(&T) -> usize + Send + Sync>;
+
+impl<T> Default for Catalog<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Catalog<T>
+where
+    T: Send + Sync + 'static,
+{
+    // Строка в Arc: данные + счетчики strong / weak
+    const ARC_ROW_BYTES: usize = size_of::<T>() + 2 * size_of::<usize>();
+
+    pub fn new() -> Self {
+        Self {
+            datasets: DashMap::new(),
+            memory_limit: None,
+            heap_size: None,
+            create_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    // Heap-память строки сверх size_of::<T>() - учитывается в оценке и лимите
+    pub fn with_heap_size<F>(mut self, heap_size: F) -> Self
+    where
+        F: Fn(&T) -> usize + Send + Sync + 'static,
+    {
+        self.heap_size = Some(Arc::new(heap_size));
+        self
+    }
+
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    pub fn create(&self, name: &str, items: Vec<T>, config: DatasetConfig) -> GlobalResult<Arc<FilterData<T>>> {
+        let _guard = self.create_lock.lock();
+        self.create_locked(name, items, config)
+    }
+
+    // Зарегистрировать уже построенный FilterData (с индексами)
+    pub fn insert(&self, name: &str, data: FilterData<T>, config: DatasetConfig) -> GlobalResult<Arc<FilterData<T>>> {
+        let _guard = self.create_lock.lock();
+        self.check_vacant(name)?;
+        self.check_limit(name, self.estimate_bytes(&data))?;
+        Ok(self.insert_locked(name, data, config))
+    }
+
+    // create под create_lock: лимит проверяется по строкам до построения FilterData
+    fn create_locked(&self, name: &str, items: Vec<T>, config: DatasetConfig) -> GlobalResult<Arc<FilterData<T>>> {
+        self.check_vacant(name)?;
+        self.check_limit(name, self.estimate_items_bytes(&items))?;
+        Ok(self.insert_locked(name, FilterData::from_vec(items), config))
+    }
+
+    fn check_vacant(&self, name: &str) -> GlobalResult<()> {
+        if self.datasets.contains_key(name) {
+            return Err(GLobalError::Catalog(CatalogError::AlreadyExists { name: name.to_string() }));
+        }
+        Ok(())
+    }
+
+    fn check_limit(&self, name: &str, bytes: usize) -> GlobalResult<()> {
+        if let Some(limit) = self.memory_limit {
+            let requested = self.memory_usage() + bytes;
+            if requested > limit {
+                return Err(GLobalError::Catalog(CatalogError::MemoryLimitExceeded {
+                    name: name.to_string(),
+                    requested,
+                    limit,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_locked(&self, name: &str, data: FilterData<T>, config: DatasetConfig) -> Arc<FilterData<T>> {
+        config.apply(&data);
+        let data = Arc::new(data);
+        self.datasets.insert(name.to_string(), CatalogEntry {
+            data: Arc::clone(&data),
+            config,
+        });
+        data
+    }
+
+    pub fn open(&self, name: &str) -> GlobalResult<Arc<FilterData<T>>> {
+        self.datasets
+            .get(name)
+            .map(|entry| Arc::clone(&entry.data))
+            .ok_or(GLobalError::Catalog(CatalogError::NotFound { name: name.to_string() }))
+    }
+
+    pub fn open_or_create<F>(&self, name: &str, items: F, config: DatasetConfig) -> GlobalResult<Arc<FilterData<T>>>
+    where
+        F: FnOnce() -> Vec<T>,
+    {
+        // Поиск и создание под одним create_lock: параллельный create
+        // того же имени не превращается в AlreadyExists
+        let _guard = self.create_lock.lock();
+        match self.open(name) {
+            Ok(data) => Ok(data),
+            Err(_) => self.create_locked(name, items(), config),
+        }
+    }
+
+    // Удаление из каталога; открытые Arc остаются валидными до их drop
+    pub fn drop_dataset(&self, name: &str) -> GlobalResult<()> {
+        self.datasets
+            .remove(name)
+            .map(|_| ())
+            .ok_or(GLobalError::Catalog(CatalogError::NotFound { name: name.to_string() }))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.datasets.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.datasets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.datasets.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.datasets.iter().map(|entry| entry.key().clone()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn config(&self, name: &str) -> GlobalResult<DatasetConfig> {
+        self.datasets
+            .get(name)
+            .map(|entry| entry.config.clone())
+            .ok_or(GLobalError::Catalog(CatalogError::NotFound { name: name.to_string() }))
+    }
+
+    pub fn update_config(&self, name: &str, config: DatasetConfig) -> GlobalResult<()> {
+        let mut entry = self.datasets
+            .get_mut(name)
+            .ok_or(GLobalError::Catalog(CatalogError::NotFound { name: name.to_string() }))?;
+        config.apply(&entry.data);
+        entry.config = config;
+        Ok(())
+    }
+
+    pub fn info(&self, name: &str) -> GlobalResult<DatasetInfo> {
+        self.datasets
+            .get(name)
+            .map(|entry| self.entry_info(entry.key(), &entry))
+            .ok_or(GLobalError::Catalog(CatalogError::NotFound { name: name.to_string() }))
+    }
+
+    // Все наборы, отсортированные по имени
+    pub fn list(&self) -> Vec<DatasetInfo> {
+        let mut result: Vec<DatasetInfo> = self.datasets
+            .iter()
+            .map(|entry| self.entry_info(entry.key(), entry.value()))
+            .collect();
+        result.sort_by(|left, right| left.name.cmp(&right.name));
+        result
+    }
+
+    // Суммарная оценка памяти всех наборов
+    pub fn memory_usage(&self) -> usize {
+        self.datasets
+            .iter()
+            .map(|entry| self.estimate_bytes(&entry.data))
+            .sum()
+    }
+
+    fn entry_info(&self, name: &str, entry: &CatalogEntry<T>) -> DatasetInfo {
+        let mut indexes = entry.data.list_indexes();
+        indexes.sort_unstable();
+        DatasetInfo {
+            name: name.to_string(),
+            rows: entry.data.storage_rows().map(|source| source.len()).unwrap_or_default(),
+            indexes,
+            estimated_bytes: self.estimate_bytes(&entry.data),
+            config: entry.config.clone(),
+        }
+    }
+
+    // Строки (Arc с данными и heap-частью), уровни drill-down и индексы
+    fn estimate_bytes(&self, data: &FilterData<T>) -> usize {
+        let rows = data.storage_rows().map(|source| self.rows_bytes(&source)).unwrap_or_default();
+        let indexes: usize = data.index_memory_stats().iter().map(|stats| stats.total_bytes).sum();
+        rows + data.memory_stats().total_stored_items * size_of::<Arc<T>>() + indexes
+    }
+
+    // Оценка create до построения FilterData: строки + исходный уровень
+    fn estimate_items_bytes(&self, items: &[T]) -> usize {
+        let heap: usize = self.heap_size.as_ref().map_or(0, |heap_size| items.iter().map(|item| heap_size(item)).sum());
+        items.len() * (Self::ARC_ROW_BYTES + size_of::<Arc<T>>()) + heap
+    }
+
+    fn rows_bytes(&self, rows: &[Arc<T>]) -> usize {
+        let heap: usize = self.heap_size.as_ref().map_or(0, |heap_size| rows.iter().map(|item| heap_size(item)).sum());
+        rows.len() * Self::ARC_ROW_BYTES + heap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog_lifecycle_and_memory_limit() {
+        let catalog: Catalog<u64> = Catalog::new().with_memory_limit(64 * 1024);
+        let config = DatasetConfig {
+            history_compaction: Some(3),
+            ..DatasetConfig::default()
+        };
+        catalog.create("small", (0..1_000).collect(), config.clone()).unwrap();
+        assert!(catalog.create("small", Vec::new(), DatasetConfig::default()).is_err());
+        assert_eq!(catalog.open("small").unwrap().history_compaction(), Some(3));
+        assert_eq!(catalog.info("small").unwrap().rows, 1_000);
+
+        // 1_000 строк ~ 32KB, 10_000 уже не помещаются в общий лимит
+        assert!(matches!(
+            catalog.create("big", (0..10_000).collect(), DatasetConfig::default()),
+            Err(GLobalError::Catalog(CatalogError::MemoryLimitExceeded { .. }))
+        ));
+        let opened = catalog.open_or_create("other", || vec![1, 2, 3], DatasetConfig::default()).unwrap();
+        assert_eq!(opened.len(), 3);
+        assert_eq!(catalog.names(), vec!["other".to_string(), "small".to_string()]);
+
+        catalog.update_config("small", DatasetConfig::default()).unwrap();
+        assert_eq!(catalog.open("small").unwrap().history_compaction(), None);
+        catalog.drop_dataset("small").unwrap();
+        assert!(catalog.open("small").is_err());
+        assert_eq!(catalog.len(), 1);
+    }
+
+    #[test]
+    fn catalog_estimate_counts_heap_and_indexes() {
+        let catalog: Catalog<String> = Catalog::new().with_heap_size(|text: &String| text.capacity());
+        let data = catalog.create("texts", (0..1_000).map(|i| format!("{i:0>100}")).collect(), DatasetConfig::default()).unwrap();
+        let before = catalog.memory_usage();
+        assert!(before > 1_000 * 100);
+        data.create_field_index("text", |text: &String| text.clone()).unwrap();
+        assert!(catalog.memory_usage() > before + 1_000 * 100);
+
+        // Параллельный open_or_create одного имени: все получают один набор
+        let catalog: Arc<Catalog<u64>> = Arc::new(Catalog::new());
+        let opened: Vec<Arc<FilterData<u64>>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| catalog.open_or_create("shared", || (0..100).collect(), DatasetConfig::default()).unwrap()))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert!(opened.iter().all(|data| Arc::ptr_eq(data, &opened[0])));
+    }
+}
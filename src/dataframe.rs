@@ -0,0 +1,144 @@
+use super::{
+    errors::{FilterDataError, GLobalError},
+    index::{ExtractorFieldValue, field::FieldValue},
+    result::GlobalResult,
+};
+use ::polars::prelude::{AnyValue, Column, DataFrame, DataType, PlSmallStr, Series};
+use ordered_float::OrderedFloat;
+use std::sync::Arc;
+
+/// Строка, загруженная из DataFrame (`FilterData::from_polars`)
+///
+/// Значения хранятся в порядке колонок; null - None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameRow {
+    columns: Arc<[String]>,
+    values: Vec<Option<FieldValue>>,
+}
+
+impl FrameRow {
+    pub fn get(&self, column: &str) -> Option<&FieldValue> {
+        let pos = self.columns.iter().position(|name| name == column)?;
+        self.values[pos].as_ref()
+    }
+
+    pub fn value(&self, pos: usize) -> Option<&FieldValue> {
+        self.values.get(pos)?.as_ref()
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Option<FieldValue>] {
+        &self.values
+    }
+}
+
+pub(crate) fn polars_error(err: impl std::fmt::Display) -> GLobalError {
+    GLobalError::FilterData(FilterDataError::DataFrame { reason: err.to_string() })
+}
+
+// Тип field индекса для колонки: числа и bool - как есть,
+// остальные типы polars индексируются строкой
+pub(crate) fn column_kind(dtype: &DataType) -> DataType {
+    match dtype {
+        DataType::Boolean
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::Int128
+        | DataType::Float32
+        | DataType::Float64 => dtype.clone(),
+        _ => DataType::String,
+    }
+}
+
+fn any_to_field_value(value: AnyValue) -> Option<FieldValue> {
+    let value = match value {
+        AnyValue::Null => return None,
+        AnyValue::Boolean(v) => FieldValue::Bool(v),
+        AnyValue::String(v) => FieldValue::String(v.to_string()),
+        AnyValue::StringOwned(v) => FieldValue::String(v.to_string()),
+        AnyValue::UInt8(v) => FieldValue::U8(v),
+        AnyValue::UInt16(v) => FieldValue::U16(v),
+        AnyValue::UInt32(v) => FieldValue::U32(v),
+        AnyValue::UInt64(v) => FieldValue::U64(v),
+        AnyValue::Int8(v) => FieldValue::I8(v),
+        AnyValue::Int16(v) => FieldValue::I16(v),
+        AnyValue::Int32(v) => FieldValue::I32(v),
+        AnyValue::Int64(v) => FieldValue::I64(v),
+        AnyValue::Int128(v) => FieldValue::I128(v),
+        AnyValue::Float32(v) => FieldValue::F32(OrderedFloat(v)),
+        AnyValue::Float64(v) => FieldValue::F64(OrderedFloat(v)),
+        other => FieldValue::String(other.to_string()),
+    };
+    Some(value)
+}
+
+fn field_value_to_any(value: FieldValue) -> AnyValue<'static> {
+    match value {
+        FieldValue::U64(v) => AnyValue::UInt64(v),
+        FieldValue::I64(v) => AnyValue::Int64(v),
+        FieldValue::U32(v) => AnyValue::UInt32(v),
+        FieldValue::I32(v) => AnyValue::Int32(v),
+        FieldValue::U16(v) => AnyValue::UInt16(v),
+        FieldValue::I16(v) => AnyValue::Int16(v),
+        FieldValue::U8(v) => AnyValue::UInt8(v),
+        FieldValue::I8(v) => AnyValue::Int8(v),
+        FieldValue::I128(v) => AnyValue::Int128(v),
+        FieldValue::Usize(v) => AnyValue::UInt64(v as u64),
+        FieldValue::Isize(v) => AnyValue::Int64(v as i64),
+        FieldValue::F64(v) => AnyValue::Float64(v.0),
+        FieldValue::F32(v) => AnyValue::Float32(v.0),
+        FieldValue::Bool(v) => AnyValue::Boolean(v),
+        FieldValue::String(v) => AnyValue::StringOwned(v.into()),
+        // Без потери точности: u128 и Decimal - строкой
+        FieldValue::U128(v) => AnyValue::StringOwned(v.to_string().into()),
+        FieldValue::Decimal(v) => AnyValue::StringOwned(v.to_string().into()),
+        #[cfg(feature = "datetime")]
+        FieldValue::DateTime(v) => AnyValue::StringOwned(v.to_rfc3339().into()),
+        #[cfg(feature = "uuid")]
+        FieldValue::Uuid(v) => AnyValue::StringOwned(v.hyphenated().to_string().into()),
+        FieldValue::Bytes(v) => AnyValue::BinaryOwned(v.to_vec()),
+        FieldValue::Null => AnyValue::Null,
+    }
+}
+
+// Колонка DataFrame по extractor-у field индекса
+pub(crate) fn build_column<T>(name: &str, items: &[Arc<T>], extractor: &ExtractorFieldValue<T>) -> GlobalResult<Column> {
+    let values: Vec<AnyValue<'static>> = items
+        .iter()
+        .map(|item| field_value_to_any(extractor(item)))
+        .collect();
+    Series::from_any_values(PlSmallStr::from(name), &values, true)
+        .map(Column::from)
+        .map_err(polars_error)
+}
+
+// Строки DataFrame в порядке следования
+pub(crate) fn frame_rows(df: &DataFrame) -> Vec<FrameRow> {
+    let columns: Arc<[String]> = df
+        .get_column_names()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect();
+    let mut rows: Vec<FrameRow> = (0..df.height())
+        .map(|_| FrameRow {
+            columns: Arc::clone(&columns),
+            values: Vec::with_capacity(columns.len()),
+        })
+        .collect();
+    for column in df.get_columns() {
+        let series = column.as_materialized_series();
+        for (row, value) in rows.iter_mut().zip(series.iter()) {
+            row.values.push(any_to_field_value(value));
+        }
+    }
+    rows
+}
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 #[derive(Debug,Clone)]
 pub enum IndexFieldError {
@@ -34,9 +34,19 @@ pub enum IndexFieldError {
     OperationRange{
         field_type: String
     },
+    OperationContainsAny{
+        field_type: String
+    },
+    OperationContainsAll{
+        field_type: String
+    },
     OperationUndefinedType{
         field_type: String
-    }
+    },
+    InvalidRegex{
+        pattern: String,
+        message: String,
+    },
 }
 
 impl Display for IndexFieldError {
@@ -55,7 +65,10 @@ impl Display for IndexFieldError {
             Self::OperationIn { field_type } => write!(f,"operation failed 'in' for {field_type}"),
             Self::OperationNotIn { field_type } => write!(f,"operation failed 'not_in' for {field_type}"),
             Self::OperationRange { field_type } => write!(f,"operation failed 'range' for {field_type}"),
-            Self::OperationUndefinedType { field_type } => write!(f,"operation failed, undefined for {field_type}")
+            Self::OperationContainsAny { field_type } => write!(f,"operation failed 'contains_any' for {field_type}"),
+            Self::OperationContainsAll { field_type } => write!(f,"operation failed 'contains_all' for {field_type}"),
+            Self::OperationUndefinedType { field_type } => write!(f,"operation failed, undefined for {field_type}"),
+            Self::InvalidRegex { pattern, message } => write!(f,"invalid regex '{pattern}': {message}"),
         }
     }
 }
@@ -144,6 +157,58 @@ pub enum FilterDataError {
     ParentDataIsEmpty,
     WrongSaveDataOwned,
     WrongSaveDataIndexed,
+    AppendToIndexed,
+    RemoveFromIndexed,
+    TooManyRows{
+        rows: usize,
+        max: usize,
+    },
+    QueryVersion{
+        version: u32,
+        supported: u32,
+    },
+    // Ошибка разбора текста запроса (QueryExpr::parse)
+    QueryParse{
+        position: usize,
+        reason: String,
+    },
+    NotNumericField{
+        name: String,
+    },
+    ViewNotFound{
+        name: String,
+    },
+    SegmentNotFound{
+        name: String,
+    },
+    KeyNotSet,
+    DuplicateKey{
+        key: String,
+    },
+    DuplicateUniqueKeys{
+        name: String,
+        keys: Vec<String>,
+    },
+    Export{
+        reason: String,
+    },
+    IndexSnapshot{
+        reason: String,
+    },
+    DataFrame{
+        reason: String,
+    },
+    Overloaded{
+        permits: usize,
+        queued: usize,
+    },
+    QueueTimeout{
+        timeout: Duration,
+    },
+    // FilterDataBuilder::build без with_data / with_indices
+    BuilderMissingData,
+    // Хранилище и индексы целиком недоступны под base filter (см. trusted_access)
+    BaseFilterActive,
 }
 
 impl Display for FilterDataError {
@@ -163,6 +228,109 @@ impl Display for FilterDataError {
             Self::ParentDataIsEmpty => write!(f,"parent data is empty"),
             Self::WrongSaveDataOwned => write!(f,"can not save data owned storage!"),
             Self::WrongSaveDataIndexed => write!(f,"can not save data indexed storage!"),
+            Self::AppendToIndexed => write!(f,"can not append rows to indexed storage, push/extend require owned data"),
+            Self::RemoveFromIndexed => write!(f,"can not remove rows from indexed storage, retain/remove_indices require owned data"),
+            Self::TooManyRows { rows, max } => write!(
+                f,
+                "dataset has {rows} rows, bitmap positions address at most {max}: split it into several datasets (Federation)"
+            ),
+            Self::QueryVersion { version, supported } => write!(
+                f,
+                "saved query version {version} is not supported, max supported version: {supported}"
+            ),
+            Self::QueryParse { position, reason } => write!(f,"query parse error at {position}: {reason}"),
+            Self::NotNumericField { name } => write!(f,"field index is not numeric: {name}"),
+            Self::ViewNotFound { name } => write!(f,"view not found: {name}"),
+            Self::SegmentNotFound { name } => write!(f,"segment not found: {name}"),
+            Self::KeyNotSet => write!(f,"primary key is not set"),
+            Self::DuplicateKey { key } => write!(f,"duplicate primary key: {key}"),
+            Self::DuplicateUniqueKeys { name, keys } => write!(f,"unique index '{name}' has duplicate keys: {}", keys.join(",")),
+            Self::Export { reason } => write!(f,"export failed: {reason}"),
+            Self::IndexSnapshot { reason } => write!(f,"index snapshot failed: {reason}"),
+            Self::DataFrame { reason } => write!(f,"dataframe conversion failed: {reason}"),
+            Self::Overloaded { permits, queued } => write!(
+                f,
+                "too many heavy operations: all {permits} permits are busy, {queued} queued"
+            ),
+            Self::QueueTimeout { timeout } => write!(f,"heavy operation waited in queue longer than {timeout:?}"),
+            Self::BuilderMissingData => write!(f,"builder data is not set: call with_data() or with_indices()"),
+            Self::BaseFilterActive => write!(f,"raw storage and indexes are not available under a base filter: use trusted_access()"),
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub enum CatalogError {
+    AlreadyExists{
+        name: String,
+    },
+    NotFound{
+        name: String,
+    },
+    MemoryLimitExceeded{
+        name: String,
+        requested: usize,
+        limit: usize,
+    },
+    // Ошибка запроса в одном из наборов Federation
+    FederatedQuery{
+        name: String,
+        error: Box<GLobalError>,
+    },
+}
+
+impl Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyExists { name } => write!(f,"dataset already exists: {name}"),
+            Self::NotFound { name } => write!(f,"dataset not found: {name}"),
+            Self::MemoryLimitExceeded { name, requested, limit } => write!(
+                f,
+                "can not create dataset '{name}': memory {requested} bytes exceeds catalog limit {limit} bytes"
+            ),
+            Self::FederatedQuery { name, error } => write!(f,"federated query failed in dataset '{name}': {error}"),
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub enum GroupError {
+    PathNotFound{
+        path: String,
+    },
+    LevelNotFound{
+        path: String,
+        level: usize,
+        levels: usize,
+    },
+    LevelChanged{
+        path: String,
+        level: usize,
+        expected: String,
+        found: String,
+    },
+    CursorVersion{
+        version: u32,
+        supported: u32,
+    },
+}
+
+impl Display for GroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PathNotFound { path } => write!(f,"group path not found: {path}"),
+            Self::LevelNotFound { path, level, levels } => write!(
+                f,
+                "group '{path}' has no level {level}, stored levels: {levels}"
+            ),
+            Self::LevelChanged { path, level, expected, found } => write!(
+                f,
+                "group '{path}' level {level} changed: expected '{expected}', found '{found}'"
+            ),
+            Self::CursorVersion { version, supported } => write!(
+                f,
+                "group cursor version {version} is not supported, max supported version: {supported}"
+            ),
         }
     }
 }
@@ -171,6 +339,8 @@ impl Display for FilterDataError {
 pub enum GLobalError {
     Index(IndexError),
     FilterData(FilterDataError),
+    Catalog(CatalogError),
+    Group(GroupError),
     ParentDataIsEmpty,
 }
 
@@ -179,6 +349,8 @@ impl Display for GLobalError {
         match self {
             Self::Index(err) => write!(f, "{err}"),
             Self::FilterData(err) => write!(f,"{err}"),
+            Self::Catalog(err) => write!(f,"{err}"),
+            Self::Group(err) => write!(f,"{err}"),
             Self::ParentDataIsEmpty => write!(f, "parent data is empty"),
         }
     }
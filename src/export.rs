@@ -0,0 +1,214 @@
+use super::{
+    errors::{FilterDataError, GLobalError},
+    index::{ExtractorFieldValue, field::FieldValue, storage::Rows},
+    result::GlobalResult,
+};
+use std::{io::Write, sync::Arc};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Колонка экспорта: имя + extractor зарегистрированного field индекса
+pub(crate) type ExportColumn<T> = (String, ExtractorFieldValue<T>);
+
+fn export_error(err: impl std::fmt::Display) -> GLobalError {
+    GLobalError::FilterData(FilterDataError::Export { reason: err.to_string() })
+}
+
+// Экранирование ячейки по RFC 4180: кавычки только когда нужны
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// CSV с заголовком; возвращает количество записанных строк (без заголовка)
+pub(crate) fn write_csv<'a, T, W, I>(mut writer: W, items: I, columns: &[ExportColumn<T>]) -> GlobalResult<usize>
+where
+    T: 'a,
+    W: Write,
+    I: IntoIterator<Item = &'a Arc<T>>,
+{
+    let header = columns
+        .iter()
+        .map(|(name, _)| csv_cell(name))
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(writer, "{}", header).map_err(export_error)?;
+    let mut rows = 0;
+    for item in items {
+        let row = columns
+            .iter()
+            .map(|(_, extractor)| match extractor(item) {
+                // Null - пустая ячейка
+                FieldValue::Null => String::new(),
+                value => csv_cell(&value.to_string()),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(writer, "{}", row).map_err(export_error)?;
+        rows += 1;
+    }
+    writer.flush().map_err(export_error)?;
+    Ok(rows)
+}
+
+// Значение в JSON: числа - числами, Decimal и 128-битные вне диапазона - строкой
+#[cfg(feature = "serde")]
+fn json_value(value: FieldValue) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        FieldValue::U128(v) => u64::try_from(v).map(Value::from).unwrap_or_else(|_| Value::from(v.to_string())),
+        FieldValue::I128(v) => i64::try_from(v).map(Value::from).unwrap_or_else(|_| Value::from(v.to_string())),
+        FieldValue::U64(v) => Value::from(v),
+        FieldValue::I64(v) => Value::from(v),
+        FieldValue::U32(v) => Value::from(v),
+        FieldValue::I32(v) => Value::from(v),
+        FieldValue::U16(v) => Value::from(v),
+        FieldValue::I16(v) => Value::from(v),
+        FieldValue::U8(v) => Value::from(v),
+        FieldValue::I8(v) => Value::from(v),
+        FieldValue::Usize(v) => Value::from(v),
+        FieldValue::Isize(v) => Value::from(v),
+        // NaN/inf в JSON нет - null
+        FieldValue::F64(v) => serde_json::Number::from_f64(v.0).map(Value::Number).unwrap_or(Value::Null),
+        FieldValue::F32(v) => serde_json::Number::from_f64(v.0 as f64).map(Value::Number).unwrap_or(Value::Null),
+        FieldValue::Decimal(v) => Value::from(v.to_string()),
+        FieldValue::String(v) => Value::from(v),
+        FieldValue::Bool(v) => Value::from(v),
+        #[cfg(feature = "datetime")]
+        FieldValue::DateTime(v) => Value::from(v.to_rfc3339()),
+        #[cfg(feature = "uuid")]
+        FieldValue::Uuid(v) => Value::from(v.hyphenated().to_string()),
+        FieldValue::Bytes(v) => Value::from(v.to_string()),
+        FieldValue::Null => Value::Null,
+    }
+}
+
+// JSON Lines: объект на строку с выбранными полями
+#[cfg(feature = "serde")]
+pub(crate) fn write_json_lines<'a, T, W, I>(mut writer: W, items: I, columns: &[ExportColumn<T>]) -> GlobalResult<usize>
+where
+    T: 'a,
+    W: Write,
+    I: IntoIterator<Item = &'a Arc<T>>,
+{
+    let mut rows = 0;
+    for item in items {
+        let row: serde_json::Map<String, serde_json::Value> = columns
+            .iter()
+            .map(|(name, extractor)| (name.clone(), json_value(extractor(item))))
+            .collect();
+        serde_json::to_writer(&mut writer, &row).map_err(export_error)?;
+        writer.write_all(b"\n").map_err(export_error)?;
+        rows += 1;
+    }
+    writer.flush().map_err(export_error)?;
+    Ok(rows)
+}
+
+// JSON Lines: элементы целиком через их Serialize
+#[cfg(feature = "serde")]
+pub(crate) fn write_json_lines_serialized<'a, T, W, I>(mut writer: W, items: I) -> GlobalResult<usize>
+where
+    T: Serialize + 'a,
+    W: Write,
+    I: IntoIterator<Item = &'a Arc<T>>,
+{
+    let mut rows = 0;
+    for item in items {
+        serde_json::to_writer(&mut writer, item.as_ref()).map_err(export_error)?;
+        writer.write_all(b"\n").map_err(export_error)?;
+        rows += 1;
+    }
+    writer.flush().map_err(export_error)?;
+    Ok(rows)
+}
+
+/// Согласованный снимок выборки для экспорта
+///
+/// Фиксирует уровень, индексы строк и extractor-ы колонок в момент создания.
+/// Запись идет без блокировок FilterData: навигация и новые фильтры
+/// не ждут окончания экспорта и не меняют его содержимое.
+/// `watermark` - число строк хранилища на момент снимка; строки за ним
+/// в экспорт не попадают.
+///
+/// # Example
+///
+/// let snapshot = data.export_snapshot(&["id", "price"])?;
+/// data.filter(|order| order.price > 100)?;   // не влияет на снимок
+/// snapshot.write_csv(std::io::BufWriter::new(file))?;
+///
+pub struct ExportSnapshot<T> {
+    pub(crate) level: usize,
+    pub(crate) description: Arc<str>,
+    pub(crate) watermark: usize,
+    pub(crate) indices: Arc<Vec<usize>>,
+    pub(crate) source: Arc<Rows<T>>,
+    pub(crate) columns: Vec<ExportColumn<T>>,
+}
+
+impl<T> ExportSnapshot<T> {
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn watermark(&self) -> usize {
+        self.watermark
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn columns(&self) -> Vec<&str> {
+        self.columns.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    // Строки снимка без промежуточного Vec<Arc<T>>
+    fn rows(&self) -> impl Iterator<Item = &Arc<T>> {
+        self.indices
+            .iter()
+            .filter(|&&idx| idx < self.watermark)
+            .filter_map(|&idx| self.source.get(idx))
+    }
+
+    pub fn write_csv<W: Write>(&self, writer: W) -> GlobalResult<usize> {
+        write_csv(writer, self.rows(), &self.columns)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn write_json_lines<W: Write>(&self, writer: W) -> GlobalResult<usize> {
+        write_json_lines(writer, self.rows(), &self.columns)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn write_json_lines_serialized<W: Write>(&self, writer: W) -> GlobalResult<usize>
+    where
+        T: Serialize,
+    {
+        write_json_lines_serialized(writer, self.rows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_cell_quotes_only_when_needed() {
+        assert_eq!(csv_cell("plain"), "plain");
+        assert_eq!(csv_cell("a,b"), "\"a,b\"");
+        assert_eq!(csv_cell("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
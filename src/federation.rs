@@ -0,0 +1,209 @@
+use super::{
+    catalog::Catalog,
+    errors::{CatalogError, GLobalError},
+    filter::FilterData,
+    index::{bit::Op, field::{FieldOperation, FieldValue}},
+    model::ValueAggregate,
+    query::QueryExpr,
+    result::GlobalResult,
+    result_set::ResultSet,
+};
+use rayon::prelude::*;
+use std::sync::Arc;
+
+/// Запрос сразу по нескольким наборам с общей схемой индексов
+///
+/// Запрос выполняется в каждом наборе отдельно (параллельно), без склейки
+/// данных в один большой набор. Drill-down уровни наборов не меняются:
+/// запрос идет по исходному уровню каждого набора, как `query_result`.
+///
+/// # Example
+///
+/// let logs = Federation::named(vec![("2024-05-01", day1), ("2024-05-02", day2)]);
+/// let errors = logs.filter_by_field_ops("level", &[(FieldOperation::eq("error"), Op::And)])?;
+/// println!("{} errors", errors.len());
+/// for (day, count) in errors.breakdown() {
+///     println!("{day}: {count}");
+/// }
+/// let first_page = errors.page(0, 50);
+///
+pub struct Federation<T>
+where
+    T: Send + Sync + 'static,
+{
+    members: Vec<(String, Arc<FilterData<T>>)>,
+}
+
+impl<T> Federation<T>
+where
+    T: Send + Sync + 'static,
+{
+    // Имена наборов - их позиции: "0", "1", ...
+    pub fn new(datasets: Vec<Arc<FilterData<T>>>) -> Self {
+        Self {
+            members: datasets
+                .into_iter()
+                .enumerate()
+                .map(|(position, data)| (position.to_string(), data))
+                .collect(),
+        }
+    }
+
+    pub fn named(datasets: Vec<(&str, Arc<FilterData<T>>)>) -> Self {
+        Self {
+            members: datasets
+                .into_iter()
+                .map(|(name, data)| (name.to_string(), data))
+                .collect(),
+        }
+    }
+
+    pub fn from_catalog(catalog: &Catalog<T>, names: &[&str]) -> GlobalResult<Self> {
+        let members = names
+            .iter()
+            .map(|name| Ok((name.to_string(), catalog.open(name)?)))
+            .collect::<GlobalResult<Vec<_>>>()?;
+        Ok(Self { members })
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.members.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn dataset(&self, name: &str) -> Option<&Arc<FilterData<T>>> {
+        self.members
+            .iter()
+            .find(|(member, _)| member == name)
+            .map(|(_, data)| data)
+    }
+
+    pub fn filter_by_field_ops(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<FederatedResult<T>> {
+        self.query(&QueryExpr::field(name, operations.to_vec()))
+    }
+
+    pub fn query(&self, expr: &QueryExpr) -> GlobalResult<FederatedResult<T>> {
+        let parts = self
+            .members
+            .par_iter()
+            .map(|(name, data)| {
+                data.query_result(expr)
+                    .map(|result| (name.clone(), result))
+                    .map_err(|error| {
+                        GLobalError::Catalog(CatalogError::FederatedQuery {
+                            name: name.clone(),
+                            error: Box::new(error),
+                        })
+                    })
+            })
+            .collect::<GlobalResult<Vec<_>>>()?;
+        Ok(FederatedResult { parts })
+    }
+}
+
+/// Результат `Federation::query`: ResultSet каждого набора в порядке федерации
+pub struct FederatedResult<T> {
+    parts: Vec<(String, ResultSet<T>)>,
+}
+
+impl<T> FederatedResult<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn len(&self) -> usize {
+        self.parts.iter().map(|(_, result)| result.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.iter().all(|(_, result)| result.is_empty())
+    }
+
+    // Строк по каждому набору
+    pub fn breakdown(&self) -> Vec<(&str, usize)> {
+        self.parts
+            .iter()
+            .map(|(name, result)| (name.as_str(), result.len()))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ResultSet<T>> {
+        self.parts
+            .iter()
+            .find(|(member, _)| member == name)
+            .map(|(_, result)| result)
+    }
+
+    pub fn parts(&self) -> &[(String, ResultSet<T>)] {
+        &self.parts
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<T>)> + '_ {
+        self.parts
+            .iter()
+            .flat_map(|(name, result)| result.iter().map(move |item| (name.as_str(), item)))
+    }
+
+    // Сквозная страница: наборы идут подряд в порядке федерации
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<Arc<T>> {
+        self.page_with_names(offset, limit)
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    pub fn page_with_names(&self, mut offset: usize, limit: usize) -> Vec<(&str, Arc<T>)> {
+        let mut page = Vec::with_capacity(limit.min(self.len()));
+        for (name, result) in &self.parts {
+            if page.len() == limit {
+                break;
+            }
+            // Наборы целиком до offset пропускаются без обхода строк
+            if offset >= result.len() {
+                offset -= result.len();
+                continue;
+            }
+            page.extend(
+                result
+                    .page(offset, limit - page.len())
+                    .into_iter()
+                    .map(|item| (name.as_str(), item)),
+            );
+            offset = 0;
+        }
+        page
+    }
+
+    pub fn aggregate<V, F>(&self, extractor: F) -> ValueAggregate
+    where
+        F: Fn(&T) -> V + Send + Sync,
+        V: Into<FieldValue>,
+    {
+        self.parts
+            .iter()
+            .map(|(_, result)| result.aggregate(&extractor))
+            .fold(ValueAggregate::default(), ValueAggregate::merge)
+    }
+
+    // Агрегаты по каждому набору
+    pub fn aggregate_breakdown<V, F>(&self, extractor: F) -> Vec<(&str, ValueAggregate)>
+    where
+        F: Fn(&T) -> V + Send + Sync,
+        V: Into<FieldValue>,
+    {
+        self.parts
+            .iter()
+            .map(|(name, result)| (name.as_str(), result.aggregate(&extractor)))
+            .collect()
+    }
+}
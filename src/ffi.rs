@@ -0,0 +1,458 @@
+// Минимальный C API (feature = "ffi")
+//
+// Данные передаются колонками, запрос - JSON `QueryExpr` (формат serde).
+// Функции возвращают 0 при успехе и -1 при ошибке; текст ошибки -
+// `tm_last_error()`, действителен до следующего вызова в том же потоке.
+// Каждый вызов сбрасывает ошибку, поэтому после успеха `tm_last_error()`
+// возвращает NULL. Паника внутри вызова не пересекает границу FFI: она
+// превращается в ошибку. Объявления для C - include/tree_man.h.
+//
+// Крейт собирается как rlib; библиотека для C собирается явно:
+// cargo rustc --release --features ffi --crate-type cdylib   (или staticlib)
+//
+// # Example (C)
+//
+// TmColumns *cols = tm_columns_new();
+// tm_columns_add_i64(cols, "price", prices, n);
+// TmData *data = tm_data_from_columns(cols);
+// tm_data_create_index(data, "price");
+// tm_data_query_json(data, "{\"Field\":{\"name\":\"price\",\"operations\":[[{\"Gt\":{\"I64\":100}},\"And\"]]}}");
+// size_t len;
+// uint64_t *ids = tm_data_result_ids(data, &len);
+// tm_ids_free(ids, len);
+// tm_data_free(data);
+
+use super::{
+    filter::FilterData,
+    index::field::FieldValue,
+    query::QueryExpr,
+};
+use ordered_float::OrderedFloat;
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+pub const TM_OK: i32 = 0;
+pub const TM_ERROR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl ToString) -> i32 {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    TM_ERROR
+}
+
+fn clear_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+// Тело extern "C" функции: сброс ошибки + перехват паники
+fn guard<R>(fallback: R, body: impl FnOnce() -> R) -> R {
+    clear_error();
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            set_error(format!("panic: {}", panic_message(payload.as_ref())));
+            fallback
+        }
+    }
+}
+
+// Тип колонки определяет тип field индекса
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    I64,
+    F64,
+    Str,
+}
+
+struct Column {
+    name: String,
+    kind: ColumnKind,
+    values: Vec<FieldValue>,
+}
+
+/// Колонки до построения данных
+pub struct TmColumns {
+    columns: Vec<Column>,
+}
+
+/// Строка данных: значения в порядке колонок
+pub struct TmRow {
+    values: Vec<FieldValue>,
+}
+
+/// Данные + схема колонок
+pub struct TmData {
+    data: FilterData<TmRow>,
+    schema: Vec<(String, ColumnKind)>,
+}
+
+impl TmColumns {
+    fn push(&mut self, name: String, kind: ColumnKind, values: Vec<FieldValue>) -> i32 {
+        if let Some(first) = self.columns.first()
+            && first.values.len() != values.len()
+        {
+            return set_error(format!(
+                "column '{}' has {} values, expected {}",
+                name,
+                values.len(),
+                first.values.len()
+            ));
+        }
+        if self.columns.iter().any(|column| column.name == name) {
+            return set_error(format!("column '{}' already exists", name));
+        }
+        self.columns.push(Column { name, kind, values });
+        TM_OK
+    }
+}
+
+unsafe fn read_str<'a>(value: *const c_char) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err("null string pointer".to_string());
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|err| err.to_string())
+}
+
+unsafe fn read_slice<'a, V>(values: *const V, len: usize) -> Result<&'a [V], String> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if values.is_null() {
+        return Err("null buffer pointer".to_string());
+    }
+    Ok(unsafe { std::slice::from_raw_parts(values, len) })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tm_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map(|message| message.as_ptr())
+            .unwrap_or(ptr::null())
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tm_columns_new() -> *mut TmColumns {
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(TmColumns { columns: Vec::new() }))
+    })
+}
+
+/// # Safety
+///
+/// `columns` - указатель из `tm_columns_new`, `name` - C строка,
+/// `values` - буфер из `len` элементов.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_columns_add_i64(columns: *mut TmColumns, name: *const c_char, values: *const i64, len: usize) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(columns) = (unsafe { columns.as_mut() }) else {
+            return set_error("null columns pointer");
+        };
+        let (name, values) = match unsafe { (read_str(name), read_slice(values, len)) } {
+            (Ok(name), Ok(values)) => (name, values),
+            (Err(err), _) | (_, Err(err)) => return set_error(err),
+        };
+        columns.push(name.to_string(), ColumnKind::I64, values.iter().map(|&value| FieldValue::I64(value)).collect())
+    })
+}
+
+/// # Safety
+///
+/// См. `tm_columns_add_i64`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_columns_add_f64(columns: *mut TmColumns, name: *const c_char, values: *const f64, len: usize) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(columns) = (unsafe { columns.as_mut() }) else {
+            return set_error("null columns pointer");
+        };
+        let (name, values) = match unsafe { (read_str(name), read_slice(values, len)) } {
+            (Ok(name), Ok(values)) => (name, values),
+            (Err(err), _) | (_, Err(err)) => return set_error(err),
+        };
+        columns.push(name.to_string(), ColumnKind::F64, values.iter().map(|&value| FieldValue::F64(OrderedFloat(value))).collect())
+    })
+}
+
+/// # Safety
+///
+/// `values` - массив из `len` указателей на C строки (UTF-8).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_columns_add_str(columns: *mut TmColumns, name: *const c_char, values: *const *const c_char, len: usize) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(columns) = (unsafe { columns.as_mut() }) else {
+            return set_error("null columns pointer");
+        };
+        let (name, values) = match unsafe { (read_str(name), read_slice(values, len)) } {
+            (Ok(name), Ok(values)) => (name, values),
+            (Err(err), _) | (_, Err(err)) => return set_error(err),
+        };
+        let mut strings = Vec::with_capacity(values.len());
+        for &value in values {
+            match unsafe { read_str(value) } {
+                Ok(value) => strings.push(FieldValue::String(value.to_string())),
+                Err(err) => return set_error(err),
+            }
+        }
+        columns.push(name.to_string(), ColumnKind::Str, strings)
+    })
+}
+
+/// # Safety
+///
+/// `columns` - указатель из `tm_columns_new`; после вызова он освобожден.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_columns_free(columns: *mut TmColumns) {
+    guard((), || {
+        if !columns.is_null() {
+            drop(unsafe { Box::from_raw(columns) });
+        }
+    })
+}
+
+/// Построить данные из колонок (колонки освобождаются)
+///
+/// # Safety
+///
+/// `columns` - указатель из `tm_columns_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_from_columns(columns: *mut TmColumns) -> *mut TmData {
+    guard(ptr::null_mut(), || {
+        if columns.is_null() {
+            set_error("null columns pointer");
+            return ptr::null_mut();
+        }
+        let columns = unsafe { Box::from_raw(columns) }.columns;
+        let schema = columns.iter().map(|column| (column.name.clone(), column.kind)).collect();
+        let row_count = columns.first().map(|column| column.values.len()).unwrap_or_default();
+        let mut rows: Vec<TmRow> = (0..row_count)
+            .map(|_| TmRow { values: Vec::with_capacity(columns.len()) })
+            .collect();
+        for column in columns {
+            for (row, value) in rows.iter_mut().zip(column.values) {
+                row.values.push(value);
+            }
+        }
+        Box::into_raw(Box::new(TmData {
+            data: FilterData::from_vec(rows),
+            schema,
+        }))
+    })
+}
+
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`; после вызова он освобожден.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_free(data: *mut TmData) {
+    guard((), || {
+        if !data.is_null() {
+            drop(unsafe { Box::from_raw(data) });
+        }
+    })
+}
+
+/// Field индекс по колонке с тем же именем
+///
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`, `name` - C строка.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_create_index(data: *mut TmData, name: *const c_char) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(data) = (unsafe { data.as_ref() }) else {
+            return set_error("null data pointer");
+        };
+        let name = match unsafe { read_str(name) } {
+            Ok(name) => name,
+            Err(err) => return set_error(err),
+        };
+        let Some(pos) = data.schema.iter().position(|(column, _)| column == name) else {
+            return set_error(format!("column '{}' not found", name));
+        };
+        let result = match data.schema[pos].1 {
+            ColumnKind::I64 => data.data.create_field_index(name, move |row: &TmRow| match &row.values[pos] {
+                FieldValue::I64(value) => *value,
+                _ => 0,
+            }),
+            ColumnKind::F64 => data.data.create_field_index(name, move |row: &TmRow| match &row.values[pos] {
+                FieldValue::F64(value) => *value,
+                _ => OrderedFloat(0.0),
+            }),
+            ColumnKind::Str => data.data.create_field_index(name, move |row: &TmRow| match &row.values[pos] {
+                FieldValue::String(value) => value.clone(),
+                _ => String::new(),
+            }),
+        };
+        match result {
+            Ok(_) => TM_OK,
+            Err(err) => set_error(err),
+        }
+    })
+}
+
+/// Выполнить JSON запрос над текущей выборкой (новый уровень drill-down)
+///
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`, `query` - C строка.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_query_json(data: *mut TmData, query: *const c_char) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(data) = (unsafe { data.as_ref() }) else {
+            return set_error("null data pointer");
+        };
+        let expr: QueryExpr = match unsafe { read_str(query) }.and_then(|query| serde_json::from_str(query).map_err(|err| err.to_string())) {
+            Ok(expr) => expr,
+            Err(err) => return set_error(err),
+        };
+        match data.data.filter_query(&expr) {
+            Ok(_) => TM_OK,
+            Err(err) => set_error(err),
+        }
+    })
+}
+
+/// Вернуться к исходным данным
+///
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_reset(data: *mut TmData) -> i32 {
+    guard(TM_ERROR, || {
+        let Some(data) = (unsafe { data.as_ref() }) else {
+            return set_error("null data pointer");
+        };
+        data.data.reset_to_source();
+        TM_OK
+    })
+}
+
+/// Количество строк текущей выборки
+///
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_len(data: *const TmData) -> usize {
+    guard(0, || {
+        unsafe { data.as_ref() }
+            .map(|data| data.data.len())
+            .unwrap_or_default()
+    })
+}
+
+/// Номера строк текущей выборки (позиции в исходных колонках)
+///
+/// Результат освобождается через `tm_ids_free` с тем же `len`.
+/// Для пустой выборки - NULL и `len = 0`.
+///
+/// # Safety
+///
+/// `data` - указатель из `tm_data_from_columns`, `len` - куда записать длину.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_data_result_ids(data: *const TmData, len: *mut usize) -> *mut u64 {
+    guard(ptr::null_mut(), || {
+        let (Some(data), Some(len)) = (unsafe { data.as_ref() }, unsafe { len.as_mut() }) else {
+            set_error("null pointer");
+            return ptr::null_mut();
+        };
+        let ids: Box<[u64]> = data.data
+            .current_indices()
+            .iter()
+            .map(|&idx| idx as u64)
+            .collect();
+        *len = ids.len();
+        if ids.is_empty() {
+            return ptr::null_mut();
+        }
+        Box::into_raw(ids) as *mut u64
+    })
+}
+
+/// # Safety
+///
+/// `ids` и `len` - результат `tm_data_result_ids`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tm_ids_free(ids: *mut u64, len: usize) {
+    guard((), || {
+        if !ids.is_null() {
+            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ids, len)) });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_query_returns_row_ids() {
+        let prices = [5i64, 150, 20, 300];
+        let names: Vec<CString> = ["a", "b", "c", "d"].iter().map(|name| CString::new(*name).unwrap()).collect();
+        let name_ptrs: Vec<*const c_char> = names.iter().map(|name| name.as_ptr()).collect();
+        unsafe {
+            let columns = tm_columns_new();
+            assert_eq!(tm_columns_add_i64(columns, c"price".as_ptr(), prices.as_ptr(), prices.len()), TM_OK);
+            assert_eq!(tm_columns_add_str(columns, c"name".as_ptr(), name_ptrs.as_ptr(), name_ptrs.len()), TM_OK);
+            assert_eq!(tm_columns_add_i64(columns, c"short".as_ptr(), prices.as_ptr(), 2), TM_ERROR);
+            let data = tm_data_from_columns(columns);
+            assert_eq!(tm_data_create_index(data, c"price".as_ptr()), TM_OK);
+            assert_eq!(tm_data_create_index(data, c"missing".as_ptr()), TM_ERROR);
+            assert!(!tm_last_error().is_null());
+
+            let query = c"{\"Field\":{\"name\":\"price\",\"operations\":[[{\"Gt\":{\"I64\":100}},\"And\"]]}}";
+            assert_eq!(tm_data_query_json(data, query.as_ptr()), TM_OK);
+            let mut len = 0;
+            let ids = tm_data_result_ids(data, &mut len);
+            assert_eq!(std::slice::from_raw_parts(ids, len), &[1, 3]);
+            tm_ids_free(ids, len);
+
+            assert_eq!(tm_data_query_json(data, c"not json".as_ptr()), TM_ERROR);
+            assert_eq!(tm_data_reset(data), TM_OK);
+            assert!(tm_last_error().is_null());
+
+            assert_eq!(tm_data_len(data), 4);
+            tm_data_free(data);
+
+            let empty = tm_data_from_columns(tm_columns_new());
+            let mut len = 7;
+            let ids = tm_data_result_ids(empty, &mut len);
+            assert!(ids.is_null());
+            assert_eq!(len, 0);
+            tm_ids_free(ids, len);
+            tm_data_free(empty);
+        }
+    }
+
+    #[test]
+    fn ffi_guard_turns_panic_into_error() {
+        let result = guard(TM_ERROR, || -> i32 { panic!("boom") });
+        assert_eq!(result, TM_ERROR);
+        let message = unsafe { CStr::from_ptr(tm_last_error()) }.to_str().unwrap();
+        assert_eq!(message, "panic: boom");
+        assert_eq!(guard(TM_ERROR, || TM_OK), TM_OK);
+        assert!(tm_last_error().is_null());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::index::field::IndexFieldEnum;
 
 use super::{
+    catalog::DatasetConfig,
     errors::{
         GLobalError,
         IndexError,
@@ -11,37 +12,69 @@ use super::{
         INDEX_TEXT,
         CompatibilityAction as IndexCompatibilityAction,
         ExtractorFieldValue,
+        IndexBuilder,
+        IndexRecipe,
         IndexType,
-        bit::Op,
+        background::{IndexBuildStatus, IndexHandle, PendingIndex},
+        bit::{MAX_INDEXED_ROWS, Op},
+        cache::{CacheLookup, ResultCache, ResultCacheStats},
+        enums::IndexEnum,
+        batch::{self, BatchColumn, FieldColumn},
+        integrity::{self, INTEGRITY_SAMPLE_SIZE, IndexFingerprint},
+        keys::KeyTable,
+        limiter::{ConcurrencyLimiter, HEAVY_FILTER_THRESHOLD, LimiterPermit, LimiterStats, QueuePolicy},
+        logic::{NullMode, TriBitmap},
+        normalize::{DecimalIndexOptions, FloatPolicy, StringIndexOptions},
+        pool,
+        range::{RangeBucket, RangeBucketCache},
+        sketch::{TopValue, TopValuesSketch},
+        stats::{ANALYZE_SAMPLE_SIZE, DEFAULT_HISTOGRAM_BUCKETS, ColumnGroupStats, FieldStats, IndexUsage, PlannerFeedback, PlannerHints, QueryHint, ValueSums},
         field::{
+            CARDINALITY_RATIO_LOW_THRESHOLD,
+            CmpOp,
+            CompiledOperations,
             FieldValue,
             IntoIndexFieldEnum,
             IndexField,
+            combine_selectivity,
+            IndexKind,
             FieldOperation,
         },
-        storage::DataStorage,
-        text::{TextIndex,TextIndexStats},
+        storage::{DataStorage, LevelIndices, Rows},
+        text::{NgramDictionary,TextIndex,TextIndexOptions,TextIndexStats},
     },
-    model::MemoryStats,
+    export::{self, ExportColumn, ExportSnapshot},
+    group::aggregate::AggregateKind,
+    model::{CohortDiff, DataQualityReport, FieldPlan, FieldQuality, IndexDiff, IndexMemoryStats, IndexReport, IndexSummary, LevelMeta, MemoryStats, OperationPlan, PlanPath, PlannerStats, QueryPlan, ValueAggregate, WarmUpStats},
+    query::{IndexRef, MaterializedView, QueryExpr, QueryStep, SAVED_QUERY_VERSION, SavedQuery, SortOrder, Step},
     result::{
         IndexResult,
         GlobalResult
     },
+    result_set::ResultSet,
 };
+#[cfg(feature = "serde")]
+use super::index::persist::{INDEX_SNAPSHOT_VERSION, IndexSnapshot, SavedIndex};
 use arc_swap::ArcSwap;
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 use rayon::prelude::*;
 use roaring::RoaringBitmap;
+use ordered_float::OrderedFloat;
+use rust_decimal::Decimal;
 use std::{
+    borrow::Cow,
     cmp::{Ord,PartialOrd},
+    collections::BTreeMap,
     fmt::Display,
     hash::Hash,
     marker::PhantomData,
+    ops::Range,
     sync::{
-        Arc,
-        atomic::{AtomicUsize, Ordering}
+        Arc, OnceLock, Weak,
+        atomic::{AtomicU64, AtomicUsize, Ordering}
     },
+    time::Instant,
 };
 
 
@@ -49,6 +82,21 @@ const MAX_HISTORY: usize = 50;
 const MATERIALIZATION_THRESHOLD: usize = 50_000;
 const SMALL_DATASET_THRESHOLD: usize = 1000;
 const SELECTIVITY_THRESHOLD: f64 = 0.1;
+// aggregate_field по bitmap-ам, если значений индекса хотя бы в столько раз меньше строк выборки
+const AGGREGATE_INDEX_RATIO: usize = 4;
+// До скольких различных значений планировщик сам считает field_stats
+const PLANNER_STATS_MAX_UNIQUE: usize = 1 << 20;
+// С какого размера выборки to_owned_vec клонирует параллельно
+const PARALLEL_CLONE_THRESHOLD: usize = 10_000;
+// Во сколько раз фактическая кардинальность может превысить оценку,
+// прежде чем index path переключится на predicate path
+const ADAPTIVE_MISESTIMATE_FACTOR: f64 = 4.0;
+// Сколько скомпилированных цепочек операций держит кеш predicate path
+const COMPILED_OPERATIONS_CACHE_LIMIT: usize = 256;
+// Сколько строк проверяется для выбора порядка полей в predicate path
+const PREDICATE_ORDER_SAMPLE_SIZE: usize = 256;
+// Относительная стоимость строкового extractor-а (аллокация + сравнение строк)
+const STRING_EXTRACTOR_COST: f64 = 4.0;
 
 // FilterData
 
@@ -57,17 +105,188 @@ where
     T: Send + Sync + 'static,
 {
     storage: DataStorage<T>,
-    level_info: ArcSwap<Vec<Arc<str>>>,
+    level_meta: ArcSwap<Vec<Arc<LevelMeta>>>,
     current_level: Arc<AtomicUsize>,
-    indexes: DashMap<String, Arc<IndexType<T>>>,
+    indexes: Arc<DashMap<String, Arc<IndexType<T>>>>,
+    index_builds: Arc<DashMap<String, PendingIndex<T>>>,
+    index_recipes: Arc<DashMap<String, IndexRecipe<T>>>,
+    index_version_seq: Arc<AtomicU64>,
+    column_stats: DashMap<String, Arc<ColumnGroupStats>>,
+    planner_feedback: DashMap<String, PlannerFeedback>,
+    planner_hints: DashMap<String, PlannerHints>,
+    // Нормализация строковых field индексов (create_field_index_with_options)
+    string_options: DashMap<String, StringIndexOptions>,
+    // Масштаб Decimal field индексов (create_decimal_index_with_scale)
+    decimal_options: DashMap<String, DecimalIndexOptions>,
+    // Индексы из load_indexes, ожидающие create_* с тем же именем
+    #[cfg(feature = "serde")]
+    saved_indexes: DashMap<String, SavedIndex>,
+    planner_stats: RwLock<PlannerStats>,
+    // LRU кеш bitmap-ов field запросов (enable_result_cache)
+    result_cache: Mutex<Option<ResultCache>>,
+    null_mode: RwLock<NullMode>,
+    // Сколько последних уровней не сворачивать в checkpoint (None - без сжатия)
+    history_compaction: RwLock<Option<usize>>,
+    // Строки новых уровней всегда в порядке хранилища (по возрастанию индекса)
+    deterministic_order: RwLock<bool>,
+    // Уровни истории хранятся bitmap-ом (см. set_bitmap_levels)
+    bitmap_levels: RwLock<bool>,
+    // Ограничение параллельных тяжелых операций (None - без ограничения)
+    concurrency_limiter: RwLock<Option<Arc<ConcurrencyLimiter>>>,
+    range_buckets: DashMap<String, Arc<RangeBucketCache>>,
+    // Sketch-и частых значений для колонок без полного индекса
+    top_sketches: DashMap<String, Arc<TopValuesSketch>>,
+    // Предрассчитанные числовые значения для sum/avg через bitmap-ы
+    value_sums: DashMap<String, Arc<ValueSums>>,
+    // Статистика значений field индексов (field_stats), привязана к версии индекса
+    field_stats: DashMap<String, Arc<FieldStats>>,
+    // Материализованные представления (сохраненные запросы с результатом)
+    views: DashMap<String, Arc<MaterializedView>>,
+    // Вычисляемые (виртуальные) поля, доступные по имени как field индексы
+    computed_fields: DashMap<String, ComputedField<T>>,
+    // Сегменты - именованные запросы, вычисляются при применении
+    segments: DashMap<String, SavedQuery>,
+    // Таблица первичных ключей (with_key): строка хранилища <-> стабильный ключ
+    key_table: ArcSwap<Option<Arc<KeyTable<T>>>>,
+    // Уникальные индексы (create_unique_index): значение -> строка хранилища
+    unique_indexes: DashMap<String, Arc<KeyTable<T>>>,
+    // Кеш скомпилированных цепочек операций для predicate path
+    compiled_operations: DashMap<Vec<(FieldOperation, Op)>, CompiledOperations>,
+    // Словарь n-грамм, общий для text индексов дерева групп
+    ngram_dictionary: Arc<NgramDictionary>,
+    // Постоянная маска (row-level security): исходный уровень = base ∩ данные
+    base_filter: ArcSwap<Option<Arc<BaseFilter<T>>>>,
     source_indices_mask: ArcSwap<Option<Arc<RoaringBitmap>>>,
-    write_lock: RwLock<()>,
+    // Bitmap всех строк исходного уровня (предрассчитывается в warm_up_with_universe)
+    universe_bitmap: ArcSwap<Option<Arc<RoaringBitmap>>>,
+    // Общий с фоновыми построениями: подмена индекса не пересекается с push / retain
+    write_lock: Arc<RwLock<()>>,
+}
+
+// Исходный уровень под base filter
+struct BaseFilter<T> {
+    bitmap: Arc<RoaringBitmap>,
+    indices: Arc<Vec<usize>>,
+    // Для Owned - элементы исходного уровня (для Indexed None)
+    items: Option<Arc<Vec<Arc<T>>>>,
 }
 
 struct FilterResult {
     bitmap: RoaringBitmap,
 }
 
+// Описание нового уровня: строка для level_name + структура операции
+struct LevelStep {
+    description: String,
+    operation: Option<QueryStep>,
+}
+
+impl LevelStep {
+    fn new(description: String, operation: QueryStep) -> Self {
+        Self {
+            description,
+            operation: Some(operation),
+        }
+    }
+}
+
+// Поле запроса для планировщика: имя, индекс, операции
+type FieldPlanEntry<'a> = (&'a str, &'a IndexFieldEnum, &'a [(FieldOperation, Op)]);
+
+// Выбор пути выполнения планировщиком (need_to_use_index / explain_field_ops)
+struct PathDecision<'a> {
+    use_index: bool,
+    reason: &'static str,
+    // Поле причины отказа от индекса (пусто, если причина общая)
+    field: &'a str,
+    threshold: Option<f64>,
+}
+
+impl<'a> PathDecision<'a> {
+    fn index(reason: &'static str) -> Self {
+        Self { use_index: true, reason, field: "", threshold: None }
+    }
+
+    fn predicate(reason: &'static str, field: &'a str, threshold: Option<f64>) -> Self {
+        Self { use_index: false, reason, field, threshold }
+    }
+}
+// Поле запроса для predicate path: extractor, операции
+type FieldPredicateEntry<'a, T> = (&'a ExtractorFieldValue<T>, &'a [(FieldOperation, Op)]);
+// Дописанный индекс (extend): адрес прежнего экземпляра, новый, отпечаток данных и хеши его чанков
+type AppendedIndex<T> = (*const IndexType<T>, Arc<IndexType<T>>, IndexFingerprint, Vec<u64>);
+// Индекс без удаленных строк (remove_indices): прежний экземпляр, новый
+type ReplacedIndex<T> = (Arc<IndexType<T>>, Arc<IndexType<T>>);
+// Таблица ключей и ключи строк, которые push в нее допишет
+type NewTableKeys<T> = (Arc<KeyTable<T>>, Vec<FieldValue>);
+
+// Структуры FilterData, которые нужны потоку фонового построения
+struct BuildShared<T>
+where
+    T: Send + Sync + 'static,
+{
+    indexes: Arc<DashMap<String, Arc<IndexType<T>>>>,
+    index_recipes: Arc<DashMap<String, IndexRecipe<T>>>,
+    index_builds: Arc<DashMap<String, PendingIndex<T>>>,
+    write_lock: Arc<RwLock<()>>,
+}
+
+// Индекс фонового построения: позиции 0..rows и хеши чанков для fingerprint-а
+struct BuiltIndex<T>
+where
+    T: Send + Sync + 'static,
+{
+    index: IndexType<T>,
+    chunk_hashes: Vec<u64>,
+    rows: usize,
+}
+
+impl<T> BuiltIndex<T>
+where
+    T: Send + Sync + 'static,
+{
+    // Дописать строки rows[self.rows..] и сбросить удаленные строки.
+    // Удаление идемпотентно, поэтому уже учтенные строки не мешают
+    fn catch_up(
+        &mut self,
+        rows: Option<&Rows<T>>,
+        removed: Option<&RoaringBitmap>,
+        builder: &IndexBuilder<T>,
+        content: &ExtractorFieldValue<T>,
+    ) {
+        if let Some(rows) = rows.filter(|rows| rows.len() > self.rows) {
+            let values: Vec<FieldValue> = rows[self.rows..].iter().map(|item| content(item)).collect();
+            if !self.index.append_values(&values) {
+                self.index = builder(rows);
+            }
+            IndexFingerprint::extend_chunk_hashes(&mut self.chunk_hashes, rows, self.rows, content);
+            self.rows = rows.len();
+        }
+        if let Some(removed) = removed.filter(|removed| !removed.is_empty()) {
+            self.index = self.index.without(removed);
+        }
+    }
+}
+
+// Pending - готового индекса нет (строится в фоне или computed поле):
+// фильтрация идет extractor-ом через predicate path
+enum ResolvedField<T>
+where
+    T: Send + Sync + 'static,
+{
+    Index(Arc<IndexType<T>>),
+    Pending(ExtractorFieldValue<T>),
+}
+
+// Вычисляемое поле: extractor + построитель индекса для материализации
+struct ComputedField<T>
+where
+    T: Send + Sync + 'static,
+{
+    extractor: ExtractorFieldValue<T>,
+    builder: IndexBuilder<T>,
+}
+
 impl<T> FilterData<T>
 where
     T: Send + Sync + 'static,
@@ -79,81 +298,487 @@ where
         
         let arc_items = match len {
             0..=499 => {
-                Arc::new(items.into_iter().map(Arc::new).collect())
+                items.into_iter().map(Arc::new).collect()
             }
             500..=50_000 => {
                 let mut arcs = Vec::with_capacity(len);
                 arcs.par_extend(items.into_par_iter().map(Arc::new));
-                Arc::new(arcs)
+                arcs
             }
             _ => {
-                Arc::new(
-                    items
-                        .into_par_iter()
-                        .with_min_len(10_000)
-                        .map(Arc::new)
-                        .collect()
-                )
+                items
+                    .into_par_iter()
+                    .with_min_len(10_000)
+                    .map(Arc::new)
+                    .collect()
             }
         };
-        let initial_indices: Vec<usize> = (0..arc_items.len()).collect();
-        let initial_indices_arc = Arc::new(initial_indices);
-        
-        Self {
-            storage: DataStorage::Owned {
-                source: Arc::clone(&arc_items),
-                current_indices: ArcSwap::new(initial_indices_arc.clone()),
-                current_cache: ArcSwap::new(Arc::new(None)),
-                full_indices: initial_indices_arc,
-                levels: ArcSwap::from_pointee(vec![Arc::clone(&arc_items)]),
-                level_indices:  ArcSwap::from_pointee(vec![Arc::new((0..arc_items.len()).collect())]),
-            },
-            level_info: ArcSwap::from_pointee(vec![Arc::from("Source")]),
-            current_level: Arc::new(AtomicUsize::new(0)),
-            indexes: DashMap::new(),
-            source_indices_mask: ArcSwap::from_pointee(None),
-            write_lock: RwLock::new(()),
-        }
+        Self::with_storage(Self::owned_storage(arc_items))
     }
 
     pub fn from_vec_arc_value(items: Vec<Arc<T>>) -> Self {
-        let arc_items = Arc::new(items);
-        let initial_indices: Vec<usize> = (0..arc_items.len()).collect();
-        let initial_indices_arc = Arc::new(initial_indices);
-        Self {
-            storage: DataStorage::Owned {
-                source: Arc::clone(&arc_items),
-                current_indices: ArcSwap::new(initial_indices_arc.clone()),
-                current_cache: ArcSwap::new(Arc::new(None)),
-                full_indices: initial_indices_arc,
-                levels: ArcSwap::from_pointee(vec![Arc::clone(&arc_items)]),
-                level_indices: ArcSwap::from_pointee(vec![Arc::new((0..arc_items.len()).collect())]),
-            },
-            level_info: ArcSwap::from_pointee(vec![Arc::from("Source")]),
-            current_level: Arc::new(AtomicUsize::new(0)),
-            indexes: DashMap::new(),
-            source_indices_mask: ArcSwap::from_pointee(None),
-            write_lock: RwLock::new(()),
-        }
+        Self::with_storage(Self::owned_storage(items))
     }
     
-    pub fn from_indices(parent_data: &Arc<Vec<Arc<T>>>, indices: Vec<usize>) -> Self {
+    pub fn from_indices(parent_data: &Arc<Rows<T>>, indices: Vec<usize>) -> Self {
         let source_indices = Arc::new(indices);
+        Self::with_storage(DataStorage::Indexed {
+            parent_data: Arc::downgrade(parent_data),
+            source_indices: Arc::clone(&source_indices),
+            current_indices: ArcSwap::new(Arc::clone(&source_indices)),
+            index_levels: ArcSwap::from_pointee(vec![LevelIndices::from(source_indices)]),
+        })
+    }
+
+    // Owned хранилище: все строки items - исходный уровень.
+    // Кеш строк исходного уровня - placeholder: строки уже лежат в source
+    fn owned_storage(items: Vec<Arc<T>>) -> DataStorage<T> {
+        let initial_indices = Arc::new((0..items.len()).collect::<Vec<usize>>());
+        DataStorage::Owned {
+            source: Arc::new(ArcSwap::from_pointee(Rows::from(items))),
+            current_indices: ArcSwap::new(Arc::clone(&initial_indices)),
+            current_cache: ArcSwap::new(Arc::new(None)),
+            full_indices: ArcSwap::new(Arc::clone(&initial_indices)),
+            removed: ArcSwap::from_pointee(RoaringBitmap::new()),
+            levels: ArcSwap::from_pointee(vec![Arc::new(Vec::new())]),
+            level_indices: ArcSwap::from_pointee(vec![LevelIndices::from(initial_indices)]),
+        }
+    }
+
+    // Начальное состояние для любого хранилища - новое поле FilterData
+    // инициализируется только здесь
+    fn with_storage(storage: DataStorage<T>) -> Self {
+        let source_len = match &storage {
+            DataStorage::Owned { source, .. } => source.load().len(),
+            DataStorage::Indexed { source_indices, .. } => source_indices.len(),
+        };
         Self {
-            storage: DataStorage::Indexed {
-                parent_data: Arc::downgrade(parent_data),
-                source_indices: Arc::clone(&source_indices),
-                current_indices: ArcSwap::new(Arc::clone(&source_indices)),
-                index_levels: ArcSwap::from_pointee(vec![source_indices]),
-            },
-            level_info: ArcSwap::from_pointee(vec![Arc::from("Source")]),
+            storage,
+            level_meta: ArcSwap::from_pointee(vec![Arc::new(LevelMeta::source(source_len))]),
             current_level: Arc::new(AtomicUsize::new(0)),
-            indexes: DashMap::new(),
+            indexes: Arc::new(DashMap::new()),
+            index_builds: Arc::new(DashMap::new()),
+            index_recipes: Arc::new(DashMap::new()),
+            index_version_seq: Arc::new(AtomicU64::new(0)),
+            column_stats: DashMap::new(),
+            planner_feedback: DashMap::new(),
+            planner_hints: DashMap::new(),
+            string_options: DashMap::new(),
+            decimal_options: DashMap::new(),
+            #[cfg(feature = "serde")]
+            saved_indexes: DashMap::new(),
+            null_mode: RwLock::new(NullMode::default()),
+            history_compaction: RwLock::new(None),
+            deterministic_order: RwLock::new(false),
+            bitmap_levels: RwLock::new(false),
+            concurrency_limiter: RwLock::new(None),
+            planner_stats: RwLock::new(PlannerStats::default()),
+            result_cache: Mutex::new(None),
+            range_buckets: DashMap::new(),
+            top_sketches: DashMap::new(),
+            value_sums: DashMap::new(),
+            field_stats: DashMap::new(),
+            views: DashMap::new(),
+            computed_fields: DashMap::new(),
+            segments: DashMap::new(),
+            key_table: ArcSwap::from_pointee(None),
+            unique_indexes: DashMap::new(),
+            compiled_operations: DashMap::new(),
+            ngram_dictionary: Arc::new(NgramDictionary::new()),
+            base_filter: ArcSwap::from_pointee(None),
             source_indices_mask: ArcSwap::from_pointee(None),
-            write_lock: RwLock::new(()),
+            universe_bitmap: ArcSwap::from_pointee(None),
+            write_lock: Arc::new(RwLock::new(())),
         }
     }
-    
+
+    // Append
+
+    /// Дописать строку в конец данных
+    /// 
+    /// Возвращает индекс строки в хранилище. См. `extend`.
+    /// 
+    /// # Example
+    /// 
+    /// let row_id = data.push(Order { id: 42, status: "open".into(), amount: 10.0 })?;
+    /// 
+    pub fn push(&self, item: T) -> GlobalResult<usize> {
+        self.extend(std::iter::once(item)).map(|rows| rows.start)
+    }
+
+    /// Дописать строки в конец данных
+    /// 
+    /// Field и text индексы дописываются на месте: новые позиции попадают
+    /// в bitmap-ы своих значений и в хвост sorted_values, fingerprint
+    /// пересчитывается только по последнему чанку, версия индекса растет
+    /// (прогретые диапазоны и value sums становятся недействительны). Индекс,
+    /// который в этот момент читают, дописывается на копии. Строки дописываются
+    /// в буфер `Rows` без копирования: прежние снимки source их не видят и
+    /// читаются без блокировок.
+    /// Поля-массивы перестраиваются по рецепту. Исходный уровень сразу видит новые
+    /// строки, уровни drill-down и base filter остаются прежними. Ключи новых
+    /// строк дописываются в таблицу ключей и уникальные индексы; если ключ уже
+    /// есть или повторяется среди новых строк, push отклоняется
+    /// (`DuplicateKey` / `DuplicateUniqueKeys`) и данные не меняются.
+    /// Материализованные представления пересчитываются при следующем чтении.
+    /// Только для Owned данных; возвращает диапазон индексов новых строк.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index("status", |order: &Order| order.status.clone())?;
+    /// let rows = data.extend(load_new_orders())?;
+    /// data.filter_by_field_ops("status", &[(FieldOperation::eq("open"), Op::And)])?;
+    /// 
+    pub fn extend<I>(&self, items: I) -> GlobalResult<Range<usize>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let DataStorage::Owned {
+            source,
+            current_indices,
+            current_cache,
+            full_indices,
+            levels,
+            level_indices,
+            ..
+        } = &self.storage else {
+            return Err(GLobalError::FilterData(FilterDataError::AppendToIndexed));
+        };
+        let _guard = self.write_lock.write();
+        let old = source.load_full();
+        let start = old.len();
+        let new_items: Vec<Arc<T>> = items.into_iter().map(Arc::new).collect();
+        let end = start + new_items.len();
+        if end == start {
+            return Ok(start..end);
+        }
+        Self::check_row_limit(end)?;
+        // Повторяющиеся ключи отклоняют push до изменения данных
+        let new_keys = self.new_table_keys(&new_items)?;
+        // Строки дописываются в буфер source: снимки читателей их не видят
+        let rows = Arc::new(old.appended(new_items));
+        let mut full = full_indices.load().to_vec();
+        full.extend(start..end);
+        let full = Arc::new(full);
+        source.store(Arc::clone(&rows));
+        full_indices.store(Arc::clone(&full));
+        self.append_to_indexes(&rows, start);
+        self.record_pending_changes();
+        for (table, keys) in new_keys {
+            table.append(start, keys);
+        }
+        self.universe_bitmap.store(Arc::new(None));
+        // Под base filter исходный уровень - base: новые строки в него не входят
+        if self.base_filter.load().is_none() {
+            let mut new_levels = levels.load().to_vec();
+            new_levels[0] = Arc::new(Vec::new());
+            levels.store(Arc::new(new_levels));
+            let mut new_level_indices = level_indices.load().to_vec();
+            new_level_indices[0] = Arc::clone(&full).into();
+            level_indices.store(Arc::new(new_level_indices));
+            let mut new_meta = self.level_meta.load().to_vec();
+            new_meta[0] = Arc::new(LevelMeta::source(full.len()));
+            self.level_meta.store(Arc::new(new_meta));
+            if self.current_level.load(Ordering::Acquire) == 0 {
+                current_indices.store(full);
+                current_cache.store(Arc::new(None));
+                self.source_indices_mask.store(Arc::new(None));
+            }
+        }
+        Ok(start..end)
+    }
+
+    // Ключи новых строк для таблицы ключей и уникальных индексов;
+    // повтор ключа - ошибка, как у set_key / create_unique_index
+    fn new_table_keys(&self, items: &[Arc<T>]) -> GlobalResult<Vec<NewTableKeys<T>>> {
+        let mut new_keys = Vec::new();
+        if let Some(table) = self.key_table.load_full().as_ref() {
+            let keys = table
+                .new_keys(items)
+                .map_err(|keys| GLobalError::FilterData(FilterDataError::DuplicateKey { key: keys[0].to_string() }))?;
+            new_keys.push((Arc::clone(table), keys));
+        }
+        for entry in self.unique_indexes.iter() {
+            let keys = entry.new_keys(items).map_err(|keys| {
+                GLobalError::FilterData(FilterDataError::DuplicateUniqueKeys {
+                    name: entry.key().clone(),
+                    keys: keys.iter().map(|key| key.to_string()).collect(),
+                })
+            })?;
+            new_keys.push((Arc::clone(entry.value()), keys));
+        }
+        Ok(new_keys)
+    }
+
+    // Дописать строки rows[start..] во все индексы. Индекс, который никто не читает,
+    // дописывается на месте, иначе - на копии. Индекс, общий для нескольких имен
+    // (shared_indexes), дописывается один раз. Fingerprint пересчитывается только
+    // по последнему чанку и новым строкам
+    fn append_to_indexes(&self, rows: &[Arc<T>], start: usize) {
+        let names: Vec<String> = self.indexes.iter().map(|entry| entry.key().clone()).collect();
+        let mut appended: Vec<AppendedIndex<T>> = Vec::new();
+        for name in names {
+            let Some((builder, content, chunk_hashes)) = self.index_recipes
+                .get_mut(&name)
+                .map(|mut recipe| (Arc::clone(&recipe.builder), Arc::clone(&recipe.content), recipe.chunk_hashes.take())) else {
+                continue;
+            };
+            let Some(mut entry) = self.indexes.get_mut(&name) else {
+                continue;
+            };
+            let old = Arc::as_ptr(entry.value());
+            let (fingerprint, chunk_hashes) = match appended.iter().find(|(ptr, ..)| *ptr == old) {
+                Some((_, updated, fingerprint, chunk_hashes)) => {
+                    *entry.value_mut() = Arc::clone(updated);
+                    (*fingerprint, chunk_hashes.clone())
+                }
+                None => {
+                    let values: Vec<FieldValue> = rows[start..].iter().map(|item| content(item)).collect();
+                    let index = entry.value_mut();
+                    let in_place = Arc::get_mut(index).is_some_and(|index| index.append_values(&values));
+                    if !in_place {
+                        *index = Arc::new(index.appended(&values).unwrap_or_else(|| self.build_index(&builder, rows)));
+                    }
+                    let chunk_hashes = match chunk_hashes {
+                        Some(mut chunk_hashes) => {
+                            IndexFingerprint::extend_chunk_hashes(&mut chunk_hashes, rows, start, &content);
+                            chunk_hashes
+                        }
+                        None => IndexFingerprint::chunk_hashes(rows, &content),
+                    };
+                    let fingerprint = IndexFingerprint::from_chunk_hashes(rows.len(), &chunk_hashes);
+                    appended.push((old, Arc::clone(index), fingerprint, chunk_hashes.clone()));
+                    (fingerprint, chunk_hashes)
+                }
+            };
+            drop(entry);
+            let version = self.next_index_version();
+            if let Some(mut recipe) = self.index_recipes.get_mut(&name) {
+                recipe.version = version;
+                recipe.fingerprint = fingerprint;
+                recipe.chunk_hashes = Some(chunk_hashes);
+            }
+        }
+    }
+
+    /// Удалить строки, для которых predicate вернул false
+    /// 
+    /// Возвращает число удаленных строк. См. `remove_indices`.
+    /// 
+    /// # Example
+    /// 
+    /// let expired = data.retain(|session: &Session| session.expires_at > now)?;
+    /// 
+    pub fn retain<F>(&self, predicate: F) -> GlobalResult<usize>
+    where
+        F: Fn(&T) -> bool + Send + Sync,
+    {
+        let DataStorage::Owned { source, full_indices, .. } = &self.storage else {
+            return Err(GLobalError::FilterData(FilterDataError::RemoveFromIndexed));
+        };
+        let source = source.load_full();
+        let rows: RoaringBitmap = full_indices
+            .load()
+            .par_iter()
+            .filter(|&&idx| source.get(idx).is_some_and(|item| !predicate(item)))
+            .map(|&idx| idx as u32)
+            .collect::<Vec<u32>>()
+            .into_iter()
+            .collect();
+        self.remove_rows(rows)
+    }
+
+    /// Удалить строки по индексам хранилища
+    /// 
+    /// Строки помечаются удаленными: они исключаются из исходного уровня
+    /// (`full_indices`), из bitmap-ов всех field и text индексов, из уровней
+    /// истории, представлений, base filter и таблиц ключей. Индексы остальных
+    /// строк не меняются. Неизвестные и уже удаленные индексы пропускаются;
+    /// возвращает число удаленных строк. Только для Owned данных.
+    /// 
+    /// # Example
+    /// 
+    /// let ids = data.current_indices();
+    /// data.remove_indices(&ids)?;
+    /// 
+    pub fn remove_indices(&self, indices: &[usize]) -> GlobalResult<usize> {
+        if matches!(self.storage, DataStorage::Indexed { .. }) {
+            return Err(GLobalError::FilterData(FilterDataError::RemoveFromIndexed));
+        }
+        self.remove_rows(indices.iter().map(|&idx| idx as u32).collect())
+    }
+
+    fn remove_rows(&self, rows: RoaringBitmap) -> GlobalResult<usize> {
+        let DataStorage::Owned {
+            source,
+            current_indices,
+            current_cache,
+            full_indices,
+            removed,
+            levels,
+            level_indices,
+        } = &self.storage else {
+            return Err(GLobalError::FilterData(FilterDataError::RemoveFromIndexed));
+        };
+        let _guard = self.write_lock.write();
+        let mut rows = rows - &**removed.load();
+        rows.remove_range(source.load().len() as u32..);
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let mut all_removed = (**removed.load()).clone();
+        all_removed |= &rows;
+        removed.store(Arc::new(all_removed));
+        let full: Arc<Vec<usize>> = Arc::new(
+            full_indices.load().iter().copied().filter(|&idx| !rows.contains(idx as u32)).collect()
+        );
+        full_indices.store(Arc::clone(&full));
+        self.remove_from_indexes(&rows);
+        self.record_pending_changes();
+        if let Some(table) = self.key_table.load().as_ref() {
+            table.remove_rows(&rows);
+        }
+        for table in self.unique_indexes.iter() {
+            table.remove_rows(&rows);
+        }
+        for mut view in self.views.iter_mut() {
+            let mut restricted = (**view).clone();
+            restricted.bitmap -= &rows;
+            *view = Arc::new(restricted);
+        }
+        if let Some(base) = self.base_filter.load_full().as_ref() {
+            let indices: Arc<Vec<usize>> = Arc::new(
+                base.indices.iter().copied().filter(|&idx| !rows.contains(idx as u32)).collect()
+            );
+            let items = base.items.as_ref().map(|_| {
+                let source = source.load();
+                Arc::new(indices.iter().filter_map(|&idx| source.get(idx).cloned()).collect())
+            });
+            self.base_filter.store(Arc::new(Some(Arc::new(BaseFilter {
+                bitmap: Arc::new(&*base.bitmap - &rows),
+                indices,
+                items,
+            }))));
+        }
+        self.universe_bitmap.store(Arc::new(None));
+        if let Some(mask) = self.source_indices_mask.load().as_ref() {
+            self.source_indices_mask.store(Arc::new(Some(Arc::new(&**mask - &rows))));
+        }
+
+        // Уровни истории: индексы без удаленных строк, кеш строк фильтруется вместе с ними
+        let old_indices = level_indices.load_full();
+        let old_levels = levels.load_full();
+        let mut new_indices = Vec::with_capacity(old_indices.len());
+        let mut new_levels = Vec::with_capacity(old_levels.len());
+        for (level, indices) in old_indices.iter().enumerate() {
+            let kept: Arc<Vec<usize>> = Arc::new(
+                indices.indices().iter().copied().filter(|&idx| !rows.contains(idx as u32)).collect()
+            );
+            let cached = old_levels.get(level).map(|items| {
+                if level == 0 && self.base_filter.load().is_none() {
+                    Arc::new(Vec::new())
+                } else if items.len() == indices.len() && !items.is_empty() {
+                    let source_ids = indices.indices();
+                    Arc::new(
+                        items
+                            .iter()
+                            .zip(source_ids.iter())
+                            .filter(|(_, idx)| !rows.contains(**idx as u32))
+                            .map(|(item, _)| Arc::clone(item))
+                            .collect()
+                    )
+                } else {
+                    // Placeholder большого уровня остается placeholder-ом
+                    Arc::clone(items)
+                }
+            });
+            if let Some(cached) = cached {
+                new_levels.push(cached);
+            }
+            new_indices.push(if indices.is_bitmap() {
+                LevelIndices::Bitmap(Arc::new(kept.iter().map(|&idx| idx as u32).collect()))
+            } else {
+                LevelIndices::Vec(kept)
+            });
+        }
+        let new_meta: Vec<Arc<LevelMeta>> = self.level_meta
+            .load()
+            .iter()
+            .zip(new_indices.iter())
+            .map(|(meta, indices)| {
+                let mut meta = (**meta).clone();
+                meta.row_count = indices.len();
+                Arc::new(meta)
+            })
+            .collect();
+        let level = self.current_level.load(Ordering::Acquire);
+        if let Some(indices) = new_indices.get(level) {
+            current_indices.store(indices.indices());
+            match new_levels.get(level) {
+                Some(items) if items.len() == indices.len() => current_cache.store(Arc::new(Some(Arc::clone(items)))),
+                _ => current_cache.store(Arc::new(None)),
+            }
+        }
+        levels.store(Arc::new(new_levels));
+        level_indices.store(Arc::new(new_indices));
+        self.level_meta.store(Arc::new(new_meta));
+        Ok(rows.len() as usize)
+    }
+
+    // Сбросить удаленные строки во всех индексах; общий индекс (shared_indexes) - один раз
+    fn remove_from_indexes(&self, rows: &RoaringBitmap) {
+        let names: Vec<String> = self.indexes.iter().map(|entry| entry.key().clone()).collect();
+        let mut updated: Vec<ReplacedIndex<T>> = Vec::new();
+        for name in names {
+            let Some(index) = self.indexes.get(&name).map(|entry| Arc::clone(entry.value())) else {
+                continue;
+            };
+            let without = match updated.iter().find(|(old, _)| Arc::ptr_eq(old, &index)) {
+                Some((_, without)) => Arc::clone(without),
+                None => {
+                    let without = Arc::new(index.without(rows));
+                    updated.push((index, Arc::clone(&without)));
+                    without
+                }
+            };
+            let version = self.next_index_version();
+            if let Some(mut recipe) = self.index_recipes.get_mut(&name) {
+                recipe.version = version;
+            }
+            self.indexes.insert(name, without);
+        }
+    }
+
+    // Удаленные строки Owned хранилища (retain / remove_indices)
+    fn removed_rows(&self) -> Arc<RoaringBitmap> {
+        match &self.storage {
+            DataStorage::Owned { removed, .. } => removed.load_full(),
+            DataStorage::Indexed { .. } => Arc::new(RoaringBitmap::new()),
+        }
+    }
+
+    // Индекс по index_base_items без удаленных строк
+    fn build_index(&self, builder: &IndexBuilder<T>, items: &[Arc<T>]) -> IndexType<T> {
+        let index = builder(items);
+        let removed = self.removed_rows();
+        if removed.is_empty() {
+            index
+        } else {
+            index.without(&removed)
+        }
+    }
+
+    // Позиции строк в bitmap-ах - u32: строки за пределом молча потерялись бы
+    fn check_row_limit(rows: usize) -> GlobalResult<()> {
+        if rows > MAX_INDEXED_ROWS {
+            return Err(GLobalError::FilterData(FilterDataError::TooManyRows {
+                rows,
+                max: MAX_INDEXED_ROWS,
+            }));
+        }
+        Ok(())
+    }
+
     // Core Access Methods
 
 
@@ -182,6 +807,7 @@ where
                 
                 // Материализуем из индексов
                 let indices = current_indices.load();  // Arc<Vec<usize>>
+                let source = source.load();
                 let items: Vec<Arc<T>> = indices
                     .iter()
                     .filter_map(|&idx| source.get(idx).cloned())
@@ -225,13 +851,97 @@ where
         }
     }
     
-    pub fn parent_data(&self) -> Option<Arc<Vec<Arc<T>>>> {
+    /// Текущая выборка вместе с индексами строк в хранилище
+    /// 
+    /// Индекс - позиция строки в source (Owned) или в parent_data (Indexed),
+    /// по нему результат сопоставляется с внешними системами.
+    /// 
+    /// # Example
+    /// 
+    /// for (row_id, order) in data.items_with_ids() {
+    ///     external.update(row_id, &order);
+    /// }
+    /// 
+    pub fn items_with_ids(&self) -> Vec<(usize, Arc<T>)> {
+        self.iter_items_with_ids().collect()
+    }
+
+    // Ленивая версия items_with_ids: держит снимок текущих индексов
+    pub fn iter_items_with_ids(&self) -> impl Iterator<Item = (usize, Arc<T>)> + use<T> {
+        let data = self.storage_rows();
+        let indices = self.current_indices();
+        (0..indices.len()).filter_map(move |pos| {
+            let idx = indices[pos];
+            data.as_ref()?.get(idx).map(|item| (idx, Arc::clone(item)))
+        })
+    }
+
+    /// Глубокая копия текущей выборки
+    /// 
+    /// Элементы клонируются из хранилища: результат не держит Arc-ов движка,
+    /// его можно свободно менять, и он не продлевает жизнь source / parent_data.
+    /// Большие выборки клонируются параллельно.
+    /// 
+    /// # Example
+    /// 
+    /// let mut orders = data.to_owned_vec();
+    /// orders.iter_mut().for_each(|order| order.price *= 2);
+    /// 
+    pub fn to_owned_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let Some(data) = self.storage_rows() else {
+            return Vec::new();
+        };
+        let indices = self.current_indices();
+        if indices.len() > PARALLEL_CLONE_THRESHOLD {
+            indices
+                .par_iter()
+                .filter_map(|&idx| data.get(idx).map(|item| T::clone(item)))
+                .collect()
+        } else {
+            indices
+                .iter()
+                .filter_map(|&idx| data.get(idx).map(|item| T::clone(item)))
+                .collect()
+        }
+    }
+
+    /// Все строки хранилища (source для Owned, parent_data для Indexed)
+    /// 
+    /// Под base filter - None: строки вне фильтра не должны попасть к коду,
+    /// которому отдан FilterData. Доверенный код берет `trusted_access()`
+    /// до `set_base_filter`.
+    /// 
+    /// # Example
+    /// 
+    /// let rows = data.parent_data().ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+    /// let view = FilterData::from_indices(&rows, vec![0, 5, 7]);
+    /// 
+    pub fn parent_data(&self) -> Option<Arc<Rows<T>>> {
+        if self.has_base_filter() {
+            return None;
+        }
+        self.storage_rows()
+    }
+
+    // Строки хранилища без учета base filter: для чтения по уже ограниченным индексам
+    pub(crate) fn storage_rows(&self) -> Option<Arc<Rows<T>>> {
         match &self.storage {
-            DataStorage::Owned { source, .. } => Some(Arc::clone(source)),
+            DataStorage::Owned { source, .. } => Some(source.load_full()),
             DataStorage::Indexed { parent_data, .. } => parent_data.upgrade(),
         }
     }
     
+    // Индексы хранилища исходного уровня; None - Owned (все строки source)
+    pub(crate) fn source_indices(&self) -> Option<Arc<Vec<usize>>> {
+        match &self.storage {
+            DataStorage::Owned { .. } => None,
+            DataStorage::Indexed { source_indices, .. } => Some(Arc::clone(source_indices)),
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         match &self.storage {
             DataStorage::Owned { .. } => true,
@@ -317,7 +1027,7 @@ where
         extractor: F,
     ) -> GlobalResult<&Self>
     where 
-        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + 'static,
         F: Fn(&T) -> V + Send + Sync + Clone + 'static,
         IndexField<V>: IntoIndexFieldEnum,
         V: Into<FieldValue> + 'static, 
@@ -325,330 +1035,4135 @@ where
     {
         // Проверяем существует ли Index с таким наименованием
         if self.has_index(name) {
-            if let Err(err) = self.check_index_type_compability(
+            self.check_index_type_compability(
                 name, 
                 INDEX_FIELD, 
                 IndexCompatibilityAction::Replace
-            ) {
-                return Err(GLobalError::Index(err));
-            }
-            // drop_index теперь просто удаляет из DashMap
-            self.drop_index(name);
+            ).map_err(GLobalError::Index)?;
         }
-        let extractor_clone = extractor.clone();
-        let items = self.items();
-        let index = IndexField::build(&items, extractor);
-        self.indexes.insert(
-            name.to_string(),
-            Arc::new(
-                IndexType::Field(
-                    (
-                        index.into_enum(),
-                        Self::create_field_value_extractor(extractor_clone),
-                    )
-                )
-            ),
-        );
+        // Старый индекс остается доступным до атомарной подмены
+        let content = Self::create_field_value_extractor(extractor.clone());
+        self.install_index(name, Self::field_index_builder(extractor, IndexField::build), content, INDEX_FIELD)?;
+        self.string_options.remove(name);
+        self.decimal_options.remove(name);
         Ok(self)
     }
 
-    pub fn get_index(&self, name: &str) -> GlobalResult<Arc<IndexType<T>>> {
-        self.indexes.get(name)
-            .ok_or(GLobalError::Index(IndexError::NotFound {
+    /// Создать строковый field индекс с нормализацией значений
+    /// 
+    /// Значения строк приводятся при построении индекса, операнды запросов
+    /// по этому полю - при каждом запросе (index и predicate path одинаково).
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index_with_options("level", |log: &Log| log.level.clone(), StringIndexOptions::case_insensitive())?;
+    /// data.filter_by_field_ops("level", &[(FieldOperation::eq("ERROR"), Op::And)])?;   // "error", " Error "
+    /// 
+    pub fn create_field_index_with_options<F>(
+        &self,
+        name: &str,
+        extractor: F,
+        options: StringIndexOptions,
+    ) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> String + Send + Sync + Clone + 'static,
+    {
+        #[cfg(not(feature = "unicode"))]
+        if options.unicode_normalize {
+            return Err(GLobalError::Index(IndexError::Build {
                 name: name.to_string(),
-            }))
-            .map(|guard| guard.clone())
+                reason: "unicode_normalize requires feature 'unicode'".to_string(),
+            }));
+        }
+        self.create_field_index(name, move |item: &T| options.normalize(&extractor(item)))?;
+        if !options.is_noop() {
+            self.string_options.insert(name.to_string(), options);
+        }
+        Ok(self)
     }
 
-    fn apply_field_operations(
+    pub fn string_index_options(&self, name: &str) -> Option<StringIndexOptions> {
+        self.string_options.get(name).map(|options| *options)
+    }
+
+    /// Создать Decimal field индекс с фиксированным масштабом
+    /// 
+    /// Значения округляются до `options.scale` знаков при построении индекса,
+    /// числовые операнды запросов по полю - при каждом запросе, поэтому
+    /// 1.1, 1.10 и 1.100 - одно значение индекса.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_decimal_index_with_scale("amount", |p: &Payment| p.amount, DecimalIndexOptions::money())?;
+    /// data.filter_by_field_ops("amount", &[(FieldOperation::eq(Decimal::new(1999, 2)), Op::And)])?;
+    /// 
+    pub fn create_decimal_index_with_scale<F>(
         &self,
-        field_index: &IndexFieldEnum,
-        operations: &[(FieldOperation, Op)],
+        name: &str,
+        extractor: F,
+        options: DecimalIndexOptions,
+    ) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> Decimal + Send + Sync + Clone + 'static,
+    {
+        self.create_field_index(name, move |item: &T| options.round(extractor(item)))?;
+        self.decimal_options.insert(name.to_string(), options);
+        Ok(self)
+    }
+
+    pub fn decimal_index_options(&self, name: &str) -> Option<DecimalIndexOptions> {
+        self.decimal_options.get(name).map(|options| *options)
+    }
+
+    /// Создать f64 field индекс с политикой NaN
+    /// 
+    /// `NaNLargest` - обычный `create_field_index`, `NaNIsNull` - nullable индекс
+    /// (NaN в nulls), `RejectNaN` - ошибка построения, если в данных есть NaN
+    /// (проверяется при создании, не при перестроении после reload).
+    /// Число NaN строк индекса - `field_stats(name)?.nans`.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_float_index_with_policy("score", |m: &Metric| m.score, FloatPolicy::RejectNaN)?;
+    /// 
+    pub fn create_float_index_with_policy<F>(
+        &self,
+        name: &str,
+        extractor: F,
+        policy: FloatPolicy,
+    ) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> f64 + Send + Sync + Clone + 'static,
+    {
+        match policy {
+            FloatPolicy::NaNLargest => {
+                self.create_field_index(name, move |item: &T| OrderedFloat(extractor(item)))
+            }
+            FloatPolicy::NaNIsNull => self.create_nullable_field_index(name, move |item: &T| {
+                let value = extractor(item);
+                (!value.is_nan()).then_some(OrderedFloat(value))
+            }),
+            FloatPolicy::RejectNaN => {
+                let nans = self.index_base_items().par_iter().filter(|item| extractor(item).is_nan()).count();
+                if nans > 0 {
+                    return Err(GLobalError::Index(IndexError::Build {
+                        name: name.to_string(),
+                        reason: format!("{nans} NaN values rejected by FloatPolicy::RejectNaN"),
+                    }));
+                }
+                self.create_field_index(name, move |item: &T| OrderedFloat(extractor(item)))
+            }
+        }
+    }
+
+    // Операнды запроса по полю с нормализацией (create_field_index_with_options,
+    // create_decimal_index_with_scale)
+    fn normalized_operations<'o>(
+        &self,
+        name: &str,
+        operations: &'o [(FieldOperation, Op)],
+    ) -> Cow<'o, [(FieldOperation, Op)]> {
+        if self.string_options.is_empty() && self.decimal_options.is_empty() {
+            return Cow::Borrowed(operations);
+        }
+        if let Some(options) = self.string_options.get(name) {
+            return Cow::Owned(
+                operations
+                    .iter()
+                    .map(|(operation, op)| (options.normalize_operation(operation), *op))
+                    .collect(),
+            );
+        }
+        match self.decimal_options.get(name) {
+            Some(options) => Cow::Owned(
+                operations
+                    .iter()
+                    .map(|(operation, op)| (options.normalize_operation(operation), *op))
+                    .collect(),
+            ),
+            None => Cow::Borrowed(operations),
+        }
+    }
+
+    /// Создать field индекс по пользовательскому enum
+    /// 
+    /// В индексе хранятся u32 коды (`IndexEnum::to_code`), фильтрация идет
+    /// через `FieldOperation::eq(Status::Active)` - enum приводится к FieldValue.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_enum_index("status", |order: &Order| order.status)?;
+    /// let counts = data.count_by_enum::<Status>("status")?;
+    /// 
+    pub fn create_enum_index<E,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<&Self>
+    where 
+        E: IndexEnum,
+        F: Fn(&T) -> E + Send + Sync + Clone + 'static,
+    {
+        self.create_field_index(name, move |item: &T| extractor(item).to_code())
+    }
+
+    /// Создать field индекс по nullable полю
+    /// 
+    /// Строки с None хранятся в индексе отдельным bitmap-ом: в двузначном режиме
+    /// они не совпадают ни с одним значением, в `NullMode::ThreeValued` любое
+    /// сравнение с ними - Unknown. Такие поля всегда фильтруются через индекс,
+    /// extractor индекса (агрегаты, экспорт) возвращает для None `FieldValue::Null`.
+    /// Строки с значением или без выбираются через `IsNull` / `IsNotNull`.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_nullable_field_index("discount", |order: &Order| order.discount)?;
+    /// data.filter_by_field_ops("discount", &[(FieldOperation::is_null(), Op::And)])?;
+    /// 
+    pub fn create_nullable_field_index<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> Option<V> + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        let value_extractor = extractor.clone();
+        let content = Self::create_field_value_extractor(move |item: &T| value_extractor(item));
+        let builder: IndexBuilder<T> = Arc::new(move |items: &[Arc<T>]| {
+            let value_extractor = extractor.clone();
+            IndexType::Field(
+                (
+                    IndexField::build_nullable(items, extractor.clone()).into_enum(),
+                    Self::create_field_value_extractor(move |item: &T| value_extractor(item)),
+                )
+            )
+        });
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        Ok(self)
+    }
+
+    /// Создать field индекс по полю-массиву (теги, категории)
+    /// 
+    /// Строка попадает в bitmap каждого своего значения, строки с пустым
+    /// списком - в nulls. Выборка по членству - `ContainsAny` / `ContainsAll`,
+    /// `Eq(v)` совпадает со строками, содержащими v. Такие поля всегда
+    /// фильтруются через индекс, extractor индекса (агрегаты, экспорт)
+    /// возвращает первое значение списка.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_multi_field_index("tags", |post: &Post| post.tags.clone())?;
+    /// data.filter_by_field_ops("tags", &[(FieldOperation::contains_all(vec!["rust", "db"]), Op::And)])?;
+    /// 
+    pub fn create_multi_field_index<V, F, I>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> I + Send + Sync + Clone + 'static,
+        I: IntoIterator<Item = V>,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        let value_extractor = extractor.clone();
+        let content = Self::create_field_value_extractor(move |item: &T| value_extractor(item).into_iter().next());
+        let builder: IndexBuilder<T> = Arc::new(move |items: &[Arc<T>]| {
+            let value_extractor = extractor.clone();
+            IndexType::Field(
+                (
+                    IndexField::build_multi(items, extractor.clone()).into_enum(),
+                    Self::create_field_value_extractor(move |item: &T| value_extractor(item).into_iter().next()),
+                )
+            )
+        });
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        Ok(self)
+    }
+
+    /// Создать field индекс по данным, уже отсортированным по ключу
+    /// 
+    /// Пропускает глобальную сортировку: sorted_values и bitmap-ы строятся
+    /// за один линейный проход. Если порядок нарушен - обычное построение.
+    /// 
+    /// # Example
+    /// 
+    /// // логи упорядочены по времени
+    /// data.create_field_index_sorted("timestamp", |log| log.timestamp)?;
+    /// 
+    pub fn create_field_index_sorted<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        let content = Self::create_field_value_extractor(extractor.clone());
+        self.install_index(name, Self::field_index_builder(extractor, IndexField::build_sorted), content, INDEX_FIELD)?;
+        Ok(self)
+    }
+
+    /// Создать field индекс заданного вида
+    /// 
+    /// `IndexKind::Hash` - для полей, по которым идут только Eq / In / NotEq / NotIn:
+    /// без BTreeMap и sorted_values, range запросы идут predicate path.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index_with_kind("session_id", |e| e.session_id.clone(), IndexKind::Hash)?;
+    /// 
+    pub fn create_field_index_with_kind<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+        kind: IndexKind,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        let content = Self::create_field_value_extractor(extractor.clone());
+        let builder = Self::field_index_builder(extractor, move |items: &[Arc<T>], extractor: F| match kind {
+            IndexKind::Sorted => IndexField::build(items, extractor),
+            IndexKind::Hash => IndexField::build_hash(items, extractor),
+        });
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        self.string_options.remove(name);
+        self.decimal_options.remove(name);
+        Ok(self)
+    }
+
+    /// Создать sparse field индекс для поля с доминирующим значением
+    /// 
+    /// Bitmap значения `default` и список позиций для range не хранятся:
+    /// Eq(default) и операции, которым default подходит, отвечают дополнением
+    /// остальных значений (все строки - nulls - строки других значений).
+    /// 
+    /// # Example
+    /// 
+    /// // 95% заказов в статусе "active"
+    /// data.create_field_index_sparse("status", |o: &Order| o.status.clone(), FieldValue::from("active"))?;
+    /// data.filter_by_field_ops("status", &[(FieldOperation::eq("active"), Op::And)])?;
+    /// 
+    pub fn create_field_index_sparse<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+        default: FieldValue,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        // Тип default проверяется до построения - на пустом индексе
+        if !IndexField::<V>::default().into_enum().set_sparse_default(&default) {
+            return Err(GLobalError::Index(IndexError::Build {
+                name: name.to_string(),
+                reason: format!("default value {default:?} does not match index type"),
+            }));
+        }
+        let content = Self::create_field_value_extractor(extractor.clone());
+        let builder: IndexBuilder<T> = Arc::new(move |items: &[Arc<T>]| {
+            let mut index = IndexField::build(items, extractor.clone()).into_enum();
+            index.set_sparse_default(&default);
+            IndexType::Field((index, Self::create_field_value_extractor(extractor.clone())))
+        });
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        self.string_options.remove(name);
+        self.decimal_options.remove(name);
+        Ok(self)
+    }
+
+    /// Создать field индекс с ограничением промежуточной памяти на построение
+    /// 
+    /// Строки обрабатываются раундами: промежуточные карты значение -> строки
+    /// всех потоков одного раунда занимают порядка `memory_target` байт и
+    /// вливаются в индекс до начала следующего раунда. Сам индекс в бюджет
+    /// не входит.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index_with_memory_target("user_id", |e| e.user_id, 16 * 1024 * 1024)?;
+    /// 
+    pub fn create_field_index_with_memory_target<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+        memory_target: usize,
+    ) -> GlobalResult<&Self>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        let content = Self::create_field_value_extractor(extractor.clone());
+        let builder = Self::field_index_builder(extractor, move |items: &[Arc<T>], extractor: F| {
+            IndexField::build_with_memory_target(items, extractor, memory_target)
+        });
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        Ok(self)
+    }
+
+    fn field_index_builder<V,F,B>(extractor: F, build: B) -> IndexBuilder<T>
+    where 
+        B: Fn(&[Arc<T>], F) -> IndexField<V> + Send + Sync + 'static,
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        Arc::new(move |items: &[Arc<T>]| {
+            IndexType::Field(
+                (
+                    build(items, extractor.clone()).into_enum(),
+                    Self::create_field_value_extractor(extractor.clone()),
+                )
+            )
+        })
+    }
+
+    fn text_index_builder<F>(&self, extractor: F, options: TextIndexOptions) -> IndexBuilder<T>
+    where
+        F: Fn(&T) -> String + Send + Sync + 'static + Clone,
+    {
+        let dictionary = Arc::clone(&self.ngram_dictionary);
+        Arc::new(move |items: &[Arc<T>]| {
+            let mut text_index = TextIndex::with_dictionary(options, Arc::clone(&dictionary));
+            text_index.build(items, extractor.clone());
+            IndexType::Text(text_index)
+        })
+    }
+
+    // Строит индекс и регистрирует рецепт для последующих rebuild
+    fn install_index(
+        &self,
+        name: &str,
+        builder: IndexBuilder<T>,
+        content: ExtractorFieldValue<T>,
+        index_type: &'static str,
+    ) -> GlobalResult<()> {
+        Self::check_row_limit(self.index_base_len())?;
+        let _permit = self.acquire_heavy()?;
+        let items = self.index_base_items();
+        let fingerprint = IndexFingerprint::compute(&items, &content);
+        #[cfg(feature = "serde")]
+        let saved = self.take_saved_index(name, &builder, &content, &items, fingerprint);
+        #[cfg(not(feature = "serde"))]
+        let saved = None;
+        let index = match saved {
+            Some(index) => index,
+            None => self.build_index(&builder, &items),
+        };
+        self.install_built_index(name, builder, content, index_type, index, fingerprint);
+        Ok(())
+    }
+
+    // Индекс из load_indexes вместо построения: то же имя, те же данные
+    // (fingerprint и удаленные строки), совместимая структура и выборочная сверка
+    #[cfg(feature = "serde")]
+    fn take_saved_index(
+        &self,
+        name: &str,
+        builder: &IndexBuilder<T>,
+        content: &ExtractorFieldValue<T>,
+        items: &[Arc<T>],
+        fingerprint: IndexFingerprint,
+    ) -> Option<IndexType<T>> {
+        let (_, saved) = self.saved_indexes.remove(name)?;
+        let removed = self.removed_rows();
+        if saved.fingerprint != fingerprint || saved.removed != *removed {
+            return None;
+        }
+        let probe = builder(&[]);
+        let index = saved.restore(&probe, content, &self.ngram_dictionary)?;
+        integrity::spot_check(&index, items, content, &removed, INTEGRITY_SAMPLE_SIZE).then_some(index)
+    }
+
+    // Зарегистрировать уже построенный индекс (пакетное построение в builder-е)
+    fn install_built_index(
+        &self,
+        name: &str,
+        builder: IndexBuilder<T>,
+        content: ExtractorFieldValue<T>,
+        index_type: &'static str,
+        index: IndexType<T>,
+        fingerprint: IndexFingerprint,
+    ) {
+        let version = self.next_index_version();
+        let index = self.shared_index(name, index_type, &index, fingerprint).unwrap_or_else(|| Arc::new(index));
+        self.cancel_index_build(name);
+        self.index_recipes.insert(
+            name.to_string(),
+            IndexRecipe { builder, index_type, version, content, fingerprint, chunk_hashes: None },
+        );
+        self.indexes.insert(name.to_string(), index);
+    }
+
+    // Дедупликация: индекс другого имени по тем же данным (совпал fingerprint
+    // и содержимое) - новый экземпляр не храним, используем существующий
+    fn shared_index(
+        &self,
+        name: &str,
+        index_type: &'static str,
+        index: &IndexType<T>,
+        fingerprint: IndexFingerprint,
+    ) -> Option<Arc<IndexType<T>>> {
+        let candidates: Vec<String> = self.index_recipes
+            .iter()
+            .filter(|recipe| {
+                recipe.key() != name
+                    && recipe.index_type == index_type
+                    && recipe.fingerprint == fingerprint
+            })
+            .map(|recipe| recipe.key().clone())
+            .collect();
+        candidates
+            .iter()
+            .filter_map(|candidate| self.indexes.get(candidate).map(|existing| Arc::clone(existing.value())))
+            .find(|existing| existing.same_content(index))
+    }
+
+    /// Группы индексов, разделяющих одну структуру (дедупликация одинаковых extractor-ов)
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index("price", |p| p.price)?;
+    /// data.create_field_index("cost", |p| p.price)?;
+    /// assert_eq!(data.shared_indexes(), vec![vec!["cost".to_string(), "price".to_string()]]);
+    /// 
+    pub fn shared_indexes(&self) -> Vec<Vec<String>> {
+        let mut indexes: Vec<(String, Arc<IndexType<T>>)> = self.indexes
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect();
+        indexes.sort_by(|left, right| left.0.cmp(&right.0));
+        let mut groups: Vec<(Arc<IndexType<T>>, Vec<String>)> = Vec::new();
+        for (name, index) in indexes {
+            match groups.iter_mut().find(|(shared, _)| Arc::ptr_eq(shared, &index)) {
+                Some((_, names)) => names.push(name),
+                None => groups.push((index, vec![name])),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(_, names)| names)
+            .collect()
+    }
+
+    fn next_index_version(&self) -> u64 {
+        self.index_version_seq.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    // Подмена индекса только если за время построения не появилась более новая версия.
+    // recipe = Some(..) - регистрируем новый рецепт вместе с индексом (фоновое построение)
+    fn swap_index_version(
+        indexes: &DashMap<String, Arc<IndexType<T>>>,
+        recipes: &DashMap<String, IndexRecipe<T>>,
+        name: &str,
+        version: u64,
+        index: IndexType<T>,
+        fingerprint: IndexFingerprint,
+        recipe: Option<(IndexBuilder<T>, ExtractorFieldValue<T>, &'static str)>,
+    ) -> bool {
+        match recipes.entry(name.to_string()) {
+            dashmap::Entry::Occupied(mut entry) => {
+                if entry.get().version > version {
+                    return false;
+                }
+                let current = entry.get_mut();
+                current.version = version;
+                current.fingerprint = fingerprint;
+                current.chunk_hashes = None;
+                if let Some((builder, content, index_type)) = recipe {
+                    current.builder = builder;
+                    current.content = content;
+                    current.index_type = index_type;
+                }
+                indexes.insert(name.to_string(), Arc::new(index));
+                true
+            }
+            dashmap::Entry::Vacant(entry) => match recipe {
+                Some((builder, content, index_type)) => {
+                    entry.insert(IndexRecipe { builder, index_type, version, content, fingerprint, chunk_hashes: None });
+                    indexes.insert(name.to_string(), Arc::new(index));
+                    true
+                }
+                // Индекс удален во время rebuild
+                None => false,
+            },
+        }
+    }
+
+    /// Перестроить индекс по текущим данным
+    /// 
+    /// Новая версия строится, пока читатели используют старую;
+    /// затем индексы атомарно подменяются. Если параллельно была построена
+    /// более новая версия, результат этого rebuild отбрасывается.
+    /// 
+    /// # Example
+    /// 
+    /// data.rebuild_index("price")?;
+    /// 
+    pub fn rebuild_index(&self, name: &str) -> GlobalResult<&Self> {
+        let (builder, content) = self.index_recipes.get(name)
+            .map(|recipe| (Arc::clone(&recipe.builder), Arc::clone(&recipe.content)))
+            .ok_or(GLobalError::Index(IndexError::NotFound { name: name.to_string() }))?;
+        Self::check_row_limit(self.index_base_len())?;
+        let version = self.next_index_version();
+        let items = self.index_base_items();
+        let index = self.build_index(&builder, &items);
+        let fingerprint = IndexFingerprint::compute(&items, &content);
+        Self::swap_index_version(&self.indexes, &self.index_recipes, name, version, index, fingerprint, None);
+        Ok(self)
+    }
+
+    /// Перестроить все индексы с рецептом по текущим данным
+    /// 
+    /// Каждый индекс перестраивается через `rebuild_index` (по имени, в порядке
+    /// возрастания). Возвращает количество перестроенных индексов.
+    /// 
+    /// # Example
+    /// 
+    /// data.reset_to_source();
+    /// data.rebuild_all_indexes()?;
+    /// 
+    pub fn rebuild_all_indexes(&self) -> GlobalResult<usize> {
+        let mut names: Vec<String> = self.index_recipes.iter().map(|recipe| recipe.key().clone()).collect();
+        names.sort_unstable();
+        for name in &names {
+            self.rebuild_index(name)?;
+        }
+        Ok(names.len())
+    }
+
+    /// Сохранить field и text индексы в файл (bincode)
+    /// 
+    /// Extractor-ы не сериализуются: после `load_indexes` индекс подхватывается
+    /// вызовом create_* с тем же именем без построения, если данные не изменились
+    /// (совпал fingerprint). Возвращает количество сохраненных индексов.
+    /// 
+    /// # Example
+    /// 
+    /// data.save_indexes("orders.idx")?;
+    /// 
+    #[cfg(feature = "serde")]
+    pub fn save_indexes(&self, path: impl AsRef<std::path::Path>) -> GlobalResult<usize> {
+        let snapshot = {
+            let _lock = self.write_lock.read_recursive();
+            let removed = self.removed_rows();
+            let mut indexes: Vec<SavedIndex> = self.index_recipes
+                .iter()
+                .filter_map(|recipe| {
+                    let index = self.indexes.get(recipe.key())?;
+                    Some(SavedIndex::from_index(recipe.key(), &index, recipe.fingerprint, removed.as_ref().clone()))
+                })
+                .collect();
+            indexes.sort_by(|left, right| left.name.cmp(&right.name));
+            IndexSnapshot { version: INDEX_SNAPSHOT_VERSION, indexes }
+        };
+        snapshot.write_to(path.as_ref()).map_err(|reason| {
+            GLobalError::FilterData(FilterDataError::IndexSnapshot { reason })
+        })?;
+        Ok(snapshot.indexes.len())
+    }
+
+    /// Загрузить индексы, сохраненные save_indexes (warm start)
+    /// 
+    /// Индексы не регистрируются сразу: create_* с тем же именем использует
+    /// загруженный индекс вместо построения, если совпали данные (fingerprint),
+    /// тип значений и параметры индекса. Иначе индекс строится как обычно.
+    /// Возвращает количество загруженных индексов.
+    /// 
+    /// # Example
+    /// 
+    /// let data = FilterData::from_vec(orders);
+    /// data.load_indexes("orders.idx")?;
+    /// data.create_field_index("price", |o: &Order| o.price)?;   // без построения
+    /// 
+    #[cfg(feature = "serde")]
+    pub fn load_indexes(&self, path: impl AsRef<std::path::Path>) -> GlobalResult<usize> {
+        let snapshot = IndexSnapshot::read_from(path.as_ref()).map_err(|reason| {
+            GLobalError::FilterData(FilterDataError::IndexSnapshot { reason })
+        })?;
+        let loaded = snapshot.indexes.len();
+        for saved in snapshot.indexes {
+            self.saved_indexes.insert(saved.name.clone(), saved);
+        }
+        Ok(loaded)
+    }
+
+    // Загруженные load_indexes индексы, еще не подхваченные create_*
+    #[cfg(feature = "serde")]
+    pub fn saved_index_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.saved_indexes.iter().map(|saved| saved.key().clone()).collect();
+        names.sort();
+        names
+    }
+
+    /// Заменить extractor field индекса с проверкой
+    /// 
+    /// Новый индекс строится рядом со старым, старый продолжает обслуживать
+    /// запросы. Возвращается `IndexReplacement` со сводкой различий (размер,
+    /// число уникальных значений, null-ы) - подмена происходит только после
+    /// явного `commit()`, без него новый индекс отбрасывается.
+    /// 
+    /// # Example
+    /// 
+    /// let replacement = data.replace_field_index_checked("price", |p| p.price_cents)?;
+    /// if replacement.diff().unique_values_ratio() > 0.5 {
+    ///     replacement.commit();
+    /// }
+    /// 
+    pub fn replace_field_index_checked<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<IndexReplacement<'_, T>>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        self.check_index_type_compability(name, INDEX_FIELD, IndexCompatibilityAction::Replace)
+            .map_err(GLobalError::Index)?;
+        let old = self.stored_index(name)?;
+        let content = Self::create_field_value_extractor(extractor.clone());
+        let builder = Self::field_index_builder(extractor, IndexField::build);
+        let version = self.next_index_version();
+        let items = self.index_base_items();
+        let index = self.build_index(&builder, &items);
+        let fingerprint = IndexFingerprint::compute(&items, &content);
+        let diff = IndexDiff {
+            name: name.to_string(),
+            old: Self::field_index_summary(&old),
+            new: Self::field_index_summary(&index),
+        };
+        Ok(IndexReplacement {
+            data: self,
+            diff,
+            index,
+            version,
+            fingerprint,
+            builder,
+            content,
+        })
+    }
+
+    fn field_index_summary(index: &IndexType<T>) -> IndexSummary {
+        match index.as_field() {
+            Some((field_index, _)) => IndexSummary {
+                type_name: field_index.type_name(),
+                rows: field_index.len(),
+                unique_values: field_index.unique_values_count(),
+                nulls: field_index.nulls().len(),
+            },
+            None => IndexSummary {
+                type_name: INDEX_TEXT,
+                rows: 0,
+                unique_values: 0,
+                nulls: 0,
+            },
+        }
+    }
+
+    // Текущая версия индекса (растет при каждом create/rebuild)
+    pub fn index_version(&self, name: &str) -> Option<u64> {
+        self.index_recipes.get(name).map(|recipe| recipe.version)
+    }
+
+    /// Построить field индекс в фоновом потоке
+    /// 
+    /// Пока индекс строится, `filter_by_field_ops` по этому имени работает
+    /// через predicate path (или через старую версию индекса, если она есть).
+    /// Готовый индекс атомарно подменяется в DashMap.
+    /// 
+    /// # Example
+    /// 
+    /// let handle = data.create_field_index_background("price", |p| p.price)?;
+    /// data.filter_by_field_ops("price", &[(FieldOperation::gt(100u64), Op::And)])?;
+    /// handle.wait();
+    /// 
+    pub fn create_field_index_background<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<IndexHandle>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        Self::check_row_limit(self.index_base_len())?;
+        let handle = IndexHandle::new(name);
+        let builder = Self::field_index_builder(extractor.clone(), IndexField::build);
+        let content = Self::create_field_value_extractor(extractor);
+        self.register_pending_index(name, &handle, Arc::clone(&builder), Arc::clone(&content));
+        self.spawn_index_build(name, &handle, builder, content)?;
+        Ok(handle)
+    }
+
+    /// Зарегистрировать field индекс с ленивым построением
+    /// 
+    /// Индекс не строится сразу: первый запрос по этому имени запускает
+    /// построение в фоновом потоке и сам выполняется через predicate path,
+    /// следующие запросы переходят на индекс, как только он готов.
+    /// 
+    /// # Example
+    /// 
+    /// let handle = data.create_field_index_lazy("price", |p| p.price)?;
+    /// assert_eq!(handle.status(), IndexBuildStatus::Deferred);
+    /// data.filter_by_field_ops("price", &[(FieldOperation::gt(100u64), Op::And)])?;   // запускает построение
+    /// handle.wait();
+    /// 
+    pub fn create_field_index_lazy<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+    ) -> GlobalResult<IndexHandle>
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        Self::check_row_limit(self.index_base_len())?;
+        let handle = IndexHandle::deferred(name);
+        let builder = Self::field_index_builder(extractor.clone(), IndexField::build);
+        let content = Self::create_field_value_extractor(extractor);
+        self.register_pending_index(name, &handle, builder, content);
+        Ok(handle)
+    }
+
+    // Предыдущее незавершенное построение с тем же именем отменяется
+    fn register_pending_index(
+        &self,
+        name: &str,
+        handle: &IndexHandle,
+        builder: IndexBuilder<T>,
+        extractor: ExtractorFieldValue<T>,
+    ) {
+        if let Some(previous) = self.index_builds.insert(
+            name.to_string(),
+            PendingIndex::new(handle.clone(), extractor, builder),
+        ) {
+            previous.handle.finish(IndexBuildStatus::Cancelled);
+        }
+    }
+
+    // Построение индекса из index_builds в фоновом потоке
+    fn spawn_index_build(
+        &self,
+        name: &str,
+        handle: &IndexHandle,
+        builder: IndexBuilder<T>,
+        content: ExtractorFieldValue<T>,
+    ) -> GlobalResult<()> {
+        let items = self.index_base_items();
+        let removed = self.removed_rows();
+        if let Some(mut pending) = self.index_builds.get_mut(name)
+            && pending.handle.same_build(handle)
+        {
+            pending.version = self.next_index_version();
+        }
+        let shared = BuildShared {
+            indexes: Arc::clone(&self.indexes),
+            index_recipes: Arc::clone(&self.index_recipes),
+            index_builds: Arc::clone(&self.index_builds),
+            write_lock: Arc::clone(&self.write_lock),
+        };
+        let worker_handle = handle.clone();
+        let index_name = name.to_string();
+        let spawned = std::thread::Builder::new()
+            .name(format!("tree_man-index-{}", name))
+            .spawn(move || {
+                let built = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    let index = builder(&items);
+                    BuiltIndex {
+                        index: if removed.is_empty() { index } else { index.without(&removed) },
+                        chunk_hashes: IndexFingerprint::chunk_hashes(&items, &content),
+                        rows: items.len(),
+                    }
+                }));
+                let status = match built {
+                    Ok(built) => Self::install_background_index(&shared, &index_name, &worker_handle, (builder, content), built),
+                    Err(_) => IndexBuildStatus::Failed("extractor panicked during index build".to_string()),
+                };
+                if matches!(status, IndexBuildStatus::Failed(_)) {
+                    shared.index_builds.remove_if(&index_name, |_, pending| {
+                        pending.handle.same_build(&worker_handle)
+                    });
+                }
+                worker_handle.finish(status);
+            });
+        if let Err(err) = spawned {
+            self.index_builds.remove_if(name, |_, pending| pending.handle.same_build(handle));
+            handle.finish(IndexBuildStatus::Failed(err.to_string()));
+            return Err(GLobalError::Index(IndexError::Build {
+                name: name.to_string(),
+                reason: err.to_string(),
+            }));
+        }
+        Ok(())
+    }
+
+    // Поставить построенный в фоне индекс. push / retain, прошедшие во время
+    // построения, записаны в PendingIndex: индекс их догоняет без блокировок,
+    // затем под write_lock проверяется, что новых нет, и индекс подменяется.
+    // Запись построения удаляется после вставки в indexes, поэтому
+    // resolve_field_index всегда видит одно из двух
+    fn install_background_index(
+        shared: &BuildShared<T>,
+        name: &str,
+        handle: &IndexHandle,
+        (builder, content): (IndexBuilder<T>, ExtractorFieldValue<T>),
+        mut built: BuiltIndex<T>,
+    ) -> IndexBuildStatus {
+        fn same<V>(left: &Option<Arc<V>>, right: &Option<Arc<V>>) -> bool {
+            match (left, right) {
+                (Some(left), Some(right)) => Arc::ptr_eq(left, right),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        loop {
+            let (rows, removed) = match shared.index_builds.get(name) {
+                Some(pending) if pending.handle.same_build(handle) => (pending.rows.clone(), pending.removed.clone()),
+                // Отменено: drop_index или новое построение с тем же именем
+                _ => return IndexBuildStatus::Cancelled,
+            };
+            let caught_up = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                built.catch_up(rows.as_deref(), removed.as_deref(), &builder, &content);
+            }));
+            if caught_up.is_err() {
+                return IndexBuildStatus::Failed("extractor panicked during index build".to_string());
+            }
+            let _guard = shared.write_lock.write();
+            let dashmap::Entry::Occupied(entry) = shared.index_builds.entry(name.to_string()) else {
+                return IndexBuildStatus::Cancelled;
+            };
+            let pending = entry.get();
+            if !pending.handle.same_build(handle) {
+                return IndexBuildStatus::Cancelled;
+            }
+            // Пока индекс догонял данные, прошли новые push / retain
+            if !same(&pending.rows, &rows) || !same(&pending.removed, &removed) {
+                continue;
+            }
+            let fingerprint = IndexFingerprint::from_chunk_hashes(built.rows, &built.chunk_hashes);
+            let swapped = Self::swap_index_version(
+                &shared.indexes,
+                &shared.index_recipes,
+                name,
+                pending.version,
+                built.index,
+                fingerprint,
+                Some((builder, content, INDEX_FIELD)),
+            );
+            entry.remove();
+            return if swapped { IndexBuildStatus::Ready } else { IndexBuildStatus::Superseded };
+        }
+    }
+
+    // push / retain при незавершенных построениях: запоминаем новое состояние
+    // данных и версию (вызывается под write_lock после обновления индексов)
+    fn record_pending_changes(&self) {
+        if self.index_builds.is_empty() {
+            return;
+        }
+        let DataStorage::Owned { source, removed, .. } = &self.storage else {
+            return;
+        };
+        for mut pending in self.index_builds.iter_mut() {
+            pending.rows = Some(source.load_full());
+            pending.removed = Some(removed.load_full());
+            pending.version = self.next_index_version();
+        }
+    }
+
+    // Первый запрос по ленивому индексу запускает построение;
+    // до готовности запросы идут через старую версию индекса или predicate path
+    fn start_deferred_build(&self, name: &str) {
+        let Some(pending) = self.index_builds.get(name) else {
+            return;
+        };
+        if !pending.handle.start() {
+            return;
+        }
+        let handle = pending.handle.clone();
+        let builder = Arc::clone(&pending.builder);
+        let content = Arc::clone(&pending.extractor);
+        drop(pending);
+        // Ошибка запуска видна в статусе handle-а
+        let _ = self.spawn_index_build(name, &handle, builder, content);
+    }
+
+    // Статус фонового построения (None - построение не запускалось или уже завершено)
+    pub fn index_build_status(&self, name: &str) -> Option<IndexBuildStatus> {
+        self.index_builds.get(name).map(|pending| pending.handle.status())
+    }
+
+    // Строки, по которым строятся индексы.
+    // Owned - весь source, Indexed - исходная выборка представления (source_indices).
+    // Позиция в индексе = позиция в этом списке, поэтому индекс не зависит
+    // от текущего уровня drill-down.
+    fn index_base_items(&self) -> Arc<Rows<T>> {
+        match &self.storage {
+            DataStorage::Owned { source, .. } => source.load_full(),
+            DataStorage::Indexed { parent_data, source_indices, .. } => {
+                match parent_data.upgrade() {
+                    Some(parent) => Arc::new(Rows::from(
+                        source_indices
+                            .iter()
+                            .filter_map(|&idx| parent.get(idx).cloned())
+                            .collect::<Vec<_>>()
+                    )),
+                    None => Arc::default(),
+                }
+            }
+        }
+    }
+
+    // Перевод позиций индекса в индексы хранилища (source / parent_data)
+    fn index_positions_to_ids(&self, bitmap: RoaringBitmap) -> RoaringBitmap {
+        match &self.storage {
+            DataStorage::Owned { .. } => bitmap,
+            DataStorage::Indexed { source_indices, .. } => bitmap
+                .iter()
+                .filter_map(|pos| source_indices.get(pos as usize))
+                .map(|&idx| idx as u32)
+                .collect(),
+        }
+    }
+
+    /// Индекс по имени
+    /// 
+    /// Индекс построен по всем строкам, поэтому под base filter недоступен
+    /// (`BaseFilterActive`); доверенный код берет `trusted_access()`
+    /// до `set_base_filter`.
+    pub fn get_index(&self, name: &str) -> GlobalResult<Arc<IndexType<T>>> {
+        if self.has_base_filter() {
+            return Err(GLobalError::FilterData(FilterDataError::BaseFilterActive));
+        }
+        self.stored_index(name)
+    }
+
+    // Индекс без проверки base filter: вызывающий сам ограничивает результат
+    fn stored_index(&self, name: &str) -> GlobalResult<Arc<IndexType<T>>> {
+        self.indexes.get(name)
+            .ok_or(GLobalError::Index(IndexError::NotFound {
+                name: name.to_string(),
+            }))
+            .map(|guard| guard.clone())
+    }
+
+    fn apply_field_operations(
+        &self,
+        field_index: &IndexFieldEnum,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<RoaringBitmap> {
+        let bitmap = if self.null_mode() == NullMode::ThreeValued && field_index.has_nulls() {
+            // Unknown строки в выборку не попадают
+            field_index.filter_operations_tri(operations).map(TriBitmap::resolve)
+        } else if operations.len() == 1 {
+            field_index.filter_operation(&operations[0].0)
+        } else {
+            field_index.filter_operations(operations)
+        };
+        let result = bitmap.map_err(|err|GLobalError::Index(IndexError::Field(err)))?;
+        Ok(result)
+    }
+
+    fn apply_field_bitmap(
+        &self,
+        bitmap: RoaringBitmap,
+        step: LevelStep,
+    ) -> GlobalResult<&Self> {
+        if bitmap.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndex {
+                name: step.description,
+            }));
+        }
+        
+        let final_bitmap = self.restrict_to_current(bitmap);
+        if final_bitmap.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
+                name: step.description,
+            }));
+        }
+        self.apply_filtered_items_with_bitmap(final_bitmap, step)
+    }
+
+    // Позиции индекса -> индексы хранилища, пересеченные с текущей выборкой
+    fn restrict_to_current(&self, bitmap: RoaringBitmap) -> RoaringBitmap {
+        let bitmap = self.index_positions_to_ids(bitmap);
+        let current_mask_opt = self.source_indices_mask.load();
+        if let DataStorage::Indexed { current_indices, .. } = &self.storage {
+            // Для Indexed storage пересекаем с текущей выборкой
+            let current_bitmap: RoaringBitmap = current_indices.load()
+                .iter()
+                .map(|&i| i as u32)
+                .collect();
+            current_bitmap & bitmap
+        } else if let Some(current_mask) = current_mask_opt.as_ref() {
+            // Маска есть - используем напрямую
+            &**current_mask & &bitmap
+        } else {
+            // маски нет - создаем из current_indices
+            match &self.storage {
+                DataStorage::Owned { current_indices, source, .. } => {
+                    let current = current_indices.load();
+                    // Удаленные строки тоже требуют маски: в только что построенном индексе их нет,
+                    // но current_indices короче source
+                    if current.len() < source.load().len() {
+                        // Есть фильтрация - создаем маску из current_indices
+                        let current_bitmap: RoaringBitmap = current.iter()
+                            .map(|&i| i as u32)
+                            .collect();
+                        // Сохраняем маску для следующих индексных операций
+                        self.source_indices_mask.store(Arc::new(Some(Arc::new(current_bitmap.clone()))));
+                        // Пересечение с индексом
+                        current_bitmap & bitmap
+                    } else {
+                        // Нет фильтрации - используем индекс напрямую
+                        bitmap
+                    }
+                }
+                DataStorage::Indexed { .. } => bitmap,
+            }
+        }
+    }
+
+    // Проверка предиката только для переданных индексов хранилища
+    fn filter_ids_by_predicate<F>(&self, ids: &RoaringBitmap, predicate: F) -> GlobalResult<RoaringBitmap>
+    where
+        F: Fn(&T) -> bool + Send + Sync,
+    {
+        let data = self.storage_rows()
+            .ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        let matches = |id: u32| data.get(id as usize).is_some_and(|item| predicate(item));
+        let result = if ids.len() > 10_000 {
+            let candidates: Vec<u32> = ids.iter().collect();
+            candidates.into_par_iter().filter(|&id| matches(id)).collect::<Vec<u32>>()
+                .into_iter()
+                .collect()
+        } else {
+            ids.iter().filter(|&id| matches(id)).collect()
+        };
+        Ok(result)
+    }
+
+    fn index_base_len(&self) -> usize {
+        match &self.storage {
+            DataStorage::Owned { source, .. } => source.load().len(),
+            DataStorage::Indexed { source_indices, .. } => source_indices.len(),
+        }
+    }
+
+    #[inline(always)]
+    fn evaluate_field_operations(
+        value: &FieldValue,
+        operations: &[(FieldOperation, Op)],
+    ) -> bool {
+        FieldOperation::evaluate_operations(value, operations)
+    }
+
+    // Скомпилированная цепочка операций (кешируется по списку операций)
+    fn compiled_operations(&self, operations: &[(FieldOperation, Op)]) -> CompiledOperations {
+        if let Some(compiled) = self.compiled_operations.get(operations) {
+            return Arc::clone(compiled.value());
+        }
+        let compiled = FieldOperation::compile_operations(operations);
+        if self.compiled_operations.len() >= COMPILED_OPERATIONS_CACHE_LIMIT {
+            self.compiled_operations.clear();
+        }
+        self.compiled_operations.insert(operations.to_vec(), Arc::clone(&compiled));
+        compiled
+    }
+
+    fn build_field_predicate(
+        &self,
+        fields: &[FieldPredicateEntry<'_, T>],
+    ) -> GlobalResult<impl Fn(&T) -> bool + Send + Sync + '_> {
+        let mut field_predicates = fields.iter()
+        .map(|(extractor,operations)| {
+            ((*extractor).clone(),self.compiled_operations(operations))
+            })
+        .collect::<Vec<(ExtractorFieldValue<T>, CompiledOperations)>>();
+        self.order_field_predicates(&mut field_predicates);
+        Ok(move |item: &T| -> bool {
+            for (extractor, matches) in &field_predicates {
+                let field_value = extractor(item);
+                if !matches(&field_value) {
+                    return false;
+                }
+            }
+            true
+        })
+    }
+
+    // Порядок проверки полей для short-circuit: первым идет условие, которое
+    // дешевле всего отсекает строку. По выборке текущих строк оцениваются доля
+    // прошедших и стоимость extractor-а (строки дороже), ранг = cost / (1 - selectivity).
+    fn order_field_predicates(&self, predicates: &mut [(ExtractorFieldValue<T>, CompiledOperations)]) {
+        if predicates.len() < 2 {
+            return;
+        }
+        let Some(data) = self.storage_rows() else {
+            return;
+        };
+        let indices = self.current_indices();
+        let step = (indices.len() / PREDICATE_ORDER_SAMPLE_SIZE).max(1);
+        let sample: Vec<&Arc<T>> = indices
+            .iter()
+            .step_by(step)
+            .take(PREDICATE_ORDER_SAMPLE_SIZE)
+            .filter_map(|&idx| data.get(idx))
+            .collect();
+        if sample.is_empty() {
+            return;
+        }
+        let mut ranked: Vec<(f64, (ExtractorFieldValue<T>, CompiledOperations))> = predicates
+            .iter()
+            .map(|(extractor, matches)| {
+                let mut passed = 0usize;
+                let mut is_string = false;
+                for item in &sample {
+                    let value = extractor(item);
+                    is_string |= matches!(value, FieldValue::String(_));
+                    if matches(&value) {
+                        passed += 1;
+                    }
+                }
+                let selectivity = passed as f64 / sample.len() as f64;
+                let cost = if is_string { STRING_EXTRACTOR_COST } else { 1.0 };
+                let rank = cost / (1.0 - selectivity).max(0.01);
+                (rank, (Arc::clone(extractor), Arc::clone(matches)))
+            })
+            .collect();
+        // Стабильная сортировка: при равных рангах сохраняется порядок вызывающего
+        ranked.sort_by(|left, right| left.0.total_cmp(&right.0));
+        for (slot, (_, predicate)) in predicates.iter_mut().zip(ranked) {
+            *slot = predicate;
+        }
+    }
+
+    fn estimate_selectivity_from_indexes(
+        &self, 
+        container: &[FieldPlanEntry<'_>]
+    ) -> f64 {
+        if container.is_empty() {
+            return 1.0;
+        }
+        let mut combined_selectivity = 1.0;
+        // Поля с совместной статистикой (analyze) оцениваются вместе
+        let mut covered = vec![false; container.len()];
+        while let Some(group) = self.best_column_group(container, &covered) {
+            let selectivity = group.selectivity(|row| {
+                container.iter().all(|(name, _, operations)| {
+                    match group.column_position(name) {
+                        Some(pos) => Self::evaluate_field_operations(&row[pos], operations),
+                        None => true,
+                    }
+                })
+            });
+            combined_selectivity *= selectivity;
+            for (n, (name, _, _)) in container.iter().enumerate() {
+                if group.column_position(name).is_some() {
+                    covered[n] = true;
+                }
+            }
+        }
+        // Остальные поля - в предположении независимости
+        for (n, (name, index, operations)) in container.iter().enumerate() {
+            if covered[n] {
+                continue;
+            }
+            let selectivity = self.estimate_field_selectivity(name, index, operations);
+            combined_selectivity *= selectivity;
+            if combined_selectivity < 0.001 {
+                return combined_selectivity;
+            }
+        }
+        combined_selectivity
+    }
+
+    // Сравнения и диапазоны с числовыми операндами оцениваются по гистограмме
+    // field_stats, остальные операции - по статистике самого индекса
+    fn estimate_field_selectivity(
+        &self,
+        name: &str,
+        index: &IndexFieldEnum,
+        operations: &[(FieldOperation, Op)],
+    ) -> f64 {
+        let numeric_range = operations.iter().any(|(operation, _)| match operation {
+            FieldOperation::Gt(value) | FieldOperation::Gte(value)
+            | FieldOperation::Lt(value) | FieldOperation::Lte(value) => value.as_f64().is_some(),
+            FieldOperation::Range(start, _) => start.as_f64().is_some(),
+            _ => false,
+        });
+        let stats = if !numeric_range {
+            None
+        } else if index.unique_values_count() <= PLANNER_STATS_MAX_UNIQUE {
+            self.field_stats_for(name, index)
+        } else {
+            // Для очень больших индексов статистика не считается в планировщике,
+            // используется только уже посчитанная (field_stats)
+            self.cached_field_stats(name)
+        };
+        match stats {
+            Some(stats) => combine_selectivity(operations, |operation| {
+                stats
+                    .operation_selectivity(operation)
+                    .unwrap_or_else(|| index.estimate_operation_selectivity(operation))
+            }),
+            None => index.estimate_operations_selectivity(operations),
+        }
+    }
+
+    // Наибольшая группа статистики, все поля которой есть среди непокрытых полей запроса
+    fn best_column_group(
+        &self,
+        container: &[FieldPlanEntry<'_>],
+        covered: &[bool],
+    ) -> Option<Arc<ColumnGroupStats>> {
+        if self.column_stats.is_empty() {
+            return None;
+        }
+        self.column_stats
+            .iter()
+            .filter(|group| {
+                group.fields().len() > 1 && group.fields().iter().all(|field| {
+                    container.iter().enumerate().any(|(n, (name, _, _))| !covered[n] && name == field)
+                })
+            })
+            .max_by_key(|group| group.fields().len())
+            .map(|group| Arc::clone(group.value()))
+    }
+
+    /// Собрать совместную статистику по нескольким полям
+    /// 
+    /// Строится по равномерной выборке строк и используется планировщиком
+    /// вместо произведения селективностей, когда в запросе есть все поля группы.
+    /// 
+    /// # Example
+    /// 
+    /// data.analyze(&["status", "error_code"])?;
+    /// // status=500 AND error_code=E42 теперь оценивается с учетом корреляции
+    /// 
+    pub fn analyze(&self, fields: &[&str]) -> GlobalResult<&Self> {
+        if fields.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let extractors = fields
+            .iter()
+            .map(|name| match self.resolve_field_index(name)? {
+                ResolvedField::Index(index) => index
+                    .as_field()
+                    .map(|(_, extractor)| Arc::clone(extractor))
+                    .ok_or(GLobalError::Index(IndexError::NotFound { name: name.to_string() })),
+                ResolvedField::Pending(extractor) => Ok(extractor),
+            })
+            .collect::<GlobalResult<Vec<ExtractorFieldValue<T>>>>()?;
+        let field_names: Vec<String> = fields.iter().map(|name| name.to_string()).collect();
+        let stats = ColumnGroupStats::build(
+            &self.index_base_items(),
+            field_names.clone(),
+            &extractors,
+            ANALYZE_SAMPLE_SIZE,
+        );
+        self.column_stats.insert(Self::column_group_key(&field_names), Arc::new(stats));
+        Ok(self)
+    }
+
+    fn column_group_key(fields: &[String]) -> String {
+        let mut sorted = fields.to_vec();
+        sorted.sort();
+        sorted.join(",")
+    }
+
+    // Группы полей, для которых собрана статистика
+    pub fn analyzed_fields(&self) -> Vec<Vec<String>> {
+        self.column_stats
+            .iter()
+            .map(|group| group.fields().to_vec())
+            .collect()
+    }
+
+    pub fn clear_analyze(&self) {
+        self.column_stats.clear();
+    }
+
+    /// Оценка селективности запроса, которой пользуется планировщик
+    pub fn estimate_fields_selectivity(
+        &self,
+        fields: &[(&str, &[(FieldOperation, Op)])],
+    ) -> GlobalResult<f64> {
+        let indexes: Vec<Arc<IndexType<T>>> = fields
+            .iter()
+            .map(|(name, _)| self.stored_index(name))
+            .collect::<Result<_, _>>()?;
+        let mut container = Vec::with_capacity(fields.len());
+        for (n, (name, operations)) in fields.iter().enumerate() {
+            let (field_index, _) = indexes[n].as_field()
+                .ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: indexes[n].index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }))?;
+            container.push((*name, field_index, *operations));
+        }
+        Ok(self.estimate_selectivity_from_indexes(&container))
+    }
+
+    fn need_to_use_index(
+        &self,
+        fields: &[FieldPlanEntry<'_>],
+        estimate_selectivity: f64,
+    ) -> GlobalResult<bool> {
+        let decision = self.index_path_decision(fields, estimate_selectivity);
+        if !decision.use_index {
+            self.log_index_rejected(fields, decision.reason, decision.field, estimate_selectivity, decision.threshold);
+        }
+        Ok(decision.use_index)
+    }
+
+    // Решение эвристик планировщика (QueryHint::Auto): причина, поле и порог
+    fn index_path_decision<'a>(
+        &self,
+        fields: &[FieldPlanEntry<'a>],
+        estimate_selectivity: f64,
+    ) -> PathDecision<'a> {
+        let hints: Vec<PlannerHints> = fields
+            .iter()
+            .map(|(name, _, _)| self.planner_hints(name).unwrap_or_default())
+            .collect();
+        // Явные подсказки пользователя важнее эвристик
+        if let Some(((name, _, _), _)) = fields.iter().zip(&hints).find(|(_, hint)| hint.usage == IndexUsage::Never) {
+            return PathDecision::predicate("hint_never", name, None);
+        }
+        if hints.iter().all(|hint| hint.usage == IndexUsage::Always) {
+            return PathDecision::index("hint_always");
+        }
+
+        if self.len() < SMALL_DATASET_THRESHOLD {
+            return PathDecision::predicate("small_dataset", "", Some(SMALL_DATASET_THRESHOLD as f64));
+        }
+
+        if let Some(((name, _, _), hint)) = fields.iter().zip(&hints).find(|((name, index, operations), hint)| {
+            hint.usage == IndexUsage::Auto
+                && operations.iter().any(|(op, _)| !index.is_efficient_for_hints(op, hint))
+                && !self.has_precomputed_bitmap(name, operations)
+        }) {
+            let cardinality_threshold = hint.cardinality_low_threshold.unwrap_or(CARDINALITY_RATIO_LOW_THRESHOLD);
+            return PathDecision::predicate("inefficient_operation", name, Some(cardinality_threshold));
+        }
+        
+        let selectivity_threshold = hints
+            .iter()
+            .filter_map(|hint| hint.selectivity_threshold)
+            .reduce(f64::max)
+            .unwrap_or(SELECTIVITY_THRESHOLD);
+        // Прогретые бакеты и кеш результатов отвечают без обхода индекса при любой селективности
+        if estimate_selectivity > selectivity_threshold
+            && !fields.iter().all(|(name, _, operations)| self.has_precomputed_bitmap(name, operations))
+        {
+            return PathDecision::predicate("selectivity", "", Some(selectivity_threshold));
+        }
+        
+        PathDecision::index("estimate")
+    }
+
+    // Отказ от index path: debug запись с target "tree_man::planner" (feature = "planner-log").
+    // field - поле, из-за которого отказ (пусто, если причина общая), threshold - порог причины
+    // (строки для small_dataset, кардинальность для inefficient_operation, селективность)
+    #[cfg(feature = "planner-log")]
+    fn log_index_rejected(
+        &self,
+        fields: &[FieldPlanEntry],
+        reason: &str,
+        field: &str,
+        estimate_selectivity: f64,
+        threshold: Option<f64>,
+    ) {
+        if !log::log_enabled!(target: "tree_man::planner", log::Level::Debug) {
+            return;
+        }
+        let names = fields.iter().map(|(name, _, _)| *name).collect::<Vec<_>>().join(",");
+        let operations = fields
+            .iter()
+            .flat_map(|(_, _, operations)| operations.iter().map(|(operation, _)| operation.to_string()))
+            .collect::<Vec<_>>()
+            .join("; ");
+        log::debug!(
+            target: "tree_man::planner",
+            reason = reason,
+            fields = names.as_str(),
+            field = field,
+            operations = operations.as_str(),
+            rows = self.len(),
+            selectivity = estimate_selectivity,
+            threshold = threshold;
+            "index path rejected ({reason}): fields [{names}], selectivity {estimate_selectivity:.4}, threshold {threshold:?}"
+        );
+    }
+
+    #[cfg(not(feature = "planner-log"))]
+    #[inline(always)]
+    fn log_index_rejected(
+        &self,
+        _fields: &[FieldPlanEntry],
+        _reason: &str,
+        _field: &str,
+        _estimate_selectivity: f64,
+        _threshold: Option<f64>,
+    ) {
+    }
+
+    /// Подсказки планировщику для field индекса
+    ///
+    /// Переопределяют пороги эвристик эффективности или режим использования индекса.
+    ///
+    /// # Example
+    ///
+    /// data.set_planner_hints("status", PlannerHints::new().with_skew_ratio(0.9))?;
+    ///
+    pub fn set_planner_hints(&self, name: &str, hints: PlannerHints) -> GlobalResult<&Self> {
+        self.field_extractor(name)?;
+        self.planner_hints.insert(name.to_string(), hints);
+        Ok(self)
+    }
+
+    pub fn planner_hints(&self, name: &str) -> Option<PlannerHints> {
+        if self.planner_hints.is_empty() {
+            return None;
+        }
+        self.planner_hints.get(name).map(|hints| hints.clone())
+    }
+
+    pub fn clear_planner_hints(&self, name: &str) {
+        self.planner_hints.remove(name);
+    }
+
+    // Всегда использовать индекс (минуя эвристики и оценку селективности)
+    pub fn always_use_index(&self, name: &str) -> GlobalResult<&Self> {
+        self.set_index_usage(name, IndexUsage::Always)
+    }
+
+    // Никогда не использовать индекс - только predicate path
+    pub fn never_use_index(&self, name: &str) -> GlobalResult<&Self> {
+        self.set_index_usage(name, IndexUsage::Never)
+    }
+
+    fn set_index_usage(&self, name: &str, usage: IndexUsage) -> GlobalResult<&Self> {
+        let hints = self.planner_hints(name).unwrap_or_default().with_usage(usage);
+        self.set_planner_hints(name, hints)
+    }
+
+    /// Режим вычисления операций над nullable полями (по умолчанию двузначный)
+    /// 
+    /// # Example
+    /// 
+    /// data.set_null_mode(NullMode::ThreeValued);
+    /// // NOT (discount = 0) не включает строки без discount
+    /// data.filter_query(&QueryExpr::not(QueryExpr::field("discount", ops)))?;
+    /// 
+    pub fn set_null_mode(&self, mode: NullMode) -> &Self {
+        *self.null_mode.write() = mode;
+        self
+    }
+
+    pub fn null_mode(&self) -> NullMode {
+        *self.null_mode.read()
+    }
+
+    pub fn filter_by_field_ops(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<&Self> {
+        self.filter_by_field_ops_with_hint(name, operations, QueryHint::Auto)
+    }
+
+    /// filter_by_field_ops с подсказкой для конкретного вызова
+    ///
+    /// # Example
+    ///
+    /// data.filter_by_field_ops_with_hint("price", &[
+    ///     (FieldOperation::gte(100u64), Op::And),
+    /// ], QueryHint::ForceIndex)?;
+    ///
+    pub fn filter_by_field_ops_with_hint(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+        hint: QueryHint,
+    ) -> GlobalResult<&Self> {
+        if operations.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        self.filter_by_fields_ops_with_hint(&[(name, operations)], hint)
+    }
+
+    /// Подготовить параметризованный запрос по одному полю
+    /// 
+    /// Операции строятся замыканием из параметров при каждом выполнении,
+    /// а путь (index / predicate) выбирается планировщиком один раз -
+    /// при первом выполнении - и затем переиспользуется.
+    /// 
+    /// # Example
+    /// 
+    /// let by_price = data.prepare_field_query("price", |p: &Params| {
+    ///     vec![(FieldOperation::gte(p.min), Op::And)]
+    /// })?;
+    /// for params in requests {
+    ///     by_price.execute(&params)?;
+    ///     respond(data.items());
+    ///     data.reset_to_source();
+    /// }
+    /// 
+    pub fn prepare_field_query<P, F>(&self, name: &str, operations: F) -> GlobalResult<PreparedFieldQuery<'_, T, P>>
+    where
+        F: Fn(&P) -> Vec<(FieldOperation, Op)> + Send + Sync + 'static,
+    {
+        self.resolve_field_index(name)?;
+        Ok(PreparedFieldQuery {
+            data: self,
+            name: name.to_string(),
+            operations: Box::new(operations),
+            plan: OnceLock::new(),
+        })
+    }
+
+    // План prepared запроса; None - у поля нет готового индекса, план не кешируется
+    fn plan_field_query(&self, name: &str, operations: &[(FieldOperation, Op)]) -> GlobalResult<Option<QueryHint>> {
+        let ResolvedField::Index(index_ref) = self.resolve_field_index(name)? else {
+            return Ok(None);
+        };
+        let Some((field_index, _)) = index_ref.as_field() else {
+            return Ok(None);
+        };
+        // Null и поля-массивы видны только в индексе
+        if field_index.requires_index() {
+            return Ok(Some(QueryHint::ForceIndex));
+        }
+        let container = [(name, field_index, operations)];
+        let raw_estimate = self.estimate_selectivity_from_indexes(&container);
+        let estimate = self.planner_feedback
+            .get(&Self::planner_feedback_key(std::iter::once(name)))
+            .map(|feedback| feedback.apply(raw_estimate))
+            .unwrap_or(raw_estimate);
+        Ok(Some(if self.need_to_use_index(&container, estimate)? {
+            QueryHint::ForceIndex
+        } else {
+            QueryHint::ForceScan
+        }))
+    }
+
+    // Поле для фильтрации: готовый индекс или индекс в процессе построения
+    fn resolve_field_index(&self, name: &str) -> GlobalResult<ResolvedField<T>> {
+        if !self.index_builds.is_empty() {
+            self.start_deferred_build(name);
+        }
+        // Сначала строящийся, потом готовый: фоновое построение ставит индекс
+        // до удаления записи о построении, так что при таком порядке чтения
+        // индекс не пропадает в момент подмены
+        let pending = self.index_builds.get(name).map(|pending| Arc::clone(&pending.extractor));
+        if let Some(index_ref) = self.indexes.get(name) {
+            if !index_ref.is_field() {
+                return Err(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }));
+            }
+            return Ok(ResolvedField::Index(Arc::clone(index_ref.value())));
+        }
+        if let Some(extractor) = pending {
+            return Ok(ResolvedField::Pending(extractor));
+        }
+        if let Some(computed) = self.computed_fields.get(name) {
+            return Ok(ResolvedField::Pending(Arc::clone(&computed.extractor)));
+        }
+        Err(GLobalError::Index(IndexError::NotFound { name: name.to_string() }))
+    }
+
+    // Extractor field индекса (готового или строящегося в фоне)
+    pub(crate) fn field_extractor(&self, name: &str) -> GlobalResult<ExtractorFieldValue<T>> {
+        match self.resolve_field_index(name)? {
+            ResolvedField::Index(index_ref) => index_ref
+                .as_field()
+                .map(|(_, extractor)| Arc::clone(extractor))
+                .ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                })),
+            ResolvedField::Pending(extractor) => Ok(extractor),
+        }
+    }
+
+    /// Зарегистрировать вычисляемое поле
+    /// 
+    /// Поле доступно по имени везде, где ожидается field индекс: фильтры,
+    /// count_by_field_ops, sum/avg, агрегаты подгрупп, экспорт, сортировка
+    /// и `GroupData::group_by_field`. Без индекса фильтрация идет predicate
+    /// path; `materialize_computed_field` строит по нему обычный field индекс.
+    /// Индекс с тем же именем имеет приоритет над полем.
+    /// 
+    /// # Example
+    /// 
+    /// data.register_computed_field("margin", |order: &Order| order.revenue - order.cost);
+    /// data.filter_by_field_ops("margin", &[(FieldOperation::lt(0i64), Op::And)])?;
+    /// let total = data.sum_field("margin")?;
+    /// 
+    pub fn register_computed_field<V,F>(&self, name: &str, extractor: F) -> &Self
+    where 
+        V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+        F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+        IndexField<V>: IntoIndexFieldEnum,
+        V: Into<FieldValue> + 'static, 
+    {
+        self.computed_fields.insert(
+            name.to_string(),
+            ComputedField {
+                extractor: Self::create_field_value_extractor(extractor.clone()),
+                builder: Self::field_index_builder(extractor, IndexField::build),
+            },
+        );
+        self
+    }
+
+    // Построить field индекс по вычисляемому полю (под тем же именем)
+    pub fn materialize_computed_field(&self, name: &str) -> GlobalResult<&Self> {
+        let (builder, content) = self.computed_fields.get(name)
+            .map(|computed| (Arc::clone(&computed.builder), Arc::clone(&computed.extractor)))
+            .ok_or(GLobalError::Index(IndexError::NotFound { name: name.to_string() }))?;
+        if self.has_index(name) {
+            self.check_index_type_compability(
+                name, 
+                INDEX_FIELD, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
+        }
+        self.install_index(name, builder, content, INDEX_FIELD)?;
+        Ok(self)
+    }
+
+    pub fn computed_fields(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.computed_fields.iter().map(|entry| entry.key().clone()).collect();
+        names.sort();
+        names
+    }
+
+    // Материализованный индекс поля остается - его удаляет drop_index
+    pub fn drop_computed_field(&self, name: &str) -> bool {
+        self.computed_fields.remove(name).is_some()
+    }
+
+    /// Текущая выборка, отсортированная по значению field индекса или computed поля
+    /// 
+    /// Уровни drill-down не меняются. Для равных значений сохраняется порядок выборки.
+    /// 
+    /// # Example
+    /// 
+    /// let by_margin = data.sorted_items_by_field("margin", true)?; // по убыванию
+    /// 
+    pub fn sorted_items_by_field(&self, name: &str, descending: bool) -> GlobalResult<Vec<Arc<T>>> {
+        let extractor = self.field_extractor(name)?;
+        let mut keyed: Vec<(FieldValue, Arc<T>)> = self.items()
+            .par_iter()
+            .map(|item| (extractor(item), Arc::clone(item)))
+            .collect();
+        if descending {
+            keyed.par_sort_by(|left, right| right.0.cmp(&left.0));
+        } else {
+            keyed.par_sort_by(|left, right| left.0.cmp(&right.0));
+        }
+        Ok(keyed.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Новый уровень: текущая выборка, переставленная по значению поля
+    /// 
+    /// Для field индекса порядок берется из bitmap-ов значений по возрастанию
+    /// (без чтения строк), для computed поля без индекса - сортировкой значений
+    /// extractor-а. Строки без значения (nulls) - в конце при любом направлении,
+    /// для равных значений сохраняется порядок выборки. `items()` и экспорт
+    /// возвращают строки в этом порядке; следующий фильтр снова дает порядок источника.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_by_field_ops("category", &[(FieldOperation::eq("books"), Op::And)])?;
+    /// data.sort_by_field("price", SortOrder::Desc)?;
+    /// let expensive = data.items();
+    /// 
+    pub fn sort_by_field(&self, name: &str, order: SortOrder) -> GlobalResult<&Self> {
+        let indices = match self.resolve_field_index(name)? {
+            ResolvedField::Index(index_ref) => {
+                let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }))?;
+                self.sorted_indices_by_index(field_index, order)
+            }
+            ResolvedField::Pending(extractor) => self.sorted_indices_by_scan(&extractor, order),
+        };
+        let description = format!("sort {name} {order:?}");
+        let step = LevelStep::new(description, QueryStep::Sort { name: name.to_string(), order });
+        let _guard = self.write_lock.write();
+        self.apply_level_indices(indices, step)?;
+        Ok(self)
+    }
+
+    // Текущая выборка в порядке значений индекса: ранг строки - номер группы
+    // значения в sorted порядке, nulls и строки вне индекса - после всех групп
+    fn sorted_indices_by_index(&self, field_index: &IndexFieldEnum, order: SortOrder) -> Vec<usize> {
+        let current = self.current_indices();
+        let Some(&max_idx) = current.iter().max() else {
+            return Vec::new();
+        };
+        let mut rest = RoaringBitmap::clone(&self.current_id_bitmap());
+        let mut ranks = vec![u32::MAX; max_idx + 1];
+        let groups = field_index.value_bitmaps();
+        let last = groups.len().saturating_sub(1);
+        for (position, (_, bitmap)) in groups.into_iter().enumerate() {
+            if rest.is_empty() {
+                break;
+            }
+            let rows = self.index_positions_to_ids(bitmap.clone()) & &rest;
+            rest -= &rows;
+            let rank = match order {
+                SortOrder::Asc => position,
+                SortOrder::Desc => last - position,
+            } as u32;
+            for idx in rows {
+                ranks[idx as usize] = rank;
+            }
+        }
+        // Стабильная сортировка - для равных значений порядок выборки
+        let mut indices = current.to_vec();
+        indices.par_sort_by_key(|&idx| ranks[idx]);
+        indices
+    }
+
+    // Индексы хранилища текущей выборки; для Owned - сохраненная маска, если есть
+    fn current_id_bitmap(&self) -> Arc<RoaringBitmap> {
+        if let DataStorage::Owned { .. } = &self.storage
+            && let Some(mask) = self.source_indices_mask.load().as_ref()
+        {
+            return Arc::clone(mask);
+        }
+        Arc::new(self.current_indices().iter().map(|&idx| idx as u32).collect())
+    }
+
+    /// k строк текущей выборки с наибольшими (Desc) / наименьшими (Asc) значениями поля
+    /// 
+    /// Для field индекса группы значений перебираются в sorted порядке до набора
+    /// k строк - выборка не материализуется и не сортируется. Для равных значений -
+    /// порядок строк источника, строки без значения добирают результат в конце.
+    /// Computed поле без индекса - сортировка значений extractor-а.
+    /// Уровни drill-down не меняются.
+    /// 
+    /// # Example
+    /// 
+    /// let best = data.top_k_by_field("score", 10, SortOrder::Desc)?;
+    /// 
+    pub fn top_k_by_field(&self, name: &str, k: usize, order: SortOrder) -> GlobalResult<Vec<Arc<T>>> {
+        let indices = match self.resolve_field_index(name)? {
+            ResolvedField::Index(index_ref) => {
+                let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }))?;
+                self.top_k_indices_by_index(field_index, k, order)
+            }
+            ResolvedField::Pending(extractor) => {
+                let mut indices = self.sorted_indices_by_scan(&extractor, order);
+                indices.truncate(k);
+                indices
+            }
+        };
+        let data = self.storage_rows()
+            .ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        Ok(indices.into_iter().filter_map(|idx| data.get(idx).cloned()).collect())
+    }
+
+    fn top_k_indices_by_index(&self, field_index: &IndexFieldEnum, k: usize, order: SortOrder) -> Vec<usize> {
+        let mut rest = RoaringBitmap::clone(&self.current_id_bitmap());
+        let mut indices = Vec::with_capacity(k.min(rest.len() as usize));
+        let mut groups = field_index.value_bitmaps();
+        if order == SortOrder::Desc {
+            groups.reverse();
+        }
+        for (_, bitmap) in groups {
+            if indices.len() >= k || rest.is_empty() {
+                break;
+            }
+            let rows = self.index_positions_to_ids(bitmap.clone()) & &rest;
+            rest -= &rows;
+            indices.extend(rows.iter().take(k - indices.len()).map(|idx| idx as usize));
+        }
+        // nulls и строки вне индекса
+        let missing = k.saturating_sub(indices.len());
+        indices.extend(rest.iter().take(missing).map(|idx| idx as usize));
+        indices
+    }
+
+    /// Страница текущей выборки в ее порядке (с учетом sort_by_field)
+    /// 
+    /// Читаются только строки страницы, выборка целиком не материализуется.
+    /// 
+    /// # Example
+    /// 
+    /// data.sort_by_field("created_at", SortOrder::Desc)?;
+    /// let second_page = data.page(20, 20);
+    /// 
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<Arc<T>> {
+        let Some(data) = self.storage_rows() else {
+            return Vec::new();
+        };
+        let indices = self.current_indices();
+        indices
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|&idx| data.get(idx).cloned())
+            .collect()
+    }
+
+    pub fn pages_count(&self, page_size: usize) -> usize {
+        self.len().div_ceil(page_size.max(1))
+    }
+
+    fn sorted_indices_by_scan(&self, extractor: &ExtractorFieldValue<T>, order: SortOrder) -> Vec<usize> {
+        let mut keyed: Vec<(FieldValue, usize)> = self.items_with_ids()
+            .into_par_iter()
+            .map(|(idx, item)| (extractor(&item), idx))
+            .collect();
+        // Стабильная сортировка, Null - в конце
+        keyed.par_sort_by(|left, right| {
+            left.0.is_null().cmp(&right.0.is_null()).then_with(|| match order {
+                SortOrder::Asc => left.0.cmp(&right.0),
+                SortOrder::Desc => right.0.cmp(&left.0),
+            })
+        });
+        keyed.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// Фильтр сравнением двух полей одной строки: left <op> right
+    /// 
+    /// Поля - имена field индексов (берутся их extractor-ы), строки текущей
+    /// выборки проверяются параллельным predicate pass. Если создан derived
+    /// индекс (`create_field_cmp_index`) - фильтр идет по его bitmap-у.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_field_cmp("bytes_sent", CmpOp::Gt, "bytes_received")?;
+    /// 
+    pub fn filter_field_cmp(&self, left: &str, op: CmpOp, right: &str) -> GlobalResult<&Self> {
+        let description = format!("{left} {op} {right}");
+        let step = LevelStep::new(description.clone(), QueryStep::FieldCmp {
+            left: left.to_string(),
+            op,
+            right: right.to_string(),
+        });
+        if let Ok(index_ref) = self.stored_index(&description)
+            && let Some((field_index, _)) = index_ref.as_field()
+        {
+            let bitmap = self.apply_field_operations(field_index, &[(FieldOperation::eq(true), Op::And)])?;
+            return self.apply_field_bitmap(bitmap, step);
+        }
+        let predicate = self.field_cmp_predicate(left, op, right)?;
+        self.apply_filtered_items(predicate, step)
+    }
+
+    /// Derived bool индекс для `filter_field_cmp(left, op, right)`
+    /// 
+    /// Имя индекса - "left op right" (например "bytes_sent > bytes_received").
+    /// Строится по текущим extractor-ам полей: после пересоздания индексов
+    /// left/right его нужно создать заново.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_cmp_index("bytes_sent", CmpOp::Gt, "bytes_received")?;
+    /// data.filter_field_cmp("bytes_sent", CmpOp::Gt, "bytes_received")?; // через bitmap
+    /// 
+    pub fn create_field_cmp_index(&self, left: &str, op: CmpOp, right: &str) -> GlobalResult<&Self> {
+        let predicate = self.field_cmp_predicate(left, op, right)?;
+        self.create_field_index(&format!("{left} {op} {right}"), move |item: &T| predicate(item))
+    }
+
+    fn field_cmp_predicate(
+        &self,
+        left: &str,
+        op: CmpOp,
+        right: &str,
+    ) -> GlobalResult<impl Fn(&T) -> bool + Send + Sync + Clone + 'static> {
+        let left_extractor = self.field_extractor(left)?;
+        let right_extractor = self.field_extractor(right)?;
+        Ok(move |item: &T| op.evaluate(&left_extractor(item), &right_extractor(item)))
+    }
+
+    /// Экспорт текущей выборки в CSV
+    /// 
+    /// Колонки - field индексы (их extractor-ы), в порядке `fields`.
+    /// Первая строка - заголовок. Возвращает количество строк данных.
+    /// 
+    /// # Example
+    /// 
+    /// let file = std::fs::File::create("orders.csv")?;
+    /// data.export_csv(std::io::BufWriter::new(file), &["id", "price", "status"])?;
+    /// 
+    pub fn export_csv<W: std::io::Write>(&self, writer: W, fields: &[&str]) -> GlobalResult<usize> {
+        let columns = self.export_columns(fields)?;
+        export::write_csv(writer, self.items().iter(), &columns)
+    }
+
+    /// Экспорт текущей выборки в JSON Lines по field индексам
+    /// 
+    /// # Example
+    /// 
+    /// let mut body = Vec::new();
+    /// data.export_json_lines(&mut body, &["id", "price"])?;
+    /// // {"id":1,"price":10}\n{"id":2,"price":25}\n
+    /// 
+    #[cfg(feature = "serde")]
+    pub fn export_json_lines<W: std::io::Write>(&self, writer: W, fields: &[&str]) -> GlobalResult<usize> {
+        let columns = self.export_columns(fields)?;
+        export::write_json_lines(writer, self.items().iter(), &columns)
+    }
+
+    // JSON Lines через Serialize самих элементов - все поля, без индексов
+    #[cfg(feature = "serde")]
+    pub fn export_json_lines_serialized<W: std::io::Write>(&self, writer: W) -> GlobalResult<usize>
+    where
+        T: serde::Serialize,
+    {
+        export::write_json_lines_serialized(writer, self.items().iter())
+    }
+
+    /// DataFrame из текущей выборки
+    /// 
+    /// Колонки - field индексы в порядке `fields`. u128 и Decimal
+    /// переносятся строками, чтобы не терять точность.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_by_field_ops("status", &[(FieldOperation::eq("paid"), Op::And)])?;
+    /// let df = data.to_polars(&["price", "status"])?;
+    /// let by_status = df.lazy().group_by([col("status")]).agg([col("price").sum()]);
+    /// 
+    #[cfg(feature = "polars")]
+    pub fn to_polars(&self, fields: &[&str]) -> GlobalResult<::polars::prelude::DataFrame> {
+        let items = self.items();
+        let columns = self.export_columns(fields)?
+            .iter()
+            .map(|(name, extractor)| crate::dataframe::build_column(name, &items, extractor))
+            .collect::<GlobalResult<Vec<_>>>()?;
+        ::polars::prelude::DataFrame::new(columns).map_err(crate::dataframe::polars_error)
+    }
+
+    // Extractor-ы для колонок экспорта; все поля проверяются до записи
+    /// Снимок текущей выборки для экспорта (см. ExportSnapshot)
+    /// 
+    /// Уровень и колонки фиксируются под блокировкой уровня, сама запись
+    /// идет без нее - фильтрация продолжается, пока снимок выгружается.
+    /// 
+    /// # Example
+    /// 
+    /// let snapshot = data.export_snapshot(&["id", "price"])?;
+    /// std::thread::spawn(move || snapshot.write_csv(file));
+    /// data.filter(|order| order.price > 100)?;
+    /// 
+    pub fn export_snapshot(&self, fields: &[&str]) -> GlobalResult<ExportSnapshot<T>> {
+        let columns = self.export_columns(fields)?;
+        let _lock = self.write_lock.read_recursive();
+        let source = self.storage_rows()
+            .ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        let level = self.current_level();
+        let description = self.level_meta.load()
+            .get(level)
+            .map(|meta| Arc::clone(&meta.description))
+            .unwrap_or_else(|| Arc::from(""));
+        Ok(ExportSnapshot {
+            level,
+            description,
+            watermark: source.len(),
+            indices: self.current_indices(),
+            source,
+            columns,
+        })
+    }
+
+    fn export_columns(&self, fields: &[&str]) -> GlobalResult<Vec<ExportColumn<T>>> {
+        fields
+            .iter()
+            .map(|name| Ok((name.to_string(), self.field_extractor(name)?)))
+            .collect()
+    }
+
+    /// Количество строк текущей выборки для каждого значения field индекса
+    /// 
+    /// Для field индекса - |bitmap значения ∩ выборка|; для computed поля или
+    /// еще строящегося индекса - проход extractor-ом по выборке. Значения без
+    /// строк в текущей выборке и null не возвращаются; порядок - по возрастанию
+    /// значения.
+    /// 
+    /// # Example
+    /// 
+    /// for (value, count) in data.count_by("status")? {
+    ///     println!("{value}: {count}");
+    /// }
+    /// 
+    pub fn count_by(&self, name: &str) -> GlobalResult<Vec<(FieldValue, usize)>> {
+        match self.resolve_field_index(name)? {
+            ResolvedField::Index(index_ref) => {
+                let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }))?;
+                // null-ы хранятся в индексе отдельно и в value_bitmaps не входят
+                let mask = self.current_positions_mask();
+                Ok(field_index
+                    .value_bitmaps()
+                    .into_par_iter()
+                    .filter_map(|(value, bitmap)| {
+                        let matched = bitmap.intersection_len(&mask) as usize;
+                        (matched > 0).then_some((value, matched))
+                    })
+                    .collect())
+            }
+            ResolvedField::Pending(extractor) => {
+                let data = self.storage_rows()
+                    .ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+                let counts = self.current_indices()
+                    .par_iter()
+                    .filter_map(|&idx| data.get(idx).map(|item| extractor(item)))
+                    .filter(|value| !value.is_null())
+                    .fold(BTreeMap::new, |mut counts, value| {
+                        *counts.entry(value).or_insert(0usize) += 1;
+                        counts
+                    })
+                    .reduce(BTreeMap::new, |mut left, right| {
+                        for (value, count) in right {
+                            *left.entry(value).or_insert(0) += count;
+                        }
+                        left
+                    });
+                Ok(counts.into_iter().collect())
+            }
+        }
+    }
+
+    /// Количество строк текущей выборки, удовлетворяющих операциям
+    /// 
+    /// Новый уровень не создается, строки не материализуются.
+    /// 
+    /// # Example
+    /// 
+    /// let errors = data.count_by_field_ops("status", &[(FieldOperation::gte(500), Op::And)])?;
+    /// 
+    pub fn count_by_field_ops(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<usize> {
+        if operations.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let operations = self.normalized_operations(name, operations);
+        let operations = operations.as_ref();
+        match self.resolve_field_index(name)? {
+            ResolvedField::Index(index_ref) => {
+                let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index_ref.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }))?;
+                let bitmap = match self.cached_range_bitmap(name, operations) {
+                    Some(bitmap) => bitmap,
+                    None => self.cached_field_operations(name, field_index, operations)?,
+                };
+                Ok(self.restrict_to_current(bitmap).len() as usize)
+            }
+            ResolvedField::Pending(extractor) => Ok(self.count_with_extractor(&extractor, operations)),
+        }
+    }
+
+    // Подсчет предикатом по текущей выборке (индекса нет или он строится)
+    pub(crate) fn count_with_extractor(
+        &self,
+        extractor: &ExtractorFieldValue<T>,
+        operations: &[(FieldOperation, Op)],
+    ) -> usize {
+        let matches = self.compiled_operations(operations);
+        self.items()
+            .par_iter()
+            .filter(|item| matches(&extractor(item)))
+            .count()
+    }
+
+    /// Построить sketch частых значений (SpaceSaving) вместо полного индекса
+    /// 
+    /// Память - не больше `capacity` счетчиков, строится по всем строкам
+    /// (как и индексы - независимо от текущего уровня).
+    /// 
+    /// # Example
+    /// 
+    /// data.create_top_values_sketch("url", |hit: &Hit| hit.url.clone(), 1024)?;
+    /// let top = data.approx_top_values("url", 10)?;
+    /// 
+    pub fn create_top_values_sketch<V,F>(
+        &self,
+        name: &str,
+        extractor: F,
+        capacity: usize,
+    ) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> V + Send + Sync,
+        V: Into<FieldValue>,
+    {
+        let sketch = TopValuesSketch::build(
+            &self.index_base_items(),
+            |item: &T| extractor(item).into(),
+            capacity,
+        );
+        self.top_sketches.insert(name.to_string(), Arc::new(sketch));
+        Ok(self)
+    }
+
+    pub fn drop_top_values_sketch(&self, name: &str) {
+        self.top_sketches.remove(name);
+    }
+
+    /// k самых частых значений колонки
+    /// 
+    /// Sketch (если построен) дает приближенный ответ, иначе ответ точный
+    /// по field индексу. Считается по всем строкам, не только текущей выборке.
+    /// Sketch построен по всем строкам, поэтому под base filter ответ всегда
+    /// точный по строкам фильтра (см. field_top_values).
+    /// 
+    /// # Example
+    /// 
+    /// for top in data.approx_top_values("url", 10)? {
+    ///     println!("{:?}: ~{} (>= {})", top.value, top.count, top.guaranteed_count());
+    /// }
+    /// 
+    pub fn approx_top_values(&self, name: &str, k: usize) -> GlobalResult<Vec<TopValue>> {
+        if !self.has_base_filter()
+            && let Some(sketch) = self.top_sketches.get(name)
+        {
+            return Ok(sketch.top(k));
+        }
+        Ok(self.field_top_values(name, k)?
+            .into_iter()
+            .map(|(value, count)| TopValue {
+                value,
+                count: count as u64,
+                error: 0,
+            })
+            .collect())
+    }
+
+    /// k самых частых значений field индекса с числом строк
+    /// 
+    /// Точный ответ по bitmap-ам индекса (аналог top_text для text индекса).
+    /// Считается по всем строкам индекса (внутри base filter), не только
+    /// текущей выборке - для счетчиков по выборке см. count_by.
+    /// 
+    /// # Example
+    /// 
+    /// for (brand, count) in data.field_top_values("brand", 10)? {
+    ///     println!("{brand} ({count})");
+    /// }
+    /// 
+    pub fn field_top_values(&self, name: &str, k: usize) -> GlobalResult<Vec<(FieldValue, usize)>> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        let Some(mask) = self.base_positions_mask() else {
+            return Ok(field_index.top_values(k));
+        };
+        let mut counts: Vec<(FieldValue, usize)> = field_index.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.intersection_len(&mask) as usize))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        counts.sort_unstable_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+        counts.truncate(k);
+        Ok(counts)
+    }
+
+    /// Повторяющиеся значения field индекса вместе с их строками
+    /// 
+    /// Возвращает значения, встречающиеся больше одного раза, и индексы
+    /// хранилища строк с этим значением (см. items_with_ids).
+    /// Считается по всем строкам индекса (внутри base filter), не только текущей выборке.
+    /// 
+    /// # Example
+    /// 
+    /// for (email, rows) in data.duplicates("email")? {
+    ///     println!("{email}: {} rows {:?}", rows.len(), rows.iter().collect::<Vec<_>>());
+    /// }
+    /// 
+    pub fn duplicates(&self, name: &str) -> GlobalResult<Vec<(FieldValue, RoaringBitmap)>> {
+        self.duplicates_above(name, 1)
+    }
+
+    // duplicates с порогом: значения, встречающиеся больше threshold раз (и не меньше двух)
+    pub fn duplicates_above(&self, name: &str, threshold: u64) -> GlobalResult<Vec<(FieldValue, RoaringBitmap)>> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        let mask = self.base_positions_mask();
+        Ok(field_index.value_bitmaps()
+            .into_iter()
+            .filter_map(|(value, bitmap)| {
+                let rows = match &mask {
+                    Some(mask) => bitmap & mask,
+                    None => bitmap.clone(),
+                };
+                (rows.len() > threshold.max(1)).then_some((value, rows))
+            })
+            .map(|(value, rows)| (value, self.index_positions_to_ids(rows)))
+            .collect())
+    }
+
+    // count_by для enum индекса: коды переводятся обратно в значения enum
+    pub fn count_by_enum<E: IndexEnum>(&self, name: &str) -> GlobalResult<Vec<(E, usize)>> {
+        Ok(self.count_by(name)?
+            .into_iter()
+            .filter_map(|(value, count)| Self::decode_enum(&value).map(|variant| (variant, count)))
+            .collect())
+    }
+
+    // Все значения enum индекса (по всему набору индекса, не только текущей выборке)
+    pub fn enum_values<E: IndexEnum>(&self, name: &str) -> GlobalResult<Vec<E>> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        Ok(field_index.value_bitmaps()
+            .into_iter()
+            .filter_map(|(value, _)| Self::decode_enum(&value))
+            .collect())
+    }
+
+    fn decode_enum<E: IndexEnum>(value: &FieldValue) -> Option<E> {
+        match value {
+            FieldValue::U32(code) => E::from_code(*code),
+            _ => None,
+        }
+    }
+
+    // Возвращает фактическую селективность: строки результата / размер индекса
+    fn do_filter_by_fields_ops(
+        &self,
+        fields: &[FieldPlanEntry<'_>],
+        extractors: &[FieldPredicateEntry<'_, T>],
+        estimate: f64,
+        adaptive: bool,
+    ) -> GlobalResult<f64> {
+        if fields.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let step = QueryStep::Fields(
+            fields
+                .iter()
+                .map(|(name, _, operations)| (name.to_string(), operations.to_vec()))
+                .collect()
+        );
+        let base_len = self.index_base_len().max(1) as f64;
+        // Порог, после которого оценка считается ошибочной
+        let switch_ratio = (estimate * ADAPTIVE_MISESTIMATE_FACTOR).max(SELECTIVITY_THRESHOLD);
+        // Получаем bitmap от каждого индекса
+        let mut combined_bitmap: Option<RoaringBitmap> = None;
+        let mut descriptions = Vec::<String>::with_capacity(fields.len());
+        for (n, (field_name,field_index, operations)) in fields.iter().enumerate() {
+            if operations.is_empty() {
+                continue;
+            }
+            // Получаем bitmap для текущего поля
+            let field_bitmap = match self.cached_range_bitmap(field_name, operations) {
+                Some(bitmap) => {
+                    self.planner_stats.write().range_cache_hits += 1;
+                    bitmap
+                }
+                None => self.cached_field_operations(field_name, field_index, operations)?,
+            };
+            // Формируем описание операции
+            descriptions.push(Self::format_field_ops_desc(field_name, operations));
+            // Объединяем bitmapы через AND
+            let combined = match combined_bitmap {
+                None => field_bitmap,
+                Some(existing) => existing & field_bitmap,
+            };
+            // Адаптивное выполнение: промежуточный результат намного больше оценки -
+            // оставшиеся поля проверяем предикатом только по кандидатам
+            let remaining = &extractors[n + 1..];
+            if adaptive
+                && !remaining.is_empty()
+                && combined.len() as f64 / base_len > switch_ratio
+                && !fields[n + 1..].iter().any(|(name, _, _)| {
+                    self.planner_hints(name).is_some_and(|hints| hints.usage == IndexUsage::Always)
+                })
+            {
+                self.planner_stats.write().adaptive_switches += 1;
+                for (field_name, _, operations) in &fields[n + 1..] {
+                    descriptions.push(Self::format_field_ops_desc(field_name, operations));
+                }
+                let description = descriptions.join(" AND ");
+                let combined_len = combined.len();
+                let candidates = self.restrict_to_current(combined);
+                let predicate = self.build_field_predicate(remaining)?;
+                let matched = self.filter_ids_by_predicate(&candidates, predicate)?;
+                if matched.is_empty() {
+                    return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
+                        name: description,
+                    }));
+                }
+                // Доля прошедших предикат кандидатов переносится на весь bitmap индексов
+                let observed = combined_len as f64 / base_len * matched.len() as f64 / candidates.len().max(1) as f64;
+                self.apply_filtered_items_with_bitmap(matched, LevelStep::new(description, step))?;
+                return Ok(observed);
+            }
+            combined_bitmap = Some(combined);
+        }
+
+        let final_bitmap = combined_bitmap
+            .ok_or(GLobalError::FilterData(FilterDataError::EmptyOperations))?;
+        // Формируем итоговое описание
+        let description = descriptions.join(" AND ");
+        let observed = final_bitmap.len() as f64 / base_len;
+        // Применяем результат ОДИН раз
+        self.apply_field_bitmap(final_bitmap, LevelStep::new(description, step))?;
+        Ok(observed)
+    }
+
+    /// Предрассчитать bitmap-ы для часто запрашиваемых диапазонов
+    /// 
+    /// Запросы Gt/Gte/Lt/Lte/Range, границы которых совпадают с цепочкой
+    /// соседних бакетов, отвечаются объединением готовых bitmap-ов.
+    /// Кеш привязан к версии индекса и не используется после rebuild.
+    /// 
+    /// # Example
+    /// 
+    /// data.warm_ranges("price", &[(0..100).into(), (100..500).into(), (500..).into()])?;
+    /// // price >= 100 - объединение бакетов [100, 500) и [500, ..)
+    /// data.filter_by_field_ops("price", &[(FieldOperation::gte(100), Op::And)])?;
+    /// 
+    pub fn warm_ranges(&self, name: &str, ranges: &[RangeBucket]) -> GlobalResult<&Self> {
+        let index = self.stored_index(name)?;
+        let (field_index, _) = index.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        let version = self.index_version(name).unwrap_or(0);
+        let buckets = ranges
+            .par_iter()
+            .map(|bucket| {
+                let operations = bucket.to_operations();
+                let bitmap = if operations.is_empty() {
+                    (0..field_index.len() as u32).collect()
+                } else {
+                    self.apply_field_operations(field_index, &operations)?
+                };
+                Ok((bucket.clone(), bitmap))
+            })
+            .collect::<GlobalResult<Vec<(RangeBucket, RoaringBitmap)>>>()?;
+        self.range_buckets.insert(name.to_string(), Arc::new(RangeBucketCache::new(version, buckets)));
+        Ok(self)
+    }
+
+    /// Прогреть индексы после загрузки
+    /// 
+    /// Проходит по bitmap-ам field индексов и posting-ам / текстам text индексов,
+    /// чтобы первые запросы после переключения не платили за page fault и промахи кеша.
+    /// 
+    /// # Example
+    /// 
+    /// let stats = data.warm_up(&["price", "status"], &["search"])?;
+    /// println!("warmed {} bitmaps, {} bytes in {:?}", stats.bitmaps, stats.bytes, stats.elapsed);
+    /// 
+    pub fn warm_up(&self, fields: &[&str], text: &[&str]) -> GlobalResult<WarmUpStats> {
+        self.warm_up_impl(fields, text, false)
+    }
+
+    // warm_up + bitmap всех строк исходного уровня: query_ids / query_count
+    // и остальные запросы по всей базе не строят его заново
+    pub fn warm_up_with_universe(&self, fields: &[&str], text: &[&str]) -> GlobalResult<WarmUpStats> {
+        self.warm_up_impl(fields, text, true)
+    }
+
+    fn warm_up_impl(&self, fields: &[&str], text: &[&str], universe: bool) -> GlobalResult<WarmUpStats> {
+        let started = Instant::now();
+        // Сначала проверяем все имена - частичного прогрева не бывает
+        let mut field_indexes = Vec::with_capacity(fields.len());
+        for name in fields {
+            let index = self.stored_index(name)?;
+            if !index.is_field() {
+                return Err(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index.index_type().to_string(),
+                    type_expect: INDEX_FIELD.to_string(),
+                }));
+            }
+            field_indexes.push(index);
+        }
+        let mut text_indexes = Vec::with_capacity(text.len());
+        for name in text {
+            let index = self.stored_index(name)?;
+            if index.as_text().is_none() {
+                return Err(GLobalError::Index(IndexError::Compatibility {
+                    name: name.to_string(),
+                    type_exist: index.index_type().to_string(),
+                    type_expect: INDEX_TEXT.to_string(),
+                }));
+            }
+            text_indexes.push(index);
+        }
+
+        let touched: Vec<(usize, usize)> = field_indexes
+            .par_iter()
+            .chain(text_indexes.par_iter())
+            .map(|index| match (index.as_field(), index.as_text()) {
+                (Some((field_index, _)), _) => field_index.touch(),
+                (_, Some(text_index)) => text_index.touch(),
+                _ => (0, 0),
+            })
+            .collect();
+        let universe_rows = universe.then(|| {
+            let bitmap = self.source_ids();
+            let rows = bitmap.len() as usize;
+            self.universe_bitmap.store(Arc::new(Some(Arc::new(bitmap))));
+            rows
+        });
+        Ok(WarmUpStats {
+            indexes: touched.len(),
+            bitmaps: touched.iter().map(|(bitmaps, _)| bitmaps).sum(),
+            bytes: touched.iter().map(|(_, bytes)| bytes).sum(),
+            universe_rows,
+            elapsed: started.elapsed(),
+        })
+    }
+
+    pub fn has_universe_bitmap(&self) -> bool {
+        self.universe_bitmap.load().is_some()
+    }
+
+    pub fn warmed_ranges(&self, name: &str) -> Vec<RangeBucket> {
+        self.range_buckets
+            .get(name)
+            .map(|cache| cache.buckets())
+            .unwrap_or_default()
+    }
+
+    pub fn clear_warm_ranges(&self, name: &str) {
+        self.range_buckets.remove(name);
+    }
+
+    /// Включить LRU кеш результатов field запросов
+    /// 
+    /// Bitmap операций над индексом (в позициях индекса, до пересечения с
+    /// выборкой) запоминается по имени индекса и нормализованным операциям,
+    /// поэтому повторные drill-down-ы с теми же условиями на любом уровне не
+    /// пересчитывают bitmap. Запись действительна для версии индекса: rebuild,
+    /// push / extend / retain делают ее устаревшей. Повторный вызов сбрасывает кеш.
+    /// 
+    /// # Example
+    /// 
+    /// data.enable_result_cache(256);
+    /// data.filter_by_field_ops("region", &[(FieldOperation::eq("EU"), Op::And)])?;
+    /// let stats = data.result_cache_stats().unwrap();
+    /// 
+    pub fn enable_result_cache(&self, capacity: usize) -> &Self {
+        *self.result_cache.lock() = Some(ResultCache::new(capacity));
+        self
+    }
+
+    // Кеш результатов с ограничением и по числу записей, и по байтам bitmap-ов
+    pub fn enable_result_cache_bounded(&self, capacity: usize, max_bytes: usize) -> &Self {
+        *self.result_cache.lock() = Some(ResultCache::new(capacity).with_max_bytes(max_bytes));
+        self
+    }
+
+    pub fn disable_result_cache(&self) {
+        *self.result_cache.lock() = None;
+    }
+
+    pub fn clear_result_cache(&self) {
+        if let Some(cache) = self.result_cache.lock().as_mut() {
+            cache.clear();
+        }
+    }
+
+    pub fn result_cache_stats(&self) -> Option<ResultCacheStats> {
+        self.result_cache.lock().as_ref().map(ResultCache::stats)
+    }
+
+    // Ключ кеша: имя индекса, NullMode (меняет результат при nulls) и операции.
+    // Одни And (Op первой операции не используется) не зависят от порядка -
+    // операции сортируются; списки In / NotIn / Contains* - множества.
+    fn result_cache_key(&self, name: &str, operations: &[(FieldOperation, Op)]) -> String {
+        let commutative = operations.iter().skip(1).all(|(_, op)| *op == Op::And);
+        let mut parts: Vec<String> = operations
+            .iter()
+            .enumerate()
+            .map(|(n, (operation, op))| {
+                let operation = Self::canonical_operation(operation);
+                if commutative || n == 0 {
+                    format!("{operation:?}")
+                } else {
+                    format!("{op:?} {operation:?}")
+                }
+            })
+            .collect();
+        if commutative {
+            parts.sort_unstable();
+            parts.dedup();
+        }
+        format!("{name}\0{:?}\0{}", self.null_mode(), parts.join("\u{1}"))
+    }
+
+    fn canonical_operation(operation: &FieldOperation) -> Cow<'_, FieldOperation> {
+        let sorted = |values: &[FieldValue]| {
+            let mut values = values.to_vec();
+            values.sort_unstable();
+            values.dedup();
+            values
+        };
+        match operation {
+            FieldOperation::In(values) => Cow::Owned(FieldOperation::In(sorted(values))),
+            FieldOperation::NotIn(values) => Cow::Owned(FieldOperation::NotIn(sorted(values))),
+            FieldOperation::ContainsAny(values) => Cow::Owned(FieldOperation::ContainsAny(sorted(values))),
+            FieldOperation::ContainsAll(values) => Cow::Owned(FieldOperation::ContainsAll(sorted(values))),
+            _ => Cow::Borrowed(operation),
+        }
+    }
+
+    // apply_field_operations через кеш результатов (если включен и у индекса есть версия)
+    fn cached_field_operations(
+        &self,
+        name: &str,
+        field_index: &IndexFieldEnum,
+        operations: &[(FieldOperation, Op)],
     ) -> GlobalResult<RoaringBitmap> {
-        let bitmap = if operations.len() == 1 {
-            field_index.filter_operation(&operations[0].0)
+        let Some(version) = self.index_version(name) else {
+            return self.apply_field_operations(field_index, operations);
+        };
+        let key = self.result_cache_key(name, operations);
+        let lookup = match self.result_cache.lock().as_mut() {
+            Some(cache) => cache.lookup(&key, version),
+            None => return self.apply_field_operations(field_index, operations),
+        };
+        let pending = match lookup {
+            CacheLookup::Hit(bitmap) => return Ok(bitmap),
+            CacheLookup::Miss(pending) => pending,
+        };
+        // Считаем без блокировки кеша: ждут только запросы с тем же ключом
+        let mut slot = pending.lock();
+        if let Some(bitmap) = slot.as_ref() {
+            return Ok(bitmap.clone());
+        }
+        let result = self.apply_field_operations(field_index, operations);
+        if let Ok(bitmap) = &result {
+            *slot = Some(bitmap.clone());
+        }
+        if let Some(cache) = self.result_cache.lock().as_mut() {
+            cache.complete(key, version, &pending, result.as_ref().ok().cloned());
+        }
+        result
+    }
+
+    // bitmap запроса уже посчитан: прогретые бакеты или кеш результатов
+    fn has_precomputed_bitmap(&self, name: &str, operations: &[(FieldOperation, Op)]) -> bool {
+        if self.cached_range_bitmap(name, operations).is_some() {
+            return true;
+        }
+        let Some(version) = self.index_version(name) else {
+            return false;
+        };
+        let cache = self.result_cache.lock();
+        cache.as_ref().is_some_and(|cache| cache.contains(&self.result_cache_key(name, operations), version))
+    }
+
+    // bitmap из прогретых бакетов (в позициях индекса)
+    fn cached_range_bitmap(&self, name: &str, operations: &[(FieldOperation, Op)]) -> Option<RoaringBitmap> {
+        if self.range_buckets.is_empty() {
+            return None;
+        }
+        let cache = self.range_buckets.get(name)?;
+        // Индекс перестроен - кеш устарел
+        if self.index_version(name) != Some(cache.version) {
+            return None;
+        }
+        let query = RangeBucket::from_operations(operations)?;
+        cache.lookup(&query)
+    }
+
+    /// Статистика значений field индекса: min/max, перцентили, гистограмма
+    /// 
+    /// Считается по bitmap-ам индекса один раз на версию индекса; та же
+    /// статистика используется планировщиком для оценки селективности
+    /// сравнений и диапазонов вместо фиксированных коэффициентов.
+    /// При base filter статистика считается только по его строкам (без кеша).
+    /// 
+    /// # Example
+    /// 
+    /// let stats = data.field_stats("price")?;
+    /// println!("min {:?}, max {:?}, p95 {:?}", stats.min, stats.max, stats.percentile(0.95));
+    /// for bucket in &stats.histogram {
+    ///     println!("[{}, {}): {}", bucket.start, bucket.end, bucket.rows);
+    /// }
+    /// 
+    pub fn field_stats(&self, name: &str) -> GlobalResult<Arc<FieldStats>> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        if let Some(mask) = self.base_positions_mask() {
+            let version = self.index_version(name).unwrap_or(0);
+            return Ok(Arc::new(field_index.stats_within(version, DEFAULT_HISTOGRAM_BUCKETS, &mask)));
+        }
+        Ok(self.field_stats_for(name, field_index).unwrap_or_else(|| {
+            Arc::new(field_index.stats(0, DEFAULT_HISTOGRAM_BUCKETS))
+        }))
+    }
+
+    fn cached_field_stats(&self, name: &str) -> Option<Arc<FieldStats>> {
+        let stats = self.field_stats.get(name)?;
+        // Индекс перестроен - статистика устарела
+        (self.index_version(name) == Some(stats.version)).then(|| Arc::clone(&stats))
+    }
+
+    // Статистика из кеша или посчитанная заново для текущей версии индекса
+    // (None - индекс без рецепта, например вычисляемое поле)
+    fn field_stats_for(&self, name: &str, field_index: &IndexFieldEnum) -> Option<Arc<FieldStats>> {
+        if let Some(stats) = self.cached_field_stats(name) {
+            return Some(stats);
+        }
+        let version = self.index_version(name)?;
+        let stats = Arc::new(field_index.stats(version, DEFAULT_HISTOGRAM_BUCKETS));
+        self.field_stats.insert(name.to_string(), Arc::clone(&stats));
+        Some(stats)
+    }
+
+    /// Предрассчитать числовые значения field индекса для sum/avg
+    /// 
+    /// После этого `sum_field`/`avg_field` не читают строки: сумма по текущей
+    /// выборке - Σ value × |bitmap ∩ mask| по distinct значениям индекса.
+    /// Привязано к версии индекса: после rebuild нужно вызвать снова.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_field_index("amount", |order| order.amount)?;
+    /// data.precompute_value_sums("amount")?;
+    /// data.filter_by_field_ops("region", &[(FieldOperation::eq("EU"), Op::And)])?;
+    /// let revenue = data.sum_field("amount")?;
+    /// 
+    pub fn precompute_value_sums(&self, name: &str) -> GlobalResult<&Self> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        let values = field_index.value_bitmaps()
+            .into_iter()
+            .map(|(value, _)| value.as_f64())
+            .collect::<Option<Vec<f64>>>()
+            .ok_or(GLobalError::FilterData(FilterDataError::NotNumericField { name: name.to_string() }))?;
+        let version = self.index_version(name).unwrap_or_default();
+        self.value_sums.insert(name.to_string(), Arc::new(ValueSums { version, values }));
+        Ok(self)
+    }
+
+    pub fn has_value_sums(&self, name: &str) -> bool {
+        self.valid_value_sums(name).is_some()
+    }
+
+    pub fn clear_value_sums(&self, name: &str) {
+        self.value_sums.remove(name);
+    }
+
+    // Сумма числового поля по текущей выборке
+    pub fn sum_field(&self, name: &str) -> GlobalResult<f64> {
+        Ok(self.aggregate_field(name)?.sum)
+    }
+
+    // Среднее по строкам с числовым значением; None - таких строк нет
+    pub fn avg_field(&self, name: &str) -> GlobalResult<Option<f64>> {
+        Ok(self.aggregate_field(name)?.avg())
+    }
+
+    /// Агрегат числового поля по текущей выборке
+    /// 
+    /// Учитываются строки с числовым значением (null, строки, bool пропускаются).
+    /// См. `aggregate_field` - все агрегаты за один проход.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_by_field_ops("region", &[(FieldOperation::eq("EU"), Op::And)])?;
+    /// let max_price = data.aggregate("price", AggregateKind::Max)?;
+    /// 
+    pub fn aggregate(&self, name: &str, kind: AggregateKind) -> GlobalResult<Option<f64>> {
+        Ok(self.aggregate_field(name)?.get(kind))
+    }
+
+    // Несколько агрегатов; каждое поле считается один раз
+    pub fn aggregate_many(&self, aggregates: &[(&str, AggregateKind)]) -> GlobalResult<Vec<Option<f64>>> {
+        let mut computed: Vec<(&str, ValueAggregate)> = Vec::new();
+        aggregates
+            .iter()
+            .map(|(name, kind)| {
+                if let Some((_, aggregate)) = computed.iter().find(|(field, _)| field == name) {
+                    return Ok(aggregate.get(*kind));
+                }
+                let aggregate = self.aggregate_field(name)?;
+                computed.push((name, aggregate));
+                Ok(aggregate.get(*kind))
+            })
+            .collect()
+    }
+
+    /// count / sum / min / max по текущей выборке за один проход
+    /// 
+    /// Для числового field индекса с `precompute_value_sums` или с небольшим
+    /// числом значений агрегаты считаются по bitmap-ам: Σ value × |bitmap ∩ выборка|,
+    /// min / max - по значениям с непустым пересечением. Иначе - параллельный
+    /// проход extractor-ом по строкам выборки.
+    /// 
+    /// # Example
+    /// 
+    /// let stats = data.aggregate_field("amount")?;
+    /// println!("{} orders, avg {:?}", stats.count, stats.avg());
+    /// 
+    pub fn aggregate_field(&self, name: &str) -> GlobalResult<ValueAggregate> {
+        if let ResolvedField::Index(index_ref) = self.resolve_field_index(name)?
+            && let Some((field_index, _)) = index_ref.as_field()
+            && !field_index.is_multi_valued()
+        {
+            if let Some(sums) = self.valid_value_sums(name) {
+                return Ok(self.aggregate_by_index(&sums.values, field_index.bitmaps().into_iter()));
+            }
+            if field_index.unique_values_count() * AGGREGATE_INDEX_RATIO <= self.len() {
+                let values = field_index.value_bitmaps();
+                if let Some(numbers) = values.iter().map(|(value, _)| value.as_f64()).collect::<Option<Vec<f64>>>() {
+                    return Ok(self.aggregate_by_index(&numbers, values.iter().map(|(_, bitmap)| *bitmap)));
+                }
+            }
+        }
+        let extractor = self.field_extractor(name)?;
+        let data = self.storage_rows()
+            .ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        Ok(self.current_indices()
+            .par_iter()
+            .filter_map(|&idx| data.get(idx).and_then(|item| extractor(item).as_f64()))
+            .fold(ValueAggregate::default, ValueAggregate::push)
+            .reduce(ValueAggregate::default, ValueAggregate::merge))
+    }
+
+    // Агрегаты по bitmap-ам значений (numbers - в порядке bitmap-ов)
+    fn aggregate_by_index<'b>(&self, numbers: &[f64], bitmaps: impl Iterator<Item = &'b RoaringBitmap>) -> ValueAggregate {
+        let mask = self.current_positions_mask();
+        let mut aggregate = ValueAggregate::default();
+        for (&number, bitmap) in numbers.iter().zip(bitmaps) {
+            let matched = bitmap.intersection_len(&mask);
+            if matched == 0 {
+                continue;
+            }
+            aggregate = aggregate.merge(ValueAggregate {
+                count: matched as usize,
+                sum: number * matched as f64,
+                min: Some(number),
+                max: Some(number),
+            });
+        }
+        aggregate
+    }
+
+    /// Число строк текущей выборки по каждому значению поля (фасеты)
+    /// 
+    /// То же, что count_by (без построения GroupData), со счетчиками в u64.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_by_field_ops("price", &[(FieldOperation::lt(100u64), Op::And)])?;
+    /// for (category, count) in data.facet_counts("category")? {
+    ///     println!("{category:?}: {count}");
+    /// }
+    /// 
+    pub fn facet_counts(&self, name: &str) -> GlobalResult<Vec<(FieldValue, u64)>> {
+        Ok(self.count_by(name)?
+            .into_iter()
+            .map(|(value, count)| (value, count as u64))
+            .collect())
+    }
+
+    fn valid_value_sums(&self, name: &str) -> Option<Arc<ValueSums>> {
+        let sums = self.value_sums.get(name)?;
+        // Индекс перестроен - значения устарели
+        (self.index_version(name) == Some(sums.version)).then(|| Arc::clone(&sums))
+    }
+
+    // Текущая выборка в позициях индекса (обратное к index_positions_to_ids)
+    fn current_positions_mask(&self) -> RoaringBitmap {
+        let current: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        match &self.storage {
+            DataStorage::Owned { .. } => current,
+            DataStorage::Indexed { source_indices, .. } => source_indices
+                .iter()
+                .enumerate()
+                .filter(|(_, idx)| current.contains(**idx as u32))
+                .map(|(pos, _)| pos as u32)
+                .collect(),
+        }
+    }
+
+    // Уровень для predicate path по полям
+    fn fields_ops_step(fields: &[(&str, &[(FieldOperation, Op)])]) -> LevelStep {
+        let description = fields
+            .iter()
+            .map(|(name, operations)| Self::format_field_ops_desc(name, operations))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        LevelStep::new(description, QueryStep::fields(fields))
+    }
+
+    fn format_field_ops_desc(field_name: &str, operations: &[(FieldOperation, Op)]) -> String {
+        let op_desc = operations.iter()
+            .map(|(op, _)| format!("{}", op))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}: {}", field_name, op_desc)
+    }
+
+    pub fn filter_by_fields_ops(
+        &self,
+        fields: &[(&str, &[(FieldOperation, Op)])],
+    ) -> GlobalResult<&Self> {
+        self.filter_by_fields_ops_with_hint(fields, QueryHint::Auto)
+    }
+
+    /// Новый уровень: строки, подходящие хотя бы под одно из условий полей
+    /// 
+    /// В отличие от `filter_by_fields_ops` bitmap-ы полей объединяются через OR.
+    /// Выполняется как `QueryExpr::or` из `QueryExpr::field` (индексы, computed
+    /// поля и NullMode - как в filter_query).
+    /// 
+    /// # Example
+    /// 
+    /// // price < 10 OR category == "sale"
+    /// data.filter_by_fields_ops_any(&[
+    ///     ("price", &[(FieldOperation::lt(10u64), Op::And)]),
+    ///     ("category", &[(FieldOperation::eq("sale"), Op::And)]),
+    /// ])?;
+    /// 
+    pub fn filter_by_fields_ops_any(
+        &self,
+        fields: &[(&str, &[(FieldOperation, Op)])],
+    ) -> GlobalResult<&Self> {
+        if fields.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let description = fields
+            .iter()
+            .map(|(name, operations)| Self::format_field_ops_desc(name, operations))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let expr = QueryExpr::or(
+            fields
+                .iter()
+                .map(|(name, operations)| QueryExpr::field(name, operations.to_vec()))
+                .collect()
+        );
+        self.apply_query_expr(&expr, description)
+    }
+
+    // ForceIndex / ForceScan обходят need_to_use_index.
+    // Поля, индекс которых еще строится, всегда проверяются предикатом.
+    pub fn filter_by_fields_ops_with_hint(
+        &self,
+        fields: &[(&str, &[(FieldOperation, Op)])],
+        hint: QueryHint,
+    ) -> GlobalResult<&Self> {
+        if fields.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let normalized: Vec<Cow<[(FieldOperation, Op)]>> = fields
+            .iter()
+            .map(|(name, operations)| self.normalized_operations(name, operations))
+            .collect();
+        let fields: Vec<(&str, &[(FieldOperation, Op)])> = fields
+            .iter()
+            .zip(&normalized)
+            .map(|((name, _), operations)| (*name, operations.as_ref()))
+            .collect();
+        let fields = fields.as_slice();
+        let resolved: Vec<ResolvedField<T>> = fields
+        .iter()
+        .map(|(name, _)| self.resolve_field_index(name))
+        .collect::<Result<_, _>>()?;
+
+        let mut temp_container = Vec::<FieldPlanEntry>::with_capacity(fields.len());
+        let mut temp_extractors = Vec::<(&ExtractorFieldValue<T>,&[(FieldOperation, Op)])>::with_capacity(fields.len());
+        let mut all_indexed = true;
+        for (n,(name,operations)) in fields.iter().enumerate(){
+            match &resolved[n] {
+                ResolvedField::Index(index_ref) => {
+                    if let Some((field_index,extractor)) = index_ref.as_field() {
+                        temp_container.push((*name,field_index,*operations));
+                        temp_extractors.push((extractor,*operations));
+                    }
+                }
+                ResolvedField::Pending(extractor) => {
+                    // Индекс еще строится - только predicate path
+                    all_indexed = false;
+                    temp_extractors.push((extractor,*operations));
+                }
+            }
+        }
+
+        if !all_indexed {
+            let predicate = self.build_field_predicate(&temp_extractors)?;
+            return self.apply_filtered_items(predicate, Self::fields_ops_step(fields));
+        }
+
+        // Оценка с учетом обратной связи от предыдущих выполнений
+        let feedback_key = Self::planner_feedback_key(fields.iter().map(|(name, _)| *name));
+        let raw_estimate = self.estimate_selectivity_from_indexes(&temp_container);
+        let estimate = self.planner_feedback
+            .get(&feedback_key)
+            .map(|feedback| feedback.apply(raw_estimate))
+            .unwrap_or(raw_estimate);
+        let len_before = self.len();
+        // Null и поля-массивы видны только в индексе - predicate path их не различает
+        let index_only = temp_container.iter().any(|(_, field_index, _)| field_index.requires_index());
+        let can_use_field_indexes = index_only || match hint {
+            QueryHint::Auto => self.need_to_use_index(&temp_container, estimate)?,
+            QueryHint::ForceIndex => true,
+            QueryHint::ForceScan => false,
+        };
+        // Фактическая селективность считается самим путем выполнения:
+        // self.len() после фильтра может уже отражать чужой уровень
+        let result = if can_use_field_indexes{
+            self.planner_stats.write().index_executions += 1;
+            // Принудительный index path не переключается на предикат
+            let adaptive = hint == QueryHint::Auto && !index_only;
+            self.do_filter_by_fields_ops(&temp_container, &temp_extractors, estimate, adaptive)
         } else {
-            field_index.filter_operations(operations)
+            self.planner_stats.write().predicate_executions += 1;
+            let predicate = self.build_field_predicate(&temp_extractors)?;
+            self.apply_filtered_items_observed(predicate, Self::fields_ops_step(fields))
         };
-        let result = bitmap.map_err(|err|GLobalError::Index(IndexError::Field(err)))?;
-        Ok(result)
+        // Запоминаем фактическую селективность для следующих решений
+        // (пусто только в текущей выборке - про весь индекс это ничего не говорит)
+        let observed = match &result {
+            Ok(observed) => Some(*observed),
+            Err(GLobalError::FilterData(
+                FilterDataError::DataNotFound
+                | FilterDataError::DataNotFoundByIndex { .. }
+            )) => Some(0.0),
+            Err(_) => None,
+        };
+        if let Some(observed) = observed && len_before >= SMALL_DATASET_THRESHOLD {
+            self.planner_feedback
+                .entry(feedback_key)
+                .or_default()
+                .record(raw_estimate, observed);
+        }
+        result.map(|_| self)
     }
 
-    fn apply_field_bitmap(
+    /// План `filter_by_fields_ops` без выполнения (EXPLAIN)
+    /// 
+    /// Показывает выбранный путь (index / predicate) и причину, оценку
+    /// селективности по полям и операциям, качество индексов (IndexAnalizer)
+    /// и ожидаемое число строк. Решение то же, что при выполнении запроса;
+    /// адаптивное переключение на предикат во время выполнения не учитывается.
+    /// 
+    /// # Example
+    /// 
+    /// let plan = data.explain_field_ops(&[("price", &[(FieldOperation::lt(10u64), Op::And)])])?;
+    /// println!("{plan}");
+    /// 
+    pub fn explain_field_ops(&self, fields: &[(&str, &[(FieldOperation, Op)])]) -> GlobalResult<QueryPlan> {
+        self.explain_field_ops_with_hint(fields, QueryHint::Auto)
+    }
+
+    pub fn explain_field_ops_with_hint(
         &self,
-        bitmap: RoaringBitmap,
-        description: String,
-    ) -> GlobalResult<&Self> {
-        if bitmap.is_empty() {
-            return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndex {
-                name: description.clone(),
-            }));
+        fields: &[(&str, &[(FieldOperation, Op)])],
+        hint: QueryHint,
+    ) -> GlobalResult<QueryPlan> {
+        if fields.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
         }
-        
-        let current_mask_opt = self.source_indices_mask.load();
-        let final_bitmap = if let Some(current_mask) = current_mask_opt.as_ref() {
-            // Маска есть - используем напрямую
-            &**current_mask & &bitmap
-        } else {
-            // маски нет - создаем из current_indices
-            match &self.storage {
-                DataStorage::Owned { current_indices, full_indices, .. } => {
-                    let current = current_indices.load();
-                    let full = full_indices;
-                    if current.len() < full.len() {
-                        // Есть фильтрация - создаем маску из current_indices
-                        let current_bitmap: RoaringBitmap = current.iter()
-                            .map(|&i| i as u32)
-                            .collect();
-                        // Сохраняем маску для следующих индексных операций
-                        self.source_indices_mask.store(Arc::new(Some(Arc::new(current_bitmap.clone()))));
-                        // Пересечение с индексом
-                        current_bitmap & bitmap
-                    } else {
-                        // Нет фильтрации - используем индекс напрямую
-                        bitmap
-                    }
-                }
-                DataStorage::Indexed { .. } => {
-                    // Для Indexed storage используем bitmap как есть
-                    bitmap
+        let normalized: Vec<Cow<[(FieldOperation, Op)]>> = fields
+            .iter()
+            .map(|(name, operations)| self.normalized_operations(name, operations))
+            .collect();
+        let resolved: Vec<ResolvedField<T>> = fields
+            .iter()
+            .map(|(name, _)| self.resolve_field_index(name))
+            .collect::<Result<_, _>>()?;
+        let container: Vec<FieldPlanEntry> = fields
+            .iter()
+            .zip(&normalized)
+            .zip(&resolved)
+            .filter_map(|(((name, _), operations), resolved)| match resolved {
+                ResolvedField::Index(index_ref) => index_ref
+                    .as_field()
+                    .map(|(field_index, _)| (*name, field_index, operations.as_ref())),
+                ResolvedField::Pending(_) => None,
+            })
+            .collect();
+
+        let field_plans = fields
+            .iter()
+            .zip(&normalized)
+            .map(|((name, _), operations)| {
+                let entry = container.iter().find(|(indexed, _, _)| indexed == name);
+                let operation_plans = operations
+                    .iter()
+                    .map(|(operation, op)| OperationPlan {
+                        operation: operation.clone(),
+                        op: *op,
+                        selectivity: entry.map(|(_, index, _)| {
+                            self.estimate_field_selectivity(name, index, &[(operation.clone(), Op::And)])
+                        }),
+                        efficient: entry.map(|(_, index, _)| {
+                            index.is_efficient_for_hints(operation, &self.planner_hints(name).unwrap_or_default())
+                        }),
+                    })
+                    .collect();
+                FieldPlan {
+                    name: name.to_string(),
+                    quality: entry.map(|(_, index, _)| index.index_analize()),
+                    cardinality_ratio: entry.map(|(_, index, _)| {
+                        index.unique_values_count() as f64 / index.len().max(1) as f64
+                    }),
+                    unique_values: entry.map(|(_, index, _)| index.unique_values_count()),
+                    selectivity: entry.map(|(_, index, _)| self.estimate_field_selectivity(name, index, operations)),
+                    warmed_range: self.cached_range_bitmap(name, operations).is_some(),
+                    operations: operation_plans,
                 }
+            })
+            .collect();
+
+        let rows = self.len();
+        let all_indexed = container.len() == fields.len();
+        let raw_selectivity = if all_indexed { self.estimate_selectivity_from_indexes(&container) } else { 1.0 };
+        let feedback_key = Self::planner_feedback_key(fields.iter().map(|(name, _)| *name));
+        let selectivity = self.planner_feedback
+            .get(&feedback_key)
+            .map(|feedback| feedback.apply(raw_selectivity))
+            .unwrap_or(raw_selectivity);
+        let index_only = container.iter().any(|(_, field_index, _)| field_index.requires_index());
+        let decision = if !all_indexed {
+            PathDecision::predicate("pending_index", "", None)
+        } else if index_only {
+            PathDecision::index("index_only")
+        } else {
+            match hint {
+                QueryHint::Auto => self.index_path_decision(&container, selectivity),
+                QueryHint::ForceIndex => PathDecision::index("force_index"),
+                QueryHint::ForceScan => PathDecision::predicate("force_scan", "", None),
             }
         };
-        if final_bitmap.is_empty() {
+        Ok(QueryPlan {
+            path: if decision.use_index { PlanPath::Index } else { PlanPath::Predicate },
+            reason: decision.reason,
+            field: (!decision.field.is_empty()).then(|| decision.field.to_string()),
+            threshold: decision.threshold,
+            raw_selectivity,
+            selectivity,
+            rows,
+            estimated_rows: (selectivity * rows as f64).round() as usize,
+            fields: field_plans,
+        })
+    }
+
+    fn planner_feedback_key<'a>(names: impl Iterator<Item = &'a str>) -> String {
+        let mut sorted: Vec<&str> = names.collect();
+        sorted.sort_unstable();
+        sorted.dedup();
+        sorted.join(",")
+    }
+
+    // Поправочный коэффициент планировщика для набора полей (None - выполнений еще не было)
+    pub fn planner_correction(&self, fields: &[&str]) -> Option<f64> {
+        self.planner_feedback
+            .get(&Self::planner_feedback_key(fields.iter().copied()))
+            .map(|feedback| feedback.correction())
+    }
+
+    pub fn planner_stats(&self) -> PlannerStats {
+        self.planner_stats.read().clone()
+    }
+
+    pub fn reset_planner_feedback(&self) {
+        self.planner_feedback.clear();
+        *self.planner_stats.write() = PlannerStats::default();
+    }
+
+    /// Фильтрация по дереву запроса (drill-down)
+    ///
+    /// # Example
+    ///
+    /// let expr = QueryExpr::or(vec![
+    ///     QueryExpr::field("status", vec![(FieldOperation::eq(500), Op::And)]),
+    ///     QueryExpr::text("message", "timeout"),
+    /// ]);
+    /// data.filter_query(&expr)?;
+    ///
+    pub fn filter_query(&self, expr: &QueryExpr) -> GlobalResult<&Self> {
+        self.apply_query_expr(expr, expr.to_string())
+    }
+
+    // filter_query по тексту запроса (QueryExpr::parse); описание уровня - сам текст
+    pub fn filter_query_str(&self, text: &str) -> GlobalResult<&Self> {
+        let expr = QueryExpr::parse(text)?;
+        self.apply_query_expr(&expr, text.trim().to_string())
+    }
+
+    fn apply_query_expr(&self, expr: &QueryExpr, description: String) -> GlobalResult<&Self> {
+        let current: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        let bitmap = self.evaluate_query_expr(expr, &current)?.resolve();
+        if bitmap.is_empty() {
             return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
-                name: description.clone(),
+                name: description,
             }));
         }
-        self.apply_filtered_items_with_bitmap(final_bitmap, description)
+        self.apply_filtered_items_with_bitmap(bitmap, LevelStep::new(description, QueryStep::Query(expr.clone())))
     }
 
-    #[inline(always)]
-    fn evaluate_field_operations(
-        value: &FieldValue,
-        operations: &[(FieldOperation, Op)],
-    ) -> bool {
-        let mut result = true;
-        
-        for (operation, op_type) in operations {
-            let matches = operation.evaluate(value);
-            
-            match op_type {
-                Op::And => {
-                    result = result && matches;
-                    if !result {
-                        return false;
-                    }
+    // Вычисление дерева запроса в индексах хранилища, в пределах current
+    fn evaluate_query_expr(&self, expr: &QueryExpr, current: &RoaringBitmap) -> GlobalResult<TriBitmap> {
+        match expr {
+            QueryExpr::Field { name, operations } => {
+                if operations.is_empty() {
+                    return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
                 }
-                Op::Or => {
-                    result = result || matches;
+                let operations = self.normalized_operations(name, operations);
+                let operations = operations.as_ref();
+                match self.resolve_field_index(name)? {
+                    ResolvedField::Index(index_ref) => {
+                        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+                            name: name.to_string(),
+                            type_exist: index_ref.index_type().to_string(),
+                            type_expect: INDEX_FIELD.to_string(),
+                        }))?;
+                        if self.null_mode() == NullMode::ThreeValued && field_index.has_nulls() {
+                            // Unknown сохраняется до внешнего оператора (NOT/OR)
+                            let result = field_index
+                                .filter_operations_tri(operations)
+                                .map_err(|err| GLobalError::Index(IndexError::Field(err)))?;
+                            return Ok(TriBitmap::new(
+                                self.index_positions_to_ids(result.true_bits) & current,
+                                self.index_positions_to_ids(result.unknown_bits) & current,
+                            ));
+                        }
+                        let bitmap = match self.cached_range_bitmap(name, operations) {
+                            Some(bitmap) => bitmap,
+                            None => self.cached_field_operations(name, field_index, operations)?,
+                        };
+                        Ok(TriBitmap::known(self.index_positions_to_ids(bitmap) & current))
+                    }
+                    ResolvedField::Pending(extractor) => {
+                        // Индекс еще строится - проверяем текущую выборку предикатом
+                        let matches = self.compiled_operations(operations);
+                        self.filter_ids_by_predicate(current, |item| matches(&extractor(item)))
+                            .map(TriBitmap::known)
+                    }
                 }
-                Op::AndNot => {
-                    result = result && !matches;
-                    if !result {
-                        return false;
+            }
+            QueryExpr::Text { name, query } => {
+                let bitmap: RoaringBitmap = self.get_indices_with_text(name, query)?
+                    .into_iter()
+                    .map(|idx| idx as u32)
+                    .collect();
+                Ok(TriBitmap::known(bitmap & current))
+            }
+            QueryExpr::And(exprs) => {
+                // Промежуточные результаты шагов возвращаются в пул bitmap-ов
+                let mut result = TriBitmap::known(pool::take_copy(current));
+                for expr in exprs {
+                    // Дальше вычисляем только по оставшимся кандидатам (True и Unknown)
+                    let candidates = result.maybe_bits();
+                    let step = self.evaluate_query_expr(expr, &candidates)?;
+                    pool::recycle(candidates);
+                    let next = result.and(&step);
+                    step.recycle();
+                    std::mem::replace(&mut result, next).recycle();
+                    if result.is_empty() {
+                        break;
                     }
                 }
-                Op::Xor => {
-                    result = result ^ matches;
+                Ok(result)
+            }
+            QueryExpr::Or(exprs) => {
+                let mut result = TriBitmap::default();
+                for expr in exprs {
+                    let step = self.evaluate_query_expr(expr, current)?;
+                    let next = result.or(&step);
+                    step.recycle();
+                    std::mem::replace(&mut result, next).recycle();
                 }
-                Op::Invert => {
-                    result = !result;
+                Ok(result)
+            }
+            QueryExpr::Not(expr) => Ok(self.evaluate_query_expr(expr, current)?.not(current)),
+        }
+    }
+
+    /// Сравнить две когорты (запроса) в пределах текущей выборки
+    /// 
+    /// Уровень drill-down не добавляется. Считается, сколько строк попало
+    /// только в A, только в B и в обе когорты.
+    /// 
+    /// # Example
+    /// 
+    /// let diff = data.compare_queries(
+    ///     &QueryExpr::field("variant", vec![(FieldOperation::eq("A"), Op::And)]),
+    ///     &QueryExpr::field("converted", vec![(FieldOperation::eq(true), Op::And)]),
+    /// )?;
+    /// println!("{diff}");
+    /// 
+    pub fn compare_queries(&self, a: &QueryExpr, b: &QueryExpr) -> GlobalResult<CohortDiff> {
+        self.compare_cohorts(a, b, false)
+    }
+
+    // То же, что compare_queries, плюс bitmap-ы строк (индексы хранилища)
+    pub fn compare_queries_with_bitmaps(&self, a: &QueryExpr, b: &QueryExpr) -> GlobalResult<CohortDiff> {
+        self.compare_cohorts(a, b, true)
+    }
+
+    // Сравнить два сохраненных сегмента
+    pub fn compare_segments(&self, a: &str, b: &str) -> GlobalResult<CohortDiff> {
+        let (a, b) = (self.segment(a)?, self.segment(b)?);
+        self.compare_cohorts(&a.expr, &b.expr, false)
+    }
+
+    fn compare_cohorts(&self, a: &QueryExpr, b: &QueryExpr, keep_bitmaps: bool) -> GlobalResult<CohortDiff> {
+        self.check_query_indexes(&a.referenced_indexes())?;
+        self.check_query_indexes(&b.referenced_indexes())?;
+        let current: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        let cohort_a = self.evaluate_query_expr(a, &current)?.resolve();
+        let cohort_b = self.evaluate_query_expr(b, &current)?.resolve();
+        Ok(CohortDiff::from_bitmaps(&cohort_a, &cohort_b, keep_bitmaps))
+    }
+
+    /// Применить последовательность шагов одним уровнем drill-down
+    /// 
+    /// Шаги выполняются по порядку, каждый - над результатом предыдущего.
+    /// Уровень добавляется только если все шаги выполнены и результат не пуст,
+    /// иначе состояние выборки не меняется.
+    /// 
+    /// # Example
+    /// 
+    /// data.apply_steps(&[
+    ///     Step::field("region", vec![(FieldOperation::eq("EU"), Op::And)]),
+    ///     Step::text("comment", "refund"),
+    ///     Step::predicate("large", |order: &Order| order.amount > 1_000),
+    /// ])?;
+    /// 
+    pub fn apply_steps(&self, steps: &[Step<T>]) -> GlobalResult<&Self> {
+        if steps.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let exprs: Vec<QueryExpr> = steps.iter().filter_map(Step::as_expr).collect();
+        self.check_query_indexes(&QueryExpr::and(exprs).referenced_indexes())?;
+
+        let mut bitmap: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        for step in steps {
+            bitmap = match step {
+                Step::Predicate { predicate, .. } => {
+                    self.filter_ids_by_predicate(&bitmap, |item| predicate(item))?
                 }
+                step => match step.as_expr() {
+                    Some(expr) => self.evaluate_query_expr(&expr, &bitmap)?.resolve(),
+                    None => bitmap,
+                },
+            };
+            if bitmap.is_empty() {
+                return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
+                    name: step.description(),
+                }));
             }
         }
-        result
+        let description = steps
+            .iter()
+            .map(Step::description)
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let operation = QueryStep::Steps(steps.iter().map(Step::query_step).collect());
+        self.apply_filtered_items_with_bitmap(bitmap, LevelStep::new(description, operation))
     }
 
-    fn build_field_predicate(
-        &self,
-        fields: &[(&ExtractorFieldValue<T>, &[(FieldOperation, Op)])],
-    ) -> GlobalResult<impl Fn(&T) -> bool + Send + Sync + '_> {
-        let field_predicates = fields.iter()
-        .map(|(extractor,operations)| {
-            ((*extractor).clone(),operations.to_vec())
+    // Проверка, что индексы запроса существуют и имеют ожидаемый тип
+    fn check_query_indexes(&self, indexes: &[IndexRef]) -> GlobalResult<()> {
+        let missing: Vec<String> = indexes
+            .iter()
+            .filter(|index| {
+                !self.indexes.contains_key(&index.name)
+                    && !self.index_builds.contains_key(&index.name)
+                    && !self.computed_fields.contains_key(&index.name)
             })
-        .collect::<Vec<(ExtractorFieldValue<T>, Vec<(FieldOperation, Op)>)>>();
-        Ok(move |item: &T| -> bool {
-            for (extractor, operations) in &field_predicates {
-                let field_value = extractor(item);
-                if !Self::evaluate_field_operations(&field_value, operations) {
-                    return false;
-                }
+            .map(|index| index.name.clone())
+            .collect();
+        if !missing.is_empty() {
+            return Err(GLobalError::Index(IndexError::NotFoundMany { names: missing }));
+        }
+        for index in indexes {
+            let type_exist = match self.indexes.get(&index.name) {
+                Some(index_ref) => index_ref.index_type().to_string(),
+                // Фоновое построение и computed поля - только field
+                None => INDEX_FIELD.to_string(),
+            };
+            if type_exist != index.index_type {
+                return Err(GLobalError::Index(IndexError::Compatibility {
+                    name: index.name.clone(),
+                    type_exist,
+                    type_expect: index.index_type.clone(),
+                }));
             }
-            true
+        }
+        Ok(())
+    }
+
+    /// Сохранить запрос для повторного выполнения
+    ///
+    /// Проверяет, что все индексы запроса есть в текущем FilterData.
+    /// Результат можно выполнить над другим FilterData с той же схемой.
+    ///
+    /// # Example
+    ///
+    /// let saved = data.plan_query(&expr)?;
+    /// other.replay_query(&saved)?;
+    ///
+    pub fn plan_query(&self, expr: &QueryExpr) -> GlobalResult<SavedQuery> {
+        let saved = SavedQuery::new(expr.clone());
+        self.check_query_indexes(&saved.indexes)?;
+        Ok(saved)
+    }
+
+    pub fn replay_query(&self, saved: &SavedQuery) -> GlobalResult<&Self> {
+        if saved.version > SAVED_QUERY_VERSION {
+            return Err(GLobalError::FilterData(FilterDataError::QueryVersion {
+                version: saved.version,
+                supported: SAVED_QUERY_VERSION,
+            }));
+        }
+        self.check_query_indexes(&saved.indexes)?;
+        self.filter_query(&saved.expr)
+    }
+
+    /// Создать материализованное представление
+    ///
+    /// Запрос вычисляется один раз по всей базе (без учета drill-down),
+    /// результат хранится как bitmap. Пересоздание индекса из запроса
+    /// делает представление устаревшим - оно пересчитывается при следующем обращении.
+    ///
+    /// # Example
+    ///
+    /// data.create_view("errors_last_hour", QueryExpr::and(vec![
+    ///     QueryExpr::field("status", vec![(FieldOperation::gte(500), Op::And)]),
+    ///     QueryExpr::field("ts", vec![(FieldOperation::gte(hour_ago), Op::And)]),
+    /// ]))?;
+    /// let total = data.view_count("errors_last_hour")?;
+    /// data.filter_by_view("errors_last_hour")?;
+    ///
+    pub fn create_view(&self, name: &str, expr: QueryExpr) -> GlobalResult<&Self> {
+        let view = self.materialize_view(name, expr)?;
+        self.views.insert(name.to_string(), Arc::new(view));
+        Ok(self)
+    }
+
+    // Актуальное представление (с пересчетом, если индексы изменились)
+    pub fn view(&self, name: &str) -> GlobalResult<Arc<MaterializedView>> {
+        let view = self.views
+            .get(name)
+            .map(|view| Arc::clone(&view))
+            .ok_or(GLobalError::FilterData(FilterDataError::ViewNotFound { name: name.to_string() }))?;
+        let is_stale = view.rows != self.index_base_len() || view.versions
+            .iter()
+            .any(|(index, version)| self.index_version(index) != *version);
+        if !is_stale {
+            return Ok(view);
+        }
+        self.refresh_view(name)
+    }
+
+    pub fn view_count(&self, name: &str) -> GlobalResult<usize> {
+        Ok(self.view(name)?.len())
+    }
+
+    pub fn refresh_view(&self, name: &str) -> GlobalResult<Arc<MaterializedView>> {
+        let expr = self.views
+            .get(name)
+            .map(|view| view.expr.clone())
+            .ok_or(GLobalError::FilterData(FilterDataError::ViewNotFound { name: name.to_string() }))?;
+        let view = Arc::new(self.materialize_view(name, expr)?);
+        self.views.insert(name.to_string(), Arc::clone(&view));
+        Ok(view)
+    }
+
+    pub fn drop_view(&self, name: &str) -> bool {
+        self.views.remove(name).is_some()
+    }
+
+    pub fn view_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.views.iter().map(|view| view.key().clone()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Сохранить сегмент - именованный запрос для повторного применения
+    ///
+    /// В отличие от представления, результат не хранится: сегмент вычисляется
+    /// при каждом `apply_segment` над текущей выборкой, поэтому его можно
+    /// комбинировать с любым drill-down. Индексы запроса проверяются при сохранении.
+    ///
+    /// # Example
+    ///
+    /// data.save_segment("vip_eu", QueryExpr::and(vec![
+    ///     QueryExpr::field("tier", vec![(FieldOperation::eq("vip"), Op::And)]),
+    ///     QueryExpr::field("region", vec![(FieldOperation::eq("EU"), Op::And)]),
+    /// ]))?;
+    /// data.filter_by_field_ops("year", &[(FieldOperation::eq(2024u32), Op::And)])?;
+    /// data.apply_segment("vip_eu")?;
+    ///
+    pub fn save_segment(&self, name: &str, expr: QueryExpr) -> GlobalResult<&Self> {
+        let saved = SavedQuery::new(expr);
+        self.check_query_indexes(&saved.indexes)?;
+        self.segments.insert(name.to_string(), saved);
+        Ok(self)
+    }
+
+    pub fn segment(&self, name: &str) -> GlobalResult<SavedQuery> {
+        self.segments
+            .get(name)
+            .map(|segment| segment.clone())
+            .ok_or(GLobalError::FilterData(FilterDataError::SegmentNotFound { name: name.to_string() }))
+    }
+
+    // Новый уровень: текущая выборка ∩ сегмент
+    pub fn apply_segment(&self, name: &str) -> GlobalResult<&Self> {
+        let segment = self.segment(name)?;
+        self.check_query_indexes(&segment.indexes)?;
+        self.apply_query_expr(&segment.expr, format!("segment {}", name))
+    }
+
+    pub fn remove_segment(&self, name: &str) -> bool {
+        self.segments.remove(name).is_some()
+    }
+
+    pub fn segment_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.segments.iter().map(|segment| segment.key().clone()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Включить режим первичного ключа
+    /// 
+    /// Каждой строке сопоставляется стабильный ключ (например id записи).
+    /// Позиционные индексы строк меняются при перезагрузке данных, ключи - нет:
+    /// выборку можно сохранить через current_keys() и восстановить
+    /// на новых данных через filter_by_keys(). Ключи должны быть уникальны.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_key(|order| order.id)?;
+    /// data.filter_by_field_ops("status", &[(FieldOperation::eq("open"), Op::And)])?;
+    /// let keys = data.current_keys()?;
+    /// 
+    /// let reloaded = FilterData::from_vec(load_orders());
+    /// reloaded.set_key(|order| order.id)?;
+    /// reloaded.filter_by_keys(&keys)?;
+    /// 
+    pub fn set_key<V, F>(&self, extractor: F) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<FieldValue> + 'static,
+    {
+        // Таблица строится по снимку строк: push ждет, пока она не встанет
+        let _guard = self.write_lock.read();
+        let table = self
+            .build_key_table(extractor)
+            .map_err(|keys| GLobalError::FilterData(FilterDataError::DuplicateKey { key: keys[0].to_string() }))?;
+        self.key_table.store(Arc::new(Some(Arc::new(table))));
+        Ok(self)
+    }
+
+    // Таблица ключей по строкам хранилища; Err - повторяющиеся ключи
+    fn build_key_table<V, F>(&self, extractor: F) -> Result<KeyTable<T>, Vec<FieldValue>>
+    where
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<FieldValue> + 'static,
+    {
+        let extractor = Self::create_field_value_extractor(extractor);
+        let items = self.index_base_items();
+        match &self.storage {
+            DataStorage::Owned { full_indices, .. } => {
+                let full = full_indices.load();
+                let live: Vec<Arc<T>> = full.iter().filter_map(|&idx| items.get(idx).cloned()).collect();
+                KeyTable::build(&live, full.iter().copied(), extractor)
+            }
+            DataStorage::Indexed { source_indices, .. } => {
+                KeyTable::build(&items, source_indices.iter().copied(), extractor)
+            }
+        }
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.key_table.load().is_some()
+    }
+
+    pub fn clear_key(&self) {
+        self.key_table.store(Arc::new(None));
+    }
+
+    fn key_table(&self) -> GlobalResult<Arc<KeyTable<T>>> {
+        self.key_table
+            .load()
+            .as_ref()
+            .as_ref()
+            .map(Arc::clone)
+            .ok_or(GLobalError::FilterData(FilterDataError::KeyNotSet))
+    }
+
+    // Ключ строки по индексу хранилища (см. items_with_ids)
+    pub fn key_of(&self, row_id: usize) -> GlobalResult<Option<FieldValue>> {
+        let table = self.key_table()?;
+        Ok(self.in_base(row_id).then(|| table.key(row_id)).flatten())
+    }
+
+    // Индекс хранилища по ключу (строки вне base filter не находятся)
+    pub fn row_id_of(&self, key: &FieldValue) -> GlobalResult<Option<usize>> {
+        Ok(self.key_table()?.row(key).filter(|&row_id| self.in_base(row_id)))
+    }
+
+    // Ключи текущей выборки в порядке строк
+    pub fn current_keys(&self) -> GlobalResult<Vec<FieldValue>> {
+        Ok(self.key_table()?.keys_of(&self.current_indices()))
+    }
+
+    // Текущая выборка вместе с ключами строк
+    pub fn items_with_keys(&self) -> GlobalResult<Vec<(FieldValue, Arc<T>)>> {
+        let table = self.key_table()?;
+        Ok(self
+            .iter_items_with_ids()
+            .filter_map(|(idx, item)| table.key(idx).map(|key| (key, item)))
+            .collect())
+    }
+
+    /// Создать уникальный индекс для поиска строки по ключу за O(1)
+    /// 
+    /// Значения extractor-а должны быть уникальны, иначе возвращается ошибка
+    /// со списком всех повторяющихся ключей. Поиск идет по всем данным
+    /// (исходному уровню), а не по текущей выборке.
+    /// 
+    /// # Example
+    /// 
+    /// data.create_unique_index("id", |user| user.id)?;
+    /// let user = data.get_by_key("id", 42u64)?;
+    /// 
+    pub fn create_unique_index<V, F>(&self, name: &str, extractor: F) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<FieldValue> + 'static,
+    {
+        let _guard = self.write_lock.read();
+        let table = self.build_key_table(extractor).map_err(|keys| {
+            GLobalError::FilterData(FilterDataError::DuplicateUniqueKeys {
+                name: name.to_string(),
+                keys: keys.iter().map(|key| key.to_string()).collect(),
+            })
+        })?;
+        self.unique_indexes.insert(name.to_string(), Arc::new(table));
+        Ok(self)
+    }
+
+    pub fn has_unique_index(&self, name: &str) -> bool {
+        self.unique_indexes.contains_key(name)
+    }
+
+    pub fn drop_unique_index(&self, name: &str) -> bool {
+        self.unique_indexes.remove(name).is_some()
+    }
+
+    // Строка по значению уникального индекса; None - ключ не найден или строка вне base filter
+    pub fn get_by_key(&self, name: &str, key: impl Into<FieldValue>) -> GlobalResult<Option<Arc<T>>> {
+        let table = self
+            .unique_indexes
+            .get(name)
+            .map(|entry| Arc::clone(entry.value()))
+            .ok_or(GLobalError::Index(IndexError::NotFound { name: name.to_string() }))?;
+        let Some(row_id) = table.row(&key.into()).filter(|&row_id| self.in_base(row_id)) else {
+            return Ok(None);
+        };
+        Ok(self.storage_rows().and_then(|data| data.get(row_id).cloned()))
+    }
+
+    /// Новый уровень: текущая выборка ∩ строки с указанными ключами
+    /// 
+    /// Неизвестные ключи (строки удалены при перезагрузке) пропускаются.
+    /// 
+    /// # Example
+    /// 
+    /// data.filter_by_keys(&[FieldValue::from(10u64), FieldValue::from(42u64)])?;
+    /// 
+    pub fn filter_by_keys(&self, keys: &[FieldValue]) -> GlobalResult<&Self> {
+        let rows = self.key_table()?.rows_of(keys);
+        let current: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        let bitmap = current & rows;
+        let description = format!("keys ({})", keys.len());
+        if bitmap.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
+                name: description,
+            }));
+        }
+        self.apply_filtered_items_with_bitmap(bitmap, LevelStep::new(description, QueryStep::Keys(keys.to_vec())))
+    }
+
+    // Новый уровень: текущая выборка ∩ представление
+    pub fn filter_by_view(&self, name: &str) -> GlobalResult<&Self> {
+        let view = self.view(name)?;
+        let current: RoaringBitmap = self.current_indices()
+            .iter()
+            .map(|&idx| idx as u32)
+            .collect();
+        let bitmap = current & &view.bitmap;
+        let description = format!("view {}", name);
+        if bitmap.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::DataNotFoundByIndexCurrent {
+                name: description,
+            }));
+        }
+        self.apply_filtered_items_with_bitmap(bitmap, LevelStep::new(description, QueryStep::Query(view.expr.clone())))
+    }
+
+    fn materialize_view(&self, name: &str, expr: QueryExpr) -> GlobalResult<MaterializedView> {
+        let indexes = expr.referenced_indexes();
+        self.check_query_indexes(&indexes)?;
+        // Версии и число строк читаем до вычисления: параллельная замена индекса
+        // или push сделает view устаревшим
+        let rows = self.index_base_len();
+        let versions = indexes
+            .into_iter()
+            .map(|index| {
+                let version = self.index_version(&index.name);
+                (index.name, version)
+            })
+            .collect();
+        let bitmap = self.query_bitmap(&expr)?;
+        Ok(MaterializedView {
+            name: name.to_string(),
+            expr,
+            bitmap,
+            versions,
+            rows,
         })
     }
 
-    fn estimate_selectivity_from_indexes(
-        &self, 
-        container: &[(&str,&IndexFieldEnum, &[(FieldOperation, Op)])]
-    ) -> f64 {
-        if container.is_empty() {
-            return 1.0;
+    /// Индексы хранилища, подходящие под запрос, по всей базе
+    /// 
+    /// В отличие от `filter_query` не создает уровень и не зависит
+    /// от текущей выборки - подходит для параллельных независимых запросов.
+    /// Элементы - `parent_data()[idx]`.
+    /// 
+    /// # Example
+    /// 
+    /// let ids = data.query_ids(&QueryExpr::text("message", "timeout"))?;
+    /// 
+    pub fn query_ids(&self, expr: &QueryExpr) -> GlobalResult<Vec<usize>> {
+        Ok(self.query_bitmap(expr)?.iter().map(|idx| idx as usize).collect())
+    }
+
+    /// Результат запроса как `ResultSet`
+    /// 
+    /// Как `query_ids`, но строки хранилища идут вместе с bitmap-ом:
+    /// страницы, агрегаты и view без повторного обращения к FilterData.
+    /// 
+    /// # Example
+    /// 
+    /// let result = data.query_result(&QueryExpr::text("message", "timeout"))?;
+    /// let page = result.page(0, 20);
+    /// 
+    pub fn query_result(&self, expr: &QueryExpr) -> GlobalResult<ResultSet<T>> {
+        let source = self.storage_rows().ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        Ok(ResultSet::new(self.query_bitmap(expr)?, source))
+    }
+
+    // Текущий уровень drill-down как ResultSet
+    pub fn result_set(&self) -> GlobalResult<ResultSet<T>> {
+        let source = self.storage_rows().ok_or(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty))?;
+        let ids = self.current_indices().iter().map(|&idx| idx as u32).collect();
+        Ok(ResultSet::new(ids, source))
+    }
+
+    fn query_bitmap(&self, expr: &QueryExpr) -> GlobalResult<RoaringBitmap> {
+        Ok(self.evaluate_query_expr(expr, &self.base_ids())?.resolve())
+    }
+
+    // Все индексы хранилища исходного уровня (с учетом base filter)
+    fn base_ids(&self) -> RoaringBitmap {
+        if let Some(base) = self.base_filter.load().as_ref() {
+            return (*base.bitmap).clone();
         }
-        let mut combined_selectivity = 1.0;
-        for (_,index, operations) in container {       
-            let selectivity = index.estimate_operations_selectivity(operations);
-            combined_selectivity *= selectivity;
-            if combined_selectivity < 0.001 {
-                return combined_selectivity;
-            }
+        if let Some(universe) = self.universe_bitmap.load().as_ref() {
+            return (**universe).clone();
         }
-        combined_selectivity
+        self.source_ids()
     }
 
-    fn need_to_use_index(&self, fields: &[(&str,&IndexFieldEnum, &[(FieldOperation, Op)])]) -> GlobalResult<bool> {
-        if self.len() < SMALL_DATASET_THRESHOLD {
-            return Ok(false)
+    // Строка хранилища входит в base filter (без base filter - любая строка)
+    fn in_base(&self, row_id: usize) -> bool {
+        match self.base_filter.load().as_ref() {
+            Some(base) => base.bitmap.contains(row_id as u32),
+            None => true,
         }
+    }
 
-        if fields.iter().any(|(_, index, operations)| {
-            operations.iter().any(|(op, _)| !index.is_efficient_for(op))
-        }) {
-            return Ok(false);
-        }
-        
-        let estimate_selectivity = self.estimate_selectivity_from_indexes(fields);
-        if estimate_selectivity > SELECTIVITY_THRESHOLD{
-            return Ok(false)
-        }
-        
-        Ok(true)
+    // base filter в позициях индекса (None - base filter не задан)
+    fn base_positions_mask(&self) -> Option<RoaringBitmap> {
+        let base = self.base_filter.load_full();
+        let base = base.as_ref().as_ref()?;
+        Some(match &self.storage {
+            DataStorage::Owned { .. } => (*base.bitmap).clone(),
+            DataStorage::Indexed { source_indices, .. } => source_indices
+                .iter()
+                .enumerate()
+                .filter(|(_, idx)| base.bitmap.contains(**idx as u32))
+                .map(|(pos, _)| pos as u32)
+                .collect(),
+        })
     }
 
-    pub fn filter_by_field_ops(
-        &self,
-        name: &str,
-        operations: &[(FieldOperation, Op)],
-    ) -> GlobalResult<&Self> {
-        if operations.is_empty() {
-            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
-        }
-        let index = self.get_index(name)?;
-        let (field_index,extractor) = index.as_field().ok_or(GLobalError::Index(IndexError::Compatibility 
-            {
-                name: name.to_string(),
-                type_exist: index.index_type().to_string(),
-                type_expect: INDEX_FIELD.to_string(),
-            }
-        ))?;
-        let mut temp_container = Vec::<(&str,&IndexFieldEnum,&[(FieldOperation, Op)])>::with_capacity(1);
-        let mut extractor_fields = Vec::<(&ExtractorFieldValue<T>,&[(FieldOperation, Op)])>::with_capacity(1);
-        temp_container.push((name,field_index,operations));
-        extractor_fields.push((extractor,operations));
-        let can_use_field_indexes = self.need_to_use_index(&temp_container)?;
-        if can_use_field_indexes{
-            self.do_filter_by_fields_ops(&temp_container)?;
-        } else {
-            let predicate = self.build_field_predicate(&extractor_fields)?;
-            self.filter(predicate)?;
+    fn source_ids(&self) -> RoaringBitmap {
+        match &self.storage {
+            DataStorage::Owned { full_indices, .. } => full_indices.load().iter().map(|&idx| idx as u32).collect(),
+            DataStorage::Indexed { source_indices, .. } => source_indices.iter().map(|&idx| idx as u32).collect(),
         }
-        Ok(self)
     }
 
-    fn do_filter_by_fields_ops(
-        &self,
-        fields: &[(&str,&IndexFieldEnum, &[(FieldOperation, Op)])],
-    ) -> GlobalResult<&Self> {
-        if fields.is_empty() {
-            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
-        }
-        // Получаем bitmap от каждого индекса
-        let mut combined_bitmap: Option<RoaringBitmap> = None;
-        let mut descriptions = Vec::<String>::with_capacity(fields.len());
-        for (field_name,field_index, operations) in fields {
-            if operations.is_empty() {
-                continue;
-            }
-            // Получаем bitmap для текущего поля
-            let field_bitmap = self.apply_field_operations(field_index, operations)?;
-            // Формируем описание операции
-            let op_desc = operations.iter()
-                .map(|(op, _)| format!("{}", op))
-                .collect::<Vec<_>>()
-                .join(", ");
-            descriptions.push(format!("{}: {}", field_name, op_desc));
-            // Объединяем bitmapы через AND
-            combined_bitmap = Some(match combined_bitmap {
-                None => field_bitmap,
-                Some(existing) => existing & field_bitmap,
-            });
+    #[inline]
+    fn update_level_metadata(&self, current_level: usize, step: LevelStep, row_count: usize) -> GlobalResult<()> {
+        let mut new_level_meta = Vec::with_capacity(current_level + 2);
+        new_level_meta.extend_from_slice(&self.level_meta.load());
+        new_level_meta.push(Arc::new(LevelMeta::new(&step.description, row_count, step.operation)));
+        self.level_meta.store(Arc::new(new_level_meta));
+        self.current_level.store(current_level + 1, Ordering::Release);
+        self.auto_compact_history();
+        Ok(())
+    }
+
+    /// Автоматическое сжатие истории drill-down
+    /// 
+    /// После каждого нового уровня все уровни, кроме исходного и `keep_recent`
+    /// последних, сворачиваются в один checkpoint: память промежуточных уровней
+    /// освобождается, их описания остаются в `LevelMeta::collapsed`.
+    /// None - отключить сжатие.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_history_compaction(Some(5));
+    /// // длинная сессия не упирается в MAX_HISTORY:
+    /// // Source -> checkpoint -> 5 последних уровней
+    /// 
+    pub fn set_history_compaction(&self, keep_recent: Option<usize>) -> &Self {
+        *self.history_compaction.write() = keep_recent;
+        self
+    }
+
+    pub fn history_compaction(&self) -> Option<usize> {
+        *self.history_compaction.read()
+    }
+
+    /// Гарантированный порядок строк на всех путях фильтрации
+    /// 
+    /// Bitmap path отдает строки по возрастанию индекса хранилища, а predicate
+    /// и text path сохраняют порядок предыдущего уровня - для представлений
+    /// с неотсортированными индексами результат зависел от выбранного пути.
+    /// При включенном флаге каждый новый уровень упорядочен по индексу хранилища.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_deterministic_order(true);
+    /// data.filter(|order| order.total > 100)?;
+    /// // items() - в порядке хранилища, как и у filter_by_field_ops
+    /// 
+    pub fn set_deterministic_order(&self, enabled: bool) -> &Self {
+        *self.deterministic_order.write() = enabled;
+        self
+    }
+
+    pub fn deterministic_order(&self) -> bool {
+        *self.deterministic_order.read()
+    }
+
+    /// Хранить уровни истории только как bitmap
+    /// 
+    /// Каждый уровень drill-down держит вектор индексов строк (8 байт на строку).
+    /// С флагом уровни истории (кроме исходного и текущего) сжимаются в bitmap,
+    /// а вектор восстанавливается при возврате на уровень (go_to_level / up).
+    /// Уровни с неупорядоченными строками остаются векторами.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_bitmap_levels(true);
+    /// data.filter_by_field_ops("region", &eu)?;
+    /// data.filter_by_field_ops("status", &active)?; // уровень "region" хранится bitmap-ом
+    /// 
+    pub fn set_bitmap_levels(&self, enabled: bool) -> &Self {
+        let _guard = self.write_lock.write();
+        *self.bitmap_levels.write() = enabled;
+        let current = self.current_level();
+        let convert = |levels: &[LevelIndices]| -> Vec<LevelIndices> {
+            levels
+                .iter()
+                .enumerate()
+                .map(|(level, indices)| match indices {
+                    LevelIndices::Vec(_) if enabled && level != 0 && level != current => indices.compact(),
+                    LevelIndices::Bitmap(_) if !enabled => LevelIndices::Vec(indices.indices()),
+                    _ => indices.clone(),
+                })
+                .collect()
+        };
+        match &self.storage {
+            DataStorage::Owned { level_indices, .. } => level_indices.store(Arc::new(convert(&level_indices.load()))),
+            DataStorage::Indexed { index_levels, .. } => index_levels.store(Arc::new(convert(&index_levels.load()))),
         }
+        self
+    }
 
-        let final_bitmap = combined_bitmap
-            .ok_or(GLobalError::FilterData(FilterDataError::EmptyOperations))?;
-        // Формируем итоговое описание
-        let description = descriptions.join(" AND ");
-        // Применяем результат ОДИН раз
-        self.apply_field_bitmap(final_bitmap, description)
+    pub fn bitmap_levels(&self) -> bool {
+        *self.bitmap_levels.read()
     }
 
-    pub fn filter_by_fields_ops(
-        &self,
-        fields: &[(&str, &[(FieldOperation, Op)])],
-    ) -> GlobalResult<&Self> {
-        if fields.is_empty() {
-            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+    // Сколько уровней истории хранится bitmap-ом
+    pub fn bitmap_levels_count(&self) -> usize {
+        let levels = match &self.storage {
+            DataStorage::Owned { level_indices, .. } => level_indices.load(),
+            DataStorage::Indexed { index_levels, .. } => index_levels.load(),
+        };
+        levels.iter().filter(|indices| indices.is_bitmap()).count()
+    }
+
+    /// Ограничить число одновременных тяжелых операций
+    /// 
+    /// Тяжелые операции - построение индексов и фильтры предикатом по выборке
+    /// от HEAVY_FILTER_THRESHOLD строк. Остальные ждут свободный слот в порядке
+    /// прихода (или получают ошибку - по QueuePolicy), поэтому всплеск запросов
+    /// дашборда не занимает все ядра. Операции по индексу не ограничиваются.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_concurrency_limit(2, QueuePolicy::Timeout(Duration::from_secs(5)));
+    /// match data.filter(|order| order.total > 100) {
+    ///     Err(GLobalError::FilterData(FilterDataError::QueueTimeout { .. })) => { /* 503 */ }
+    ///     result => { result?; }
+    /// }
+    /// 
+    pub fn set_concurrency_limit(&self, permits: usize, policy: QueuePolicy) -> &Self {
+        // Операции, уже получившие слот, досчитываются по старому ограничителю
+        *self.concurrency_limiter.write() = Some(Arc::new(ConcurrencyLimiter::new(permits, policy)));
+        self
+    }
+
+    pub fn clear_concurrency_limit(&self) -> &Self {
+        *self.concurrency_limiter.write() = None;
+        self
+    }
+
+    pub fn concurrency_stats(&self) -> Option<LimiterStats> {
+        self.concurrency_limiter.read().as_ref().map(|limiter| limiter.stats())
+    }
+
+    // Слот для тяжелой операции; берется до write_lock, чтобы ожидание
+    // в очереди не блокировало навигацию и легкие запросы
+    fn acquire_heavy(&self) -> GlobalResult<Option<LimiterPermit>> {
+        let limiter = self.concurrency_limiter.read().clone();
+        limiter.map(|limiter| limiter.acquire()).transpose()
+    }
+
+    // История + новый текущий уровень; при bitmap_levels бывший текущий уровень сжимается
+    fn push_level_indices(&self, levels: &[LevelIndices], indices: Arc<Vec<usize>>) -> Vec<LevelIndices> {
+        let mut new_levels = Vec::with_capacity(levels.len() + 1);
+        new_levels.extend_from_slice(levels);
+        if self.bitmap_levels()
+            && let Some(previous) = new_levels.last_mut()
+            && levels.len() > 1
+        {
+            *previous = previous.compact();
         }
-        let indexes: Vec<Arc<IndexType<T>>> = fields
-        .iter()
-        .map(|(name, _)| self.get_index(name))
-        .collect::<Result<_, _>>()?;
+        new_levels.push(LevelIndices::from(indices));
+        new_levels
+    }
 
-        let mut temp_container = Vec::<
-            (
-                &str,
-                &IndexFieldEnum,
-                &[(FieldOperation, Op)],
-            )
-        >::with_capacity(fields.len());
-        let mut temp_extractors = Vec::<(&ExtractorFieldValue<T>,&[(FieldOperation, Op)])>::with_capacity(fields.len());
-        for (n,(name,operations)) in fields.iter().enumerate(){
-            //let field_index = self.get_field_index(*name)?;
-            let index_ref = &indexes[n];
-            let (field_index,extractor) = index_ref.as_field()
-                .ok_or(GLobalError::Index(IndexError::Compatibility {
-                    name: name.to_string(),
-                    type_exist: index_ref.index_type().to_string(),
-                    type_expect: INDEX_FIELD.to_string(),
-                }
-            ))?;
-            temp_container.push((*name,field_index,*operations));
-            temp_extractors.push((extractor,*operations));
+    // Свернуть уровни старше keep_recent в checkpoint; возвращает число удаленных уровней
+    pub fn compact_history(&self, keep_recent: usize) -> usize {
+        let _guard = self.write_lock.write();
+        self.compact_levels(keep_recent)
+    }
+
+    fn auto_compact_history(&self) {
+        if let Some(keep_recent) = self.history_compaction() {
+            self.compact_levels(keep_recent);
         }
+    }
 
-        let can_use_field_indexes = self.need_to_use_index(&temp_container)?;   
-        if can_use_field_indexes{
-            self.do_filter_by_fields_ops(&temp_container)?;
-        } else {
-            let predicate = self.build_field_predicate(&temp_extractors)?;
-            self.filter(predicate)?;
+    // Вызывается под write_lock
+    fn compact_levels(&self, keep_recent: usize) -> usize {
+        let meta = self.level_meta.load();
+        // Сворачиваются уровни 1..=end, нужно минимум два
+        let end = match meta.len().checked_sub(keep_recent + 1) {
+            Some(end) if end >= 2 => end,
+            _ => return 0,
+        };
+        match &self.storage {
+            DataStorage::Owned { levels, level_indices, .. } => {
+                levels.store(Arc::new(Self::collapse_levels(&levels.load(), end)));
+                level_indices.store(Arc::new(Self::collapse_levels(&level_indices.load(), end)));
+            }
+            DataStorage::Indexed { index_levels, .. } => {
+                index_levels.store(Arc::new(Self::collapse_levels(&index_levels.load(), end)));
+            }
         }
-        Ok(self)
+        let mut new_meta = Self::collapse_levels(&meta, end);
+        new_meta[1] = Arc::new(LevelMeta::checkpoint(&meta[1..=end]));
+        self.level_meta.store(Arc::new(new_meta));
+        let removed = end - 1;
+        let current = self.current_level.load(Ordering::Acquire);
+        self.current_level.store(current.saturating_sub(removed), Ordering::Release);
+        removed
     }
 
-    #[inline]
-    fn update_level_metadata(&self, current_level: usize, info: String) -> GlobalResult<()> {
-        let mut new_level_info = Vec::with_capacity(current_level + 2);
-        new_level_info.extend_from_slice(&self.level_info.load());
-        new_level_info.push(Arc::from(info));
-        self.level_info.store(Arc::new(new_level_info));
-        self.current_level.store(current_level + 1, Ordering::Release);
-        Ok(())
+    // [source, checkpoint (данные последнего свернутого уровня), недавние уровни...]
+    fn collapse_levels<L: Clone>(levels: &[L], end: usize) -> Vec<L> {
+        if levels.len() <= end {
+            return levels.to_vec();
+        }
+        let mut result = Vec::with_capacity(levels.len() - end + 1);
+        result.push(levels[0].clone());
+        result.extend_from_slice(&levels[end..]);
+        result
     }
 
-    fn apply_owned_data(&self, result: FilterResult, info: String) -> GlobalResult<()> {
+    fn apply_owned_data(&self, result: FilterResult, step: LevelStep) -> GlobalResult<()> {
         match &self.storage {
             DataStorage::Owned { 
                 source, 
                 current_indices, 
                 current_cache,
                 full_indices: _,
+                removed: _,
                 levels, 
                 level_indices 
             } => {
                 let total_level = self.current_level.load(Ordering::Relaxed);
+                let source = source.load();
                 let bitmap_arc = Arc::new(result.bitmap);
                 // сохраняем bitmap для drill-down с индексами
                 self.source_indices_mask.store(Arc::new(Some(Arc::clone(&bitmap_arc))));
@@ -657,10 +5172,7 @@ where
                 let indices_arc = Arc::new(indices);
                 current_indices.store(Arc::clone(&indices_arc));
                 // обновляем level_indices
-                let mut new_level_indices = Vec::with_capacity(total_level + 2);
-                new_level_indices.extend_from_slice(&level_indices.load());
-                new_level_indices.push(indices_arc.clone());
-                level_indices.store(Arc::new(new_level_indices));
+                level_indices.store(Arc::new(self.push_level_indices(&level_indices.load(), Arc::clone(&indices_arc))));
                 // синхронизируем levels с level_indices
                 if indices_arc.len() < MATERIALIZATION_THRESHOLD {
                     // Материализуем для маленьких
@@ -684,7 +5196,7 @@ where
                 }
                 
                 // Метаданные
-                self.update_level_metadata(total_level, info)?;
+                self.update_level_metadata(total_level, step, indices_arc.len())?;
                 Ok(())
             },
             _ => Err(GLobalError::FilterData(FilterDataError::WrongSaveDataIndexed)),
@@ -694,7 +5206,7 @@ where
     fn apply_indexed_data(
         &self,
         indices: Vec<usize>,
-        info: String,
+        step: LevelStep,
     ) -> GlobalResult<()> {
         match &self.storage {
             DataStorage::Indexed {
@@ -710,23 +5222,22 @@ where
                 let total_level = levels_guard.len();
                 if indices.is_empty() {
                     return Err(GLobalError::FilterData(
-                        FilterDataError::DataNotFoundByIndexCurrent { name: info }
+                        FilterDataError::DataNotFoundByIndexCurrent { name: step.description }
                     ));
                 }
 
-                current_indices.store(Arc::new(indices.clone()));
+                let row_count = indices.len();
                 let indices_arc = Arc::new(indices);
-                let mut new_levels = Vec::with_capacity(total_level + 1);
-                new_levels.extend_from_slice(&levels_guard);
-                new_levels.push(indices_arc);
-                index_levels.store(Arc::new(new_levels));
+                current_indices.store(Arc::clone(&indices_arc));
+                index_levels.store(Arc::new(self.push_level_indices(&levels_guard, indices_arc)));
                 // Метаданные
-                let info_guard = self.level_info.load();
-                let mut new_info = Vec::with_capacity(info_guard.len() + 1);
-                new_info.extend_from_slice(&info_guard);
-                new_info.push(Arc::from(info));
-                self.level_info.store(Arc::new(new_info));
+                let meta_guard = self.level_meta.load();
+                let mut new_meta = Vec::with_capacity(meta_guard.len() + 1);
+                new_meta.extend_from_slice(&meta_guard);
+                new_meta.push(Arc::new(LevelMeta::new(&step.description, row_count, step.operation)));
+                self.level_meta.store(Arc::new(new_meta));
                 self.current_level.store(total_level, Ordering::Release);
+                self.auto_compact_history();
                 Ok(())
             },
             _ => Err(GLobalError::FilterData(FilterDataError::WrongSaveDataOwned)),
@@ -736,7 +5247,7 @@ where
     fn apply_filtered_items_with_bitmap(
         &self,
         final_bitmap: RoaringBitmap,
-        info: String
+        step: LevelStep
     ) -> GlobalResult<&Self> {
         let _guard = self.write_lock.write();
         
@@ -752,14 +5263,14 @@ where
 
                 if final_bitmap.is_empty() {
                     return Err(GLobalError::FilterData(
-                        FilterDataError::DataNotFoundByIndexCurrent { name: info }
+                        FilterDataError::DataNotFoundByIndexCurrent { name: step.description }
                     ));
                 }
 
                 let result = FilterResult {
                     bitmap: final_bitmap,
                 };
-                self.apply_owned_data(result, info)?;
+                self.apply_owned_data(result, step)?;
             },
             DataStorage::Indexed { index_levels, .. } => {
                 let levels_guard = index_levels.load();
@@ -773,7 +5284,7 @@ where
                 let indices: Vec<usize> = final_bitmap.iter()
                     .map(|i| i as usize)
                     .collect();
-                self.apply_indexed_data(indices, info)?;
+                self.apply_indexed_data(indices, step)?;
             }
         }
         
@@ -781,14 +5292,29 @@ where
     }
 
     fn apply_filtered_indices(
+        &self,
+        mut indices: Vec<usize>,
+        step: LevelStep,
+    ) -> GlobalResult<()> {
+        if indices.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::DataNotFound));
+        }
+        // Bitmap path уже упорядочен - сортируются только predicate / text результаты
+        if self.deterministic_order() && !indices.is_sorted() {
+            indices.par_sort_unstable();
+        }
+        self.apply_level_indices(indices, step)
+    }
+
+    // Новый уровень с индексами в заданном порядке (вызывается под write_lock)
+    fn apply_level_indices(
         &self,
         indices: Vec<usize>,
-        info: String,
+        step: LevelStep,
     ) -> GlobalResult<()> {
         if indices.is_empty() {
             return Err(GLobalError::FilterData(FilterDataError::DataNotFound));
         }
-        
         match &self.storage {
             DataStorage::Owned {
                 source,
@@ -799,6 +5325,7 @@ where
                 ..
             } => {
                 let total_level = self.current_level.load(Ordering::Relaxed);
+                let source = source.load();
                 let levels_guard = levels.load();
                 if levels_guard.len() > MAX_HISTORY {
                     return Err(GLobalError::FilterData(FilterDataError::MaxHistoryExceeded {
@@ -810,10 +5337,7 @@ where
                 let indices_arc = Arc::new(indices);
                 current_indices.store(indices_arc.clone());
                 self.source_indices_mask.store(Arc::new(None));
-                let mut new_level_indices = Vec::with_capacity(total_level + 2);
-                new_level_indices.extend_from_slice(&level_indices.load());
-                new_level_indices.push(indices_arc.clone());
-                level_indices.store(Arc::new(new_level_indices));
+                level_indices.store(Arc::new(self.push_level_indices(&level_indices.load(), Arc::clone(&indices_arc))));
                 if indices_arc.len() < MATERIALIZATION_THRESHOLD {
                     let items: Vec<Arc<T>> = indices_arc
                         .iter()
@@ -833,7 +5357,7 @@ where
                     levels.store(Arc::new(new_levels));
                     current_cache.store(Arc::new(None));
                 }
-                self.update_level_metadata(total_level, info)?;
+                self.update_level_metadata(total_level, step, indices_arc.len())?;
                 Ok(())
             },
             DataStorage::Indexed {
@@ -855,12 +5379,10 @@ where
                 }
                 
                 let indices_arc = Arc::new(indices);
+                let row_count = indices_arc.len();
                 current_indices.store(indices_arc.clone());
-                let mut new_levels = Vec::with_capacity(total_level + 2);
-                new_levels.extend_from_slice(&levels_guard);
-                new_levels.push(indices_arc);
-                index_levels.store(Arc::new(new_levels));
-                self.update_level_metadata(total_level, info)?;
+                index_levels.store(Arc::new(self.push_level_indices(&levels_guard, indices_arc)));
+                self.update_level_metadata(total_level, step, row_count)?;
                 
                 Ok(())
             }
@@ -870,17 +5392,31 @@ where
     fn apply_filtered_items_with_indices(
         &self,
         indices: Vec<usize>,
-        info: String
+        step: LevelStep
     ) -> GlobalResult<&Self> {
         let _guard = self.write_lock.write();
-        self.apply_filtered_indices(indices, info)?;
+        self.apply_filtered_indices(indices, step)?;
         Ok(self)
     }
     
-    fn apply_filtered_items<F>(&self, predicate: F, info: String) -> GlobalResult<&Self>
+    fn apply_filtered_items<F>(&self, predicate: F, step: LevelStep) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> bool + Send + Sync,
+    {
+        self.apply_filtered_items_observed(predicate, step)?;
+        Ok(self)
+    }
+
+    // apply_filtered_items + доля строк выборки, прошедших предикат
+    fn apply_filtered_items_observed<F>(&self, predicate: F, step: LevelStep) -> GlobalResult<f64>
     where
         F: Fn(&T) -> bool + Send + Sync,
     {
+        let _permit = if self.len() >= HEAVY_FILTER_THRESHOLD {
+            self.acquire_heavy()?
+        } else {
+            None
+        };
         let _guard = self.write_lock.write();
         match &self.storage {
             DataStorage::Owned {
@@ -898,6 +5434,7 @@ where
                 }
                 
                 let current = current_indices.load();
+                let source = source.load();
                 let filtered_indices: Vec<usize> = if current.len() < 10_000 {
                     current.iter()
                         .filter_map(|&idx| {
@@ -918,8 +5455,10 @@ where
                 if filtered_indices.is_empty() {
                     return Err(GLobalError::FilterData(FilterDataError::DataNotFound));
                 }
+                let observed = filtered_indices.len() as f64 / current.len().max(1) as f64;
                 // Сразу применяем через apply_filtered_items_with_indices
-                self.apply_filtered_indices(filtered_indices, info)?;
+                self.apply_filtered_indices(filtered_indices, step)?;
+                Ok(observed)
             },
             DataStorage::Indexed {
                 parent_data,
@@ -959,10 +5498,11 @@ where
                 if filtered_indices.is_empty() {
                     return Err(GLobalError::FilterData(FilterDataError::DataNotFound));
                 }
-                self.apply_filtered_indices(filtered_indices, info)?;
+                let observed = filtered_indices.len() as f64 / current.len().max(1) as f64;
+                self.apply_filtered_indices(filtered_indices, step)?;
+                Ok(observed)
             }
         }
-        Ok(self)
     }
     
     pub fn has_index(&self, name: &str) -> bool {
@@ -970,46 +5510,263 @@ where
     }
     
     pub fn drop_index(&self, name: &str) -> &Self {
+        // Сначала отмена: завершающееся построение не вернет индекс после удаления
+        self.cancel_index_build(name);
         self.indexes.remove(name);
+        self.index_recipes.remove(name);
+        self.planner_hints.remove(name);
+        self.string_options.remove(name);
+        self.decimal_options.remove(name);
+        self.range_buckets.remove(name);
+        self.value_sums.remove(name);
+        self.field_stats.remove(name);
+        if let Some(cache) = self.result_cache.lock().as_mut() {
+            cache.remove_index(name);
+        }
+        self.column_stats.retain(|_, group| group.column_position(name).is_none());
         self
     }
 
-    pub fn clear_filed_index(&self) {
-        self.indexes.retain(|_, index| {
-            if index.is_field() {
-                false
-            } else {
-                true
-            }
+    fn cancel_index_build(&self, name: &str) {
+        if let Some((_, pending)) = self.index_builds.remove(name) {
+            pending.handle.finish(IndexBuildStatus::Cancelled);
+        }
+    }
+
+    fn cancel_all_index_builds(&self) {
+        self.index_builds.retain(|_, pending| {
+            pending.handle.finish(IndexBuildStatus::Cancelled);
+            false
         });
     }
 
+    pub fn clear_filed_index(&self) {
+        self.cancel_all_index_builds();
+        self.indexes.retain(|_, index| !index.is_field());
+        self.index_recipes.retain(|_, recipe| recipe.index_type != INDEX_FIELD);
+        self.planner_hints.clear();
+        self.string_options.clear();
+        self.decimal_options.clear();
+        self.value_sums.clear();
+        self.unique_indexes.clear();
+    }
+
     pub fn clear_text_indexes(&self) {
         self.indexes.retain(|_k, v| !v.is_text());
+        self.index_recipes.retain(|_, recipe| recipe.index_type != INDEX_TEXT);
     }
 
     // Очистить все индексы
     pub fn clear_all_indexes(&self) {
+        self.cancel_all_index_builds();
         self.indexes.clear();
+        self.index_recipes.clear();
+        self.range_buckets.clear();
+        self.column_stats.clear();
+        self.planner_hints.clear();
+        self.string_options.clear();
+        self.decimal_options.clear();
+        self.value_sums.clear();
+        self.unique_indexes.clear();
+        self.clear_result_cache();
     }
     
     pub fn list_indexes(&self) -> Vec<String> {
         self.indexes.iter().map(|entry| entry.key().clone()).collect()
     }
     
+    /// Проверить, что индексы соответствуют данным
+    /// 
+    /// Для каждого индекса заново считается fingerprint данных и сравнивается
+    /// с сохраненным при построении, плюс выборка строк сверяется с bitmap-ами.
+    /// Ловит устаревшие индексы после изменения данных в обход FilterData
+    /// (interior mutability) - такие индексы надо перестроить через `rebuild_index`.
+    /// 
+    /// # Example
+    /// 
+    /// for name in data.stale_indexes() {
+    ///     data.rebuild_index(&name)?;
+    /// }
+    /// 
     pub fn validate_indexes(&self) -> bool {
-        if let DataStorage::Indexed { parent_data, .. } = &self.storage {
-                if parent_data.strong_count() == 0 {
-                    return false;
+        if let DataStorage::Indexed { parent_data, .. } = &self.storage
+            && parent_data.strong_count() == 0
+        {
+            return false;
+        }
+        self.stale_indexes().is_empty()
+    }
+
+    // Индексы, не прошедшие проверку fingerprint-а или выборочной сверки
+    pub fn stale_indexes(&self) -> Vec<String> {
+        let items = self.index_base_items();
+        let removed = self.removed_rows();
+        let mut stale: Vec<String> = self.indexes
+            .iter()
+            .filter(|entry| {
+                let index = entry.value();
+                if !index.is_valid() {
+                    return true;
                 }
-            }
-        // Проверяем каждый индекс
-        for entry in self.indexes.iter() {
-            if !entry.value().is_valid() {
-                return false;
+                let Some((content, fingerprint)) = self.index_recipes.get(entry.key())
+                    .map(|recipe| (Arc::clone(&recipe.content), recipe.fingerprint))
+                else {
+                    return false;
+                };
+                IndexFingerprint::compute(&items, &content) != fingerprint
+                    || !integrity::spot_check(index, &items, &content, &removed, INTEGRITY_SAMPLE_SIZE)
+            })
+            .map(|entry| entry.key().clone())
+            .collect();
+        stale.sort();
+        stale
+    }
+
+    /// Разбор оценки field индекса: причины и конкретные рекомендации
+    /// 
+    /// # Example
+    /// 
+    /// let report = data.index_report("price")?;
+    /// println!("{report}");
+    /// if report.recommends(&IndexRecommendation::BucketedIndex) {
+    ///     data.register_computed_field("price_bucket", |order: &Order| order.price / 100);
+    /// }
+    /// 
+    pub fn index_report(&self, name: &str) -> GlobalResult<IndexReport> {
+        let index_ref = self.stored_index(name)?;
+        let (field_index, _) = index_ref.as_field().ok_or(GLobalError::Index(IndexError::Compatibility {
+            name: name.to_string(),
+            type_exist: index_ref.index_type().to_string(),
+            type_expect: INDEX_FIELD.to_string(),
+        }))?;
+        Ok(match self.base_positions_mask() {
+            Some(mask) => field_index.report_within(name, &mask),
+            None => field_index.report(name),
+        })
+    }
+
+    /// Отчет о качестве данных по всем индексам
+    /// 
+    /// Для каждого индекса: null-ы и пустые строки, min/max, кардинальность,
+    /// доля самого частого значения (перекос) и оценка IndexAnalizer.
+    /// Считается по строкам, на которых построены индексы (исходный уровень),
+    /// под base filter - только по строкам фильтра.
+    /// 
+    /// # Example
+    /// 
+    /// let report = data.data_quality_report();
+    /// println!("{report}");
+    /// if report.field("email").is_some_and(|field| field.missing_ratio() > 0.1) { ... }
+    /// 
+    pub fn data_quality_report(&self) -> DataQualityReport {
+        let mask = self.base_positions_mask();
+        let mut fields: Vec<FieldQuality> = self.indexes
+            .iter()
+            .map(|entry| match entry.value().as_ref() {
+                IndexType::Field((field_index, _)) => match &mask {
+                    Some(mask) => Self::field_quality_within(entry.key(), field_index, mask),
+                    None => Self::field_quality(entry.key(), field_index),
+                },
+                IndexType::Text(text_index) => Self::text_quality(entry.key(), text_index, mask.as_ref()),
+            })
+            .collect();
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+        DataQualityReport {
+            rows: mask.map_or_else(|| self.index_base_len(), |mask| mask.len() as usize),
+            fields,
+        }
+    }
+
+    fn field_quality(name: &str, field_index: &IndexFieldEnum) -> FieldQuality {
+        let rows = field_index.len();
+        let unique_values = field_index.unique_values_count();
+        // value_bitmaps упорядочены по значению
+        let value_bitmaps = field_index.value_bitmaps();
+        let empty = value_bitmaps
+            .iter()
+            .find(|(value, _)| matches!(value, FieldValue::String(text) if text.is_empty()))
+            .map(|(_, bitmap)| bitmap.len())
+            .unwrap_or(0);
+        FieldQuality {
+            name: name.to_string(),
+            index_type: INDEX_FIELD,
+            value_type: field_index.type_name(),
+            rows,
+            nulls: field_index.nulls().len(),
+            empty,
+            min: value_bitmaps.first().map(|(value, _)| value.clone()),
+            max: value_bitmaps.last().map(|(value, _)| value.clone()),
+            unique_values,
+            cardinality_ratio: if rows == 0 { 0.0 } else { unique_values as f64 / rows as f64 },
+            max_value_ratio: field_index.max_value_ratio(),
+            is_skewed: field_index.is_skewed(),
+            verdict: Some(field_index.index_analize()),
+        }
+    }
+
+    // field_quality только по позициям mask (base filter)
+    fn field_quality_within(name: &str, field_index: &IndexFieldEnum, mask: &RoaringBitmap) -> FieldQuality {
+        let report = field_index.report_within(name, mask);
+        let counts: Vec<(FieldValue, u64)> = field_index.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.intersection_len(mask)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let empty = counts
+            .iter()
+            .find(|(value, _)| matches!(value, FieldValue::String(text) if text.is_empty()))
+            .map_or(0, |(_, count)| *count);
+        FieldQuality {
+            name: name.to_string(),
+            index_type: INDEX_FIELD,
+            value_type: field_index.type_name(),
+            rows: report.rows,
+            nulls: field_index.nulls().intersection_len(mask),
+            empty,
+            min: counts.first().map(|(value, _)| value.clone()),
+            max: counts.last().map(|(value, _)| value.clone()),
+            unique_values: report.unique_values,
+            cardinality_ratio: report.cardinality_ratio,
+            max_value_ratio: report.dominant_value_ratio,
+            is_skewed: report.is_skewed,
+            verdict: Some(report.verdict),
+        }
+    }
+
+    // mask - позиции base filter: строки вне него не учитываются
+    fn text_quality(name: &str, text_index: &TextIndex<T>, mask: Option<&RoaringBitmap>) -> FieldQuality {
+        let total = text_index.stats().total_items;
+        let positions: Box<dyn Iterator<Item = usize>> = match mask {
+            Some(mask) => Box::new(mask.range(0..total as u32).map(|pos| pos as usize)),
+            None => Box::new(0..total),
+        };
+        let mut rows = 0;
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for pos in positions {
+            rows += 1;
+            if let Some(text) = text_index.get_text(pos) {
+                *counts.entry(text).or_default() += 1;
             }
         }
-        true
+        let unique_values = counts.len();
+        let max_count = counts.values().copied().max().unwrap_or(0);
+        let max_value_ratio = if rows == 0 { 0.0 } else { max_count as f64 / rows as f64 };
+        FieldQuality {
+            name: name.to_string(),
+            index_type: INDEX_TEXT,
+            value_type: "String",
+            rows,
+            nulls: 0,
+            empty: counts.get("").copied().unwrap_or(0) as u64,
+            min: counts.keys().next().map(|text| FieldValue::String(text.to_string())),
+            max: counts.keys().next_back().map(|text| FieldValue::String(text.to_string())),
+            unique_values,
+            cardinality_ratio: if rows == 0 { 0.0 } else { unique_values as f64 / rows as f64 },
+            max_value_ratio,
+            // Тот же порог, что у field индексов
+            is_skewed: max_value_ratio > 0.5,
+            verdict: None,
+        }
     }
 
     /// Создать Text индекс для быстрого substring search
@@ -1035,29 +5792,58 @@ where
         name: &str,
         extractor: F,
     ) -> GlobalResult< &Self>
+    where
+        F: Fn(&T) -> String + Send + Sync + 'static + Clone,
+    {
+        self.create_text_index_with_options(name, extractor, TextIndexOptions::default())
+    }
+
+    /// Создать text индекс с языковым анализатором
+    /// 
+    /// Анализатор нормализует тексты и запросы одинаково: для CJK - биграммы,
+    /// для кириллицы и греческого - свертка вариантов букв (ё/е, тонос).
+    /// 
+    /// # Example
+    /// 
+    /// data.create_text_index_with_options("title", |book| book.title.clone(), TextIndexOptions::cjk())?;
+    /// data.search_with_text("title", "東京")?;
+    /// 
+    pub fn create_text_index_with_options<F>(
+        &self,
+        name: &str,
+        extractor: F,
+        options: TextIndexOptions,
+    ) -> GlobalResult<&Self>
     where
         F: Fn(&T) -> String + Send + Sync + 'static + Clone,
     {
         if self.has_index(name) {
-            if let Err(err) = self.check_index_type_compability(
-            name, 
-            INDEX_TEXT,
-            IndexCompatibilityAction::Replace
-            ){
-                return Err(GLobalError::Index(err))
-            }
-            self.drop_index(name);
+            self.check_index_type_compability(
+                name, 
+                INDEX_TEXT, 
+                IndexCompatibilityAction::Replace
+            ).map_err(GLobalError::Index)?;
         }
-        let mut text_index = TextIndex::new_tri_gram();
-        let items = self.items();
-        text_index.build(&items, extractor);
-        self.indexes.insert(
-            name.to_string(),
-            Arc::new(IndexType::Text(text_index))
-        );
+        let content = Self::create_field_value_extractor(extractor.clone());
+        self.install_index(name, self.text_index_builder(extractor, options), content, INDEX_TEXT)?;
         Ok(self)
     }
 
+    // Использовать словарь n-грамм родителя (подгруппы GroupData)
+    pub(crate) fn with_ngram_dictionary(mut self, dictionary: Arc<NgramDictionary>) -> Self {
+        self.ngram_dictionary = dictionary;
+        self
+    }
+
+    pub(crate) fn ngram_dictionary(&self) -> Arc<NgramDictionary> {
+        Arc::clone(&self.ngram_dictionary)
+    }
+
+    // Число n-грамм в словаре, общем для text индексов дерева групп
+    pub fn ngram_dictionary_len(&self) -> usize {
+        self.ngram_dictionary.len()
+    }
+
     /// Быстрый substring search через Text индекс
     /// 
     /// # Пример
@@ -1087,7 +5873,42 @@ where
                 type_expect: INDEX_TEXT.to_string(),
             }
         ))?;
-        Ok(ngram_index.search(query))
+        let mut indices = self.index_positions_to_indices(ngram_index.search(query));
+        indices.retain(|&idx| self.in_base(idx));
+        Ok(indices)
+    }
+
+    // Количество совпадений text запроса в текущей выборке (без drill-down)
+    pub fn count_text_matches(&self, name: &str, query: &str) -> GlobalResult<usize> {
+        let ids = self.text_match_ids(name, query)?;
+        Ok(self.count_current_ids(&ids))
+    }
+
+    // Совпадения text запроса по всем строкам индекса - индексы хранилища
+    pub(crate) fn text_match_ids(&self, name: &str, query: &str) -> GlobalResult<RoaringBitmap> {
+        Ok(self.get_indices_with_text(name, query)?
+            .into_iter()
+            .map(|idx| idx as u32)
+            .collect())
+    }
+
+    // Сколько строк текущей выборки входит в ids (индексы хранилища)
+    pub(crate) fn count_current_ids(&self, ids: &RoaringBitmap) -> usize {
+        self.current_indices()
+            .iter()
+            .filter(|&&idx| ids.contains(idx as u32))
+            .count()
+    }
+
+    // Позиции текстового индекса -> индексы хранилища
+    fn index_positions_to_indices(&self, positions: Vec<usize>) -> Vec<usize> {
+        match &self.storage {
+            DataStorage::Owned { .. } => positions,
+            DataStorage::Indexed { source_indices, .. } => positions
+                .into_iter()
+                .filter_map(|pos| source_indices.get(pos).copied())
+                .collect(),
+        }
     }
 
     /// Применить n-gram фильтр (drill-down)
@@ -1105,7 +5926,7 @@ where
             }));
         }
         let current_indices = self.current_indices();
-        let intersected_indices = if current_indices.len() == self.parent_data().map(|d| d.len()).unwrap_or(0) {
+        let intersected_indices = if current_indices.len() == self.storage_rows().map(|d| d.len()).unwrap_or(0) {
             // Если текущие индексы = все данные, используем результат напрямую
             text_indices
         } else {
@@ -1117,11 +5938,15 @@ where
                 name: name.to_string() 
             }));
         }
-        if self.parent_data().is_none(){
+        if self.storage_rows().is_none(){
             return Err(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty)) 
         }
         let desc = format!("Text search: '{}'", query);
-        self.apply_filtered_items_with_indices(intersected_indices, desc)
+        let step = QueryStep::Text {
+            name: name.to_string(),
+            query: query.to_string(),
+        };
+        self.apply_filtered_items_with_indices(intersected_indices, LevelStep::new(desc, step))
     }
 
     /// Комплексный поиск по словам через текстовый индекс
@@ -1153,7 +5978,7 @@ where
                 type_expect: INDEX_TEXT.to_string() 
             }
         ))?;
-        Ok(index.search_complex_words(or_words,and_words,not_words))
+        Ok(self.index_positions_to_indices(index.search_complex_words(or_words,and_words,not_words)))
     }
 
 
@@ -1199,7 +6024,7 @@ where
             }))
         }
         let current_indices = self.current_indices();
-        let intersected_indices = if current_indices.len() == self.parent_data().map(|d| d.len()).unwrap_or(0) {
+        let intersected_indices = if current_indices.len() == self.storage_rows().map(|d| d.len()).unwrap_or(0) {
             // Если текущие индексы = все данные, используем результат напрямую
             complex_indices
         } else {
@@ -1211,13 +6036,19 @@ where
                 name: name.to_string() 
             }))
         }
-        if self.parent_data().is_none(){
+        if self.storage_rows().is_none(){
             return Err(GLobalError::FilterData(FilterDataError::ParentDataIsEmpty)) 
         }
         let desc = Self::format_complex_query_desc(or_words, and_words, not_words);
+        let step = QueryStep::ComplexText {
+            name: name.to_string(),
+            or_words: or_words.iter().map(|word| word.to_string()).collect(),
+            and_words: and_words.iter().map(|word| word.to_string()).collect(),
+            not_words: not_words.iter().map(|word| word.to_string()).collect(),
+        };
         self.apply_filtered_items_with_indices(
             intersected_indices,
-            format!("Complex search: {}", desc)
+            LevelStep::new(format!("Complex search: {}", desc), step)
         )
     }
 
@@ -1281,7 +6112,10 @@ where
                 type_expect: INDEX_TEXT.to_string() 
             }
         ))?;
-        Ok(index.stats())
+        Ok(match self.base_positions_mask() {
+            Some(mask) => index.stats_within(&mask),
+            None => index.stats(),
+        })
     }
 
     /// Получить топ N самых частых n-грамм
@@ -1304,7 +6138,10 @@ where
                 type_expect: INDEX_TEXT.to_string() 
             }
         ))?;
-        Ok(index.top_ngrams(n))
+        Ok(match self.base_positions_mask() {
+            Some(mask) => index.top_ngrams_within(n, &mask),
+            None => index.top_ngrams(n),
+        })
     }
 
     /// Список всех n-грамм в индексе
@@ -1319,7 +6156,10 @@ where
                 type_expect: INDEX_TEXT.to_string() 
             }
         ))?;
-        Ok(index.list_ngrams())
+        Ok(match self.base_positions_mask() {
+            Some(mask) => index.list_ngrams_within(&mask),
+            None => index.list_ngrams(),
+        })
     }
 
     /// Получить статистику по конкретной n-грамме
@@ -1341,7 +6181,10 @@ where
                 type_expect: INDEX_TEXT.to_string() 
             }
         ))?;
-        Ok(index.ngram_stats(ngram))
+        Ok(match self.base_positions_mask() {
+            Some(mask) => index.ngram_stats_within(ngram, &mask),
+            None => index.ngram_stats(ngram),
+        })
     }
 
     // Filter Methods
@@ -1350,7 +6193,7 @@ where
     where
         F: Fn(&T) -> bool + Sync + Send,
     {
-        self.apply_filtered_items(predicate, "Filtered".to_string())
+        self.apply_filtered_items(predicate, LevelStep::new("Filtered".to_string(), QueryStep::Predicate))
     }
 
     pub fn filter<F>(&self, predicate: F) -> GlobalResult<&Self>
@@ -1363,6 +6206,28 @@ where
 
     // Navigation Methods
 
+    /// Закрепить текущий уровень на время жизни guard-а
+    /// 
+    /// Читатель получает согласованный вид без внешних блокировок:
+    /// конкурентные reset_to_source / filter_* дождутся drop guard-а.
+    /// 
+    /// # Example
+    /// 
+    /// let pinned = data.pin_level();
+    /// let total = pinned.len();
+    /// let items = pinned.items(); // тот же уровень, items.len() == total
+    /// drop(pinned);
+    /// 
+    pub fn pin_level(&self) -> LevelGuard<'_, T> {
+        // read_recursive - повторный pin в том же потоке не блокируется
+        let lock = self.write_lock.read_recursive();
+        LevelGuard {
+            data: self,
+            level: self.current_level(),
+            _lock: lock,
+        }
+    }
+
     // Сброс к исходным данным с ПОЛНОЙ очисткой
     // 
     // Очищает:
@@ -1370,19 +6235,31 @@ where
     // - Историю операций
     pub fn reset_to_source(&self) -> &Self {
         let _guard = self.write_lock.write();
+        self.reset_levels();
+        self
+    }
+
+    // Вызывается под write_lock
+    fn reset_levels(&self) {
+        if let Some(base) = self.base_filter.load_full().as_ref() {
+            self.reset_to_base(base);
+            return;
+        }
         match &self.storage {
             DataStorage::Owned {
-                source,
                 current_indices,
                 current_cache,
-                full_indices,  // Используем кеш!
+                full_indices,
                 levels,
                 level_indices,
+                ..
             } => {
-                current_indices.store(Arc::clone(full_indices));
-                current_cache.store(Arc::new(Some(Arc::clone(source))));
-                levels.store(Arc::new(vec![Arc::clone(source)]));
-                level_indices.store(Arc::new(vec![Arc::clone(full_indices)]));
+                // Строки исходного уровня лежат в source - кеш не нужен
+                let full_indices = full_indices.load_full();
+                current_indices.store(Arc::clone(&full_indices));
+                current_cache.store(Arc::new(None));
+                levels.store(Arc::new(vec![Arc::new(Vec::new())]));
+                level_indices.store(Arc::new(vec![full_indices.into()]));
             },
             DataStorage::Indexed {
                 source_indices,
@@ -1391,18 +6268,111 @@ where
                 ..
             } => {
                 current_indices.store(Arc::clone(source_indices));
-                index_levels.store(Arc::new(vec![Arc::clone(source_indices)]));
+                index_levels.store(Arc::new(vec![Arc::clone(source_indices).into()]));
             }
         }
-        self.level_info.store(Arc::new(vec![Arc::from("Source")]));
+        let source_len = self.index_base_len();
+        self.level_meta.store(Arc::new(vec![Arc::new(LevelMeta::source(source_len))]));
         self.current_level.store(0, Ordering::Release);
         self.source_indices_mask.store(Arc::new(None));
+    }
+
+    // Исходный уровень = base filter: уровни ниже него недоступны
+    fn reset_to_base(&self, base: &BaseFilter<T>) {
+        match &self.storage {
+            DataStorage::Owned {
+                current_indices,
+                current_cache,
+                levels,
+                level_indices,
+                ..
+            } => {
+                let items = base.items.clone().unwrap_or_default();
+                current_indices.store(Arc::clone(&base.indices));
+                current_cache.store(Arc::new(Some(Arc::clone(&items))));
+                levels.store(Arc::new(vec![items]));
+                level_indices.store(Arc::new(vec![Arc::clone(&base.indices).into()]));
+            },
+            DataStorage::Indexed {
+                current_indices,
+                index_levels,
+                ..
+            } => {
+                current_indices.store(Arc::clone(&base.indices));
+                index_levels.store(Arc::new(vec![Arc::clone(&base.indices).into()]));
+            }
+        }
+        self.level_meta.store(Arc::new(vec![Arc::new(LevelMeta::source(base.indices.len()))]));
+        self.current_level.store(0, Ordering::Release);
+        self.source_indices_mask.store(Arc::new(Some(Arc::clone(&base.bitmap))));
+    }
+
+    /// Постоянный фильтр строк (row-level security)
+    /// 
+    /// `bitmap` - индексы хранилища (как у `query_ids`). Становится исходным
+    /// уровнем: все запросы, представления и `reset_to_source` работают
+    /// только внутри него. Повторный вызов может только сузить фильтр,
+    /// снять его нельзя - FilterData можно безопасно отдать чужому коду.
+    /// История drill-down сбрасывается.
+    /// 
+    /// # Example
+    /// 
+    /// data.set_base_filter_query(&QueryExpr::field("tenant_id", vec![(FieldOperation::eq(42u64), Op::And)]))?;
+    /// untrusted_handler(&data); // видит только строки tenant 42
+    /// 
+    pub fn set_base_filter(&self, bitmap: RoaringBitmap) -> &Self {
+        let _guard = self.write_lock.write();
+        let bitmap = self.base_ids() & bitmap;
+        let indices: Arc<Vec<usize>> = Arc::new(bitmap.iter().map(|idx| idx as usize).collect());
+        let items = match &self.storage {
+            DataStorage::Owned { source, .. } => Some(Arc::new(
+                indices.iter().filter_map(|&idx| source.load().get(idx).cloned()).collect()
+            )),
+            DataStorage::Indexed { .. } => None,
+        };
+        // Представления тоже не должны выходить за base filter
+        for mut view in self.views.iter_mut() {
+            let mut restricted = (**view).clone();
+            restricted.bitmap &= &bitmap;
+            *view = Arc::new(restricted);
+        }
+        self.base_filter.store(Arc::new(Some(Arc::new(BaseFilter {
+            bitmap: Arc::new(bitmap),
+            indices,
+            items,
+        }))));
+        self.reset_levels();
         self
     }
+
+    pub fn set_base_filter_query(&self, expr: &QueryExpr) -> GlobalResult<&Self> {
+        let bitmap = self.query_bitmap(expr)?;
+        Ok(self.set_base_filter(bitmap))
+    }
+
+    pub fn base_filter(&self) -> Option<Arc<RoaringBitmap>> {
+        self.base_filter.load().as_ref().as_ref().map(|base| Arc::clone(&base.bitmap))
+    }
+
+    pub fn has_base_filter(&self) -> bool {
+        self.base_filter.load().is_some()
+    }
+
+    // Handle для parent_data / get_index в обход base filter; под base filter - ошибка
+    pub fn trusted_access(&self) -> GlobalResult<TrustedAccess<T>> {
+        if self.has_base_filter() {
+            return Err(GLobalError::FilterData(FilterDataError::BaseFilterActive));
+        }
+        let rows = match &self.storage {
+            DataStorage::Owned { source, .. } => TrustedRows::Owned(Arc::clone(source)),
+            DataStorage::Indexed { parent_data, .. } => TrustedRows::Indexed(Weak::clone(parent_data)),
+        };
+        Ok(TrustedAccess { rows, indexes: Arc::clone(&self.indexes) })
+    }
     
     pub fn go_to_level(&self, target_level: usize) -> &Self {
         let _guard = self.write_lock.write();
-        let total_levels = self.level_info.load().len();
+        let total_levels = self.level_meta.load().len();
         if target_level >= total_levels {
             return self;
         }
@@ -1415,14 +6385,16 @@ where
                 level_indices,
                 ..
             } => {
-                if let Some(indices) = level_indices.load().get(target_level) {
-                    current_indices.store(Arc::clone(indices));
-                }
-                // Восстанавливаем кеш
-                if let Some(cached_level) = levels.load().get(target_level) {
-                    current_cache.store(Arc::new(Some(Arc::clone(cached_level))));
-                } else {
-                    current_cache.store(Arc::new(None));
+                let target_len = level_indices.load().get(target_level).map(|indices| {
+                    current_indices.store(indices.indices());
+                    indices.len()
+                });
+                // Восстанавливаем кеш; пустой placeholder большого уровня - не кеш
+                match levels.load().get(target_level) {
+                    Some(cached_level) if cached_level.len() == target_len.unwrap_or(0) => {
+                        current_cache.store(Arc::new(Some(Arc::clone(cached_level))));
+                    }
+                    _ => current_cache.store(Arc::new(None)),
                 }
                 // Обрезаем историю
                 if target_level < total_levels - 1 {
@@ -1433,7 +6405,7 @@ where
                         .collect();
                     levels.store(Arc::new(trimmed_levels));
                     
-                    let trimmed_indices: Vec<Arc<Vec<usize>>> = level_indices.load()
+                    let trimmed_indices: Vec<LevelIndices> = level_indices.load()
                         .iter()
                         .take(target_level + 1)
                         .cloned()
@@ -1447,11 +6419,11 @@ where
                 ..
             } => {
                 if let Some(indices) = index_levels.load().get(target_level) {
-                    current_indices.store(Arc::clone(indices));
+                    current_indices.store(indices.indices());
                 }
                 
                 if target_level < total_levels - 1 {
-                    let trimmed: Vec<Arc<Vec<usize>>> = index_levels.load()
+                    let trimmed: Vec<LevelIndices> = index_levels.load()
                         .iter()
                         .take(target_level + 1)
                         .cloned()
@@ -1462,15 +6434,15 @@ where
         }
         // Обновляем метаданные...
         if target_level < total_levels - 1 {
-            let trimmed_info: Vec<Arc<str>> = self.level_info.load()
+            let trimmed_meta: Vec<Arc<LevelMeta>> = self.level_meta.load()
                 .iter()
                 .take(target_level + 1)
                 .cloned()
                 .collect();
-            self.level_info.store(Arc::new(trimmed_info));
+            self.level_meta.store(Arc::new(trimmed_meta));
         }
         self.current_level.store(target_level, Ordering::Relaxed);
-        let source_len = self.parent_data().map(|d| d.len()).unwrap_or(0);
+        let source_len = self.storage_rows().map(|d| d.len()).unwrap_or(0);
         if source_len > 0 {
             let current = match &self.storage {
                 DataStorage::Owned { current_indices, .. } => current_indices.load(),
@@ -1549,9 +6521,11 @@ where
 
     pub fn memory_stats(&self) -> MemoryStats {
         match &self.storage {
-            DataStorage::Owned { levels, .. } => {
+            DataStorage::Owned { levels, level_indices, .. } => {
                 let current_lvl = self.current_level.load(Ordering::Acquire);
                 let levels_guard = levels.load();
+                // Строки исходного уровня лежат в source, кеш уровня - placeholder
+                let source_len = level_indices.load().first().map_or(0, |indices| indices.len());
                 let mut stats = MemoryStats {
                     current_level: current_lvl,
                     stored_levels: levels_guard.len(),
@@ -1561,7 +6535,7 @@ where
                     wasted_items: 0,
                 };
                 for (idx, level_data) in levels_guard.iter().enumerate() {
-                    let count = level_data.len();
+                    let count = if idx == 0 { source_len } else { level_data.len() };
                     stats.total_stored_items += count;
                     if idx == current_lvl {
                         stats.current_level_items = count;
@@ -1602,8 +6576,63 @@ where
         }
     }
     
+    /// Память индексов: bitmap-ы, sorted_values и накладные расходы по каждому
+    ///
+    /// Bitmap-ы считаются по сериализованному размеру (`Index::memory_size`),
+    /// карты значений и строки - оценкой. Индексы отсортированы по имени.
+    ///
+    /// # Example
+    ///
+    /// for stats in data.index_memory_stats() {
+    ///     println!("{}: {} KB", stats.name, stats.total_bytes / 1024);
+    /// }
+    ///
+    pub fn index_memory_stats(&self) -> Vec<IndexMemoryStats> {
+        let mut stats: Vec<IndexMemoryStats> = self.indexes
+            .iter()
+            .map(|entry| {
+                let (kind, bitmap_bytes, sorted_values_bytes, overhead_bytes) = match entry.value().as_ref() {
+                    IndexType::Field((field_index, _)) => (
+                        Some(field_index.kind()),
+                        field_index.bitmap_bytes(),
+                        field_index.sorted_values_bytes(),
+                        field_index.map_overhead_bytes() + field_index.value_heap_bytes(),
+                    ),
+                    IndexType::Text(text_index) => (None, text_index.postings_bytes(), 0, text_index.texts_bytes()),
+                };
+                IndexMemoryStats {
+                    name: entry.key().clone(),
+                    index_type: entry.value().index_type().to_string(),
+                    kind,
+                    bitmap_bytes,
+                    sorted_values_bytes,
+                    overhead_bytes,
+                    total_bytes: bitmap_bytes + sorted_values_bytes + overhead_bytes,
+                }
+            })
+            .collect();
+        stats.sort_by(|left, right| left.name.cmp(&right.name));
+        stats
+    }
+    
     pub fn level_name(&self, level: usize) -> Option<Arc<str>> {
-        self.level_info.load().get(level).map(Arc::clone)
+        self.level_meta.load().get(level).map(|meta| Arc::clone(&meta.description))
+    }
+
+    /// Метаданные всех сохраненных уровней (breadcrumb)
+    ///
+    /// # Example
+    ///
+    /// for (level, meta) in data.levels_meta().iter().enumerate() {
+    ///     println!("{level}: {} ({} rows)", meta.description, meta.row_count);
+    /// }
+    ///
+    pub fn levels_meta(&self) -> Vec<LevelMeta> {
+        self.level_meta.load().iter().map(|meta| (**meta).clone()).collect()
+    }
+
+    pub fn level_meta(&self, level: usize) -> Option<LevelMeta> {
+        self.level_meta.load().get(level).map(|meta| (**meta).clone())
     }
 
     pub fn builder() -> FilterDataBuilder<T> {
@@ -1615,7 +6644,7 @@ where
     }
 
     pub fn filter_state_info(&self) -> FilterStateInfo {
-        let source_len = self.parent_data().map(|d| d.len()).unwrap_or(0);
+        let source_len = self.storage_rows().map(|d| d.len()).unwrap_or(0);
         let filtered_len = self.len();
         let mask_opt = self.source_indices_mask.load();
         let has_mask = mask_opt.is_some();
@@ -1636,6 +6665,239 @@ where
     }
 }
 
+#[cfg(feature = "polars")]
+impl FilterData<crate::dataframe::FrameRow> {
+    /// Загрузка DataFrame: строка на строку, field индекс на каждую колонку
+    /// 
+    /// Числовые и bool колонки индексируются своим типом, остальные - строкой.
+    /// Null значения попадают в nulls индекса (см. `NullMode`).
+    /// 
+    /// # Example
+    /// 
+    /// let data = FilterData::from_polars(&df)?;
+    /// data.filter_by_field_ops("price", &[(FieldOperation::gt(100i64), Op::And)])?;
+    /// let result = data.to_polars(&["id", "price"])?;
+    /// 
+    pub fn from_polars(df: &::polars::prelude::DataFrame) -> GlobalResult<Self> {
+        use crate::dataframe::{FrameRow, column_kind};
+        use ::polars::prelude::DataType;
+
+        let data = Self::from_vec(crate::dataframe::frame_rows(df));
+        for (pos, column) in df.get_columns().iter().enumerate() {
+            let name = column.name().as_str();
+            macro_rules! column_index {
+                ($variant:ident) => {
+                    data.create_nullable_field_index(name, move |row: &FrameRow| match row.value(pos) {
+                        Some(FieldValue::$variant(value)) => Some(value.clone()),
+                        _ => None,
+                    })?
+                };
+            }
+            match column_kind(column.dtype()) {
+                DataType::Boolean => column_index!(Bool),
+                DataType::UInt8 => column_index!(U8),
+                DataType::UInt16 => column_index!(U16),
+                DataType::UInt32 => column_index!(U32),
+                DataType::UInt64 => column_index!(U64),
+                DataType::Int8 => column_index!(I8),
+                DataType::Int16 => column_index!(I16),
+                DataType::Int32 => column_index!(I32),
+                DataType::Int64 => column_index!(I64),
+                DataType::Int128 => column_index!(I128),
+                DataType::Float32 => column_index!(F32),
+                DataType::Float64 => column_index!(F64),
+                _ => column_index!(String),
+            };
+        }
+        Ok(data)
+    }
+}
+
+/// Подготовленная замена field индекса
+/// 
+/// Возвращается из `replace_field_index_checked`: новый индекс уже построен,
+/// `diff()` сравнивает его с действующим. `commit()` атомарно подменяет индекс,
+/// drop без commit - отказ от замены.
+pub struct IndexReplacement<'a, T>
+where
+    T: Send + Sync + 'static,
+{
+    data: &'a FilterData<T>,
+    diff: IndexDiff,
+    index: IndexType<T>,
+    version: u64,
+    fingerprint: IndexFingerprint,
+    builder: IndexBuilder<T>,
+    content: ExtractorFieldValue<T>,
+}
+
+impl<T> IndexReplacement<'_, T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn diff(&self) -> &IndexDiff {
+        &self.diff
+    }
+
+    // false - индекс удален или пересоздан, пока замена ждала commit
+    pub fn commit(self) -> bool {
+        let data = self.data;
+        let name = self.diff.name;
+        if !data.has_index(&name) {
+            return false;
+        }
+        // Кеши range/value_sums привязаны к версии и устаревают сами
+        FilterData::swap_index_version(
+            &data.indexes,
+            &data.index_recipes,
+            &name,
+            self.version,
+            self.index,
+            self.fingerprint,
+            Some((self.builder, self.content, INDEX_FIELD)),
+        )
+    }
+}
+
+// Построение операций prepared запроса из параметров
+type ParamOperations<P> = Box<dyn Fn(&P) -> Vec<(FieldOperation, Op)> + Send + Sync>;
+
+/// Подготовленный параметризованный запрос по field индексу
+/// 
+/// Возвращается из `prepare_field_query`. Путь выполнения выбирается при первом
+/// `execute` и переиспользуется; `replan` сбрасывает сохраненный план.
+pub struct PreparedFieldQuery<'a, T, P>
+where
+    T: Send + Sync + 'static,
+{
+    data: &'a FilterData<T>,
+    name: String,
+    operations: ParamOperations<P>,
+    plan: OnceLock<QueryHint>,
+}
+
+impl<'a, T, P> PreparedFieldQuery<'a, T, P>
+where
+    T: Send + Sync + 'static,
+{
+    // Новый уровень drill-down с операциями для params
+    pub fn execute(&self, params: &P) -> GlobalResult<&'a FilterData<T>> {
+        let operations = (self.operations)(params);
+        if operations.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let hint = match self.plan.get() {
+            Some(hint) => *hint,
+            None => match self.data.plan_field_query(&self.name, &operations)? {
+                Some(hint) => *self.plan.get_or_init(|| hint),
+                None => QueryHint::Auto,
+            },
+        };
+        self.data.filter_by_field_ops_with_hint(&self.name, &operations, hint)
+    }
+
+    // Сохраненный план (None - запрос еще не выполнялся с готовым индексом)
+    pub fn plan(&self) -> Option<QueryHint> {
+        self.plan.get().copied()
+    }
+
+    pub fn replan(&mut self) {
+        self.plan.take();
+    }
+}
+
+/// Закрепленный уровень выборки
+/// 
+/// Возвращается из `pin_level`: пока guard жив, фильтрация и навигация
+/// (reset_to_source, go_to_level, filter_*) в других потоках ждут его drop,
+/// поэтому len() и items() видят один и тот же уровень.
+/// Навигация в том же потоке при живом guard-е приведет к deadlock.
+pub struct LevelGuard<'a, T>
+where
+    T: Send + Sync + 'static,
+{
+    data: &'a FilterData<T>,
+    level: usize,
+    _lock: RwLockReadGuard<'a, ()>,
+}
+
+impl<T> LevelGuard<'_, T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn items(&self) -> Arc<Vec<Arc<T>>> {
+        self.data.items()
+    }
+
+    pub fn items_with_ids(&self) -> Vec<(usize, Arc<T>)> {
+        self.data.items_with_ids()
+    }
+
+    pub fn current_indices(&self) -> Arc<Vec<usize>> {
+        self.data.current_indices()
+    }
+}
+
+/// Доступ к хранилищу и индексам в обход base filter
+/// 
+/// Возвращается из `trusted_access` и только до `set_base_filter`: FilterData
+/// с base filter можно отдать чужому коду, handle остается у владельца.
+/// Видит текущие строки и индексы (после push, rebuild), а не снимок.
+/// 
+/// # Example
+/// 
+/// let trusted = data.trusted_access()?;
+/// data.set_base_filter_query(&tenant_query)?;
+/// untrusted_handler(&data);
+/// let all_rows = trusted.parent_data();
+/// 
+pub struct TrustedAccess<T>
+where
+    T: Send + Sync + 'static,
+{
+    rows: TrustedRows<T>,
+    indexes: Arc<DashMap<String, Arc<IndexType<T>>>>,
+}
+
+enum TrustedRows<T>
+where
+    T: Send + Sync + 'static,
+{
+    Owned(Arc<ArcSwap<Rows<T>>>),
+    Indexed(Weak<Rows<T>>),
+}
+
+impl<T> TrustedAccess<T>
+where
+    T: Send + Sync + 'static,
+{
+    // Все строки хранилища, как FilterData::parent_data без base filter
+    pub fn parent_data(&self) -> Option<Arc<Rows<T>>> {
+        match &self.rows {
+            TrustedRows::Owned(source) => Some(source.load_full()),
+            TrustedRows::Indexed(parent_data) => parent_data.upgrade(),
+        }
+    }
+
+    pub fn get_index(&self, name: &str) -> GlobalResult<Arc<IndexType<T>>> {
+        self.indexes
+            .get(name)
+            .map(|index| Arc::clone(index.value()))
+            .ok_or(GLobalError::Index(IndexError::NotFound { name: name.to_string() }))
+    }
+}
 
 // Builder
 
@@ -1643,16 +6905,41 @@ pub struct FilterDataBuilder<T>
 where
     T: Send + Sync + 'static,
 {
-    data: Option<Vec<T>>,
+    data: Option<BuilderSource<T>>,
+    config: Option<DatasetConfig>,
     indexes: Vec<IndexDefinition<T>>,
     _phantom: PhantomData<T>,
 }
 
-struct IndexDefinition<T>
+// Откуда берутся строки: собственный Vec или view над строками родителя
+enum BuilderSource<T> {
+    Owned(Vec<T>),
+    Indexed {
+        parent_data: Arc<Rows<T>>,
+        indices: Vec<usize>,
+    },
+}
+
+// Отложенное построение индекса из батча (все виды, кроме field)
+type IndexApplier<T> = Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>;
+
+enum IndexDefinition<T>
+where
+    T: Send + Sync + 'static,
+{
+    Applier(IndexApplier<T>),
+    // Field индекс - строится пакетно вместе с остальными field индексами
+    Field(FieldDefinition<T>),
+}
+
+struct FieldDefinition<T>
 where
     T: Send + Sync + 'static,
 {
-    applier: Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>,
+    name: String,
+    column: Box<dyn BatchColumn<T>>,
+    builder: IndexBuilder<T>,
+    content: ExtractorFieldValue<T>,
 }
 
 impl<T> FilterDataBuilder<T>
@@ -1662,13 +6949,41 @@ where
     pub fn new() -> Self {
         Self {
             data: None,
+            config: None,
             indexes: Vec::new(),
             _phantom: PhantomData,
         }
     }
     
     pub fn with_data(mut self, data: Vec<T>) -> Self {
-        self.data = Some(data);
+        self.data = Some(BuilderSource::Owned(data));
+        self
+    }
+
+    /// Построить view над строками родителя (см. FilterData::from_indices)
+    ///
+    /// Индексы из builder-а строятся только по строкам view.
+    ///
+    /// # Example
+    ///
+    /// let parent = data.parent_data().unwrap();
+    /// let view = FilterData::builder()
+    ///     .with_indices(&parent, vec![5, 7, 9])
+    ///     .with_field_index("price", |p: &Product| p.price)
+    ///     .with_config(DatasetConfig { null_mode: NullMode::ThreeValued, ..Default::default() })
+    ///     .build()?;
+    ///
+    pub fn with_indices(mut self, parent_data: &Arc<Rows<T>>, indices: Vec<usize>) -> Self {
+        self.data = Some(BuilderSource::Indexed {
+            parent_data: Arc::clone(parent_data),
+            indices,
+        });
+        self
+    }
+
+    // Настройки набора (null_mode, history_compaction), применяются до построения индексов
+    pub fn with_config(mut self, config: DatasetConfig) -> Self {
+        self.config = Some(config);
         self
     }
 
@@ -1679,17 +6994,12 @@ where
         F: Fn(&T) -> V + Send + Sync + 'static + Clone,
         IndexField<V>: IntoIndexFieldEnum,
     {
-        let name_owned = name.to_string();
-        let extractor_clone = extractor.clone();
-        
-        let applier = Box::new(move |fd: &FilterData<T>| -> GlobalResult<()> {
-            fd.create_field_index(&name_owned, extractor_clone)?;
-            Ok(())
-        }) as Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>;
-        
-        self.indexes.push(IndexDefinition {
-            applier,
-        });
+        self.indexes.push(IndexDefinition::Field(FieldDefinition {
+            name: name.to_string(),
+            column: Box::new(FieldColumn::new(extractor.clone())),
+            content: FilterData::<T>::create_field_value_extractor(extractor.clone()),
+            builder: FilterData::<T>::field_index_builder(extractor, IndexField::build),
+        }));
         self
     }
 
@@ -1701,7 +7011,7 @@ where
     /// let data = FilterData::builder()
     ///     .with_data(logs)
     ///     .with_text_index("search", |log| log.message.clone(), 3)
-    ///     .build();
+    ///     .build()?;
     /// 
     pub fn with_text_index<F>(mut self, name: &str, extractor: F) -> Self
     where
@@ -1715,17 +7025,95 @@ where
             Ok(())
         }) as Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()>  + Send>;
         
-        self.indexes.push(IndexDefinition {
-            applier,
-        });
+        self.indexes.push(IndexDefinition::Applier(applier));
         self
     }
     
+    /// Включить режим первичного ключа (см. FilterData::set_key)
+    /// 
+    /// # Example
+    /// 
+    /// let data = FilterData::builder()
+    ///     .with_data(orders)
+    ///     .with_key(|order| order.id)
+    ///     .build()?;
+    /// 
+    pub fn with_key<V, F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<FieldValue> + 'static,
+    {
+        let applier = Box::new(move |fd: &FilterData<T>| -> GlobalResult<()> {
+            fd.set_key(extractor)?;
+            Ok(())
+        }) as Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>;
+
+        self.indexes.push(IndexDefinition::Applier(applier));
+        self
+    }
+
+    // Ограничение параллельных тяжелых операций (см. set_concurrency_limit)
+    pub fn with_concurrency_limit(mut self, permits: usize, policy: QueuePolicy) -> Self {
+        let applier = Box::new(move |fd: &FilterData<T>| -> GlobalResult<()> {
+            fd.set_concurrency_limit(permits, policy);
+            Ok(())
+        }) as Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>;
+
+        self.indexes.push(IndexDefinition::Applier(applier));
+        self
+    }
+
+    // Строки всех уровней в порядке хранилища (см. set_deterministic_order)
+    pub fn with_deterministic_order(mut self) -> Self {
+        let applier = Box::new(|fd: &FilterData<T>| -> GlobalResult<()> {
+            fd.set_deterministic_order(true);
+            Ok(())
+        }) as Box<dyn FnOnce(&FilterData<T>) -> GlobalResult<()> + Send>;
+
+        self.indexes.push(IndexDefinition::Applier(applier));
+        self
+    }
+
     pub fn build(self) -> GlobalResult<FilterData<T>> {
-        let data = self.data.expect("Data must be provided via with_data()");
-        let fd = FilterData::from_vec(data);
+        let source = self.data.ok_or(GLobalError::FilterData(FilterDataError::BuilderMissingData))?;
+        let fd = match source {
+            BuilderSource::Owned(data) => FilterData::from_vec(data),
+            BuilderSource::Indexed { parent_data, indices } => FilterData::from_indices(&parent_data, indices),
+        };
+        if !self.indexes.is_empty() {
+            FilterData::<T>::check_row_limit(fd.index_base_len())?;
+        }
+        if let Some(config) = &self.config {
+            config.apply(&fd);
+        }
+        // Все field индексы строятся за один проход по данным
+        let items = fd.index_base_items();
+        let columns: Vec<(&dyn BatchColumn<T>, &ExtractorFieldValue<T>)> = self.indexes
+            .iter()
+            .filter_map(|index_def| match index_def {
+                IndexDefinition::Field(field) => Some((field.column.as_ref(), &field.content)),
+                IndexDefinition::Applier(_) => None,
+            })
+            .collect();
+        let mut built = batch::build_columns(&items, &columns).into_iter();
+        drop(columns);
+        // Регистрация в порядке объявления - повторное имя заменяет предыдущий индекс
         for index_def in self.indexes {
-            (index_def.applier)(&fd)?;
+            match index_def {
+                IndexDefinition::Applier(applier) => applier(&fd)?,
+                IndexDefinition::Field(field) => {
+                    if let Some((index, fingerprint)) = built.next() {
+                        if fd.has_index(&field.name) {
+                            fd.check_index_type_compability(
+                                &field.name,
+                                INDEX_FIELD,
+                                IndexCompatibilityAction::Replace
+                            ).map_err(GLobalError::Index)?;
+                        }
+                        fd.install_built_index(&field.name, field.builder, field.content, INDEX_FIELD, index, fingerprint);
+                    }
+                }
+            }
         }
         
         Ok(fd)
@@ -1787,6 +7175,15 @@ mod unit_tests {
         }
     }
     
+    #[test]
+    fn test_row_limit() {
+        assert!(FilterData::<i32>::check_row_limit(MAX_INDEXED_ROWS).is_ok());
+        assert!(matches!(
+            FilterData::<i32>::check_row_limit(MAX_INDEXED_ROWS + 1),
+            Err(GLobalError::FilterData(FilterDataError::TooManyRows { .. }))
+        ));
+    }
+
     #[test]
     fn test_levels_bounded() {
         let items: Vec<i32> = (0..1000).collect();
@@ -1862,7 +7259,7 @@ mod unit_tests {
     #[test]
     fn test_indexed_storage_weak_valid() {
         let items: Vec<Arc<i32>> = (0..1000).map(Arc::new).collect();
-        let parent = Arc::new(items);
+        let parent = Arc::new(Rows::from(items));
         let indices = vec![0, 100, 200, 300];
         let data = FilterData::from_indices(&parent, indices);
         assert!(data.is_valid());
@@ -2119,4 +7516,51 @@ mod unit_tests {
         let excluded = [10, 20, 30, 40, 50];
         assert!(result.iter().all(|n| !excluded.contains(&**n)));
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "planner-log")]
+    #[test]
+    fn test_planner_logs_rejected_index_path() {
+        use log::kv::{Key, Value, VisitSource};
+
+        // Пары ключ-значение debug записей планировщика
+        struct Collector(parking_lot::Mutex<Vec<Vec<(String, String)>>>);
+        struct Pairs<'a>(&'a mut Vec<(String, String)>);
+        impl<'kvs> VisitSource<'kvs> for Pairs<'_> {
+            fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+                self.0.push((key.to_string(), value.to_string()));
+                Ok(())
+            }
+        }
+        impl log::Log for Collector {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.target() == "tree_man::planner"
+            }
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    let mut pairs = Vec::new();
+                    record.key_values().visit(&mut Pairs(&mut pairs)).unwrap();
+                    self.0.lock().push(pairs);
+                }
+            }
+            fn flush(&self) {}
+        }
+        static COLLECTOR: Collector = Collector(parking_lot::Mutex::new(Vec::new()));
+        log::set_logger(&COLLECTOR).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let data = FilterData::from_vec((0..20_000u64).collect());
+        data.create_field_index("even", |n: &u64| n % 2).unwrap();
+        data.filter_by_field_ops("even", &[(FieldOperation::eq(0u64), Op::And)]).unwrap();
+
+        let records = COLLECTOR.0.lock();
+        let record = records
+            .iter()
+            .find(|pairs| pairs.iter().any(|(key, value)| key == "fields" && value == "even"))
+            .expect("rejection logged");
+        let value = |name: &str| record.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+        assert_eq!(value("reason").as_deref(), Some("selectivity"));
+        assert_eq!(value("selectivity").as_deref(), Some("0.5"));
+        assert_eq!(value("threshold").as_deref(), Some("0.1"));
+        assert_eq!(value("rows").as_deref(), Some("20000"));
+    }
+}
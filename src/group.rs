@@ -1,7 +1,14 @@
+pub mod aggregate;
+pub mod cursor;
+pub mod diff;
+mod pattern;
+
 use super::{
     errors::{
         GLobalError,
         FilterDataError,
+        GroupError,
+        IndexError,
     },
     index::{
         bit::Op,
@@ -15,6 +22,9 @@ use super::{
     filter::FilterData,
     result::GlobalResult,
 };
+use aggregate::AggregatePipeline;
+use cursor::{GROUP_CURSOR_VERSION, GroupCursor};
+use diff::{GroupTreeDiff, TreeDiffer};
 use arc_swap::ArcSwap;
 use parking_lot::Mutex;
 use rayon::prelude::*;
@@ -22,7 +32,7 @@ use std::{
     collections::{BTreeMap,btree_map}, 
     fmt::{Debug, Display}, 
     hash::Hash, 
-    ops::RangeBounds,
+    ops::{Bound, RangeBounds},
     sync::{
         Arc, 
         Weak
@@ -41,6 +51,8 @@ where
     subgroups: ArcSwap<BTreeMap<K, Arc<GroupData<K, V>>>>,
     pub description: Option<Arc<str>>,
     depth: usize,
+    // source_indices по возрастанию - contains_row_id ищет бинарным поиском
+    sorted_rows: bool,
     // Mutex только для group_by 
     write_lock: Mutex<()>,
 }
@@ -63,6 +75,7 @@ where
             subgroups: ArcSwap::from_pointee(BTreeMap::new()),
             description: Some(Arc::from(description)),
             depth: 0,
+            sorted_rows: true,
             write_lock: Mutex::new(()),
         })
     }
@@ -82,6 +95,7 @@ where
         
         Ok(Arc::new(Self {
             key,
+            sorted_rows: Self::rows_sorted(&filter_data),
             data: Arc::new(filter_data),
             parent: None,
             subgroups: ArcSwap::from_pointee(BTreeMap::new()),
@@ -100,6 +114,7 @@ where
     ) -> Arc<Self> {
         Arc::new(Self {
             key,
+            sorted_rows: Self::rows_sorted(&data),
             data,
             parent: Some(Arc::downgrade(parent)),
             subgroups: ArcSwap::from_pointee(BTreeMap::new()),
@@ -109,6 +124,11 @@ where
         })
     }
 
+    // index_builder корня может вернуть view с произвольным порядком строк
+    fn rows_sorted(data: &FilterData<V>) -> bool {
+        data.source_indices().is_none_or(|indices| indices.is_sorted())
+    }
+
     // Grouping Methods 
 
     // group_by с автоматической сортировкой индексов
@@ -121,6 +141,23 @@ where
     }
     
     
+    /// group_by по имени field индекса или computed поля текущей группы
+    /// 
+    /// Ключ подгруппы - значение поля, приведенное к K (`String` или `FieldValue`).
+    /// 
+    /// # Example
+    /// 
+    /// root.data.register_computed_field("margin_band", |o: &Order| (o.revenue - o.cost) / 100);
+    /// root.group_by_field("margin_band", "By margin band")?;
+    /// 
+    pub fn group_by_field(self: &Arc<Self>, name: &str, description: &str) -> GlobalResult<()>
+    where
+        K: From<FieldValue>,
+    {
+        let extractor = self.data.field_extractor(name)?;
+        self.group_by(move |item| K::from(extractor(item)), description)
+    }
+    
     // Индексы автоматически сортируются для cache-friendly доступа
     // ВНИМАНИЕ: Индексы в подгруппах будут хранить Arc<V>, что увеличит ref count!
     // 
@@ -140,7 +177,7 @@ where
         IF: Fn(&FilterData<V>) -> GlobalResult<()> + Sync + Send,
     {
         let description_arc: Arc<str> = Arc::from(description);
-        let parent_data = match self.data.parent_data() {
+        let parent_data = match self.data.storage_rows() {
             Some(data) => data,
             None => {
                 return Err(GLobalError::ParentDataIsEmpty)
@@ -184,10 +221,11 @@ where
             .map(|(key, mut indices)| {
                 //  СОРТИРУЕМ индексы для cache-friendly доступа!
                 indices.sort_unstable();
+                // Text индексы подгрупп разделяют словарь n-грамм родителя
                 let filter_data = FilterData::from_indices(
                     &parent_data,
                     indices,  // Отсортированные индексы
-                );
+                ).with_ngram_dictionary(self.data.ngram_dictionary());
                 // Создаём индексы
                 // ВАЖНО: Индексы будут хранить Arc<V>, увеличивая ref count!
                 index_creator(&filter_data)?;
@@ -554,6 +592,51 @@ where
             .collect()
     }
     
+    // Пагинация подгрупп (порядок ключей BTreeMap)
+
+    // Количество подгрупп - для расчета числа страниц
+    #[inline]
+    pub fn subgroup_count(&self) -> usize {
+        self.subgroups_count()
+    }
+
+    // Ключи страницы: offset - сколько ключей пропустить, limit - размер страницы
+    pub fn subgroup_keys(&self, offset: usize, limit: usize) -> Vec<K> {
+        self.subgroups.load()
+            .keys()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Подгруппы страницы (тот же порядок, что и subgroup_keys)
+    pub fn subgroups_page(&self, offset: usize, limit: usize) -> Vec<Arc<GroupData<K, V>>> {
+        self.subgroups.load()
+            .values()
+            .skip(offset)
+            .take(limit)
+            .map(Arc::clone)
+            .collect()
+    }
+
+    // Keyset пагинация: следующие limit ключей после key (без O(offset) пропуска)
+    pub fn subgroup_keys_after(&self, key: &K, limit: usize) -> Vec<K> {
+        self.subgroups.load()
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .take(limit)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    // Подгруппы в окне ключей: group.subgroups_range(start_key..end_key)
+    pub fn subgroups_range<R>(&self, range: R) -> Vec<Arc<GroupData<K, V>>>
+    where
+        R: RangeBounds<K>,
+    {
+        self.get_subgroups_range(range)
+    }
+
     // Получить топ N подгрупп (по наибольшим ключам)
     pub fn get_top_n_subgroups(&self, n: usize) -> Vec<Arc<GroupData<K, V>>> {
         self.subgroups.load()
@@ -573,6 +656,60 @@ where
             .collect()
     }
 
+    // Поиск подгрупп по ключу
+
+    // Прямые подгруппы, ключ которых удовлетворяет предикату
+    pub fn find_subgroups<P>(&self, predicate: P) -> Vec<Arc<GroupData<K, V>>>
+    where
+        P: Fn(&K) -> bool,
+    {
+        self.subgroups.load()
+            .iter()
+            .filter(|(key, _)| predicate(key))
+            .map(|(_, subgroup)| Arc::clone(subgroup))
+            .collect()
+    }
+
+    // Все потомки (на любой глубине) с подходящим ключом, обход в глубину по порядку ключей
+    pub fn find_subgroups_recursive<P>(&self, predicate: P) -> Vec<Arc<GroupData<K, V>>>
+    where
+        P: Fn(&K) -> bool,
+    {
+        let mut result = Vec::new();
+        self.collect_matching_subgroups(&predicate, &mut result);
+        result
+    }
+
+    fn collect_matching_subgroups<P>(&self, predicate: &P, result: &mut Vec<Arc<GroupData<K, V>>>)
+    where
+        P: Fn(&K) -> bool,
+    {
+        for (key, subgroup) in self.subgroups.load().iter() {
+            if predicate(key) {
+                result.push(Arc::clone(subgroup));
+            }
+            subgroup.collect_matching_subgroups(predicate, result);
+        }
+    }
+
+    /// Прямые подгруппы, ключ которых (в виде Display) подходит под glob-шаблон
+    /// 
+    /// `*` - любая подстрока (в том числе пустая), `?` - ровно один символ.
+    /// 
+    /// # Example
+    /// 
+    /// // все сервисы семейства payments
+    /// let payments = by_service.find_subgroups_matching("payments-*");
+    /// 
+    pub fn find_subgroups_matching(&self, pattern: &str) -> Vec<Arc<GroupData<K, V>>> {
+        self.find_subgroups(|key| pattern::glob_match(pattern, &key.to_string()))
+    }
+
+    // find_subgroups_matching по всему поддереву
+    pub fn find_subgroups_matching_recursive(&self, pattern: &str) -> Vec<Arc<GroupData<K, V>>> {
+        self.find_subgroups_recursive(|key| pattern::glob_match(pattern, &key.to_string()))
+    }
+
     // ``````
     // let subgroups = group.get_subgroups();
     // for key in keys {
@@ -660,6 +797,139 @@ where
         result
     }
 
+    // Параллельный итератор по листьям дерева: (путь от корня, данные листа).
+    // Лист - группа без подгрупп; если подгрупп нет вовсе, отдается сама группа.
+    //
+    // # Example
+    // let scores: Vec<(Vec<String>, f64)> = root
+    //     .par_leaves()
+    //     .map(|(path, data)| (path, score(&data.items())))
+    //     .collect();
+    pub fn par_leaves(&self) -> impl ParallelIterator<Item = (Vec<K>, Arc<FilterData<V>>)> + use<K, V> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![(self.get_path(), self.subgroups.load_full(), Arc::clone(&self.data))];
+        while let Some((path, subgroups, data)) = stack.pop() {
+            if subgroups.is_empty() {
+                leaves.push((path, data));
+                continue;
+            }
+            // В обратном порядке, чтобы листья шли в порядке ключей
+            for (key, subgroup) in subgroups.iter().rev() {
+                let mut child_path = path.clone();
+                child_path.push(key.clone());
+                stack.push((child_path, subgroup.subgroups.load_full(), Arc::clone(&subgroup.data)));
+            }
+        }
+        leaves.into_par_iter()
+    }
+
+    /// Пути подгрупп, содержащих строку хранилища row_id (см. items_with_ids)
+    /// 
+    /// Возвращаются пути от корня до самых глубоких групп поддерева,
+    /// в которые попала строка. Если ни одна подгруппа ее не содержит,
+    /// возвращается путь текущей группы; пустой результат - строки нет в группе.
+    /// 
+    /// # Example
+    /// 
+    /// let (row_id, _) = root.data.items_with_ids()[0].clone();
+    /// for path in root.locate(row_id) {
+    ///     println!("{}", path.join(" / "));
+    /// }
+    /// 
+    pub fn locate(&self, row_id: usize) -> Vec<Vec<K>> {
+        if !self.contains_row_id(row_id) {
+            return Vec::new();
+        }
+        let mut paths = Vec::new();
+        let mut stack = vec![(self.get_path(), self.subgroups.load_full())];
+        while let Some((path, subgroups)) = stack.pop() {
+            let mut found = false;
+            for (key, subgroup) in subgroups.iter().rev() {
+                if subgroup.contains_row_id(row_id) {
+                    found = true;
+                    let mut child_path = path.clone();
+                    child_path.push(key.clone());
+                    stack.push((child_path, subgroup.subgroups.load_full()));
+                }
+            }
+            if !found {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    // Входит ли строка хранилища в исходный уровень группы
+    fn contains_row_id(&self, row_id: usize) -> bool {
+        match self.data.source_indices() {
+            // group_by хранит индексы подгрупп отсортированными
+            Some(indices) if self.sorted_rows => indices.binary_search(&row_id).is_ok(),
+            Some(indices) => indices.contains(&row_id),
+            None => self.data.storage_rows().is_some_and(|data| row_id < data.len()),
+        }
+    }
+
+    // Cursor
+
+    /// Сохранить позицию: путь к узлу + текущий уровень его FilterData
+    /// 
+    /// # Example
+    /// 
+    /// let cursor = node.cursor();
+    /// let node = root.restore_cursor(&cursor)?;
+    /// 
+    pub fn cursor(&self) -> GroupCursor<K> {
+        let level = self.data.current_level();
+        let meta = self.data.level_meta(level);
+        GroupCursor::new(
+            self.get_path(),
+            level,
+            meta.as_ref().map(|meta| meta.description.as_ref()).unwrap_or_default(),
+            self.data.len(),
+        )
+    }
+
+    /// Восстановить позицию из курсора
+    /// 
+    /// Путь ищется от корня дерева (вызывать можно на любом узле), узлы
+    /// по пути не очищаются. Уровень восстанавливается только если его
+    /// описание и число строк совпадают с сохраненными.
+    pub fn restore_cursor(self: &Arc<Self>, cursor: &GroupCursor<K>) -> GlobalResult<Arc<Self>> {
+        if cursor.version > GROUP_CURSOR_VERSION {
+            return Err(GLobalError::Group(GroupError::CursorVersion {
+                version: cursor.version,
+                supported: GROUP_CURSOR_VERSION,
+            }));
+        }
+        let path_not_found = || GLobalError::Group(GroupError::PathNotFound { path: cursor.path_string() });
+        let root = self.get_parents().pop().unwrap_or_else(|| Arc::clone(self));
+        let (root_key, keys) = cursor.path.split_first().ok_or_else(path_not_found)?;
+        if &root.key != root_key {
+            return Err(path_not_found());
+        }
+        let mut node = root;
+        for key in keys {
+            node = node.get_subgroup(key).ok_or_else(path_not_found)?;
+        }
+
+        let levels = node.data.levels_meta();
+        let meta = levels.get(cursor.level).ok_or_else(|| GLobalError::Group(GroupError::LevelNotFound {
+            path: cursor.path_string(),
+            level: cursor.level,
+            levels: levels.len(),
+        }))?;
+        if meta.description.as_ref() != cursor.level_description || meta.row_count != cursor.row_count {
+            return Err(GLobalError::Group(GroupError::LevelChanged {
+                path: cursor.path_string(),
+                level: cursor.level,
+                expected: format!("{} ({} rows)", cursor.level_description, cursor.row_count),
+                found: format!("{} ({} rows)", meta.description, meta.row_count),
+            }));
+        }
+        node.data.go_to_level(cursor.level);
+        Ok(node)
+    }
+
     // Statistics
 
     // Максимальная глубина дерева
@@ -743,6 +1013,104 @@ where
         results
     }
 
+    /// Количество совпадений одного и того же field запроса в каждой подгруппе
+    /// 
+    /// Подгруппы обрабатываются параллельно, каждая - своим индексом
+    /// (если его нет - extractor-ом индекса текущей группы). Уровни
+    /// подгрупп не меняются, строки не материализуются.
+    /// 
+    /// # Example
+    /// 
+    /// // сколько ошибок в каждом сервисе
+    /// let counts = by_service.filter_subgroups_by_field_ops("status", &[(FieldOperation::gte(500), Op::And)])?;
+    /// 
+    pub fn filter_subgroups_by_field_ops(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<BTreeMap<K, usize>> {
+        let fallback = self.data.field_extractor(name).ok();
+        self.subgroups.load()
+            .par_iter()
+            .map(|(key, subgroup)| {
+                let count = match subgroup.data.count_by_field_ops(name, operations) {
+                    Ok(count) => count,
+                    Err(err @ GLobalError::Index(IndexError::NotFound { .. })) => match &fallback {
+                        Some(extractor) => subgroup.data.count_with_extractor(extractor, operations),
+                        None => return Err(err),
+                    },
+                    Err(err) => return Err(err),
+                };
+                Ok((key.clone(), count))
+            })
+            .collect()
+    }
+
+    /// Количество совпадений text запроса в каждой подгруппе
+    /// 
+    /// Подгруппа с собственным text индексом `name` ищет по нему; для остальных
+    /// результат индекса текущей группы пересекается с их выборкой.
+    /// Уровни подгрупп не меняются.
+    /// 
+    /// # Example
+    /// 
+    /// // сколько 'timeout' ошибок в каждом сервисе
+    /// let counts = by_service.search_text_in_subgroups("message", "timeout")?;
+    /// 
+    pub fn search_text_in_subgroups(&self, name: &str, query: &str) -> GlobalResult<BTreeMap<K, usize>> {
+        let shared = self.data.text_match_ids(name, query).ok();
+        self.subgroups.load()
+            .par_iter()
+            .map(|(key, subgroup)| {
+                let count = match subgroup.data.count_text_matches(name, query) {
+                    Ok(count) => count,
+                    Err(err @ GLobalError::Index(IndexError::NotFound { .. })) => match &shared {
+                        Some(ids) => subgroup.data.count_current_ids(ids),
+                        None => return Err(err),
+                    },
+                    Err(err) => return Err(err),
+                };
+                Ok((key.clone(), count))
+            })
+            .collect()
+    }
+
+    // Aggregation
+
+    // Агрегаты по подгруппам: group.aggregate().count().sum("amount").collect()
+    pub fn aggregate(&self) -> AggregatePipeline<'_, K, V> {
+        AggregatePipeline::new(self)
+    }
+
+    // Diff
+
+    /// Сравнение двух деревьев: self - старый снимок, other - новый
+    /// 
+    /// Для каждого узла - добавлен/удален/изменен и количество строк до и после.
+    /// 
+    /// # Example
+    /// 
+    /// let diff = yesterday.diff(&today)?;
+    /// for node in diff.added() {
+    ///     println!("new: {:?} ({} rows)", node.path, node.count_after);
+    /// }
+    /// 
+    pub fn diff(&self, other: &GroupData<K, V>) -> GlobalResult<GroupTreeDiff<K>> {
+        TreeDiffer::new(self, other, &[]).diff()
+    }
+
+    /// Diff с суммами числовых полей (имена field индексов) в каждом узле
+    /// 
+    /// # Example
+    /// 
+    /// let diff = yesterday.diff_with_sums(&today, &["amount"])?;
+    /// let node = diff.get(&["EU".to_string()]).unwrap();
+    /// println!("{:?}", node.delta_sum("amount"));
+    /// 
+    pub fn diff_with_sums(&self, other: &GroupData<K, V>, fields: &[&str]) -> GlobalResult<GroupTreeDiff<K>> {
+        TreeDiffer::new(self, other, fields).diff()
+    }
+
     // Display/Debug
 
     // Вывод дерева в консоль для отладки
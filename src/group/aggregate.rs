@@ -0,0 +1,258 @@
+use super::GroupData;
+use crate::{
+    index::ExtractorFieldValue,
+    model::ValueAggregate,
+    result::GlobalResult,
+};
+use rayon::prelude::*;
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    sync::Arc,
+};
+
+// Вид агрегата по числовому полю
+// (GroupData::aggregate, FilterData::aggregate)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateKind {
+    // Строки с числовым значением
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+// Имя колонки результата: sum(amount), avg(latency), ...
+fn column_name(kind: AggregateKind, field: &str) -> String {
+    format!("{}({})", kind.name(), field)
+}
+
+/// Строка результата агрегации - одна подгруппа
+#[derive(Debug, Clone)]
+pub struct AggregateRow<K> {
+    pub key: K,
+    pub count: usize,
+    columns: Arc<[String]>,
+    values: Vec<Option<f64>>,
+}
+
+impl<K> AggregateRow<K> {
+    // Значение по имени колонки ("sum(amount)")
+    pub fn get(&self, column: &str) -> Option<f64> {
+        let position = self.columns.iter().position(|name| name == column)?;
+        self.values[position]
+    }
+
+    pub fn sum(&self, field: &str) -> Option<f64> {
+        self.get(&column_name(AggregateKind::Sum, field))
+    }
+
+    pub fn avg(&self, field: &str) -> Option<f64> {
+        self.get(&column_name(AggregateKind::Avg, field))
+    }
+
+    pub fn min(&self, field: &str) -> Option<f64> {
+        self.get(&column_name(AggregateKind::Min, field))
+    }
+
+    pub fn max(&self, field: &str) -> Option<f64> {
+        self.get(&column_name(AggregateKind::Max, field))
+    }
+
+    pub fn values(&self) -> &[Option<f64>] {
+        &self.values
+    }
+}
+
+/// Таблица результата: колонки + строки в порядке ключей подгрупп
+#[derive(Debug, Clone)]
+pub struct AggregateTable<K> {
+    pub columns: Vec<String>,
+    pub rows: Vec<AggregateRow<K>>,
+}
+
+impl<K: PartialEq> AggregateTable<K> {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&AggregateRow<K>> {
+        self.rows.iter().find(|row| &row.key == key)
+    }
+}
+
+type HavingFn<'a, K> = Box<dyn Fn(&AggregateRow<K>) -> bool + Send + Sync + 'a>;
+
+/// Агрегация по подгруппам за один параллельный проход
+///
+/// Поля задаются именами field индексов: extractor берется из индекса
+/// подгруппы, а если его нет - из индекса текущей группы.
+///
+/// # Example
+///
+/// let table = group.aggregate()
+///     .count()
+///     .sum("amount")
+///     .avg("latency")
+///     .having(|agg| agg.count > 100)
+///     .collect()?;
+///
+pub struct AggregatePipeline<'a, K, V>
+where
+    K: Ord + Clone + Debug + Send + Sync + Display + Hash + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    group: &'a GroupData<K, V>,
+    with_count: bool,
+    metrics: Vec<(AggregateKind, String)>,
+    having: Option<HavingFn<'a, K>>,
+}
+
+impl<'a, K, V> AggregatePipeline<'a, K, V>
+where
+    K: Ord + Clone + Debug + Send + Sync + Display + Hash + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    pub(crate) fn new(group: &'a GroupData<K, V>) -> Self {
+        Self {
+            group,
+            with_count: false,
+            metrics: Vec::new(),
+            having: None,
+        }
+    }
+
+    // Добавить колонку count (AggregateRow::count заполняется всегда)
+    pub fn count(mut self) -> Self {
+        self.with_count = true;
+        self
+    }
+
+    pub fn sum(self, field: &str) -> Self {
+        self.metric(AggregateKind::Sum, field)
+    }
+
+    pub fn avg(self, field: &str) -> Self {
+        self.metric(AggregateKind::Avg, field)
+    }
+
+    pub fn min(self, field: &str) -> Self {
+        self.metric(AggregateKind::Min, field)
+    }
+
+    pub fn max(self, field: &str) -> Self {
+        self.metric(AggregateKind::Max, field)
+    }
+
+    pub fn metric(mut self, kind: AggregateKind, field: &str) -> Self {
+        let metric = (kind, field.to_string());
+        if !self.metrics.contains(&metric) {
+            self.metrics.push(metric);
+        }
+        self
+    }
+
+    // Фильтр по уже посчитанным агрегатам
+    pub fn having<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&AggregateRow<K>) -> bool + Send + Sync + 'a,
+    {
+        self.having = Some(Box::new(predicate));
+        self
+    }
+
+    fn columns(&self) -> Vec<String> {
+        let mut columns = Vec::with_capacity(self.metrics.len() + 1);
+        if self.with_count {
+            columns.push("count".to_string());
+        }
+        columns.extend(self.metrics.iter().map(|(kind, field)| column_name(*kind, field)));
+        columns
+    }
+
+    // Уникальные поля метрик - каждое поле извлекается один раз
+    fn fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = Vec::with_capacity(self.metrics.len());
+        for (_, field) in &self.metrics {
+            if !fields.contains(&field.as_str()) {
+                fields.push(field);
+            }
+        }
+        fields
+    }
+
+    fn aggregate_subgroup(
+        &self,
+        subgroup: &GroupData<K, V>,
+        fields: &[&str],
+        columns: &Arc<[String]>,
+    ) -> GlobalResult<AggregateRow<K>> {
+        let extractors = fields
+            .iter()
+            .map(|field| {
+                subgroup.data
+                    .field_extractor(field)
+                    .or_else(|_| self.group.data.field_extractor(field))
+            })
+            .collect::<GlobalResult<Vec<ExtractorFieldValue<V>>>>()?;
+        let items = subgroup.data.items();
+        let mut accumulators = vec![ValueAggregate::default(); fields.len()];
+        for item in items.iter() {
+            for (accumulator, extractor) in accumulators.iter_mut().zip(&extractors) {
+                if let Some(value) = extractor(item).as_f64() {
+                    *accumulator = accumulator.push(value);
+                }
+            }
+        }
+        let mut values = Vec::with_capacity(columns.len());
+        if self.with_count {
+            values.push(Some(items.len() as f64));
+        }
+        for (kind, field) in &self.metrics {
+            let position = fields.iter().position(|name| name == field).unwrap_or_default();
+            values.push(accumulators[position].get(*kind));
+        }
+        Ok(AggregateRow {
+            key: subgroup.key.clone(),
+            count: items.len(),
+            columns: Arc::clone(columns),
+            values,
+        })
+    }
+
+    pub fn collect(self) -> GlobalResult<AggregateTable<K>> {
+        let columns = self.columns();
+        let shared_columns: Arc<[String]> = Arc::from(columns.clone());
+        let fields = self.fields();
+        let subgroups = self.group.get_all_subgroups();
+        let rows = subgroups
+            .par_iter()
+            .map(|subgroup| self.aggregate_subgroup(subgroup, &fields, &shared_columns))
+            .filter(|row| match (row, &self.having) {
+                (Ok(row), Some(having)) => having(row),
+                _ => true,
+            })
+            .collect::<GlobalResult<Vec<AggregateRow<K>>>>()?;
+        Ok(AggregateTable {
+            columns,
+            rows,
+        })
+    }
+}
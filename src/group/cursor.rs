@@ -0,0 +1,70 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::fmt::Display;
+
+pub const GROUP_CURSOR_VERSION: u32 = 1;
+
+/// Сохраненная позиция в дереве групп
+///
+/// Путь ключей от корня до узла + уровень drill-down его FilterData.
+/// Описание и число строк уровня сверяются при восстановлении:
+/// если узел за это время перефильтровали, курсор не подменит выборку молча.
+///
+/// # Example
+///
+/// let cursor = node.cursor();
+/// let json = cursor.to_json()?;            // feature = "serde"
+/// // ... после переподключения
+/// let node = root.restore_cursor(&GroupCursor::from_json(&json)?)?;
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GroupCursor<K> {
+    pub version: u32,
+    // Абсолютный путь: первый ключ - ключ корня
+    pub path: Vec<K>,
+    pub level: usize,
+    pub level_description: String,
+    pub row_count: usize,
+}
+
+impl<K> GroupCursor<K> {
+    pub fn new(path: Vec<K>, level: usize, level_description: &str, row_count: usize) -> Self {
+        Self {
+            version: GROUP_CURSOR_VERSION,
+            path,
+            level,
+            level_description: level_description.to_string(),
+            row_count,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.len().saturating_sub(1)
+    }
+}
+
+impl<K: Display> GroupCursor<K> {
+    // Путь для сообщений об ошибках: root/a/b
+    pub fn path_string(&self) -> String {
+        self.path
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K> GroupCursor<K>
+where
+    K: Serialize + DeserializeOwned,
+{
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(data)
+    }
+}
@@ -0,0 +1,189 @@
+use super::GroupData;
+use crate::{
+    index::ExtractorFieldValue,
+    result::GlobalResult,
+};
+use std::{
+    collections::BTreeSet,
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+
+// Состояние узла при сравнении двух деревьев
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+    // Ключ есть только в новом дереве
+    Added,
+    // Ключ есть только в старом дереве
+    Removed,
+    // Количество строк или агрегаты отличаются
+    Changed,
+    Unchanged,
+}
+
+/// Разница одного узла: путь ключей от корня сравнения, количества и суммы
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupNodeDiff<K> {
+    pub path: Vec<K>,
+    pub status: DiffStatus,
+    pub count_before: usize,
+    pub count_after: usize,
+    // (поле, сумма в старом дереве, сумма в новом дереве)
+    pub sums: Vec<(String, f64, f64)>,
+}
+
+impl<K> GroupNodeDiff<K> {
+    pub fn delta_count(&self) -> i64 {
+        self.count_after as i64 - self.count_before as i64
+    }
+
+    pub fn sum_before(&self, field: &str) -> Option<f64> {
+        self.sums.iter().find(|(name, _, _)| name == field).map(|(_, before, _)| *before)
+    }
+
+    pub fn sum_after(&self, field: &str) -> Option<f64> {
+        self.sums.iter().find(|(name, _, _)| name == field).map(|(_, _, after)| *after)
+    }
+
+    pub fn delta_sum(&self, field: &str) -> Option<f64> {
+        Some(self.sum_after(field)? - self.sum_before(field)?)
+    }
+}
+
+/// Результат `GroupData::diff` - узлы обоих деревьев в порядке обхода
+///
+/// Корень сравнения имеет пустой path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupTreeDiff<K> {
+    pub nodes: Vec<GroupNodeDiff<K>>,
+}
+
+impl<K: PartialEq> GroupTreeDiff<K> {
+    pub fn get(&self, path: &[K]) -> Option<&GroupNodeDiff<K>> {
+        self.nodes.iter().find(|node| node.path == path)
+    }
+
+    pub fn added(&self) -> Vec<&GroupNodeDiff<K>> {
+        self.with_status(DiffStatus::Added)
+    }
+
+    pub fn removed(&self) -> Vec<&GroupNodeDiff<K>> {
+        self.with_status(DiffStatus::Removed)
+    }
+
+    pub fn changed(&self) -> Vec<&GroupNodeDiff<K>> {
+        self.with_status(DiffStatus::Changed)
+    }
+
+    // Все узлы, кроме Unchanged
+    pub fn changes(&self) -> Vec<&GroupNodeDiff<K>> {
+        self.nodes.iter().filter(|node| node.status != DiffStatus::Unchanged).collect()
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.nodes.iter().any(|node| node.status != DiffStatus::Unchanged)
+    }
+
+    fn with_status(&self, status: DiffStatus) -> Vec<&GroupNodeDiff<K>> {
+        self.nodes.iter().filter(|node| node.status == status).collect()
+    }
+}
+
+// Сравнение деревьев: корни old/new, поля для сумм
+pub(crate) struct TreeDiffer<'a, K, V>
+where
+    K: Ord + Clone + Debug + Send + Sync + Display + Hash + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    old_root: &'a GroupData<K, V>,
+    new_root: &'a GroupData<K, V>,
+    fields: &'a [&'a str],
+}
+
+impl<'a, K, V> TreeDiffer<'a, K, V>
+where
+    K: Ord + Clone + Debug + Send + Sync + Display + Hash + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    pub(crate) fn new(old_root: &'a GroupData<K, V>, new_root: &'a GroupData<K, V>, fields: &'a [&'a str]) -> Self {
+        Self {
+            old_root,
+            new_root,
+            fields,
+        }
+    }
+
+    pub(crate) fn diff(&self) -> GlobalResult<GroupTreeDiff<K>> {
+        let mut nodes = Vec::new();
+        self.diff_node(Some(self.old_root), Some(self.new_root), Vec::new(), &mut nodes)?;
+        Ok(GroupTreeDiff { nodes })
+    }
+
+    fn diff_node(
+        &self,
+        old: Option<&GroupData<K, V>>,
+        new: Option<&GroupData<K, V>>,
+        path: Vec<K>,
+        nodes: &mut Vec<GroupNodeDiff<K>>,
+    ) -> GlobalResult<()> {
+        let count_before = old.map(|group| group.data.len()).unwrap_or_default();
+        let count_after = new.map(|group| group.data.len()).unwrap_or_default();
+        let mut sums = Vec::with_capacity(self.fields.len());
+        for field in self.fields {
+            let before = match old {
+                Some(group) => Self::sum(group, self.old_root, field)?,
+                None => 0.0,
+            };
+            let after = match new {
+                Some(group) => Self::sum(group, self.new_root, field)?,
+                None => 0.0,
+            };
+            sums.push((field.to_string(), before, after));
+        }
+        let status = match (old, new) {
+            (None, Some(_)) => DiffStatus::Added,
+            (Some(_), None) => DiffStatus::Removed,
+            _ if count_before != count_after
+                || sums.iter().any(|(_, before, after)| before != after) => DiffStatus::Changed,
+            _ => DiffStatus::Unchanged,
+        };
+        nodes.push(GroupNodeDiff {
+            path: path.clone(),
+            status,
+            count_before,
+            count_after,
+            sums,
+        });
+        // Поддерево добавленного/удаленного узла описывается самим узлом
+        let (Some(old), Some(new)) = (old, new) else {
+            return Ok(());
+        };
+        let old_subgroups = old.get_subgroups();
+        let new_subgroups = new.get_subgroups();
+        let keys: BTreeSet<&K> = old_subgroups.keys().chain(new_subgroups.keys()).collect();
+        for key in keys {
+            let mut child_path = path.clone();
+            child_path.push(key.clone());
+            self.diff_node(
+                old_subgroups.get(key).map(|group| group.as_ref()),
+                new_subgroups.get(key).map(|group| group.as_ref()),
+                child_path,
+                nodes,
+            )?;
+        }
+        Ok(())
+    }
+
+    // Сумма числового поля по текущей выборке узла;
+    // extractor - из индекса узла или корня сравнения
+    fn sum(group: &GroupData<K, V>, root: &GroupData<K, V>, field: &str) -> GlobalResult<f64> {
+        let extractor: ExtractorFieldValue<V> = group.data
+            .field_extractor(field)
+            .or_else(|_| root.data.field_extractor(field))?;
+        Ok(group.data
+            .items()
+            .iter()
+            .filter_map(|item| extractor(item).as_f64())
+            .sum())
+    }
+}
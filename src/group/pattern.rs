@@ -0,0 +1,52 @@
+// Сопоставление ключа с glob-шаблоном: '*' - любая подстрока, '?' - один символ.
+// Жадный проход с откатом к последней '*' - O(len(pattern) * len(text)) в худшем случае.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Позиция последней '*' в шаблоне и позиция текста, с которой она сопоставлялась
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // '*' поглощает еще один символ
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("payments-*", "payments-api"));
+        assert!(glob_match("payments-*", "payments-"));
+        assert!(!glob_match("payments-*", "billing-api"));
+        assert!(glob_match("*-api", "payments-api"));
+        assert!(glob_match("*ment*", "payments-api"));
+        assert!(glob_match("svc-?", "svc-1"));
+        assert!(!glob_match("svc-?", "svc-10"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+    }
+}
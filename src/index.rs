@@ -1,8 +1,25 @@
+pub mod background;
+pub mod batch;
 pub mod bit;
+pub mod boolean;
+pub mod cache;
+pub mod enums;
 pub mod field;
+pub mod integrity;
+pub mod keys;
+pub mod limiter;
+pub mod logic;
+pub mod normalize;
+#[cfg(feature = "serde")]
+pub mod persist;
+pub mod pool;
+pub mod range;
+pub mod sketch;
+pub mod stats;
 pub mod storage;
 pub mod text;
 
+use roaring::RoaringBitmap;
 use std::sync::Arc;
 
 pub const INDEX_FIELD: &str = "index_field";
@@ -17,6 +34,24 @@ pub enum CompatibilityAction {
 
 pub type ExtractorFieldValue<T> = Arc<dyn Fn(&T) -> field::FieldValue + Send + Sync>;
 
+// Построитель индекса по набору строк - сохраняется для rebuild_index
+pub type IndexBuilder<T> = Arc<dyn Fn(&[Arc<T>]) -> IndexType<T> + Send + Sync>;
+
+// Рецепт индекса: как его построить, текущая версия
+// и отпечаток данных, по которым построена эта версия
+pub struct IndexRecipe<T>
+where T: Send + Sync + 'static
+{
+    pub builder: IndexBuilder<T>,
+    pub index_type: &'static str,
+    pub version: u64,
+    // Значение строки для fingerprint-а и выборочной сверки
+    pub content: ExtractorFieldValue<T>,
+    pub fingerprint: integrity::IndexFingerprint,
+    // Хеши чанков fingerprint-а: считаются при первом push, дальше дописываются
+    pub chunk_hashes: Option<Vec<u64>>,
+}
+
 pub enum IndexType<T> 
 where T: Send + Sync + 'static
 {
@@ -58,6 +93,70 @@ where T: Send + Sync + 'static
         matches!(self, Self::Field(_))
     }
 
+    // Индексы совпадают по содержимому (те же значения и bitmap-ы) -
+    // второй экземпляр можно заменить ссылкой на первый
+    pub fn same_content(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Field((left, _)), Self::Field((right, _))) => {
+                left.type_name() == right.type_name()
+                    && left.len() == right.len()
+                    && left.nulls() == right.nulls()
+                    && left.value_bitmaps() == right.value_bitmaps()
+            }
+            (Self::Text(left), Self::Text(right)) => left.same_texts(right),
+            _ => false,
+        }
+    }
+
+    // Дописать новые строки в конец на месте: values - значения content extractor-а
+    // рецепта для этих строк. false - индекс нельзя дописать (не изменен), нужен rebuild
+    pub fn append_values(&mut self, values: &[field::FieldValue]) -> bool {
+        match self {
+            Self::Field((field_index, _)) => field_index.push_values(values),
+            Self::Text(text_index) => {
+                let texts = values
+                    .iter()
+                    .map(|value| match value {
+                        field::FieldValue::String(text) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<String>>>();
+                match texts {
+                    Some(texts) => {
+                        text_index.push_texts(texts);
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    // append_values на копии - для индекса, который еще читают другие
+    pub fn appended(&self, values: &[field::FieldValue]) -> Option<Self> {
+        let mut index = match self {
+            Self::Field((field_index, extractor)) => Self::Field((field_index.clone(), Arc::clone(extractor))),
+            Self::Text(text_index) => Self::Text(text_index.clone()),
+        };
+        index.append_values(values).then_some(index)
+    }
+
+    // Индекс без удаленных строк (FilterData::retain / remove_indices)
+    pub fn without(&self, rows: &RoaringBitmap) -> Self {
+        match self {
+            Self::Field((field_index, extractor)) => {
+                let mut field_index = field_index.clone();
+                field_index.remove_positions(rows);
+                Self::Field((field_index, Arc::clone(extractor)))
+            }
+            Self::Text(text_index) => {
+                let mut text_index = text_index.clone();
+                text_index.remove_positions(rows);
+                Self::Text(text_index)
+            }
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         match self {
             Self::Text(_) => true,
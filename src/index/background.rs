@@ -0,0 +1,151 @@
+use super::{ExtractorFieldValue, IndexBuilder, storage::Rows};
+use parking_lot::{Condvar, Mutex};
+use roaring::RoaringBitmap;
+use std::{
+    fmt::Display,
+    sync::Arc,
+};
+
+// Состояние фонового построения индекса
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexBuildStatus {
+    // Ленивое построение: начнется при первом запросе по индексу
+    Deferred,
+    Building,
+    Ready,
+    Failed(String),
+    Cancelled,
+    // Построен, но за время построения установлена более новая версия индекса
+    // (rebuild_index, create_* с тем же именем) - результат отброшен
+    Superseded,
+}
+
+impl IndexBuildStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, Self::Deferred | Self::Building)
+    }
+}
+
+impl Display for IndexBuildStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Deferred => write!(f, "deferred"),
+            Self::Building => write!(f, "building"),
+            Self::Ready => write!(f, "ready"),
+            Self::Failed(reason) => write!(f, "failed: {reason}"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Superseded => write!(f, "superseded"),
+        }
+    }
+}
+
+/// Handle фонового построения индекса
+///
+/// Возвращается из `create_field_index_background` и `create_field_index_lazy`.
+/// Пока индекс строится,
+/// запросы по нему выполняются через predicate path; после завершения
+/// индекс атомарно подменяется в FilterData.
+///
+/// # Example
+///
+/// let handle = data.create_field_index_background("price", |p| p.price)?;
+/// // ... запросы продолжают работать
+/// assert_eq!(handle.wait(), IndexBuildStatus::Ready);
+///
+#[derive(Clone)]
+pub struct IndexHandle {
+    name: Arc<str>,
+    state: Arc<(Mutex<IndexBuildStatus>, Condvar)>,
+}
+
+impl IndexHandle {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: Arc::from(name),
+            state: Arc::new((Mutex::new(IndexBuildStatus::Building), Condvar::new())),
+        }
+    }
+
+    // Handle ленивого построения (create_field_index_lazy)
+    pub(crate) fn deferred(name: &str) -> Self {
+        Self {
+            name: Arc::from(name),
+            state: Arc::new((Mutex::new(IndexBuildStatus::Deferred), Condvar::new())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> IndexBuildStatus {
+        self.state.0.lock().clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status() == IndexBuildStatus::Ready
+    }
+
+    // Блокирует до завершения построения
+    // (ленивое построение сначала ждет первого запроса по индексу)
+    pub fn wait(&self) -> IndexBuildStatus {
+        let (lock, condvar) = &*self.state;
+        let mut status = lock.lock();
+        while !status.is_finished() {
+            condvar.wait(&mut status);
+        }
+        status.clone()
+    }
+
+    pub(crate) fn finish(&self, status: IndexBuildStatus) {
+        let (lock, condvar) = &*self.state;
+        let mut current = lock.lock();
+        // Финальный статус выставляется только один раз
+        if !current.is_finished() {
+            *current = status;
+        }
+        condvar.notify_all();
+    }
+
+    // Deferred -> Building; false - построение уже запущено или завершено
+    pub(crate) fn start(&self) -> bool {
+        let mut status = self.state.0.lock();
+        if *status == IndexBuildStatus::Deferred {
+            *status = IndexBuildStatus::Building;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn same_build(&self, other: &IndexHandle) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+// Индекс в процессе построения: extractor нужен для predicate path,
+// builder - для запуска ленивого построения
+pub(crate) struct PendingIndex<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub handle: IndexHandle,
+    pub extractor: ExtractorFieldValue<T>,
+    pub builder: IndexBuilder<T>,
+    // Версия, с которой встанет построенный индекс. push / retain во время
+    // построения поднимают версию прежнего индекса - и эту вслед за ней
+    pub version: u64,
+    // Строки и удаленные строки после последнего push / retain во время
+    // построения: перед подменой построенный индекс их догоняет
+    pub rows: Option<Arc<Rows<T>>>,
+    pub removed: Option<Arc<RoaringBitmap>>,
+}
+
+impl<T> PendingIndex<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn new(handle: IndexHandle, extractor: ExtractorFieldValue<T>, builder: IndexBuilder<T>) -> Self {
+        Self { handle, extractor, builder, version: 0, rows: None, removed: None }
+    }
+}
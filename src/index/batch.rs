@@ -0,0 +1,141 @@
+use super::{
+    ExtractorFieldValue,
+    IndexType,
+    field::{FieldValue, IndexField, IntoIndexFieldEnum},
+    integrity::{FINGERPRINT_CHUNK, IndexFingerprint},
+};
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+// Промежуточное состояние колонки по одному чанку строк
+pub type ColumnChunk = Box<dyn Any + Send>;
+
+// Колонка пакетного построения: тип значения скрыт за trait-ом,
+// поэтому индексы с разными V строятся за один проход по данным
+pub trait BatchColumn<T>: Send + Sync
+where
+    T: Send + Sync + 'static,
+{
+    fn empty(&self) -> ColumnChunk;
+    fn insert(&self, chunk: &mut ColumnChunk, pos: u32, item: &T);
+    fn merge(&self, left: ColumnChunk, right: ColumnChunk) -> ColumnChunk;
+    fn finish(&self, chunk: ColumnChunk, size: usize) -> IndexType<T>;
+}
+
+pub struct FieldColumn<V, F> {
+    extractor: F,
+    _phantom: PhantomData<fn() -> V>,
+}
+
+impl<V, F> FieldColumn<V, F> {
+    pub fn new(extractor: F) -> Self {
+        Self {
+            extractor,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, V, F> BatchColumn<T> for FieldColumn<V, F>
+where
+    T: Send + Sync + 'static,
+    V: Eq + Hash + Clone + Send + Sync + Ord + PartialOrd + Display + 'static,
+    V: Into<FieldValue>,
+    F: Fn(&T) -> V + Send + Sync + Clone + 'static,
+    IndexField<V>: IntoIndexFieldEnum,
+{
+    fn empty(&self) -> ColumnChunk {
+        Box::new(BTreeMap::<V, RoaringBitmap>::new())
+    }
+
+    fn insert(&self, chunk: &mut ColumnChunk, pos: u32, item: &T) {
+        if let Some(map) = chunk.downcast_mut::<BTreeMap<V, RoaringBitmap>>() {
+            map.entry((self.extractor)(item)).or_default().insert(pos);
+        }
+    }
+
+    fn merge(&self, left: ColumnChunk, right: ColumnChunk) -> ColumnChunk {
+        let (Ok(left), Ok(right)) = (
+            left.downcast::<BTreeMap<V, RoaringBitmap>>(),
+            right.downcast::<BTreeMap<V, RoaringBitmap>>(),
+        ) else {
+            unreachable!("column chunk type mismatch");
+        };
+        Box::new(IndexField::<V>::merge_value_maps(*left, *right))
+    }
+
+    fn finish(&self, chunk: ColumnChunk, size: usize) -> IndexType<T> {
+        let map = chunk
+            .downcast::<BTreeMap<V, RoaringBitmap>>()
+            .map(|map| *map)
+            .unwrap_or_default();
+        let extractor = self.extractor.clone();
+        let content: ExtractorFieldValue<T> = Arc::new(move |item: &T| extractor(item).into());
+        IndexType::Field((IndexField::from_value_map(map, size).into_enum(), content))
+    }
+}
+
+// Построить несколько индексов за один параллельный проход:
+// каждый чанк строк читается один раз, из строки извлекаются значения всех колонок
+// и сразу считаются хеши для fingerprint-ов (чанки совпадают с IndexFingerprint::compute).
+pub fn build_columns<T>(
+    items: &[Arc<T>],
+    columns: &[(&dyn BatchColumn<T>, &ExtractorFieldValue<T>)],
+) -> Vec<(IndexType<T>, IndexFingerprint)>
+where
+    T: Send + Sync + 'static,
+{
+    let chunks: Vec<Vec<(ColumnChunk, u64)>> = items
+        .par_chunks(FINGERPRINT_CHUNK)
+        .enumerate()
+        .map(|(chunk_id, chunk)| {
+            let offset = chunk_id * FINGERPRINT_CHUNK;
+            let mut states: Vec<ColumnChunk> = columns.iter().map(|(column, _)| column.empty()).collect();
+            let mut hashers: Vec<DefaultHasher> = columns.iter().map(|_| DefaultHasher::new()).collect();
+            for (pos, item) in chunk.iter().enumerate() {
+                for (((column, content), state), hasher) in columns.iter().zip(states.iter_mut()).zip(hashers.iter_mut()) {
+                    column.insert(state, (offset + pos) as u32, item);
+                    content(item).hash(hasher);
+                }
+            }
+            states
+                .into_iter()
+                .zip(hashers)
+                .map(|(state, hasher)| (state, hasher.finish()))
+                .collect()
+        })
+        .collect();
+
+    // Транспонируем: чанки -> колонки
+    let mut per_column: Vec<Vec<(ColumnChunk, u64)>> = columns.iter().map(|_| Vec::with_capacity(chunks.len())).collect();
+    for chunk in chunks {
+        for (column_chunks, state) in per_column.iter_mut().zip(chunk) {
+            column_chunks.push(state);
+        }
+    }
+
+    columns
+        .par_iter()
+        .zip(per_column.into_par_iter())
+        .map(|((column, _), chunks)| {
+            let hashes: Vec<u64> = chunks.iter().map(|(_, hash)| *hash).collect();
+            let state = chunks
+                .into_iter()
+                .map(|(state, _)| state)
+                .reduce(|left, right| column.merge(left, right))
+                .unwrap_or_else(|| column.empty());
+            (
+                column.finish(state, items.len()),
+                IndexFingerprint::from_chunk_hashes(items.len(), &hashes),
+            )
+        })
+        .collect()
+}
@@ -5,9 +5,14 @@ use std::{
 use rayon::prelude::*;
 use roaring::RoaringBitmap;
 
+// Позиция строки в bitmap-е - u32: больше строк индексы не адресуют.
+// 64-битного (RoaringTreemap) режима нет - такие наборы отклоняются (TooManyRows)
+pub const MAX_INDEXED_ROWS: usize = u32::MAX as usize + 1;
+
 // Op - Битовые операции
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Op {
     And,    // Пересечение (∩)
     Or,     // Объединение (∪)
@@ -140,8 +145,17 @@ pub struct IndexStats {
     pub memory_bytes: usize,
 }
 
+// Прогрев: пройти по всем контейнерам bitmap-а, чтобы страницы
+// попали в память до первого запроса. Возвращает размер в байтах
+pub fn touch(bitmap: &RoaringBitmap) -> usize {
+    std::hint::black_box(bitmap.iter().fold(0u32, |acc, value| acc ^ value));
+    bitmap.serialized_size()
+}
+
 // BitIndex - Битовый индекс на основе RoaringBitmap
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Index {
     // RoaringBitmap для хранения индексов (lock-free чтение!)
     bitmap: RoaringBitmap,
@@ -255,6 +269,21 @@ impl Index {
         &self.bitmap
     }
 
+    // Установить бит (строка дописана в конец данных)
+    #[inline]
+    pub fn insert(&mut self, index: usize) {
+        self.bitmap.insert(index as u32);
+    }
+
+    // Сбросить биты строк (строки удалены из данных)
+    pub fn remove_all(&mut self, rows: &RoaringBitmap) {
+        self.bitmap -= rows;
+    }
+
+    pub fn set_total_size(&mut self, total_size: usize) {
+        self.total_size = total_size;
+    }
+
     // Получить значение бита на позиции 
     #[inline]
     pub fn get(&self, index: usize) -> bool {
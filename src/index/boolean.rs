@@ -0,0 +1,354 @@
+use super::{
+    bit::Op,
+    field::{FieldOperation, FieldValue, IndexAnalizer, IndexField, IndexKind},
+    stats::PlannerHints,
+};
+use roaring::RoaringBitmap;
+use rayon::prelude::*;
+use std::sync::Arc;
+
+// Специализированный индекс для bool полей: два bitmap-а вместо
+// BTreeMap + sorted_values. Каждая строка лежит ровно в одном из них,
+// поэтому Eq/NotEq/сравнения - просто выбор bitmap-а.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoolIndex {
+    true_bits: RoaringBitmap,
+    false_bits: RoaringBitmap,
+    size: usize,
+    // Строки без значения (nullable поле) - ни в true_bits, ни в false_bits
+    nulls: RoaringBitmap,
+    // Поле-массив: строка может быть и в true_bits, и в false_bits
+    multi_valued: bool,
+}
+
+impl BoolIndex {
+    pub fn new(true_bits: RoaringBitmap, false_bits: RoaringBitmap, size: usize) -> Self {
+        Self {
+            true_bits,
+            false_bits,
+            size,
+            nulls: RoaringBitmap::new(),
+            multi_valued: false,
+        }
+    }
+
+    pub fn build<T, F>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> bool + Send + Sync,
+    {
+        let size = items.len();
+        let true_bits: RoaringBitmap = if size > 10_000 {
+            let positions: Vec<u32> = items
+                .par_iter()
+                .enumerate()
+                .filter(|(_, item)| extractor(item))
+                .map(|(pos, _)| pos as u32)
+                .collect();
+            RoaringBitmap::from_sorted_iter(positions).unwrap_or_default()
+        } else {
+            items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| extractor(item))
+                .map(|(pos, _)| pos as u32)
+                .collect()
+        };
+        let false_bits = Self::full(size) - &true_bits;
+        Self::new(true_bits, false_bits, size)
+    }
+
+    // Дописать строки в конец индекса (см. IndexField::push_values)
+    pub fn push_values<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = Option<bool>>,
+    {
+        for value in values {
+            let position = self.size as u32;
+            match value {
+                Some(true) => self.true_bits.insert(position),
+                Some(false) => self.false_bits.insert(position),
+                None => self.nulls.insert(position),
+            };
+            self.size += 1;
+        }
+    }
+
+    pub fn remove_positions(&mut self, rows: &RoaringBitmap) {
+        self.true_bits -= rows;
+        self.false_bits -= rows;
+        self.nulls -= rows;
+    }
+
+    pub fn bitmap_bytes(&self) -> usize {
+        self.true_bits.serialized_size() + self.false_bits.serialized_size() + self.nulls.serialized_size()
+    }
+
+    // Списка позиций и карты значений нет
+    pub fn sorted_values_bytes(&self) -> usize {
+        0
+    }
+
+    pub fn map_overhead_bytes(&self) -> usize {
+        0
+    }
+
+    // Sparse вариант не нужен: два bitmap-а без списка позиций уже компактны
+    pub fn set_sparse_default(&mut self, _default: bool) {}
+
+    fn full(size: usize) -> RoaringBitmap {
+        let mut bitmap = RoaringBitmap::new();
+        bitmap.insert_range(0..size as u32);
+        bitmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn nulls(&self) -> &RoaringBitmap {
+        &self.nulls
+    }
+
+    pub fn is_multi_valued(&self) -> bool {
+        self.multi_valued
+    }
+
+    // Два bitmap-а упорядочены по значению - range доступен всегда
+    pub fn kind(&self) -> IndexKind {
+        IndexKind::Sorted
+    }
+
+    pub fn get_bitmap(&self, value: &bool) -> &RoaringBitmap {
+        if *value { &self.true_bits } else { &self.false_bits }
+    }
+
+    pub fn value_count(&self, value: &bool) -> usize {
+        self.get_bitmap(value).len() as usize
+    }
+
+    pub fn unique_values_count(&self) -> usize {
+        self.values().len()
+    }
+
+    pub fn values(&self) -> Vec<bool> {
+        [false, true]
+            .into_iter()
+            .filter(|value| !self.get_bitmap(value).is_empty())
+            .collect()
+    }
+
+    pub fn bitmaps(&self) -> impl Iterator<Item = &RoaringBitmap> {
+        self.value_bitmaps().into_iter().map(|(_, bitmap)| bitmap)
+    }
+
+    // Top-k значений: не больше двух, по убыванию числа строк
+    pub fn top_values(&self, k: usize) -> Vec<(bool, usize)> {
+        let mut counts: Vec<(bool, usize)> = self.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.len() as usize))
+            .collect();
+        counts.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+        counts.truncate(k);
+        counts
+    }
+
+    pub fn value_bitmaps(&self) -> Vec<(bool, &RoaringBitmap)> {
+        self.values()
+            .into_iter()
+            .map(|value| (value, self.get_bitmap(&value)))
+            .collect()
+    }
+
+    // Фильтрации - совпадают по семантике с IndexField<bool>
+
+    pub fn value_eq(&self, value: &bool) -> Option<RoaringBitmap> {
+        let bitmap = self.get_bitmap(value);
+        // Как и IndexField: отсутствующее значение - None
+        (!bitmap.is_empty()).then(|| bitmap.clone())
+    }
+
+    pub fn value_not_eq(&self, value: &bool) -> Option<RoaringBitmap> {
+        Some(self.get_bitmap(&!value).clone())
+    }
+
+    pub fn value_in(&self, values: &[bool]) -> Option<RoaringBitmap> {
+        Some(self.select(|value| values.contains(&value)))
+    }
+
+    pub fn value_not_in(&self, values: &[bool]) -> Option<RoaringBitmap> {
+        Some(self.select(|value| !values.contains(&value)))
+    }
+
+    pub fn value_all(&self, values: &[bool]) -> Option<RoaringBitmap> {
+        let mut result = self.true_bits.clone() | &self.false_bits;
+        for value in values {
+            result &= self.get_bitmap(value);
+        }
+        Some(result)
+    }
+
+    pub fn value_gt(&self, threshold: &bool) -> Option<RoaringBitmap> {
+        Some(self.select(|value| value && !*threshold))
+    }
+
+    pub fn value_gte(&self, threshold: &bool) -> Option<RoaringBitmap> {
+        Some(self.select(|value| value >= *threshold))
+    }
+
+    pub fn value_lt(&self, threshold: &bool) -> Option<RoaringBitmap> {
+        Some(self.select(|value| !value && *threshold))
+    }
+
+    pub fn value_lte(&self, threshold: &bool) -> Option<RoaringBitmap> {
+        Some(self.select(|value| value <= *threshold))
+    }
+
+    pub fn value_range_inclusive(&self, start: &bool, end: &bool) -> Option<RoaringBitmap> {
+        Some(self.select(|value| value >= *start && value <= *end))
+    }
+
+    // Объединение bitmap-ов значений, удовлетворяющих условию
+    fn select<P>(&self, predicate: P) -> RoaringBitmap
+    where
+        P: Fn(bool) -> bool,
+    {
+        let mut result = RoaringBitmap::new();
+        for value in [false, true] {
+            if predicate(value) {
+                result |= self.get_bitmap(&value);
+            }
+        }
+        result
+    }
+
+    // Статистика для планировщика
+
+    pub fn cardinality_ratio(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        self.unique_values_count() as f64 / self.size as f64
+    }
+
+    pub fn is_high_cardinality(&self) -> bool {
+        false
+    }
+
+    pub fn index_analize(&self) -> IndexAnalizer {
+        IndexAnalizer::Bad
+    }
+
+    // Любая операция - выбор одного из двух bitmap-ов
+    pub fn is_efficient_for(&self, _operation: &FieldOperation) -> bool {
+        true
+    }
+
+    pub fn is_efficient_for_hints(&self, operation: &FieldOperation, _hints: &PlannerHints) -> bool {
+        self.is_efficient_for(operation)
+    }
+
+    // Точная селективность: количество строк каждого значения известно
+    pub fn estimate_operation_selectivity(&self, operation: &FieldOperation) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        let ratio = |bitmap: RoaringBitmap| bitmap.len() as f64 / self.size as f64;
+        let as_bool = |value: &FieldValue| match value {
+            FieldValue::Bool(value) => Some(*value),
+            _ => None,
+        };
+        let as_bools = |values: &[FieldValue]| values.iter().filter_map(as_bool).collect::<Vec<bool>>();
+        let bitmap = match operation {
+            FieldOperation::Eq(value) => as_bool(value).and_then(|v| self.value_eq(&v)),
+            FieldOperation::NotEq(value) => as_bool(value).and_then(|v| self.value_not_eq(&v)),
+            FieldOperation::Gt(value) => as_bool(value).and_then(|v| self.value_gt(&v)),
+            FieldOperation::Gte(value) => as_bool(value).and_then(|v| self.value_gte(&v)),
+            FieldOperation::Lt(value) => as_bool(value).and_then(|v| self.value_lt(&v)),
+            FieldOperation::Lte(value) => as_bool(value).and_then(|v| self.value_lte(&v)),
+            FieldOperation::In(values) => self.value_in(&as_bools(values)),
+            FieldOperation::NotIn(values) => self.value_not_in(&as_bools(values)),
+            FieldOperation::Range(start, end) => match (as_bool(start), as_bool(end)) {
+                (Some(start), Some(end)) => self.value_range_inclusive(&start, &end),
+                _ => None,
+            },
+            FieldOperation::IsNull => Some(self.nulls.clone()),
+            FieldOperation::IsNotNull => Some(self.true_bits.clone() | &self.false_bits),
+            FieldOperation::ContainsAny(values) => self.value_in(&as_bools(values)),
+            FieldOperation::ContainsAll(values) => self.value_all(&as_bools(values)),
+            // Строковые операции к bool не применимы
+            FieldOperation::StartsWith(_) | FieldOperation::EndsWith(_) | FieldOperation::ContainsSubstr(_) => None,
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(_) => None,
+        };
+        bitmap.map(ratio).unwrap_or(0.0)
+    }
+
+    pub fn estimate_operations_selectivity(&self, operations: &[(FieldOperation, Op)]) -> f64 {
+        if operations.is_empty() {
+            return 1.0;
+        }
+        let mut result = self.estimate_operation_selectivity(&operations[0].0);
+        for (operation, op) in &operations[1..] {
+            let selectivity = self.estimate_operation_selectivity(operation);
+            result = match op {
+                Op::And => result * selectivity,
+                Op::Or => (result + selectivity).min(1.0),
+                Op::AndNot => result * (1.0 - selectivity),
+                Op::Xor => ((result + selectivity) / 2.0).min(1.0),
+                Op::Invert => 1.0 - result,
+            };
+        }
+        result.clamp(0.0, 1.0)
+    }
+}
+
+// Автоматический выбор специализации при создании field индекса по bool
+impl From<IndexField<bool>> for BoolIndex {
+    fn from(index: IndexField<bool>) -> Self {
+        let bitmap = |value: bool| index.get_bitmap(&value).cloned().unwrap_or_default();
+        let mut result = Self::new(bitmap(true), bitmap(false), index.len());
+        result.nulls = index.nulls().clone();
+        result.multi_valued = index.is_multi_valued();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bool_index_matches_index_field() {
+        let items: Vec<Arc<u32>> = (0..1_000u32).map(Arc::new).collect();
+        let extractor = |x: &u32| x.is_multiple_of(3);
+        let field = IndexField::build(&items, extractor);
+        let expected_true = field.value_eq(&true);
+        let expected_not_true = field.value_not_eq(&true);
+        let expected_gt = field.value_gt(&false);
+        let index = BoolIndex::build(&items, extractor);
+        assert_eq!(index.value_eq(&true), expected_true);
+        assert_eq!(index.value_not_eq(&true), expected_not_true);
+        assert_eq!(index.value_gt(&false), expected_gt);
+        assert_eq!(index.value_count(&true), 334);
+        let converted = BoolIndex::from(field);
+        assert_eq!(converted.value_eq(&false), index.value_eq(&false));
+        assert_eq!(index.values(), vec![false, true]);
+        let selectivity = index.estimate_operation_selectivity(&FieldOperation::eq(true));
+        assert!((selectivity - 0.334).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bool_index_single_value() {
+        let items: Vec<Arc<u32>> = (0..10u32).map(Arc::new).collect();
+        let index = BoolIndex::build(&items, |_| true);
+        assert_eq!(index.value_eq(&false), None);
+        assert_eq!(index.value_not_eq(&true), Some(RoaringBitmap::new()));
+        assert_eq!(index.unique_values_count(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+use parking_lot::Mutex;
+use roaring::RoaringBitmap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+// Статистика кеша результатов field запросов (FilterData::result_cache_stats)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    // Сериализованный размер bitmap-ов в кеше
+    pub bytes: usize,
+    // Ограничение по байтам (None - только по числу записей)
+    pub max_bytes: Option<usize>,
+    pub hits: u64,
+    pub misses: u64,
+    // Вытеснены по LRU
+    pub evictions: u64,
+    // Отброшены из-за новой версии индекса (rebuild, push, retain)
+    pub invalidations: u64,
+}
+
+struct CachedBitmap {
+    // Версия индекса, по которой посчитан bitmap
+    version: u64,
+    bitmap: RoaringBitmap,
+    bytes: usize,
+    last_used: u64,
+}
+
+// Ячейка считаемого промаха: первый поток считает bitmap под ее блокировкой,
+// остальные с тем же ключом ждут его результат, не блокируя весь кеш
+pub(crate) type PendingBitmap = Arc<Mutex<Option<RoaringBitmap>>>;
+
+pub(crate) enum CacheLookup {
+    Hit(RoaringBitmap),
+    Miss(PendingBitmap),
+}
+
+// LRU кеш bitmap-ов field запросов в позициях индекса.
+// Ключ - имя индекса + нормализованные операции (см. FilterData::result_cache_key),
+// запись действительна только для той версии индекса, по которой посчитана
+pub(crate) struct ResultCache {
+    capacity: usize,
+    max_bytes: Option<usize>,
+    bytes: usize,
+    entries: HashMap<String, CachedBitmap>,
+    // last_used -> ключ: самая старая запись - первая
+    order: BTreeMap<u64, String>,
+    // Промахи, которые сейчас считаются (ключ -> версия и ячейка)
+    pending: HashMap<String, (u64, PendingBitmap)>,
+    tick: u64,
+    stats: ResultCacheStats,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            max_bytes: None,
+            bytes: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            pending: HashMap::new(),
+            tick: 0,
+            stats: ResultCacheStats::default(),
+        }
+    }
+
+    // Дополнительное ограничение суммарного размера bitmap-ов
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    // Запись из кеша или ячейка для подсчета промаха. Считать нужно уже без
+    // блокировки кеша (см. FilterData::cached_field_operations): одинаковые
+    // промахи получают одну ячейку и не считают bitmap дважды
+    pub fn lookup(&mut self, key: &str, version: u64) -> CacheLookup {
+        if let Some(bitmap) = self.get(key, version) {
+            return CacheLookup::Hit(bitmap);
+        }
+        let (pending_version, pending) = self.pending
+            .entry(key.to_string())
+            .or_insert_with(|| (version, PendingBitmap::default()));
+        if *pending_version != version {
+            *pending_version = version;
+            *pending = PendingBitmap::default();
+        }
+        CacheLookup::Miss(Arc::clone(pending))
+    }
+
+    // Промах посчитан (bitmap = None - с ошибкой): ячейка больше не нужна
+    pub fn complete(&mut self, key: String, version: u64, pending: &PendingBitmap, bitmap: Option<RoaringBitmap>) {
+        if self.pending.get(&key).is_some_and(|(_, current)| Arc::ptr_eq(current, pending)) {
+            self.pending.remove(&key);
+        }
+        if let Some(bitmap) = bitmap {
+            self.insert(key, version, bitmap);
+        }
+    }
+
+    pub fn get(&mut self, key: &str, version: u64) -> Option<RoaringBitmap> {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) if entry.version == version => {
+                self.order.remove(&entry.last_used);
+                entry.last_used = self.tick;
+                self.order.insert(self.tick, key.to_string());
+                self.stats.hits += 1;
+                Some(entry.bitmap.clone())
+            }
+            Some(_) => {
+                self.remove_entry(key);
+                self.stats.invalidations += 1;
+                self.stats.misses += 1;
+                None
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    // Есть действительная запись (без учета в hits / misses)
+    pub fn contains(&self, key: &str, version: u64) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.version == version)
+    }
+
+    pub fn insert(&mut self, key: String, version: u64, bitmap: RoaringBitmap) {
+        self.tick += 1;
+        let bytes = bitmap.serialized_size();
+        // Bitmap больше всего бюджета не кешируется
+        if self.max_bytes.is_some_and(|max_bytes| bytes > max_bytes) {
+            return;
+        }
+        self.remove_entry(&key);
+        // Вытесняем давно не использованные записи
+        while self.entries.len() >= self.capacity
+            || self.max_bytes.is_some_and(|max_bytes| self.bytes + bytes > max_bytes)
+        {
+            let Some(oldest) = self.order.first_key_value().map(|(_, key)| key.clone()) else {
+                break;
+            };
+            self.remove_entry(&oldest);
+            self.stats.evictions += 1;
+        }
+        self.bytes += bytes;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, CachedBitmap { version, bitmap, bytes, last_used: self.tick });
+    }
+
+    fn remove_entry(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.bytes;
+            self.order.remove(&entry.last_used);
+        }
+    }
+
+    // Удалить записи индекса (drop_index)
+    pub fn remove_index(&mut self, name: &str) {
+        let of_index = |key: &str| key.split('\0').next() == Some(name);
+        let before = self.entries.len();
+        let mut removed_bytes = 0;
+        self.entries.retain(|key, entry| {
+            let keep = !of_index(key);
+            if !keep {
+                removed_bytes += entry.bytes;
+            }
+            keep
+        });
+        self.order.retain(|_, key| !of_index(key));
+        self.pending.retain(|key, _| !of_index(key));
+        self.bytes -= removed_bytes;
+        self.stats.invalidations += (before - self.entries.len()) as u64;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.pending.clear();
+        self.bytes = 0;
+    }
+
+    pub fn stats(&self) -> ResultCacheStats {
+        ResultCacheStats {
+            capacity: self.capacity,
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            max_bytes: self.max_bytes,
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_cache_lru_and_versions() {
+        let mut cache = ResultCache::new(2);
+        cache.insert("a\0x".to_string(), 1, (0..10).collect());
+        cache.insert("b\0x".to_string(), 1, (0..20).collect());
+        assert_eq!(cache.get("a\0x", 1).map(|bitmap| bitmap.len()), Some(10));
+        // b - давно не использованная
+        cache.insert("c\0x".to_string(), 1, RoaringBitmap::new());
+        assert!(cache.get("b\0x", 1).is_none());
+        assert!(cache.contains("a\0x", 1));
+        // Новая версия индекса - запись устарела
+        assert!(cache.get("a\0x", 2).is_none());
+        cache.remove_index("c");
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses, stats.evictions, stats.invalidations), (0, 1, 2, 1, 2));
+        assert_eq!(stats.bytes, 0);
+    }
+
+    #[test]
+    fn test_result_cache_byte_bound() {
+        let bitmap: RoaringBitmap = (0..1_000).map(|n| n * 3).collect();
+        let size = bitmap.serialized_size();
+        let mut cache = ResultCache::new(10).with_max_bytes(size * 2);
+        cache.insert("a\0x".to_string(), 1, bitmap.clone());
+        cache.insert("b\0x".to_string(), 1, bitmap.clone());
+        assert_eq!(cache.stats().bytes, size * 2);
+        // Третья запись не помещается в бюджет - вытесняется самая старая
+        cache.insert("c\0x".to_string(), 1, bitmap.clone());
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, size * 2, 1));
+        assert!(!cache.contains("a\0x", 1));
+        // Больше бюджета целиком - не кешируется
+        cache.insert("d\0x".to_string(), 1, (0..100_000).collect());
+        assert!(!cache.contains("d\0x", 1));
+
+        assert!(matches!(cache.lookup("c\0x", 1), CacheLookup::Hit(bitmap) if bitmap.len() == 1_000));
+    }
+
+    #[test]
+    fn test_result_cache_pending_slot() {
+        let mut cache = ResultCache::new(2);
+        // Одинаковые промахи делят одну ячейку, другая версия - новую
+        let CacheLookup::Miss(first) = cache.lookup("a\0x", 1) else { panic!("expected miss") };
+        let CacheLookup::Miss(second) = cache.lookup("a\0x", 1) else { panic!("expected miss") };
+        assert!(Arc::ptr_eq(&first, &second));
+        let CacheLookup::Miss(stale) = cache.lookup("a\0x", 2) else { panic!("expected miss") };
+        assert!(!Arc::ptr_eq(&first, &stale));
+
+        // Ячейка старой версии не снимает текущую и не попадает в кеш как версия 2
+        cache.complete("a\0x".to_string(), 1, &first, Some((0..5).collect()));
+        assert!(cache.contains("a\0x", 1));
+        cache.complete("a\0x".to_string(), 2, &stale, Some((0..7).collect()));
+        assert!(matches!(cache.lookup("a\0x", 2), CacheLookup::Hit(bitmap) if bitmap.len() == 7));
+        assert!(cache.pending.is_empty());
+
+        // LRU порядок поддерживается при попаданиях
+        cache.insert("b\0x".to_string(), 1, RoaringBitmap::new());
+        let _ = cache.get("a\0x", 2);
+        cache.insert("c\0x".to_string(), 1, RoaringBitmap::new());
+        assert!(cache.contains("a\0x", 2) && !cache.contains("b\0x", 1));
+        assert_eq!(cache.order.len(), cache.entries.len());
+    }
+}
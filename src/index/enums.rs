@@ -0,0 +1,115 @@
+use super::field::{FieldStorage, FieldValue};
+
+/// Пользовательский enum, индексируемый по компактному коду
+///
+/// Индекс хранит u32 коды (IndexField<u32>), а `enum_values`/`count_by_enum`
+/// возвращают обратно значения enum - без `format!("{:?}")` строковых индексов.
+/// Для enum без полей реализацию дает макрос `impl_index_enum!`.
+///
+/// # Example
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Status { Active, Blocked, Deleted }
+///
+/// tree_man::impl_index_enum!(Status { Active, Blocked, Deleted });
+///
+/// data.create_enum_index("status", |user: &User| user.status)?;
+/// data.filter_by_field_ops("status", &[(FieldOperation::eq(Status::Active), Op::And)])?;
+/// let counts = data.count_by_enum::<Status>("status")?;
+///
+pub trait IndexEnum: Sized + Send + Sync + 'static {
+    fn to_code(&self) -> u32;
+    fn from_code(code: u32) -> Option<Self>;
+}
+
+/// Пользовательский тип как значение field индекса
+///
+/// В индексе хранится `Stored` - встроенный тип значения (порядковый номер,
+/// имя и т.п.). Тип принимают `create_field_index` и конструкторы
+/// FieldOperation (`eq`, `in_values`, ...); range запросы идут в порядке `Stored`.
+/// Для `IndexEnum` реализован автоматически (`Stored = u32`).
+/// Построение индекса требует `Eq + Hash + Ord + Clone` у самого типа.
+///
+/// # Example
+///
+/// #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// enum Status { Active, Pending, Deleted }
+///
+/// impl IntoFieldValue for Status {
+///     type Stored = String;
+///     fn into_stored(self) -> String {
+///         match self { Status::Active => "active", Status::Pending => "pending", Status::Deleted => "deleted" }.to_string()
+///     }
+/// }
+///
+/// data.create_field_index("status", |order: &Order| order.status)?;
+/// data.filter_by_field_ops("status", &[(FieldOperation::eq(Status::Active), Op::And)])?;
+///
+pub trait IntoFieldValue: Sized {
+    type Stored: FieldStorage;
+
+    fn into_stored(self) -> Self::Stored;
+}
+
+impl<E: IndexEnum> IntoFieldValue for E {
+    type Stored = u32;
+
+    fn into_stored(self) -> u32 {
+        self.to_code()
+    }
+}
+
+impl<V: IntoFieldValue> From<V> for FieldValue {
+    fn from(value: V) -> Self {
+        value.into_stored().into()
+    }
+}
+
+/// Реализация `IndexEnum` для enum без полей
+///
+/// Код варианта - его дискриминант (`Variant as u32`), поэтому явные
+/// дискриминанты (`Active = 10`) сохраняются в индексе как есть.
+#[macro_export]
+macro_rules! impl_index_enum {
+    ($enum:ident { $($variant:ident),+ $(,)? }) => {
+        impl $crate::IndexEnum for $enum {
+            fn to_code(&self) -> u32 {
+                match self {
+                    $(
+                        $enum::$variant => $enum::$variant as u32,
+                    )+
+                }
+            }
+
+            fn from_code(code: u32) -> Option<Self> {
+                $(
+                    if code == $enum::$variant as u32 {
+                        return Some($enum::$variant);
+                    }
+                )+
+                None
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Level {
+        Low = 1,
+        High = 7,
+    }
+
+    impl_index_enum!(Level { Low, High });
+
+    #[test]
+    fn index_enum_codes_roundtrip() {
+        assert_eq!(Level::High.to_code(), 7);
+        assert_eq!(Level::from_code(1), Some(Level::Low));
+        assert_eq!(Level::from_code(2), None);
+        assert_eq!(FieldValue::from(Level::High), FieldValue::U32(7));
+    }
+}
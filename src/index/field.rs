@@ -2,27 +2,41 @@ use super::bit::{
     Index,
     Op,
 };
+use super::boolean::BoolIndex;
+use super::enums::IntoFieldValue;
+use super::normalize::DecimalIndexOptions;
+use super::logic::{TriBitmap, Truth};
+use super::pool;
+use super::stats::{FieldStats, PlannerHints};
 use super::super::{
     errors::IndexFieldError,
+    model::{IndexRecommendation, IndexReport},
     result::IndexFieldResult,
 };
+use ahash::AHashMap;
 use ordered_float::OrderedFloat;
 use roaring::RoaringBitmap;
 use rayon::prelude::*;
+use smallvec::SmallVec;
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, btree_map},
     cmp::{self,Ord},
     hash::Hash,
     fmt::{Debug,Display},
-    ops::Bound,
-    sync::Arc,
+    ops::{Bound, Deref, RangeBounds},
+    sync::{Arc, OnceLock},
 };
 use rust_decimal::{
     Decimal,
     prelude::*,
 };
+#[cfg(feature = "datetime")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
-const CARDINALITY_RATIO_LOW_THRESHOLD: f64 = 0.05;
+pub(crate) const CARDINALITY_RATIO_LOW_THRESHOLD: f64 = 0.05;
 const CARDINALITY_RATIO_HIGH_THRESHOLD: f64 = 0.50;
 const SELECTIVITY_RATIO_EXCELLENT: f64 = 0.001;
 const SELECTIVITY_RATIO_GOOD: f64 = 0.01;
@@ -34,9 +48,128 @@ const SELECTIVITY_RANGE_RATIO_BAD: f64 = 0.20;
 const SELECTIVITY_RANGE_RATIO_BAD_SKEWED: f64 = 0.40;
 const SKEWED_RATIO: f64 = 0.30;
 const VALUE_OFTEN_RATIO: f64 = 0.5;
+// Целевой пик памяти на промежуточные структуры при построении индекса
+pub const DEFAULT_BUILD_MEMORY_TARGET: usize = 64 * 1024 * 1024;
+const MIN_BUILD_CHUNK: usize = 1024;
+
+// Оценка индекса по числу строк, значений и строк самого частого значения
+fn build_index_quantity(
+   size: usize,
+   unique_count: usize,
+   max_count: usize, 
+) -> f64{
+    if unique_count == 0 || size == 0{
+        return 0.0
+    }
+
+    let greate_count = size as f64 / unique_count as f64;
+    let deviation = (max_count as f64 - greate_count).abs();
+    1.0 - (deviation / size as f64)
+}
+
+fn build_index_skewed(
+    size: usize,
+    max_count: usize,
+) -> bool {
+    if size > 0 {
+        return (max_count as f64 / size as f64) > VALUE_OFTEN_RATIO
+    }
+    false
+}
+
+fn build_index_analyzier(
+    index_quality: f64,
+    cardinality_ratio: f64,
+) -> IndexAnalizer{
+    let cardinality = cardinality_ratio;
+    // Высокая кардинальности (> 50% уникальных)
+    if cardinality > CARDINALITY_RATIO_HIGH_THRESHOLD {
+        return IndexAnalizer::Excellent
+    }
+    //Очень низкая кардинальность + перекос
+    if cardinality < CARDINALITY_RATIO_LOW_THRESHOLD{
+        return IndexAnalizer::Bad;
+    } 
+    // Набдюается сильный перекос независимо от кардинальности
+    if index_quality < SKEWED_RATIO {
+        return IndexAnalizer::Bad
+    }
+    // Остальные случаи могут быть приемлимыми
+    IndexAnalizer::Good
+}
 
 pub type F64 = OrderedFloat<f64>;
 pub type F32 = OrderedFloat<f32>;
+#[cfg(feature = "datetime")]
+pub type Timestamp = DateTime<Utc>;
+
+/// Бинарный ключ (хеш, MAC)
+///
+/// До 32 байт хранится без аллокации. Порядок - лексикографический по байтам,
+/// в тексте (Display, экспорт) - hex.
+///
+/// # Example
+///
+/// data.create_field_index("hash", |file: &File| Bytes::from(file.sha256))?;
+/// data.filter_by_field_ops("hash", &[(FieldOperation::eq(Bytes::from(digest)), Op::And)])?;
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bytes(SmallVec<[u8; 32]>);
+
+impl Bytes {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self(SmallVec::from_slice(bytes))
+    }
+
+    // Байты вне inline-буфера (длинные значения)
+    pub fn heap_bytes(&self) -> usize {
+        if self.0.spilled() { self.0.capacity() } else { 0 }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    // Разбор hex строки (как в Display); None - нечетная длина или не hex символ
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.as_bytes();
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+        hex.chunks_exact(2)
+            .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+            .collect::<Option<SmallVec<[u8; 32]>>>()
+            .map(Self)
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Display for Bytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(v: &[u8]) -> Self { Bytes::new(v) }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(v: Vec<u8>) -> Self { Bytes(SmallVec::from_vec(v)) }
+}
+
+impl<const N: usize> From<[u8; N]> for Bytes {
+    fn from(v: [u8; N]) -> Self { Bytes::new(&v) }
+}
 
 #[derive(Debug,Clone,Copy,PartialEq)]
 pub enum TypeFamily {
@@ -45,9 +178,16 @@ pub enum TypeFamily {
     Decimal,
     String,
     Bool,
+    #[cfg(feature = "datetime")]
+    DateTime,
+    #[cfg(feature = "uuid")]
+    Uuid,
+    Bytes,
+    Null,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FieldValue {
     U128(u128),
     I128(i128),
@@ -66,6 +206,13 @@ pub enum FieldValue {
     Decimal(Decimal),
     String(String),
     Bool(bool),
+    #[cfg(feature = "datetime")]
+    DateTime(Timestamp),
+    #[cfg(feature = "uuid")]
+    Uuid(Uuid),
+    Bytes(Bytes),
+    // Отсутствующее значение (extractor вернул None)
+    Null,
 }
 
 impl FieldValue {
@@ -87,11 +234,41 @@ impl FieldValue {
             FieldValue::String(_) => TypeFamily::String,
             // Утверждения (Boolean)
             FieldValue::Bool(_) => TypeFamily::Bool,
+            // Дата и время (UTC)
+            #[cfg(feature = "datetime")]
+            FieldValue::DateTime(_) => TypeFamily::DateTime,
+            // Идентификаторы
+            #[cfg(feature = "uuid")]
+            FieldValue::Uuid(_) => TypeFamily::Uuid,
+            // Бинарные ключи
+            FieldValue::Bytes(_) => TypeFamily::Bytes,
+            FieldValue::Null => TypeFamily::Null,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, FieldValue::Null)
+    }
+
+    pub fn is_nan(&self) -> bool {
+        match self {
+            FieldValue::F64(value) => value.is_nan(),
+            FieldValue::F32(value) => value.is_nan(),
+            _ => false,
         }
     }
 
+    // Числовое значение (для агрегатов); None для строк и bool
+    pub fn as_f64(&self) -> Option<f64> {
+        self.try_to_f64().map(|value| value.0)
+    }
+
     #[inline(always)]
     pub fn eq(&self, other: &Self) -> bool {
+        // Null не равен ничему, в том числе другому Null
+        if self.is_null() || other.is_null() {
+            return false;
+        }
         if self == other {
             return true;
         }
@@ -120,6 +297,23 @@ impl FieldValue {
         let self_family = self.type_family();
         let other_family = other.type_family();
 
+        // DateTime сравнивается только с DateTime или строкой RFC 3339
+        #[cfg(feature = "datetime")]
+        if matches!(self_family, TypeFamily::DateTime) || matches!(other_family, TypeFamily::DateTime) {
+            return matches!((self.try_to_datetime(), other.try_to_datetime()), (Some(a), Some(b)) if a == b);
+        }
+
+        // Uuid сравнивается только с Uuid или его строковой записью
+        #[cfg(feature = "uuid")]
+        if matches!(self_family, TypeFamily::Uuid) || matches!(other_family, TypeFamily::Uuid) {
+            return matches!((self.try_to_uuid(), other.try_to_uuid()), (Some(a), Some(b)) if a == b);
+        }
+
+        // Bytes сравнивается только с Bytes или hex строкой
+        if matches!(self_family, TypeFamily::Bytes) || matches!(other_family, TypeFamily::Bytes) {
+            return matches!((self.try_to_bytes(), other.try_to_bytes()), (Some(a), Some(b)) if a == b);
+        }
+
         match (self_family, other_family) {
             (TypeFamily::String, TypeFamily::String) => return false,
             (TypeFamily::Bool, TypeFamily::Bool) => return false,
@@ -156,7 +350,7 @@ impl FieldValue {
 
     #[inline(always)]
     pub fn gt(&self, other: &Self) -> bool {
-        if self == other {
+        if self == other || self.is_null() || other.is_null() {
             return false;
         }
 
@@ -200,6 +394,20 @@ impl FieldValue {
         
         let self_family = self.type_family();
         let other_family = other.type_family();
+
+        #[cfg(feature = "datetime")]
+        if matches!(self_family, TypeFamily::DateTime) || matches!(other_family, TypeFamily::DateTime) {
+            return matches!((self.try_to_datetime(), other.try_to_datetime()), (Some(a), Some(b)) if a > b);
+        }
+
+        #[cfg(feature = "uuid")]
+        if matches!(self_family, TypeFamily::Uuid) || matches!(other_family, TypeFamily::Uuid) {
+            return matches!((self.try_to_uuid(), other.try_to_uuid()), (Some(a), Some(b)) if a > b);
+        }
+
+        if matches!(self_family, TypeFamily::Bytes) || matches!(other_family, TypeFamily::Bytes) {
+            return matches!((self.try_to_bytes(), other.try_to_bytes()), (Some(a), Some(b)) if a > b);
+        }
         
         // String через PartialOrd
         if matches!(self_family, TypeFamily::String) || matches!(other_family, TypeFamily::String) {
@@ -243,6 +451,9 @@ impl FieldValue {
 
     #[inline(always)]
     pub fn gte(&self, other: &Self) -> bool {
+        if self.is_null() || other.is_null() {
+            return false;
+        }
         if self == other {
             return true;
         }
@@ -287,14 +498,30 @@ impl FieldValue {
         self.gt(other)
     }
 
+    // Decimal-значение, округленное политикой индекса (не числа - без изменений)
+    pub fn round_decimal(&self, options: &DecimalIndexOptions) -> FieldValue {
+        match self.try_to_decimal() {
+            Some(value) => FieldValue::Decimal(options.round(value)),
+            None => self.clone(),
+        }
+    }
+
+    // Равенство после округления обоих значений до scale знаков (денежные суммы)
+    pub fn eq_at_scale(&self, other: &Self, options: &DecimalIndexOptions) -> bool {
+        match (self.try_to_decimal(), other.try_to_decimal()) {
+            (Some(left), Some(right)) => options.round(left) == options.round(right),
+            _ => false,
+        }
+    }
+
     #[inline(always)]
     pub fn lt(&self, other: &Self) -> bool {
-        !self.gte(other)
+        !self.is_null() && !other.is_null() && !self.gte(other)
     }
 
     #[inline(always)]
     pub fn lte(&self, other: &Self) -> bool {
-        !self.gt(other)
+        !self.is_null() && !other.is_null() && !self.gt(other)
     }
     
 }
@@ -397,10 +624,47 @@ impl From<bool> for FieldValue {
     fn from(v: bool) -> Self { FieldValue::Bool(v) }
 }
 
+#[cfg(feature = "datetime")]
+impl From<Timestamp> for FieldValue {
+    fn from(v: Timestamp) -> Self { FieldValue::DateTime(v) }
+}
+
+#[cfg(feature = "uuid")]
+impl From<Uuid> for FieldValue {
+    fn from(v: Uuid) -> Self { FieldValue::Uuid(v) }
+}
+
+impl From<Bytes> for FieldValue {
+    fn from(v: Bytes) -> Self { FieldValue::Bytes(v) }
+}
+
+impl From<&[u8]> for FieldValue {
+    fn from(v: &[u8]) -> Self { FieldValue::Bytes(Bytes::new(v)) }
+}
+
+impl From<Vec<u8>> for FieldValue {
+    fn from(v: Vec<u8>) -> Self { FieldValue::Bytes(Bytes::from(v)) }
+}
+
+impl<const N: usize> From<[u8; N]> for FieldValue {
+    fn from(v: [u8; N]) -> Self { FieldValue::Bytes(Bytes::new(&v)) }
+}
+
+// None -> Null: extractor nullable поля можно передавать как есть
+impl<V: Into<FieldValue>> From<Option<V>> for FieldValue {
+    fn from(v: Option<V>) -> Self {
+        v.map_or(FieldValue::Null, Into::into)
+    }
+}
+
 
 // FieldOperation - API операции
 
-#[derive(Clone, Debug,PartialEq)]
+// Скомпилированная цепочка операций для predicate path
+pub type CompiledOperations = Arc<dyn Fn(&FieldValue) -> bool + Send + Sync>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FieldOperation {
     // Равенство: field == value
     Eq(FieldValue),
@@ -428,6 +692,42 @@ pub enum FieldOperation {
     
     // Диапазон: start <= field <= end
     Range(FieldValue, FieldValue),
+
+    // Значения нет: field IS NULL
+    IsNull,
+
+    // Значение есть: field IS NOT NULL
+    IsNotNull,
+
+    // Поле-массив содержит хотя бы одно из значений
+    ContainsAny(Vec<FieldValue>),
+
+    // Поле-массив содержит все значения
+    ContainsAll(Vec<FieldValue>),
+
+    // Строка начинается с префикса
+    StartsWith(String),
+
+    // Строка заканчивается суффиксом
+    EndsWith(String),
+
+    // Строка содержит подстроку
+    ContainsSubstr(String),
+
+    // Строка соответствует регулярному выражению
+    #[cfg(feature = "regex")]
+    Regex(String),
+}
+
+
+// Ключ группировки по значению поля (GroupData::group_by_field)
+impl From<FieldValue> for String {
+    fn from(value: FieldValue) -> Self {
+        match value {
+            FieldValue::String(value) => value,
+            value => value.to_string(),
+        }
+    }
 }
 
 
@@ -476,6 +776,45 @@ impl FieldOperation {
         FieldOperation::Range(start.into(), end.into())
     }
 
+    pub fn is_null() -> Self {
+        FieldOperation::IsNull
+    }
+
+    pub fn is_not_null() -> Self {
+        FieldOperation::IsNotNull
+    }
+
+    pub fn contains_any<V>(values: Vec<V>) -> Self
+    where
+        V: Into<FieldValue>,
+    {
+        FieldOperation::ContainsAny(values.into_iter().map(|v| v.into()).collect())
+    }
+
+    pub fn contains_all<V>(values: Vec<V>) -> Self
+    where
+        V: Into<FieldValue>,
+    {
+        FieldOperation::ContainsAll(values.into_iter().map(|v| v.into()).collect())
+    }
+
+    pub fn starts_with(prefix: impl Into<String>) -> Self {
+        FieldOperation::StartsWith(prefix.into())
+    }
+
+    pub fn ends_with(suffix: impl Into<String>) -> Self {
+        FieldOperation::EndsWith(suffix.into())
+    }
+
+    pub fn contains_substr(substr: impl Into<String>) -> Self {
+        FieldOperation::ContainsSubstr(substr.into())
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        FieldOperation::Regex(pattern.into())
+    }
+
     #[inline(always)]
     pub fn evaluate(&self, value: &FieldValue) -> bool {
         match self {
@@ -498,6 +837,201 @@ impl FieldOperation {
             FieldOperation::Range(start, end) => {
                 value.gte(start) && value.lte(end)
             },
+            FieldOperation::IsNull => value.is_null(),
+            FieldOperation::IsNotNull => !value.is_null(),
+            // Скалярное значение - массив из одного элемента
+            FieldOperation::ContainsAny(targets) => targets.iter().any(|t| value.eq(t)),
+            FieldOperation::ContainsAll(targets) => targets.iter().all(|t| value.eq(t)),
+            // Строковые операции - только для строковых значений
+            FieldOperation::StartsWith(prefix) => matches!(value, FieldValue::String(v) if v.starts_with(prefix.as_str())),
+            FieldOperation::EndsWith(suffix) => matches!(value, FieldValue::String(v) if v.ends_with(suffix.as_str())),
+            FieldOperation::ContainsSubstr(substr) => matches!(value, FieldValue::String(v) if v.contains(substr.as_str())),
+            // Разовая проверка компилирует выражение; цепочки идут через compile_operations
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(pattern) => match value {
+                FieldValue::String(v) => regex::Regex::new(pattern).is_ok_and(|re| re.is_match(v)),
+                _ => false,
+            },
+        }
+    }
+
+    // Трехзначная логика: сравнение с null (None) - Unknown,
+    // IS NULL / IS NOT NULL всегда дают True или False
+    pub fn evaluate_nullable(&self, value: Option<&FieldValue>) -> Truth {
+        let value = value.filter(|value| !value.is_null());
+        match (self, value) {
+            (FieldOperation::IsNull, value) => Truth::from_bool(value.is_none()),
+            (FieldOperation::IsNotNull, value) => Truth::from_bool(value.is_some()),
+            (_, Some(value)) => Truth::from_bool(self.evaluate(value)),
+            (_, None) => Truth::Unknown,
+        }
+    }
+
+    // Цепочка операций в трехзначной логике, связки - как у filter_operations
+    pub fn evaluate_operations_nullable(
+        value: Option<&FieldValue>,
+        operations: &[(FieldOperation, Op)],
+    ) -> Truth {
+        let Some(((first, _), rest)) = operations.split_first() else {
+            return Truth::True;
+        };
+        rest.iter().fold(first.evaluate_nullable(value), |result, (operation, op)| {
+            result.combine(*op, operation.evaluate_nullable(value))
+        })
+    }
+
+    // Цепочка операций в двузначной логике (predicate path):
+    // результат начинается с true, каждая операция связывается своим Op
+    pub fn evaluate_operations(value: &FieldValue, operations: &[(FieldOperation, Op)]) -> bool {
+        let mut result = true;
+        for (operation, op) in operations {
+            let matches = operation.evaluate(value);
+            match op {
+                Op::And => {
+                    result = result && matches;
+                    if !result {
+                        return false;
+                    }
+                }
+                Op::Or => result = result || matches,
+                Op::AndNot => {
+                    result = result && !matches;
+                    if !result {
+                        return false;
+                    }
+                }
+                Op::Xor => result ^= matches,
+                Op::Invert => result = !result,
+            }
+        }
+        result
+    }
+
+    // Скомпилировать цепочку операций в closure с той же семантикой, что evaluate_operations.
+    // Для значений того же варианта, что и операнд операции, сравнение идет напрямую
+    // (без приведения типов); одиночные Eq / Range / In - отдельные быстрые пути.
+    pub fn compile_operations(operations: &[(FieldOperation, Op)]) -> CompiledOperations {
+        match operations {
+            [] => Arc::new(|_: &FieldValue| true),
+            [(operation, Op::And)] => operation.compile(),
+            [(operation, Op::AndNot)] => {
+                let compiled = operation.compile();
+                Arc::new(move |value: &FieldValue| !compiled(value))
+            }
+            _ => {
+                let compiled: Vec<(CompiledOperations, Op)> = operations
+                    .iter()
+                    .map(|(operation, op)| (operation.compile(), *op))
+                    .collect();
+                Arc::new(move |value: &FieldValue| {
+                    let mut result = true;
+                    for (matches, op) in &compiled {
+                        match op {
+                            Op::And => {
+                                if !(result && matches(value)) {
+                                    return false;
+                                }
+                            }
+                            Op::Or => result = result || matches(value),
+                            Op::AndNot => {
+                                if !result || matches(value) {
+                                    return false;
+                                }
+                            }
+                            Op::Xor => result ^= matches(value),
+                            Op::Invert => result = !result,
+                        }
+                    }
+                    result
+                })
+            }
+        }
+    }
+
+    fn compile(&self) -> CompiledOperations {
+        match self.clone() {
+            FieldOperation::Eq(target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_exact_variant(value, &target) { *value == target } else { value.eq(&target) }
+            }),
+            FieldOperation::NotEq(target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_exact_variant(value, &target) { *value != target } else { !value.eq(&target) }
+            }),
+            FieldOperation::Gt(target) if Self::is_ordered_variant(&target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_variant(value, &target) { *value > target } else { value.gt(&target) }
+            }),
+            FieldOperation::Gte(target) if Self::is_ordered_variant(&target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_variant(value, &target) { *value >= target } else { value.gte(&target) }
+            }),
+            FieldOperation::Lt(target) if Self::is_ordered_variant(&target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_variant(value, &target) { *value < target } else { value.lt(&target) }
+            }),
+            FieldOperation::Lte(target) if Self::is_ordered_variant(&target) => Arc::new(move |value: &FieldValue| {
+                if Self::same_variant(value, &target) { *value <= target } else { value.lte(&target) }
+            }),
+            FieldOperation::Range(start, end)
+                if Self::is_ordered_variant(&start) && Self::same_variant(&start, &end) =>
+            {
+                Arc::new(move |value: &FieldValue| {
+                    if Self::same_variant(value, &start) {
+                        start <= *value && *value <= end
+                    } else {
+                        value.gte(&start) && value.lte(&end)
+                    }
+                })
+            }
+            FieldOperation::In(targets) => Self::compile_in(targets, false),
+            FieldOperation::NotIn(targets) => Self::compile_in(targets, true),
+            // Выражение компилируется один раз; некорректное не совпадает ни с чем
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(pattern) => match regex::Regex::new(&pattern) {
+                Ok(re) => Arc::new(move |value: &FieldValue| matches!(value, FieldValue::String(v) if re.is_match(v))),
+                Err(_) => Arc::new(|_: &FieldValue| false),
+            },
+            operation => Arc::new(move |value: &FieldValue| operation.evaluate(value)),
+        }
+    }
+
+    // In / NotIn: однотипный список точных значений проверяется через hash set
+    fn compile_in(targets: Vec<FieldValue>, negate: bool) -> CompiledOperations {
+        let set_applicable = targets.first().is_some_and(|first| {
+            targets.iter().all(|target| Self::same_exact_variant(first, target))
+        });
+        if !set_applicable {
+            return Arc::new(move |value: &FieldValue| targets.iter().any(|target| value.eq(target)) != negate);
+        }
+        let set: ahash::AHashSet<FieldValue> = targets.iter().cloned().collect();
+        Arc::new(move |value: &FieldValue| {
+            let found = if Self::same_variant(value, &targets[0]) {
+                set.contains(value)
+            } else {
+                targets.iter().any(|target| value.eq(target))
+            };
+            found != negate
+        })
+    }
+
+    #[inline(always)]
+    fn same_variant(value: &FieldValue, target: &FieldValue) -> bool {
+        std::mem::discriminant(value) == std::mem::discriminant(target)
+    }
+
+    // Тот же вариант и равенство без приведения совпадает с FieldValue::eq
+    // (float сравниваются через decimal - для них быстрого пути нет)
+    #[inline(always)]
+    fn same_exact_variant(value: &FieldValue, target: &FieldValue) -> bool {
+        Self::same_variant(value, target) && !matches!(target.type_family(), TypeFamily::Float | TypeFamily::Null)
+    }
+
+    // Порядок внутри варианта совпадает с FieldValue::gt (bool и float - нет)
+    fn is_ordered_variant(target: &FieldValue) -> bool {
+        match target.type_family() {
+            TypeFamily::Integer | TypeFamily::Decimal | TypeFamily::String => true,
+            #[cfg(feature = "datetime")]
+            TypeFamily::DateTime => true,
+            #[cfg(feature = "uuid")]
+            TypeFamily::Uuid => true,
+            TypeFamily::Bytes => true,
+            _ => false,
         }
     }
 
@@ -505,7 +1039,9 @@ impl FieldOperation {
     pub fn is_equality_query(&self) -> bool {
         matches!(self, 
             FieldOperation::Eq(_) |
-            FieldOperation::In(_)
+            FieldOperation::In(_) |
+            FieldOperation::ContainsAny(_) |
+            FieldOperation::ContainsAll(_)
         )
     }
 
@@ -524,10 +1060,60 @@ impl FieldOperation {
             FieldOperation::Gte(_) |
             FieldOperation::Lt(_) |
             FieldOperation::Lte(_) |
-            FieldOperation::Range(_, _)
+            FieldOperation::Range(_, _) |
+            // Префикс - диапазон отсортированных ключей
+            FieldOperation::StartsWith(_)
         )
     }
 
+    // Является ли операция строковым сопоставлением
+    pub fn is_string_query(&self) -> bool {
+        match self {
+            FieldOperation::StartsWith(_) |
+            FieldOperation::EndsWith(_) |
+            FieldOperation::ContainsSubstr(_) => true,
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(_) => true,
+            _ => false,
+        }
+    }
+
+    // Проверка на наличие значения
+    pub fn is_null_query(&self) -> bool {
+        matches!(self, FieldOperation::IsNull | FieldOperation::IsNotNull)
+    }
+
+}
+
+// Текстовое представление значения (экспорт, сообщения)
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::U128(v) => write!(f, "{}", v),
+            FieldValue::I128(v) => write!(f, "{}", v),
+            FieldValue::U64(v) => write!(f, "{}", v),
+            FieldValue::I64(v) => write!(f, "{}", v),
+            FieldValue::U32(v) => write!(f, "{}", v),
+            FieldValue::I32(v) => write!(f, "{}", v),
+            FieldValue::U16(v) => write!(f, "{}", v),
+            FieldValue::I16(v) => write!(f, "{}", v),
+            FieldValue::U8(v) => write!(f, "{}", v),
+            FieldValue::I8(v) => write!(f, "{}", v),
+            FieldValue::Usize(v) => write!(f, "{}", v),
+            FieldValue::Isize(v) => write!(f, "{}", v),
+            FieldValue::F64(v) => write!(f, "{}", v),
+            FieldValue::F32(v) => write!(f, "{}", v),
+            FieldValue::Decimal(v) => write!(f, "{}", v),
+            FieldValue::String(v) => write!(f, "{}", v),
+            FieldValue::Bool(v) => write!(f, "{}", v),
+            #[cfg(feature = "datetime")]
+            FieldValue::DateTime(v) => write!(f, "{}", v.to_rfc3339()),
+            #[cfg(feature = "uuid")]
+            FieldValue::Uuid(v) => write!(f, "{}", v.hyphenated()),
+            FieldValue::Bytes(v) => write!(f, "{}", v),
+            FieldValue::Null => write!(f, "null"),
+        }
+    }
 }
 
 impl Display for FieldOperation {
@@ -542,12 +1128,61 @@ impl Display for FieldOperation {
             FieldOperation::In(values) => write!(f, "IN ({:?})", values),
             FieldOperation::NotIn(values) => write!(f, "NOT IN ({:?})", values),
             FieldOperation::Range(start, end) => write!(f, "BETWEEN {:?} AND {:?}", start, end),
+            FieldOperation::IsNull => write!(f, "IS NULL"),
+            FieldOperation::IsNotNull => write!(f, "IS NOT NULL"),
+            FieldOperation::ContainsAny(values) => write!(f, "CONTAINS ANY ({:?})", values),
+            FieldOperation::ContainsAll(values) => write!(f, "CONTAINS ALL ({:?})", values),
+            FieldOperation::StartsWith(prefix) => write!(f, "STARTS WITH {:?}", prefix),
+            FieldOperation::EndsWith(suffix) => write!(f, "ENDS WITH {:?}", suffix),
+            FieldOperation::ContainsSubstr(substr) => write!(f, "CONTAINS {:?}", substr),
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(pattern) => write!(f, "MATCHES {:?}", pattern),
+        }
+    }
+}
+
+// Сравнение двух полей одной строки: left <op> right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl CmpOp {
+    #[inline(always)]
+    pub fn evaluate(&self, left: &FieldValue, right: &FieldValue) -> bool {
+        match self {
+            CmpOp::Eq => left.eq(right),
+            CmpOp::NotEq => !left.eq(right),
+            CmpOp::Gt => left.gt(right),
+            CmpOp::Gte => left.gte(right),
+            CmpOp::Lt => left.lt(right),
+            CmpOp::Lte => left.lte(right),
+        }
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CmpOp::Eq => write!(f, "=="),
+            CmpOp::NotEq => write!(f, "!="),
+            CmpOp::Gt => write!(f, ">"),
+            CmpOp::Gte => write!(f, ">="),
+            CmpOp::Lt => write!(f, "<"),
+            CmpOp::Lte => write!(f, "<="),
         }
     }
 }
 
 // Анализитор выборки через Index
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IndexAnalizer {
     Excellent,
     Good,
@@ -573,183 +1208,912 @@ impl Display for IndexAnalizer {
 }
 
 
-// IndexField<V> - типизированный индекс
+/// Способ хранения значений field индекса
+///
+/// `Hash` - только хеш-таблица значение -> bitmap, без отсортированных позиций:
+/// построение быстрее и меньше памяти, но range / сортировка через индекс
+/// недоступны (планировщик выбирает predicate path).
+///
+/// # Example
+///
+/// data.create_field_index_with_kind("session_id", |e: &Event| e.session_id.clone(), IndexKind::Hash)?;
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndexKind {
+    #[default]
+    Sorted,
+    Hash,
+}
 
-pub struct IndexField<V>
-where 
-    V: Eq + Hash + Clone + Send + Sync + PartialOrd + Ord
-{
-    values: BTreeMap<V, Index>,
-    size: usize,
-    sorted_values: Option<Vec<(V, usize)>>,
-    cardinality_ratio: f64,
-    unique_count: usize, // Количество уникальных значений
-    index_quality: f64,
-    index_skewed: bool,
-    index_analyzer: IndexAnalizer,
+// Значение -> Index: упорядоченная карта или хеш-таблица (IndexKind)
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "V: serde::Serialize + Eq + Hash",
+    deserialize = "V: serde::Deserialize<'de> + Eq + Hash + Ord",
+)))]
+enum ValueMap<V> {
+    Sorted(BTreeMap<V, Index>),
+    Hash(AHashMap<V, Index>),
 }
 
-impl<V> IndexField<V>  
-where 
-    V: Eq + Hash + Clone + Send + Sync + PartialOrd + Ord + 'static
+// Итератор по одному из вариантов ValueMap
+enum ValueMapIter<S, H> {
+    Sorted(S),
+    Hash(H),
+}
+
+impl<S, H> Iterator for ValueMapIter<S, H>
+where
+    S: Iterator,
+    H: Iterator<Item = S::Item>,
 {
-    // Конструкторы
-    
-    pub fn new(
-        values: BTreeMap<V, Index>,
-        size: usize,
-        sorted_values: Option<Vec<(V, usize)>>,
-        cardinality_ratio: f64,
-        unique_count: usize,
-        index_quality: f64,
-        index_skewed: bool,
-        index_analyzer: IndexAnalizer,
-    ) -> Self {
-        Self { 
-            values, 
-            size,
-            sorted_values,
-            cardinality_ratio,
-            unique_count,
-            index_quality,
-            index_skewed,
-            index_analyzer,
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Sorted(iter) => iter.next(),
+            Self::Hash(iter) => iter.next(),
         }
     }
+}
 
-    // Построить индекс из данных
-    pub fn build<T, F>(items: &[Arc<T>], extractor: F) -> Self
-    where
-        T: Send + Sync,
-        F: Fn(&T) -> V + Send + Sync,
-    {
-        let size = items.len();
-        if size == 0 {
-            return Default::default()
+impl<V> ValueMap<V>
+where
+    V: Eq + Hash + Ord,
+{
+    fn kind(&self) -> IndexKind {
+        match self {
+            Self::Sorted(_) => IndexKind::Sorted,
+            Self::Hash(_) => IndexKind::Hash,
         }
+    }
 
-        // Извлечение значений (параллельно для больших наборов)
-        let values: Vec<(usize, V)> = if items.len() > 10_000 {
-            items
-                .par_iter()
-                .enumerate()
-                .map(|(id, item)| (id, extractor(item)))
-                .collect()    
-        } else {
-            items 
-                .iter()
-                .enumerate()
-                .map(|(id, item)| (id, extractor(item)))
-                .collect()
-        };
-
-        let mut sorted_values: Vec<(V, usize)> = values.iter()
-            .map(|(idx, val)| (val.clone(), *idx))
-            .collect();
-        sorted_values.sort_by(|a, b| a.0.cmp(&b.0));
-
-        // Группировка индексов по значениям
-        let mut values_indices = BTreeMap::<V, Vec<usize>>::new();
-        for (id, value) in values {
-            values_indices.entry(value).or_default().push(id);
+    fn len(&self) -> usize {
+        match self {
+            Self::Sorted(map) => map.len(),
+            Self::Hash(map) => map.len(),
         }
+    }
 
-        // вычесляем cardinality ratio
-        let unique_count = values_indices.len();
-        let cardinality_ratio = if size > 0 {
-            unique_count as f64 / size as f64
+    fn get(&self, value: &V) -> Option<&Index> {
+        match self {
+            Self::Sorted(map) => map.get(value),
+            Self::Hash(map) => map.get(value),
+        }
+    }
+
+    fn contains_key(&self, value: &V) -> bool {
+        self.get(value).is_some()
+    }
+
+    fn insert_position(&mut self, value: V, position: usize) {
+        match self {
+            Self::Sorted(map) => map.entry(value).or_default().insert(position),
+            Self::Hash(map) => map.entry(value).or_default().insert(position),
+        }
+    }
+
+    fn insert_bitmap(&mut self, value: V, index: Index) {
+        match self {
+            Self::Sorted(map) => map.insert(value, index),
+            Self::Hash(map) => map.insert(value, index),
+        };
+    }
+
+    fn remove(&mut self, value: &V) -> Option<Index> {
+        match self {
+            Self::Sorted(map) => map.remove(value),
+            Self::Hash(map) => map.remove(value),
+        }
+    }
+
+    // Сбросить позиции строк; значения без строк удаляются из карты
+    fn remove_positions(&mut self, rows: &RoaringBitmap) {
+        let keep = |index: &mut Index| {
+            index.remove_all(rows);
+            !index.is_empty()
+        };
+        match self {
+            Self::Sorted(map) => map.retain(|_, index| keep(index)),
+            Self::Hash(map) => map.retain(|_, index| keep(index)),
+        }
+    }
+
+    fn values_mut(&mut self) -> Box<dyn Iterator<Item = &mut Index> + '_> {
+        match self {
+            Self::Sorted(map) => Box::new(map.values_mut()),
+            Self::Hash(map) => Box::new(map.values_mut()),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&V, &Index)> {
+        match self {
+            Self::Sorted(map) => ValueMapIter::Sorted(map.iter()),
+            Self::Hash(map) => ValueMapIter::Hash(map.iter()),
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Index> {
+        self.iter().map(|(_, index)| index)
+    }
+
+    // Пары по возрастанию значений: hash карта сортируется при вызове
+    fn iter_sorted(&self) -> impl Iterator<Item = (&V, &Index)> {
+        match self {
+            Self::Sorted(map) => ValueMapIter::Sorted(map.iter()),
+            Self::Hash(map) => {
+                let mut entries: Vec<(&V, &Index)> = map.iter().collect();
+                entries.sort_unstable_by(|left, right| left.0.cmp(right.0));
+                ValueMapIter::Hash(entries.into_iter())
+            }
+        }
+    }
+
+    // Диапазон значений - только для упорядоченной карты
+    fn range(&self, start: Bound<&V>, end: Bound<&V>) -> Option<btree_map::Range<'_, V, Index>> {
+        match self {
+            Self::Sorted(map) => Some(map.range((start, end))),
+            Self::Hash(_) => None,
+        }
+    }
+}
+
+// Дописанных позиций sorted_values не больше 1/SORTED_PENDING_RATIO от основных -
+// иначе они сливаются с основными массивами (amortized O(1) на строку push)
+const SORTED_PENDING_RATIO: usize = 8;
+
+// Позиции строк в порядке значений для range запросов.
+// Каждое различное значение хранится один раз: строки values[i] лежат
+// в positions[offsets[i]..offsets[i + 1]] (по возрастанию позиции).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(deserialize = "V: serde::Deserialize<'de> + Ord")))]
+pub struct SortedPositions<V> {
+    values: Vec<V>,
+    offsets: Vec<u32>,
+    positions: Vec<u32>,
+    // Позиции, дописанные после построения (push), по значениям
+    pending: BTreeMap<V, RoaringBitmap>,
+    pending_len: usize,
+}
+
+impl<V: Ord> SortedPositions<V> {
+    // groups - значения по возрастанию вместе с позициями их строк
+    fn from_groups<I>(groups: impl Iterator<Item = (V, I)>, size: usize) -> Self
+    where
+        I: Iterator<Item = u32>,
+    {
+        let mut values = Vec::new();
+        let mut offsets = vec![0u32];
+        let mut positions = Vec::with_capacity(size);
+        for (value, group) in groups {
+            values.push(value);
+            positions.extend(group);
+            offsets.push(positions.len() as u32);
+        }
+        Self { values, offsets, positions, pending: BTreeMap::new(), pending_len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len() + self.pending_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Байты значений, смещений и позиций (без heap-части самих значений)
+    fn memory_size(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<V>()
+            + (self.offsets.capacity() + self.positions.capacity()) * std::mem::size_of::<u32>()
+            + self.pending.len() * std::mem::size_of::<V>()
+            + self.pending.values().map(|rows| rows.serialized_size()).sum::<usize>()
+    }
+
+    // Дописать строки в конец (позиции больше всех имеющихся): без перестройки
+    // массивов, пока дописанных немного
+    fn append(&mut self, rows: impl IntoIterator<Item = (V, u32)>) {
+        for (value, position) in rows {
+            self.pending.entry(value).or_default().insert(position);
+            self.pending_len += 1;
+        }
+        if self.pending_len * SORTED_PENDING_RATIO > self.positions.len() {
+            self.merge_pending();
+        }
+    }
+
+    // Слить дописанные позиции с основными массивами за один линейный проход:
+    // позиции значения после push больше его прежних позиций
+    fn merge_pending(&mut self) {
+        let old_values = std::mem::take(&mut self.values);
+        let old_offsets = std::mem::take(&mut self.offsets);
+        let old_positions = std::mem::take(&mut self.positions);
+        let mut pending = std::mem::take(&mut self.pending).into_iter().peekable();
+        self.values.reserve(old_values.len() + pending.len());
+        self.offsets.reserve(old_offsets.len() + pending.len());
+        self.offsets.push(0);
+        self.positions.reserve(old_positions.len() + self.pending_len);
+        self.pending_len = 0;
+        for (i, value) in old_values.into_iter().enumerate() {
+            // Новые значения меньше текущего
+            while let Some((smaller, rows)) = pending.next_if(|(pending_value, _)| pending_value < &value) {
+                self.push_group(smaller, rows.iter());
+            }
+            let own = old_positions[old_offsets[i] as usize..old_offsets[i + 1] as usize].iter().copied();
+            match pending.next_if(|(pending_value, _)| pending_value == &value) {
+                Some((_, rows)) => self.push_group(value, own.chain(rows.iter())),
+                None => self.push_group(value, own),
+            }
+        }
+        for (value, rows) in pending {
+            self.push_group(value, rows.iter());
+        }
+    }
+
+    fn push_group(&mut self, value: V, rows: impl Iterator<Item = u32>) {
+        self.values.push(value);
+        self.positions.extend(rows);
+        self.offsets.push(self.positions.len() as u32);
+    }
+
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    // Позиции строк со значениями в диапазоне
+    fn range(&self, start: Bound<&V>, end: Bound<&V>) -> RoaringBitmap {
+        // partition_point по различным значениям
+        let start_idx = match start {
+            Bound::Included(val) => self.values.partition_point(|v| v < val),
+            Bound::Excluded(val) => self.values.partition_point(|v| v <= val),
+            Bound::Unbounded => 0,
+        };
+        let end_idx = match end {
+            Bound::Included(val) => self.values.partition_point(|v| v <= val),
+            Bound::Excluded(val) => self.values.partition_point(|v| v < val),
+            Bound::Unbounded => self.values.len(),
+        };
+        let mut result: RoaringBitmap = if start_idx < end_idx {
+            let from = self.offsets[start_idx] as usize;
+            let to = self.offsets[end_idx] as usize;
+            self.positions[from..to].iter().copied().collect()
         } else {
-            0.0
+            RoaringBitmap::new()
+        };
+        if !self.pending.is_empty() && Self::is_valid_range(start, end) {
+            for (_, rows) in self.pending.range((start, end)) {
+                result |= rows;
+            }
+        }
+        result
+    }
+
+    // BTreeMap::range паникует при start > end (и start == end, если обе границы Excluded)
+    fn is_valid_range(start: Bound<&V>, end: Bound<&V>) -> bool {
+        match (start, end) {
+            (Bound::Excluded(start), Bound::Excluded(end)) => start < end,
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) => start <= end,
+            _ => true,
+        }
+    }
+}
+
+// Селективность цепочки операций из селективностей отдельных операций
+pub fn combine_selectivity<F>(operations: &[(FieldOperation, Op)], estimate: F) -> f64
+where
+    F: Fn(&FieldOperation) -> f64,
+{
+    if operations.is_empty() {
+        return 1.0;
+    }
+    
+    let mut result_selectivity = estimate(&operations[0].0);
+    for (operation, op_type) in &operations[1..] {
+        let op_selectivity = estimate(operation);
+        
+        result_selectivity = match op_type {
+            Op::And => {
+                // P(A AND B) = P(A) * P(B) (если независимы)
+                result_selectivity * op_selectivity
+            }
+            Op::Or => {
+                // P(A) + P(B) если независимы
+                (result_selectivity + op_selectivity).min(1.0)
+            }
+            Op::AndNot => {
+                // P(A AND NOT B) = P(A) * (1 - P(B))
+                result_selectivity * (1.0 - op_selectivity)
+            }
+            Op::Xor => {
+                // P(A XOR B) = P(A) + P(B) - 2*P(A AND B)
+                // Упрощение для независимых
+                ((result_selectivity + op_selectivity) / 2.0).min(1.0)
+            }
+            Op::Invert => {
+                // NOT A
+                1.0 - result_selectivity
+            }
+        };
+    }
+    result_selectivity.clamp(0.0, 1.0)
+}
+
+// IndexField<V> - типизированный индекс
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexField<V>
+where 
+    V: Eq + Hash + Clone + Send + Sync + PartialOrd + Ord
+{
+    values: ValueMap<V>,
+    size: usize,
+    sorted_values: Option<SortedPositions<V>>,
+    cardinality_ratio: f64,
+    unique_count: usize, // Количество уникальных значений
+    index_quality: f64,
+    index_skewed: bool,
+    index_analyzer: IndexAnalizer,
+    // Позиции строк без значения (nullable поле) - не входят ни в один bitmap значения
+    nulls: RoaringBitmap,
+    // Строка может входить в bitmap-ы нескольких значений (поле-массив)
+    multi_valued: bool,
+    // Sparse индекс: значение по умолчанию без своего bitmap-а
+    sparse: Option<Box<SparseDefault<V>>>,
+}
+
+// Значение по умолчанию sparse индекса: его строки - все, кроме nulls,
+// удаленных и строк остальных значений. Bitmap вычисляется при запросе
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct SparseDefault<V> {
+    value: V,
+    // Удаленные строки (remove_positions) - иначе попали бы в дополнение
+    removed: RoaringBitmap,
+    // Материализованный bitmap для итераторов и агрегаций (value_bitmaps и т.п.)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cache: OnceLock<Index>,
+}
+
+impl<V> IndexField<V>  
+where 
+    V: Eq + Hash + Clone + Send + Sync + PartialOrd + Ord + 'static
+{
+    // Конструкторы
+    
+    pub fn new(
+        values: BTreeMap<V, Index>,
+        size: usize,
+        sorted_values: Option<SortedPositions<V>>,
+        cardinality_ratio: f64,
+        unique_count: usize,
+        index_quality: f64,
+        index_skewed: bool,
+        index_analyzer: IndexAnalizer,
+    ) -> Self {
+        Self { 
+            values: ValueMap::Sorted(values), 
+            size,
+            sorted_values,
+            cardinality_ratio,
+            unique_count,
+            index_quality,
+            index_skewed,
+            index_analyzer,
+            nulls: RoaringBitmap::new(),
+            multi_valued: false,
+            sparse: None,
+        }
+    }
+
+    // Построить индекс из данных
+    pub fn build<T, F>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> V + Send + Sync,
+    {
+        Self::build_with_memory_target(items, extractor, DEFAULT_BUILD_MEMORY_TARGET)
+    }
+
+    // Построить индекс с ограничением промежуточной памяти.
+    // Данные обрабатываются раундами по memory_target: в раунде каждый поток собирает
+    // BTreeMap<V, RoaringBitmap> своего чанка, после раунда карты вливаются в итоговую
+    // и освобождаются. Сверх самого индекса живут только карты одного раунда;
+    // промежуточный Vec<(idx, V)> не создается, sorted_values строится из итоговой карты.
+    pub fn build_with_memory_target<T, F>(items: &[Arc<T>], extractor: F, memory_target: usize) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> V + Send + Sync,
+    {
+        Self::build_optional(items, |item: &T| Some(extractor(item)), memory_target)
+    }
+
+    // Построить индекс по nullable полю: строки с None попадают в nulls
+    pub fn build_nullable<T, F>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> Option<V> + Send + Sync,
+    {
+        let mut index = Self::build_optional(items, extractor, DEFAULT_BUILD_MEMORY_TARGET);
+        index.fill_nulls();
+        index
+    }
+
+    // Построить индекс по полю-массиву: строка попадает в bitmap каждого своего значения,
+    // строки с пустым списком - в nulls
+    pub fn build_multi<T, F, I>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> I + Send + Sync,
+        I: IntoIterator<Item = V>,
+    {
+        let mut index = Self::build_optional(items, extractor, DEFAULT_BUILD_MEMORY_TARGET);
+        index.fill_nulls();
+        index.multi_valued = true;
+        index
+    }
+
+    fn fill_nulls(&mut self) {
+        let mut nulls = RoaringBitmap::new();
+        nulls.insert_range(0..self.size as u32);
+        for idx in self.values.values() {
+            nulls -= idx.bitmap();
+        }
+        self.nulls = nulls;
+    }
+
+    // Дописать строки в конец индекса: values[i] - строка на позиции len() + i,
+    // None - строка без значения. Позиции добавляются в bitmap-ы значений и в
+    // хвост sorted_values на месте, статистика пересчитывается по карте значений.
+    pub fn push_values<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = Option<V>>,
+    {
+        let mut sorted_rows: Vec<(V, u32)> = Vec::new();
+        for value in values {
+            let position = self.size as u32;
+            match value {
+                // Значение по умолчанию sparse индекса не хранится
+                Some(value) if self.is_sparse_default(&value) => {}
+                Some(value) => {
+                    if self.sorted_values.is_some() {
+                        sorted_rows.push((value.clone(), position));
+                    }
+                    self.values.insert_position(value, self.size);
+                }
+                None => {
+                    self.nulls.insert(position);
+                }
+            }
+            self.size += 1;
+        }
+        let size = self.size;
+        self.values.values_mut().for_each(|index| index.set_total_size(size));
+        if let Some(sorted) = &mut self.sorted_values {
+            sorted.append(sorted_rows);
+        }
+        self.refresh_stats();
+    }
+
+    // Удалить строки из индекса: позиции остаются (строки других позиций
+    // не сдвигаются), значения без строк исчезают, статистика пересчитывается
+    pub fn remove_positions(&mut self, rows: &RoaringBitmap) {
+        self.values.remove_positions(rows);
+        self.nulls -= rows;
+        if let Some(sparse) = &mut self.sparse {
+            sparse.removed |= rows;
+        }
+        self.refresh();
+    }
+
+    // Перевести индекс в sparse: bitmap значения по умолчанию и sorted_values
+    // не хранятся, строки default вычисляются как дополнение остальных значений.
+    // Поле-массив остается как есть (строка может иметь несколько значений)
+    pub fn into_sparse(mut self, default: V) -> Self {
+        self.set_sparse_default(default);
+        self
+    }
+
+    pub fn set_sparse_default(&mut self, default: V) {
+        if self.multi_valued {
+            return;
+        }
+        let mut removed = RoaringBitmap::new();
+        if let Some(sparse) = self.sparse.take() {
+            // Прежнее значение по умолчанию возвращается в карту
+            let rows = self.default_rows_of(&sparse);
+            if !rows.is_empty() {
+                self.values.insert_bitmap(sparse.value, Index::with_bitmap(rows, self.size));
+            }
+            removed = sparse.removed;
+        }
+        self.values.remove(&default);
+        self.sorted_values = None;
+        self.sparse = Some(Box::new(SparseDefault {
+            value: default,
+            removed,
+            cache: OnceLock::new(),
+        }));
+        self.refresh();
+    }
+
+    pub fn sparse_default(&self) -> Option<&V> {
+        self.sparse.as_ref().map(|sparse| &sparse.value)
+    }
+
+    fn is_sparse_default(&self, value: &V) -> bool {
+        self.sparse.as_ref().is_some_and(|sparse| &sparse.value == value)
+    }
+
+    // Строки значения по умолчанию: все - nulls - удаленные - остальные значения
+    fn default_rows(&self) -> Option<RoaringBitmap> {
+        self.sparse.as_ref().map(|sparse| self.default_rows_of(sparse))
+    }
+
+    fn default_rows_of(&self, sparse: &SparseDefault<V>) -> RoaringBitmap {
+        if let Some(index) = sparse.cache.get() {
+            return (*index.bitmap()).clone();
+        }
+        let mut rows = RoaringBitmap::new();
+        rows.insert_range(0..self.size as u32);
+        rows -= &self.nulls;
+        rows -= &sparse.removed;
+        for index in self.values.values() {
+            rows -= index.bitmap();
+        }
+        rows
+    }
+
+    // Значение по умолчанию с материализованным bitmap-ом (None - строк нет)
+    fn default_entry(&self) -> Option<(&V, &Index)> {
+        let sparse = self.sparse.as_ref()?;
+        let index = sparse.cache.get_or_init(|| {
+            Index::with_bitmap(self.default_rows().unwrap_or_default(), self.size)
+        });
+        (!index.bitmap().is_empty()).then_some((&sparse.value, index))
+    }
+
+    // Значения с bitmap-ами по возрастанию, включая значение по умолчанию
+    fn sorted_entries(&self) -> Vec<(&V, &Index)> {
+        let mut entries: Vec<(&V, &Index)> = self.values.iter_sorted().collect();
+        if let Some((value, index)) = self.default_entry() {
+            let position = entries.partition_point(|(other, _)| *other < value);
+            entries.insert(position, (value, index));
+        }
+        entries
+    }
+
+    // Строки значения без материализации bitmap-а по умолчанию
+    fn value_rows(&self, value: &V) -> Option<Cow<'_, RoaringBitmap>> {
+        if self.is_sparse_default(value) {
+            return self.default_rows().map(Cow::Owned);
+        }
+        self.values.get(value).map(|index| Cow::Borrowed(index.bitmap()))
+    }
+
+    // Индекс с другим типом значений (IntoFieldValue): bitmap-ы значений,
+    // которые отображаются в одно, объединяются; sorted_values строится заново
+    pub fn map_values<W, M>(self, map: M) -> IndexField<W>
+    where
+        W: Eq + Hash + Clone + Send + Sync + PartialOrd + Ord + 'static,
+        M: Fn(V) -> W,
+    {
+        let values = match self.values {
+            ValueMap::Sorted(values) => {
+                let mut mapped: BTreeMap<W, Index> = BTreeMap::new();
+                for (value, index) in values {
+                    Self::merge_mapped(mapped.entry(map(value)).or_default(), index);
+                }
+                ValueMap::Sorted(mapped)
+            }
+            ValueMap::Hash(values) => {
+                let mut mapped: AHashMap<W, Index> = AHashMap::with_capacity(values.len());
+                for (value, index) in values {
+                    Self::merge_mapped(mapped.entry(map(value)).or_default(), index);
+                }
+                ValueMap::Hash(mapped)
+            }
+        };
+        let mut index = IndexField::from_parts(values, self.size, None, 0);
+        index.nulls = self.nulls;
+        index.multi_valued = self.multi_valued;
+        index.sparse = self.sparse.map(|sparse| Box::new(SparseDefault {
+            value: map(sparse.value),
+            removed: sparse.removed,
+            cache: OnceLock::new(),
+        }));
+        index.refresh();
+        if self.sorted_values.is_some() {
+            index.sorted_values = Some(index.sorted_positions());
+        }
+        index
+    }
+
+    fn merge_mapped(target: &mut Index, index: Index) {
+        if target.is_empty() {
+            *target = index;
+        } else {
+            *target = Index::with_bitmap(target.bitmap() | index.bitmap(), index.total_size());
+        }
+    }
+
+    fn sorted_positions(&self) -> SortedPositions<V> {
+        SortedPositions::from_groups(
+            self.values
+                .iter_sorted()
+                .map(|(value, index)| (value.clone(), index.bitmap().iter())),
+            self.size,
+        )
+    }
+
+    // sorted_values и статистика по текущим bitmap-ам
+    fn refresh(&mut self) {
+        if self.sorted_values.is_some() {
+            self.sorted_values = Some(self.sorted_positions());
+        }
+        self.refresh_stats();
+    }
+
+    // Статистика по текущим bitmap-ам (sorted_values не перестраивается)
+    fn refresh_stats(&mut self) {
+        let size = self.size;
+        if let Some(sparse) = &mut self.sparse {
+            sparse.cache = OnceLock::new();
+        }
+        let default_count = self.default_rows().map_or(0, |rows| rows.len() as usize);
+        let max_count = self.values.values().map(|index| index.len()).max().unwrap_or(0).max(default_count);
+        self.unique_count = self.values.len() + usize::from(default_count > 0);
+        self.cardinality_ratio = if size > 0 { self.unique_count as f64 / size as f64 } else { 0.0 };
+        self.index_quality = build_index_quantity(size, self.unique_count, max_count);
+        self.index_skewed = build_index_skewed(size, max_count);
+        self.index_analyzer = build_index_analyzier(self.index_quality, self.cardinality_ratio);
+    }
+
+    // extractor отдает значения строки: Option - ноль или одно, итератор - сколько угодно
+    fn build_optional<T, F, I>(items: &[Arc<T>], extractor: F, memory_target: usize) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> I + Send + Sync,
+        I: IntoIterator<Item = V>,
+    {
+        let size = items.len();
+        if size == 0 {
+            return Default::default()
+        }
+
+        // Размер чанка: на каждый поток приходится своя доля бюджета
+        let entry_size = std::mem::size_of::<(V, u32)>().max(1);
+        let threads = rayon::current_num_threads().max(1);
+        let chunk_len = (memory_target / threads / entry_size).max(MIN_BUILD_CHUNK);
+        let round_len = chunk_len * threads;
+
+        let chunk_map = |offset: usize, chunk: &[Arc<T>]| {
+            let mut map: BTreeMap<V, RoaringBitmap> = BTreeMap::new();
+            for (pos, item) in chunk.iter().enumerate() {
+                for value in extractor(item) {
+                    map.entry(value)
+                        .or_default()
+                        .insert((offset + pos) as u32);
+                }
+            }
+            map
         };
 
-        // Вычисляем min/max
-        let (_min_count, max_count) = values_indices.values()
-            .map(|v| v.len())
-            .fold((usize::MAX, 0), |(min, max), count| {
-                (min.min(count), max.max(count))
+        // Группировка индексов по значениям (параллельно для больших наборов)
+        let mut values_indices: BTreeMap<V, RoaringBitmap> = BTreeMap::new();
+        for (round_id, round) in items.chunks(round_len).enumerate() {
+            let offset = round_id * round_len;
+            let maps: Vec<BTreeMap<V, RoaringBitmap>> = if size > 10_000 {
+                round
+                    .par_chunks(chunk_len)
+                    .enumerate()
+                    .map(|(chunk_id, chunk)| chunk_map(offset + chunk_id * chunk_len, chunk))
+                    .collect()
+            } else {
+                vec![chunk_map(offset, round)]
+            };
+            for map in maps {
+                values_indices = Self::merge_value_maps(values_indices, map);
             }
+        }
+        Self::from_value_map(values_indices, size)
+    }
+
+    // Собрать индекс из готовой карты значение -> bitmap позиций
+    // (общий хвост build и пакетного построения нескольких индексов)
+    pub(crate) fn from_value_map(values_indices: BTreeMap<V, RoaringBitmap>, size: usize) -> Self {
+        if size == 0 {
+            return Default::default()
+        }
+
+        // Вычисляем max
+        let max_count = values_indices.values()
+            .map(|bitmap| bitmap.len() as usize)
+            .max()
+            .unwrap_or(0);
+
+        // sorted_values: значения по возрастанию, внутри значения - индексы по возрастанию
+        let sorted_values = SortedPositions::from_groups(
+            values_indices.iter().map(|(value, bitmap)| (value.clone(), bitmap.iter())),
+            size,
         );
 
         // Создание BitIndex для каждого значения
         let indexes: BTreeMap<V, Index> = if values_indices.len() > 100 {
             values_indices
                 .into_par_iter()
-                .map(|(value, indices)| {
-                    let bitmap: RoaringBitmap = indices.iter().map(|&i| i as u32).collect();
-                    let bit_index = Index::with_bitmap(bitmap, size);
-                    (value, bit_index)    
-                })
+                .map(|(value, bitmap)| (value, Index::with_bitmap(bitmap, size)))
                 .collect()
         } else {
             values_indices
                 .into_iter()
-                .map(|(value, indices)| {
-                    let bitmap: RoaringBitmap = indices.iter().map(|&i| i as u32).collect();
-                    let bit_index = Index::with_bitmap(bitmap, size);
-                    (value, bit_index) 
-                })
+                .map(|(value, bitmap)| (value, Index::with_bitmap(bitmap, size)))
                 .collect()
         };
-        let index_quality = Self::build_index_quantity(size, unique_count, max_count);
-        let index_skewed = Self::build_index_skewed(size, max_count);
-        let index_analyzer = Self::build_index_analyzier(index_quality, cardinality_ratio);
+        Self::from_parts(ValueMap::Sorted(indexes), size, Some(sorted_values), max_count)
+    }
+
+    // Построить hash индекс (IndexKind::Hash): только карта значение -> bitmap,
+    // без BTreeMap и sorted_values
+    pub fn build_hash<T, F>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> V + Send + Sync,
+    {
+        let size = items.len();
+        let entry_size = std::mem::size_of::<(V, u32)>().max(1);
+        let threads = rayon::current_num_threads().max(1);
+        let chunk_len = (DEFAULT_BUILD_MEMORY_TARGET / threads / entry_size).max(MIN_BUILD_CHUNK);
+
+        let fold_chunk = |mut map: AHashMap<V, RoaringBitmap>, (chunk_id, chunk): (usize, &[Arc<T>])| {
+            let offset = chunk_id * chunk_len;
+            for (pos, item) in chunk.iter().enumerate() {
+                map.entry(extractor(item))
+                    .or_default()
+                    .insert((offset + pos) as u32);
+            }
+            map
+        };
+        let merge = |mut left: AHashMap<V, RoaringBitmap>, mut right: AHashMap<V, RoaringBitmap>| {
+            if left.len() < right.len() {
+                std::mem::swap(&mut left, &mut right);
+            }
+            for (value, bitmap) in right {
+                *left.entry(value).or_default() |= bitmap;
+            }
+            left
+        };
+        let values_indices: AHashMap<V, RoaringBitmap> = if size > 10_000 {
+            items
+                .par_chunks(chunk_len)
+                .enumerate()
+                .fold(AHashMap::new, fold_chunk)
+                .reduce(AHashMap::new, merge)
+        } else {
+            items
+                .chunks(chunk_len)
+                .enumerate()
+                .fold(AHashMap::new(), fold_chunk)
+        };
 
+        let max_count = values_indices.values()
+            .map(|bitmap| bitmap.len() as usize)
+            .max()
+            .unwrap_or(0);
+        let indexes: AHashMap<V, Index> = values_indices
+            .into_iter()
+            .map(|(value, bitmap)| (value, Index::with_bitmap(bitmap, size)))
+            .collect();
+        Self::from_parts(ValueMap::Hash(indexes), size, None, max_count)
+    }
 
-        Self { 
-            values: indexes, 
-            size,
-            sorted_values: Some(sorted_values),
-            cardinality_ratio,
-            unique_count,
-            index_quality,
-            index_skewed,
-            index_analyzer,
+    // Построить индекс из данных, уже отсортированных по ключу
+    // (например логи по timestamp). Без глобальной сортировки и группировки:
+    // sorted_values и bitmap-ы строятся за один линейный проход.
+    // Если данные оказались не отсортированы - группировка уже извлеченных значений.
+    pub fn build_sorted<T, F>(items: &[Arc<T>], extractor: F) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> V + Send + Sync,
+    {
+        let size = items.len();
+        if size == 0 {
+            return Default::default()
+        }
+
+        let values: Vec<V> = if items.len() > 10_000 {
+            items.par_iter().map(|item| extractor(item)).collect()
+        } else {
+            items.iter().map(|item| extractor(item)).collect()
+        };
+        if values.windows(2).any(|pair| pair[0] > pair[1]) {
+            // extractor второй раз не вызывается
+            return Self::from_value_map(Self::group_positions(values), size);
         }
-    }
 
-    fn build_index_quantity(
-       size: usize,
-       unique_count: usize,
-       max_count: usize, 
-    ) -> f64{
-        if unique_count == 0 || size == 0{
-            return 0.0
+        let mut indexes = Vec::<(V, Index)>::new();
+        let mut groups = Vec::<(V, std::ops::Range<u32>)>::new();
+        let mut max_count = 0;
+        let mut group_start = 0;
+        for id in 1..=size {
+            if id == size || values[id] != values[group_start] {
+                // id внутри группы возрастают - bitmap строится без сортировки
+                let group = (group_start as u32)..(id as u32);
+                let bitmap = RoaringBitmap::from_sorted_iter(group.clone()).unwrap_or_default();
+                max_count = max_count.max(id - group_start);
+                indexes.push((values[group_start].clone(), Index::with_bitmap(bitmap, size)));
+                groups.push((values[group_start].clone(), group));
+                group_start = id;
+            }
         }
+        let sorted_values = SortedPositions::from_groups(groups.into_iter(), size);
 
-        let greate_count = size as f64 / unique_count as f64;
-        let deviation = (max_count as f64 - greate_count).abs();
-        1.0 - (deviation / size as f64)
+        // BTreeMap из отсортированной последовательности строится bulk-вставкой
+        Self::from_parts(ValueMap::Sorted(indexes.into_iter().collect()), size, Some(sorted_values), max_count)
     }
 
-    fn build_index_skewed(
-        size: usize,
-        max_count: usize,
-    ) -> bool {
-        if size > 0 {
-            return (max_count as f64 / size as f64) > VALUE_OFTEN_RATIO
+    // Значение -> bitmap позиций для уже извлеченных значений
+    fn group_positions(values: Vec<V>) -> BTreeMap<V, RoaringBitmap> {
+        let insert = |mut map: BTreeMap<V, RoaringBitmap>, (pos, value): (usize, V)| {
+            map.entry(value).or_default().insert(pos as u32);
+            map
+        };
+        if values.len() > 10_000 {
+            values
+                .into_par_iter()
+                .enumerate()
+                .fold(BTreeMap::new, insert)
+                .reduce(BTreeMap::new, Self::merge_value_maps)
+        } else {
+            values.into_iter().enumerate().fold(BTreeMap::new(), insert)
         }
-        false
     }
 
-    fn build_index_analyzier(
-        index_quality: f64,
-        cardinality_ratio: f64,
-    ) -> IndexAnalizer{
-        let cardinality = cardinality_ratio;
-        // Высокая кардинальности (> 50% уникальных)
-        if cardinality > CARDINALITY_RATIO_HIGH_THRESHOLD {
-            return IndexAnalizer::Excellent
+    pub(crate) fn merge_value_maps(
+        mut left: BTreeMap<V, RoaringBitmap>,
+        mut right: BTreeMap<V, RoaringBitmap>,
+    ) -> BTreeMap<V, RoaringBitmap> {
+        if left.len() < right.len() {
+            std::mem::swap(&mut left, &mut right);
         }
-        //Очень низкая кардинальность + перекос
-        if cardinality < CARDINALITY_RATIO_LOW_THRESHOLD{
-            return IndexAnalizer::Bad;
-        } 
-        // Набдюается сильный перекос независимо от кардинальности
-        if index_quality < SKEWED_RATIO {
-            return IndexAnalizer::Bad
+        for (value, bitmap) in right {
+            *left.entry(value).or_default() |= bitmap;
+        }
+        left
+    }
+
+    fn from_parts(
+        indexes: ValueMap<V>,
+        size: usize,
+        sorted_values: Option<SortedPositions<V>>,
+        max_count: usize,
+    ) -> Self {
+        // вычесляем cardinality ratio
+        let unique_count = indexes.len();
+        let cardinality_ratio = if size > 0 {
+            unique_count as f64 / size as f64
+        } else {
+            0.0
+        };
+        let index_quality = build_index_quantity(size, unique_count, max_count);
+        let index_skewed = build_index_skewed(size, max_count);
+        let index_analyzer = build_index_analyzier(index_quality, cardinality_ratio);
+
+        Self { 
+            values: indexes, 
+            size,
+            sorted_values,
+            cardinality_ratio,
+            unique_count,
+            index_quality,
+            index_skewed,
+            index_analyzer,
+            nulls: RoaringBitmap::new(),
+            multi_valued: false,
+            sparse: None,
         }
-        // Остальные случаи могут быть приемлимыми
-        IndexAnalizer::Good
     }
 
     // Информация об индексе
@@ -763,32 +2127,104 @@ where
     }
 
     pub fn unique_values_count(&self) -> usize {
-        self.values.len()
+        self.values.len() + usize::from(self.default_entry().is_some())
     }
 
+    // Значения по возрастанию (hash индекс сортирует их при вызове)
     pub fn values(&self) -> Vec<V> {
-        self.values.keys().cloned().collect()
+        self.sorted_entries().into_iter().map(|(value, _)| value.clone()).collect()
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.values.kind()
     }
 
     pub fn get_bitmap(&self, value: &V) -> Option<&RoaringBitmap> {
+        if self.is_sparse_default(value) {
+            return self.default_entry().map(|(_, idx)| idx.bitmap());
+        }
         self.values.get(value).map(|idx| idx.bitmap())
     }
 
+    pub fn nulls(&self) -> &RoaringBitmap {
+        &self.nulls
+    }
+
+    pub fn has_nulls(&self) -> bool {
+        !self.nulls.is_empty()
+    }
+
+    pub fn is_multi_valued(&self) -> bool {
+        self.multi_valued
+    }
+
+    // Bitmap-ы значений в порядке возрастания значений
+    pub fn bitmaps(&self) -> impl Iterator<Item = &RoaringBitmap> {
+        self.sorted_entries().into_iter().map(|(_, idx)| idx.bitmap())
+    }
+
+    // Значения вместе с их bitmap-ами (в порядке возрастания)
+    pub fn value_bitmaps(&self) -> Vec<(V, &RoaringBitmap)> {
+        self.sorted_entries().into_iter().map(|(value, idx)| (value.clone(), idx.bitmap())).collect()
+    }
+
     pub fn contains_value(&self, value: &V) -> bool {
-        self.values.contains_key(value)
+        self.values.contains_key(value) || self.value_count(value) > 0
     }
 
     pub fn value_count(&self, value: &V) -> usize {
-        self.values
-            .get(value)
-            .map(|idx| idx.len())
+        self.value_rows(value)
+            .map(|rows| rows.len() as usize)
             .unwrap_or(0)
     }
 
+    // Top-k самых частых значений (при равенстве - по возрастанию значения)
+    pub fn top_values(&self, k: usize) -> Vec<(V, usize)> {
+        let mut counts: Vec<(&V, usize)> = self
+            .sorted_entries()
+            .into_iter()
+            .map(|(value, idx)| (value, idx.len()))
+            .collect();
+        let order = |left: &(&V, usize), right: &(&V, usize)| right.1.cmp(&left.1).then_with(|| left.0.cmp(right.0));
+        // Полная сортировка только первых k
+        if k < counts.len() {
+            counts.select_nth_unstable_by(k, order);
+            counts.truncate(k);
+        }
+        counts.sort_unstable_by(order);
+        counts.into_iter().map(|(value, count)| (value.clone(), count)).collect()
+    }
+
     pub fn cardinality_ratio(&self) -> f64{
         self.cardinality_ratio
     }
 
+    // Память
+
+    // Сериализованный размер bitmap-ов значений и nulls
+    pub fn bitmap_bytes(&self) -> usize {
+        let sparse = self.sparse.as_ref().map_or(0, |sparse| {
+            sparse.removed.serialized_size() + sparse.cache.get().map_or(0, |index| index.memory_size())
+        });
+        self.values.values().map(|index| index.memory_size()).sum::<usize>()
+            + self.nulls.serialized_size()
+            + sparse
+    }
+
+    pub fn sorted_values_bytes(&self) -> usize {
+        self.sorted_values.as_ref().map_or(0, |sorted| sorted.memory_size())
+    }
+
+    // Оценка накладных расходов карты значений: пары (V, Index) плюс узлы / управляющие байты
+    pub fn map_overhead_bytes(&self) -> usize {
+        let entry = std::mem::size_of::<(V, Index)>();
+        let per_entry = match self.values.kind() {
+            IndexKind::Sorted => entry + BTREE_ENTRY_OVERHEAD,
+            IndexKind::Hash => (entry + 1) * 8 / 7,
+        };
+        self.values.len() * per_entry
+    }
+
     pub fn is_high_cardinality(&self) -> bool {
         self.cardinality_ratio > CARDINALITY_RATIO_HIGH_THRESHOLD
     }
@@ -825,7 +2261,9 @@ where
     }
 
     pub fn is_efficient_for_range(&self) -> bool {
-        self.cardinality_ratio >= CARDINALITY_RATIO_LOW_THRESHOLD && !self.index_skewed
+        self.values.kind() == IndexKind::Sorted
+            && self.cardinality_ratio >= CARDINALITY_RATIO_LOW_THRESHOLD
+            && !self.index_skewed
     }
 
     pub fn is_efficient_for(&self, operation: &FieldOperation) -> bool {
@@ -840,6 +2278,36 @@ where
         }
     }
 
+    // Доля самого частого значения
+    pub fn max_value_ratio(&self) -> f64 {
+        if self.size == 0 {
+            return 0.0;
+        }
+        let max_count = self.iter_bit_indexes().map(|idx| idx.len()).max().unwrap_or(0);
+        max_count as f64 / self.size as f64
+    }
+
+    // is_efficient_for с порогами из подсказок планировщика
+    pub fn is_efficient_for_hints(&self, operation: &FieldOperation, hints: &PlannerHints) -> bool {
+        if !hints.overrides_efficiency() {
+            return self.is_efficient_for(operation);
+        }
+        let low_threshold = hints.cardinality_low_threshold.unwrap_or(CARDINALITY_RATIO_LOW_THRESHOLD);
+        let skewed = match hints.skew_ratio {
+            Some(ratio) => self.max_value_ratio() > ratio,
+            None => self.index_skewed,
+        };
+        if operation.is_equality_query() {
+            self.is_efficient_for_equality()
+        } else if operation.is_inverse_query() {
+            skewed && self.cardinality_ratio < low_threshold
+        } else if operation.is_range_query() {
+            self.values.kind() == IndexKind::Sorted && self.cardinality_ratio >= low_threshold && !skewed
+        } else {
+            true
+        }
+    }
+
     // Оценка селектиновсти индексов
     
     // Селективность операции
@@ -937,59 +2405,52 @@ where
             FieldOperation::Range(_, _) => {
                 self.estimate_range_selectivity()
             }
+            // Null: точная доля из bitmap-а nulls
+            FieldOperation::IsNull => self.nulls.len() as f64 / self.size as f64,
+            FieldOperation::IsNotNull => 1.0 - self.nulls.len() as f64 / self.size as f64,
+            // ContainsAny: как In, ContainsAll: не больше, чем у одного значения
+            FieldOperation::ContainsAny(values) => {
+                if self.unique_count > 0 {
+                    (values.len().min(self.unique_count) as f64) / self.unique_count as f64
+                } else {
+                    0.0
+                }
+            },
+            FieldOperation::ContainsAll(_) => {
+                if self.unique_count > 0 {
+                    1.0 / self.unique_count as f64
+                } else {
+                    0.0
+                }
+            },
+            // Префикс - как диапазон, суффикс и подстрока - как сравнение
+            FieldOperation::StartsWith(_) => self.estimate_range_selectivity(),
+            FieldOperation::EndsWith(_) | FieldOperation::ContainsSubstr(_) => {
+                self.estimate_comparison_selectivity()
+            },
+            #[cfg(feature = "regex")]
+            FieldOperation::Regex(_) => self.estimate_comparison_selectivity(),
         }
     }
 
 
     // селективность множестенных операций с условиями
     pub fn estimate_operations_selectivity(&self, operations: &[(FieldOperation, Op)]) -> f64 {
-        if operations.is_empty() {
-            return 1.0;
-        }
-        
-        let mut result_selectivity = self.estimate_operation_selectivity(&operations[0].0);
-        for (operation, op_type) in &operations[1..] {
-            let op_selectivity = self.estimate_operation_selectivity(operation);
-            
-            result_selectivity = match op_type {
-                Op::And => {
-                    // P(A AND B) = P(A) * P(B) (если независимы)
-                    result_selectivity * op_selectivity
-                }
-                Op::Or => {
-                    // P(A) + P(B) если независимы
-                    (result_selectivity + op_selectivity).min(1.0)
-                }
-                Op::AndNot => {
-                    // P(A AND NOT B) = P(A) * (1 - P(B))
-                    result_selectivity * (1.0 - op_selectivity)
-                }
-                Op::Xor => {
-                    // P(A XOR B) = P(A) + P(B) - 2*P(A AND B)
-                    // Упрощение для независимых
-                    ((result_selectivity + op_selectivity) / 2.0).min(1.0)
-                }
-                Op::Invert => {
-                    // NOT A
-                    1.0 - result_selectivity
-                }
-            };
-        }
-        result_selectivity.max(0.0).min(1.0)
+        combine_selectivity(operations, |operation| self.estimate_operation_selectivity(operation))
     }
 
     // Итераторы
     
-    pub fn iter_indexes(&self) -> btree_map::Iter<'_, V, Index> {
-        self.values.iter()
+    pub fn iter_indexes(&self) -> impl Iterator<Item = (&V, &Index)> {
+        self.values.iter().chain(self.default_entry())
     }
 
-    pub fn iter_values(&self) -> btree_map::Keys<'_, V, Index> {
-        self.values.keys()
+    pub fn iter_values(&self) -> impl Iterator<Item = &V> {
+        self.iter_indexes().map(|(value, _)| value)
     }
     
-    pub fn iter_bit_indexes(&self) -> btree_map::Values<'_, V, Index> {
-        self.values.values()
+    pub fn iter_bit_indexes(&self) -> impl Iterator<Item = &Index> {
+        self.iter_indexes().map(|(_, index)| index)
     }
 
 
@@ -997,14 +2458,14 @@ where
 
     // Равенство: field == value
     pub fn value_eq(&self, value: &V) -> Option<RoaringBitmap> {
-        self.get_bitmap(value).map(|b| (*b).clone())
+        self.value_rows(value).map(Cow::into_owned)
     }
 
     // Не равно: field != value
     pub fn value_not_eq(&self, value: &V) -> Option<RoaringBitmap> {
         let mut result = RoaringBitmap::from_iter(0..(self.size as u32));
-        if let Some(bitmap) = self.get_bitmap(value) {
-            result -= bitmap;
+        if let Some(bitmap) = self.value_rows(value) {
+            result -= &*bitmap;
         }
         Some(result)
     }
@@ -1013,8 +2474,8 @@ where
     pub fn value_in(&self, values: &[V]) -> Option<RoaringBitmap> {
         let mut result = RoaringBitmap::new();
         for value in values {
-            if let Some(bitmap) = self.get_bitmap(value) {
-                result |= bitmap;
+            if let Some(bitmap) = self.value_rows(value) {
+                result |= &*bitmap;
             }
         }
         Some(result)
@@ -1024,13 +2485,27 @@ where
     pub fn value_not_in(&self, values: &[V]) -> Option<RoaringBitmap> {
         let mut result = RoaringBitmap::from_iter(0..(self.size as u32));
         for value in values {
-            if let Some(bitmap) = self.get_bitmap(value) {
-                result -= bitmap;
+            if let Some(bitmap) = self.value_rows(value) {
+                result -= &*bitmap;
             }
         }
         Some(result)
     }
 
+    // Все значения сразу (поле-массив): пересечение bitmap-ов, начиная с самого малого
+    pub fn value_all(&self, values: &[V]) -> Option<RoaringBitmap> {
+        let mut bitmaps = Vec::with_capacity(values.len());
+        for value in values {
+            match self.value_rows(value) {
+                Some(bitmap) => bitmaps.push(bitmap),
+                None => return Some(RoaringBitmap::new()),
+            }
+        }
+        bitmaps.sort_by_key(|bitmap| bitmap.len());
+        let (first, rest) = bitmaps.split_first()?;
+        Some(rest.iter().fold((**first).clone(), |result, bitmap| result & &**bitmap))
+    }
+
     // Больше: field > value
     pub fn value_gt(&self, threshold: &V) -> Option<RoaringBitmap> {
         self.value_range(
@@ -1096,9 +2571,12 @@ where
             return Some(bitmap);
         }
         let mut result = RoaringBitmap::new();
-        for (_, index) in self.values.range((start, end)) {
+        for (_, index) in self.values.range(start, end)? {
             result |= index.bitmap();
         }
+        if self.sparse_default().is_some_and(|default| (start, end).contains(default)) {
+            result |= self.default_rows().unwrap_or_default();
+        }
         Some(result)
     }
 
@@ -1108,51 +2586,7 @@ where
         end: Bound<&V>,
     ) -> Option<RoaringBitmap> {
         let sorted = self.sorted_values.as_ref()?;
-        // Binary search для start
-        let start_idx = match start {
-            Bound::Included(val) => {
-                sorted.binary_search_by(|(v, _)| v.cmp(val))
-                    .unwrap_or_else(|idx| idx)
-            }
-            Bound::Excluded(val) => {
-                match sorted.binary_search_by(|(v, _)| v.cmp(val)) {
-                    Ok(idx) => idx + 1,
-                    Err(idx) => idx,
-                }
-            }
-            Bound::Unbounded => 0,
-        };
-        // Binary search для end
-        let end_idx = match end {
-            Bound::Included(val) => {
-                match sorted.binary_search_by(|(v, _)| v.cmp(val)) {
-                    Ok(idx) => {
-                        // Найти последний элемент с этим значением
-                        let mut last = idx;
-                        while last + 1 < sorted.len() && sorted[last + 1].0 == *val {
-                            last += 1;
-                        }
-                        last + 1
-                    }
-                    Err(idx) => idx,
-                }
-            }
-            Bound::Excluded(val) => {
-                sorted.binary_search_by(|(v, _)| v.cmp(val))
-                    .unwrap_or_else(|idx| idx)
-            }
-            Bound::Unbounded => sorted.len(),
-        };
-        if start_idx >= end_idx {
-            return Some(RoaringBitmap::new());
-        }
-        // Собираем bitmap из индексов
-        let bitmap: RoaringBitmap = sorted[start_idx..end_idx]
-            .iter()
-            .map(|(_, idx)| *idx as u32)
-            .collect();
-
-        Some(bitmap)
+        Some(sorted.range(start, end))
     }
 
     // Комбинация значений с произвольной операцией
@@ -1161,32 +2595,90 @@ where
             return None;
         }
 
-        let first_index = self.values.get(operations[0].0)?;
-        let mut result = (*first_index.bitmap()).clone();
+        let mut result = self.value_rows(operations[0].0)?.into_owned();
 
         for (value, op) in &operations[1..] {
             if op.is_unary() {
                 match op {
                     Op::Invert => {
-                        let full = RoaringBitmap::from_iter(0..(self.size as u32));
-                        result = full - &result;
+                        let mut full = pool::take();
+                        full.insert_range(0..(self.size as u32));
+                        full -= &result;
+                        pool::recycle(std::mem::replace(&mut result, full));
                     }
                     _ => unreachable!("Only Invert is unary"),
                 }
             } else {
-                if let Some(index) = self.values.get(value) {
+                if let Some(bitmap) = self.value_rows(value) {
                     match op {
-                        Op::And => result &= index.bitmap(),
-                        Op::Or => result |= index.bitmap(),
-                        Op::AndNot => result -= index.bitmap(),
-                        Op::Xor => result ^= index.bitmap(),
+                        Op::And => result &= &*bitmap,
+                        Op::Or => result |= &*bitmap,
+                        Op::AndNot => result -= &*bitmap,
+                        Op::Xor => result ^= &*bitmap,
                         Op::Invert => unreachable!(),
                     }
                 }
             }
         }
 
-        Some(result)
+        Some(result)
+    }
+}
+
+// Строковые операции: префикс - диапазон отсортированных ключей,
+// суффикс и подстрока - проход по всем ключам (без проверки строк данных)
+impl IndexField<String> {
+    pub fn value_starts_with(&self, prefix: &str) -> RoaringBitmap {
+        let ValueMap::Sorted(values) = &self.values else {
+            return self.value_matching(|value| value.starts_with(prefix));
+        };
+        let mut result = RoaringBitmap::new();
+        for (_, index) in values
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(value, _)| value.starts_with(prefix))
+        {
+            result |= index.bitmap();
+        }
+        self.with_default_matching(result, |value| value.starts_with(prefix))
+    }
+
+    pub fn value_ends_with(&self, suffix: &str) -> RoaringBitmap {
+        self.value_matching(|value| value.ends_with(suffix))
+    }
+
+    pub fn value_contains(&self, substr: &str) -> RoaringBitmap {
+        self.value_matching(|value| value.contains(substr))
+    }
+
+    // Регулярное выражение проверяется только на уникальных значениях
+    #[cfg(feature = "regex")]
+    pub fn value_regex(&self, pattern: &str) -> Result<RoaringBitmap, regex::Error> {
+        let re = regex::Regex::new(pattern)?;
+        Ok(self.value_matching(|value| re.is_match(value)))
+    }
+
+    fn value_matching<F>(&self, predicate: F) -> RoaringBitmap
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut result = RoaringBitmap::new();
+        for (value, index) in self.values.iter() {
+            if predicate(value) {
+                result |= index.bitmap();
+            }
+        }
+        self.with_default_matching(result, predicate)
+    }
+
+    // Добавить строки значения по умолчанию sparse индекса, если оно подходит
+    fn with_default_matching<F>(&self, mut result: RoaringBitmap, predicate: F) -> RoaringBitmap
+    where
+        F: Fn(&str) -> bool,
+    {
+        if self.sparse_default().is_some_and(|default| predicate(default)) {
+            result |= self.default_rows().unwrap_or_default();
+        }
+        result
     }
 }
 
@@ -1212,7 +2704,22 @@ pub trait IntoIndexFieldEnum {
     fn into_enum(self) -> IndexFieldEnum;
 }
 
-trait TypeConvert {
+// Встроенный тип значения field индекса (IntoFieldValue::Stored)
+pub trait FieldStorage: Eq + Hash + Clone + Send + Sync + Ord + Into<FieldValue> + 'static {
+    fn into_index_enum(index: IndexField<Self>) -> IndexFieldEnum;
+}
+
+// Пользовательские типы индексируются как свой Stored
+impl<V> IntoIndexFieldEnum for IndexField<V>
+where
+    V: IntoFieldValue + Eq + Hash + Clone + Send + Sync + Ord + 'static,
+{
+    fn into_enum(self) -> IndexFieldEnum {
+        V::Stored::into_index_enum(self.map_values(V::into_stored))
+    }
+}
+
+pub(crate) trait TypeConvert {
     fn try_to_u128(&self) -> Option<u128>;
     fn try_to_i128(&self) -> Option<i128>;
     fn try_to_u64(&self) -> Option<u64>;
@@ -1230,6 +2737,11 @@ trait TypeConvert {
     fn try_to_decimal(&self) -> Option<Decimal>;
     fn try_to_string(&self) -> Option<String>;
     fn try_to_bool(&self) -> Option<bool>;
+    #[cfg(feature = "datetime")]
+    fn try_to_datetime(&self) -> Option<Timestamp>;
+    #[cfg(feature = "uuid")]
+    fn try_to_uuid(&self) -> Option<Uuid>;
+    fn try_to_bytes(&self) -> Option<Bytes>;
 }
 
 impl TypeConvert for FieldValue {
@@ -1538,19 +3050,53 @@ impl TypeConvert for FieldValue {
             _ => None,
         }
     }
+
+    // DateTime - точное значение или строка RFC 3339 (читаемые границы диапазона)
+    #[cfg(feature = "datetime")]
+    fn try_to_datetime(&self) -> Option<Timestamp> {
+        match self {
+            FieldValue::DateTime(v) => Some(*v),
+            FieldValue::String(v) => DateTime::parse_from_rfc3339(v).ok().map(|v| v.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+
+    // Uuid - точное значение или его строковая запись
+    #[cfg(feature = "uuid")]
+    fn try_to_uuid(&self) -> Option<Uuid> {
+        match self {
+            FieldValue::Uuid(v) => Some(*v),
+            FieldValue::String(v) => Uuid::parse_str(v).ok(),
+            _ => None,
+        }
+    }
+
+    // Bytes - точное значение или hex строка
+    fn try_to_bytes(&self) -> Option<Bytes> {
+        match self {
+            FieldValue::Bytes(v) => Some(v.clone()),
+            FieldValue::String(v) => Bytes::from_hex(v),
+            _ => None,
+        }
+    }
 }
 
 #[macro_export]
 macro_rules! define_index_field_enum {
     (
         $(
-            $variant:ident => $type:ty => $field_value:ident => $convert_method:ident
+            $(#[$meta:meta])*
+            $variant:ident => $type:ty => $field_value:ident => $convert_method:ident => $storage:ty
         ),* $(,)?
     ) => {
         // Enum-обертка для IndexField с разными типами
+        // ($storage - структура индекса: IndexField<$type> или специализация)
+        #[derive(Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub enum IndexFieldEnum {
             $(
-                $variant(IndexField<$type>),
+                $(#[$meta])*
+                $variant($storage),
             )*
         }
 
@@ -1559,6 +3105,7 @@ macro_rules! define_index_field_enum {
             pub fn type_name(&self) -> &'static str {
                 match self {
                     $(
+                        $(#[$meta])*
                         IndexFieldEnum::$variant(_) => stringify!($type),
                     )*
                 }
@@ -1567,6 +3114,7 @@ macro_rules! define_index_field_enum {
             pub fn len(&self) -> usize {
                 match self {
                     $(
+                        $(#[$meta])*
                         IndexFieldEnum::$variant(idx) => idx.len(),
                     )*
                 }
@@ -1579,14 +3127,29 @@ macro_rules! define_index_field_enum {
             pub fn unique_values_count(&self) -> usize {
                 match self {
                     $(
+                        $(#[$meta])*
                         IndexFieldEnum::$variant(idx) => idx.unique_values_count(),
                     )*
                 }
             }
 
+            // Top-k самых частых значений с числом строк
+            pub fn top_values(&self, k: usize) -> Vec<(FieldValue, usize)> {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.top_values(k)
+                            .into_iter()
+                            .map(|(value, count)| (FieldValue::$field_value(value), count))
+                            .collect(),
+                    )*
+                }
+            }
+
             pub fn values_as_strings(&self) -> Vec<String> {
                 match self {
                     $(
+                        $(#[$meta])*
                         IndexFieldEnum::$variant(idx) => {
                             idx.values().into_iter()
                                 .map(|v| format!("{:?}", v))
@@ -1596,6 +3159,149 @@ macro_rules! define_index_field_enum {
                 }
             }
 
+            // Позиции строк без значения
+            pub fn nulls(&self) -> &RoaringBitmap {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.nulls(),
+                    )*
+                }
+            }
+
+            pub fn has_nulls(&self) -> bool {
+                !self.nulls().is_empty()
+            }
+
+            // Поле-массив: строка входит в bitmap-ы нескольких значений
+            pub fn is_multi_valued(&self) -> bool {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.is_multi_valued(),
+                    )*
+                }
+            }
+
+            pub fn kind(&self) -> IndexKind {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.kind(),
+                    )*
+                }
+            }
+
+            // Дописать строки в конец индекса (FilterData::push / extend).
+            // false - поле-массив или значение не приводится к типу индекса: нужен rebuild
+            pub fn push_values(&mut self, values: &[FieldValue]) -> bool {
+                if self.is_multi_valued() {
+                    return false;
+                }
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => {
+                            let converted = values
+                                .iter()
+                                .map(|value| match value {
+                                    FieldValue::Null => Some(None),
+                                    value => value.$convert_method().map(Some),
+                                })
+                                .collect::<Option<Vec<Option<$type>>>>();
+                            match converted {
+                                Some(converted) => {
+                                    idx.push_values(converted);
+                                    true
+                                }
+                                None => false,
+                            }
+                        }
+                    )*
+                }
+            }
+
+            // Sparse индекс; false - default не приводится к типу индекса
+            pub fn set_sparse_default(&mut self, default: &FieldValue) -> bool {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => match default.$convert_method() {
+                            Some(default) => {
+                                idx.set_sparse_default(default);
+                                true
+                            }
+                            None => false,
+                        },
+                    )*
+                }
+            }
+
+            pub fn bitmap_bytes(&self) -> usize {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.bitmap_bytes(),
+                    )*
+                }
+            }
+
+            pub fn sorted_values_bytes(&self) -> usize {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.sorted_values_bytes(),
+                    )*
+                }
+            }
+
+            // Карта значений без heap-части строк / байтов (см. value_heap_bytes)
+            pub fn map_overhead_bytes(&self) -> usize {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.map_overhead_bytes(),
+                    )*
+                }
+            }
+
+            pub fn remove_positions(&mut self, rows: &RoaringBitmap) {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.remove_positions(rows),
+                    )*
+                }
+            }
+
+            // Null и поля-массивы видны только в индексе - predicate path их не различает
+            pub fn requires_index(&self) -> bool {
+                self.has_nulls() || self.is_multi_valued()
+            }
+
+            // Bitmap-ы значений - тот же порядок, что и value_bitmaps
+            pub fn bitmaps(&self) -> Vec<&RoaringBitmap> {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.bitmaps().collect(),
+                    )*
+                }
+            }
+
+            // Значения индекса как FieldValue вместе с bitmap-ами позиций
+            pub fn value_bitmaps(&self) -> Vec<(FieldValue, &RoaringBitmap)> {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.value_bitmaps()
+                            .into_iter()
+                            .map(|(value, bitmap)| (FieldValue::$field_value(value), bitmap))
+                            .collect(),
+                    )*
+                }
+            }
+
             // Применить FieldOperation (напрямую вызывает методы IndexField)
             #[allow(unreachable_patterns)]
             pub fn filter_operation(
@@ -1603,8 +3309,16 @@ macro_rules! define_index_field_enum {
                 operation: &FieldOperation
             ) -> IndexFieldResult<RoaringBitmap> {
                 match (self, operation) {
+                    // IS NULL / IS NOT NULL не зависят от типа значения
+                    (_, FieldOperation::IsNull) => Ok(self.nulls().clone()),
+                    (_, FieldOperation::IsNotNull) => {
+                        let mut result = RoaringBitmap::new();
+                        result.insert_range(0..self.len() as u32);
+                        Ok(result - self.nulls())
+                    },
                     $(
                         // Eq
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Eq(value)
@@ -1619,6 +3333,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // NotEq
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::NotEq(value)
@@ -1633,6 +3348,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // Gt
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Gt(value)
@@ -1647,6 +3363,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // Gte
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Gte(value)
@@ -1661,6 +3378,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // Lt
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Lt(value)
@@ -1675,6 +3393,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // Lte
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Lte(value)
@@ -1689,6 +3408,7 @@ macro_rules! define_index_field_enum {
                             }
                         },
                         // In
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::In(values)
@@ -1706,7 +3426,46 @@ macro_rules! define_index_field_enum {
                             idx.value_in(&typed_values)
                                 .ok_or_else(|| IndexFieldError::OperationIn{field_type: stringify!($type).to_string()})
                         },
+                        // ContainsAny - для поля-массива то же, что In
+                        $(#[$meta])*
+                        (
+                            IndexFieldEnum::$variant(idx),
+                            FieldOperation::ContainsAny(values)
+                        ) => {
+                            let typed_values: Vec<$type> = values.iter()
+                                .filter_map(|v|v.$convert_method())
+                                .collect();
+                            if typed_values.is_empty() {
+                                return Err(
+                                    IndexFieldError::OperationContainsAny{field_type: stringify!($type).to_string()}
+                                );
+                            }
+                            idx.value_in(&typed_values)
+                                .ok_or_else(|| IndexFieldError::OperationContainsAny{field_type: stringify!($type).to_string()})
+                        },
+                        // ContainsAll - каждое значение должно конвертироваться, иначе условие ослабнет
+                        $(#[$meta])*
+                        (
+                            IndexFieldEnum::$variant(idx),
+                            FieldOperation::ContainsAll(values)
+                        ) => {
+                            if values.is_empty() {
+                                return Err(
+                                    IndexFieldError::OperationContainsAll{field_type: stringify!($type).to_string()}
+                                );
+                            }
+                            let typed_values = values.iter()
+                                .map(|v| v.$convert_method())
+                                .collect::<Option<Vec<$type>>>()
+                                .ok_or_else(|| IndexFieldError::ConvertType{
+                                    field_type: stringify!($type).to_string(),
+                                    operation: "contains_all".to_string()
+                                })?;
+                            idx.value_all(&typed_values)
+                                .ok_or_else(|| IndexFieldError::OperationContainsAll{field_type: stringify!($type).to_string()})
+                        },
                         // NotIn
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::NotIn(values)
@@ -1723,6 +3482,7 @@ macro_rules! define_index_field_enum {
                                 .ok_or_else(|| IndexFieldError::OperationIn{field_type: stringify!($type).to_string()})
                         },
                         // Range
+                        $(#[$meta])*
                         (
                             IndexFieldEnum::$variant(idx),
                             FieldOperation::Range(start, end)
@@ -1738,6 +3498,13 @@ macro_rules! define_index_field_enum {
                             
                         }
                     )*
+                    // Строковые операции - только для строкового индекса
+                    (IndexFieldEnum::String(idx), FieldOperation::StartsWith(prefix)) => Ok(idx.value_starts_with(prefix)),
+                    (IndexFieldEnum::String(idx), FieldOperation::EndsWith(suffix)) => Ok(idx.value_ends_with(suffix)),
+                    (IndexFieldEnum::String(idx), FieldOperation::ContainsSubstr(substr)) => Ok(idx.value_contains(substr)),
+                    #[cfg(feature = "regex")]
+                    (IndexFieldEnum::String(idx), FieldOperation::Regex(pattern)) => idx.value_regex(pattern)
+                        .map_err(|err| IndexFieldError::InvalidRegex{pattern: pattern.clone(), message: err.to_string()}),
                     // Несовпадение типов
                     _ => Err(IndexFieldError::OperationUndefinedType{field_type: self.type_name().to_string()}),
                 }
@@ -1756,19 +3523,22 @@ macro_rules! define_index_field_enum {
                 let mut result = self.filter_operation(&operations[0].0)?;
                 for (operation, op) in &operations[1..] {
                     let bitmap = self.filter_operation(operation)?;
-                    result = if op == &Op::Invert{
-                        let size = self.len();
-                        let full = RoaringBitmap::from_iter(0..(size as u32));
-                        full - &result
+                    // Промежуточные bitmap-ы возвращаются в пул потока
+                    if op == &Op::Invert {
+                        let mut full = pool::take();
+                        full.insert_range(0..(self.len() as u32));
+                        full -= &result;
+                        pool::recycle(std::mem::replace(&mut result, full));
                     } else {
                         match op {
-                            Op::And => result & bitmap,
-                            Op::Or => result | bitmap,
-                            Op::Xor => result ^ bitmap,
-                            Op::AndNot => result - bitmap,
+                            Op::And => result &= &bitmap,
+                            Op::Or => result |= &bitmap,
+                            Op::Xor => result ^= &bitmap,
+                            Op::AndNot => result -= &bitmap,
                             Op::Invert => unreachable!("Invert is not binary operation")
                         }
                     }
+                    pool::recycle(bitmap);
                 }
 
                 Ok(result)
@@ -1776,113 +3546,57 @@ macro_rules! define_index_field_enum {
 
             pub fn index_analize(&self) -> IndexAnalizer {
                 match self {
-                    IndexFieldEnum::U128(idx) => idx.index_analize(),
-                    IndexFieldEnum::I128(idx) => idx.index_analize(),
-                    IndexFieldEnum::U64(idx) => idx.index_analize(),
-                    IndexFieldEnum::I64(idx) => idx.index_analize(),
-                    IndexFieldEnum::U32(idx) => idx.index_analize(),
-                    IndexFieldEnum::I32(idx) => idx.index_analize(),
-                    IndexFieldEnum::U16(idx) => idx.index_analize(),
-                    IndexFieldEnum::I16(idx) => idx.index_analize(),
-                    IndexFieldEnum::U8(idx) => idx.index_analize(),
-                    IndexFieldEnum::I8(idx) => idx.index_analize(),
-                    IndexFieldEnum::Usize(idx) => idx.index_analize(),
-                    IndexFieldEnum::Isize(idx) => idx.index_analize(),
-                    IndexFieldEnum::F64(idx) => idx.index_analize(),
-                    IndexFieldEnum::F32(idx) => idx.index_analize(),
-                    IndexFieldEnum::Decimal(idx) => idx.index_analize(),
-                    IndexFieldEnum::String(idx) => idx.index_analize(),
-                    IndexFieldEnum::Bool(idx) => idx.index_analize(),
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.index_analize(),
+                    )*
                 }
             }
 
             pub fn is_efficient_for(&self, operation: &FieldOperation) -> bool {
                 match self {
-                    IndexFieldEnum::U128(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::I128(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::U64(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::I64(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::U32(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::I32(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::U16(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::I16(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::U8(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::I8(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::Usize(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::Isize(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::F64(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::F32(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::Decimal(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::String(idx) => idx.is_efficient_for(operation),
-                    IndexFieldEnum::Bool(idx) => idx.is_efficient_for(operation),
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.is_efficient_for(operation),
+                    )*
+                }
+            }
+
+            pub fn is_efficient_for_hints(&self, operation: &FieldOperation, hints: &PlannerHints) -> bool {
+                match self {
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.is_efficient_for_hints(operation, hints),
+                    )*
                 }
             }
 
             pub fn is_high_cardinality(&self) -> bool {
                 match self {
-                    IndexFieldEnum::U128(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::I128(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::U64(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::I64(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::U32(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::I32(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::U16(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::I16(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::U8(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::I8(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::Usize(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::Isize(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::F64(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::F32(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::Decimal(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::String(idx) => idx.is_high_cardinality(),
-                    IndexFieldEnum::Bool(idx) => idx.is_high_cardinality(),
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.is_high_cardinality(),
+                    )*
                 }
             }
 
             // Оценить селективность операции
             pub fn estimate_operation_selectivity(&self, operation: &FieldOperation) -> f64 {
                 match self {
-                    IndexFieldEnum::U128(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::I128(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::U64(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::I64(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::U32(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::I32(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::U16(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::I16(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::U8(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::I8(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::Usize(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::Isize(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::F64(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::F32(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::Decimal(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::String(idx) => idx.estimate_operation_selectivity(operation),
-                    IndexFieldEnum::Bool(idx) => idx.estimate_operation_selectivity(operation),
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.estimate_operation_selectivity(operation),
+                    )*
                 }
             }
 
             // Оценить комбинацию операций
             pub fn estimate_operations_selectivity(&self, operations: &[(FieldOperation, Op)]) -> f64 {
                 match self {
-                    IndexFieldEnum::U128(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::I128(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::U64(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::I64(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::U32(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::I32(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::U16(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::I16(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::U8(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::I8(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::Usize(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::Isize(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::F64(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::F32(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::Decimal(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::String(idx) => idx.estimate_operations_selectivity(operations),
-                    IndexFieldEnum::Bool(idx) => idx.estimate_operations_selectivity(operations),
+                    $(
+                        $(#[$meta])*
+                        IndexFieldEnum::$variant(idx) => idx.estimate_operations_selectivity(operations),
+                    )*
                 }
             }
         }
@@ -1901,9 +3615,17 @@ macro_rules! define_index_field_enum {
 
         // IntoIndexFieldEnum trait для удобного преобразования
         $(
+            $(#[$meta])*
             impl IntoIndexFieldEnum for IndexField<$type> {
                 fn into_enum(self) -> IndexFieldEnum {
-                    IndexFieldEnum::$variant(self)
+                    IndexFieldEnum::$variant(self.into())
+                }
+            }
+
+            $(#[$meta])*
+            impl FieldStorage for $type {
+                fn into_index_enum(index: IndexField<$type>) -> IndexFieldEnum {
+                    index.into_enum()
                 }
             }
         )*
@@ -1912,24 +3634,229 @@ macro_rules! define_index_field_enum {
 
 
 // ОПРЕДЕЛЕНИЕ ВСЕХ ТИПОВ (единая точка изменения!)
+// Оценка накладных расходов BTreeMap на одну пару (указатели узлов, незаполненные слоты)
+const BTREE_ENTRY_OVERHEAD: usize = 16;
+
 define_index_field_enum! {
-    U128 => u128 => U128 => try_to_u128,
-    I128 => i128 => I128 => try_to_i128,
-    U64 => u64 => U64 => try_to_u64,
-    I64 => i64 => I64 => try_to_i64,
-    U32 => u32 => U32 => try_to_u32,
-    I32 => i32 => I32 => try_to_i32,
-    U16 => u16 => U16 => try_to_u16,
-    I16 => i16 => I16 => try_to_i16,
-    U8 => u8 => U8 => try_to_u8,
-    I8 => i8 => I8 => try_to_i8,
-    Usize => usize => Usize => try_to_usize,
-    Isize => isize => Isize => try_to_isize,
-    F64 => F64 => F64 => try_to_f64,
-    F32 => F32 => F32 => try_to_f32,
-    Decimal => Decimal => Decimal => try_to_decimal,
-    String => String => String => try_to_string,
-    Bool => bool => Bool => try_to_bool,
+    U128 => u128 => U128 => try_to_u128 => IndexField<u128>,
+    I128 => i128 => I128 => try_to_i128 => IndexField<i128>,
+    U64 => u64 => U64 => try_to_u64 => IndexField<u64>,
+    I64 => i64 => I64 => try_to_i64 => IndexField<i64>,
+    U32 => u32 => U32 => try_to_u32 => IndexField<u32>,
+    I32 => i32 => I32 => try_to_i32 => IndexField<i32>,
+    U16 => u16 => U16 => try_to_u16 => IndexField<u16>,
+    I16 => i16 => I16 => try_to_i16 => IndexField<i16>,
+    U8 => u8 => U8 => try_to_u8 => IndexField<u8>,
+    I8 => i8 => I8 => try_to_i8 => IndexField<i8>,
+    Usize => usize => Usize => try_to_usize => IndexField<usize>,
+    Isize => isize => Isize => try_to_isize => IndexField<isize>,
+    F64 => F64 => F64 => try_to_f64 => IndexField<F64>,
+    F32 => F32 => F32 => try_to_f32 => IndexField<F32>,
+    Decimal => Decimal => Decimal => try_to_decimal => IndexField<Decimal>,
+    String => String => String => try_to_string => IndexField<String>,
+    Bool => bool => Bool => try_to_bool => BoolIndex,
+    #[cfg(feature = "datetime")]
+    DateTime => Timestamp => DateTime => try_to_datetime => IndexField<Timestamp>,
+    #[cfg(feature = "uuid")]
+    Uuid => Uuid => Uuid => try_to_uuid => IndexField<Uuid>,
+    Bytes => Bytes => Bytes => try_to_bytes => IndexField<Bytes>,
+}
+
+impl IndexFieldEnum {
+    // Статистика значений: min/max, перцентили, гистограмма (см. FieldStats)
+    pub fn stats(&self, version: u64, buckets: usize) -> FieldStats {
+        let counts: Vec<(FieldValue, u64)> = self.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.len()))
+            .collect();
+        FieldStats::from_value_counts(version, &counts, self.len(), self.nulls().len(), buckets)
+    }
+
+    // stats только по позициям mask (base filter): значения вне mask не учитываются
+    pub fn stats_within(&self, version: u64, buckets: usize, mask: &RoaringBitmap) -> FieldStats {
+        let counts: Vec<(FieldValue, u64)> = self.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.intersection_len(mask)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let rows = mask.range_cardinality(0..self.len() as u32) as usize;
+        FieldStats::from_value_counts(version, &counts, rows, self.nulls().intersection_len(mask), buckets)
+    }
+
+    // Heap-часть значений: строки карты и sorted_values (значение хранится в обоих)
+    pub fn value_heap_bytes(&self) -> usize {
+        match self {
+            IndexFieldEnum::String(idx) => {
+                let copies = 1 + usize::from(idx.sorted_values_bytes() > 0);
+                idx.iter_values().map(|value| value.capacity()).sum::<usize>() * copies
+            }
+            IndexFieldEnum::Bytes(idx) => {
+                let copies = 1 + usize::from(idx.sorted_values_bytes() > 0);
+                idx.iter_values().map(|value| value.heap_bytes()).sum::<usize>() * copies
+            }
+            _ => 0,
+        }
+    }
+
+    // Прогрев bitmap-ов значений и nulls: (число bitmap-ов, байты)
+    pub fn touch(&self) -> (usize, usize) {
+        let mut bitmaps = self.bitmaps();
+        bitmaps.push(self.nulls());
+        let bytes = bitmaps.par_iter().map(|bitmap| super::bit::touch(bitmap)).sum();
+        (bitmaps.len(), bytes)
+    }
+
+    // filter_operations в трехзначной логике: строки nulls - Unknown
+    // для каждой операции, связки вычисляются по Kleene
+    pub fn filter_operations_tri(
+        &self,
+        operations: &[(FieldOperation, Op)],
+    ) -> IndexFieldResult<TriBitmap> {
+        if operations.is_empty() {
+            return Err(IndexFieldError::OperationListEmpty)
+        }
+        let nulls = self.nulls();
+        let mut universe = RoaringBitmap::new();
+        universe.insert_range(0..self.len() as u32);
+        let leaf = |operation: &FieldOperation| -> IndexFieldResult<TriBitmap> {
+            let bitmap = self.filter_operation(operation)?;
+            if operation.is_null_query() {
+                // Проверка на null всегда определена
+                return Ok(TriBitmap::new(bitmap, RoaringBitmap::new()));
+            }
+            Ok(TriBitmap::new(bitmap - nulls, nulls.clone()))
+        };
+        let mut result = leaf(&operations[0].0)?;
+        for (operation, op) in &operations[1..] {
+            result = if op == &Op::Invert {
+                result.not(&universe)
+            } else {
+                result.combine(*op, &leaf(operation)?, &universe)
+            };
+        }
+        Ok(result)
+    }
+
+    // Доля самого частого значения среди всех строк индекса
+    pub fn max_value_ratio(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let max_count = self.bitmaps().iter().map(|bitmap| bitmap.len()).max().unwrap_or(0);
+        max_count as f64 / self.len() as f64
+    }
+
+    pub fn is_skewed(&self) -> bool {
+        self.max_value_ratio() > VALUE_OFTEN_RATIO
+    }
+
+    // Объяснение оценки index_analize с рекомендациями
+    pub fn report(&self, name: &str) -> IndexReport {
+        let counts = self.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.len()))
+            .collect();
+        self.report_from_counts(name, self.len(), counts, self.nulls().len(), self.index_analize())
+    }
+
+    // report только по позициям mask (base filter): оценка пересчитывается по строкам mask
+    pub fn report_within(&self, name: &str, mask: &RoaringBitmap) -> IndexReport {
+        let counts: Vec<(FieldValue, u64)> = self.value_bitmaps()
+            .into_iter()
+            .map(|(value, bitmap)| (value, bitmap.intersection_len(mask)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        let rows = mask.range_cardinality(0..self.len() as u32) as usize;
+        let verdict = match self {
+            IndexFieldEnum::Bool(_) => self.index_analize(),
+            _ => {
+                let max_count = counts.iter().map(|(_, count)| *count as usize).max().unwrap_or(0);
+                let cardinality_ratio = if rows == 0 { 0.0 } else { counts.len() as f64 / rows as f64 };
+                build_index_analyzier(build_index_quantity(rows, counts.len(), max_count), cardinality_ratio)
+            }
+        };
+        self.report_from_counts(name, rows, counts, self.nulls().intersection_len(mask), verdict)
+    }
+
+    fn report_from_counts(
+        &self,
+        name: &str,
+        rows: usize,
+        counts: Vec<(FieldValue, u64)>,
+        nulls: u64,
+        verdict: IndexAnalizer,
+    ) -> IndexReport {
+        let unique_values = counts.len();
+        let cardinality_ratio = if rows == 0 { 0.0 } else { unique_values as f64 / rows as f64 };
+        let dominant = counts.into_iter().max_by_key(|(_, count)| *count);
+        let dominant_value_ratio = match &dominant {
+            Some((_, count)) if rows > 0 => *count as f64 / rows as f64,
+            _ => 0.0,
+        };
+        let is_skewed = dominant_value_ratio > VALUE_OFTEN_RATIO;
+
+        let mut reasons = Vec::new();
+        if cardinality_ratio > CARDINALITY_RATIO_HIGH_THRESHOLD {
+            reasons.push(format!(
+                "high cardinality {cardinality_ratio:.3} (> {CARDINALITY_RATIO_HIGH_THRESHOLD}): equality hits few rows"
+            ));
+        } else if cardinality_ratio < CARDINALITY_RATIO_LOW_THRESHOLD {
+            reasons.push(format!(
+                "low cardinality {cardinality_ratio:.3} (< {CARDINALITY_RATIO_LOW_THRESHOLD}): each value covers many rows"
+            ));
+        } else {
+            reasons.push(format!("moderate cardinality {cardinality_ratio:.3}"));
+        }
+        if let Some((value, _)) = &dominant {
+            if is_skewed {
+                reasons.push(format!(
+                    "skewed: dominant value {value} covers {:.1}% of rows (> {:.0}%)",
+                    dominant_value_ratio * 100.0,
+                    VALUE_OFTEN_RATIO * 100.0,
+                ));
+            } else {
+                reasons.push(format!("top value {value} covers {:.1}% of rows", dominant_value_ratio * 100.0));
+            }
+        }
+        if matches!(verdict, IndexAnalizer::Bad) && cardinality_ratio >= CARDINALITY_RATIO_LOW_THRESHOLD {
+            reasons.push("uneven distribution of rows between values".to_string());
+        }
+        if nulls > 0 {
+            reasons.push(format!("{nulls} rows without value (nulls)"));
+        }
+
+        let mut recommendations = Vec::new();
+        if unique_values <= 1 && rows > 0 {
+            recommendations.push(IndexRecommendation::DropIndex);
+        }
+        if unique_values == rows && rows > 1 {
+            recommendations.push(IndexRecommendation::UniqueIndex);
+        }
+        if !(is_skewed && cardinality_ratio < CARDINALITY_RATIO_LOW_THRESHOLD) {
+            recommendations.push(IndexRecommendation::PredicateForInverse);
+        }
+        if cardinality_ratio < CARDINALITY_RATIO_LOW_THRESHOLD || is_skewed {
+            recommendations.push(IndexRecommendation::PredicateForRange);
+        }
+        let numeric = !matches!(self, IndexFieldEnum::String(_) | IndexFieldEnum::Bool(_));
+        if numeric && cardinality_ratio > CARDINALITY_RATIO_HIGH_THRESHOLD {
+            recommendations.push(IndexRecommendation::BucketedIndex);
+        }
+
+        IndexReport {
+            name: name.to_string(),
+            value_type: self.type_name(),
+            verdict,
+            rows,
+            unique_values,
+            cardinality_ratio,
+            dominant_value: dominant.map(|(value, _)| value),
+            dominant_value_ratio,
+            is_skewed,
+            reasons,
+            recommendations,
+        }
+    }
 }
 
 
@@ -2196,5 +4123,206 @@ mod tests {
         assert!(index.quality_distribution() < 0.3);
     }
 
+    #[test]
+    fn test_index_field_build_sorted_matches_build() {
+        let items: Vec<Arc<u64>> = (0..5_000u64).map(|i| Arc::new(i / 7)).collect();
+        let regular = IndexField::build(&items, |v| *v);
+        let sorted = IndexField::build_sorted(&items, |v| *v);
+        assert_eq!(regular.unique_values_count(), sorted.unique_values_count());
+        assert_eq!(regular.sorted_values, sorted.sorted_values);
+        assert_eq!(regular.cardinality_ratio(), sorted.cardinality_ratio());
+        for value in regular.values() {
+            assert_eq!(regular.get_bitmap(&value), sorted.get_bitmap(&value));
+        }
+        let result = sorted.value_range_inclusive(&10, &20).unwrap();
+        assert_eq!(result.len(), 77);
+    }
+
+    #[test]
+    fn test_index_field_build_sorted_fallback_unsorted() {
+        let items: Vec<Arc<u64>> = [5u64, 1, 3, 1, 5].into_iter().map(Arc::new).collect();
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let index = IndexField::build_sorted(&items, |v| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            *v
+        });
+        // Значения извлекаются один раз и для запасного пути
+        assert_eq!(calls.into_inner(), items.len());
+        assert_eq!(index.values(), vec![1, 3, 5]);
+        let result = index.value_eq(&1).unwrap();
+        assert!(result.contains(1) && result.contains(3));
+        assert_eq!(index.sorted_values, IndexField::build(&items, |v| *v).sorted_values);
+
+        let shuffled: Vec<Arc<u64>> = (0..20_000u64).map(|i| Arc::new((i * 7919) % 1_000)).collect();
+        let fallback = IndexField::build_sorted(&shuffled, |v| *v);
+        let regular = IndexField::build(&shuffled, |v| *v);
+        assert_eq!(fallback.sorted_values, regular.sorted_values);
+        assert_eq!(fallback.value_lt(&10), regular.value_lt(&10));
+    }
+
+    #[test]
+    fn test_value_range_duplicate_bounds() {
+        // Каждое значение повторяется: границы диапазона попадают на группы строк
+        let items: Vec<Arc<u64>> = (0..200u64).map(|i| Arc::new((i * 7) % 10)).collect();
+        let index = IndexField::build(&items, |v| *v);
+        assert!(index.sorted_values.is_some());
+        let rows = |predicate: &dyn Fn(u64) -> bool| -> RoaringBitmap {
+            (0..200u32).filter(|&id| predicate(*items[id as usize])).collect()
+        };
+        for bound in 0..=10u64 {
+            assert_eq!(index.value_gt(&bound).unwrap(), rows(&|v| v > bound));
+            assert_eq!(index.value_gte(&bound).unwrap(), rows(&|v| v >= bound));
+            assert_eq!(index.value_lt(&bound).unwrap(), rows(&|v| v < bound));
+            assert_eq!(index.value_lte(&bound).unwrap(), rows(&|v| v <= bound));
+            assert_eq!(index.value_range_inclusive(&bound, &(bound + 2)).unwrap(), rows(&|v| (bound..=bound + 2).contains(&v)));
+            assert_eq!(index.value_range_exclusive(&bound, &(bound + 2)).unwrap(), rows(&|v| (bound..bound + 2).contains(&v)));
+        }
+    }
+
+    #[test]
+    fn test_index_field_build_with_memory_target_chunks() {
+        let items: Vec<Arc<u64>> = (0..50_000u64).map(|i| Arc::new((i * 7919) % 1_000)).collect();
+        // Минимальный бюджет - много маленьких чанков, которые потом сливаются
+        let chunked = IndexField::build_with_memory_target(&items, |v| *v, 1);
+        let regular = IndexField::build(&items, |v| *v);
+        assert_eq!(chunked.unique_values_count(), 1_000);
+        assert_eq!(chunked.sorted_values, regular.sorted_values);
+        assert_eq!(chunked.value_count(&0), 50);
+        // Позиции строк не сдвигаются на границах раундов
+        assert!((0..1_000).all(|value| chunked.value_rows(&value) == regular.value_rows(&value)));
+        let sorted = chunked.sorted_values.as_ref().unwrap();
+        assert_eq!(sorted.len(), 50_000);
+        assert!(sorted.values().windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_index_field_build_hash_matches_build() {
+        let items: Vec<Arc<String>> = (0..30_000).map(|i| Arc::new(format!("user_{}", (i * 7919) % 5_000))).collect();
+        let regular = IndexField::build(&items, |v| v.clone());
+        let hashed = IndexField::build_hash(&items, |v| v.clone());
+        assert_eq!(hashed.kind(), IndexKind::Hash);
+        assert!(hashed.sorted_values.is_none());
+        assert_eq!(hashed.unique_values_count(), regular.unique_values_count());
+        assert_eq!(hashed.cardinality_ratio(), regular.cardinality_ratio());
+        // Порядок значений и bitmap-ов тот же, что у упорядоченного индекса
+        assert_eq!(hashed.values(), regular.values());
+        assert_eq!(hashed.value_bitmaps(), regular.value_bitmaps());
+        let targets = ["user_1".to_string(), "user_42".to_string(), "missing".to_string()];
+        assert_eq!(hashed.value_in(&targets), regular.value_in(&targets));
+        assert_eq!(hashed.value_not_eq(&targets[0]), regular.value_not_eq(&targets[0]));
+        assert_eq!(hashed.value_starts_with("user_49"), regular.value_starts_with("user_49"));
+        // Range по hash индексу недоступен
+        assert!(hashed.value_gt(&targets[0]).is_none());
+        assert!(!hashed.is_efficient_for(&FieldOperation::gt("user_1")));
+    }
+
+    #[test]
+    fn test_sorted_positions_store_distinct_values_once() {
+        let items: Vec<Arc<String>> = (0..20_000).map(|i| Arc::new(format!("city_{}", i % 10))).collect();
+        let index = IndexField::build(&items, |city| city.clone());
+        let sorted = index.sorted_values.as_ref().unwrap();
+        assert_eq!(sorted.values().len(), 10);
+        assert_eq!(sorted.len(), 20_000);
+
+        let numbers: Vec<Arc<u64>> = (0..1_000u64).map(|i| Arc::new(i % 100)).collect();
+        let index = IndexField::build(&numbers, |v| *v);
+        let expect = |predicate: &dyn Fn(u64) -> bool| -> u64 {
+            numbers.iter().filter(|v| predicate(***v)).count() as u64
+        };
+        assert_eq!(index.value_range_exclusive(&10, &20).unwrap().len(), expect(&|v| (10..20).contains(&v)));
+        assert_eq!(index.value_gt(&95).unwrap().len(), expect(&|v| v > 95));
+        assert_eq!(index.value_lte(&0).unwrap().len(), expect(&|v| v == 0));
+        assert!(index.value_range_inclusive(&200, &300).unwrap().is_empty());
+        let bitmap = index.value_range_inclusive(&42, &42).unwrap();
+        assert!(bitmap.iter().all(|pos| pos % 100 == 42));
+    }
+
+    #[test]
+    fn test_push_values_appends_sorted_positions() {
+        let numbers: Vec<Arc<u64>> = (0..1_000u64).map(|i| Arc::new(i % 100)).collect();
+        let mut index = IndexField::build(&numbers, |v| *v);
+        let mut all = numbers.clone();
+        // Мелкие push копятся в хвосте, затем сливаются с основными массивами
+        for batch in 0..40u64 {
+            let values: Vec<u64> = (0..10).map(|i| (batch * 7 + i * 13) % 150).collect();
+            all.extend(values.iter().map(|v| Arc::new(*v)));
+            index.push_values(values.into_iter().map(Some));
+            let rebuilt = IndexField::build(&all, |v| *v);
+            assert_eq!(index.value_range_inclusive(&90, &120), rebuilt.value_range_inclusive(&90, &120));
+            assert_eq!(index.value_gt(&140), rebuilt.value_gt(&140));
+            assert_eq!(index.value_lt(&5), rebuilt.value_lt(&5));
+        }
+        let sorted = index.sorted_values.as_ref().unwrap();
+        assert_eq!(sorted.len(), all.len());
+        assert!(index.value_range_exclusive(&10, &10).unwrap().is_empty());
+        assert!(index.value_range_inclusive(&20, &10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compiled_operations_match_evaluate() {
+        let values = vec![
+            FieldValue::U64(5), FieldValue::U64(50), FieldValue::I32(-3), FieldValue::I64(5),
+            FieldValue::U8(20), FieldValue::F64(OrderedFloat(5.0)), FieldValue::String("b".into()),
+            FieldValue::String("x".into()), FieldValue::Bool(true), FieldValue::Bool(false),
+        ];
+        let chains: Vec<Vec<(FieldOperation, Op)>> = vec![
+            vec![],
+            vec![(FieldOperation::eq(5u64), Op::And)],
+            vec![(FieldOperation::not_eq(5u64), Op::And)],
+            vec![(FieldOperation::gt(10u64), Op::And)],
+            vec![(FieldOperation::lte("c"), Op::And)],
+            vec![(FieldOperation::range(1u64, 20u64), Op::And)],
+            vec![(FieldOperation::range(1u64, 20i32), Op::And)],
+            vec![(FieldOperation::in_values(vec![5u64, 20]), Op::And)],
+            vec![(FieldOperation::not_in_values(vec!["b", "c"]), Op::And)],
+            vec![(FieldOperation::eq(true), Op::AndNot)],
+            vec![(FieldOperation::gte(5.0f64), Op::And), (FieldOperation::eq(50u64), Op::Or)],
+            vec![(FieldOperation::lt(10i64), Op::And), (FieldOperation::eq(-3i32), Op::Xor), (FieldOperation::eq(0u64), Op::Invert)],
+        ];
+        for operations in &chains {
+            let compiled = FieldOperation::compile_operations(operations);
+            for value in &values {
+                assert_eq!(
+                    compiled(value),
+                    FieldOperation::evaluate_operations(value, operations),
+                    "{:?} on {:?}", operations, value
+                );
+            }
+        }
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_sparse_index_matches_dense() {
+        // 90% строк - "active"
+        let status = |i: usize| if i % 10 == 3 { "closed" } else if i % 10 == 7 { "new" } else { "active" };
+        let items: Vec<Arc<String>> = (0..10_000).map(|i| Arc::new(status(i).to_string())).collect();
+        let dense = IndexField::build(&items, |v| v.clone());
+        let mut sparse = IndexField::build(&items, |v| v.clone()).into_sparse("active".to_string());
+        assert!(sparse.sorted_values.is_none());
+        assert!(!sparse.values.contains_key(&"active".to_string()));
+
+        let active = "active".to_string();
+        let new = "new".to_string();
+        assert_eq!(sparse.value_eq(&active), dense.value_eq(&active));
+        assert_eq!(sparse.value_not_eq(&active), dense.value_not_eq(&active));
+        assert_eq!(sparse.value_in(&[active.clone(), new.clone()]), dense.value_in(&[active.clone(), new.clone()]));
+        assert_eq!(sparse.value_gte(&"b".to_string()), dense.value_gte(&"b".to_string()));
+        assert_eq!(sparse.value_lt(&"b".to_string()), dense.value_lt(&"b".to_string()));
+        assert_eq!(sparse.value_starts_with("act"), dense.value_starts_with("act"));
+        assert_eq!(sparse.value_contains("e"), dense.value_contains("e"));
+        assert_eq!(sparse.values(), dense.values());
+        assert_eq!(sparse.unique_values_count(), 3);
+        assert_eq!(sparse.top_values(1), vec![(active.clone(), 8_000)]);
+        assert!(sparse.is_skewed());
+
+        // Дописанные и удаленные строки не ломают дополнение
+        sparse.push_values([Some(active.clone()), None, Some(new.clone())]);
+        let removed: RoaringBitmap = [0u32, 3, 7].into_iter().collect();
+        sparse.remove_positions(&removed);
+        let rows = sparse.value_eq(&active).unwrap();
+        assert_eq!(rows.len(), 8_000 - 1 + 1);
+        assert!(rows.contains(10_000) && !rows.contains(10_001) && !rows.contains(0));
+        assert_eq!(sparse.value_count(&active), 8_000);
+        assert_eq!(sparse.get_bitmap(&active), Some(&rows));
+    }
+}
@@ -0,0 +1,120 @@
+use super::{
+    ExtractorFieldValue,
+    IndexType,
+    field::{FieldOperation, FieldValue},
+};
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+};
+
+// Сколько строк проверяется против bitmap-ов индекса при валидации
+pub const INTEGRITY_SAMPLE_SIZE: usize = 64;
+// Размер чанка для параллельного подсчета fingerprint-а
+pub(crate) const FINGERPRINT_CHUNK: usize = 64 * 1024;
+
+// Отпечаток данных, по которым построен индекс:
+// число строк + хеш значений extractor-а в порядке позиций.
+// DefaultHasher::new() детерминирован, поэтому отпечатки сравнимы между вызовами.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexFingerprint {
+    pub rows: usize,
+    pub hash: u64,
+}
+
+impl IndexFingerprint {
+    pub fn compute<T>(items: &[Arc<T>], content: &ExtractorFieldValue<T>) -> Self
+    where
+        T: Send + Sync,
+    {
+        Self::from_chunk_hashes(items.len(), &Self::chunk_hashes(items, content))
+    }
+
+    // Хеши чанков по FINGERPRINT_CHUNK строк
+    pub(crate) fn chunk_hashes<T>(items: &[Arc<T>], content: &ExtractorFieldValue<T>) -> Vec<u64>
+    where
+        T: Send + Sync,
+    {
+        items
+            .par_chunks(FINGERPRINT_CHUNK)
+            .map(|chunk| {
+                let mut hasher = DefaultHasher::new();
+                for item in chunk {
+                    content(item).hash(&mut hasher);
+                }
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    // Хеши чанков после дописывания строк items[start..] (push): пересчитываются
+    // только последний неполный чанк и новые, начало данных не читается
+    pub(crate) fn extend_chunk_hashes<T>(
+        chunk_hashes: &mut Vec<u64>,
+        items: &[Arc<T>],
+        start: usize,
+        content: &ExtractorFieldValue<T>,
+    ) where
+        T: Send + Sync,
+    {
+        let first = start / FINGERPRINT_CHUNK;
+        chunk_hashes.truncate(first);
+        chunk_hashes.extend(Self::chunk_hashes(&items[first * FINGERPRINT_CHUNK..], content));
+    }
+
+    // Отпечаток из хешей чанков по FINGERPRINT_CHUNK строк (в порядке чанков)
+    pub(crate) fn from_chunk_hashes(rows: usize, chunk_hashes: &[u64]) -> Self {
+        // Порядок чанков сохраняется - перестановка строк меняет отпечаток
+        let mut hasher = DefaultHasher::new();
+        rows.hash(&mut hasher);
+        chunk_hashes.hash(&mut hasher);
+        Self {
+            rows,
+            hash: hasher.finish(),
+        }
+    }
+}
+
+// Выборочная сверка индекса с extractor-ом: строка на позиции pos
+// должна лежать в bitmap-е своего значения (или в тексте индекса).
+pub fn spot_check<T>(
+    index: &IndexType<T>,
+    items: &[Arc<T>],
+    content: &ExtractorFieldValue<T>,
+    removed: &RoaringBitmap,
+    sample_size: usize,
+) -> bool
+where
+    T: Send + Sync + 'static,
+{
+    let step = (items.len() / sample_size.max(1)).max(1);
+    items
+        .iter()
+        .enumerate()
+        // Удаленных строк (retain / remove_indices) в индексе нет
+        .filter(|(pos, _)| !removed.contains(*pos as u32))
+        .step_by(step)
+        .take(sample_size)
+        .all(|(pos, item)| {
+            let value = content(item);
+            match index {
+                IndexType::Field((field_index, _)) => {
+                    // Для nullable индекса extractor отдает Null - позицию ищем среди null-ов
+                    if field_index.nulls().contains(pos as u32) {
+                        return true;
+                    }
+                    field_index
+                        .filter_operation(&FieldOperation::Eq(value))
+                        .is_ok_and(|bitmap| bitmap.contains(pos as u32))
+                }
+                // Text индекс хранит тексты после нормализации анализатором
+                IndexType::Text(text_index) => match value {
+                    FieldValue::String(text) => text_index.get_text(pos) == Some(text_index.analyzer().normalize(&text).as_str()),
+                    _ => false,
+                },
+            }
+        })
+}
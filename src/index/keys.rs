@@ -0,0 +1,120 @@
+use super::{
+    ExtractorFieldValue,
+    field::FieldValue,
+};
+use ahash::{AHashMap, AHashSet};
+use parking_lot::RwLock;
+use roaring::RoaringBitmap;
+use std::sync::Arc;
+
+// Таблица первичных ключей: индекс строки в хранилище <-> стабильный ключ.
+// Позиционные индексы меняются при перезагрузке/компактизации данных,
+// ключи - нет, поэтому состояние выборки можно сохранить в ключах.
+// Extractor хранится вместе с таблицей: push дописывает ключи новых строк
+pub struct KeyTable<T>
+where
+    T: Send + Sync,
+{
+    extractor: ExtractorFieldValue<T>,
+    maps: RwLock<KeyMaps>,
+}
+
+#[derive(Default)]
+struct KeyMaps {
+    keys: AHashMap<u32, FieldValue>,
+    rows: AHashMap<FieldValue, u32>,
+}
+
+impl<T> KeyTable<T>
+where
+    T: Send + Sync,
+{
+    // ids[i] - индекс хранилища строки items[i];
+    // Err - все повторяющиеся ключи в порядке первого повтора
+    pub fn build(
+        items: &[Arc<T>],
+        ids: impl Iterator<Item = usize>,
+        extractor: ExtractorFieldValue<T>,
+    ) -> Result<Self, Vec<FieldValue>> {
+        let mut maps = KeyMaps {
+            keys: AHashMap::with_capacity(items.len()),
+            rows: AHashMap::with_capacity(items.len()),
+        };
+        let mut duplicates = Vec::new();
+        let mut reported = AHashSet::new();
+        for (item, id) in items.iter().zip(ids) {
+            let key = extractor(item);
+            if maps.rows.contains_key(&key) {
+                if reported.insert(key.clone()) {
+                    duplicates.push(key);
+                }
+                continue;
+            }
+            maps.rows.insert(key.clone(), id as u32);
+            maps.keys.insert(id as u32, key);
+        }
+        if !duplicates.is_empty() {
+            return Err(duplicates);
+        }
+        Ok(Self { extractor, maps: RwLock::new(maps) })
+    }
+
+    // Ключи новых строк для append; Err - ключи, которые уже есть
+    // в таблице или повторяются среди новых строк
+    pub fn new_keys(&self, items: &[Arc<T>]) -> Result<Vec<FieldValue>, Vec<FieldValue>> {
+        let maps = self.maps.read();
+        let mut seen = AHashSet::with_capacity(items.len());
+        let mut duplicates = Vec::new();
+        let mut reported = AHashSet::new();
+        let keys: Vec<FieldValue> = items.iter().map(|item| (self.extractor)(item)).collect();
+        for key in &keys {
+            if (maps.rows.contains_key(key) || !seen.insert(key)) && reported.insert(key) {
+                duplicates.push(key.clone());
+            }
+        }
+        if !duplicates.is_empty() {
+            return Err(duplicates);
+        }
+        Ok(keys)
+    }
+
+    // Дописать ключи строк start, start + 1, ... (проверенные new_keys)
+    pub fn append(&self, start: usize, keys: Vec<FieldValue>) {
+        let mut maps = self.maps.write();
+        for (offset, key) in keys.into_iter().enumerate() {
+            let id = (start + offset) as u32;
+            maps.rows.insert(key.clone(), id);
+            maps.keys.insert(id, key);
+        }
+    }
+
+    // Убрать удаленные строки
+    pub fn remove_rows(&self, removed: &RoaringBitmap) {
+        let mut maps = self.maps.write();
+        for id in removed {
+            if let Some(key) = maps.keys.remove(&id) {
+                maps.rows.remove(&key);
+            }
+        }
+    }
+
+    pub fn key(&self, id: usize) -> Option<FieldValue> {
+        self.maps.read().keys.get(&(id as u32)).cloned()
+    }
+
+    pub fn row(&self, key: &FieldValue) -> Option<usize> {
+        self.maps.read().rows.get(key).map(|&id| id as usize)
+    }
+
+    // Ключи строк в порядке ids; строки без ключа пропускаются
+    pub fn keys_of(&self, ids: &[usize]) -> Vec<FieldValue> {
+        let maps = self.maps.read();
+        ids.iter().filter_map(|&id| maps.keys.get(&(id as u32)).cloned()).collect()
+    }
+
+    // Индексы хранилища для ключей; неизвестные ключи пропускаются
+    pub fn rows_of(&self, keys: &[FieldValue]) -> RoaringBitmap {
+        let maps = self.maps.read();
+        keys.iter().filter_map(|key| maps.rows.get(key).copied()).collect()
+    }
+}
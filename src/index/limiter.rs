@@ -0,0 +1,157 @@
+use crate::{
+    errors::{FilterDataError, GLobalError},
+    result::GlobalResult,
+};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+// Фильтр предикатом по выборке от этого размера считается тяжелым
+pub const HEAVY_FILTER_THRESHOLD: usize = 10_000;
+
+/// Что делать с тяжелой операцией, когда все слоты заняты
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    // Ждать в очереди (FIFO) без ограничения по времени
+    Wait,
+    // Сразу вернуть ошибку Overloaded
+    Reject,
+    // Ждать в очереди не дольше указанного времени
+    Timeout(Duration),
+}
+
+/// Состояние ограничителя тяжелых операций
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimiterStats {
+    pub permits: usize,
+    // Выполняются сейчас
+    pub active: usize,
+    // Ждут в очереди
+    pub queued: usize,
+    // Всего пропущено операций
+    pub acquired: u64,
+    pub rejected: u64,
+    pub timed_out: u64,
+}
+
+struct LimiterState {
+    active: usize,
+    // Билеты ожидающих в порядке прихода
+    queue: VecDeque<u64>,
+    next_ticket: u64,
+}
+
+// Семафор с честной очередью: новый запрос не обгоняет ожидающих,
+// даже если слот освободился в момент его прихода
+pub(crate) struct ConcurrencyLimiter {
+    permits: usize,
+    policy: QueuePolicy,
+    state: Mutex<LimiterState>,
+    released: Condvar,
+    acquired: AtomicU64,
+    rejected: AtomicU64,
+    timed_out: AtomicU64,
+}
+
+// Слот освобождается при drop
+pub(crate) struct LimiterPermit {
+    limiter: Arc<ConcurrencyLimiter>,
+}
+
+impl Drop for LimiterPermit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock();
+        state.active -= 1;
+        drop(state);
+        self.limiter.released.notify_all();
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub(crate) fn new(permits: usize, policy: QueuePolicy) -> Self {
+        Self {
+            permits: permits.max(1),
+            policy,
+            state: Mutex::new(LimiterState {
+                active: 0,
+                queue: VecDeque::new(),
+                next_ticket: 0,
+            }),
+            released: Condvar::new(),
+            acquired: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            timed_out: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn acquire(self: &Arc<Self>) -> GlobalResult<LimiterPermit> {
+        let mut state = self.state.lock();
+        if state.queue.is_empty() && state.active < self.permits {
+            return Ok(self.grant(&mut state));
+        }
+        let timeout = match self.policy {
+            QueuePolicy::Reject => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(GLobalError::FilterData(FilterDataError::Overloaded {
+                    permits: self.permits,
+                    queued: state.queue.len(),
+                }));
+            }
+            QueuePolicy::Timeout(timeout) => Some(timeout),
+            QueuePolicy::Wait => None,
+        };
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(ticket);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if state.queue.front() == Some(&ticket) && state.active < self.permits {
+                state.queue.pop_front();
+                let permit = self.grant(&mut state);
+                // Свободных слотов может быть несколько - будим следующего
+                self.released.notify_all();
+                return Ok(permit);
+            }
+            match (deadline, timeout) {
+                (Some(deadline), Some(timeout)) => {
+                    if Instant::now() >= deadline {
+                        state.queue.retain(|waiting| *waiting != ticket);
+                        self.timed_out.fetch_add(1, Ordering::Relaxed);
+                        // Ушедший из головы очереди пропускает следующего
+                        self.released.notify_all();
+                        return Err(GLobalError::FilterData(FilterDataError::QueueTimeout { timeout }));
+                    }
+                    self.released.wait_until(&mut state, deadline);
+                }
+                _ => self.released.wait(&mut state),
+            }
+        }
+    }
+
+    fn grant(self: &Arc<Self>, state: &mut LimiterState) -> LimiterPermit {
+        state.active += 1;
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+        LimiterPermit {
+            limiter: Arc::clone(self),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> LimiterStats {
+        let state = self.state.lock();
+        LimiterStats {
+            permits: self.permits,
+            active: state.active,
+            queued: state.queue.len(),
+            acquired: self.acquired.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+        }
+    }
+}
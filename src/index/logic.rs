@@ -0,0 +1,205 @@
+use super::bit::Op;
+use super::pool;
+use roaring::RoaringBitmap;
+
+/// Режим вычисления операций над nullable полями
+///
+/// # Example
+///
+/// data.create_nullable_field_index("discount", |order| order.discount)?;
+/// data.set_null_mode(NullMode::ThreeValued);
+/// // строки без discount не попадают ни в "= 0", ни в "NOT (= 0)"
+/// data.filter_by_field_ops("discount", &[(FieldOperation::not_eq(0u32), Op::And)])?;
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullMode {
+    // Null не совпадает ни с одним значением, но попадает в отрицания
+    // (NotEq/NotIn/Invert) - поведение по умолчанию
+    #[default]
+    TwoValued,
+    // Логика SQL: сравнение с null - Unknown, в выборку попадает только True
+    ThreeValued,
+}
+
+/// Значение трехзначной логики (Kleene / SQL)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truth {
+    False,
+    Unknown,
+    True,
+}
+
+impl Truth {
+    pub fn from_bool(value: bool) -> Self {
+        if value { Self::True } else { Self::False }
+    }
+
+    pub fn is_true(self) -> bool {
+        self == Self::True
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::False, _) | (_, Self::False) => Self::False,
+            (Self::True, Self::True) => Self::True,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::True, _) | (_, Self::True) => Self::True,
+            (Self::False, Self::False) => Self::False,
+            _ => Self::Unknown,
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        match self {
+            Self::True => Self::False,
+            Self::False => Self::True,
+            Self::Unknown => Self::Unknown,
+        }
+    }
+
+    pub fn xor(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
+            (left, right) => Self::from_bool(left != right),
+        }
+    }
+
+    // Связка с очередной операцией - та же семантика Op, что и у bitmap-ов
+    pub fn combine(self, op: Op, other: Self) -> Self {
+        match op {
+            Op::And => self.and(other),
+            Op::Or => self.or(other),
+            Op::AndNot => self.and(other.not()),
+            Op::Xor => self.xor(other),
+            Op::Invert => self.not(),
+        }
+    }
+}
+
+/// Результат bitmap-планирования в трехзначной логике
+///
+/// Строки из `true_bits` - True, из `unknown_bits` - Unknown,
+/// остальные строки universe - False.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriBitmap {
+    pub true_bits: RoaringBitmap,
+    pub unknown_bits: RoaringBitmap,
+}
+
+impl TriBitmap {
+    pub fn new(true_bits: RoaringBitmap, unknown_bits: RoaringBitmap) -> Self {
+        let unknown_bits = unknown_bits - &true_bits;
+        Self { true_bits, unknown_bits }
+    }
+
+    // Результат без Unknown (поле без null)
+    pub fn known(true_bits: RoaringBitmap) -> Self {
+        Self {
+            true_bits,
+            unknown_bits: RoaringBitmap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.true_bits.is_empty() && self.unknown_bits.is_empty()
+    }
+
+    // Строки, которые еще могут стать True (True + Unknown)
+    pub fn maybe_bits(&self) -> RoaringBitmap {
+        &self.true_bits | &self.unknown_bits
+    }
+
+    pub fn false_bits(&self, universe: &RoaringBitmap) -> RoaringBitmap {
+        universe - &self.maybe_bits()
+    }
+
+    pub fn and(&self, other: &Self) -> Self {
+        let true_bits = &self.true_bits & &other.true_bits;
+        let unknown_bits = self.maybe_bits() & other.maybe_bits();
+        Self::new(true_bits, unknown_bits)
+    }
+
+    pub fn or(&self, other: &Self) -> Self {
+        let true_bits = &self.true_bits | &other.true_bits;
+        let unknown_bits = &self.unknown_bits | &other.unknown_bits;
+        Self::new(true_bits, unknown_bits)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(&self, universe: &RoaringBitmap) -> Self {
+        Self::new(self.false_bits(universe), self.unknown_bits.clone())
+    }
+
+    pub fn xor(&self, other: &Self, universe: &RoaringBitmap) -> Self {
+        let true_bits = (&self.true_bits & other.false_bits(universe))
+            | (self.false_bits(universe) & &other.true_bits);
+        let unknown_bits = &self.unknown_bits | &other.unknown_bits;
+        Self::new(true_bits, unknown_bits)
+    }
+
+    pub fn combine(&self, op: Op, other: &Self, universe: &RoaringBitmap) -> Self {
+        match op {
+            Op::And => self.and(other),
+            Op::Or => self.or(other),
+            Op::AndNot => self.and(&other.not(universe)),
+            Op::Xor => self.xor(other, universe),
+            Op::Invert => self.not(universe),
+        }
+    }
+
+    // Вернуть bitmap-ы промежуточного результата в пул потока
+    pub fn recycle(self) {
+        pool::recycle(self.true_bits);
+        pool::recycle(self.unknown_bits);
+    }
+
+    // Внешний WHERE: Unknown отбрасывается
+    pub fn resolve(self) -> RoaringBitmap {
+        self.true_bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truth_follows_kleene_logic() {
+        assert_eq!(Truth::Unknown.and(Truth::False), Truth::False);
+        assert_eq!(Truth::Unknown.and(Truth::True), Truth::Unknown);
+        assert_eq!(Truth::Unknown.or(Truth::True), Truth::True);
+        assert_eq!(Truth::Unknown.or(Truth::False), Truth::Unknown);
+        assert_eq!(Truth::Unknown.not(), Truth::Unknown);
+        assert_eq!(Truth::True.combine(Op::AndNot, Truth::Unknown), Truth::Unknown);
+    }
+
+    #[test]
+    fn tri_bitmap_matches_truth_per_row() {
+        let universe: RoaringBitmap = (0..9).collect();
+        // Все 9 сочетаний (left, right) по строкам 0..9
+        let truth = |bits: &TriBitmap, row: u32| {
+            if bits.true_bits.contains(row) {
+                Truth::True
+            } else if bits.unknown_bits.contains(row) {
+                Truth::Unknown
+            } else {
+                Truth::False
+            }
+        };
+        let left = TriBitmap::new([0, 1, 2].into_iter().collect(), [3, 4, 5].into_iter().collect());
+        let right = TriBitmap::new([0, 3, 6].into_iter().collect(), [1, 4, 7].into_iter().collect());
+        for op in [Op::And, Op::Or, Op::AndNot, Op::Xor, Op::Invert] {
+            let result = left.combine(op, &right, &universe);
+            for row in universe.iter() {
+                let expected = truth(&left, row).combine(op, truth(&right, row));
+                assert_eq!(truth(&result, row), expected, "{:?} row {}", op, row);
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use super::field::{FieldOperation, FieldValue, TypeConvert};
+use rust_decimal::{Decimal, RoundingStrategy};
+#[cfg(feature = "unicode")]
+use unicode_normalization::UnicodeNormalization;
+
+/// Нормализация строкового field индекса
+///
+/// Значения строк и операнды запросов приводятся одинаково, поэтому
+/// `eq("ERROR")` находит `"error"` без нормализации в extractor-е и в запросах.
+/// Порядок: trim, unicode NFC (feature = "unicode"), lowercase, ключ collation.
+///
+/// # Example
+///
+/// data.create_field_index_with_options("level", |log: &Log| log.level.clone(), StringIndexOptions::case_insensitive())?;
+/// data.filter_by_field_ops("level", &[(FieldOperation::eq("ERROR"), Op::And)])?;
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StringIndexOptions {
+    pub lowercase: bool,
+    pub trim: bool,
+    // Требует feature = "unicode"
+    pub unicode_normalize: bool,
+    pub collation: Collation,
+}
+
+/// Порядок строк индекса (range запросы, sorted_values, сортировка)
+///
+/// Индекс хранит ключ сортировки, побайтовый порядок которого совпадает
+/// с порядком collation; строки с одинаковым ключом равны (как при lowercase).
+/// Значения индекса (values, top_values) - это ключи.
+///
+/// # Example
+///
+/// // "file2" < "file10", "Zeta" после "alpha"
+/// data.create_field_index_with_options("name", |f: &File| f.name.clone(), StringIndexOptions::natural())?;
+/// data.filter_by_field_ops("name", &[(FieldOperation::range("file1", "file9"), Op::And)])?;
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Collation {
+    // Побайтовый порядок UTF-8 ("Z" < "a")
+    #[default]
+    Binary,
+    // Без учета регистра
+    CaseInsensitive,
+    // Без учета регистра, числа внутри строки сравниваются как числа
+    Natural,
+    // Пользовательский ключ сортировки (например, sort key ICU collator-а)
+    Custom(fn(&str) -> String),
+}
+
+impl PartialEq for Collation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Custom(left), Self::Custom(right)) => std::ptr::fn_addr_eq(*left, *right),
+            (left, right) => std::mem::discriminant(left) == std::mem::discriminant(right),
+        }
+    }
+}
+
+impl Eq for Collation {}
+
+impl Collation {
+    pub fn sort_key(&self, value: &str) -> String {
+        match self {
+            Self::Binary => value.to_string(),
+            Self::CaseInsensitive => value.to_lowercase(),
+            Self::Natural => Self::natural_key(&value.to_lowercase()),
+            Self::Custom(key) => key(value),
+        }
+    }
+
+    // Части строки (префикс, подстрока): числа не дополняются - ключ известен только для целой строки
+    fn pattern_key(&self, value: &str) -> String {
+        match self {
+            Self::CaseInsensitive | Self::Natural => value.to_lowercase(),
+            Self::Binary | Self::Custom(_) => value.to_string(),
+        }
+    }
+
+    // Каждая последовательность цифр без ведущих нулей предваряется своей длиной
+    // (четыре цифры): короткое число меньше длинного, равные по длине - побайтово
+    fn natural_key(value: &str) -> String {
+        let mut key = String::with_capacity(value.len() + 8);
+        let mut digits = String::new();
+        let flush = |key: &mut String, digits: &mut String| {
+            if digits.is_empty() {
+                return;
+            }
+            let number = digits.trim_start_matches('0');
+            let number = if number.is_empty() { "0" } else { number };
+            key.push_str(&format!("{:04}", number.len().min(9_999)));
+            key.push_str(number);
+            digits.clear();
+        };
+        for ch in value.chars() {
+            if ch.is_ascii_digit() {
+                digits.push(ch);
+            } else {
+                flush(&mut key, &mut digits);
+                key.push(ch);
+            }
+        }
+        flush(&mut key, &mut digits);
+        key
+    }
+}
+
+impl StringIndexOptions {
+    pub fn case_insensitive() -> Self {
+        Self {
+            lowercase: true,
+            trim: true,
+            unicode_normalize: false,
+            collation: Collation::Binary,
+        }
+    }
+
+    // Натуральный порядок: "file2" < "file10", "v1.9" < "v1.10", без учета регистра
+    pub fn natural() -> Self {
+        Self {
+            lowercase: false,
+            trim: true,
+            unicode_normalize: false,
+            collation: Collation::Natural,
+        }
+    }
+
+    pub fn with_lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn with_unicode_normalize(mut self, unicode_normalize: bool) -> Self {
+        self.unicode_normalize = unicode_normalize;
+        self
+    }
+
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    pub fn is_noop(&self) -> bool {
+        !self.lowercase && !self.trim && !self.unicode_normalize && self.collation == Collation::Binary
+    }
+
+    pub fn normalize(&self, value: &str) -> String {
+        let value = if self.trim { value.trim() } else { value };
+        self.collation.sort_key(&self.normalize_text(value))
+    }
+
+    fn normalize_pattern(&self, value: &str) -> String {
+        self.collation.pattern_key(&self.normalize_text(value))
+    }
+
+    // Части строки (префикс, подстрока) не обрезаются: пробел в них значим
+    fn normalize_text(&self, value: &str) -> String {
+        #[cfg(feature = "unicode")]
+        let normalized: String = if self.unicode_normalize {
+            value.nfc().collect()
+        } else {
+            value.to_string()
+        };
+        #[cfg(not(feature = "unicode"))]
+        let normalized = value.to_string();
+        if self.lowercase {
+            normalized.to_lowercase()
+        } else {
+            normalized
+        }
+    }
+
+    fn normalize_value(&self, value: &FieldValue) -> FieldValue {
+        match value {
+            FieldValue::String(value) => FieldValue::String(self.normalize(value)),
+            value => value.clone(),
+        }
+    }
+
+    fn normalize_values(&self, values: &[FieldValue]) -> Vec<FieldValue> {
+        values.iter().map(|value| self.normalize_value(value)).collect()
+    }
+
+    // Операнды запроса приводятся так же, как значения индекса (Regex - без изменений)
+    pub fn normalize_operation(&self, operation: &FieldOperation) -> FieldOperation {
+        match operation {
+            FieldOperation::Eq(value) => FieldOperation::Eq(self.normalize_value(value)),
+            FieldOperation::NotEq(value) => FieldOperation::NotEq(self.normalize_value(value)),
+            FieldOperation::Gt(value) => FieldOperation::Gt(self.normalize_value(value)),
+            FieldOperation::Gte(value) => FieldOperation::Gte(self.normalize_value(value)),
+            FieldOperation::Lt(value) => FieldOperation::Lt(self.normalize_value(value)),
+            FieldOperation::Lte(value) => FieldOperation::Lte(self.normalize_value(value)),
+            FieldOperation::In(values) => FieldOperation::In(self.normalize_values(values)),
+            FieldOperation::NotIn(values) => FieldOperation::NotIn(self.normalize_values(values)),
+            FieldOperation::Range(start, end) => {
+                FieldOperation::Range(self.normalize_value(start), self.normalize_value(end))
+            }
+            FieldOperation::ContainsAny(values) => FieldOperation::ContainsAny(self.normalize_values(values)),
+            FieldOperation::ContainsAll(values) => FieldOperation::ContainsAll(self.normalize_values(values)),
+            FieldOperation::StartsWith(prefix) => FieldOperation::StartsWith(self.normalize_pattern(prefix)),
+            FieldOperation::EndsWith(suffix) => FieldOperation::EndsWith(self.normalize_pattern(suffix)),
+            FieldOperation::ContainsSubstr(substr) => FieldOperation::ContainsSubstr(self.normalize_pattern(substr)),
+            operation => operation.clone(),
+        }
+    }
+}
+
+/// Масштаб Decimal field индекса
+///
+/// Значения и числовые операнды запросов округляются до `scale` знаков
+/// одной политикой, поэтому `eq(1.1)` находит строки с 1.10, 1.100 и 1.104
+/// (при HalfEven), а в индексе и выдаче хранится 1.10.
+///
+/// # Example
+///
+/// data.create_decimal_index_with_scale("amount", |p: &Payment| p.amount, DecimalIndexOptions::money())?;
+/// data.filter_by_field_ops("amount", &[(FieldOperation::eq(Decimal::new(11, 1)), Op::And)])?;
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalIndexOptions {
+    pub scale: u32,
+    pub rounding: DecimalRounding,
+}
+
+/// Политика округления Decimal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecimalRounding {
+    // Банковское округление: 0.125 -> 0.12, 0.135 -> 0.14
+    #[default]
+    HalfEven,
+    // Половина - от нуля: 0.125 -> 0.13
+    HalfUp,
+    // Отбросить лишние знаки: 0.129 -> 0.12
+    Truncate,
+}
+
+impl DecimalRounding {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            Self::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Self::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Self::Truncate => RoundingStrategy::ToZero,
+        }
+    }
+}
+
+impl DecimalIndexOptions {
+    pub fn new(scale: u32, rounding: DecimalRounding) -> Self {
+        Self { scale, rounding }
+    }
+
+    // Денежные суммы: два знака, банковское округление
+    pub fn money() -> Self {
+        Self::new(2, DecimalRounding::HalfEven)
+    }
+
+    // Округлить и привести к ровно scale знакам (1.1 -> 1.10)
+    pub fn round(&self, value: Decimal) -> Decimal {
+        let mut rounded = value.round_dp_with_strategy(self.scale, self.rounding.strategy());
+        rounded.rescale(self.scale);
+        rounded
+    }
+
+    // Числовые операнды приводятся к Decimal масштаба индекса, остальные - без изменений
+    fn round_value(&self, value: &FieldValue) -> FieldValue {
+        match value {
+            FieldValue::Decimal(_) | FieldValue::F64(_) | FieldValue::F32(_) => value
+                .try_to_decimal()
+                .map(|decimal| FieldValue::Decimal(self.round(decimal)))
+                .unwrap_or_else(|| value.clone()),
+            value => value.clone(),
+        }
+    }
+
+    fn round_values(&self, values: &[FieldValue]) -> Vec<FieldValue> {
+        values.iter().map(|value| self.round_value(value)).collect()
+    }
+
+    pub fn normalize_operation(&self, operation: &FieldOperation) -> FieldOperation {
+        match operation {
+            FieldOperation::Eq(value) => FieldOperation::Eq(self.round_value(value)),
+            FieldOperation::NotEq(value) => FieldOperation::NotEq(self.round_value(value)),
+            FieldOperation::Gt(value) => FieldOperation::Gt(self.round_value(value)),
+            FieldOperation::Gte(value) => FieldOperation::Gte(self.round_value(value)),
+            FieldOperation::Lt(value) => FieldOperation::Lt(self.round_value(value)),
+            FieldOperation::Lte(value) => FieldOperation::Lte(self.round_value(value)),
+            FieldOperation::In(values) => FieldOperation::In(self.round_values(values)),
+            FieldOperation::NotIn(values) => FieldOperation::NotIn(self.round_values(values)),
+            FieldOperation::Range(start, end) => {
+                FieldOperation::Range(self.round_value(start), self.round_value(end))
+            }
+            FieldOperation::ContainsAny(values) => FieldOperation::ContainsAny(self.round_values(values)),
+            FieldOperation::ContainsAll(values) => FieldOperation::ContainsAll(self.round_values(values)),
+            operation => operation.clone(),
+        }
+    }
+}
+
+/// Обработка NaN во float field индексе
+///
+/// OrderedFloat считает NaN наибольшим значением, поэтому без политики
+/// NaN попадает в `gt`/`gte`/`range` до +inf. Inf - обычные значения.
+///
+/// # Example
+///
+/// data.create_float_index_with_policy("score", |m: &Metric| m.score, FloatPolicy::NaNIsNull)?;
+/// data.filter_by_field_ops("score", &[(FieldOperation::is_null(), Op::And)])?;   // строки с NaN
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloatPolicy {
+    // NaN - отдельное значение больше +inf (поведение OrderedFloat)
+    #[default]
+    NaNLargest,
+    // NaN хранится в nulls: не совпадает ни с одним сравнением, выбирается IsNull
+    NaNIsNull,
+    // Индекс не создается, если в данных есть NaN
+    RejectNaN,
+}
@@ -0,0 +1,105 @@
+use super::{
+    IndexType,
+    field::IndexFieldEnum,
+    integrity::IndexFingerprint,
+    text::{NgramDictionary, TextIndex, TextIndexSnapshot},
+    ExtractorFieldValue,
+};
+use roaring::RoaringBitmap;
+use std::sync::Arc;
+
+// Версия формата файла save_indexes - при несовпадении файл не загружается
+pub const INDEX_SNAPSHOT_VERSION: u32 = 2;
+
+// Файл сохраненных индексов FilterData
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct IndexSnapshot {
+    pub version: u32,
+    pub indexes: Vec<SavedIndex>,
+}
+
+// Сохраненный индекс: extractor-ы не сериализуются, поэтому индекс
+// подхватывается при create_* с тем же именем, если совпал fingerprint данных
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SavedIndex {
+    pub name: String,
+    pub fingerprint: IndexFingerprint,
+    // Удаленные строки (retain / remove_indices) на момент сохранения
+    pub removed: RoaringBitmap,
+    pub body: SavedIndexBody,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum SavedIndexBody {
+    Field(IndexFieldEnum),
+    Text(TextIndexSnapshot),
+}
+
+impl SavedIndex {
+    pub fn from_index<T>(
+        name: &str,
+        index: &IndexType<T>,
+        fingerprint: IndexFingerprint,
+        removed: RoaringBitmap,
+    ) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        let body = match index {
+            IndexType::Field((field_index, _)) => SavedIndexBody::Field(field_index.clone()),
+            IndexType::Text(text_index) => SavedIndexBody::Text(text_index.to_snapshot()),
+        };
+        Self {
+            name: name.to_string(),
+            fingerprint,
+            removed,
+            body,
+        }
+    }
+
+    // Восстановить индекс, если он совместим с probe - индексом того же
+    // рецепта на пустом наборе строк (тип значений, IndexKind, параметры text)
+    pub fn restore<T>(
+        self,
+        probe: &IndexType<T>,
+        content: &ExtractorFieldValue<T>,
+        dictionary: &Arc<NgramDictionary>,
+    ) -> Option<IndexType<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        match (self.body, probe) {
+            (SavedIndexBody::Field(field_index), IndexType::Field((probe, _))) => {
+                (field_index.type_name() == probe.type_name() && field_index.kind() == probe.kind())
+                    .then(|| IndexType::Field((field_index, Arc::clone(content))))
+            }
+            (SavedIndexBody::Text(snapshot), IndexType::Text(probe)) => {
+                let text_index = TextIndex::from_snapshot(snapshot, Arc::clone(dictionary));
+                text_index.same_options(probe).then_some(IndexType::Text(text_index))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IndexSnapshot {
+    pub fn write_to(&self, path: &std::path::Path) -> Result<(), String> {
+        let file = std::fs::File::create(path).map_err(|err| err.to_string())?;
+        let mut writer = std::io::BufWriter::new(file);
+        bincode::serialize_into(&mut writer, self).map_err(|err| err.to_string())?;
+        std::io::Write::flush(&mut writer).map_err(|err| err.to_string())
+    }
+
+    pub fn read_from(path: &std::path::Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+        let snapshot: Self = bincode::deserialize_from(std::io::BufReader::new(file))
+            .map_err(|err| err.to_string())?;
+        if snapshot.version != INDEX_SNAPSHOT_VERSION {
+            return Err(format!(
+                "unsupported snapshot version {}, expected {INDEX_SNAPSHOT_VERSION}",
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,95 @@
+use roaring::RoaringBitmap;
+use std::cell::{Cell, RefCell};
+
+// Сколько очищенных bitmap-ов держит пул одного потока
+pub const BITMAP_POOL_CAPACITY: usize = 16;
+
+thread_local! {
+    static POOL: RefCell<Vec<RoaringBitmap>> = const { RefCell::new(Vec::new()) };
+    static REUSED: Cell<u64> = const { Cell::new(0) };
+    static ALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Статистика пула bitmap-ов текущего потока
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BitmapPoolStats {
+    // Выдано из пула повторно
+    pub reused: u64,
+    // Пул был пуст - создан новый bitmap
+    pub allocated: u64,
+    // Лежит в пуле сейчас
+    pub pooled: usize,
+}
+
+// Пустой bitmap для промежуточного результата: из пула или новый
+pub fn take() -> RoaringBitmap {
+    match POOL.with(|pool| pool.borrow_mut().pop()) {
+        Some(bitmap) => {
+            REUSED.with(|reused| reused.set(reused.get() + 1));
+            bitmap
+        }
+        None => {
+            ALLOCATED.with(|allocated| allocated.set(allocated.get() + 1));
+            RoaringBitmap::new()
+        }
+    }
+}
+
+// Копия bitmap-а в буфере из пула
+pub fn take_copy(source: &RoaringBitmap) -> RoaringBitmap {
+    let mut bitmap = take();
+    bitmap |= source;
+    bitmap
+}
+
+// Вернуть промежуточный bitmap: очищается, лишние (сверх емкости) освобождаются
+pub fn recycle(mut bitmap: RoaringBitmap) {
+    bitmap.clear();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < BITMAP_POOL_CAPACITY {
+            pool.push(bitmap);
+        }
+    });
+}
+
+/// Статистика пула промежуточных bitmap-ов текущего потока
+/// 
+/// # Example
+/// 
+/// data.filter_query(&expr)?;
+/// let stats = bitmap_pool_stats();
+/// println!("reused {} / allocated {}", stats.reused, stats.allocated);
+/// 
+pub fn bitmap_pool_stats() -> BitmapPoolStats {
+    BitmapPoolStats {
+        reused: REUSED.with(Cell::get),
+        allocated: ALLOCATED.with(Cell::get),
+        pooled: POOL.with(|pool| pool.borrow().len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recycled_bitmap_is_reused_empty() {
+        let mut bitmap = take();
+        bitmap.insert_range(0..1_000);
+        recycle(bitmap);
+        let before = bitmap_pool_stats();
+        let bitmap = take();
+        assert!(bitmap.is_empty());
+        assert_eq!(bitmap_pool_stats().reused, before.reused + 1);
+        assert_eq!(bitmap_pool_stats().pooled, before.pooled - 1);
+    }
+
+    #[test]
+    fn pool_is_bounded() {
+        for _ in 0..BITMAP_POOL_CAPACITY * 2 {
+            recycle(RoaringBitmap::new());
+        }
+        assert_eq!(bitmap_pool_stats().pooled, BITMAP_POOL_CAPACITY);
+    }
+}
@@ -0,0 +1,180 @@
+use super::{
+    bit::Op,
+    field::{FieldOperation, FieldValue},
+};
+use roaring::RoaringBitmap;
+use std::ops::{
+    Bound,
+    Range,
+    RangeFrom,
+    RangeInclusive,
+    RangeTo,
+    RangeToInclusive,
+};
+
+/// Диапазон значений для прогрева (warm_ranges)
+///
+/// # Example
+///
+/// data.warm_ranges("price", &[(0..100).into(), (100..500).into(), (500..).into()])?;
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeBucket {
+    pub start: Bound<FieldValue>,
+    pub end: Bound<FieldValue>,
+}
+
+impl RangeBucket {
+    pub fn new(start: Bound<FieldValue>, end: Bound<FieldValue>) -> Self {
+        Self { start, end }
+    }
+
+    // Диапазон из операций запроса (Gt/Gte/Lt/Lte/Range, объединенных через And)
+    pub fn from_operations(operations: &[(FieldOperation, Op)]) -> Option<Self> {
+        let mut start = Bound::Unbounded;
+        let mut end = Bound::Unbounded;
+        for (n, (operation, op)) in operations.iter().enumerate() {
+            if n > 0 && *op != Op::And {
+                return None;
+            }
+            match operation {
+                FieldOperation::Gt(value) => Self::set_bound(&mut start, Bound::Excluded(value.clone()))?,
+                FieldOperation::Gte(value) => Self::set_bound(&mut start, Bound::Included(value.clone()))?,
+                FieldOperation::Lt(value) => Self::set_bound(&mut end, Bound::Excluded(value.clone()))?,
+                FieldOperation::Lte(value) => Self::set_bound(&mut end, Bound::Included(value.clone()))?,
+                FieldOperation::Range(from, to) => {
+                    Self::set_bound(&mut start, Bound::Included(from.clone()))?;
+                    Self::set_bound(&mut end, Bound::Included(to.clone()))?;
+                }
+                _ => return None,
+            }
+        }
+        Some(Self { start, end })
+    }
+
+    // Одна граница задается только один раз - иначе запрос не выровнен с бакетами
+    fn set_bound(target: &mut Bound<FieldValue>, bound: Bound<FieldValue>) -> Option<()> {
+        if !matches!(target, Bound::Unbounded) {
+            return None;
+        }
+        *target = bound;
+        Some(())
+    }
+
+    // Операции, которыми вычисляется bitmap бакета через индекс
+    pub fn to_operations(&self) -> Vec<(FieldOperation, Op)> {
+        let mut operations = Vec::with_capacity(2);
+        match &self.start {
+            Bound::Included(value) => operations.push((FieldOperation::Gte(value.clone()), Op::And)),
+            Bound::Excluded(value) => operations.push((FieldOperation::Gt(value.clone()), Op::And)),
+            Bound::Unbounded => {}
+        }
+        match &self.end {
+            Bound::Included(value) => operations.push((FieldOperation::Lte(value.clone()), Op::And)),
+            Bound::Excluded(value) => operations.push((FieldOperation::Lt(value.clone()), Op::And)),
+            Bound::Unbounded => {}
+        }
+        operations
+    }
+
+    pub fn contains(&self, value: &FieldValue) -> bool {
+        let after_start = match &self.start {
+            Bound::Included(start) => value.gte(start),
+            Bound::Excluded(start) => value.gt(start),
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(end) => value.lte(end),
+            Bound::Excluded(end) => value.lt(end),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+
+    fn same_bound(left: &Bound<FieldValue>, right: &Bound<FieldValue>) -> bool {
+        match (left, right) {
+            (Bound::Included(a), Bound::Included(b)) |
+            (Bound::Excluded(a), Bound::Excluded(b)) => a.eq(b),
+            (Bound::Unbounded, Bound::Unbounded) => true,
+            _ => false,
+        }
+    }
+
+    // Бакеты идут встык: [a, x) + [x, b] или [a, x] + (x, b]
+    fn is_adjacent(end: &Bound<FieldValue>, next_start: &Bound<FieldValue>) -> bool {
+        match (end, next_start) {
+            (Bound::Excluded(a), Bound::Included(b)) |
+            (Bound::Included(a), Bound::Excluded(b)) => a.eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<V: Into<FieldValue>> From<Range<V>> for RangeBucket {
+    fn from(range: Range<V>) -> Self {
+        Self::new(Bound::Included(range.start.into()), Bound::Excluded(range.end.into()))
+    }
+}
+
+impl<V: Into<FieldValue>> From<RangeInclusive<V>> for RangeBucket {
+    fn from(range: RangeInclusive<V>) -> Self {
+        let (start, end) = range.into_inner();
+        Self::new(Bound::Included(start.into()), Bound::Included(end.into()))
+    }
+}
+
+impl<V: Into<FieldValue>> From<RangeFrom<V>> for RangeBucket {
+    fn from(range: RangeFrom<V>) -> Self {
+        Self::new(Bound::Included(range.start.into()), Bound::Unbounded)
+    }
+}
+
+impl<V: Into<FieldValue>> From<RangeTo<V>> for RangeBucket {
+    fn from(range: RangeTo<V>) -> Self {
+        Self::new(Bound::Unbounded, Bound::Excluded(range.end.into()))
+    }
+}
+
+impl<V: Into<FieldValue>> From<RangeToInclusive<V>> for RangeBucket {
+    fn from(range: RangeToInclusive<V>) -> Self {
+        Self::new(Bound::Unbounded, Bound::Included(range.end.into()))
+    }
+}
+
+// Прогретые бакеты одного индекса
+pub struct RangeBucketCache {
+    // Версия индекса, для которой посчитаны bitmap-ы
+    pub version: u64,
+    buckets: Vec<(RangeBucket, RoaringBitmap)>,
+}
+
+impl RangeBucketCache {
+    pub fn new(version: u64, buckets: Vec<(RangeBucket, RoaringBitmap)>) -> Self {
+        Self { version, buckets }
+    }
+
+    pub fn buckets(&self) -> Vec<RangeBucket> {
+        self.buckets.iter().map(|(bucket, _)| bucket.clone()).collect()
+    }
+
+    // Ответ из кеша, если запрос совпадает с цепочкой соседних бакетов
+    pub fn lookup(&self, query: &RangeBucket) -> Option<RoaringBitmap> {
+        let first = self.buckets
+            .iter()
+            .position(|(bucket, _)| RangeBucket::same_bound(&bucket.start, &query.start))?;
+        let mut result = RoaringBitmap::new();
+        let mut current = first;
+        // Не больше шагов, чем бакетов - защита от зацикливания
+        for _ in 0..self.buckets.len() {
+            let (bucket, bitmap) = &self.buckets[current];
+            result |= bitmap;
+            if RangeBucket::same_bound(&bucket.end, &query.end) {
+                return Some(result);
+            }
+            current = self.buckets
+                .iter()
+                .position(|(next, _)| RangeBucket::is_adjacent(&bucket.end, &next.start))?;
+        }
+        None
+    }
+}
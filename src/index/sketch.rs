@@ -0,0 +1,173 @@
+use super::field::FieldValue;
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+// Размер чанка для параллельного построения sketch-а
+const SKETCH_CHUNK: usize = 64 * 1024;
+
+/// Частое значение колонки
+///
+/// `count` - верхняя оценка числа строк, `count - error` - гарантированная
+/// нижняя. Для ответа по полному индексу error = 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopValue {
+    pub value: FieldValue,
+    pub count: u64,
+    pub error: u64,
+}
+
+impl TopValue {
+    pub fn guaranteed_count(&self) -> u64 {
+        self.count - self.error
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counter {
+    count: u64,
+    error: u64,
+}
+
+// SpaceSaving: не больше capacity счетчиков, при переполнении
+// вытесняется минимальный, новое значение наследует его count как error.
+pub struct TopValuesSketch {
+    capacity: usize,
+    counters: HashMap<FieldValue, Counter>,
+    // (count, value) - поиск минимального счетчика за O(log capacity)
+    order: BTreeSet<(u64, FieldValue)>,
+    total: u64,
+}
+
+impl TopValuesSketch {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            counters: HashMap::with_capacity(capacity),
+            order: BTreeSet::new(),
+            total: 0,
+        }
+    }
+
+    pub fn build<T, F>(items: &[Arc<T>], extractor: F, capacity: usize) -> Self
+    where
+        T: Send + Sync,
+        F: Fn(&T) -> FieldValue + Send + Sync,
+    {
+        let fill = |chunk: &[Arc<T>]| {
+            let mut sketch = Self::new(capacity);
+            for item in chunk {
+                sketch.update(extractor(item));
+            }
+            sketch
+        };
+        if items.len() > SKETCH_CHUNK {
+            items
+                .par_chunks(SKETCH_CHUNK)
+                .map(fill)
+                .reduce(|| Self::new(capacity), Self::merge)
+        } else {
+            fill(items)
+        }
+    }
+
+    pub fn update(&mut self, value: FieldValue) {
+        self.total += 1;
+        if let Some(counter) = self.counters.get_mut(&value) {
+            self.order.remove(&(counter.count, value.clone()));
+            counter.count += 1;
+            self.order.insert((counter.count, value));
+            return;
+        }
+        let counter = if self.counters.len() < self.capacity {
+            Counter { count: 1, error: 0 }
+        } else {
+            let Some((min_count, min_value)) = self.order.pop_first() else {
+                return;
+            };
+            self.counters.remove(&min_value);
+            Counter { count: min_count + 1, error: min_count }
+        };
+        self.order.insert((counter.count, value.clone()));
+        self.counters.insert(value, counter);
+    }
+
+    // Минимальный счетчик - верхняя граница для значений вне sketch-а
+    fn min_count(&self) -> u64 {
+        if self.counters.len() < self.capacity {
+            return 0;
+        }
+        self.order.first().map(|(count, _)| *count).unwrap_or_default()
+    }
+
+    // Объединение sketch-ей по разным частям данных
+    pub fn merge(self, other: Self) -> Self {
+        let capacity = self.capacity.max(other.capacity);
+        let (self_min, other_min) = (self.min_count(), other.min_count());
+        let mut combined: HashMap<FieldValue, Counter> = HashMap::with_capacity(self.counters.len() + other.counters.len());
+        for (value, counter) in &self.counters {
+            let (count, error) = match other.counters.get(value) {
+                Some(other_counter) => (counter.count + other_counter.count, counter.error + other_counter.error),
+                None => (counter.count + other_min, counter.error + other_min),
+            };
+            combined.insert(value.clone(), Counter { count, error });
+        }
+        for (value, counter) in other.counters {
+            combined.entry(value).or_insert(Counter {
+                count: counter.count + self_min,
+                error: counter.error + self_min,
+            });
+        }
+        let mut entries: Vec<(FieldValue, Counter)> = combined.into_iter().collect();
+        entries.sort_by(|(left_value, left), (right_value, right)| {
+            right.count.cmp(&left.count).then_with(|| left_value.cmp(right_value))
+        });
+        entries.truncate(capacity);
+        let mut result = Self::new(capacity);
+        result.total = self.total + other.total;
+        for (value, counter) in entries {
+            result.order.insert((counter.count, value.clone()));
+            result.counters.insert(value, counter);
+        }
+        result
+    }
+
+    // k самых частых значений по убыванию count
+    pub fn top(&self, k: usize) -> Vec<TopValue> {
+        self.order
+            .iter()
+            .rev()
+            .take(k)
+            .map(|(count, value)| TopValue {
+                value: value.clone(),
+                count: *count,
+                error: self.counters.get(value).map(|counter| counter.error).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn space_saving_finds_heavy_hitters() {
+        // 3 частых значения + длинный хвост уникальных
+        let items: Vec<Arc<u64>> = (0..200_000u64)
+            .map(|i| Arc::new(if i % 10 < 3 { i % 10 } else { 1_000 + i }))
+            .collect();
+        let sketch = TopValuesSketch::build(&items, |x| FieldValue::U64(*x), 64);
+        assert_eq!(sketch.total, 200_000);
+        let top = sketch.top(3);
+        let mut values: Vec<FieldValue> = top.iter().map(|item| item.value.clone()).collect();
+        values.sort();
+        assert_eq!(values, vec![FieldValue::U64(0), FieldValue::U64(1), FieldValue::U64(2)]);
+        for item in &top {
+            assert!(item.guaranteed_count() <= 20_000 && item.count >= 20_000);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use super::{
+    ExtractorFieldValue,
+    field::{FieldOperation, FieldValue},
+};
+use std::sync::Arc;
+
+// Размер выборки для многоколоночной статистики
+pub const ANALYZE_SAMPLE_SIZE: usize = 10_000;
+
+// Совместная статистика нескольких полей по выборке строк.
+// Нужна планировщику: для коррелированных полей (status / error_code)
+// произведение селективностей сильно занижает реальную долю строк.
+pub struct ColumnGroupStats {
+    fields: Vec<String>,
+    rows: Vec<Vec<FieldValue>>,
+}
+
+impl ColumnGroupStats {
+    pub fn build<T>(
+        items: &[Arc<T>],
+        fields: Vec<String>,
+        extractors: &[ExtractorFieldValue<T>],
+        sample_size: usize,
+    ) -> Self
+    where
+        T: Send + Sync,
+    {
+        let step = (items.len() / sample_size.max(1)).max(1);
+        // Равномерная детерминированная выборка
+        let rows = items
+            .iter()
+            .step_by(step)
+            .take(sample_size)
+            .map(|item| extractors.iter().map(|extractor| extractor(item)).collect())
+            .collect();
+        Self {
+            fields,
+            rows,
+        }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn column_position(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|name| name == field)
+    }
+
+    // Доля строк выборки, удовлетворяющих предикату над кортежем значений
+    pub fn selectivity<P>(&self, predicate: P) -> f64
+    where
+        P: Fn(&[FieldValue]) -> bool,
+    {
+        if self.rows.is_empty() {
+            return 1.0;
+        }
+        let matched = self.rows.iter().filter(|row| predicate(row)).count();
+        matched as f64 / self.rows.len() as f64
+    }
+}
+
+// Обратная связь планировщика по набору полей:
+// поправочный коэффициент к оценке селективности по фактическим результатам
+#[derive(Debug, Clone)]
+pub struct PlannerFeedback {
+    correction: f64,
+    executions: u64,
+}
+
+const FEEDBACK_WEIGHT: f64 = 0.5;
+const MIN_CORRECTION: f64 = 0.01;
+const MAX_CORRECTION: f64 = 1000.0;
+
+impl PlannerFeedback {
+    pub fn new() -> Self {
+        Self {
+            correction: 1.0,
+            executions: 0,
+        }
+    }
+
+    pub fn correction(&self) -> f64 {
+        self.correction
+    }
+
+    // estimate - исходная оценка индексов, observed - фактическая доля строк
+    pub fn record(&mut self, estimate: f64, observed: f64) {
+        let target = (observed.max(0.0) / estimate.max(1e-6))
+            .clamp(MIN_CORRECTION, MAX_CORRECTION);
+        self.correction = if self.executions == 0 {
+            target
+        } else {
+            self.correction * (1.0 - FEEDBACK_WEIGHT) + target * FEEDBACK_WEIGHT
+        };
+        self.executions += 1;
+    }
+
+    pub fn apply(&self, estimate: f64) -> f64 {
+        (estimate * self.correction).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for PlannerFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Числовые значения field индекса в порядке его bitmap-ов -
+// сумма по выборке считается как Σ value × |bitmap ∩ mask|
+pub struct ValueSums {
+    // Версия индекса, для которой посчитаны значения
+    pub version: u64,
+    pub values: Vec<f64>,
+}
+
+// Число bucket-ов гистограммы field_stats
+pub const DEFAULT_HISTOGRAM_BUCKETS: usize = 32;
+// Перцентили хранятся с шагом 1% (p0..p100)
+const PERCENTILE_POINTS: u64 = 100;
+
+// Bucket equi-width гистограммы: строки со значением в [start, end)
+// (последний bucket включает end)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    pub start: f64,
+    pub end: f64,
+    pub rows: u64,
+}
+
+/// Статистика значений field индекса
+///
+/// Считается по bitmap-ам индекса: min/max, перцентили с шагом 1%
+/// (nearest-rank по числу вхождений значений) и equi-width гистограмма
+/// для числовых значений. Для строк, bool и т.п. гистограмма пустая.
+/// NaN float индекса считаются отдельно (`nans`) и в min/max, перцентили
+/// и гистограмму не входят.
+///
+/// # Example
+///
+/// let stats = data.field_stats("price")?;
+/// let p95 = stats.percentile(0.95);
+/// let cheap = stats.range_fraction(None, Some(100.0));
+///
+#[derive(Debug, Clone)]
+pub struct FieldStats {
+    // Версия индекса, по которой посчитана статистика
+    pub version: u64,
+    pub rows: usize,
+    pub nulls: u64,
+    // Строки со значением NaN (float индекс с FloatPolicy::NaNLargest)
+    pub nans: u64,
+    pub unique_values: usize,
+    pub min: Option<FieldValue>,
+    pub max: Option<FieldValue>,
+    // percentiles[k] - значение k-го перцентиля
+    pub percentiles: Vec<FieldValue>,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+impl FieldStats {
+    // counts - значения по возрастанию с числом строк каждого
+    pub fn from_value_counts(
+        version: u64,
+        counts: &[(FieldValue, u64)],
+        rows: usize,
+        nulls: u64,
+        buckets: usize,
+    ) -> Self {
+        // NaN - всегда последнее значение (OrderedFloat)
+        let (counts, nans) = match counts.split_last() {
+            Some(((value, count), rest)) if value.is_nan() => (rest, *count),
+            _ => (counts, 0),
+        };
+        Self {
+            version,
+            rows,
+            nulls,
+            nans,
+            unique_values: counts.len() + usize::from(nans > 0),
+            min: counts.first().map(|(value, _)| value.clone()),
+            max: counts.last().map(|(value, _)| value.clone()),
+            percentiles: Self::percentile_points(counts),
+            histogram: Self::equi_width(counts, buckets),
+        }
+    }
+
+    fn percentile_points(counts: &[(FieldValue, u64)]) -> Vec<FieldValue> {
+        let total: u64 = counts.iter().map(|(_, count)| count).sum();
+        if total == 0 {
+            return Vec::new();
+        }
+        let mut points = Vec::with_capacity(PERCENTILE_POINTS as usize + 1);
+        let mut pos = 0;
+        let mut before = 0;
+        for point in 0..=PERCENTILE_POINTS {
+            // Ранг значения перцентиля (1..=total)
+            let rank = (point * total).div_ceil(PERCENTILE_POINTS).max(1);
+            while before + counts[pos].1 < rank {
+                before += counts[pos].1;
+                pos += 1;
+            }
+            points.push(counts[pos].0.clone());
+        }
+        points
+    }
+
+    fn equi_width(counts: &[(FieldValue, u64)], buckets: usize) -> Vec<HistogramBucket> {
+        let numbers: Option<Vec<(f64, u64)>> = counts
+            .iter()
+            .map(|(value, count)| value.as_f64().map(|number| (number, *count)))
+            .collect();
+        let Some(numbers) = numbers else {
+            return Vec::new();
+        };
+        let (Some(&(min, _)), Some(&(max, _))) = (numbers.first(), numbers.last()) else {
+            return Vec::new();
+        };
+        if buckets == 0 || !min.is_finite() || !max.is_finite() {
+            return Vec::new();
+        }
+        // Одно значение - один bucket нулевой ширины
+        let buckets = if max > min { buckets } else { 1 };
+        let width = (max - min) / buckets as f64;
+        let mut histogram: Vec<HistogramBucket> = (0..buckets)
+            .map(|n| HistogramBucket {
+                start: min + width * n as f64,
+                end: if n + 1 == buckets { max } else { min + width * (n + 1) as f64 },
+                rows: 0,
+            })
+            .collect();
+        for (number, count) in numbers {
+            let bucket = if width > 0.0 { ((number - min) / width) as usize } else { 0 };
+            histogram[bucket.min(buckets - 1)].rows += count;
+        }
+        histogram
+    }
+
+    // Значение перцентиля p (0.0..=1.0), с точностью до 1%
+    pub fn percentile(&self, p: f64) -> Option<&FieldValue> {
+        let point = (p.clamp(0.0, 1.0) * PERCENTILE_POINTS as f64).round() as usize;
+        self.percentiles.get(point)
+    }
+
+    pub fn median(&self) -> Option<&FieldValue> {
+        self.percentile(0.5)
+    }
+
+    // Доля всех строк со значением в [start, end] по гистограмме
+    // (внутри bucket-а значения считаются равномерными); None - нет гистограммы
+    pub fn range_fraction(&self, start: Option<f64>, end: Option<f64>) -> Option<f64> {
+        if self.histogram.is_empty() {
+            return None;
+        }
+        if self.rows == 0 {
+            return Some(0.0);
+        }
+        let start = start.unwrap_or(f64::NEG_INFINITY);
+        let end = end.unwrap_or(f64::INFINITY);
+        if start > end {
+            return Some(0.0);
+        }
+        let matched: f64 = self.histogram
+            .iter()
+            .map(|bucket| {
+                let width = bucket.end - bucket.start;
+                if width <= 0.0 {
+                    return if start <= bucket.start && bucket.end <= end { bucket.rows as f64 } else { 0.0 };
+                }
+                let overlap = end.min(bucket.end) - start.max(bucket.start);
+                if overlap <= 0.0 {
+                    0.0
+                } else {
+                    bucket.rows as f64 * (overlap / width).min(1.0)
+                }
+            })
+            .sum();
+        Some((matched / self.rows as f64).clamp(0.0, 1.0))
+    }
+
+    // Селективность сравнения / диапазона по гистограмме;
+    // None - операция не диапазонная или операнд не число
+    pub fn operation_selectivity(&self, operation: &FieldOperation) -> Option<f64> {
+        match operation {
+            FieldOperation::Gt(value) | FieldOperation::Gte(value) => self.range_fraction(Some(value.as_f64()?), None),
+            FieldOperation::Lt(value) | FieldOperation::Lte(value) => self.range_fraction(None, Some(value.as_f64()?)),
+            FieldOperation::Range(start, end) => self.range_fraction(Some(start.as_f64()?), Some(end.as_f64()?)),
+            _ => None,
+        }
+    }
+}
+
+// Режим использования индекса планировщиком
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexUsage {
+    // Решает планировщик (эвристики + оценка селективности)
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+// Подсказка для одного вызова filter_by_field(s)_ops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryHint {
+    #[default]
+    Auto,
+    // Всегда index path (без адаптивного переключения)
+    ForceIndex,
+    // Всегда predicate path
+    ForceScan,
+}
+
+/// Подсказки планировщику для конкретного field индекса
+///
+/// None - используется значение по умолчанию.
+///
+/// # Example
+///
+/// data.set_planner_hints("status", PlannerHints::new()
+///     .with_cardinality_low_threshold(0.001)
+///     .with_skew_ratio(0.9))?;
+/// data.never_use_index("comment_len")?;
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlannerHints {
+    pub usage: IndexUsage,
+    // Порог низкой кардинальности (unique / size)
+    pub cardinality_low_threshold: Option<f64>,
+    // Доля самого частого значения, после которой распределение считается перекошенным
+    pub skew_ratio: Option<f64>,
+    // Максимальная оценка селективности для index path
+    pub selectivity_threshold: Option<f64>,
+}
+
+impl PlannerHints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_usage(mut self, usage: IndexUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_cardinality_low_threshold(mut self, threshold: f64) -> Self {
+        self.cardinality_low_threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_skew_ratio(mut self, ratio: f64) -> Self {
+        self.skew_ratio = Some(ratio);
+        self
+    }
+
+    pub fn with_selectivity_threshold(mut self, threshold: f64) -> Self {
+        self.selectivity_threshold = Some(threshold);
+        self
+    }
+
+    // Переопределены ли эвристики эффективности
+    pub fn overrides_efficiency(&self) -> bool {
+        self.cardinality_low_threshold.is_some() || self.skew_ratio.is_some()
+    }
+}
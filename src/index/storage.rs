@@ -1,5 +1,10 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
 use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use arc_swap::ArcSwap;
+use roaring::RoaringBitmap;
 
 
 // DataStorage - Architecture
@@ -10,21 +15,219 @@ where
     T: Send + Sync,
 {
     Owned {
-        // Текущее состояние
-        source: Arc<Vec<Arc<T>>>,
+        // Текущее состояние (source и full_indices растут при push / extend)
+        source: Arc<ArcSwap<Rows<T>>>,
         current_indices: ArcSwap<Vec<usize>>,
         current_cache: ArcSwap<Option<Arc<Vec<Arc<T>>>>>,
-        full_indices: Arc<Vec<usize>>,
+        full_indices: ArcSwap<Vec<usize>>,
+        // Удаленные строки (retain / remove_indices): остаются в source,
+        // но не входят в full_indices и bitmap-ы индексов
+        removed: ArcSwap<RoaringBitmap>,
         // История для навигации
         levels: ArcSwap<Vec<Arc<Vec<Arc<T>>>>>, // кеш
-        level_indices: ArcSwap<Vec<Arc<Vec<usize>>>>, // Индексы для навигации
+        level_indices: ArcSwap<Vec<LevelIndices>>, // Индексы для навигации
     },
     Indexed {
         // Текущее состояние
-        parent_data: Weak<Vec<Arc<T>>>,
+        parent_data: Weak<Rows<T>>,
         source_indices: Arc<Vec<usize>>,
         current_indices: ArcSwap<Vec<usize>>,
         // История для навигации
-        index_levels: ArcSwap<Vec<Arc<Vec<usize>>>>, // Индексы для навигации
+        index_levels: ArcSwap<Vec<LevelIndices>>, // Индексы для навигации
     },
-}
\ No newline at end of file
+}
+
+/// Строки хранилища: неизменяемый снимок первых `len()` строк
+///
+/// Снимки разных версий делят один буфер: push пишет новые строки в свободные
+/// слоты за длиной последнего снимка, поэтому прежние снимки их не видят,
+/// а строки не копируются. Когда буфер заполнен, строки переносятся в буфер
+/// вдвое большего размера - копирование Arc-ов амортизировано O(1) на строку.
+///
+/// # Example
+///
+/// let rows = Rows::from(vec![Arc::new(1u64), Arc::new(2)]);
+/// let more = rows.appended(vec![Arc::new(3)]);
+/// assert_eq!((rows.len(), more.len()), (2, 3));
+pub struct Rows<T> {
+    buffer: Arc<RowBuffer<T>>,
+    len: usize,
+}
+
+// Слоты [0, filled) инициализированы и больше не меняются;
+// слоты дальше занимает тот, кто первым передвинул filled
+struct RowBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<Arc<T>>>]>,
+    filled: AtomicUsize,
+}
+
+// Инициализированные слоты только читаются, запись идет в слоты, занятые через filled
+unsafe impl<T: Send + Sync> Send for RowBuffer<T> {}
+unsafe impl<T: Send + Sync> Sync for RowBuffer<T> {}
+
+impl<T> RowBuffer<T> {
+    // Буфер на capacity слотов, первые заняты rows
+    fn with_rows(rows: impl Iterator<Item = Arc<T>>, capacity: usize) -> Self {
+        let mut slots: Vec<UnsafeCell<MaybeUninit<Arc<T>>>> = Vec::with_capacity(capacity);
+        slots.extend(rows.map(|row| UnsafeCell::new(MaybeUninit::new(row))));
+        let len = slots.len();
+        slots.resize_with(capacity.max(len), || UnsafeCell::new(MaybeUninit::uninit()));
+        Self { slots: slots.into_boxed_slice(), filled: AtomicUsize::new(len) }
+    }
+}
+
+impl<T> Drop for RowBuffer<T> {
+    fn drop(&mut self) {
+        let filled = *self.filled.get_mut();
+        for slot in &mut self.slots[..filled] {
+            // SAFETY: слоты до filled инициализированы
+            unsafe { slot.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl<T> Rows<T> {
+    /// Снимок со строками `rows` после строк этого снимка
+    ///
+    /// Если за снимком никто еще не дописывал и в буфере есть место,
+    /// строки пишутся в тот же буфер, иначе - в новый.
+    pub fn appended(&self, rows: Vec<Arc<T>>) -> Self {
+        let end = self.len + rows.len();
+        let claimed = end <= self.buffer.slots.len() && self.buffer.filled
+            .compare_exchange(self.len, end, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok();
+        if !claimed {
+            let capacity = (end * 2).max(MIN_ROWS_CAPACITY);
+            let buffer = RowBuffer::with_rows(self.iter().cloned().chain(rows), capacity);
+            return Self { buffer: Arc::new(buffer), len: end };
+        }
+        for (slot, row) in self.buffer.slots[self.len..end].iter().zip(rows) {
+            // SAFETY: слоты [len, end) заняты через filled только этим вызовом,
+            // ни один снимок их еще не видит
+            unsafe { (*slot.get()).write(row) };
+        }
+        Self { buffer: Arc::clone(&self.buffer), len: end }
+    }
+}
+
+// Минимальный размер буфера после первого push
+const MIN_ROWS_CAPACITY: usize = 16;
+
+impl<T> Deref for Rows<T> {
+    type Target = [Arc<T>];
+
+    fn deref(&self) -> &[Arc<T>] {
+        // SAFETY: слоты до len инициализированы до публикации снимка и не меняются;
+        // UnsafeCell<MaybeUninit<Arc<T>>> имеет представление Arc<T>
+        unsafe { std::slice::from_raw_parts(self.buffer.slots.as_ptr().cast::<Arc<T>>(), self.len) }
+    }
+}
+
+impl<T> Clone for Rows<T> {
+    fn clone(&self) -> Self {
+        Self { buffer: Arc::clone(&self.buffer), len: self.len }
+    }
+}
+
+impl<T> Default for Rows<T> {
+    fn default() -> Self {
+        Self::from(Vec::new())
+    }
+}
+
+impl<T> From<Vec<Arc<T>>> for Rows<T> {
+    fn from(rows: Vec<Arc<T>>) -> Self {
+        let len = rows.len();
+        Self { buffer: Arc::new(RowBuffer::with_rows(rows.into_iter(), len)), len }
+    }
+}
+
+impl<T> std::fmt::Debug for Rows<T>
+where
+    T: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// Индексы строк уровня истории.
+// Bitmap - компактная форма (bitmap_levels): ~8 байт на строку меньше,
+// вектор восстанавливается при возврате на уровень.
+#[derive(Clone)]
+pub enum LevelIndices {
+    Vec(Arc<Vec<usize>>),
+    Bitmap(Arc<RoaringBitmap>),
+}
+
+impl LevelIndices {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Vec(indices) => indices.len(),
+            Self::Bitmap(bitmap) => bitmap.len() as usize,
+        }
+    }
+
+    pub fn is_bitmap(&self) -> bool {
+        matches!(self, Self::Bitmap(_))
+    }
+
+    pub fn indices(&self) -> Arc<Vec<usize>> {
+        match self {
+            Self::Vec(indices) => Arc::clone(indices),
+            Self::Bitmap(bitmap) => Arc::new(bitmap.iter().map(|idx| idx as usize).collect()),
+        }
+    }
+
+    // Bitmap хранит только возрастающий порядок - неупорядоченный уровень остается вектором
+    pub fn compact(&self) -> Self {
+        match self {
+            Self::Vec(indices) if indices.is_sorted() => {
+                RoaringBitmap::from_sorted_iter(indices.iter().map(|&idx| idx as u32))
+                    .map(|bitmap| Self::Bitmap(Arc::new(bitmap)))
+                    .unwrap_or_else(|_| self.clone())
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl From<Arc<Vec<usize>>> for LevelIndices {
+    fn from(indices: Arc<Vec<usize>>) -> Self {
+        Self::Vec(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_append_shares_buffer() {
+        let rows = Rows::from((0..4u64).map(Arc::new).collect::<Vec<_>>());
+        // Полный буфер - первый push переносит строки в буфер с запасом
+        let grown = rows.appended(vec![Arc::new(4)]);
+        assert!(!Arc::ptr_eq(&rows.buffer, &grown.buffer));
+        let more = grown.appended(vec![Arc::new(5), Arc::new(6)]);
+        assert!(Arc::ptr_eq(&grown.buffer, &more.buffer));
+        assert_eq!(grown.iter().map(|row| **row).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(more.iter().map(|row| **row).collect::<Vec<_>>(), (0..7).collect::<Vec<_>>());
+
+        // Второй push от того же снимка не пишет поверх чужих строк
+        let fork = grown.appended(vec![Arc::new(50)]);
+        assert!(!Arc::ptr_eq(&grown.buffer, &fork.buffer));
+        assert_eq!(fork.iter().map(|row| **row).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 50]);
+        assert_eq!(*more[5], 5);
+    }
+
+    #[test]
+    fn test_rows_drop_releases_appended_rows() {
+        let row = Arc::new(7u64);
+        let rows = Rows::from(vec![Arc::clone(&row)]).appended(vec![Arc::clone(&row), Arc::clone(&row)]);
+        let snapshot = rows.clone();
+        drop(rows);
+        assert_eq!(Arc::strong_count(&row), 4);
+        drop(snapshot);
+        assert_eq!(Arc::strong_count(&row), 1);
+    }
+}
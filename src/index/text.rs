@@ -1,9 +1,12 @@
 use super::bit::{
     Index as BitIndex,
+    touch,
     Op as BitOp,
     OpResult as BitOpResult,
 };
 use ahash::{AHashMap, HashMap};
+use dashmap::DashMap;
+use parking_lot::RwLock;
 use memchr::memmem::Finder;
 use rayon::prelude::*;
 use roaring::RoaringBitmap;
@@ -17,16 +20,167 @@ use std::{
     }
 };
 
+/// Языковой анализатор текста: нормализация перед разбиением на n-граммы
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextAnalyzer {
+    // Только нижний регистр
+    #[default]
+    Standard,
+    // Иероглифы: слова не разделяются пробелами - используются биграммы
+    Cjk,
+    // Кириллица: ё -> е
+    Cyrillic,
+    // Греческий: без диакритики (тонос, диалитика), ς -> σ
+    Greek,
+}
+
+impl TextAnalyzer {
+    // Нормализация текста и запроса - одинаковая, иначе поиск не совпадет
+    pub fn normalize(&self, text: &str) -> String {
+        let lower = text.to_lowercase();
+        // ASCII не меняется ни одним анализатором
+        if lower.is_ascii() {
+            return lower;
+        }
+        match self {
+            Self::Standard | Self::Cjk => lower,
+            Self::Cyrillic => lower.chars().map(Self::fold_cyrillic).collect(),
+            Self::Greek => lower.chars().map(Self::fold_greek).collect(),
+        }
+    }
+
+    // Размер n-граммы по умолчанию для анализатора
+    pub fn default_ngram(&self) -> usize {
+        match self {
+            Self::Cjk => 2,
+            _ => 3,
+        }
+    }
+
+    fn fold_cyrillic(c: char) -> char {
+        match c {
+            'ё' => 'е',
+            _ => c,
+        }
+    }
+
+    fn fold_greek(c: char) -> char {
+        match c {
+            'ά' => 'α',
+            'έ' => 'ε',
+            'ή' => 'η',
+            'ί' | 'ϊ' | 'ΐ' => 'ι',
+            'ό' => 'ο',
+            'ύ' | 'ϋ' | 'ΰ' => 'υ',
+            'ώ' => 'ω',
+            'ς' => 'σ',
+            _ => c,
+        }
+    }
+}
+
+/// Настройки text индекса
+/// 
+/// # Example
+/// 
+/// data.create_text_index_with_options("title", |book| book.title.clone(), TextIndexOptions::cjk())?;
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextIndexOptions {
+    pub n: usize,
+    pub analyzer: TextAnalyzer,
+}
+
+impl TextIndexOptions {
+    pub fn new(analyzer: TextAnalyzer) -> Self {
+        Self {
+            n: analyzer.default_ngram(),
+            analyzer,
+        }
+    }
+
+    pub fn cjk() -> Self {
+        Self::new(TextAnalyzer::Cjk)
+    }
+
+    pub fn cyrillic() -> Self {
+        Self::new(TextAnalyzer::Cyrillic)
+    }
+
+    pub fn greek() -> Self {
+        Self::new(TextAnalyzer::Greek)
+    }
+
+    pub fn with_ngram(mut self, n: usize) -> Self {
+        self.n = n.max(1);
+        self
+    }
+}
+
+impl Default for TextIndexOptions {
+    fn default() -> Self {
+        Self::new(TextAnalyzer::Standard)
+    }
+}
+
+/// Общий словарь n-грамм (n-грамма -> id)
+/// 
+/// Один словарь разделяют все text индексы дерева FilterData / GroupData:
+/// строки n-грамм хранятся один раз, индексы держат posting-и по id.
+/// Словарь только растет - n-граммы удаленных индексов остаются в нем.
+#[derive(Default)]
+pub struct NgramDictionary {
+    ids: DashMap<Arc<str>, u32>,
+    names: RwLock<Vec<Arc<str>>>,
+}
+
+impl NgramDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&self, ngram: &str) -> u32 {
+        if let Some(id) = self.ids.get(ngram) {
+            return *id;
+        }
+        *self.ids.entry(Arc::from(ngram)).or_insert_with(|| {
+            let mut names = self.names.write();
+            names.push(Arc::from(ngram));
+            (names.len() - 1) as u32
+        })
+    }
+
+    pub fn get(&self, ngram: &str) -> Option<u32> {
+        self.ids.get(ngram).map(|id| *id)
+    }
+
+    pub fn name(&self, id: u32) -> Option<Arc<str>> {
+        self.names.read().get(id as usize).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
 // N-gram индекс для быстрого substring search
 pub struct TextIndex<T>
 where
     T: Send + Sync,
 {
-    ngrams: Arc<AHashMap<String, BitIndex>>,
+    // Posting-и по id n-граммы из общего словаря
+    ngrams: Arc<AHashMap<u32, BitIndex>>,
+    dictionary: Arc<NgramDictionary>,
     // Store texts for full verification
     item_texts: Arc<Vec<String>>,
     // N-gram size (3 для trigrams)
     n: usize,
+    analyzer: TextAnalyzer,
     total_items: usize,
     // Stats
     unique_ngrams: usize,
@@ -40,10 +194,21 @@ where
 {
     // Создать новый n-gram индекс
     pub fn new(n: usize) -> Self {
+        Self::with_options(TextIndexOptions::default().with_ngram(n))
+    }
+
+    pub fn with_options(options: TextIndexOptions) -> Self {
+        Self::with_dictionary(options, Arc::new(NgramDictionary::new()))
+    }
+
+    // Индекс, разделяющий словарь n-грамм с другими индексами
+    pub fn with_dictionary(options: TextIndexOptions, dictionary: Arc<NgramDictionary>) -> Self {
         Self {
             ngrams: Arc::new(AHashMap::new()),
+            dictionary,
             item_texts: Arc::new(Vec::new()),
-            n,
+            n: options.n,
+            analyzer: options.analyzer,
             total_items: 0,
             unique_ngrams: 0,
             total_ngrams: 0,
@@ -68,7 +233,7 @@ where
         // Extract texts
         let texts: Vec<String> = items
             .par_iter()
-            .map(|item| extractor(item).to_lowercase())
+            .map(|item| self.analyzer.normalize(&extractor(item)))
             .collect();
         
         let estimated_capacity = match texts.len() {
@@ -139,13 +304,13 @@ where
         let mut entries: Vec<(String, Vec<usize>)> = ngrams_map.into_iter().collect();
         entries.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
         
-        let pairs: Vec<(String, BitIndex)> = entries
+        let pairs: Vec<(u32, BitIndex)> = entries
             .into_par_iter()
             .map(|(ngram, mut indices)| {
                 indices.sort_unstable();
                 indices.dedup();
                 let bit_index = BitIndex::from_indices(&indices, self.total_items);
-                (ngram, bit_index)
+                (self.dictionary.intern(&ngram), bit_index)
             })
             .collect();
         
@@ -158,17 +323,63 @@ where
         self.item_texts = Arc::new(texts);
     }
 
+    // Дописать тексты новых строк в конец индекса (FilterData::push / extend)
+    pub fn push_texts<I>(&mut self, texts: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut ngrams_buffer: SmallVec<[String; 64]> = SmallVec::new();
+        let mut postings: Vec<(u32, usize)> = Vec::new();
+        let mut new_texts = Vec::new();
+        for (offset, text) in texts.into_iter().enumerate() {
+            let text = self.analyzer.normalize(&text);
+            let idx = self.item_texts.len() + offset;
+            ngrams_buffer.clear();
+            self.extract_ngrams_to_buffer(&text, &mut ngrams_buffer);
+            self.total_ngrams += ngrams_buffer.len();
+            postings.extend(ngrams_buffer.iter().map(|ngram| (self.dictionary.intern(ngram), idx)));
+            new_texts.push(text);
+        }
+        let item_texts = Arc::make_mut(&mut self.item_texts);
+        item_texts.extend(new_texts);
+        self.total_items = item_texts.len();
+        let total_items = self.total_items;
+        let ngrams = Arc::make_mut(&mut self.ngrams);
+        for (id, idx) in postings {
+            ngrams.entry(id).or_default().insert(idx);
+        }
+        ngrams.values_mut().for_each(|bit_index| bit_index.set_total_size(total_items));
+        self.unique_ngrams = ngrams.len();
+    }
+
+    // Удалить строки: posting-и без строк удаляются, тексты строк очищаются
+    // (позиции остальных строк не меняются)
+    pub fn remove_positions(&mut self, rows: &RoaringBitmap) {
+        let ngrams = Arc::make_mut(&mut self.ngrams);
+        ngrams.retain(|_, bit_index| {
+            bit_index.remove_all(rows);
+            !bit_index.is_empty()
+        });
+        self.unique_ngrams = ngrams.len();
+        let item_texts = Arc::make_mut(&mut self.item_texts);
+        for pos in rows {
+            if let Some(text) = item_texts.get_mut(pos as usize) {
+                text.clear();
+            }
+        }
+    }
+
     // Извлекаем все n-граммы в буфер
     #[inline]
     fn extract_ngrams_to_buffer(&self, text: &str, buffer: &mut SmallVec<[String; 64]>) {
-        if text.len() < self.n {
-            if !text.is_empty() {
-                buffer.push(text.to_string());
-            }
-            return;
-        }
         // Быстрый путь для ASCII
         if text.is_ascii() {
+            if text.len() < self.n {
+                if !text.is_empty() {
+                    buffer.push(text.to_string());
+                }
+                return;
+            }
             let bytes = text.as_bytes();
             for i in 0..=bytes.len().saturating_sub(self.n) {
                 // SAFETY: ASCII is valid UTF-8
@@ -178,8 +389,12 @@ where
                 buffer.push(ngram.to_string());
             }
         } else {
-            // Fallback для Unicode
+            // Fallback для Unicode: длина в символах, а не в байтах
             let chars: Vec<char> = text.chars().collect();
+            if chars.len() < self.n {
+                buffer.push(text.to_string());
+                return;
+            }
             for i in 0..=chars.len().saturating_sub(self.n) {
                 let ngram: String = chars[i..i + self.n].iter().collect();
                 buffer.push(ngram);
@@ -205,9 +420,9 @@ where
         if query.is_empty() {
             return Vec::new();
         }
-        let query_lower = query.to_lowercase();
+        let query_lower = self.analyzer.normalize(query);
         // Для очень коротких query - linear search
-        if query_lower.len() < self.n {
+        if query_lower.chars().count() < self.n {
             return self.linear_search(&query_lower);
         }
         // Извлекаем n-граммы из query
@@ -341,7 +556,7 @@ where
             return Vec::new();
         }
         // Получаем BitIndex для первой n-граммы
-        let first_bit = match self.ngrams.get(&query_ngrams[0]) {
+        let first_bit = match self.posting(&query_ngrams[0]) {
             Some(bit) => bit,
             None => return Vec::new(),
         };
@@ -352,7 +567,7 @@ where
         let operations: Vec<(&BitIndex, BitOp)> = query_ngrams[1..]
             .iter()
             .filter_map(|ngram| {
-                self.ngrams.get(ngram).map(|bit| (bit, BitOp::And))
+                self.posting(ngram).map(|bit| (bit, BitOp::And))
             })
             .collect();
         if operations.len() != query_ngrams.len() - 1 {
@@ -367,7 +582,16 @@ where
     // Получить BitIndex для n-граммы (для сложный операций)
     #[allow(dead_code)]
     pub fn get_ngram_bitindex(&self, ngram: &str) -> Option<&BitIndex> {
-        self.ngrams.get(ngram)
+        self.posting(ngram)
+    }
+
+    #[inline]
+    fn posting(&self, ngram: &str) -> Option<&BitIndex> {
+        self.dictionary.get(ngram).and_then(|id| self.ngrams.get(&id))
+    }
+
+    pub fn dictionary(&self) -> &Arc<NgramDictionary> {
+        &self.dictionary
     }
 
     /// Комплексный поиск с BitIndex операциями
@@ -391,7 +615,7 @@ where
         // Шаг 1: OR операции
         let mut result = if !or_ngrams.is_empty() {
             // Первая n-грамма
-            let first = match self.ngrams.get(or_ngrams[0]) {
+            let first = match self.posting(or_ngrams[0]) {
                 Some(bit) => bit,
                 None => return Vec::new(),
             };
@@ -401,7 +625,7 @@ where
                 // OR остальных
                 let operations: Vec<(&BitIndex, BitOp)> = or_ngrams[1..]
                     .iter()
-                    .filter_map(|&ng| self.ngrams.get(ng).map(|b| (b, BitOp::Or)))
+                    .filter_map(|&ng| self.posting(ng).map(|b| (b, BitOp::Or)))
                     .collect();
                 first.multi_operation(&operations)
             }
@@ -415,7 +639,7 @@ where
         };
         // Шаг 2: AND операции
         for &ngram in and_ngrams {
-            if let Some(bit) = self.ngrams.get(ngram) {
+            if let Some(bit) = self.posting(ngram) {
                 let current_bitmap = result.bitmap().clone();
                 let and_bitmap = bit.bitmap();
                 let new_bitmap = &current_bitmap & and_bitmap;
@@ -430,7 +654,7 @@ where
         }
         // Шаг 3: NOT операции
         for &ngram in not_ngrams {
-            if let Some(bit) = self.ngrams.get(ngram) {
+            if let Some(bit) = self.posting(ngram) {
                 let current_bitmap = result.bitmap().clone();
                 let not_bitmap = bit.bitmap();
                 let new_bitmap = &current_bitmap - not_bitmap;
@@ -441,6 +665,20 @@ where
         result.to_indices()
     }
 
+    // Прогрев posting-ов и сохраненных текстов: (число posting-ов, байты)
+    pub fn touch(&self) -> (usize, usize) {
+        let postings: Vec<&BitIndex> = self.ngrams.values().collect();
+        let postings_bytes: usize = postings.par_iter().map(|posting| touch(posting.bitmap())).sum();
+        let texts_bytes: usize = self.item_texts
+            .par_iter()
+            .map(|text| {
+                std::hint::black_box(text.bytes().fold(0u8, |acc, byte| acc ^ byte));
+                text.len()
+            })
+            .sum();
+        (postings.len(), postings_bytes + texts_bytes)
+    }
+
     // Статистика индекса
     pub fn stats(&self) -> TextIndexStats {
         let memory_bytes = self.estimate_memory();
@@ -458,45 +696,169 @@ where
         }
     }
 
+    // stats только по позициям mask (base filter): n-граммы и строки вне mask не учитываются
+    pub fn stats_within(&self, mask: &RoaringBitmap) -> TextIndexStats {
+        let total_items = mask.range_cardinality(0..self.total_items as u32) as usize;
+        let counts: Vec<u64> = self.ngrams
+            .values()
+            .map(|bit_index| bit_index.bitmap().intersection_len(mask))
+            .filter(|&count| count > 0)
+            .collect();
+        let total_ngrams = counts.iter().sum::<u64>() as usize;
+        TextIndexStats {
+            n: self.n,
+            total_items,
+            unique_ngrams: counts.len(),
+            total_ngrams,
+            avg_ngrams_per_item: if total_items > 0 {
+                total_ngrams as f64 / total_items as f64
+            } else {
+                0.0
+            },
+            memory_kb: self.estimate_memory() / 1024,
+        }
+    }
+
     fn estimate_memory(&self) -> usize {
-        // Memory от BitIndex
-        let ngrams_memory: usize = self.ngrams
+        self.postings_bytes() + self.texts_bytes()
+    }
+
+    // Memory от BitIndex
+    pub fn postings_bytes(&self) -> usize {
+        self.ngrams
             .values()
             .map(|bit_index| bit_index.memory_size())
-            .sum();
-        let texts_memory: usize = self.item_texts
+            .sum()
+    }
+
+    // Тексты строк (нужны для проверки совпадений) и карта n-грамм
+    pub fn texts_bytes(&self) -> usize {
+        let texts: usize = self.item_texts
             .iter()
-            .map(|text| text.len())
+            .map(|text| text.capacity() + std::mem::size_of::<String>())
             .sum();
-        ngrams_memory + texts_memory
+        texts + self.ngrams.len() * (std::mem::size_of::<(u32, BitIndex)>() + 1) * 8 / 7
     }
 
     ///Получить статистику по конкретной n-грамме
     pub fn ngram_stats(&self, ngram: &str) -> Option<String> {
-        self.ngrams.get(ngram).map(|bit| bit.to_string())
+        self.posting(ngram).map(|bit| bit.to_string())
+    }
+
+    // ngram_stats только по позициям mask; None - n-граммы нет в строках mask
+    pub fn ngram_stats_within(&self, ngram: &str, mask: &RoaringBitmap) -> Option<String> {
+        let bitmap = self.posting(ngram)?.bitmap() & mask;
+        if bitmap.is_empty() {
+            return None;
+        }
+        let total_items = mask.range_cardinality(0..self.total_items as u32) as usize;
+        Some(BitIndex::from_bitmap(bitmap, total_items).to_string())
     }
 
     // Список всех n-грамм
     pub fn list_ngrams(&self) -> Vec<String> {
-        self.ngrams.keys().cloned().collect()
+        self.ngrams
+            .keys()
+            .filter_map(|&id| self.dictionary.name(id))
+            .map(|ngram| ngram.to_string())
+            .collect()
+    }
+
+    // n-граммы, которые есть в строках mask
+    pub fn list_ngrams_within(&self, mask: &RoaringBitmap) -> Vec<String> {
+        self.ngrams
+            .iter()
+            .filter(|(_, bit)| !bit.bitmap().is_disjoint(mask))
+            .filter_map(|(&id, _)| self.dictionary.name(id))
+            .map(|ngram| ngram.to_string())
+            .collect()
     }
 
     // Top-N самых частых n-грамм
     pub fn top_ngrams(&self, n: usize) -> Vec<(String, usize)> {
         let mut ngrams: Vec<(String, usize)> = self.ngrams
             .iter()
-            .map(|(ngram, bit)| (ngram.clone(), bit.count_ones()))
+            .filter_map(|(&id, bit)| self.dictionary.name(id).map(|ngram| (ngram.to_string(), bit.count_ones())))
             .collect();
         ngrams.sort_by(|a, b| b.1.cmp(&a.1));
         ngrams.truncate(n);
         ngrams
     }
 
+    // top_ngrams только по позициям mask
+    pub fn top_ngrams_within(&self, n: usize, mask: &RoaringBitmap) -> Vec<(String, usize)> {
+        let mut ngrams: Vec<(String, usize)> = self.ngrams
+            .iter()
+            .map(|(&id, bit)| (id, bit.bitmap().intersection_len(mask) as usize))
+            .filter(|(_, count)| *count > 0)
+            .filter_map(|(id, count)| self.dictionary.name(id).map(|ngram| (ngram.to_string(), count)))
+            .collect();
+        ngrams.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        ngrams.truncate(n);
+        ngrams
+    }
+
     #[allow(dead_code)]
     pub fn get_text(&self, index: usize) -> Option<&str> {
         self.item_texts.get(index).map(|s| s.as_str())
     }
+
+    pub fn analyzer(&self) -> TextAnalyzer {
+        self.analyzer
+    }
+
+    // Индексы построены по одним и тем же текстам с тем же n
+    pub fn same_texts(&self, other: &Self) -> bool {
+        self.n == other.n && self.analyzer == other.analyzer && (Arc::ptr_eq(&self.item_texts, &other.item_texts) || self.item_texts == other.item_texts)
+    }
     
+    // Снимок для сохранения на диск: posting-и по строкам n-грамм,
+    // а не по id - id зависят от порядка заполнения общего словаря
+    #[cfg(feature = "serde")]
+    pub fn to_snapshot(&self) -> TextIndexSnapshot {
+        let mut ngrams: Vec<(String, BitIndex)> = self.ngrams
+            .iter()
+            .filter_map(|(id, bit_index)| {
+                self.dictionary.name(*id).map(|ngram| (ngram.to_string(), bit_index.clone()))
+            })
+            .collect();
+        ngrams.sort_unstable_by(|left, right| left.0.cmp(&right.0));
+        TextIndexSnapshot {
+            n: self.n,
+            analyzer: self.analyzer,
+            total_items: self.total_items,
+            total_ngrams: self.total_ngrams,
+            item_texts: self.item_texts.as_ref().clone(),
+            ngrams,
+        }
+    }
+
+    // Восстановить индекс из снимка: n-граммы заносятся в словарь дерева
+    #[cfg(feature = "serde")]
+    pub fn from_snapshot(snapshot: TextIndexSnapshot, dictionary: Arc<NgramDictionary>) -> Self {
+        let ngrams: AHashMap<u32, BitIndex> = snapshot.ngrams
+            .into_iter()
+            .map(|(ngram, bit_index)| (dictionary.intern(&ngram), bit_index))
+            .collect();
+        Self {
+            unique_ngrams: ngrams.len(),
+            ngrams: Arc::new(ngrams),
+            dictionary,
+            item_texts: Arc::new(snapshot.item_texts),
+            n: snapshot.n,
+            analyzer: snapshot.analyzer,
+            total_items: snapshot.total_items,
+            total_ngrams: snapshot.total_ngrams,
+            _phantom: PhantomData,
+        }
+    }
+
+    // Индексы построены с одинаковыми параметрами (n, анализатор)
+    #[cfg(feature = "serde")]
+    pub fn same_options(&self, other: &Self) -> bool {
+        self.n == other.n && self.analyzer == other.analyzer
+    }
+
     #[allow(dead_code)]
     pub fn get_item_ngrams(&self, index: usize) -> Vec<String> {
         if let Some(text) = self.item_texts.get(index) {
@@ -507,6 +869,37 @@ where
     }
 }
 
+impl<T> Clone for TextIndex<T>
+where
+    T: Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            ngrams: Arc::clone(&self.ngrams),
+            dictionary: Arc::clone(&self.dictionary),
+            item_texts: Arc::clone(&self.item_texts),
+            n: self.n,
+            analyzer: self.analyzer,
+            total_items: self.total_items,
+            unique_ngrams: self.unique_ngrams,
+            total_ngrams: self.total_ngrams,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// Содержимое TextIndex без словаря (FilterData::save_indexes)
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TextIndexSnapshot {
+    n: usize,
+    analyzer: TextAnalyzer,
+    total_items: usize,
+    total_ngrams: usize,
+    item_texts: Vec<String>,
+    ngrams: Vec<(String, BitIndex)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct TextIndexStats {
     pub n: usize,
@@ -1108,4 +1501,38 @@ mod tests {
             .unwrap_or(0);
         assert!(pay_count >= 2, "Expected 'pay' in at least 2 documents, got {}", pay_count);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_language_analyzers() {
+        let items = vec![
+            Arc::new(TestItem { text: "東京都の天気".into() }),
+            Arc::new(TestItem { text: "京都の観光".into() }),
+        ];
+        let mut index = TextIndex::with_options(TextIndexOptions::cjk());
+        index.build(&items, |item| item.text.clone());
+        assert_eq!(index.stats().n, 2);
+        assert_eq!(index.search("京都"), vec![0, 1]);
+        assert_eq!(index.search("東京"), vec![0]);
+        // Запрос короче n - линейный поиск по символам
+        assert_eq!(index.search("天"), vec![0]);
+
+        let items = vec![
+            Arc::new(TestItem { text: "Ёлка и зелёный чай".into() }),
+            Arc::new(TestItem { text: "Черный чай".into() }),
+        ];
+        let mut index = TextIndex::with_options(TextIndexOptions::cyrillic());
+        index.build(&items, |item| item.text.clone());
+        assert_eq!(index.search("елка"), vec![0]);
+        assert_eq!(index.search("ЗЕЛЕНЫЙ"), vec![0]);
+        assert_eq!(index.search("чай").len(), 2);
+
+        let items = vec![Arc::new(TestItem { text: "Καλημέρα κόσμος".into() })];
+        let mut index = TextIndex::with_options(TextIndexOptions::greek());
+        index.build(&items, |item| item.text.clone());
+        assert_eq!(index.search("καλημερα"), vec![0]);
+        assert_eq!(index.search("ΚΟΣΜΟΣ"), vec![0]);
+
+        // Стандартный анализатор не сворачивает буквы
+        assert_eq!(TextAnalyzer::Standard.normalize("Ёлка"), "ёлка");
+    }
+}
@@ -0,0 +1,268 @@
+use super::{
+    errors::{FilterDataError, GLobalError, IndexError},
+    index::{
+        bit::Op,
+        field::{FieldOperation, FieldValue, IndexField, IndexFieldEnum, IntoIndexFieldEnum},
+    },
+    result::GlobalResult,
+};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+use std::{collections::BTreeMap, fmt::Display, hash::Hash, sync::Arc};
+
+const MAX_HISTORY: usize = 50;
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+// Уровень drill-down: позиции строк в исходном срезе
+struct InlineLevel {
+    description: Arc<str>,
+    positions: Arc<RoaringBitmap>,
+}
+
+// История и текущий уровень под одной блокировкой - читатель не увидит
+// номер уровня, которого уже нет в истории
+struct InlineLevels {
+    levels: Vec<InlineLevel>,
+    current: usize,
+}
+
+impl InlineLevels {
+    fn current(&self) -> &InlineLevel {
+        &self.levels[self.current]
+    }
+}
+
+/// Фильтрация без Arc на каждую строку
+///
+/// Строки лежат подряд в `Box<[T]>`, уровни хранятся как bitmap позиций,
+/// результаты отдаются списком позиций или ссылками. Подходит для
+/// числовых данных и небольших POD структур, где `Arc<T>` дороже самой строки.
+///
+/// # Example
+///
+/// let data = InlineFilterData::from_vec_inline((0..1_000_000u64).collect());
+/// data.create_field_index("value", |v: &u64| *v % 10)?;
+/// data.filter_by_field_ops("value", &[(FieldOperation::eq(3u64), Op::And)])?;
+/// data.filter(|v| *v > 500_000)?;
+/// let positions = data.indices();
+/// let items: Vec<&u64> = data.items();
+///
+pub struct InlineFilterData<T> {
+    items: Box<[T]>,
+    levels: RwLock<InlineLevels>,
+    field_indexes: DashMap<String, Arc<IndexFieldEnum>>,
+}
+
+impl<T> InlineFilterData<T>
+where
+    T: Send + Sync,
+{
+    pub fn from_vec_inline(items: Vec<T>) -> Self {
+        Self::from_boxed(items.into_boxed_slice())
+    }
+
+    pub fn from_boxed(items: Box<[T]>) -> Self {
+        let mut positions = RoaringBitmap::new();
+        positions.insert_range(0..items.len() as u32);
+        Self {
+            items,
+            levels: RwLock::new(InlineLevels {
+                levels: vec![InlineLevel {
+                    description: Arc::from("Source"),
+                    positions: Arc::new(positions),
+                }],
+                current: 0,
+            }),
+            field_indexes: DashMap::new(),
+        }
+    }
+
+    pub fn from_slice(items: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_boxed(items.into())
+    }
+
+    // Исходные строки (все уровни ссылаются на позиции в этом срезе)
+    pub fn source(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn source_len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn current_positions(&self) -> Arc<RoaringBitmap> {
+        Arc::clone(&self.levels.read().current().positions)
+    }
+
+    pub fn len(&self) -> usize {
+        self.current_positions().len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn current_level(&self) -> usize {
+        self.levels.read().current
+    }
+
+    pub fn stored_levels_count(&self) -> usize {
+        self.levels.read().levels.len()
+    }
+
+    pub fn level_description(&self) -> Arc<str> {
+        Arc::clone(&self.levels.read().current().description)
+    }
+
+    // Позиции строк текущего уровня (по возрастанию)
+    pub fn indices(&self) -> Vec<usize> {
+        self.current_positions().iter().map(|pos| pos as usize).collect()
+    }
+
+    pub fn bitmap(&self) -> Arc<RoaringBitmap> {
+        self.current_positions()
+    }
+
+    pub fn items(&self) -> Vec<&T> {
+        self.current_positions().iter().map(|pos| &self.items[pos as usize]).collect()
+    }
+
+    pub fn items_with_ids(&self) -> Vec<(usize, &T)> {
+        self.current_positions()
+            .iter()
+            .map(|pos| (pos as usize, &self.items[pos as usize]))
+            .collect()
+    }
+
+    // Строка по позиции в исходном срезе, если она входит в текущий уровень
+    pub fn get(&self, position: usize) -> Option<&T> {
+        self.current_positions()
+            .contains(position as u32)
+            .then(|| &self.items[position])
+    }
+
+    pub fn filter<F>(&self, predicate: F) -> GlobalResult<&Self>
+    where
+        F: Fn(&T) -> bool + Sync + Send,
+    {
+        let current = self.current_positions();
+        let positions: RoaringBitmap = if current.len() as usize > PARALLEL_THRESHOLD {
+            let candidates: Vec<u32> = current.iter().collect();
+            let mut matched: Vec<u32> = candidates
+                .into_par_iter()
+                .filter(|&pos| predicate(&self.items[pos as usize]))
+                .collect();
+            matched.par_sort_unstable();
+            RoaringBitmap::from_sorted_iter(matched).unwrap_or_default()
+        } else {
+            current.iter().filter(|&pos| predicate(&self.items[pos as usize])).collect()
+        };
+        self.push_level("Filtered", positions)
+    }
+
+    pub fn create_field_index<V, F>(&self, name: &str, extractor: F) -> GlobalResult<&Self>
+    where
+        V: Eq + Hash + Clone + Send + Sync + Ord + Display + Into<FieldValue> + 'static,
+        F: Fn(&T) -> V + Send + Sync,
+        IndexField<V>: IntoIndexFieldEnum,
+    {
+        // Индекс строится по всему срезу: позиции bitmap-ов совпадают с позициями строк
+        let values_indices = self.items
+            .par_chunks(PARALLEL_THRESHOLD)
+            .enumerate()
+            .fold(BTreeMap::new, |mut map: BTreeMap<V, RoaringBitmap>, (chunk_idx, chunk)| {
+                let offset = chunk_idx * PARALLEL_THRESHOLD;
+                for (idx, item) in chunk.iter().enumerate() {
+                    map.entry(extractor(item)).or_default().insert((offset + idx) as u32);
+                }
+                map
+            })
+            .reduce(BTreeMap::new, |mut left, right| {
+                for (value, bitmap) in right {
+                    *left.entry(value).or_default() |= bitmap;
+                }
+                left
+            });
+        let index = IndexField::from_value_map(values_indices, self.items.len()).into_enum();
+        self.field_indexes.insert(name.to_string(), Arc::new(index));
+        Ok(self)
+    }
+
+    pub fn has_index(&self, name: &str) -> bool {
+        self.field_indexes.contains_key(name)
+    }
+
+    pub fn drop_index(&self, name: &str) -> bool {
+        self.field_indexes.remove(name).is_some()
+    }
+
+    pub fn filter_by_field_ops(
+        &self,
+        name: &str,
+        operations: &[(FieldOperation, Op)],
+    ) -> GlobalResult<&Self> {
+        if operations.is_empty() {
+            return Err(GLobalError::FilterData(FilterDataError::EmptyOperations));
+        }
+        let field_index = self.field_indexes
+            .get(name)
+            .map(|index| Arc::clone(index.value()))
+            .ok_or_else(|| GLobalError::Index(IndexError::NotFound { name: name.to_string() }))?;
+        let bitmap = if operations.len() == 1 {
+            field_index.filter_operation(&operations[0].0)
+        } else {
+            field_index.filter_operations(operations)
+        }
+        .map_err(|err| GLobalError::Index(IndexError::Field(err)))?;
+        let positions = bitmap & self.current_positions().as_ref();
+        self.push_level(&format!("Field '{name}'"), positions)
+    }
+
+    fn push_level(&self, description: &str, positions: RoaringBitmap) -> GlobalResult<&Self> {
+        let mut guard = self.levels.write();
+        let InlineLevels { levels, current } = &mut *guard;
+        // Уровни после текущего (после up) отбрасываются
+        levels.truncate(*current + 1);
+        if levels.len() >= MAX_HISTORY {
+            return Err(GLobalError::FilterData(FilterDataError::MaxHistoryExceeded {
+                current: levels.len(),
+                max: MAX_HISTORY,
+            }));
+        }
+        levels.push(InlineLevel {
+            description: Arc::from(description),
+            positions: Arc::new(positions),
+        });
+        *current = levels.len() - 1;
+        Ok(self)
+    }
+
+    // Navigation Methods
+
+    pub fn go_to_level(&self, target_level: usize) -> &Self {
+        let mut levels = self.levels.write();
+        if target_level < levels.levels.len() {
+            levels.levels.truncate(target_level + 1);
+            levels.current = target_level;
+        }
+        self
+    }
+
+    pub fn up(&self) -> &Self {
+        let current = self.current_level();
+        if current > 0 {
+            self.go_to_level(current - 1)
+        } else {
+            self
+        }
+    }
+
+    pub fn reset_to_source(&self) -> &Self {
+        self.go_to_level(0)
+    }
+}
@@ -2,18 +2,63 @@ pub mod allocator;
 pub mod errors;
 pub mod result;
 pub(crate) mod index;
+pub(crate) mod export;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod model;
 pub mod filter;
+pub mod inline;
 pub mod group;
+pub mod query;
+pub(crate) mod query_parser;
+pub mod result_set;
+pub mod catalog;
+pub mod federation;
 
 pub use index::{
+    background::{
+        IndexBuildStatus,
+        IndexHandle,
+    },
     bit::Op,
+    enums::{IndexEnum, IntoFieldValue},
     field::{
+        Bytes,
+        CmpOp,
         FieldOperation,
         FieldValue,
+        IndexAnalizer,
+        IndexKind,
     },
+    cache::ResultCacheStats,
+    limiter::{LimiterStats, QueuePolicy},
+    logic::{NullMode, Truth},
+    normalize::{Collation, DecimalIndexOptions, DecimalRounding, FloatPolicy, StringIndexOptions},
+    pool::{BitmapPoolStats, bitmap_pool_stats},
+    range::RangeBucket,
+    sketch::TopValue,
+    stats::{FieldStats, HistogramBucket, IndexUsage, PlannerHints, QueryHint},
+    storage::Rows,
+    text::{TextAnalyzer, TextIndexOptions},
 };
 
-pub use group::GroupData;
-pub use filter::{FilterData};
+pub use catalog::{Catalog, DatasetConfig};
+pub use export::ExportSnapshot;
+pub use federation::{FederatedResult, Federation};
+pub use group::{GroupData, aggregate::AggregateKind, cursor::GroupCursor};
+pub use inline::InlineFilterData;
+pub use query::{MaterializedView, QueryExpr, QueryStep, SavedQuery, SortOrder, Step};
+pub use result_set::ResultSet;
+pub use filter::{FilterData, IndexReplacement, LevelGuard, PreparedFieldQuery, TrustedAccess};
+pub use model::ValueAggregate;
 pub use ordered_float::OrderedFloat;
+#[cfg(feature = "datetime")]
+pub use index::field::Timestamp;
+#[cfg(feature = "datetime")]
+pub use chrono;
+#[cfg(feature = "uuid")]
+pub use uuid::{self, Uuid};
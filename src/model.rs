@@ -1,3 +1,14 @@
+use super::{
+    group::aggregate::AggregateKind,
+    index::{
+        bit::Op,
+        field::{FieldOperation, FieldValue, IndexAnalizer, IndexKind},
+    },
+    query::QueryStep,
+};
+use roaring::RoaringBitmap;
+use std::{fmt::Display, sync::Arc, time::{Duration, SystemTime}};
+
 #[derive(Debug, Clone)]
 pub struct MemoryStats {
     pub current_level: usize,
@@ -39,4 +50,491 @@ impl MemoryStats {
         }
         self.wasted_items as f64 / self.total_stored_items as f64
     }
-}
\ No newline at end of file
+}
+// Статистика решений планировщика
+#[derive(Debug, Clone, Default)]
+pub struct PlannerStats {
+    pub index_executions: u64,
+    pub predicate_executions: u64,
+    pub adaptive_switches: u64,
+    pub range_cache_hits: u64,
+}
+
+// Память одного индекса (FilterData::index_memory_stats), байты - оценка
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexMemoryStats {
+    pub name: String,
+    // INDEX_FIELD / INDEX_TEXT
+    pub index_type: String,
+    // Вид карты значений field индекса (у text - None)
+    pub kind: Option<IndexKind>,
+    // Bitmap-ы значений, nulls (field) или n-грамм (text)
+    pub bitmap_bytes: usize,
+    // Позиции строк по возрастанию значений (range без обхода bitmap-ов)
+    pub sorted_values_bytes: usize,
+    // Карта значений, строки / байты значений, тексты text индекса
+    pub overhead_bytes: usize,
+    pub total_bytes: usize,
+}
+
+// Сводка field индекса: по ней сравниваются старая и новая версии
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSummary {
+    pub type_name: &'static str,
+    pub rows: usize,
+    pub unique_values: usize,
+    pub nulls: u64,
+}
+
+// Разница между действующим индексом и кандидатом на замену
+#[derive(Debug, Clone)]
+pub struct IndexDiff {
+    pub name: String,
+    pub old: IndexSummary,
+    pub new: IndexSummary,
+}
+
+impl IndexDiff {
+    pub fn is_unchanged(&self) -> bool {
+        self.old == self.new
+    }
+
+    pub fn type_changed(&self) -> bool {
+        self.old.type_name != self.new.type_name
+    }
+
+    // Во сколько раз изменилось число уникальных значений (new / old)
+    pub fn unique_values_ratio(&self) -> f64 {
+        if self.old.unique_values == 0 {
+            return if self.new.unique_values == 0 { 1.0 } else { f64::INFINITY };
+        }
+        self.new.unique_values as f64 / self.old.unique_values as f64
+    }
+}
+
+impl Display for IndexDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "index '{}': type {} -> {}, rows {} -> {}, unique values {} -> {}, nulls {} -> {}",
+            self.name,
+            self.old.type_name, self.new.type_name,
+            self.old.rows, self.new.rows,
+            self.old.unique_values, self.new.unique_values,
+            self.old.nulls, self.new.nulls,
+        )
+    }
+}
+
+// Пересечение двух когорт (FilterData::compare_queries)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CohortDiff {
+    pub only_a: usize,
+    pub only_b: usize,
+    pub both: usize,
+    // Строки (индексы хранилища) - только при compare_queries_with_bitmaps
+    pub bitmaps: Option<CohortBitmaps>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CohortBitmaps {
+    pub only_a: RoaringBitmap,
+    pub only_b: RoaringBitmap,
+    pub both: RoaringBitmap,
+}
+
+impl CohortDiff {
+    pub fn from_bitmaps(a: &RoaringBitmap, b: &RoaringBitmap, keep_bitmaps: bool) -> Self {
+        let both = a & b;
+        let only_a = a - &both;
+        let only_b = b - &both;
+        Self {
+            only_a: only_a.len() as usize,
+            only_b: only_b.len() as usize,
+            both: both.len() as usize,
+            bitmaps: keep_bitmaps.then_some(CohortBitmaps { only_a, only_b, both }),
+        }
+    }
+
+    pub fn total_a(&self) -> usize {
+        self.only_a + self.both
+    }
+
+    pub fn total_b(&self) -> usize {
+        self.only_b + self.both
+    }
+
+    pub fn union(&self) -> usize {
+        self.only_a + self.only_b + self.both
+    }
+
+    // Коэффициент Жаккара: |A ∩ B| / |A ∪ B|
+    pub fn jaccard(&self) -> f64 {
+        match self.union() {
+            0 => 0.0,
+            union => self.both as f64 / union as f64,
+        }
+    }
+}
+
+impl Display for CohortDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "only A: {}, only B: {}, both: {}, jaccard: {:.3}",
+            self.only_a, self.only_b, self.both, self.jaccard(),
+        )
+    }
+}
+
+// Качество данных одного индекса
+#[derive(Debug, Clone)]
+pub struct FieldQuality {
+    pub name: String,
+    // field / text
+    pub index_type: &'static str,
+    // Тип значений (u64, String, ...); для text индекса - String
+    pub value_type: &'static str,
+    pub rows: usize,
+    pub nulls: u64,
+    // Пустые строки ("") - только для строковых значений
+    pub empty: u64,
+    pub min: Option<FieldValue>,
+    pub max: Option<FieldValue>,
+    pub unique_values: usize,
+    pub cardinality_ratio: f64,
+    // Доля самого частого значения
+    pub max_value_ratio: f64,
+    pub is_skewed: bool,
+    // Оценка индекса (только для field)
+    pub verdict: Option<IndexAnalizer>,
+}
+
+impl FieldQuality {
+    // Доля строк без значения (null или пустая строка)
+    pub fn missing_ratio(&self) -> f64 {
+        if self.rows == 0 {
+            return 0.0;
+        }
+        (self.nulls + self.empty) as f64 / self.rows as f64
+    }
+}
+
+// Отчет о качестве данных по всем индексам (отсортирован по имени)
+#[derive(Debug, Clone)]
+pub struct DataQualityReport {
+    pub rows: usize,
+    pub fields: Vec<FieldQuality>,
+}
+
+impl DataQualityReport {
+    pub fn field(&self, name: &str) -> Option<&FieldQuality> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl Display for DataQualityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Data quality report ({} rows):", self.rows)?;
+        for field in &self.fields {
+            let bound = |value: &Option<FieldValue>| value
+                .as_ref()
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            writeln!(
+                f,
+                "  {} [{} {}]: nulls {}, empty {}, min {}, max {}, unique {} ({:.3}), top value {:.1}%{}{}",
+                field.name,
+                field.index_type,
+                field.value_type,
+                field.nulls,
+                field.empty,
+                bound(&field.min),
+                bound(&field.max),
+                field.unique_values,
+                field.cardinality_ratio,
+                field.max_value_ratio * 100.0,
+                if field.is_skewed { ", skewed" } else { "" },
+                field.verdict.as_ref().map(|verdict| format!(", {verdict}")).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Конкретная рекомендация по использованию field индекса
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexRecommendation {
+    // NotEq / NotIn через индекс дороже проверки предикатом
+    PredicateForInverse,
+    // Range запросы объединяют слишком большие bitmap-ы
+    PredicateForRange,
+    // Много различных числовых значений - range объединяет тысячи bitmap-ов
+    BucketedIndex,
+    // Все значения уникальны - точечный поиск через create_unique_index
+    UniqueIndex,
+    // Одно значение на все строки - индекс ничего не отсекает
+    DropIndex,
+}
+
+impl Display for IndexRecommendation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PredicateForInverse => write!(f, "prefer predicate path for NotEq / NotIn"),
+            Self::PredicateForRange => write!(f, "prefer predicate path for range queries"),
+            Self::BucketedIndex => write!(
+                f,
+                "consider bucketed index (computed field value / step) or warm_ranges for range queries"
+            ),
+            Self::UniqueIndex => write!(f, "values are unique: use create_unique_index for point lookups"),
+            Self::DropIndex => write!(f, "single distinct value: index does not filter anything, consider dropping it"),
+        }
+    }
+}
+
+// Разбор оценки IndexAnalizer: метрики, причины и рекомендации
+#[derive(Debug, Clone)]
+pub struct IndexReport {
+    pub name: String,
+    pub value_type: &'static str,
+    pub verdict: IndexAnalizer,
+    pub rows: usize,
+    pub unique_values: usize,
+    pub cardinality_ratio: f64,
+    // Самое частое значение и его доля среди строк индекса
+    pub dominant_value: Option<FieldValue>,
+    pub dominant_value_ratio: f64,
+    pub is_skewed: bool,
+    pub reasons: Vec<String>,
+    pub recommendations: Vec<IndexRecommendation>,
+}
+
+impl IndexReport {
+    pub fn recommends(&self, recommendation: &IndexRecommendation) -> bool {
+        self.recommendations.contains(recommendation)
+    }
+}
+
+impl Display for IndexReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Index '{}' [{}]: {} ({} rows, {} unique)",
+            self.name, self.value_type, self.verdict, self.rows, self.unique_values,
+        )?;
+        for reason in &self.reasons {
+            writeln!(f, "  - {reason}")?;
+        }
+        for recommendation in &self.recommendations {
+            writeln!(f, "  * {recommendation}")?;
+        }
+        Ok(())
+    }
+}
+
+// Путь выполнения запроса по полям
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanPath {
+    Index,
+    Predicate,
+}
+
+// План filter_by_fields_ops (FilterData::explain_field_ops)
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub path: PlanPath,
+    // Причина выбора: hint_never / small_dataset / inefficient_operation / selectivity
+    // (predicate), estimate / hint_always / index_only / force_index (index),
+    // force_scan / pending_index (predicate без оценки)
+    pub reason: &'static str,
+    // Поле, из-за которого выбран predicate path (пусто, если причина общая)
+    pub field: Option<String>,
+    // Порог причины: строки, кардинальность или селективность
+    pub threshold: Option<f64>,
+    // Оценка по индексам и с поправкой обратной связи планировщика
+    pub raw_selectivity: f64,
+    pub selectivity: f64,
+    pub rows: usize,
+    pub estimated_rows: usize,
+    pub fields: Vec<FieldPlan>,
+}
+
+// Поле плана; для поля без готового индекса метрики индекса - None
+#[derive(Debug, Clone)]
+pub struct FieldPlan {
+    pub name: String,
+    pub quality: Option<IndexAnalizer>,
+    pub cardinality_ratio: Option<f64>,
+    pub unique_values: Option<usize>,
+    pub selectivity: Option<f64>,
+    // Запрос отвечается прогретыми бакетами (warm_ranges)
+    pub warmed_range: bool,
+    pub operations: Vec<OperationPlan>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationPlan {
+    pub operation: FieldOperation,
+    pub op: Op,
+    pub selectivity: Option<f64>,
+    // Индекс эффективен для операции (с учетом PlannerHints)
+    pub efficient: Option<bool>,
+}
+
+impl Display for QueryPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = match self.path {
+            PlanPath::Index => "INDEX",
+            PlanPath::Predicate => "PREDICATE",
+        };
+        write!(f, "{path} ({}", self.reason)?;
+        if let Some(field) = &self.field {
+            write!(f, " on '{field}'")?;
+        }
+        if let Some(threshold) = self.threshold {
+            write!(f, ", threshold {threshold}")?;
+        }
+        writeln!(
+            f,
+            "): selectivity {:.4} (raw {:.4}), ~{} of {} rows",
+            self.selectivity, self.raw_selectivity, self.estimated_rows, self.rows,
+        )?;
+        for field in &self.fields {
+            match (&field.quality, field.selectivity) {
+                (Some(quality), Some(selectivity)) => writeln!(
+                    f,
+                    "  {}: {quality}, {} unique, selectivity {selectivity:.4}{}",
+                    field.name,
+                    field.unique_values.unwrap_or_default(),
+                    if field.warmed_range { ", warmed range" } else { "" },
+                )?,
+                _ => writeln!(f, "  {}: no index", field.name)?,
+            }
+            for operation in &field.operations {
+                write!(f, "    {:?} {}", operation.op, operation.operation)?;
+                if let Some(selectivity) = operation.selectivity {
+                    write!(f, ": {selectivity:.4}")?;
+                }
+                if operation.efficient == Some(false) {
+                    write!(f, " (inefficient)")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Агрегаты ResultSet::aggregate / FilterData::aggregate_field
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ValueAggregate {
+    pub count: usize,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl ValueAggregate {
+    pub fn avg(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    // Значение агрегата; без числовых значений count / sum - 0, остальные - None
+    pub fn get(&self, kind: AggregateKind) -> Option<f64> {
+        match kind {
+            AggregateKind::Count => Some(self.count as f64),
+            AggregateKind::Sum => Some(self.sum),
+            AggregateKind::Min => self.min,
+            AggregateKind::Max => self.max,
+            AggregateKind::Avg => self.avg(),
+        }
+    }
+
+    pub(crate) fn push(mut self, value: f64) -> Self {
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        self
+    }
+
+    pub(crate) fn merge(self, other: Self) -> Self {
+        let pick = |a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64| match (a, b) {
+            (Some(a), Some(b)) => Some(f(a, b)),
+            (a, b) => a.or(b),
+        };
+        Self {
+            count: self.count + other.count,
+            sum: self.sum + other.sum,
+            min: pick(self.min, other.min, f64::min),
+            max: pick(self.max, other.max, f64::max),
+        }
+    }
+}
+
+// Результат FilterData::warm_up
+#[derive(Debug, Clone, Default)]
+pub struct WarmUpStats {
+    pub indexes: usize,
+    // Пройдено bitmap-ов (значения field индексов, posting-и text индексов)
+    pub bitmaps: usize,
+    pub bytes: usize,
+    // Строк в предрассчитанном bitmap-е исходного уровня (None - не считался)
+    pub universe_rows: Option<usize>,
+    pub elapsed: Duration,
+}
+
+// Метаданные уровня drill-down
+#[derive(Debug, Clone)]
+pub struct LevelMeta {
+    pub description: Arc<str>,
+    pub created_at: SystemTime,
+    pub row_count: usize,
+    // None - исходный уровень (Source) или checkpoint
+    pub operation: Option<QueryStep>,
+    // Описания уровней, свернутых в checkpoint (от старых к новым)
+    pub collapsed: Vec<Arc<str>>,
+}
+
+impl LevelMeta {
+    pub fn new(description: &str, row_count: usize, operation: Option<QueryStep>) -> Self {
+        Self {
+            description: Arc::from(description),
+            created_at: SystemTime::now(),
+            row_count,
+            operation,
+            collapsed: Vec::new(),
+        }
+    }
+
+    pub fn source(row_count: usize) -> Self {
+        Self::new("Source", row_count, None)
+    }
+
+    // Один checkpoint вместо нескольких последовательных уровней:
+    // строки - как у последнего из них, описания сохраняются
+    pub fn checkpoint(levels: &[Arc<LevelMeta>]) -> Self {
+        let collapsed: Vec<Arc<str>> = levels
+            .iter()
+            .flat_map(|meta| {
+                if meta.is_checkpoint() {
+                    meta.collapsed.clone()
+                } else {
+                    vec![Arc::clone(&meta.description)]
+                }
+            })
+            .collect();
+        let last = levels.last();
+        Self {
+            description: Arc::from(collapsed.join(" AND ")),
+            created_at: last.map(|meta| meta.created_at).unwrap_or_else(SystemTime::now),
+            row_count: last.map(|meta| meta.row_count).unwrap_or_default(),
+            operation: None,
+            collapsed,
+        }
+    }
+
+    pub fn is_checkpoint(&self) -> bool {
+        !self.collapsed.is_empty()
+    }
+}
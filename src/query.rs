@@ -0,0 +1,385 @@
+use super::errors::{FilterDataError, GLobalError};
+use super::result::GlobalResult;
+use super::query_parser::{parse_query, write_field, write_text};
+use super::index::{
+    INDEX_FIELD,
+    INDEX_TEXT,
+    bit::Op,
+    field::{CmpOp, FieldOperation, FieldValue},
+};
+use roaring::RoaringBitmap;
+use std::{fmt::Display, sync::Arc};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Версия формата сохраненного запроса
+pub const SAVED_QUERY_VERSION: u32 = 1;
+
+/// Дерево запроса
+///
+/// Листья - операции над field/text индексами, узлы - логические связки.
+/// Вычисляется над текущей выборкой FilterData.
+///
+/// # Example
+///
+/// let expr = QueryExpr::and(vec![
+///     QueryExpr::field("status", vec![(FieldOperation::eq(500), Op::And)]),
+///     QueryExpr::not(QueryExpr::text("message", "timeout")),
+/// ]);
+/// data.filter_query(&expr)?;
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum QueryExpr {
+    Field {
+        name: String,
+        operations: Vec<(FieldOperation, Op)>,
+    },
+    Text {
+        name: String,
+        query: String,
+    },
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+}
+
+impl QueryExpr {
+    pub fn field(name: &str, operations: Vec<(FieldOperation, Op)>) -> Self {
+        Self::Field {
+            name: name.to_string(),
+            operations,
+        }
+    }
+
+    /// Разобрать запрос из текста
+    /// 
+    /// Приоритет NOT > AND > OR (ключевые слова без учета регистра), скобки группируют.
+    /// Сравнения: `= != <> < <= > >=`, `IN (..)`, `NOT IN (..)`, `BETWEEN a AND b`,
+    /// `IS [NOT] NULL`, `~ "шаблон"` (`*` в начале / конце - EndsWith / StartsWith,
+    /// с обеих сторон - ContainsSubstr), `MATCH "слова"` - запрос к text индексу.
+    /// Значения - строки в кавычках, числа, true / false.
+    /// 
+    /// # Example
+    /// 
+    /// let expr = QueryExpr::parse(r#"price >= 100 AND (category IN ("a", "b") OR name ~ "foo*")"#)?;
+    /// data.filter_query(&expr)?;
+    /// 
+    pub fn parse(text: &str) -> GlobalResult<Self> {
+        parse_query(text).map_err(|err| GLobalError::FilterData(FilterDataError::QueryParse {
+            position: err.position,
+            reason: err.reason,
+        }))
+    }
+
+    // Лист с одной операцией: QueryExpr::op("price", FieldOperation::lt(10))
+    pub fn op(name: &str, operation: FieldOperation) -> Self {
+        Self::field(name, vec![(operation, Op::And)])
+    }
+
+    pub fn text(name: &str, query: &str) -> Self {
+        Self::Text {
+            name: name.to_string(),
+            query: query.to_string(),
+        }
+    }
+
+    pub fn and(exprs: Vec<QueryExpr>) -> Self {
+        Self::And(exprs)
+    }
+
+    pub fn or(exprs: Vec<QueryExpr>) -> Self {
+        Self::Or(exprs)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(expr: QueryExpr) -> Self {
+        Self::Not(Box::new(expr))
+    }
+
+    // Индексы, на которые ссылается запрос: (имя, тип индекса)
+    pub fn referenced_indexes(&self) -> Vec<IndexRef> {
+        let mut result = Vec::new();
+        self.collect_indexes(&mut result);
+        result
+    }
+
+    fn collect_indexes(&self, result: &mut Vec<IndexRef>) {
+        let index_ref = match self {
+            Self::Field { name, .. } => IndexRef::new(name, INDEX_FIELD),
+            Self::Text { name, .. } => IndexRef::new(name, INDEX_TEXT),
+            Self::And(exprs) | Self::Or(exprs) => {
+                exprs.iter().for_each(|expr| expr.collect_indexes(result));
+                return;
+            }
+            Self::Not(expr) => {
+                expr.collect_indexes(result);
+                return;
+            }
+        };
+        if !result.contains(&index_ref) {
+            result.push(index_ref);
+        }
+    }
+}
+
+/// Шаг пакетного drill-down (`FilterData::apply_steps`)
+///
+/// # Example
+///
+/// data.apply_steps(&[
+///     Step::field("status", vec![(FieldOperation::eq(500), Op::And)]),
+///     Step::text("message", "timeout"),
+///     Step::predicate("slow", |log: &Log| log.duration_ms > 1_000),
+/// ])?;
+///
+pub enum Step<T> {
+    Field {
+        name: String,
+        operations: Vec<(FieldOperation, Op)>,
+    },
+    Text {
+        name: String,
+        query: String,
+    },
+    Query(QueryExpr),
+    // Произвольный closure - в описании уровня только description
+    Predicate {
+        description: String,
+        predicate: Arc<dyn Fn(&T) -> bool + Send + Sync>,
+    },
+}
+
+impl<T> Step<T> {
+    pub fn field(name: &str, operations: Vec<(FieldOperation, Op)>) -> Self {
+        Self::Field {
+            name: name.to_string(),
+            operations,
+        }
+    }
+
+    pub fn text(name: &str, query: &str) -> Self {
+        Self::Text {
+            name: name.to_string(),
+            query: query.to_string(),
+        }
+    }
+
+    pub fn query(expr: QueryExpr) -> Self {
+        Self::Query(expr)
+    }
+
+    pub fn predicate<F>(description: &str, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Self::Predicate {
+            description: description.to_string(),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    // Шаг как дерево запроса (None - predicate)
+    pub fn as_expr(&self) -> Option<QueryExpr> {
+        match self {
+            Self::Field { name, operations } => Some(QueryExpr::field(name, operations.clone())),
+            Self::Text { name, query } => Some(QueryExpr::text(name, query)),
+            Self::Query(expr) => Some(expr.clone()),
+            Self::Predicate { .. } => None,
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Self::Predicate { description, .. } => description.clone(),
+            step => step.as_expr().map(|expr| expr.to_string()).unwrap_or_default(),
+        }
+    }
+
+    // Структурированная операция для LevelMeta
+    pub fn query_step(&self) -> QueryStep {
+        match self {
+            Self::Field { name, operations } => QueryStep::Fields(vec![(name.clone(), operations.clone())]),
+            Self::Text { name, query } => QueryStep::Text {
+                name: name.clone(),
+                query: query.clone(),
+            },
+            Self::Query(expr) => QueryStep::Query(expr.clone()),
+            Self::Predicate { .. } => QueryStep::Predicate,
+        }
+    }
+}
+
+impl Display for QueryExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // Текст разбирается обратно через QueryExpr::parse
+            Self::Field { name, operations } => write_field(f, name, operations),
+            Self::Text { name, query } => write_text(f, name, query),
+            Self::And(exprs) | Self::Or(exprs) => {
+                let separator = if matches!(self, Self::And(_)) { " AND " } else { " OR " };
+                let parts = exprs
+                    .iter()
+                    .map(|expr| expr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(separator);
+                write!(f, "({})", parts)
+            }
+            Self::Not(expr) => write!(f, "NOT {}", expr),
+        }
+    }
+}
+
+// Ссылка на индекс из запроса - часть "схемы", нужной для replay
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IndexRef {
+    pub name: String,
+    pub index_type: String,
+}
+
+impl IndexRef {
+    pub fn new(name: &str, index_type: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            index_type: index_type.to_string(),
+        }
+    }
+}
+
+/// Сохраненный запрос: дерево + индексы, которые он использует
+///
+/// Строится через `FilterData::plan_query` и может быть выполнен
+/// над другим FilterData с такой же схемой индексов (`replay_query`).
+///
+/// # Example
+///
+/// let saved = data.plan_query(&expr)?;
+/// let json = saved.to_json()?;            // feature = "serde"
+/// // ... ночью, над свежими данными
+/// fresh.replay_query(&SavedQuery::from_json(&json)?)?;
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SavedQuery {
+    pub version: u32,
+    pub expr: QueryExpr,
+    pub indexes: Vec<IndexRef>,
+}
+
+impl SavedQuery {
+    pub fn new(expr: QueryExpr) -> Self {
+        let indexes = expr.referenced_indexes();
+        Self {
+            version: SAVED_QUERY_VERSION,
+            expr,
+            indexes,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SavedQuery {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(data)
+    }
+}
+
+/// Материализованное представление: сохраненный запрос + его результат
+///
+/// Bitmap хранит индексы хранилища всей базы (не текущей выборки)
+/// и пересчитывается, когда меняется версия любого индекса запроса.
+///
+/// # Example
+///
+/// data.create_view("errors", QueryExpr::field("status", vec![(FieldOperation::gte(500), Op::And)]))?;
+/// let errors = data.view_count("errors")?;   // без вычисления запроса
+/// data.filter_by_view("errors")?;
+///
+#[derive(Debug, Clone)]
+pub struct MaterializedView {
+    pub name: String,
+    pub expr: QueryExpr,
+    pub bitmap: RoaringBitmap,
+    // Версии индексов запроса на момент вычисления
+    pub(crate) versions: Vec<(String, Option<u64>)>,
+    // Число строк хранилища на момент вычисления: push делает view устаревшим
+    pub(crate) rows: usize,
+}
+
+impl MaterializedView {
+    pub fn len(&self) -> usize {
+        self.bitmap.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.bitmap.contains(idx as u32)
+    }
+}
+
+/// Структурированная операция уровня drill-down
+///
+/// Хранится в `LevelMeta::operation` - по ней UI может отрисовать
+/// breadcrumb, не разбирая строку описания.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum QueryStep {
+    // Произвольный closure - структура недоступна
+    Predicate,
+    Fields(Vec<(String, Vec<(FieldOperation, Op)>)>),
+    Text {
+        name: String,
+        query: String,
+    },
+    ComplexText {
+        name: String,
+        or_words: Vec<String>,
+        and_words: Vec<String>,
+        not_words: Vec<String>,
+    },
+    Query(QueryExpr),
+    // Сравнение двух полей строки
+    FieldCmp {
+        left: String,
+        op: CmpOp,
+        right: String,
+    },
+    // Выборка по стабильным первичным ключам
+    Keys(Vec<FieldValue>),
+    // Несколько шагов, примененных одним уровнем (apply_steps)
+    Steps(Vec<QueryStep>),
+    // Перестановка строк выборки по значению поля (sort_by_field)
+    Sort {
+        name: String,
+        order: SortOrder,
+    },
+}
+
+/// Направление сортировки `FilterData::sort_by_field`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl QueryStep {
+    pub fn fields(fields: &[(&str, &[(FieldOperation, Op)])]) -> Self {
+        Self::Fields(
+            fields
+                .iter()
+                .map(|(name, operations)| (name.to_string(), operations.to_vec()))
+                .collect()
+        )
+    }
+}
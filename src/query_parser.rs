@@ -0,0 +1,589 @@
+use super::{
+    index::{
+        bit::Op,
+        field::{FieldOperation, FieldValue},
+    },
+    query::QueryExpr,
+};
+use std::fmt::{Formatter, Result as FmtResult, Write};
+
+// Ошибка разбора текста запроса: позиция (в символах) и причина
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParseError {
+    pub position: usize,
+    pub reason: String,
+}
+
+impl ParseError {
+    fn new(position: usize, reason: impl Into<String>) -> Self {
+        Self { position, reason: reason.into() }
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+// Предел вложенности скобок и NOT: текст приходит от пользователя,
+// а разбор рекурсивный - глубже стек потока не выдержит
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(String),
+    // = == != <> < <= > >= ~
+    Cmp(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+// Ключевые слова - идентификаторы без учета регистра
+const KEYWORDS: [&str; 10] = ["AND", "OR", "NOT", "IN", "IS", "NULL", "BETWEEN", "TRUE", "FALSE", "MATCH"];
+
+// Разбор текста вида `price >= 100 AND (category IN ("a", "b") OR name ~ "foo*")`
+//
+// Приоритет: NOT > AND > OR, скобки группируют. Сравнения: = == != <> < <= > >=,
+// IN / NOT IN (список), BETWEEN a AND b, IS [NOT] NULL, ~ "шаблон" (* в начале
+// и/или в конце: StartsWith / EndsWith / ContainsSubstr), MATCH "слова" - text индекс.
+// Значения: строки в "..." или '...', числа, true / false.
+pub(crate) fn parse_query(text: &str) -> ParseResult<QueryExpr> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, pos: 0, end: text.chars().count() };
+    let expr = parser.parse_or(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.error("unexpected token after end of expression")),
+    }
+}
+
+fn tokenize(text: &str) -> ParseResult<Vec<(usize, Token)>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let start = pos;
+        let ch = chars[pos];
+        let token = match ch {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '~' => Token::Cmp("~"),
+            '=' => {
+                if chars.get(pos + 1) == Some(&'=') {
+                    pos += 1;
+                }
+                Token::Cmp("=")
+            }
+            '!' if chars.get(pos + 1) == Some(&'=') => {
+                pos += 1;
+                Token::Cmp("!=")
+            }
+            '<' => match chars.get(pos + 1) {
+                Some('=') => {
+                    pos += 1;
+                    Token::Cmp("<=")
+                }
+                Some('>') => {
+                    pos += 1;
+                    Token::Cmp("!=")
+                }
+                _ => Token::Cmp("<"),
+            },
+            '>' => {
+                if chars.get(pos + 1) == Some(&'=') {
+                    pos += 1;
+                    Token::Cmp(">=")
+                } else {
+                    Token::Cmp(">")
+                }
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                pos += 1;
+                loop {
+                    match chars.get(pos) {
+                        None => return Err(ParseError::new(start, "unterminated string")),
+                        Some(&c) if c == ch => break,
+                        Some('\\') => {
+                            pos += 1;
+                            match chars.get(pos) {
+                                Some(&escaped) => value.push(escaped),
+                                None => return Err(ParseError::new(start, "unterminated string")),
+                            }
+                        }
+                        Some(&c) => value.push(c),
+                    }
+                    pos += 1;
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_digit() || (c == '-' && chars.get(pos + 1).is_some_and(|next| next.is_ascii_digit())) => {
+                let mut end = pos + 1;
+                while end < chars.len()
+                    && (chars[end].is_ascii_digit()
+                        || chars[end] == '.'
+                        || chars[end] == 'e'
+                        || chars[end] == 'E'
+                        || (matches!(chars[end], '+' | '-') && matches!(chars[end - 1], 'e' | 'E')))
+                {
+                    end += 1;
+                }
+                let number: String = chars[pos..end].iter().collect();
+                pos = end - 1;
+                Token::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = pos + 1;
+                while end < chars.len() && (chars[end].is_alphanumeric() || matches!(chars[end], '_' | '.')) {
+                    end += 1;
+                }
+                let ident: String = chars[pos..end].iter().collect();
+                pos = end - 1;
+                Token::Ident(ident)
+            }
+            c => return Err(ParseError::new(start, format!("unexpected character '{c}'"))),
+        };
+        tokens.push((start, token));
+        pos += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    // Длина текста - позиция ошибки "неожиданный конец"
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, token)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, reason: impl Into<String>) -> ParseError {
+        let position = self.tokens.get(self.pos).map_or(self.end, |(position, _)| *position);
+        ParseError::new(position, reason)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> ParseResult<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {keyword}")))
+        }
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> ParseResult<()> {
+        if self.peek() == Some(&expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {what}")))
+        }
+    }
+
+    fn parse_or(&mut self, depth: usize) -> ParseResult<QueryExpr> {
+        let mut exprs = vec![self.parse_and(depth)?];
+        while self.eat_keyword("OR") {
+            exprs.push(self.parse_and(depth)?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { QueryExpr::or(exprs) })
+    }
+
+    fn parse_and(&mut self, depth: usize) -> ParseResult<QueryExpr> {
+        let mut exprs = vec![self.parse_unary(depth)?];
+        while self.eat_keyword("AND") {
+            exprs.push(self.parse_unary(depth)?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { QueryExpr::and(exprs) })
+    }
+
+    fn parse_unary(&mut self, depth: usize) -> ParseResult<QueryExpr> {
+        let nested = self.is_keyword("NOT") || self.peek() == Some(&Token::LParen);
+        if nested && depth >= MAX_DEPTH {
+            return Err(self.error(format!("expression nested deeper than {MAX_DEPTH}")));
+        }
+        if self.eat_keyword("NOT") {
+            return Ok(QueryExpr::not(self.parse_unary(depth + 1)?));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or(depth + 1)?;
+            self.expect(Token::RParen, "')'")?;
+            return Ok(expr);
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> ParseResult<QueryExpr> {
+        let name = match self.peek() {
+            Some(Token::Ident(ident)) if !KEYWORDS.iter().any(|keyword| ident.eq_ignore_ascii_case(keyword)) => ident.clone(),
+            _ => return Err(self.error("expected field name")),
+        };
+        self.pos += 1;
+        if self.eat_keyword("MATCH") {
+            let query = self.parse_string("text query")?;
+            return Ok(QueryExpr::text(&name, &query));
+        }
+        let operation = if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            if negated { FieldOperation::IsNotNull } else { FieldOperation::IsNull }
+        } else if self.eat_keyword("IN") {
+            FieldOperation::In(self.parse_list()?)
+        } else if self.eat_keyword("NOT") {
+            self.expect_keyword("IN")?;
+            FieldOperation::NotIn(self.parse_list()?)
+        } else if self.eat_keyword("BETWEEN") {
+            let start = self.parse_value()?;
+            self.expect_keyword("AND")?;
+            FieldOperation::Range(start, self.parse_value()?)
+        } else {
+            let op = match self.next() {
+                Some(Token::Cmp(op)) => op,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error(format!("expected comparison after '{name}'")));
+                }
+            };
+            if op == "~" {
+                self.parse_pattern()?
+            } else {
+                let value = self.parse_value()?;
+                match op {
+                    "=" => FieldOperation::Eq(value),
+                    "!=" => FieldOperation::NotEq(value),
+                    "<" => FieldOperation::Lt(value),
+                    "<=" => FieldOperation::Lte(value),
+                    ">" => FieldOperation::Gt(value),
+                    _ => FieldOperation::Gte(value),
+                }
+            }
+        };
+        Ok(QueryExpr::op(&name, operation))
+    }
+
+    fn parse_string(&mut self, what: &str) -> ParseResult<String> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(value),
+            _ => {
+                self.pos -= 1;
+                Err(self.error(format!("expected {what} string")))
+            }
+        }
+    }
+
+    // "foo*" - StartsWith, "*foo" - EndsWith, "*foo*" - ContainsSubstr, без * - Eq
+    fn parse_pattern(&mut self) -> ParseResult<FieldOperation> {
+        let pattern = self.parse_string("pattern")?;
+        let (leading, trailing) = (pattern.starts_with('*'), pattern.len() > 1 && pattern.ends_with('*'));
+        let body = pattern
+            .strip_prefix('*')
+            .unwrap_or(&pattern);
+        let body = if trailing { body.strip_suffix('*').unwrap_or(body) } else { body };
+        if body.contains('*') || body.is_empty() {
+            self.pos -= 1;
+            return Err(self.error("'*' is allowed only at the start or end of a non-empty pattern"));
+        }
+        let body = body.to_string();
+        Ok(match (leading, trailing) {
+            (true, true) => FieldOperation::ContainsSubstr(body),
+            (true, false) => FieldOperation::EndsWith(body),
+            (false, true) => FieldOperation::StartsWith(body),
+            (false, false) => FieldOperation::Eq(FieldValue::String(body)),
+        })
+    }
+
+    fn parse_list(&mut self) -> ParseResult<Vec<FieldValue>> {
+        self.expect(Token::LParen, "'('")?;
+        let mut values = vec![self.parse_value()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.pos += 1;
+            values.push(self.parse_value()?);
+        }
+        self.expect(Token::RParen, "')'")?;
+        Ok(values)
+    }
+
+    fn parse_value(&mut self) -> ParseResult<FieldValue> {
+        let value = match self.peek() {
+            Some(Token::Str(value)) => FieldValue::String(value.clone()),
+            Some(Token::Number(number)) => Self::number(number).ok_or_else(|| self.error(format!("invalid number '{number}'")))?,
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("TRUE") => FieldValue::Bool(true),
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("FALSE") => FieldValue::Bool(false),
+            _ => return Err(self.error("expected value")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    // Целые - i64 (u64 за пределами i64), остальные - f64; тип индекса
+    // приводится при выполнении запроса
+    fn number(number: &str) -> Option<FieldValue> {
+        if let Ok(value) = number.parse::<i64>() {
+            return Some(FieldValue::from(value));
+        }
+        if let Ok(value) = number.parse::<u64>() {
+            return Some(FieldValue::from(value));
+        }
+        number.parse::<f64>().ok().filter(|value| value.is_finite()).map(FieldValue::from)
+    }
+}
+
+// Обратное к parse_query: текст, который разбирается в эквивалентный запрос
+// (QueryExpr::to_string). Field с несколькими операциями - свертка слева,
+// как при выполнении; CONTAINS ANY / ALL - через IN / AND по значениям
+pub(crate) fn write_field(
+    f: &mut Formatter<'_>,
+    name: &str,
+    operations: &[(FieldOperation, Op)],
+) -> FmtResult {
+    let Some(((first, _), rest)) = operations.split_first() else {
+        return f.write_str("()");
+    };
+    let mut text = String::new();
+    write_operation(&mut text, name, first)?;
+    for (operation, op) in rest {
+        let mut right = String::new();
+        write_operation(&mut right, name, operation)?;
+        text = match op {
+            Op::And => format!("({text} AND {right})"),
+            Op::Or => format!("({text} OR {right})"),
+            Op::AndNot => format!("({text} AND NOT {right})"),
+            Op::Xor => format!("(({text} AND NOT {right}) OR (NOT {text} AND {right}))"),
+            Op::Invert => format!("NOT {text}"),
+        };
+    }
+    f.write_str(&text)
+}
+
+pub(crate) fn write_text(f: &mut Formatter<'_>, name: &str, query: &str) -> FmtResult {
+    let mut text = format!("{name} MATCH ");
+    write_string(&mut text, query)?;
+    f.write_str(&text)
+}
+
+fn write_operation(out: &mut String, name: &str, operation: &FieldOperation) -> FmtResult {
+    match operation {
+        FieldOperation::Eq(FieldValue::Null) | FieldOperation::IsNull => {
+            write!(out, "{name} IS NULL")
+        }
+        FieldOperation::NotEq(FieldValue::Null) | FieldOperation::IsNotNull => {
+            write!(out, "{name} IS NOT NULL")
+        }
+        FieldOperation::Eq(value) => write_compare(out, name, "=", value),
+        FieldOperation::NotEq(value) => write_compare(out, name, "!=", value),
+        FieldOperation::Gt(value) => write_compare(out, name, ">", value),
+        FieldOperation::Gte(value) => write_compare(out, name, ">=", value),
+        FieldOperation::Lt(value) => write_compare(out, name, "<", value),
+        FieldOperation::Lte(value) => write_compare(out, name, "<=", value),
+        FieldOperation::In(values) | FieldOperation::ContainsAny(values) => {
+            write!(out, "{name} IN ")?;
+            write_list(out, values)
+        }
+        FieldOperation::NotIn(values) => {
+            write!(out, "{name} NOT IN ")?;
+            write_list(out, values)
+        }
+        FieldOperation::ContainsAll(values) => {
+            out.push('(');
+            for (n, value) in values.iter().enumerate() {
+                if n > 0 {
+                    out.push_str(" AND ");
+                }
+                write_compare(out, name, "=", value)?;
+            }
+            out.push(')');
+            Ok(())
+        }
+        FieldOperation::Range(start, end) => {
+            write!(out, "{name} BETWEEN ")?;
+            write_value(out, start)?;
+            out.push_str(" AND ");
+            write_value(out, end)
+        }
+        FieldOperation::StartsWith(prefix) => write_pattern(out, name, "", prefix, "*"),
+        FieldOperation::EndsWith(suffix) => write_pattern(out, name, "*", suffix, ""),
+        FieldOperation::ContainsSubstr(substr) => write_pattern(out, name, "*", substr, "*"),
+        // В грамматике нет регулярных выражений - такой текст не разбирается
+        #[cfg(feature = "regex")]
+        FieldOperation::Regex(pattern) => {
+            write!(out, "{name} MATCHES ")?;
+            write_string(out, pattern)
+        }
+    }
+}
+
+fn write_compare(out: &mut String, name: &str, op: &str, value: &FieldValue) -> FmtResult {
+    write!(out, "{name} {op} ")?;
+    write_value(out, value)
+}
+
+fn write_pattern(
+    out: &mut String,
+    name: &str,
+    leading: &str,
+    body: &str,
+    trailing: &str,
+) -> FmtResult {
+    write!(out, "{name} ~ ")?;
+    write_string(out, &format!("{leading}{body}{trailing}"))
+}
+
+fn write_list(out: &mut String, values: &[FieldValue]) -> FmtResult {
+    out.push('(');
+    for (n, value) in values.iter().enumerate() {
+        if n > 0 {
+            out.push_str(", ");
+        }
+        write_value(out, value)?;
+    }
+    out.push(')');
+    Ok(())
+}
+
+// Числа и bool - литералы (float всегда с точкой), остальное - строка в "..."
+fn write_value(out: &mut String, value: &FieldValue) -> FmtResult {
+    match value {
+        FieldValue::F64(value) if value.is_finite() => write!(out, "{:?}", value.0),
+        FieldValue::F32(value) if value.is_finite() => write!(out, "{:?}", value.0),
+        FieldValue::U128(_)
+        | FieldValue::I128(_)
+        | FieldValue::U64(_)
+        | FieldValue::I64(_)
+        | FieldValue::U32(_)
+        | FieldValue::I32(_)
+        | FieldValue::U16(_)
+        | FieldValue::I16(_)
+        | FieldValue::U8(_)
+        | FieldValue::I8(_)
+        | FieldValue::Usize(_)
+        | FieldValue::Isize(_)
+        | FieldValue::Decimal(_)
+        | FieldValue::Bool(_) => write!(out, "{value}"),
+        FieldValue::String(text) => write_string(out, text),
+        value => write_string(out, &value.to_string()),
+    }
+}
+
+// Кавычки и обратный слеш экранируются - как в tokenize
+fn write_string(out: &mut String, text: &str) -> FmtResult {
+    out.push('"');
+    for ch in text.chars() {
+        if matches!(ch, '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out.push('"');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_precedence_and_errors() {
+        let expr = parse_query(r#"price >= 100 AND (category IN ("a", 'b') OR name ~ "foo*") OR NOT deleted = true"#).unwrap();
+        let expected = QueryExpr::or(vec![
+            QueryExpr::and(vec![
+                QueryExpr::op("price", FieldOperation::gte(100i64)),
+                QueryExpr::or(vec![
+                    QueryExpr::op("category", FieldOperation::In(vec!["a".into(), "b".into()])),
+                    QueryExpr::op("name", FieldOperation::StartsWith("foo".to_string())),
+                ]),
+            ]),
+            QueryExpr::not(QueryExpr::field("deleted", vec![(FieldOperation::eq(true), Op::And)])),
+        ]);
+        assert_eq!(expr, expected);
+
+        assert_eq!(
+            parse_query("age between 18 and 30.5").unwrap(),
+            QueryExpr::op("age", FieldOperation::Range(18i64.into(), 30.5f64.into()))
+        );
+        assert_eq!(parse_query("email IS NOT NULL").unwrap(), QueryExpr::op("email", FieldOperation::IsNotNull));
+        assert_eq!(parse_query(r#"body MATCH "disk full""#).unwrap(), QueryExpr::text("body", "disk full"));
+        assert_eq!(parse_query("a <> -5").unwrap(), QueryExpr::op("a", FieldOperation::not_eq(-5i64)));
+
+        assert_eq!(parse_query("price >").unwrap_err().position, 7);
+        assert_eq!(parse_query("(a = 1").unwrap_err().position, 6);
+        assert_eq!(parse_query(r#"name = "open"#).unwrap_err().position, 7);
+        assert_eq!(parse_query(r#"name ~ "f*o""#).unwrap_err().position, 7);
+        assert!(parse_query("a = 1 b = 2").is_err());
+        assert!(parse_query("AND = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        let nested = |depth: usize| format!("{}a = 1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_query(&nested(MAX_DEPTH)).is_ok());
+        let error = parse_query(&nested(100_000)).unwrap_err();
+        assert_eq!(error.position, MAX_DEPTH);
+        assert!(error.reason.contains("nested"));
+
+        let negated = |depth: usize| format!("{}a = 1", "NOT ".repeat(depth));
+        assert!(parse_query(&negated(MAX_DEPTH)).is_ok());
+        assert!(parse_query(&negated(100_000)).is_err());
+        assert!(parse_query(&format!("{}{}", "NOT (".repeat(MAX_DEPTH), "a = 1")).is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let exprs = [
+            r#"price >= 100 AND (category IN ("a", "b") OR name ~ "foo*") OR NOT deleted = true"#,
+            r#"body MATCH "disk \"full\"" AND path = "C:\\tmp""#,
+            "age BETWEEN 18 AND 30.5 AND email IS NOT NULL AND score < -2.0",
+            r#"name ~ "*bar" OR name ~ "*oo*" OR id NOT IN (1, 2)"#,
+        ];
+        for text in exprs {
+            let expr = parse_query(text).unwrap();
+            assert_eq!(parse_query(&expr.to_string()).unwrap(), expr, "{expr}");
+        }
+        assert_eq!(QueryExpr::text("body", "disk full").to_string(), r#"body MATCH "disk full""#);
+        assert_eq!(QueryExpr::op("price", FieldOperation::gt(2.0f64)).to_string(), "price > 2.0");
+
+        // Несколько операций поля - свертка слева
+        let expr = QueryExpr::field(
+            "x",
+            vec![
+                (FieldOperation::gt(1i64), Op::And),
+                (FieldOperation::lt(9i64), Op::And),
+                (FieldOperation::eq(20i64), Op::Or),
+            ],
+        );
+        assert_eq!(expr.to_string(), "((x > 1 AND x < 9) OR x = 20)");
+        let expected = QueryExpr::or(vec![
+            QueryExpr::and(vec![
+                QueryExpr::op("x", FieldOperation::gt(1i64)),
+                QueryExpr::op("x", FieldOperation::lt(9i64)),
+            ]),
+            QueryExpr::op("x", FieldOperation::eq(20i64)),
+        ]);
+        assert_eq!(parse_query(&expr.to_string()).unwrap(), expected);
+    }
+}
@@ -0,0 +1,124 @@
+use super::{
+    filter::FilterData,
+    index::{field::FieldValue, storage::Rows},
+    model::ValueAggregate,
+};
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+use std::sync::Arc;
+
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+/// Результат запроса, отделенный от drill-down состояния
+///
+/// Хранит bitmap индексов строк и сами строки хранилища: новые фильтры
+/// и навигация FilterData на него не влияют. Строки идут в порядке
+/// индексов хранилища.
+///
+/// # Example
+///
+/// let result = data.query_result(&QueryExpr::field("status", vec![(FieldOperation::eq("paid"), Op::And)]))?;
+/// let first_page = result.page(0, 50);
+/// let revenue = result.aggregate(|order: &Order| order.amount).sum;
+/// let view = result.to_filter_data();       // drill-down дальше по результату
+///
+pub struct ResultSet<T> {
+    ids: RoaringBitmap,
+    source: Arc<Rows<T>>,
+}
+
+impl<T> ResultSet<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub(crate) fn new(ids: RoaringBitmap, source: Arc<Rows<T>>) -> Self {
+        Self { ids, source }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    // Индексы строк в хранилище (parent_data)
+    pub fn ids(&self) -> &RoaringBitmap {
+        &self.ids
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.ids.contains(id as u32)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<T>> + '_ {
+        self.ids.iter().filter_map(|id| self.source.get(id as usize))
+    }
+
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (usize, &Arc<T>)> + '_ {
+        self.ids
+            .iter()
+            .filter_map(|id| self.source.get(id as usize).map(|item| (id as usize, item)))
+    }
+
+    pub fn items(&self) -> Vec<Arc<T>> {
+        self.iter().cloned().collect()
+    }
+
+    // Страница строк: offset - номер первой строки результата
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<Arc<T>> {
+        self.iter().skip(offset).take(limit).cloned().collect()
+    }
+
+    pub fn pages_count(&self, page_size: usize) -> usize {
+        self.len().div_ceil(page_size.max(1))
+    }
+
+    /// Агрегаты числового значения по строкам результата
+    ///
+    /// Значения без числового представления (строки, bool, null) не учитываются.
+    ///
+    /// # Example
+    ///
+    /// let stats = result.aggregate(|order: &Order| order.amount);
+    /// println!("{} orders, avg {:?}", stats.count, stats.avg());
+    ///
+    pub fn aggregate<V, F>(&self, extractor: F) -> ValueAggregate
+    where
+        F: Fn(&T) -> V + Send + Sync,
+        V: Into<FieldValue>,
+    {
+        let value = |item: &Arc<T>| extractor(item).into().as_f64();
+        if self.len() > PARALLEL_THRESHOLD {
+            let ids: Vec<u32> = self.ids.iter().collect();
+            ids.par_iter()
+                .filter_map(|&id| self.source.get(id as usize).and_then(value))
+                .fold(ValueAggregate::default, ValueAggregate::push)
+                .reduce(ValueAggregate::default, ValueAggregate::merge)
+        } else {
+            self.iter().filter_map(value).fold(ValueAggregate::default(), ValueAggregate::push)
+        }
+    }
+
+    // Пересечение / объединение / разность результатов над одним хранилищем
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self::new(&self.ids & &other.ids, Arc::clone(&self.source))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(&self.ids | &other.ids, Arc::clone(&self.source))
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        Self::new(&self.ids - &other.ids, Arc::clone(&self.source))
+    }
+
+    /// FilterData над строками результата (Indexed view)
+    ///
+    /// View ссылается на хранилище слабо, как `FilterData::from_indices`:
+    /// хранилище должно жить, пока используется view.
+    pub fn to_filter_data(&self) -> FilterData<T> {
+        FilterData::from_indices(&self.source, self.ids.iter().map(|id| id as usize).collect())
+    }
+}
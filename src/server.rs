@@ -0,0 +1,387 @@
+// HTTP сервер запросов (feature = "server")
+//
+// Реестр наборов данных FilterData<serde_json::Value> за axum API:
+//
+// GET    /datasets                  - список наборов
+// PUT    /datasets/{name}           - загрузить набор (JSON массив объектов)
+// DELETE /datasets/{name}           - удалить набор
+// POST   /datasets/{name}/indexes   - создать индекс {"field", "kind", "path"?}
+// POST   /datasets/{name}/query     - запрос {"query": QueryExpr, "offset", "limit"}
+//
+// Запросы не создают уровней drill-down (`query_ids`), поэтому
+// параллельные клиенты не мешают друг другу. Построение набора, индексов
+// и выполнение запросов уходят в `spawn_blocking` и не занимают
+// потоки рантайма.
+
+use super::{
+    errors::{FilterDataError, GLobalError, IndexError},
+    filter::FilterData,
+    query::QueryExpr,
+};
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use dashmap::DashMap;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+// Размер страницы по умолчанию и максимальный
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+pub const MAX_PAGE_LIMIT: usize = 10_000;
+// Лимит тела запроса по умолчанию (PUT /datasets/{name})
+pub const DEFAULT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Реестр наборов данных + HTTP API
+///
+/// # Example
+///
+/// let server = Arc::new(QueryServer::new().with_body_limit(256 * 1024 * 1024));
+/// server.register("orders", rows);
+/// server.serve("0.0.0.0:8080").await?;
+///
+pub struct QueryServer {
+    datasets: DashMap<String, Arc<FilterData<Value>>>,
+    // Максимальный размер тела запроса в байтах
+    body_limit: usize,
+}
+
+impl Default for QueryServer {
+    fn default() -> Self {
+        Self {
+            datasets: DashMap::new(),
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+// Тип индекса по JSON полю
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexSpec {
+    I64,
+    U64,
+    F64,
+    String,
+    Bool,
+    // Текстовый (ngram) индекс по строковому полю
+    Text,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexRequest {
+    // Имя индекса (и поля, если path не задан)
+    pub field: String,
+    pub kind: IndexSpec,
+    // JSON pointer до значения ("/customer/country")
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryRequest {
+    pub query: QueryExpr,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_PAGE_LIMIT
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResponse {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    // Номера строк в исходном наборе
+    pub ids: Vec<usize>,
+    pub rows: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetInfo {
+    pub name: String,
+    pub len: usize,
+    pub indexes: Vec<String>,
+}
+
+// Ошибка API: статус + {"error": "..."}
+pub struct ServerError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ServerError {
+    fn not_found(name: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: format!("dataset not found: {name}"),
+        }
+    }
+
+    fn internal(err: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<GLobalError> for ServerError {
+    fn from(err: GLobalError) -> Self {
+        let status = match err {
+            GLobalError::Index(IndexError::NotFound { .. } | IndexError::NotFoundMany { .. }) => StatusCode::NOT_FOUND,
+            GLobalError::FilterData(FilterDataError::Overloaded { .. } | FilterDataError::QueueTimeout { .. }) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        Self {
+            status,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
+
+type ServerResult<R> = Result<Json<R>, ServerError>;
+
+// CPU работа (сборка набора, индексы, запросы) в пуле блокирующих потоков
+async fn blocking<R, F>(work: F) -> Result<R, ServerError>
+where
+    F: FnOnce() -> Result<R, ServerError> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await.map_err(ServerError::internal)?
+}
+
+// Значение поля строки: JSON pointer или ключ верхнего уровня
+fn lookup<'a>(row: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.starts_with('/') {
+        row.pointer(path)
+    } else {
+        row.get(path)
+    }
+}
+
+impl QueryServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Лимит тела запроса (по умолчанию DEFAULT_BODY_LIMIT)
+    pub fn with_body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = bytes;
+        self
+    }
+
+    pub fn register(&self, name: &str, rows: Vec<Value>) -> Arc<FilterData<Value>> {
+        let data = Arc::new(FilterData::from_vec(rows));
+        self.datasets.insert(name.to_string(), Arc::clone(&data));
+        data
+    }
+
+    pub fn dataset(&self, name: &str) -> Option<Arc<FilterData<Value>>> {
+        self.datasets.get(name).map(|data| Arc::clone(&data))
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.datasets.remove(name).is_some()
+    }
+
+    pub fn create_index(&self, name: &str, request: &IndexRequest) -> Result<DatasetInfo, ServerError> {
+        let data = self.dataset(name).ok_or_else(|| ServerError::not_found(name))?;
+        let path = request.path.clone().unwrap_or_else(|| request.field.clone());
+        let field = request.field.as_str();
+        match request.kind {
+            IndexSpec::I64 => data.create_nullable_field_index(field, move |row: &Value| lookup(row, &path).and_then(Value::as_i64)),
+            IndexSpec::U64 => data.create_nullable_field_index(field, move |row: &Value| lookup(row, &path).and_then(Value::as_u64)),
+            IndexSpec::F64 => data.create_nullable_field_index(field, move |row: &Value| {
+                lookup(row, &path).and_then(Value::as_f64).map(OrderedFloat)
+            }),
+            IndexSpec::String => data.create_nullable_field_index(field, move |row: &Value| {
+                lookup(row, &path).and_then(Value::as_str).map(str::to_string)
+            }),
+            IndexSpec::Bool => data.create_nullable_field_index(field, move |row: &Value| lookup(row, &path).and_then(Value::as_bool)),
+            IndexSpec::Text => data.create_text_index(field, move |row: &Value| {
+                lookup(row, &path).and_then(Value::as_str).unwrap_or_default().to_string()
+            }),
+        }?;
+        Ok(Self::info(name, &data))
+    }
+
+    pub fn query(&self, name: &str, request: &QueryRequest) -> Result<QueryResponse, ServerError> {
+        let data = self.dataset(name).ok_or_else(|| ServerError::not_found(name))?;
+        let ids = data.query_ids(&request.query)?;
+        let limit = request.limit.min(MAX_PAGE_LIMIT);
+        let page: Vec<usize> = ids.iter().skip(request.offset).take(limit).copied().collect();
+        let source = data.storage_rows().unwrap_or_default();
+        let rows = page
+            .iter()
+            .filter_map(|&idx| source.get(idx).map(|row| (**row).clone()))
+            .collect();
+        Ok(QueryResponse {
+            total: ids.len(),
+            offset: request.offset,
+            limit,
+            ids: page,
+            rows,
+        })
+    }
+
+    pub fn datasets(&self) -> Vec<DatasetInfo> {
+        let mut result: Vec<DatasetInfo> = self.datasets
+            .iter()
+            .map(|entry| Self::info(entry.key(), entry.value()))
+            .collect();
+        result.sort_by(|left, right| left.name.cmp(&right.name));
+        result
+    }
+
+    fn info(name: &str, data: &FilterData<Value>) -> DatasetInfo {
+        let mut indexes = data.list_indexes();
+        indexes.sort_unstable();
+        DatasetInfo {
+            name: name.to_string(),
+            len: data.storage_rows().map(|source| source.len()).unwrap_or_default(),
+            indexes,
+        }
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        let body_limit = self.body_limit;
+        Router::new()
+            .route("/datasets", get(list_datasets))
+            .route("/datasets/{name}", put(register_dataset).delete(remove_dataset))
+            .route("/datasets/{name}/indexes", post(create_index))
+            .route("/datasets/{name}/query", post(run_query))
+            .layer(DefaultBodyLimit::max(body_limit))
+            .with_state(self)
+    }
+
+    pub async fn serve(self: Arc<Self>, addr: &str) -> std::io::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, self.router()).await
+    }
+}
+
+async fn list_datasets(State(server): State<Arc<QueryServer>>) -> Json<Vec<DatasetInfo>> {
+    Json(server.datasets())
+}
+
+async fn register_dataset(
+    State(server): State<Arc<QueryServer>>,
+    Path(name): Path<String>,
+    Json(rows): Json<Vec<Value>>,
+) -> ServerResult<DatasetInfo> {
+    blocking(move || {
+        let data = server.register(&name, rows);
+        Ok(Json(QueryServer::info(&name, &data)))
+    })
+    .await
+}
+
+async fn remove_dataset(State(server): State<Arc<QueryServer>>, Path(name): Path<String>) -> Result<StatusCode, ServerError> {
+    if server.remove(&name) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ServerError::not_found(&name))
+    }
+}
+
+async fn create_index(
+    State(server): State<Arc<QueryServer>>,
+    Path(name): Path<String>,
+    Json(request): Json<IndexRequest>,
+) -> ServerResult<DatasetInfo> {
+    blocking(move || server.create_index(&name, &request).map(Json)).await
+}
+
+async fn run_query(
+    State(server): State<Arc<QueryServer>>,
+    Path(name): Path<String>,
+    Json(request): Json<QueryRequest>,
+) -> ServerResult<QueryResponse> {
+    blocking(move || server.query(&name, &request).map(Json)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn call(router: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn server_registers_indexes_and_paginates() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let router = Arc::new(QueryServer::new()).router();
+            let rows: Vec<Value> = (0..50)
+                .map(|id| serde_json::json!({ "id": id, "status": if id % 5 == 0 { "error" } else { "ok" } }))
+                .collect();
+            let (status, info) = call(&router, "PUT", "/datasets/logs", Value::from(rows)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(info["len"], 50);
+
+            let index = serde_json::json!({ "field": "status", "kind": "string" });
+            let (status, info) = call(&router, "POST", "/datasets/logs/indexes", index).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(info["indexes"], serde_json::json!(["status"]));
+
+            let query = serde_json::json!({
+                "query": { "Field": { "name": "status", "operations": [[{ "Eq": { "String": "error" } }, "And"]] } },
+                "offset": 2,
+                "limit": 3,
+            });
+            let (status, page) = call(&router, "POST", "/datasets/logs/query", query).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(page["total"], 10);
+            assert_eq!(page["ids"], serde_json::json!([10, 15, 20]));
+            assert_eq!(page["rows"][0]["id"], 10);
+
+            let (status, _) = call(&router, "POST", "/datasets/missing/query", serde_json::json!({ "query": { "And": [] } })).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn server_rejects_body_over_limit() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let router = Arc::new(QueryServer::new().with_body_limit(64)).router();
+            let rows: Vec<Value> = (0..50).map(|id| serde_json::json!({ "id": id })).collect();
+            let (status, _) = call(&router, "PUT", "/datasets/big", Value::from(rows)).await;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+            let (status, info) = call(&router, "PUT", "/datasets/small", serde_json::json!([{ "id": 1 }])).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(info["len"], 1);
+        });
+    }
+}
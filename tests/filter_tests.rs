@@ -2,7 +2,9 @@
 mod filter_data_tests{
     use rust_decimal::{Decimal, prelude::FromPrimitive};
     use tree_man::{
-        Op, FieldOperation,
+        AggregateKind, Bytes, CmpOp, DatasetConfig, Federation, IndexKind, Op, FieldOperation, FieldValue,
+        IndexBuildStatus, IndexEnum, IntoFieldValue, IndexUsage, NullMode, PlannerHints, QueryHint, Truth,
+        Collation, DecimalIndexOptions, DecimalRounding, FloatPolicy, QueryExpr, QueryStep, SavedQuery, SortOrder, Step, StringIndexOptions, Rows,
         filter::{
             IntoFilterData,
             FilterData,
@@ -930,4 +932,2954 @@ mod filter_data_tests{
         let excluded = [10, 20, 30, 40, 50];
         assert!(items.iter().all(|n| !excluded.contains(&**n)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_field_index_background_build() {
+        let data: Vec<u64> = (0..50_000).collect();
+        let filtered = data.into_filtered();
+        let handle = filtered.create_field_index_background("value", |x| *x % 1000).unwrap();
+        // Пока индекс строится, запросы идут через predicate path
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::eq(7u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 50);
+        assert_eq!(handle.wait(), IndexBuildStatus::Ready);
+        assert!(handle.is_ready());
+        assert!(filtered.has_index("value"));
+        assert!(filtered.index_build_status("value").is_none());
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::eq(7u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 50);
+    }
+
+    #[test]
+    fn test_field_index_lazy_build() {
+        let data: Vec<u64> = (0..50_000).collect();
+        let filtered = data.into_filtered();
+        let handle = filtered.create_field_index_lazy("value", |x| *x % 1000).unwrap();
+        assert_eq!(handle.status(), IndexBuildStatus::Deferred);
+        assert_eq!(filtered.index_build_status("value"), Some(IndexBuildStatus::Deferred));
+        assert!(!filtered.has_index("value"));
+        // Первый запрос запускает построение и идет через predicate path
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::eq(7u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 50);
+        assert_ne!(handle.status(), IndexBuildStatus::Deferred);
+        assert_eq!(handle.wait(), IndexBuildStatus::Ready);
+        assert!(filtered.has_index("value"));
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::eq(7u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 50);
+    }
+
+    #[test]
+    fn test_field_index_background_cancelled_by_drop() {
+        let data: Vec<u64> = (0..200_000).collect();
+        let filtered = data.into_filtered();
+        let handle = filtered.create_field_index_background("value", |x| *x).unwrap();
+        filtered.drop_index("value");
+        let status = handle.wait();
+        // Построение могло успеть завершиться до drop_index, но индекс удален в обоих случаях
+        assert!(matches!(status, IndexBuildStatus::Cancelled | IndexBuildStatus::Ready));
+        assert!(!filtered.has_index("value"));
+    }
+
+    #[test]
+    fn test_field_index_background_superseded_by_rebuild() {
+        let data: Vec<u64> = (0..200).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("value", |x| *x).unwrap();
+        let handle = filtered.create_field_index_background("value", |x| {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            *x % 10
+        }).unwrap();
+        // rebuild получает более новую версию и успевает раньше фонового построения
+        filtered.rebuild_index("value").unwrap();
+        assert_eq!(handle.wait(), IndexBuildStatus::Superseded);
+        assert!(!handle.is_ready());
+        assert!(filtered.index_build_status("value").is_none());
+        filtered.filter_by_field_ops("value", &[(FieldOperation::lt(5u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 5);
+    }
+
+    #[test]
+    fn test_field_index_visible_during_background_swap() {
+        let data: Vec<u64> = (0..20_000).collect();
+        let filtered = data.into_filtered();
+        for _ in 0..20 {
+            filtered.drop_index("value");
+            let handle = filtered.create_field_index_background("value", |x| *x % 100).unwrap();
+            // Запросы во время построения и подмены не теряют индекс
+            while !handle.status().is_finished() {
+                let count = filtered.count_by_field_ops("value", &[(FieldOperation::eq(7u64), Op::And)]);
+                assert_eq!(count.unwrap(), 200);
+            }
+            assert_eq!(handle.wait(), IndexBuildStatus::Ready);
+        }
+    }
+
+    #[test]
+    fn test_field_index_background_catches_up_with_changes() {
+        let data: Vec<u64> = (0..200_000).collect();
+        let filtered = data.into_filtered();
+        let mut pushed = 0;
+        let mut removed = 0;
+        for round in 0..5u64 {
+            filtered.drop_index("value");
+            let handle = filtered.create_field_index_background("value", |x| *x % 10).unwrap();
+            // push и retain во время построения попадают в построенный индекс
+            filtered.push(3).unwrap();
+            filtered.extend([3, 13]).unwrap();
+            pushed += 3;
+            removed += filtered.retain(|x| *x != 100_000 + round * 10 + 3).unwrap();
+            assert_eq!(handle.wait(), IndexBuildStatus::Ready);
+            let count = filtered.count_by_field_ops("value", &[(FieldOperation::eq(3u64), Op::And)]).unwrap();
+            assert_eq!(count, 20_000 + pushed - removed);
+        }
+        assert_eq!(removed, 5);
+    }
+
+    #[test]
+    fn test_field_index_created_after_filter() {
+        let data: Vec<u64> = (0..10_000).collect();
+        let filtered = data.into_filtered();
+        filtered.filter(|x| *x >= 5_000).unwrap();
+        // Индекс строится по исходным данным, а не по текущему уровню
+        filtered.create_field_index("value", |x| *x).unwrap();
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::lt(5_050u64), Op::And),
+        ]).unwrap();
+        let values: Vec<u64> = filtered.items().iter().map(|x| **x).collect();
+        assert_eq!(values, (5_000..5_050).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_field_index_on_indexed_view() {
+        let parent = Arc::new(Rows::from((0..20_000u64).map(Arc::new).collect::<Vec<_>>()));
+        let view_indices: Vec<usize> = (0..20_000).filter(|i| i % 2 == 1).collect();
+        let view = FilterData::from_indices(&parent, view_indices);
+        view.create_field_index("value", |x| *x).unwrap();
+        view.filter_by_field_ops("value", &[
+            (FieldOperation::lt(100u64), Op::And),
+        ]).unwrap();
+        let values: Vec<u64> = view.items().iter().map(|x| **x).collect();
+        assert_eq!(values, (0..100).filter(|x| x % 2 == 1).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_rebuild_index_versioned_swap() {
+        struct Counter {
+            value: AtomicUsize,
+        }
+        let data: Vec<Counter> = (0..5_000).map(|i| Counter { value: AtomicUsize::new(i % 10) }).collect();
+        let filtered = FilterData::from_vec(data);
+        filtered.create_field_index("value", |c| c.value.load(Ordering::Relaxed) as u64).unwrap();
+        let first_version = filtered.index_version("value").unwrap();
+        // Данные изменились - индекс устарел
+        for item in filtered.items().iter() {
+            item.value.store(42, Ordering::Relaxed);
+        }
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                // Во время rebuild индекс всегда доступен
+                for _ in 0..100 {
+                    assert!(filtered.get_index("value").is_ok());
+                }
+            });
+            filtered.rebuild_index("value").unwrap();
+        });
+        assert!(filtered.index_version("value").unwrap() > first_version);
+        let index = filtered.get_index("value").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        let bitmap = field_index.filter_operation(&FieldOperation::eq(42u64)).unwrap();
+        assert_eq!(bitmap.len(), 5_000);
+        assert!(filtered.rebuild_index("missing").is_err());
+    }
+
+    #[test]
+    fn test_field_index_with_memory_target() {
+        let data: Vec<u64> = (0..30_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index_with_memory_target("value", |x| *x % 3_000, 64 * 1024).unwrap();
+        filtered.filter_by_field_ops("value", &[
+            (FieldOperation::eq(5u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 10);
+        assert!(filtered.items().iter().all(|x| **x % 3_000 == 5));
+    }
+
+    #[test]
+    fn test_analyze_correlated_fields() {
+        #[derive(Clone)]
+        struct Request {
+            status: u64,
+            error_code: String,
+        }
+        let data: Vec<Request> = (0..20_000)
+            .map(|i| {
+                // status и error_code полностью коррелированы
+                let failed = i % 5 == 0;
+                Request {
+                    status: if failed { 500 } else { 200 + (i % 7) as u64 },
+                    error_code: if failed { "E42".into() } else { format!("OK{}", i % 7) },
+                }
+            })
+            .collect();
+        let filtered = FilterData::from_vec(data);
+        filtered.create_field_index("status", |r| r.status).unwrap();
+        filtered.create_field_index("error_code", |r| r.error_code.clone()).unwrap();
+        let status_ops = [(FieldOperation::eq(500u64), Op::And)];
+        let error_ops = [(FieldOperation::eq("E42"), Op::And)];
+        let query: [(&str, &[(FieldOperation, Op)]); 2] = [
+            ("status", &status_ops),
+            ("error_code", &error_ops),
+        ];
+        let independent = filtered.estimate_fields_selectivity(&query).unwrap();
+        filtered.analyze(&["status", "error_code"]).unwrap();
+        assert_eq!(filtered.analyzed_fields().len(), 1);
+        let correlated = filtered.estimate_fields_selectivity(&query).unwrap();
+        assert!((correlated - 0.2).abs() < 0.01, "correlated estimate: {}", correlated);
+        assert!(correlated > independent);
+        filtered.filter_by_fields_ops(&query).unwrap();
+        assert_eq!(filtered.len(), 4_000);
+        // Удаление индекса сбрасывает связанную статистику
+        filtered.drop_index("status");
+        assert!(filtered.analyzed_fields().is_empty());
+    }
+
+    #[test]
+    fn test_adaptive_execution_switches_and_learns() {
+        #[derive(Clone)]
+        struct Event {
+            kind: u64,
+            user: u64,
+        }
+        // kind = 0 у 90% строк, но индекс видит 2000 уникальных значений
+        let data: Vec<Event> = (0..20_000u64)
+            .map(|i| Event {
+                kind: if i % 10 == 0 { i } else { 0 },
+                user: i % 100,
+            })
+            .collect();
+        let filtered = FilterData::from_vec(data);
+        filtered.create_field_index("kind", |e| e.kind).unwrap();
+        filtered.create_field_index("user", |e| e.user).unwrap();
+        let kind_ops = [(FieldOperation::eq(0u64), Op::And)];
+        let user_ops = [(FieldOperation::in_values((0..50u64).collect()), Op::And)];
+        let query: [(&str, &[(FieldOperation, Op)]); 2] = [
+            ("kind", &kind_ops),
+            ("user", &user_ops),
+        ];
+        let expected = filtered.items().iter().filter(|e| e.kind == 0 && e.user < 50).count();
+        filtered.filter_by_fields_ops(&query).unwrap();
+        assert_eq!(filtered.len(), expected);
+        assert!(filtered.items().iter().all(|e| e.kind == 0 && e.user < 50));
+        let stats = filtered.planner_stats();
+        assert_eq!(stats.index_executions, 1);
+        assert_eq!(stats.adaptive_switches, 1);
+        // Оценка была занижена - поправка увеличивает следующие оценки
+        assert!(filtered.planner_correction(&["user", "kind"]).unwrap() > 1.0);
+        filtered.reset_to_source();
+        filtered.filter_by_fields_ops(&query).unwrap();
+        assert_eq!(filtered.len(), expected);
+        assert_eq!(filtered.planner_stats().predicate_executions, 1);
+    }
+
+    #[test]
+    fn test_planner_feedback_measures_against_index() {
+        let data: Vec<u64> = (0..20_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("bucket", |x| *x % 100).unwrap();
+        filtered.create_field_index("mirror", |x| *x % 100).unwrap();
+        filtered.filter_by_field_ops("bucket", &[(FieldOperation::lt(50u64), Op::And)]).unwrap();
+        // В текущей выборке проходят все строки, по индексу - половина:
+        // поправка считается относительно индекса, а не уровня drill-down
+        let query: [(&str, &[(FieldOperation, Op)]); 1] = [("mirror", &[(FieldOperation::lt(50u64), Op::And)])];
+        filtered.filter_by_fields_ops_with_hint(&query, QueryHint::ForceIndex).unwrap();
+        assert_eq!(filtered.len(), 10_000);
+        let correction = filtered.planner_correction(&["mirror"]).unwrap();
+        assert!((0.8..1.25).contains(&correction), "correction {correction}");
+    }
+
+    #[test]
+    fn test_warm_up_indexes() {
+        let data: Vec<u64> = (0..20_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("rem", |x| *x % 100).unwrap();
+        filtered.create_text_index("label", |x| format!("item {x}")).unwrap();
+
+        let stats = filtered.warm_up(&["rem"], &["label"]).unwrap();
+        assert_eq!(stats.indexes, 2);
+        // 100 значений + nulls, плюс posting-и text индекса
+        assert!(stats.bitmaps > 101);
+        assert!(stats.bytes > 0);
+        assert!(stats.universe_rows.is_none());
+        assert!(!filtered.has_universe_bitmap());
+
+        // Неверный тип или имя - ошибка до прогрева
+        assert!(filtered.warm_up(&["label"], &[]).is_err());
+        assert!(filtered.warm_up(&["rem"], &["missing"]).is_err());
+
+        filtered.filter(|x| *x < 1_000).unwrap();
+        let stats = filtered.warm_up_with_universe(&["rem"], &[]).unwrap();
+        assert_eq!(stats.universe_rows, Some(20_000));
+        assert!(filtered.has_universe_bitmap());
+        // Запрос по всей базе не зависит от текущей выборки
+        let ids = filtered.query_ids(&QueryExpr::field("rem", vec![(FieldOperation::eq(7u64), Op::And)])).unwrap();
+        assert_eq!(ids.len(), 200);
+    }
+
+    #[test]
+    fn test_warm_ranges_answer_aligned_queries() {
+        let data: Vec<u64> = (0..20_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("price", |x| *x).unwrap();
+        filtered.warm_ranges("price", &[
+            (0u64..100).into(),
+            (100u64..500).into(),
+            (500u64..).into(),
+        ]).unwrap();
+        assert_eq!(filtered.warmed_ranges("price").len(), 3);
+        // Не выровнен с бакетами - обычный путь
+        filtered.filter_by_field_ops("price", &[
+            (FieldOperation::gte(19_990u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.planner_stats().range_cache_hits, 0);
+        filtered.reset_to_source();
+        // [100, 500) + [500, ..) из кеша
+        filtered.filter_by_field_ops("price", &[
+            (FieldOperation::gte(100u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 19_900);
+        assert_eq!(filtered.planner_stats().range_cache_hits, 1);
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("price", &[
+            (FieldOperation::gte(100u64), Op::And),
+            (FieldOperation::lt(500u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 400);
+        assert_eq!(filtered.planner_stats().range_cache_hits, 2);
+        // После rebuild кеш не используется
+        filtered.rebuild_index("price").unwrap();
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("price", &[
+            (FieldOperation::gte(0u64), Op::And),
+            (FieldOperation::lt(100u64), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 100);
+        assert_eq!(filtered.planner_stats().range_cache_hits, 2);
+    }
+
+    fn query_products(offset: u64) -> FilterData<Product> {
+        let products: Vec<Product> = (0..1_000)
+            .map(|i| Product {
+                id: i + offset,
+                price: i % 100,
+                category: if i % 2 == 0 { "books".to_string() } else { "games".to_string() },
+                in_stock: i % 3 == 0,
+            })
+            .collect();
+        let filtered = FilterData::from_vec(products);
+        filtered.create_field_index("price", |p| p.price).unwrap();
+        filtered.create_field_index("in_stock", |p| p.in_stock).unwrap();
+        filtered.create_text_index("category", |p| p.category.clone()).unwrap();
+        filtered
+    }
+
+    #[test]
+    fn test_plan_and_replay_query() {
+        let first = query_products(0);
+        let expr = QueryExpr::and(vec![
+            QueryExpr::or(vec![
+                QueryExpr::field("price", vec![(FieldOperation::lt(10u64), Op::And)]),
+                QueryExpr::field("in_stock", vec![(FieldOperation::eq(true), Op::And)]),
+            ]),
+            QueryExpr::not(QueryExpr::text("category", "games")),
+        ]);
+        let saved = first.plan_query(&expr).unwrap();
+        assert_eq!(saved.indexes.len(), 3);
+        first.replay_query(&saved).unwrap();
+        assert!(first.items()
+            .iter()
+            .all(|p| (p.price < 10 || p.in_stock) && p.category == "books"));
+        let expected = (0..1_000u64)
+            .filter(|i| i % 2 == 0 && (i % 100 < 10 || i % 3 == 0))
+            .count();
+        assert_eq!(first.len(), expected);
+        assert_eq!(first.current_level(), 1);
+
+        // Другой набор данных с той же схемой
+        let second = query_products(10_000);
+        second.replay_query(&saved).unwrap();
+        assert_eq!(second.len(), expected);
+        assert!(second.items().iter().all(|p| p.id >= 10_000));
+    }
+
+    #[test]
+    fn test_replay_query_schema_mismatch() {
+        let first = query_products(0);
+        let saved = first.plan_query(&QueryExpr::text("category", "books")).unwrap();
+        let other: FilterData<Product> = FilterData::from_vec(vec![]);
+        assert!(other.replay_query(&saved).is_err());
+        // То же имя, но другой тип индекса
+        other.create_field_index("category", |p| p.category.clone()).unwrap();
+        assert!(other.replay_query(&saved).is_err());
+        let unsupported = SavedQuery { version: 99, ..saved };
+        assert!(first.replay_query(&unsupported).is_err());
+        assert!(first.plan_query(&QueryExpr::field("unknown", vec![])).is_err());
+    }
+
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn test_datetime_field_index() {
+        use tree_man::{Timestamp, chrono::{Duration, TimeZone, Utc}};
+
+        #[derive(Clone)]
+        struct LogRecord {
+            at: Timestamp,
+            level: &'static str,
+        }
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let records: Vec<LogRecord> = (0..1_000i64)
+            .map(|minute| LogRecord {
+                at: start + Duration::minutes(minute),
+                level: if minute % 10 == 0 { "error" } else { "info" },
+            })
+            .collect();
+        let data = records.into_filtered();
+        data.create_field_index("at", |record: &LogRecord| record.at).unwrap();
+
+        let from = start + Duration::minutes(100);
+        let to = start + Duration::minutes(199);
+        data.filter_by_field_ops("at", &[(FieldOperation::range(from, to), Op::And)]).unwrap();
+        assert_eq!(data.len(), 100);
+        assert!(data.items().iter().all(|record| record.at >= from && record.at <= to));
+
+        // Границы строкой RFC 3339 и predicate path дают тот же результат
+        data.reset_to_source();
+        data.filter_by_field_ops("at", &[
+            (FieldOperation::gte("2024-01-01T01:40:00Z"), Op::And),
+            (FieldOperation::lt("2024-01-01T03:20:00+00:00"), Op::And),
+        ]).unwrap();
+        assert_eq!(data.len(), 100);
+        data.reset_to_source();
+        data.filter(|record| record.level == "error").unwrap();
+        data.filter_by_field_ops("at", &[(FieldOperation::eq(start + Duration::minutes(110)), Op::And)]).unwrap();
+        assert_eq!(data.len(), 1);
+
+        assert_eq!(FieldValue::from(start).to_string(), "2024-01-01T00:00:00+00:00");
+        assert!(FieldOperation::gt(start).evaluate(&FieldValue::from(to)));
+        assert!(!FieldOperation::eq(1_704_067_200i64).evaluate(&FieldValue::from(start)));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid_field_index() {
+        use tree_man::Uuid;
+
+        let ids: Vec<Uuid> = (0..2_000u128).map(|n| Uuid::from_u128(n * 7_919)).collect();
+        let data = ids.clone().into_filtered();
+        data.create_field_index("id", |id: &Uuid| *id).unwrap();
+
+        data.filter_by_field_ops("id", &[(FieldOperation::eq(ids[42]), Op::And)]).unwrap();
+        assert_eq!(data.items().iter().map(|id| **id).collect::<Vec<_>>(), vec![ids[42]]);
+        data.reset_to_source();
+
+        // IN со смесью Uuid и строковой записи
+        data.filter_by_field_ops("id", &[(
+            FieldOperation::in_values(vec![FieldValue::from(ids[1]), FieldValue::from(ids[2].to_string())]),
+            Op::And,
+        )]).unwrap();
+        assert_eq!(data.len(), 2);
+        data.reset_to_source();
+
+        assert_eq!(FieldValue::from(ids[3]).to_string(), ids[3].hyphenated().to_string());
+        assert!(FieldOperation::eq(ids[5].to_string()).evaluate(&FieldValue::from(ids[5])));
+        assert!(!FieldOperation::eq(5u64).evaluate(&FieldValue::from(ids[5])));
+    }
+
+    #[test]
+    fn test_bytes_field_index() {
+        // 6-байтные MAC адреса: первый байт - производитель
+        let macs: Vec<[u8; 6]> = (0..1_024u32)
+            .map(|n| [(n % 4) as u8, 0x1a, 0x2b, (n >> 8) as u8, n as u8, 0xff])
+            .collect();
+        let data = macs.clone().into_filtered();
+        data.create_field_index("mac", |mac: &[u8; 6]| Bytes::from(*mac)).unwrap();
+
+        data.filter_by_field_ops("mac", &[(FieldOperation::eq(macs[10]), Op::And)]).unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(*data.items()[0], macs[10]);
+        data.reset_to_source();
+
+        data.filter_by_field_ops("mac", &[(FieldOperation::in_values(vec![macs[1], macs[2], macs[3]]), Op::And)]).unwrap();
+        assert_eq!(data.len(), 3);
+        data.reset_to_source();
+
+        // Лексикографический диапазон: все MAC производителя 0x02
+        data.filter_by_field_ops("mac", &[(FieldOperation::range([2u8, 0, 0, 0, 0, 0], [2u8, 0xff, 0xff, 0xff, 0xff, 0xff]), Op::And)]).unwrap();
+        assert_eq!(data.len(), 256);
+        assert!(data.items().iter().all(|mac| mac[0] == 2));
+        data.reset_to_source();
+
+        // hex строка как операнд
+        let hex = Bytes::from(macs[7]).to_string();
+        assert_eq!(hex, "031a2b0007ff");
+        data.filter_by_field_ops("mac", &[(FieldOperation::eq(hex.as_str()), Op::And)]).unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(Bytes::from_hex(&hex), Some(Bytes::from(macs[7])));
+        assert_eq!(Bytes::from_hex("abc"), None);
+        assert!(FieldOperation::gt(vec![1u8]).evaluate(&FieldValue::from(vec![1u8, 0])));
+        assert!(!FieldOperation::eq(1u8).evaluate(&FieldValue::from(vec![1u8])));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_saved_query_json_roundtrip() {
+        let first = query_products(0);
+        let expr = QueryExpr::and(vec![
+            QueryExpr::field("price", vec![
+                (FieldOperation::range(10u64, 20u64), Op::And),
+                (FieldOperation::not_eq(15u64), Op::And),
+            ]),
+            QueryExpr::text("category", "books"),
+        ]);
+        let json = first.plan_query(&expr).unwrap().to_json().unwrap();
+        let restored = SavedQuery::from_json(&json).unwrap();
+        assert_eq!(restored.expr, expr);
+        let second = query_products(5_000);
+        second.replay_query(&restored).unwrap();
+        assert!(second.items().iter().all(|p| (10..=20).contains(&p.price) && p.price != 15));
+    }
+
+    #[test]
+    fn test_levels_meta_breadcrumb() {
+        let filtered = query_products(0);
+        filtered.filter_by_field_ops("price", &[(FieldOperation::lt(50u64), Op::And)]).unwrap();
+        filtered.search_with_text("category", "books").unwrap();
+        filtered.filter(|p| p.in_stock).unwrap();
+
+        let meta = filtered.levels_meta();
+        assert_eq!(meta.len(), 4);
+        assert_eq!(meta[0].row_count, 1_000);
+        assert!(meta[0].operation.is_none());
+        assert_eq!(meta[1].row_count, 500);
+        assert_eq!(meta[1].operation, Some(QueryStep::Fields(vec![
+            ("price".to_string(), vec![(FieldOperation::lt(50u64), Op::And)]),
+        ])));
+        assert_eq!(meta[2].row_count, 250);
+        assert_eq!(meta[2].operation, Some(QueryStep::Text {
+            name: "category".to_string(),
+            query: "books".to_string(),
+        }));
+        assert_eq!(meta[3].row_count, filtered.len());
+        assert_eq!(meta[3].operation, Some(QueryStep::Predicate));
+        assert!(meta.windows(2).all(|pair| pair[0].created_at <= pair[1].created_at));
+        assert_eq!(filtered.level_name(2).as_deref(), Some(&*meta[2].description));
+
+        filtered.go_to_level(1);
+        assert_eq!(filtered.levels_meta().len(), 2);
+        filtered.reset_to_source();
+        let meta = filtered.levels_meta();
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta[0].row_count, 1_000);
+    }
+
+    #[test]
+    fn test_planner_hints_per_index() {
+        let data: Vec<u64> = (0..10_000).map(|i| i % 100).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("bucket", |x| *x).unwrap();
+        let range = [(FieldOperation::lt(5u64), Op::And)];
+        // Низкая кардинальность - эвристика отклоняет range по индексу
+        filtered.filter_by_field_ops("bucket", &range).unwrap();
+        assert_eq!(filtered.planner_stats().predicate_executions, 1);
+        filtered.reset_to_source();
+
+        filtered.set_planner_hints("bucket", PlannerHints::new()
+            .with_cardinality_low_threshold(0.001)).unwrap();
+        filtered.filter_by_field_ops("bucket", &range).unwrap();
+        assert_eq!(filtered.len(), 500);
+        assert_eq!(filtered.planner_stats().index_executions, 1);
+        filtered.reset_to_source();
+
+        // Never сильнее оценки: eq очень селективный, но индекс не используется
+        filtered.never_use_index("bucket").unwrap();
+        assert_eq!(filtered.planner_hints("bucket").unwrap().usage, IndexUsage::Never);
+        assert_eq!(filtered.planner_hints("bucket").unwrap().cardinality_low_threshold, Some(0.001));
+        filtered.filter_by_field_ops("bucket", &[(FieldOperation::eq(7u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 100);
+        assert_eq!(filtered.planner_stats().predicate_executions, 2);
+        filtered.reset_to_source();
+
+        // Always - даже для неселективного запроса
+        filtered.clear_planner_hints("bucket");
+        filtered.always_use_index("bucket").unwrap();
+        filtered.filter_by_field_ops("bucket", &[(FieldOperation::gte(10u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 9_000);
+        assert_eq!(filtered.planner_stats().index_executions, 2);
+
+        assert!(filtered.set_planner_hints("unknown", PlannerHints::new()).is_err());
+        filtered.drop_index("bucket");
+        assert!(filtered.planner_hints("bucket").is_none());
+    }
+
+    #[test]
+    fn test_query_hint_force_index_and_scan() {
+        let data: Vec<u64> = (0..10_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("value", |x| *x).unwrap();
+        // Неселективный запрос - планировщик выбрал бы predicate path
+        let wide = [(FieldOperation::gte(1_000u64), Op::And)];
+        filtered.filter_by_field_ops_with_hint("value", &wide, QueryHint::ForceIndex).unwrap();
+        assert_eq!(filtered.len(), 9_000);
+        assert_eq!(filtered.planner_stats().index_executions, 1);
+        filtered.reset_to_source();
+
+        // Селективный запрос - планировщик выбрал бы индекс
+        let narrow = [(FieldOperation::eq(42u64), Op::And)];
+        filtered.filter_by_field_ops_with_hint("value", &narrow, QueryHint::ForceScan).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered.planner_stats().predicate_executions, 1);
+        filtered.reset_to_source();
+
+        filtered.filter_by_fields_ops_with_hint(&[
+            ("value", &wide[..]),
+            ("value", &[(FieldOperation::lt(1_010u64), Op::And)][..]),
+        ], QueryHint::ForceIndex).unwrap();
+        assert_eq!(filtered.len(), 10);
+        let stats = filtered.planner_stats();
+        assert_eq!(stats.index_executions, 2);
+        assert_eq!(stats.adaptive_switches, 0);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    enum OrderStatus {
+        New,
+        Paid,
+        Shipped,
+        Cancelled = 10,
+    }
+
+    tree_man::impl_index_enum!(OrderStatus { New, Paid, Shipped, Cancelled });
+
+    #[test]
+    fn test_enum_index_values_and_count_by() {
+        let statuses = [OrderStatus::New, OrderStatus::Paid, OrderStatus::Shipped];
+        let data: Vec<(u64, OrderStatus)> = (0..300u64)
+            .map(|id| (id, statuses[id as usize % 3]))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_enum_index("status", |order: &(u64, OrderStatus)| order.1).unwrap();
+
+        assert_eq!(OrderStatus::Cancelled.to_code(), 10);
+        assert_eq!(
+            filtered.enum_values::<OrderStatus>("status").unwrap(),
+            vec![OrderStatus::New, OrderStatus::Paid, OrderStatus::Shipped],
+        );
+
+        filtered.filter(|order| order.0 < 30).unwrap();
+        filtered.filter_by_field_ops("status", &[
+            (FieldOperation::not_eq(OrderStatus::New), Op::And),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 20);
+        assert!(filtered.items().iter().all(|order| order.1 != OrderStatus::New));
+        assert_eq!(
+            filtered.count_by_enum::<OrderStatus>("status").unwrap(),
+            vec![(OrderStatus::Paid, 10), (OrderStatus::Shipped, 10)],
+        );
+        assert!(filtered.count_by_enum::<OrderStatus>("missing").is_err());
+    }
+
+    #[test]
+    fn test_nullable_index_three_valued_logic() {
+        // Каждая третья строка без скидки
+        let data: Vec<(u64, Option<u32>)> = (0..3_000u64)
+            .map(|id| (id, (id % 3 != 0).then_some((id % 2) as u32)))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_nullable_field_index("discount", |row: &(u64, Option<u32>)| row.1).unwrap();
+        let not_zero = [(FieldOperation::not_eq(0u32), Op::And)];
+
+        // Двузначный режим: null попадает в отрицание
+        assert_eq!(filtered.null_mode(), NullMode::TwoValued);
+        filtered.filter_by_field_ops("discount", &not_zero).unwrap();
+        assert_eq!(filtered.len(), 2_000);
+        filtered.reset_to_source();
+
+        filtered.set_null_mode(NullMode::ThreeValued);
+        filtered.filter_by_field_ops("discount", &not_zero).unwrap();
+        assert_eq!(filtered.len(), 1_000);
+        assert!(filtered.items().iter().all(|row| row.1 == Some(1)));
+        filtered.reset_to_source();
+
+        // NOT (discount = 0 OR id < 1500): Unknown остается Unknown после NOT
+        let expr = QueryExpr::not(QueryExpr::or(vec![
+            QueryExpr::field("discount", vec![(FieldOperation::eq(0u32), Op::And)]),
+            QueryExpr::field("id", vec![(FieldOperation::lt(1_500u64), Op::And)]),
+        ]));
+        filtered.create_field_index("id", |row: &(u64, Option<u32>)| row.0).unwrap();
+        filtered.filter_query(&expr).unwrap();
+        assert_eq!(filtered.len(), 500);
+        assert!(filtered.items().iter().all(|row| row.0 >= 1_500 && row.1 == Some(1)));
+        filtered.reset_to_source();
+
+        // discount = 0 OR id < 1500: null строки с id < 1500 - True
+        let expr = QueryExpr::or(vec![
+            QueryExpr::field("discount", vec![(FieldOperation::eq(0u32), Op::And)]),
+            QueryExpr::field("id", vec![(FieldOperation::lt(1_500u64), Op::And)]),
+        ]);
+        filtered.filter_query(&expr).unwrap();
+        assert_eq!(filtered.len(), 1_500 + 500);
+    }
+
+    #[test]
+    fn test_is_null_operations() {
+        let data: Vec<(u64, Option<u32>)> = (0..3_000u64)
+            .map(|id| (id, (id % 3 != 0).then_some((id % 2) as u32)))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_nullable_field_index("discount", |row: &(u64, Option<u32>)| row.1).unwrap();
+
+        filtered.filter_by_field_ops("discount", &[(FieldOperation::is_null(), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 1_000);
+        assert!(filtered.items().iter().all(|row| row.1.is_none()));
+        filtered.reset_to_source();
+
+        filtered.filter_by_field_ops("discount", &[(FieldOperation::is_not_null(), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 2_000);
+        filtered.reset_to_source();
+
+        // IS NULL всегда определен - в трехзначном режиме null строки не теряются
+        filtered.set_null_mode(NullMode::ThreeValued);
+        filtered.filter_by_field_ops("discount", &[
+            (FieldOperation::eq(1u32), Op::And),
+            (FieldOperation::is_null(), Op::Or),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 1_000 + 1_000);
+        filtered.reset_to_source();
+
+        // Селективность по bitmap-у nulls
+        let index = filtered.get_index("discount").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        assert!((field_index.estimate_operation_selectivity(&FieldOperation::IsNull) - 1.0 / 3.0).abs() < 1e-9);
+        assert!((field_index.estimate_operation_selectivity(&FieldOperation::IsNotNull) - 2.0 / 3.0).abs() < 1e-9);
+
+        // Null в значениях: сравнения ложны, IS NULL - истина
+        let null = FieldValue::from(None::<u32>);
+        assert!(null.is_null());
+        assert_eq!(FieldValue::from(Some(5u32)), FieldValue::U32(5));
+        assert!(!FieldOperation::lt(10u32).evaluate(&null));
+        assert!(!FieldOperation::eq(FieldValue::Null).evaluate(&null));
+        assert!(FieldOperation::IsNull.evaluate(&null));
+        assert_eq!(FieldOperation::IsNull.evaluate_nullable(None), Truth::True);
+        assert_eq!(FieldOperation::IsNotNull.evaluate_nullable(Some(&null)), Truth::False);
+        assert_eq!(FieldOperation::IsNotNull.to_string(), "IS NOT NULL");
+    }
+
+    #[test]
+    fn test_multi_field_index_contains() {
+        #[derive(Clone)]
+        struct Post {
+            id: u64,
+            tags: Vec<String>,
+        }
+
+        let tags = ["rust", "db", "web", "async"];
+        // id % 4 == 0 - без тегов, иначе теги по битам id
+        let posts: Vec<Post> = (0..4_000u64)
+            .map(|id| Post {
+                id,
+                tags: tags
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| id % 4 != 0 && (id >> bit) & 1 == 1)
+                    .map(|(_, tag)| tag.to_string())
+                    .collect(),
+            })
+            .collect();
+        let expected = |f: &dyn Fn(&Post) -> bool| posts.iter().filter(|post| f(post)).count();
+        let filtered = posts.clone().into_filtered();
+        filtered.create_multi_field_index("tags", |post: &Post| post.tags.clone()).unwrap();
+
+        filtered.filter_by_field_ops("tags", &[(FieldOperation::contains_any(vec!["rust", "async"]), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), expected(&|p| p.tags.iter().any(|t| t == "rust" || t == "async")));
+        filtered.reset_to_source();
+
+        filtered.filter_by_field_ops("tags", &[(FieldOperation::contains_all(vec!["rust", "db"]), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), expected(&|p| p.tags.contains(&"rust".to_string()) && p.tags.contains(&"db".to_string())));
+        assert!(filtered.items().iter().all(|p| p.id % 4 == 3));
+        filtered.reset_to_source();
+
+        // Eq - членство, пустой список - null
+        filtered.filter_by_field_ops("tags", &[(FieldOperation::eq("web"), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), expected(&|p| p.tags.contains(&"web".to_string())));
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("tags", &[(FieldOperation::is_null(), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 1_000);
+        filtered.reset_to_source();
+
+        // Значение, которого нет в индексе - пустой результат
+        assert!(filtered.filter_by_field_ops("tags", &[(FieldOperation::contains_all(vec!["rust", "go"]), Op::And)]).is_err());
+        assert_eq!(filtered.len(), 4_000);
+        let index = filtered.get_index("tags").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        assert!(field_index.is_multi_valued());
+        assert!(FieldOperation::contains_any(vec![1u64, 2]).evaluate(&FieldValue::from(2u64)));
+        assert!(!FieldOperation::contains_all(vec![1u64, 2]).evaluate(&FieldValue::from(2u64)));
+    }
+
+    #[test]
+    fn test_history_compaction_checkpoints() {
+        let data: Vec<u64> = (0..10_000).collect();
+        let filtered = data.into_filtered();
+        filtered.set_history_compaction(Some(3));
+        // Больше шагов, чем MAX_HISTORY
+        for step in 1..=80u64 {
+            filtered.filter(move |x| *x >= step * 100).unwrap();
+        }
+        assert_eq!(filtered.len(), 2_000);
+        // Source + checkpoint + 3 последних уровня
+        assert_eq!(filtered.stored_levels_count(), 5);
+        assert_eq!(filtered.current_level(), 4);
+        let checkpoint = filtered.level_meta(1).unwrap();
+        assert!(checkpoint.is_checkpoint());
+        assert_eq!(checkpoint.collapsed.len(), 77);
+        assert_eq!(checkpoint.row_count, 2_300);
+        assert!(!filtered.level_meta(2).unwrap().is_checkpoint());
+
+        // Переход к checkpoint и обратно к исходным данным
+        filtered.go_to_level(1);
+        assert_eq!(filtered.len(), 2_300);
+        assert!(filtered.items().iter().all(|x| **x >= 7_700));
+        filtered.reset_to_source();
+        assert_eq!(filtered.len(), 10_000);
+
+        // Ручное сжатие
+        filtered.set_history_compaction(None);
+        for step in 1..=6u64 {
+            filtered.filter(move |x| *x >= step * 1_000).unwrap();
+        }
+        assert_eq!(filtered.compact_history(2), 3);
+        assert_eq!(filtered.stored_levels_count(), 4);
+        assert_eq!(filtered.level_meta(1).unwrap().collapsed.len(), 4);
+        assert_eq!(filtered.compact_history(2), 0);
+        filtered.up();
+        assert_eq!(filtered.len(), 5_000);
+    }
+
+    #[test]
+    fn test_approx_top_values() {
+        // Частые url + длинный хвост уникальных
+        let data: Vec<(u64, String)> = (0..50_000u64)
+            .map(|id| {
+                let url = match id % 20 {
+                    0..=5 => "/home".to_string(),
+                    6..=8 => "/search".to_string(),
+                    9 => "/cart".to_string(),
+                    _ => format!("/item/{}", id),
+                };
+                (id, url)
+            })
+            .collect();
+        let filtered = data.into_filtered();
+        assert!(filtered.approx_top_values("url", 3).is_err());
+
+        filtered.create_top_values_sketch("url", |hit: &(u64, String)| hit.1.clone(), 256).unwrap();
+        let top = filtered.approx_top_values("url", 3).unwrap();
+        let values: Vec<FieldValue> = top.iter().map(|item| item.value.clone()).collect();
+        assert_eq!(values, vec![
+            FieldValue::from("/home"),
+            FieldValue::from("/search"),
+            FieldValue::from("/cart"),
+        ]);
+        assert!(top[0].count >= 15_000 && top[0].guaranteed_count() <= 15_000);
+
+        // Без sketch-а - точный ответ по field индексу
+        filtered.drop_top_values_sketch("url");
+        filtered.create_field_index("url", |hit: &(u64, String)| hit.1.clone()).unwrap();
+        let exact = filtered.approx_top_values("url", 2).unwrap();
+        assert_eq!(exact[0].count, 15_000);
+        assert_eq!(exact[1].count, 7_500);
+        assert_eq!(exact[1].error, 0);
+    }
+
+    #[test]
+    fn test_sum_avg_with_value_sums() {
+        // (region, amount)
+        let data: Vec<(u32, u64)> = (0..20_000u64)
+            .map(|id| ((id % 4) as u32, id % 100))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("region", |order: &(u32, u64)| order.0).unwrap();
+        filtered.create_field_index("amount", |order: &(u32, u64)| order.1).unwrap();
+        filtered.filter_by_field_ops("region", &[(FieldOperation::eq(1u32), Op::And)]).unwrap();
+
+        let expected: u64 = filtered.items().iter().map(|order| order.1).sum();
+        let scanned = filtered.sum_field("amount").unwrap();
+        assert_eq!(scanned, expected as f64);
+        assert!(!filtered.has_value_sums("amount"));
+
+        filtered.precompute_value_sums("amount").unwrap();
+        assert!(filtered.has_value_sums("amount"));
+        assert_eq!(filtered.sum_field("amount").unwrap(), scanned);
+        let avg = filtered.avg_field("amount").unwrap().unwrap();
+        assert!((avg - expected as f64 / 5_000.0).abs() < 1e-9);
+
+        // После rebuild значения устаревают - снова полный проход
+        filtered.create_field_index("amount", |order: &(u32, u64)| order.1 * 2).unwrap();
+        assert!(!filtered.has_value_sums("amount"));
+        assert_eq!(filtered.sum_field("amount").unwrap(), (expected * 2) as f64);
+
+        filtered.create_field_index("name", |order: &(u32, u64)| format!("n{}", order.0)).unwrap();
+        assert!(filtered.precompute_value_sums("name").is_err());
+    }
+
+    #[test]
+    fn test_materialized_view() {
+        // (status, service)
+        let data: Vec<(u32, u32)> = (0..10_000u32)
+            .map(|id| (if id % 10 == 0 { 500 } else { 200 }, id % 3))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("status", |hit: &(u32, u32)| hit.0).unwrap();
+        filtered.create_field_index("service", |hit: &(u32, u32)| hit.1).unwrap();
+        let expr = QueryExpr::field("status", vec![(FieldOperation::gte(500u32), Op::And)]);
+        filtered.create_view("errors", expr).unwrap();
+        assert_eq!(filtered.view_count("errors").unwrap(), 1_000);
+        assert_eq!(filtered.view_names(), vec!["errors".to_string()]);
+
+        // Представление считается по всей базе, фильтр - в пределах текущей выборки
+        filtered.filter_by_field_ops("service", &[(FieldOperation::eq(0u32), Op::And)]).unwrap();
+        assert_eq!(filtered.view_count("errors").unwrap(), 1_000);
+        filtered.filter_by_view("errors").unwrap();
+        assert_eq!(filtered.len(), 334);
+        assert!(filtered.items().iter().all(|hit| hit.0 == 500 && hit.1 == 0));
+        let level = filtered.current_level();
+        assert!(matches!(filtered.level_meta(level).unwrap().operation, Some(QueryStep::Query(_))));
+
+        // Новый индекс status - представление пересчитывается
+        filtered.create_field_index("status", |hit: &(u32, u32)| hit.0 + 300).unwrap();
+        assert_eq!(filtered.view_count("errors").unwrap(), 10_000);
+
+        assert!(filtered.drop_view("errors"));
+        assert!(filtered.view("errors").is_err());
+        assert!(filtered.create_view("missing", QueryExpr::text("message", "timeout")).is_err());
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let data: Vec<(u64, String)> = vec![
+            (1, "book".to_string()),
+            (2, "pen, blue".to_string()),
+            (3, "book".to_string()),
+        ];
+        let filtered = data.into_filtered();
+        filtered.create_field_index("id", |row: &(u64, String)| row.0).unwrap();
+        filtered.create_field_index("name", |row: &(u64, String)| row.1.clone()).unwrap();
+        filtered.filter_by_field_ops("id", &[(FieldOperation::gte(2u64), Op::And)]).unwrap();
+
+        let mut out = Vec::new();
+        let rows = filtered.export_csv(&mut out, &["id", "name"]).unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "id,name\n2,\"pen, blue\"\n3,book\n");
+        assert!(filtered.export_csv(Vec::new(), &["id", "missing"]).is_err());
+    }
+
+    #[test]
+    fn test_export_snapshot_is_consistent() {
+        let data: Vec<u64> = (0..50_000).collect();
+        let filtered = Arc::new(data.into_filtered());
+        filtered.create_field_index("value", |x: &u64| *x).unwrap();
+        filtered.filter(|x| x % 10 == 0).unwrap();
+
+        let snapshot = filtered.export_snapshot(&["value"]).unwrap();
+        assert_eq!((snapshot.level(), snapshot.watermark(), snapshot.len()), (1, 50_000, 5_000));
+        assert_eq!(snapshot.columns(), vec!["value"]);
+
+        // Навигация во время выгрузки не блокируется и не меняет снимок
+        let writer = thread::spawn(move || {
+            let mut out = Vec::new();
+            let rows = snapshot.write_csv(&mut out).unwrap();
+            (rows, out)
+        });
+        filtered.filter(|x| x % 20 == 0).unwrap();
+        filtered.reset_to_source();
+        let (rows, out) = writer.join().unwrap();
+        assert_eq!(rows, 5_000);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("value\n0\n10\n20\n"));
+        assert_eq!(text.lines().count(), 5_001);
+        assert!(filtered.export_snapshot(&["missing"]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_export_json_lines() {
+        let data: Vec<(u64, String)> = vec![(1, "book".to_string()), (2, "pen".to_string())];
+        let filtered = data.into_filtered();
+        filtered.create_field_index("id", |row: &(u64, String)| row.0).unwrap();
+        filtered.create_field_index("name", |row: &(u64, String)| row.1.clone()).unwrap();
+        let mut out = Vec::new();
+        filtered.export_json_lines(&mut out, &["id", "name"]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"id\":1,\"name\":\"book\"}\n{\"id\":2,\"name\":\"pen\"}\n");
+
+        let mut out = Vec::new();
+        assert_eq!(filtered.export_json_lines_serialized(&mut out).unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "[1,\"book\"]\n[2,\"pen\"]\n");
+    }
+
+    #[cfg(feature = "polars")]
+    #[test]
+    fn test_polars_roundtrip() {
+        use polars::prelude::*;
+
+        let df = df!(
+            "id" => [1i64, 2, 3, 4],
+            "status" => [Some("paid"), Some("new"), None, Some("paid")],
+            "price" => [10.5f64, 20.0, 30.0, 40.0],
+        ).unwrap();
+        let filtered = FilterData::from_polars(&df).unwrap();
+        assert_eq!(filtered.len(), 4);
+        assert_eq!(filtered.items()[2].get("status"), None);
+        filtered.filter_by_field_ops("status", &[(FieldOperation::eq("paid"), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 2);
+
+        let result = filtered.to_polars(&["id", "price"]).unwrap();
+        assert_eq!(result.height(), 2);
+        let ids: Vec<Option<i64>> = result.column("id").unwrap().i64().unwrap().into_iter().collect();
+        assert_eq!(ids, vec![Some(1), Some(4)]);
+        assert_eq!(result.column("price").unwrap().dtype(), &DataType::Float64);
+        assert!(filtered.to_polars(&["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_base_filter_row_level_security() {
+        // (tenant, amount)
+        let data: Vec<(u32, u64)> = (0..1_000u64).map(|id| ((id % 4) as u32, id)).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("tenant", |row: &(u32, u64)| row.0).unwrap();
+        filtered.create_field_index("amount", |row: &(u32, u64)| row.1).unwrap();
+        filtered.set_base_filter_query(&QueryExpr::field("tenant", vec![(FieldOperation::eq(2u32), Op::And)])).unwrap();
+        assert!(filtered.has_base_filter());
+        assert_eq!(filtered.len(), 250);
+
+        filtered.filter_by_field_ops("amount", &[(FieldOperation::lt(100u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 25);
+        filtered.reset_to_source();
+        assert_eq!(filtered.len(), 250);
+        assert!(filtered.items().iter().all(|row| row.0 == 2));
+
+        // Запросы чужого tenant-а ничего не находят
+        assert!(filtered.filter_by_field_ops("tenant", &[(FieldOperation::eq(1u32), Op::And)]).is_err());
+        let other = QueryExpr::field("tenant", vec![(FieldOperation::eq(1u32), Op::And)]);
+        assert!(filtered.query_ids(&other).unwrap().is_empty());
+        filtered.create_view("all", QueryExpr::field("amount", vec![(FieldOperation::gte(0u64), Op::And)])).unwrap();
+        assert_eq!(filtered.view_count("all").unwrap(), 250);
+
+        // Повторный вызов только сужает
+        filtered.set_base_filter((0..1_000u32).collect());
+        assert_eq!(filtered.len(), 250);
+        filtered.set_base_filter((0..100u32).collect());
+        assert_eq!(filtered.len(), 25);
+        assert_eq!(filtered.view_count("all").unwrap(), 25);
+    }
+
+    #[test]
+    fn test_base_filter_index_reads() {
+        // (tenant, id, text)
+        let data: Vec<(u32, u64, String)> = (0..100u64)
+            .map(|id| ((id / 10) as u32, id, format!("order {}", id % 3)))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("tenant", |row: &(u32, u64, String)| row.0).unwrap();
+        filtered.create_text_index("text", |row: &(u32, u64, String)| row.2.clone()).unwrap();
+        filtered.set_key(|row: &(u32, u64, String)| row.1).unwrap();
+        filtered.set_base_filter((0..10u32).collect());
+
+        // Индексы построены по всем строкам, но видны только строки base filter
+        let hits = filtered.get_indices_with_text("text", "order 1").unwrap();
+        assert_eq!(hits, vec![1, 4, 7]);
+        assert_eq!(filtered.row_id_of(&FieldValue::from(5u64)).unwrap(), Some(5));
+        assert_eq!(filtered.row_id_of(&FieldValue::from(50u64)).unwrap(), None);
+        assert_eq!(filtered.key_of(50).unwrap(), None);
+
+        filtered.create_unique_index("id", |row: &(u32, u64, String)| row.1).unwrap();
+        assert_eq!(filtered.get_by_key("id", 5u64).unwrap().map(|row| row.1), Some(5));
+        assert!(filtered.get_by_key("id", 50u64).unwrap().is_none());
+
+        filtered.create_field_index("bucket", |row: &(u32, u64, String)| row.1 % 3).unwrap();
+        assert_eq!(filtered.field_top_values("bucket", 2).unwrap(), vec![(FieldValue::from(0u64), 4), (FieldValue::from(1u64), 3)]);
+        assert_eq!(filtered.field_top_values("tenant", 5).unwrap(), vec![(FieldValue::from(0u32), 10)]);
+
+        let duplicates = filtered.duplicates("bucket").unwrap();
+        assert_eq!(duplicates.len(), 3);
+        assert!(duplicates.iter().all(|(_, rows)| rows.max() < Some(10)));
+        assert_eq!(filtered.duplicates_above("bucket", 3).unwrap().len(), 1);
+        assert!(filtered.duplicates("tenant").unwrap().iter().all(|(_, rows)| rows.len() == 10));
+
+        filtered.create_field_index("id", |row: &(u32, u64, String)| row.1).unwrap();
+        let stats = filtered.field_stats("id").unwrap();
+        assert_eq!((stats.min.clone(), stats.max.clone()), (Some(FieldValue::from(0u64)), Some(FieldValue::from(9u64))));
+        assert_eq!(stats.unique_values, 10);
+    }
+
+    #[test]
+    fn test_base_filter_hides_other_tenant_everywhere() {
+        // (tenant, id, text): tenant 0 - "alpha", tenant 1 - "omega"
+        let data: Vec<(u32, u64, String)> = (0..100u64)
+            .map(|id| ((id % 2) as u32, id, if id % 2 == 0 { "alpha".to_string() } else { "omega".to_string() }))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("tenant", |row: &(u32, u64, String)| row.0).unwrap();
+        filtered.create_field_index("kind", |row: &(u32, u64, String)| row.2.clone()).unwrap();
+        filtered.create_text_index("text", |row: &(u32, u64, String)| row.2.clone()).unwrap();
+        filtered.create_top_values_sketch("kind", |row: &(u32, u64, String)| row.2.clone(), 16).unwrap();
+        let trusted = filtered.trusted_access().unwrap();
+        filtered.set_base_filter_query(&QueryExpr::field("tenant", vec![(FieldOperation::eq(0u32), Op::And)])).unwrap();
+        assert_eq!(filtered.len(), 50);
+
+        // Сырые данные и индексы целиком - только через trusted_access до фильтра
+        assert!(filtered.parent_data().is_none());
+        assert!(filtered.get_index("tenant").is_err());
+        assert!(filtered.trusted_access().is_err());
+        assert_eq!(trusted.parent_data().unwrap().len(), 100);
+        assert!(trusted.get_index("tenant").is_ok());
+
+        let top = filtered.approx_top_values("kind", 5).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].value.clone(), top[0].count), (FieldValue::from("alpha"), 50));
+
+        let ngrams = filtered.top_text("text", 100).unwrap();
+        assert!(!ngrams.is_empty());
+        assert!(ngrams.iter().all(|(ngram, count)| "alpha".contains(ngram.as_str()) && *count == 50));
+        let listed = filtered.list_text_ngrams("text").unwrap();
+        assert!(listed.iter().all(|ngram| "alpha".contains(ngram.as_str())));
+        assert!(filtered.text_stats("text", "ome").unwrap().is_none());
+        assert!(filtered.text_stats("text", "alp").unwrap().is_some());
+        assert_eq!(filtered.text_index_stats("text").unwrap().total_items, 50);
+
+        let quality = filtered.data_quality_report();
+        assert_eq!(quality.rows, 50);
+        let kind = quality.field("kind").unwrap();
+        assert_eq!((kind.rows, kind.unique_values), (50, 1));
+        assert_eq!((kind.min.clone(), kind.max.clone()), (Some(FieldValue::from("alpha")), Some(FieldValue::from("alpha"))));
+        let text = quality.field("text").unwrap();
+        assert_eq!((text.rows, text.unique_values), (50, 1));
+
+        let report = filtered.index_report("tenant").unwrap();
+        assert_eq!((report.rows, report.unique_values), (50, 1));
+        assert_eq!(report.dominant_value, Some(FieldValue::from(0u32)));
+    }
+
+    #[test]
+    fn test_validate_indexes_detects_stale_data() {
+        use std::sync::atomic::AtomicU64;
+        // Данные меняются в обход FilterData через interior mutability
+        let data: Vec<(u64, AtomicU64, String)> = (0..1_000u64)
+            .map(|id| (id, AtomicU64::new(id % 10), format!("item {id}")))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("id", |row: &(u64, AtomicU64, String)| row.0).unwrap();
+        filtered.create_field_index("bucket", |row: &(u64, AtomicU64, String)| row.1.load(Ordering::Relaxed)).unwrap();
+        filtered.create_text_index("name", |row: &(u64, AtomicU64, String)| row.2.clone()).unwrap();
+        assert!(filtered.validate_indexes());
+        assert!(filtered.stale_indexes().is_empty());
+
+        filtered.items()[500].1.store(42, Ordering::Relaxed);
+        assert!(!filtered.validate_indexes());
+        assert_eq!(filtered.stale_indexes(), vec!["bucket".to_string()]);
+
+        filtered.rebuild_index("bucket").unwrap();
+        assert!(filtered.validate_indexes());
+        filtered.filter_by_field_ops("bucket", &[(FieldOperation::eq(42u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[test]
+    fn test_to_owned_vec_detached_copy() {
+        let products: Vec<Product> = (0..20_000u64)
+            .map(|id| Product { id, price: id % 100, category: "books".to_string(), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.filter(|p| p.in_stock).unwrap();
+        let mut owned = filtered.to_owned_vec();
+        assert_eq!(owned.len(), 10_000);
+        assert!(owned.iter().all(|p| p.in_stock));
+        owned[0].price = 1_000;
+        assert_eq!(filtered.items()[0].price, 0);
+
+        filtered.filter(|p| p.id < 10).unwrap();
+        let ids: Vec<u64> = filtered.to_owned_vec().into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![0, 2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_replace_field_index_checked() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 50, category: format!("cat{}", id % 5), in_stock: true })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        let version = filtered.index_version("price").unwrap();
+
+        // Опечатка в extractor-е: вместо цены - флаг наличия
+        let replacement = filtered.replace_field_index_checked("price", |p: &Product| p.in_stock).unwrap();
+        let diff = replacement.diff().clone();
+        assert!(diff.type_changed());
+        assert_eq!(diff.old.unique_values, 50);
+        assert_eq!(diff.new.unique_values, 1);
+        drop(replacement);
+        assert_eq!(filtered.index_version("price"), Some(version));
+        filtered.filter_by_field_ops("price", &[(FieldOperation::eq(10u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 20);
+        filtered.reset_to_source();
+
+        let replacement = filtered.replace_field_index_checked("price", |p: &Product| p.price * 100).unwrap();
+        assert!(!replacement.diff().type_changed());
+        assert_eq!(replacement.diff().unique_values_ratio(), 1.0);
+        assert!(replacement.commit());
+        filtered.filter_by_field_ops("price", &[(FieldOperation::eq(1_000u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 20);
+        assert!(filtered.validate_indexes());
+
+        assert!(filtered.replace_field_index_checked("missing", |p: &Product| p.id).is_err());
+    }
+
+    #[test]
+    fn test_data_quality_report() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product {
+                id,
+                price: id % 10,
+                category: if id % 4 == 0 { String::new() } else { "books".to_string() },
+                in_stock: id < 900,
+            })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_nullable_field_index("discount", |p: &Product| p.id.is_multiple_of(2).then_some(p.id)).unwrap();
+        filtered.create_text_index("title", |p: &Product| p.category.clone()).unwrap();
+
+        let report = filtered.data_quality_report();
+        assert_eq!(report.rows, 1_000);
+        let names: Vec<&str> = report.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, vec!["category", "discount", "price", "title"]);
+
+        let price = report.field("price").unwrap();
+        assert_eq!(price.min, Some(FieldValue::U64(0)));
+        assert_eq!(price.max, Some(FieldValue::U64(9)));
+        assert_eq!(price.unique_values, 10);
+        assert!(!price.is_skewed);
+        assert!(price.verdict.is_some());
+
+        let category = report.field("category").unwrap();
+        assert_eq!(category.empty, 250);
+        assert!(category.is_skewed);
+        assert_eq!(category.missing_ratio(), 0.25);
+
+        assert_eq!(report.field("discount").unwrap().nulls, 500);
+        let title = report.field("title").unwrap();
+        assert_eq!(title.empty, 250);
+        assert_eq!(title.unique_values, 2);
+        assert!(title.verdict.is_none());
+        assert!(report.to_string().contains("category [index_field String]"));
+    }
+
+    #[test]
+    fn test_filter_field_cmp() {
+        // (bytes_sent, bytes_received)
+        let data: Vec<(u64, u64)> = (0..2_000u64).map(|id| (id % 100, (id * 7) % 100)).collect();
+        let expected = data.iter().filter(|row| row.0 > row.1).count();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("bytes_sent", |row: &(u64, u64)| row.0).unwrap();
+        filtered.create_field_index("bytes_received", |row: &(u64, u64)| row.1).unwrap();
+
+        filtered.filter_field_cmp("bytes_sent", CmpOp::Gt, "bytes_received").unwrap();
+        assert_eq!(filtered.len(), expected);
+        assert!(filtered.items().iter().all(|row| row.0 > row.1));
+        assert_eq!(
+            filtered.level_meta(1).unwrap().operation,
+            Some(QueryStep::FieldCmp {
+                left: "bytes_sent".to_string(),
+                op: CmpOp::Gt,
+                right: "bytes_received".to_string(),
+            })
+        );
+
+        // Через derived индекс - тот же результат
+        filtered.reset_to_source();
+        filtered.create_field_cmp_index("bytes_sent", CmpOp::Gt, "bytes_received").unwrap();
+        assert!(filtered.has_index("bytes_sent > bytes_received"));
+        filtered.filter_by_field_ops("bytes_sent", &[(FieldOperation::lt(50u64), Op::And)]).unwrap();
+        filtered.filter_field_cmp("bytes_sent", CmpOp::Gt, "bytes_received").unwrap();
+        assert!(filtered.items().iter().all(|row| row.0 > row.1 && row.0 < 50));
+
+        filtered.reset_to_source();
+        filtered.filter_field_cmp("bytes_sent", CmpOp::Eq, "bytes_received").unwrap();
+        assert!(filtered.items().iter().all(|row| row.0 == row.1));
+        assert!(filtered.filter_field_cmp("bytes_sent", CmpOp::Gt, "missing").is_err());
+    }
+
+    #[test]
+    fn test_computed_field() {
+        // (revenue, cost)
+        let data: Vec<(i64, i64)> = (0..1_000i64).map(|id| (id % 100, (id * 3) % 100)).collect();
+        let expected: Vec<i64> = data.iter().map(|row| row.0 - row.1).filter(|margin| *margin < 0).collect();
+        let filtered = data.into_filtered();
+        filtered.register_computed_field("margin", |row: &(i64, i64)| row.0 - row.1);
+        assert_eq!(filtered.computed_fields(), vec!["margin".to_string()]);
+        assert!(!filtered.has_index("margin"));
+
+        filtered.filter_by_field_ops("margin", &[(FieldOperation::lt(0i64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), expected.len());
+        assert_eq!(filtered.sum_field("margin").unwrap(), expected.iter().sum::<i64>() as f64);
+        let sorted = filtered.sorted_items_by_field("margin", false).unwrap();
+        assert!(sorted.windows(2).all(|pair| pair[0].0 - pair[0].1 <= pair[1].0 - pair[1].1));
+
+        // Материализация - тот же результат через индекс
+        filtered.reset_to_source();
+        filtered.materialize_computed_field("margin").unwrap();
+        assert!(filtered.has_index("margin"));
+        filtered.filter_by_field_ops("margin", &[(FieldOperation::lt(0i64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), expected.len());
+
+        assert!(filtered.drop_computed_field("margin"));
+        assert!(filtered.materialize_computed_field("margin").is_err());
+    }
+
+    #[test]
+    fn test_saved_segments() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+
+        let cheap_games = QueryExpr::and(vec![
+            QueryExpr::field("category", vec![(FieldOperation::eq("games"), Op::And)]),
+            QueryExpr::field("price", vec![(FieldOperation::lt(20u64), Op::And)]),
+        ]);
+        filtered.save_segment("cheap_games", cheap_games.clone()).unwrap();
+        filtered.save_segment("in_stock", QueryExpr::field("in_stock", vec![(FieldOperation::eq(true), Op::And)])).unwrap();
+        assert_eq!(filtered.segment_names(), vec!["cheap_games".to_string(), "in_stock".to_string()]);
+        assert_eq!(filtered.segment("cheap_games").unwrap().expr, cheap_games);
+
+        filtered.apply_segment("cheap_games").unwrap();
+        assert_eq!(filtered.len(), 100);
+        assert_eq!(filtered.level_name(1).as_deref(), Some("segment cheap_games"));
+        // Сегменты комбинируются с drill-down
+        filtered.apply_segment("in_stock").unwrap();
+        assert!(filtered.items().iter().all(|p| p.category == "games" && p.price < 20 && p.in_stock));
+        assert_eq!(filtered.current_level(), 2);
+
+        assert!(filtered.save_segment("bad", QueryExpr::field("missing", vec![(FieldOperation::eq(1u64), Op::And)])).is_err());
+        assert!(filtered.remove_segment("cheap_games"));
+        assert!(filtered.apply_segment("cheap_games").is_err());
+    }
+
+    #[test]
+    fn test_items_with_ids() {
+        let data: Vec<u64> = (0..100).map(|value| value * 10).collect();
+        let filtered = data.into_filtered();
+        filtered.filter(|value| value % 30 == 0).unwrap();
+        let with_ids = filtered.items_with_ids();
+        assert_eq!(with_ids.len(), filtered.len());
+        assert!(with_ids.iter().all(|(idx, value)| **value == *idx as u64 * 10));
+        let ids: Vec<usize> = filtered.iter_items_with_ids().map(|(idx, _)| idx).take(3).collect();
+        assert_eq!(ids, vec![0, 3, 6]);
+
+        // Indexed storage - индексы parent_data
+        let parent = filtered.parent_data().unwrap();
+        let view = FilterData::from_indices(&parent, vec![5, 7, 9]);
+        view.filter(|value| *value > 60).unwrap();
+        let ids: Vec<usize> = view.items_with_ids().into_iter().map(|(idx, _)| idx).collect();
+        assert_eq!(ids, vec![7, 9]);
+    }
+
+    #[test]
+    fn test_builder_indexed_view() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let data = products.into_filtered();
+        let parent = data.parent_data().unwrap();
+        let games: Vec<usize> = (0..1_000).filter(|id| id % 2 == 1).collect();
+
+        let view = FilterData::builder()
+            .with_indices(&parent, games)
+            .with_field_index("price", |p: &Product| p.price)
+            .with_config(DatasetConfig { null_mode: NullMode::ThreeValued, history_compaction: Some(4), ..Default::default() })
+            .build()
+            .unwrap();
+        assert_eq!(view.len(), 500);
+        assert_eq!(view.null_mode(), NullMode::ThreeValued);
+        // Индекс построен по строкам view: id - индексы parent_data
+        view.filter_by_field_ops("price", &[(FieldOperation::eq(11u64), Op::And)]).unwrap();
+        let ids: Vec<usize> = view.items_with_ids().into_iter().map(|(idx, _)| idx).collect();
+        assert_eq!(ids, vec![11, 111, 211, 311, 411, 511, 611, 711, 811, 911]);
+        assert!(view.items().iter().all(|p| p.category == "games"));
+        // Родитель не затронут
+        assert_eq!(data.len(), 1_000);
+        assert!(!data.has_index("price"));
+
+        // Без with_data / with_indices - ошибка, а не паника
+        let missing = FilterData::<Product>::builder()
+            .with_field_index("price", |p: &Product| p.price)
+            .build();
+        assert!(matches!(
+            missing,
+            Err(tree_man::errors::GLobalError::FilterData(tree_man::errors::FilterDataError::BuilderMissingData))
+        ));
+    }
+
+    #[test]
+    fn test_stable_keys() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = FilterData::builder()
+            .with_data(products.clone())
+            .with_key(|p: &Product| p.id)
+            .with_field_index("price", |p: &Product| p.price)
+            .build()
+            .unwrap();
+        assert!(filtered.has_key());
+        assert_eq!(filtered.key_of(7).unwrap(), Some(FieldValue::from(7u64)));
+        assert_eq!(filtered.row_id_of(&FieldValue::from(7u64)).unwrap(), Some(7));
+        filtered.filter_by_field_ops("price", &[(FieldOperation::lt(5u64), Op::And)]).unwrap();
+        let keys = filtered.current_keys().unwrap();
+        assert_eq!(keys.len(), 50);
+
+        // Перезагрузка: строки в другом порядке, часть удалена
+        let reloaded_products: Vec<Product> = products.into_iter().rev().filter(|p| p.id != 100).collect();
+        let reloaded = reloaded_products.into_filtered();
+        assert!(reloaded.current_keys().is_err());
+        reloaded.set_key(|p: &Product| p.id).unwrap();
+        reloaded.filter_by_keys(&keys).unwrap();
+        assert_eq!(reloaded.len(), 49);
+        assert!(reloaded.items().iter().all(|p| p.price < 5 && p.id != 100));
+        assert!(matches!(reloaded.level_meta(1).unwrap().operation, Some(QueryStep::Keys(_))));
+        let (key, item) = &reloaded.items_with_keys().unwrap()[0];
+        assert_eq!(*key, FieldValue::from(item.id));
+
+        let duplicated = vec![1u64, 2, 2].into_filtered();
+        assert!(duplicated.set_key(|value| *value).is_err());
+        assert!(!duplicated.has_key());
+    }
+
+    #[test]
+    fn test_unique_index_lookup() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_unique_index("id", |p: &Product| p.id).unwrap();
+        assert!(filtered.has_unique_index("id"));
+        // Поиск не зависит от текущей выборки
+        filtered.filter(|p| p.category == "books").unwrap();
+        assert_eq!(filtered.get_by_key("id", 7u64).unwrap().unwrap().id, 7);
+        assert!(filtered.get_by_key("id", 5_000u64).unwrap().is_none());
+        assert!(filtered.get_by_key("missing", 7u64).is_err());
+
+        let Err(error) = filtered.create_unique_index("price", |p: &Product| p.price) else {
+            panic!("duplicate prices must be rejected");
+        };
+        let error = error.to_string();
+        assert!(error.contains("unique index 'price'"));
+        assert!(error.contains("0,1,2"));
+        assert!(!filtered.has_unique_index("price"));
+
+        let view = filtered.items().iter().map(|p| (**p).clone()).collect::<Vec<_>>().into_filtered();
+        view.create_unique_index("id", |p: &Product| p.id).unwrap();
+        assert!(view.get_by_key("id", 7u64).unwrap().is_none());
+        assert_eq!(view.get_by_key("id", 8u64).unwrap().unwrap().category, "books");
+        assert!(view.drop_unique_index("id"));
+        assert!(view.get_by_key("id", 8u64).is_err());
+    }
+
+    #[test]
+    fn test_duplicates_report() {
+        let emails = vec!["a@x.io", "b@x.io", "a@x.io", "c@x.io", "b@x.io", "a@x.io"];
+        let filtered = emails.into_filtered();
+        filtered.create_field_index("email", |email: &&str| email.to_string()).unwrap();
+        let duplicates = filtered.duplicates("email").unwrap();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].0, FieldValue::from("a@x.io"));
+        assert_eq!(duplicates[0].1.iter().collect::<Vec<_>>(), vec![0, 2, 5]);
+        assert_eq!(duplicates[1].1.iter().collect::<Vec<_>>(), vec![1, 4]);
+
+        let frequent = filtered.duplicates_above("email", 2).unwrap();
+        assert_eq!(frequent.len(), 1);
+        assert_eq!(frequent[0].0, FieldValue::from("a@x.io"));
+        assert!(filtered.duplicates("missing").is_err());
+    }
+
+    #[test]
+    fn test_pin_level_blocks_navigation() {
+        let data = Arc::new((0..10_000u64).collect::<Vec<_>>().into_filtered());
+        data.filter(|value| value % 2 == 0).unwrap();
+        let pinned = data.pin_level();
+        assert_eq!(pinned.level(), 1);
+        let total = pinned.len();
+
+        let resetter = {
+            let data = Arc::clone(&data);
+            std::thread::spawn(move || {
+                data.reset_to_source();
+                data.len()
+            })
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        // reset_to_source ждет drop guard-а
+        assert!(!resetter.is_finished());
+        assert_eq!(pinned.items().len(), total);
+        assert_eq!(data.current_level(), 1);
+        // Повторный pin в том же потоке не блокируется
+        assert_eq!(data.pin_level().len(), total);
+        drop(pinned);
+        assert_eq!(resetter.join().unwrap(), 10_000);
+        assert_eq!(data.current_level(), 0);
+    }
+
+    #[test]
+    fn test_concurrency_limit_policies() {
+        use std::{sync::atomic::AtomicBool, time::Duration};
+        use tree_man::QueuePolicy;
+
+        let data = Arc::new((0..20_000u64).collect::<Vec<_>>().into_filtered());
+        assert!(data.concurrency_stats().is_none());
+        data.set_concurrency_limit(1, QueuePolicy::Reject);
+
+        // Тяжелый фильтр держит единственный слот, пока не снят флаг
+        let release = Arc::new(AtomicBool::new(false));
+        let holder = {
+            let data = Arc::clone(&data);
+            let release = Arc::clone(&release);
+            thread::spawn(move || {
+                data.filter(|value| {
+                    while !release.load(Ordering::Acquire) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    value % 2 == 0
+                }).map(|data| data.len())
+            })
+        };
+        while data.concurrency_stats().unwrap().active == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let Err(error) = data.filter(|value| *value > 10) else { panic!("filter must be rejected") };
+        assert_eq!(error.to_string(), "too many heavy operations: all 1 permits are busy, 0 queued");
+        assert!(data.create_field_index("mod", |value: &u64| value % 3).is_err());
+
+        data.set_concurrency_limit(1, QueuePolicy::Timeout(Duration::from_millis(20)));
+        let limiter_holder = {
+            let data = Arc::clone(&data);
+            let release = Arc::clone(&release);
+            thread::spawn(move || {
+                data.create_field_index("slow", move |value: &u64| {
+                    while !release.load(Ordering::Acquire) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    value % 5
+                }).map(|_| ())
+            })
+        };
+        while data.concurrency_stats().unwrap().active == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(matches!(
+            data.create_field_index("mod", |value: &u64| value % 3),
+            Err(tree_man::errors::GLobalError::FilterData(tree_man::errors::FilterDataError::QueueTimeout { .. }))
+        ));
+        let stats = data.concurrency_stats().unwrap();
+        assert_eq!((stats.active, stats.queued, stats.timed_out), (1, 0, 1));
+
+        release.store(true, Ordering::Release);
+        assert_eq!(holder.join().unwrap().unwrap(), 10_000);
+        limiter_holder.join().unwrap().unwrap();
+
+        // Свободный слот выдается сразу; легкие фильтры (меньше порога) не ограничиваются
+        data.set_concurrency_limit(1, QueuePolicy::Wait);
+        data.filter(|value| value % 4 == 0).unwrap();
+        data.filter(|value| value % 8 == 0).unwrap();
+        assert_eq!(data.len(), 2_500);
+        assert_eq!(data.concurrency_stats().unwrap().acquired, 1);
+        data.clear_concurrency_limit();
+        assert!(data.concurrency_stats().is_none());
+    }
+
+    #[test]
+    fn test_deterministic_result_order() {
+        let source = (0..20_000u64).collect::<Vec<_>>().into_filtered();
+        let parent = source.parent_data().unwrap();
+        // Представление с обратным порядком строк
+        let reversed: Vec<usize> = (0..20_000).rev().collect();
+        let by_predicate = |view: &FilterData<u64>| {
+            view.filter(|value| value % 7 == 0).unwrap();
+            view.current_indices()
+        };
+        let by_index = |view: &FilterData<u64>| {
+            view.create_field_index("mod", |value: &u64| value % 7).unwrap();
+            view.filter_by_field_ops("mod", &[(FieldOperation::eq(0u64), Op::And)]).unwrap();
+            view.current_indices()
+        };
+
+        let view = FilterData::from_indices(&parent, reversed.clone());
+        let unordered = by_predicate(&view);
+        assert!(!unordered.is_sorted());
+
+        let predicate_view = FilterData::from_indices(&parent, reversed.clone());
+        predicate_view.set_deterministic_order(true);
+        let predicate_order = by_predicate(&predicate_view);
+        let index_view = FilterData::from_indices(&parent, reversed.clone());
+        index_view.set_deterministic_order(true);
+        let index_order = by_index(&index_view);
+        assert!(predicate_order.is_sorted());
+        assert_eq!(predicate_order, index_order);
+
+        let text_view = FilterData::from_indices(&parent, reversed);
+        text_view.set_deterministic_order(true);
+        text_view.filter(|value| value % 2 == 0).unwrap();
+        text_view.create_text_index("digits", |value: &u64| value.to_string()).unwrap();
+        text_view.search_with_text("digits", "777").unwrap();
+        assert!(text_view.current_indices().is_sorted());
+
+        let built = FilterData::builder()
+            .with_data(vec![3u64, 1, 2])
+            .with_deterministic_order()
+            .build()
+            .unwrap();
+        assert!(built.deterministic_order());
+    }
+
+    #[test]
+    fn test_prepared_field_query() {
+        struct PriceRange {
+            min: u64,
+            max: u64,
+        }
+        let products: Vec<Product> = (0..20_000u64)
+            .map(|id| Product { id, price: id % 1_000, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        assert!(filtered.prepare_field_query("price", |_: &PriceRange| Vec::new()).is_err());
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+
+        let mut by_price = filtered.prepare_field_query("price", |range: &PriceRange| {
+            vec![(FieldOperation::range(range.min, range.max), Op::And)]
+        }).unwrap();
+        assert_eq!(by_price.plan(), None);
+        for (min, max) in [(10u64, 19u64), (500, 549), (0, 0)] {
+            by_price.execute(&PriceRange { min, max }).unwrap();
+            assert_eq!(filtered.len() as u64, (max - min + 1) * 20);
+            assert!(filtered.items().iter().all(|p| p.price >= min && p.price <= max));
+            filtered.reset_to_source();
+        }
+        assert_eq!(by_price.plan(), Some(QueryHint::ForceIndex));
+        assert!(by_price.execute(&PriceRange { min: 5_000, max: 6_000 }).is_err());
+        by_price.replan();
+        assert_eq!(by_price.plan(), None);
+
+        let empty = filtered.prepare_field_query("price", |_: &PriceRange| Vec::new()).unwrap();
+        assert!(empty.execute(&PriceRange { min: 0, max: 1 }).is_err());
+    }
+
+    #[test]
+    fn test_index_report_recommendations() {
+        use tree_man::model::IndexRecommendation;
+        let products: Vec<Product> = (0..10_000u64)
+            .map(|id| Product { id, price: id % 1_000, category: if id % 10 == 0 { "books" } else { "games" }.to_string(), in_stock: true })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("id", |p: &Product| p.id).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        filtered.create_text_index("name", |p: &Product| p.category.clone()).unwrap();
+
+        let id = filtered.index_report("id").unwrap();
+        assert_eq!(id.unique_values, 10_000);
+        assert!(id.recommends(&IndexRecommendation::UniqueIndex));
+        assert!(id.recommends(&IndexRecommendation::BucketedIndex));
+        assert!(id.reasons[0].starts_with("high cardinality"));
+
+        let category = filtered.index_report("category").unwrap();
+        assert!(category.is_skewed);
+        assert_eq!(category.dominant_value, Some(FieldValue::from("games")));
+        assert!((category.dominant_value_ratio - 0.9).abs() < 1e-9);
+        assert!(!category.recommends(&IndexRecommendation::PredicateForInverse));
+        assert!(category.recommends(&IndexRecommendation::PredicateForRange));
+        assert!(category.to_string().contains("skewed: dominant value games covers 90.0% of rows"));
+
+        let in_stock = filtered.index_report("in_stock").unwrap();
+        assert!(in_stock.recommends(&IndexRecommendation::DropIndex));
+        assert!(filtered.index_report("name").is_err());
+        assert!(filtered.index_report("missing").is_err());
+    }
+
+    #[test]
+    fn test_bitmap_levels() {
+        let data = (0..200_000u64).collect::<Vec<_>>().into_filtered();
+        data.create_field_index("mod", |value: &u64| value % 10).unwrap();
+        data.set_bitmap_levels(true);
+        data.filter_by_field_ops("mod", &[(FieldOperation::lt(5u64), Op::And)]).unwrap();
+        data.filter(|value| value % 2 == 0).unwrap();
+        data.filter(|value| value % 3 == 0).unwrap();
+        // Исходный и текущий уровни - векторы, промежуточные - bitmap-ы
+        assert_eq!(data.bitmap_levels_count(), 2);
+        let deepest = data.items();
+
+        data.go_to_level(1);
+        assert_eq!(data.len(), 100_000);
+        assert_eq!(data.items().len(), 100_000);
+        assert!(data.items().iter().all(|value| **value % 10 < 5));
+        data.filter(|value| value % 2 == 0).unwrap();
+        data.filter(|value| value % 3 == 0).unwrap();
+        assert_eq!(data.items(), deepest);
+
+        data.set_bitmap_levels(false);
+        assert_eq!(data.bitmap_levels_count(), 0);
+        data.up();
+        assert_eq!(data.len(), 60_000);
+        data.set_bitmap_levels(true);
+        assert_eq!(data.bitmap_levels_count(), 1);
+        data.reset_to_source();
+        assert_eq!(data.bitmap_levels_count(), 0);
+        assert_eq!(data.items().len(), 200_000);
+    }
+
+    #[test]
+    fn test_inline_storage() {
+        let data = tree_man::InlineFilterData::from_vec_inline((0..100_000u64).collect());
+        data.create_field_index("rem", |v: &u64| *v % 10).unwrap();
+
+        data.filter_by_field_ops("rem", &[(FieldOperation::eq(3u64), Op::And)]).unwrap();
+        assert_eq!(data.len(), 10_000);
+        data.filter(|v| *v >= 50_000).unwrap();
+        assert_eq!(data.len(), 5_000);
+        assert_eq!(data.current_level(), 2);
+        assert_eq!(data.indices()[0], 50_003);
+        assert!(data.items().iter().all(|v| **v % 10 == 3 && **v >= 50_000));
+        assert_eq!(data.get(50_013), Some(&50_013));
+        assert_eq!(data.get(50_014), None);
+
+        data.up();
+        assert_eq!(data.len(), 10_000);
+        data.reset_to_source();
+        assert_eq!(data.len(), 100_000);
+        assert_eq!(data.stored_levels_count(), 1);
+        assert!(data.filter_by_field_ops("missing", &[(FieldOperation::eq(1u64), Op::And)]).is_err());
+
+        let slice = [5u8, 1, 5, 2];
+        let small = tree_man::InlineFilterData::from_slice(&slice);
+        small.filter(|v| *v == 5).unwrap();
+        assert_eq!(small.indices(), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_builder_combined_index_build() {
+        let products: Vec<Product> = (0..150_000u64)
+            .map(|id| Product { id, price: id % 1_000, category: ["books", "games", "music"][(id % 3) as usize].to_string(), in_stock: id % 7 == 0 })
+            .collect();
+        let combined = FilterData::builder()
+            .with_data(products.clone())
+            .with_field_index("price", |p: &Product| p.price)
+            .with_field_index("category", |p: &Product| p.category.clone())
+            .with_text_index("title", |p: &Product| p.category.clone())
+            .with_field_index("in_stock", |p: &Product| p.in_stock)
+            .build()
+            .unwrap();
+        assert_eq!(combined.list_indexes().len(), 4);
+        // Fingerprint-ы пакетного построения совпадают с обычными
+        assert!(combined.stale_indexes().is_empty());
+
+        let sequential = products.into_filtered();
+        sequential.create_field_index("price", |p: &Product| p.price).unwrap();
+        sequential.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        sequential.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        for data in [&combined, &sequential] {
+            data.filter_by_field_ops("price", &[(FieldOperation::lt(10u64), Op::And)]).unwrap();
+            data.filter_by_field_ops("category", &[(FieldOperation::eq("games"), Op::And)]).unwrap();
+            data.filter_by_field_ops("in_stock", &[(FieldOperation::eq(true), Op::And)]).unwrap();
+        }
+        let ids = |data: &FilterData<Product>| data.items().iter().map(|p| p.id).collect::<Vec<_>>();
+        assert_eq!(ids(&combined), ids(&sequential));
+        assert!(!ids(&combined).is_empty());
+    }
+
+    #[test]
+    fn test_index_deduplication() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = FilterData::builder()
+            .with_data(products)
+            .with_field_index("price", |p: &Product| p.price)
+            .with_field_index("price_copy", |p: &Product| p.price)
+            .with_field_index("category", |p: &Product| p.category.clone())
+            .build()
+            .unwrap();
+        filtered.create_text_index("category_text", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_text_index("category_search", |p: &Product| p.category.clone()).unwrap();
+        // Nullable индекс с тем же extractor-ом по умолчанию отличается nulls-ами
+        filtered.create_nullable_field_index("price_nullable", |p: &Product| (p.price > 0).then_some(p.price)).unwrap();
+        filtered.create_field_index("price_default", |p: &Product| p.price).unwrap();
+
+        assert_eq!(filtered.shared_indexes(), vec![
+            vec!["category_search".to_string(), "category_text".to_string()],
+            vec!["price".to_string(), "price_copy".to_string(), "price_default".to_string()],
+        ]);
+        assert!(Arc::ptr_eq(&filtered.get_index("price").unwrap(), &filtered.get_index("price_copy").unwrap()));
+        filtered.filter_by_field_ops("price_copy", &[(FieldOperation::eq(5u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 10);
+
+        // Замена одного из индексов не трогает остальные
+        filtered.reset_to_source();
+        filtered.create_field_index("price_copy", |p: &Product| p.price + 1).unwrap();
+        assert_eq!(filtered.shared_indexes().len(), 2);
+        filtered.filter_by_field_ops("price", &[(FieldOperation::eq(0u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 10);
+    }
+
+    #[test]
+    fn test_query_reuses_pooled_bitmaps() {
+        let products: Vec<Product> = (0..10_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        let expr = QueryExpr::and(vec![
+            QueryExpr::field("category", vec![(FieldOperation::eq("games"), Op::And)]),
+            QueryExpr::or(vec![
+                QueryExpr::field("price", vec![(FieldOperation::lt(10u64), Op::And), (FieldOperation::eq(3u64), Op::AndNot)]),
+                QueryExpr::field("price", vec![(FieldOperation::gte(90u64), Op::And)]),
+            ]),
+        ]);
+        filtered.filter_query(&expr).unwrap();
+        let expected = filtered.len();
+        let before = tree_man::bitmap_pool_stats();
+        filtered.reset_to_source();
+        filtered.filter_query(&expr).unwrap();
+        assert_eq!(filtered.len(), expected);
+        assert!(filtered.items().iter().all(|p| p.category == "games" && ((p.price < 10 && p.price != 3) || p.price >= 90)));
+        assert!(tree_man::bitmap_pool_stats().reused > before.reused);
+    }
+
+    #[test]
+    fn test_predicate_scan_checks_selective_field_first() {
+        let products: Vec<Product> = (0..10_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        let category_calls = Arc::new(AtomicUsize::new(0));
+        let calls = Arc::clone(&category_calls);
+        filtered.create_field_index("category", move |p: &Product| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            p.category.clone()
+        }).unwrap();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        category_calls.store(0, Ordering::Relaxed);
+
+        // Строковое неселективное поле передано первым - проверяется последним
+        filtered.filter_by_fields_ops_with_hint(&[
+            ("category", &[(FieldOperation::eq("games"), Op::And)][..]),
+            ("price", &[(FieldOperation::eq(5u64), Op::And)][..]),
+        ], QueryHint::ForceScan).unwrap();
+        assert_eq!(filtered.len(), 100);
+        assert!(filtered.items().iter().all(|p| p.price == 5 && p.category == "games"));
+        assert!(category_calls.load(Ordering::Relaxed) < 1_000);
+    }
+
+    #[test]
+    fn test_apply_steps_atomic() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_text_index("category", |p: &Product| p.category.clone()).unwrap();
+
+        filtered.apply_steps(&[
+            Step::field("price", vec![(FieldOperation::lt(10u64), Op::And)]),
+            Step::text("category", "game"),
+            Step::predicate("in stock", |p: &Product| p.in_stock),
+        ]).unwrap();
+        assert_eq!(filtered.current_level(), 1);
+        assert!(filtered.items().iter().all(|p| p.price < 10 && p.category == "games" && p.in_stock));
+        assert_eq!(filtered.len(), 17);
+        let meta = filtered.level_meta(1).unwrap();
+        assert!(matches!(meta.operation, Some(QueryStep::Steps(ref steps)) if steps.len() == 3));
+        assert!(meta.description.ends_with("AND in stock"));
+
+        // Ошибка на любом шаге - уровень не добавляется
+        let len = filtered.len();
+        assert!(filtered.apply_steps(&[
+            Step::field("price", vec![(FieldOperation::eq(1u64), Op::And)]),
+            Step::predicate("none", |_: &Product| false),
+        ]).is_err());
+        assert!(filtered.apply_steps(&[Step::field("missing", vec![(FieldOperation::eq(1u64), Op::And)])]).is_err());
+        assert!(filtered.apply_steps(&[]).is_err());
+        assert_eq!(filtered.current_level(), 1);
+        assert_eq!(filtered.len(), len);
+    }
+
+    #[test]
+    fn test_compare_cohorts() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        let games = QueryExpr::field("category", vec![(FieldOperation::eq("games"), Op::And)]);
+        let in_stock = QueryExpr::field("in_stock", vec![(FieldOperation::eq(true), Op::And)]);
+
+        let diff = filtered.compare_queries(&games, &in_stock).unwrap();
+        // games: нечетные id (500), in_stock: id % 3 == 0 (334), обе - id % 6 == 3 (167)
+        assert_eq!((diff.only_a, diff.only_b, diff.both), (333, 167, 167));
+        assert_eq!(diff.total_a(), 500);
+        assert_eq!(diff.union(), 667);
+        assert!(diff.bitmaps.is_none());
+        assert_eq!(filtered.current_level(), 0);
+
+        let diff = filtered.compare_queries_with_bitmaps(&games, &in_stock).unwrap();
+        let bitmaps = diff.bitmaps.unwrap();
+        assert!(bitmaps.both.iter().all(|id| id % 6 == 3));
+        assert_eq!(bitmaps.only_b.len(), 167);
+
+        // Сравнение в пределах текущей выборки и через сегменты
+        filtered.filter(|p| p.id < 100).unwrap();
+        filtered.save_segment("games", games).unwrap();
+        filtered.save_segment("in_stock", in_stock).unwrap();
+        let diff = filtered.compare_segments("games", "in_stock").unwrap();
+        assert_eq!(diff.total_a(), 50);
+        assert!(filtered.compare_segments("games", "missing").is_err());
+    }
+
+    #[test]
+    fn test_query_result_set() {
+        let products: Vec<Product> = (0..1_000u64)
+            .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        let games = filtered.query_result(&QueryExpr::field("category", vec![(FieldOperation::eq("games"), Op::And)])).unwrap();
+        let in_stock = filtered.query_result(&QueryExpr::field("in_stock", vec![(FieldOperation::eq(true), Op::And)])).unwrap();
+        assert_eq!(games.len(), 500);
+        assert_eq!(filtered.current_level(), 0);
+        assert!(games.iter().all(|p| p.id % 2 == 1));
+
+        // Страницы идут в порядке хранилища
+        let page = games.page(10, 5);
+        assert_eq!(page.iter().map(|p| p.id).collect::<Vec<_>>(), vec![21, 23, 25, 27, 29]);
+        assert!(games.page(500, 5).is_empty());
+        assert_eq!(games.pages_count(30), 17);
+
+        let stats = games.aggregate(|p: &Product| p.price);
+        assert_eq!(stats.count, 500);
+        assert_eq!((stats.min, stats.max), (Some(1.0), Some(99.0)));
+        assert_eq!(stats.avg(), Some(50.0));
+        // Нечисловые значения не учитываются
+        assert_eq!(games.aggregate(|p: &Product| p.category.clone()).count, 0);
+
+        let both = games.intersect(&in_stock);
+        assert_eq!(both.len(), 167);
+        assert_eq!(games.union(&in_stock).len(), 667);
+        assert!(both.iter_with_ids().all(|(id, p)| id as u64 == p.id && id % 6 == 3));
+
+        // Результат не зависит от последующего drill-down
+        filtered.filter(|p| p.id < 100).unwrap();
+        assert_eq!(games.len(), 500);
+        assert_eq!(filtered.result_set().unwrap().len(), 100);
+
+        let view = both.to_filter_data();
+        assert_eq!(view.len(), 167);
+        view.filter(|p| p.price < 10).unwrap();
+        assert!(view.items().iter().all(|p| p.price < 10 && p.id % 6 == 3));
+    }
+
+    #[test]
+    fn test_federated_query() {
+        let day = |from: u64, to: u64| {
+            let products: Vec<Product> = (from..to)
+                .map(|id| Product { id, price: id % 100, category: ["books", "games"][(id % 2) as usize].to_string(), in_stock: id % 3 == 0 })
+                .collect();
+            let data = Arc::new(products.into_filtered());
+            data.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+            data
+        };
+        let federation = Federation::named(vec![("mon", day(0, 100)), ("tue", day(100, 300)), ("wed", day(300, 310))]);
+        assert_eq!(federation.names(), vec!["mon", "tue", "wed"]);
+
+        let games = federation.filter_by_field_ops("category", &[(FieldOperation::eq("games"), Op::And)]).unwrap();
+        assert_eq!(games.len(), 155);
+        assert_eq!(games.breakdown(), vec![("mon", 50), ("tue", 100), ("wed", 5)]);
+        assert_eq!(games.get("tue").unwrap().len(), 100);
+
+        // Страница на стыке наборов
+        let page = games.page_with_names(148, 4);
+        assert_eq!(page.iter().map(|(name, p)| (*name, p.id)).collect::<Vec<_>>(), vec![("tue", 297), ("tue", 299), ("wed", 301), ("wed", 303)]);
+        assert_eq!(games.page(154, 10).len(), 1);
+        assert!(games.page(155, 10).is_empty());
+
+        let stats = games.aggregate(|p: &Product| p.id);
+        assert_eq!((stats.count, stats.min, stats.max), (155, Some(1.0), Some(309.0)));
+        assert_eq!(games.aggregate_breakdown(|p: &Product| p.id)[2].1.count, 5);
+
+        // Ошибка указывает набор
+        let missing = Federation::named(vec![("mon", day(0, 10)), ("raw", Arc::new(Vec::<Product>::new().into_filtered()))]);
+        let error = missing.filter_by_field_ops("category", &[(FieldOperation::eq("games"), Op::And)]).err().unwrap();
+        assert!(error.to_string().contains("'raw'"));
+    }
+
+    #[test]
+    fn test_string_match_operations() {
+        let names = ["alpha", "alphabet", "alps", "beta", "gamma", "delta", "alpha-beta"];
+        let products: Vec<Product> = (0..700u64)
+            .map(|id| Product { id, price: id % 100, category: names[(id % 7) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        let index = filtered.get_index("category").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        let count = |operation: FieldOperation| field_index.filter_operation(&operation).unwrap().len();
+        assert_eq!(count(FieldOperation::starts_with("alp")), 400);
+        assert_eq!(count(FieldOperation::starts_with("alpha")), 300);
+        assert_eq!(count(FieldOperation::starts_with("zeta")), 0);
+        assert_eq!(count(FieldOperation::ends_with("ta")), 300);
+        assert_eq!(count(FieldOperation::contains_substr("ph")), 300);
+        assert_eq!(count(FieldOperation::contains_substr("")), 700);
+
+        // Индекс и predicate path дают одно и то же
+        filtered.filter_by_field_ops("category", &[
+            (FieldOperation::starts_with("alpha"), Op::And),
+            (FieldOperation::ends_with("beta"), Op::AndNot),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 200);
+        assert!(filtered.items().iter().all(|p| p.category == "alpha" || p.category == "alphabet"));
+        assert!(FieldOperation::contains_substr("lph").evaluate(&FieldValue::from("alpha")));
+        assert!(!FieldOperation::starts_with("1").evaluate(&FieldValue::from(10u64)));
+
+        // Строковые операции не применимы к другим типам
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        assert!(filtered.filter_by_field_ops("price", &[(FieldOperation::starts_with("1"), Op::And)]).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_operation() {
+        let names = ["order-1", "order-22", "refund-3", "order-x", "ORDER-5"];
+        let products: Vec<Product> = (0..500u64)
+            .map(|id| Product { id, price: id % 100, category: names[(id % 5) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        let index = filtered.get_index("category").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        let count = |pattern: &str| field_index.filter_operation(&FieldOperation::regex(pattern)).map(|bitmap| bitmap.len());
+        assert_eq!(count(r"^order-\d+$").unwrap(), 200);
+        assert_eq!(count(r"(?i)^order").unwrap(), 400);
+        assert_eq!(count(r"^none$").unwrap(), 0);
+        assert!(count(r"order-(").is_err());
+
+        filtered.filter_by_field_ops("category", &[(FieldOperation::regex(r"-\d$"), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 300);
+        assert!(FieldOperation::regex(r"^a.c$").evaluate(&FieldValue::from("abc")));
+        assert!(!FieldOperation::regex(r"1").evaluate(&FieldValue::from(1u64)));
+    }
+
+    #[test]
+    fn test_normalized_string_index() {
+        let levels = ["error", "ERROR", " Error ", "warn", "Warning", "info"];
+        let products: Vec<Product> = (0..600u64)
+            .map(|id| Product { id, price: id % 100, category: levels[(id % 6) as usize].to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index_with_options("level", |p: &Product| p.category.clone(), StringIndexOptions::case_insensitive()).unwrap();
+        assert_eq!(filtered.string_index_options("level"), Some(StringIndexOptions::case_insensitive()));
+
+        let level = |value: &str| vec![(FieldOperation::eq(value), Op::And)];
+        assert_eq!(filtered.count_by_field_ops("level", &level("ERROR")).unwrap(), 300);
+        assert_eq!(filtered.count_by_field_ops("level", &level("  error")).unwrap(), 300);
+        assert_eq!(filtered.count_by_field_ops("level", &[(FieldOperation::starts_with("WARN"), Op::And)]).unwrap(), 200);
+        assert_eq!(filtered.query_ids(&QueryExpr::field("level", vec![(FieldOperation::in_values(vec!["Info", "WARN"]), Op::And)])).unwrap().len(), 200);
+
+        filtered.filter_by_field_ops("level", &level("Error")).unwrap();
+        assert_eq!(filtered.len(), 300);
+        assert!(filtered.items().iter().all(|p| p.category.trim().eq_ignore_ascii_case("error")));
+
+        // Обычный индекс с тем же именем снимает нормализацию
+        filtered.reset_to_source();
+        filtered.create_field_index("level", |p: &Product| p.category.clone()).unwrap();
+        assert_eq!(filtered.string_index_options("level"), None);
+        assert_eq!(filtered.count_by_field_ops("level", &level("ERROR")).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_hash_index_kind() {
+        let products: Vec<Product> = (0..20_000u64)
+            .map(|id| Product { id, price: id % 100, category: format!("sku_{}", id % 4_000), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index_with_kind("sku", |p: &Product| p.category.clone(), IndexKind::Hash).unwrap();
+        filtered.create_field_index_with_kind("price", |p: &Product| p.price, IndexKind::Hash).unwrap();
+        let index = filtered.get_index("sku").unwrap();
+        assert_eq!(index.as_field().unwrap().0.kind(), IndexKind::Hash);
+
+        assert_eq!(filtered.count_by_field_ops("sku", &[(FieldOperation::eq("sku_7"), Op::And)]).unwrap(), 5);
+        assert_eq!(filtered.count_by_field_ops("sku", &[(FieldOperation::in_values(vec!["sku_7", "sku_8"]), Op::And)]).unwrap(), 10);
+
+        // Range по hash индексу идет predicate path
+        filtered.filter_by_field_ops("price", &[(FieldOperation::lt(10u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 2_000);
+        filtered.filter_by_field_ops("sku", &[(FieldOperation::eq("sku_3"), Op::And)]).unwrap();
+        assert_eq!(filtered.items().iter().map(|p| p.id).collect::<Vec<_>>(), vec![3, 4_003, 8_003, 12_003, 16_003]);
+    }
+
+    #[test]
+    fn test_push_extend_updates_indexes() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id % 10, category: format!("cat_{}", id % 5), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index_with_kind("category", |p: &Product| p.category.clone(), IndexKind::Hash).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        filtered.create_text_index("title", |p: &Product| p.category.clone()).unwrap();
+        let version = filtered.index_version("price").unwrap();
+
+        let row = filtered.push(Product { id: 100, price: 500, category: "fresh".to_string(), in_stock: true }).unwrap();
+        assert_eq!(row, 100);
+        let rows = filtered
+            .extend((101..105u64).map(|id| Product { id, price: 3, category: "cat_1".to_string(), in_stock: false }))
+            .unwrap();
+        assert_eq!(rows, 101..105);
+        assert_eq!(filtered.len(), 105);
+        assert!(filtered.index_version("price").unwrap() > version);
+        assert!(filtered.validate_indexes());
+        assert!(filtered.stale_indexes().is_empty());
+
+        assert_eq!(filtered.count_by_field_ops("price", &[(FieldOperation::eq(3u64), Op::And)]).unwrap(), 14);
+        assert_eq!(filtered.count_by_field_ops("price", &[(FieldOperation::gt(100u64), Op::And)]).unwrap(), 1);
+        assert_eq!(filtered.count_by_field_ops("category", &[(FieldOperation::eq("cat_1"), Op::And)]).unwrap(), 24);
+        assert_eq!(filtered.count_by_field_ops("in_stock", &[(FieldOperation::eq(false), Op::And)]).unwrap(), 54);
+        filtered.search_with_text("title", "fresh").unwrap();
+        assert_eq!(filtered.items().iter().map(|p| p.id).collect::<Vec<_>>(), vec![100]);
+
+        // Drill-down уровень не меняется, исходный уровень видит новые строки
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("price", &[(FieldOperation::eq(500u64), Op::And)]).unwrap();
+        filtered.push(Product { id: 105, price: 500, category: "fresh".to_string(), in_stock: true }).unwrap();
+        assert_eq!(filtered.len(), 1);
+        filtered.reset_to_source();
+        assert_eq!(filtered.len(), 106);
+        assert_eq!(filtered.count_by_field_ops("price", &[(FieldOperation::eq(500u64), Op::And)]).unwrap(), 2);
+
+        let parent = filtered.parent_data().unwrap();
+        let view = FilterData::from_indices(&parent, vec![0, 1]);
+        assert!(view.push(Product { id: 0, price: 0, category: String::new(), in_stock: true }).is_err());
+    }
+
+    #[test]
+    fn test_push_appends_indexes_in_place() {
+        // Данные чуть меньше чанка fingerprint-а (64K строк): push пересекает границу чанка
+        let rows: Vec<(u64, u64)> = (0..65_530u64).map(|id| (id, id % 100)).collect();
+        let filtered = rows.into_filtered();
+        filtered.create_field_index("bucket", |row: &(u64, u64)| row.1).unwrap();
+        let before = Arc::as_ptr(&filtered.get_index("bucket").unwrap());
+
+        for id in 65_530..65_560u64 {
+            filtered.push((id, id % 100)).unwrap();
+        }
+        // Индекс никто не держал - дописан на месте, без копии
+        assert_eq!(Arc::as_ptr(&filtered.get_index("bucket").unwrap()), before);
+        assert!(filtered.validate_indexes());
+        assert_eq!(filtered.count_by_field_ops("bucket", &[(FieldOperation::gte(95u64), Op::And)]).unwrap(), 3_275);
+
+        // Индекс читают - push дописывает копию, читатель видит прежнюю версию
+        let held = filtered.get_index("bucket").unwrap();
+        filtered.push((65_560, 7)).unwrap();
+        assert!(!Arc::ptr_eq(&held, &filtered.get_index("bucket").unwrap()));
+        assert!(filtered.validate_indexes());
+    }
+
+    #[test]
+    fn test_push_extends_keys_and_views() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id % 10, category: format!("cat_{}", id % 5), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.set_key(|p: &Product| p.id).unwrap();
+        filtered.create_unique_index("id", |p: &Product| p.id).unwrap();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        let expr = QueryExpr::field("price", vec![(FieldOperation::eq(3u64), Op::And)]);
+        filtered.create_view("cheap", expr).unwrap();
+        assert_eq!(filtered.view_count("cheap").unwrap(), 10);
+
+        let rows = filtered
+            .extend((100..103u64).map(|id| Product { id, price: 3, category: "fresh".to_string(), in_stock: true }))
+            .unwrap();
+        assert_eq!(filtered.row_id_of(&FieldValue::from(101u64)).unwrap(), Some(rows.start + 1));
+        assert_eq!(filtered.key_of(rows.end - 1).unwrap(), Some(FieldValue::from(102u64)));
+        assert_eq!(filtered.get_by_key("id", 102u64).unwrap().unwrap().category, "fresh");
+        assert_eq!(filtered.view_count("cheap").unwrap(), 13);
+
+        // Повтор ключа - с существующей строкой или внутри push - отклоняется целиком
+        let Err(error) = filtered.push(Product { id: 7, price: 0, category: String::new(), in_stock: true }) else {
+            panic!("duplicate primary key must be rejected");
+        };
+        assert!(error.to_string().contains("duplicate primary key: 7"));
+        filtered.clear_key();
+        let duplicate = (200..202u64).chain([200]).map(|id| Product { id, price: 3, category: String::new(), in_stock: true });
+        let Err(error) = filtered.extend(duplicate) else {
+            panic!("duplicate unique key must be rejected");
+        };
+        assert!(error.to_string().contains("unique index 'id' has duplicate keys: 200"));
+        assert_eq!(filtered.len(), 103);
+        assert_eq!(filtered.view_count("cheap").unwrap(), 13);
+
+        // Удаленная строка освобождает ключ
+        filtered.retain(|p| p.id != 7).unwrap();
+        filtered.push(Product { id: 7, price: 3, category: "again".to_string(), in_stock: true }).unwrap();
+        assert_eq!(filtered.get_by_key("id", 7u64).unwrap().unwrap().category, "again");
+    }
+
+    #[test]
+    fn test_retain_and_remove_indices() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id % 10, category: format!("cat_{}", id % 5), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+        filtered.create_text_index("title", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_unique_index("id", |p: &Product| p.id).unwrap();
+
+        // Уровень drill-down теряет удаленные строки
+        filtered.filter_by_field_ops("price", &[(FieldOperation::eq(3u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 10);
+        assert_eq!(filtered.remove_indices(&[3, 13, 14, 1_000]).unwrap(), 3);
+        assert_eq!(filtered.remove_indices(&[3]).unwrap(), 0);
+        assert_eq!(filtered.len(), 8);
+        assert!(!filtered.items().iter().any(|p| p.id == 3 || p.id == 13));
+
+        assert_eq!(filtered.retain(|p: &Product| p.category != "cat_4").unwrap(), 19);
+        filtered.reset_to_source();
+        assert_eq!(filtered.len(), 78);
+        assert_eq!(filtered.count_by_field_ops("price", &[(FieldOperation::eq(3u64), Op::And)]).unwrap(), 8);
+        // Значение без строк удалено из индекса
+        assert!(filtered.count_by_field_ops("price", &[(FieldOperation::eq(4u64), Op::And)]).is_err());
+        assert!(filtered.get_by_key("id", 13u64).unwrap().is_none());
+        assert!(filtered.search_with_text("title", "cat_4").is_err());
+        assert!(filtered.stale_indexes().is_empty());
+
+        // Новый индекс строится без удаленных строк
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        assert!(filtered.count_by_field_ops("category", &[(FieldOperation::eq("cat_4"), Op::And)]).is_err());
+        filtered.filter_by_field_ops("in_stock", &[(FieldOperation::eq(true), Op::And)]).unwrap();
+        assert!(filtered.items().iter().all(|p| p.id % 5 != 4 && p.id != 14));
+
+        // push после удаления: строки дописываются после удаленных
+        filtered.reset_to_source();
+        assert_eq!(filtered.push(Product { id: 100, price: 4, category: "cat_4".to_string(), in_stock: true }).unwrap(), 100);
+        assert_eq!(filtered.len(), 79);
+        assert_eq!(filtered.count_by_field_ops("price", &[(FieldOperation::eq(4u64), Op::And)]).unwrap(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_and_load_indexes() {
+        let rows: Vec<(u64, String)> = (0..2_000u64)
+            .map(|id| (id % 50, format!("order {id} status {}", if id % 3 == 0 { "paid" } else { "new" })))
+            .collect();
+        let path = std::env::temp_dir().join(format!("tree_man_indexes_{}.bin", std::process::id()));
+
+        let cold = FilterData::from_vec(rows.clone());
+        cold.create_field_index("price", |row: &(u64, String)| row.0).unwrap();
+        cold.create_text_index("message", |row: &(u64, String)| row.1.clone()).unwrap();
+        assert_eq!(cold.save_indexes(&path).unwrap(), 2);
+
+        // Warm start: extractor вызывается только для fingerprint-а и выборочной сверки
+        let calls = Arc::new(AtomicUsize::new(0));
+        let warm = FilterData::from_vec(rows.clone());
+        assert_eq!(warm.load_indexes(&path).unwrap(), 2);
+        assert_eq!(warm.saved_index_names(), vec!["message".to_string(), "price".to_string()]);
+        let counter = Arc::clone(&calls);
+        warm.create_field_index("price", move |row: &(u64, String)| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            row.0
+        }).unwrap();
+        assert!(calls.load(Ordering::Relaxed) <= 2_000 + 64);
+        warm.create_text_index("message", |row: &(u64, String)| row.1.clone()).unwrap();
+        assert!(warm.saved_index_names().is_empty());
+
+        warm.filter_by_field_ops("price", &[(FieldOperation::eq(7u64), Op::And)]).unwrap();
+        assert_eq!(warm.len(), 40);
+        warm.search_with_text("message", "paid").unwrap();
+        assert_eq!(warm.len(), 13);
+
+        // Другие данные или другой IndexKind - индекс строится заново
+        let mut changed = rows.clone();
+        changed[0].0 = 49;
+        let other = FilterData::from_vec(changed);
+        other.load_indexes(&path).unwrap();
+        other.create_field_index("price", |row: &(u64, String)| row.0).unwrap();
+        other.create_field_index_with_kind("message", |row: &(u64, String)| row.1.clone(), IndexKind::Hash).unwrap();
+        other.filter_by_field_ops("price", &[(FieldOperation::eq(49u64), Op::And)]).unwrap();
+        assert_eq!(other.len(), 41);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(warm.load_indexes(&path).is_err());
+    }
+
+    #[test]
+    fn test_field_stats_percentiles_and_histogram() {
+        let data: Vec<(u64, String)> = (0..1_000u64).map(|id| (id, format!("user{}", id % 10))).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("id", |row: &(u64, String)| row.0).unwrap();
+        filtered.create_field_index("user", |row: &(u64, String)| row.1.clone()).unwrap();
+
+        let stats = filtered.field_stats("id").unwrap();
+        assert_eq!(stats.rows, 1_000);
+        assert_eq!(stats.unique_values, 1_000);
+        assert_eq!(stats.min, Some(FieldValue::U64(0)));
+        assert_eq!(stats.max, Some(FieldValue::U64(999)));
+        assert_eq!(stats.percentile(0.0), Some(&FieldValue::U64(0)));
+        assert_eq!(stats.median(), Some(&FieldValue::U64(499)));
+        assert_eq!(stats.percentile(0.95), Some(&FieldValue::U64(949)));
+        assert_eq!(stats.percentile(1.0), Some(&FieldValue::U64(999)));
+        assert_eq!(stats.histogram.len(), 32);
+        assert_eq!(stats.histogram.iter().map(|bucket| bucket.rows).sum::<u64>(), 1_000);
+        let fraction = stats.range_fraction(None, Some(250.0)).unwrap();
+        assert!((fraction - 0.25).abs() < 0.01);
+        // Кешируется до перестройки индекса
+        assert!(Arc::ptr_eq(&stats, &filtered.field_stats("id").unwrap()));
+
+        // Оценка селективности диапазона по гистограмме, а не по константе
+        let lt: &[(FieldOperation, Op)] = &[(FieldOperation::lt(100u64), Op::And)];
+        let selectivity = filtered.estimate_fields_selectivity(&[("id", lt)]).unwrap();
+        assert!((selectivity - 0.1).abs() < 0.01);
+
+        // Строки: min/max и перцентили есть, гистограммы нет
+        let stats = filtered.field_stats("user").unwrap();
+        assert_eq!(stats.min, Some(FieldValue::String("user0".to_string())));
+        assert_eq!(stats.max, Some(FieldValue::String("user9".to_string())));
+        assert!(stats.histogram.is_empty());
+        assert_eq!(stats.range_fraction(None, Some(1.0)), None);
+    }
+
+    #[test]
+    fn test_field_top_values() {
+        let brands = ["acme", "bolt", "acme", "core", "bolt", "acme", "dyne"];
+        let data: Vec<(String, bool)> = brands.iter().enumerate().map(|(n, brand)| (brand.to_string(), n % 3 == 0)).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("brand", |row: &(String, bool)| row.0.clone()).unwrap();
+        filtered.create_field_index("promo", |row: &(String, bool)| row.1).unwrap();
+
+        let top = filtered.field_top_values("brand", 3).unwrap();
+        assert_eq!(top, vec![
+            (FieldValue::String("acme".to_string()), 3),
+            (FieldValue::String("bolt".to_string()), 2),
+            (FieldValue::String("core".to_string()), 1),
+        ]);
+        assert_eq!(filtered.field_top_values("brand", 10).unwrap().len(), 4);
+        assert!(filtered.field_top_values("brand", 0).unwrap().is_empty());
+        assert_eq!(filtered.field_top_values("promo", 1).unwrap(), vec![(FieldValue::Bool(false), 4)]);
+        assert!(filtered.field_top_values("missing", 1).is_err());
+    }
+
+    #[test]
+    fn test_field_index_sparse() {
+        let data: Vec<(String, u32)> = (0..1_000u32)
+            .map(|n| (if n % 20 == 0 { "blocked" } else { "active" }.to_string(), n))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index_sparse("status", |row: &(String, u32)| row.0.clone(), FieldValue::from("active")).unwrap();
+
+        let active = filtered.filter_by_field_ops("status", &[(FieldOperation::eq("active"), Op::And)]).unwrap();
+        assert_eq!(active.len(), 950);
+        filtered.reset_to_source();
+        let blocked = filtered.filter_by_field_ops("status", &[(FieldOperation::not_eq("active"), Op::And)]).unwrap();
+        assert_eq!(blocked.len(), 50);
+        filtered.reset_to_source();
+        assert_eq!(filtered.field_top_values("status", 1).unwrap(), vec![(FieldValue::from("active"), 950)]);
+
+        filtered.push(("blocked".to_string(), 1_000)).unwrap();
+        filtered.push(("active".to_string(), 1_001)).unwrap();
+        let active = filtered.filter_by_field_ops("status", &[(FieldOperation::eq("active"), Op::And)]).unwrap();
+        assert_eq!(active.len(), 951);
+
+        // default другого типа - ошибка построения
+        assert!(filtered.create_field_index_sparse("number", |row: &(String, u32)| row.1, FieldValue::from("x")).is_err());
+    }
+
+    #[test]
+    fn test_index_memory_stats() {
+        let data: Vec<(String, u32)> = (0..5_000u32)
+            .map(|n| (if n % 50 == 0 { format!("rare_{n}") } else { "common".to_string() }, n))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("status", |row: &(String, u32)| row.0.clone()).unwrap();
+        filtered.create_field_index_with_kind("number", |row: &(String, u32)| row.1, IndexKind::Hash).unwrap();
+        filtered.create_text_index("text", |row: &(String, u32)| row.0.clone()).unwrap();
+
+        let stats = filtered.index_memory_stats();
+        let names: Vec<&str> = stats.iter().map(|stats| stats.name.as_str()).collect();
+        assert_eq!(names, vec!["number", "status", "text"]);
+        for stats in &stats {
+            assert!(stats.bitmap_bytes > 0);
+            assert_eq!(stats.total_bytes, stats.bitmap_bytes + stats.sorted_values_bytes + stats.overhead_bytes);
+        }
+        assert_eq!(stats[0].kind, Some(IndexKind::Hash));
+        assert_eq!(stats[0].sorted_values_bytes, 0);
+        assert_eq!(stats[1].kind, Some(IndexKind::Sorted));
+        // Позиция u32 на каждую строку
+        assert!(stats[1].sorted_values_bytes >= 5_000 * 4);
+        assert_eq!(stats[2].index_type, "text");
+        assert_eq!(stats[2].kind, None);
+
+        // Sparse индекс не хранит sorted_values и bitmap доминирующего значения
+        filtered.create_field_index_sparse("status", |row: &(String, u32)| row.0.clone(), FieldValue::from("common")).unwrap();
+        let sparse = filtered.index_memory_stats().into_iter().find(|stats| stats.name == "status").unwrap();
+        assert_eq!(sparse.sorted_values_bytes, 0);
+        assert!(sparse.total_bytes < stats[1].total_bytes);
+    }
+
+    #[test]
+    fn test_rebuild_all_indexes() {
+        let data: Vec<(AtomicUsize, String)> = (0..1_000).map(|i| (AtomicUsize::new(i % 4), format!("item {i}"))).collect();
+        let filtered = FilterData::from_vec(data);
+        filtered.create_field_index("bucket", |row: &(AtomicUsize, String)| row.0.load(Ordering::Relaxed) as u64).unwrap();
+        filtered.create_text_index("name", |row: &(AtomicUsize, String)| row.1.clone()).unwrap();
+        let bucket_version = filtered.index_version("bucket").unwrap();
+        let name_version = filtered.index_version("name").unwrap();
+        for item in filtered.items().iter() {
+            item.0.store(7, Ordering::Relaxed);
+        }
+        assert_eq!(filtered.stale_indexes(), vec!["bucket".to_string()]);
+
+        assert_eq!(filtered.rebuild_all_indexes().unwrap(), 2);
+        assert!(filtered.index_version("bucket").unwrap() > bucket_version);
+        assert!(filtered.index_version("name").unwrap() > name_version);
+        assert!(filtered.stale_indexes().is_empty());
+        filtered.filter_by_field_ops("bucket", &[(FieldOperation::eq(7u64), Op::And)]).unwrap();
+        assert_eq!(filtered.len(), 1_000);
+    }
+
+    #[test]
+    fn test_string_index_collation() {
+        let names = ["file10", "File2", "file1", "Zeta", "alpha", "file02", "beta"];
+        let rows: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let filtered = rows.into_filtered();
+        let natural = StringIndexOptions::default().with_collation(Collation::Natural);
+        filtered.create_field_index_with_options("name", |name: &String| name.clone(), natural).unwrap();
+
+        // Натуральный порядок без учета регистра: file1 < file2 = file02 < file10
+        let count = |operations: &[(FieldOperation, Op)]| filtered.count_by_field_ops("name", operations).unwrap();
+        assert_eq!(count(&[(FieldOperation::range("file1", "file9"), Op::And)]), 3);
+        assert_eq!(count(&[(FieldOperation::gt("FILE9"), Op::And)]), 2);
+        assert_eq!(count(&[(FieldOperation::eq("file2"), Op::And)]), 2);
+        assert_eq!(count(&[(FieldOperation::starts_with("FILE"), Op::And)]), 4);
+        // "Zeta" после "beta" (побайтово "Z" < "b")
+        assert_eq!(count(&[(FieldOperation::gt("beta"), Op::And), (FieldOperation::lt("file"), Op::And)]), 0);
+        assert_eq!(count(&[(FieldOperation::gt("file99"), Op::And)]), 1);
+        let index = filtered.get_index("name").unwrap();
+        let (field_index, _) = index.as_field().unwrap();
+        let keys = field_index.values_as_strings();
+        assert_eq!(keys.len(), 6);
+
+        // Пользовательский ключ: обратный порядок символов
+        fn reversed(value: &str) -> String {
+            value.chars().rev().collect()
+        }
+        let custom = StringIndexOptions::default().with_collation(Collation::Custom(reversed));
+        filtered.create_field_index_with_options("name", |name: &String| name.clone(), custom).unwrap();
+        assert_eq!(filtered.string_index_options("name"), Some(custom));
+        assert_eq!(count(&[(FieldOperation::eq("Zeta"), Op::And)]), 1);
+        // Ключи строк, оканчивающихся цифрой, начинаются с цифры
+        assert_eq!(count(&[(FieldOperation::lt("a"), Op::And)]), 4);
+    }
+
+    #[test]
+    fn test_string_index_natural_order() {
+        let versions = ["v1.10.0", "v1.9.2", "V1.2", "v10.0", "v1.10.1", "v2.0"];
+        let rows: Vec<String> = versions.iter().map(|version| version.to_string()).collect();
+        let filtered = rows.into_filtered();
+        filtered.create_field_index_with_options("version", |version: &String| version.clone(), StringIndexOptions::natural()).unwrap();
+
+        let sorted: Vec<String> = filtered.sorted_items_by_field("version", false).unwrap().iter().map(|version| version.to_string()).collect();
+        assert_eq!(sorted, vec!["V1.2", "v1.9.2", "v1.10.0", "v1.10.1", "v2.0", "v10.0"]);
+        let count = |operations: &[(FieldOperation, Op)]| filtered.count_by_field_ops("version", operations).unwrap();
+        assert_eq!(count(&[(FieldOperation::gte("v1.10"), Op::And)]), 4);
+        assert_eq!(count(&[(FieldOperation::range("v1.5", "v2"), Op::And)]), 3);
+
+        // Без collation - побайтовый порядок
+        filtered.create_field_index("version", |version: &String| version.clone()).unwrap();
+        assert_eq!(count(&[(FieldOperation::gte("v1.10"), Op::And)]), 5);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    enum Priority {
+        Low,
+        High,
+        Urgent,
+    }
+
+    impl IntoFieldValue for Priority {
+        type Stored = String;
+
+        fn into_stored(self) -> String {
+            match self {
+                Priority::Low => "low",
+                Priority::High => "high",
+                Priority::Urgent => "urgent",
+            }.to_string()
+        }
+    }
+
+    #[test]
+    fn test_into_field_value_user_types() {
+        let priorities = [Priority::Low, Priority::High, Priority::Urgent, Priority::Low];
+        let data: Vec<(u64, Priority, OrderStatus)> = (0..400u64)
+            .map(|id| (id, priorities[id as usize % 4], if id % 5 == 0 { OrderStatus::Cancelled } else { OrderStatus::Paid }))
+            .collect();
+        let filtered = data.into_filtered();
+        // Имя значения и код IndexEnum - через create_field_index
+        filtered.create_field_index("priority", |row: &(u64, Priority, OrderStatus)| row.1).unwrap();
+        filtered.create_field_index("status", |row: &(u64, Priority, OrderStatus)| row.2).unwrap();
+
+        assert_eq!(FieldValue::from(Priority::Urgent), FieldValue::String("urgent".to_string()));
+        let count = |name: &str, operation: FieldOperation| filtered.count_by_field_ops(name, &[(operation, Op::And)]).unwrap();
+        assert_eq!(count("priority", FieldOperation::eq(Priority::Low)), 200);
+        assert_eq!(count("priority", FieldOperation::in_values(vec![Priority::High, Priority::Urgent])), 200);
+        assert_eq!(count("priority", FieldOperation::eq("urgent")), 100);
+        assert_eq!(count("status", FieldOperation::eq(OrderStatus::Cancelled)), 80);
+        assert_eq!(
+            filtered.count_by_enum::<OrderStatus>("status").unwrap(),
+            vec![(OrderStatus::Paid, 320), (OrderStatus::Cancelled, 80)],
+        );
+        let values = filtered.get_index("priority").unwrap().as_field().unwrap().0.values_as_strings();
+        assert_eq!(values, vec!["\"high\"", "\"low\"", "\"urgent\""]);
+    }
+
+    #[test]
+    fn test_decimal_index_scale() {
+        let amounts = [Decimal::new(110, 2), Decimal::new(1100, 3), Decimal::new(1104, 3), Decimal::new(1105, 3), Decimal::new(1115, 3), Decimal::new(250, 2)];
+        let filtered = amounts.to_vec().into_filtered();
+        filtered.create_decimal_index_with_scale("amount", |amount: &Decimal| *amount, DecimalIndexOptions::money()).unwrap();
+        assert_eq!(filtered.decimal_index_options("amount"), Some(DecimalIndexOptions::money()));
+
+        let count = |operations: &[(FieldOperation, Op)]| filtered.count_by_field_ops("amount", operations).unwrap();
+        // 1.10, 1.100, 1.104 и 1.105 (half-even) -> 1.10; 1.115 -> 1.12
+        assert_eq!(count(&[(FieldOperation::eq(Decimal::new(11, 1)), Op::And)]), 4);
+        assert_eq!(count(&[(FieldOperation::eq(Decimal::new(1101, 3)), Op::And)]), 4);
+        assert_eq!(count(&[(FieldOperation::eq(1.12f64), Op::And)]), 1);
+        assert_eq!(count(&[(FieldOperation::gt(Decimal::new(11, 1)), Op::And)]), 2);
+
+        // HalfUp: 1.105 -> 1.11
+        filtered.create_decimal_index_with_scale("amount", |amount: &Decimal| *amount, DecimalIndexOptions::new(2, DecimalRounding::HalfUp)).unwrap();
+        assert_eq!(count(&[(FieldOperation::eq(Decimal::new(111, 2)), Op::And)]), 1);
+
+        // Обычный индекс - масштаб снимается
+        filtered.create_field_index("amount", |amount: &Decimal| *amount).unwrap();
+        assert_eq!(filtered.decimal_index_options("amount"), None);
+        assert_eq!(count(&[(FieldOperation::eq(Decimal::new(1104, 3)), Op::And)]), 1);
+
+        let options = DecimalIndexOptions::new(1, DecimalRounding::Truncate);
+        assert!(FieldValue::Decimal(Decimal::new(119, 2)).eq_at_scale(&FieldValue::from(1.1f64), &options));
+        assert!(!FieldValue::Decimal(Decimal::new(119, 2)).eq_at_scale(&FieldValue::String("1.1".into()), &options));
+        assert_eq!(FieldValue::Decimal(Decimal::new(119, 2)).round_decimal(&options), FieldValue::Decimal(Decimal::new(11, 1)));
+    }
+
+    #[test]
+    fn test_float_index_nan_policy() {
+        let values = vec![1.0, f64::NAN, 3.0, f64::INFINITY, f64::NAN, 2.0];
+        let filtered = values.into_filtered();
+        let count = |operations: &[(FieldOperation, Op)]| filtered.count_by_field_ops("score", operations).unwrap();
+
+        // NaN больше +inf
+        filtered.create_float_index_with_policy("score", |value: &f64| *value, FloatPolicy::NaNLargest).unwrap();
+        assert_eq!(count(&[(FieldOperation::gt(2.5), Op::And)]), 4);
+        let stats = filtered.field_stats("score").unwrap();
+        assert_eq!((stats.nans, stats.nulls, stats.unique_values), (2, 0, 5));
+        assert_eq!(stats.max, Some(FieldValue::from(f64::INFINITY)));
+
+        // NaN - null
+        filtered.create_float_index_with_policy("score", |value: &f64| *value, FloatPolicy::NaNIsNull).unwrap();
+        assert_eq!(count(&[(FieldOperation::gt(2.5), Op::And)]), 2);
+        assert_eq!(count(&[(FieldOperation::is_null(), Op::And)]), 2);
+        let stats = filtered.field_stats("score").unwrap();
+        assert_eq!((stats.nans, stats.nulls), (0, 2));
+
+        // NaN в данных - индекс не создается, старый остается
+        assert!(filtered.create_float_index_with_policy("score", |value: &f64| *value, FloatPolicy::RejectNaN).is_err());
+        assert_eq!(count(&[(FieldOperation::is_null(), Op::And)]), 2);
+        filtered.create_float_index_with_policy("finite", |value: &f64| if value.is_nan() { 0.0 } else { *value }, FloatPolicy::RejectNaN).unwrap();
+        assert_eq!(filtered.field_stats("finite").unwrap().nans, 0);
+    }
+
+    #[test]
+    fn test_sort_by_field() {
+        let products: Vec<Product> = [(1, 30, "books"), (2, 10, "toys"), (3, 20, "books"), (4, 30, "toys"), (5, 10, "books")]
+            .into_iter()
+            .map(|(id, price, category)| Product { id, price, category: category.to_string(), in_stock: true })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.register_computed_field("neg_id", |p: &Product| -(p.id as i64));
+        let ids = || filtered.items().iter().map(|p| p.id).collect::<Vec<u64>>();
+
+        filtered.sort_by_field("price", SortOrder::Asc).unwrap();
+        assert_eq!(ids(), vec![2, 5, 3, 1, 4]);
+        assert_eq!(filtered.current_level(), 1);
+        // Равные цены - порядок предыдущей сортировки
+        filtered.sort_by_field("neg_id", SortOrder::Asc).unwrap();
+        assert_eq!(ids(), vec![5, 4, 3, 2, 1]);
+        filtered.sort_by_field("price", SortOrder::Desc).unwrap();
+        assert_eq!(ids(), vec![4, 1, 3, 5, 2]);
+        assert_eq!(
+            filtered.level_meta(3).unwrap().operation,
+            Some(QueryStep::Sort { name: "price".to_string(), order: SortOrder::Desc })
+        );
+
+        // Сортировка внутри выборки, возврат на уровень - прежний порядок
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("price", &[(FieldOperation::gte(20u64), Op::And)]).unwrap();
+        filtered.sort_by_field("price", SortOrder::Desc).unwrap();
+        assert_eq!(ids(), vec![1, 4, 3]);
+        filtered.up();
+        assert_eq!(ids(), vec![1, 3, 4]);
+        assert!(filtered.sort_by_field("missing", SortOrder::Asc).is_err());
+    }
+
+    #[test]
+    fn test_top_k_and_page() {
+        let products: Vec<Product> = (0..1000u64)
+            .map(|id| Product { id, price: (id * 37) % 500, category: if id % 2 == 0 { "even" } else { "odd" }.to_string(), in_stock: true })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.register_computed_field("price_scan", |p: &Product| p.price);
+
+        let prices = |items: Vec<Arc<Product>>| items.iter().map(|p| p.price).collect::<Vec<u64>>();
+        assert_eq!(prices(filtered.top_k_by_field("price", 5, SortOrder::Desc).unwrap()), vec![499, 499, 498, 498, 497]);
+        assert_eq!(prices(filtered.top_k_by_field("price", 3, SortOrder::Asc).unwrap()), vec![0, 0, 1]);
+
+        // Только текущая выборка; computed поле дает тот же результат
+        filtered.filter_by_field_ops("category", &[(FieldOperation::eq("odd"), Op::And)]).unwrap();
+        let top = filtered.top_k_by_field("price", 4, SortOrder::Desc).unwrap();
+        assert!(top.iter().all(|p| p.id % 2 == 1));
+        assert_eq!(prices(top.clone()), prices(filtered.top_k_by_field("price_scan", 4, SortOrder::Desc).unwrap()));
+        assert_eq!(filtered.current_level(), 1);
+        assert_eq!(filtered.top_k_by_field("price", 10_000, SortOrder::Asc).unwrap().len(), 500);
+
+        // Страницы в порядке сортировки
+        filtered.sort_by_field("price", SortOrder::Desc).unwrap();
+        assert_eq!(filtered.pages_count(30), 17);
+        let first = filtered.page(0, 4);
+        assert_eq!(prices(first), prices(top));
+        assert_eq!(filtered.page(490, 30).len(), 10);
+        assert!(filtered.page(600, 10).is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_fields_ops_any() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id, category: if id % 10 == 0 { "sale" } else { "regular" }.to_string(), in_stock: id % 3 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.register_computed_field("in_stock", |p: &Product| p.in_stock);
+
+        // price < 10 (10 строк) OR sale (0, 10, ..., 90) -> 19
+        filtered.filter_by_fields_ops_any(&[
+            ("price", &[(FieldOperation::lt(10u64), Op::And)]),
+            ("category", &[(FieldOperation::eq("sale"), Op::And)]),
+        ]).unwrap();
+        assert_eq!(filtered.len(), 19);
+        assert_eq!(filtered.current_level(), 1);
+
+        // Внутри выборки, с computed полем без индекса
+        filtered.filter_by_fields_ops_any(&[
+            ("category", &[(FieldOperation::eq("sale"), Op::And)]),
+            ("in_stock", &[(FieldOperation::eq(true), Op::And)]),
+        ]).unwrap();
+        // sale: 10 строк; в наличии из 1..9: 3, 6, 9
+        assert_eq!(filtered.len(), 13);
+
+        filtered.reset_to_source();
+        assert!(filtered.filter_by_fields_ops_any(&[]).is_err());
+        assert!(filtered.filter_by_fields_ops_any(&[
+            ("price", &[(FieldOperation::gt(1000u64), Op::And)]),
+            ("category", &[(FieldOperation::eq("none"), Op::And)]),
+        ]).is_err());
+        assert_eq!(filtered.len(), 100);
+    }
+
+    #[test]
+    fn test_query_expr_nested_groups() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id, category: ["books", "toys", "games"][id as usize % 3].to_string(), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+
+        // (books AND price < 30) OR (toys AND NOT in_stock)
+        let expr = QueryExpr::or(vec![
+            QueryExpr::and(vec![
+                QueryExpr::op("category", FieldOperation::eq("books")),
+                QueryExpr::op("price", FieldOperation::lt(30u64)),
+            ]),
+            QueryExpr::and(vec![
+                QueryExpr::op("category", FieldOperation::eq("toys")),
+                QueryExpr::not(QueryExpr::op("in_stock", FieldOperation::eq(true))),
+            ]),
+        ]);
+        let expected = (0..100u64)
+            .filter(|id| (id % 3 == 0 && *id < 30) || (id % 3 == 1 && id % 2 == 1))
+            .count();
+        filtered.filter_query(&expr).unwrap();
+        assert_eq!(filtered.len(), expected);
+        assert!(filtered.items().iter().all(|p| (p.category == "books" && p.price < 30) || (p.category == "toys" && !p.in_stock)));
+    }
+
+    #[test]
+    fn test_filter_query_str() {
+        let products: Vec<Product> = (0..100u64)
+            .map(|id| Product { id, price: id * 10, category: ["books", "toys", "games"][id as usize % 3].to_string(), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.create_field_index("in_stock", |p: &Product| p.in_stock).unwrap();
+
+        let text = r#"price >= 500 AND (category IN ("books", "toys") OR category ~ "gam*") AND NOT in_stock = true"#;
+        filtered.filter_query_str(text).unwrap();
+        assert_eq!(filtered.len(), 25);
+        assert_eq!(&*filtered.level_meta(1).unwrap().description, text);
+
+        filtered.reset_to_source();
+        filtered.filter_query_str("price BETWEEN 100 AND 190 and category = 'toys'").unwrap();
+        assert_eq!(filtered.items().iter().map(|p| p.id).collect::<Vec<u64>>(), vec![10, 13, 16, 19]);
+
+        filtered.reset_to_source();
+        let err = filtered.filter_query_str("price >= AND").err().unwrap().to_string();
+        assert_eq!(err, "query parse error at 9: expected value");
+        assert_eq!(filtered.current_level(), 0);
+    }
+
+    #[test]
+    fn test_explain_field_ops() {
+        use tree_man::model::PlanPath;
+        let data: Vec<(u64, u64)> = (0..10_000).map(|i| (i, i % 100)).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("value", |row: &(u64, u64)| row.0).unwrap();
+        filtered.create_field_index("bucket", |row: &(u64, u64)| row.1).unwrap();
+
+        let plan = filtered.explain_field_ops(&[("bucket", &[(FieldOperation::eq(7u64), Op::And)])]).unwrap();
+        assert_eq!((plan.path, plan.reason), (PlanPath::Index, "estimate"));
+        assert_eq!(plan.estimated_rows, 100);
+        assert_eq!(plan.fields[0].unique_values, Some(100));
+        assert!(plan.fields[0].quality.is_some());
+        assert!(plan.to_string().starts_with("INDEX (estimate): selectivity 0.0100"));
+
+        // Range по индексу низкой кардинальности
+        let plan = filtered.explain_field_ops(&[("bucket", &[(FieldOperation::lt(5u64), Op::And)])]).unwrap();
+        assert_eq!((plan.path, plan.reason, plan.field.as_deref()), (PlanPath::Predicate, "inefficient_operation", Some("bucket")));
+        assert_eq!(plan.fields[0].operations[0].efficient, Some(false));
+
+        // Неселективный запрос, оценка по двум полям
+        let plan = filtered.explain_field_ops(&[
+            ("value", &[(FieldOperation::gte(1_000u64), Op::And)]),
+            ("bucket", &[(FieldOperation::eq(7u64), Op::And)]),
+        ]).unwrap();
+        assert_eq!(plan.fields.len(), 2);
+        assert!((plan.fields[0].selectivity.unwrap() - 0.9).abs() < 0.05);
+        assert!(plan.selectivity < plan.fields[0].selectivity.unwrap());
+        let plan = filtered.explain_field_ops(&[("value", &[(FieldOperation::gte(1_000u64), Op::And)])]).unwrap();
+        assert_eq!((plan.path, plan.reason, plan.threshold), (PlanPath::Predicate, "selectivity", Some(0.1)));
+
+        let narrow: &[(FieldOperation, Op)] = &[(FieldOperation::eq(42u64), Op::And)];
+        let plan = filtered.explain_field_ops_with_hint(&[("value", narrow)], QueryHint::ForceScan).unwrap();
+        assert_eq!((plan.path, plan.reason), (PlanPath::Predicate, "force_scan"));
+        filtered.never_use_index("value").unwrap();
+        let plan = filtered.explain_field_ops(&[("value", narrow)]).unwrap();
+        assert_eq!((plan.path, plan.reason), (PlanPath::Predicate, "hint_never"));
+
+        // Только оценка: выборка и статистика планировщика не меняются
+        assert_eq!(filtered.current_level(), 0);
+        assert_eq!(filtered.planner_stats().index_executions + filtered.planner_stats().predicate_executions, 0);
+        assert!(filtered.explain_field_ops(&[("missing", narrow)]).is_err());
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let data: Vec<u64> = (0..5_000).collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("value", |x: &u64| *x).unwrap();
+        filtered.create_field_index("bucket", |x: &u64| *x % 10).unwrap();
+        assert!(filtered.result_cache_stats().is_none());
+        filtered.enable_result_cache(2);
+
+        let narrow = [(FieldOperation::lt(100u64), Op::And)];
+        let bucket = [(FieldOperation::eq(3u64), Op::And)];
+        filtered.filter_by_field_ops("value", &narrow).unwrap();
+        filtered.filter_by_field_ops("bucket", &bucket).unwrap();
+        assert_eq!(filtered.len(), 10);
+        // Тот же drill-down - bitmap из кеша (bucket на 100 строках - predicate path)
+        filtered.reset_to_source();
+        filtered.filter_by_field_ops("value", &narrow).unwrap();
+        filtered.filter_by_field_ops("bucket", &bucket).unwrap();
+        assert_eq!(filtered.len(), 10);
+        assert_eq!(filtered.count_by_field_ops("bucket", &bucket).unwrap(), 10);
+        assert_eq!(filtered.count_by_field_ops("bucket", &bucket).unwrap(), 10);
+        let stats = filtered.result_cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 2, 2));
+
+        // Новые строки - новая версия индекса, запись устарела
+        filtered.reset_to_source();
+        filtered.push(7).unwrap();
+        assert_eq!(filtered.count_by_field_ops("value", &narrow).unwrap(), 101);
+        let stats = filtered.result_cache_stats().unwrap();
+        assert_eq!((stats.invalidations, stats.misses), (1, 3));
+
+        // LRU: третий запрос вытесняет давно не использованный
+        filtered.count_by_field_ops("bucket", &[(FieldOperation::eq(4u64), Op::And)]).unwrap();
+        assert_eq!(filtered.result_cache_stats().unwrap().evictions, 1);
+
+        filtered.drop_index("bucket");
+        assert_eq!(filtered.result_cache_stats().unwrap().entries, 1);
+        filtered.disable_result_cache();
+        assert!(filtered.result_cache_stats().is_none());
+        assert_eq!(filtered.count_by_field_ops("value", &narrow).unwrap(), 101);
+        // Порядок And операций и значений In не меняет ключ
+        filtered.enable_result_cache_bounded(8, 1 << 20);
+        let range = [(FieldOperation::gte(10u64), Op::And), (FieldOperation::lt(100u64), Op::And)];
+        let reversed = [(FieldOperation::lt(100u64), Op::And), (FieldOperation::gte(10u64), Op::And)];
+        assert_eq!(filtered.count_by_field_ops("value", &range).unwrap(), 90);
+        assert_eq!(filtered.count_by_field_ops("value", &reversed).unwrap(), 90);
+        let in_list = [(FieldOperation::in_values(vec![3u64, 1, 2]), Op::And)];
+        let in_sorted = [(FieldOperation::in_values(vec![1u64, 2, 3, 3]), Op::And)];
+        assert_eq!(filtered.count_by_field_ops("value", &in_list).unwrap(), 3);
+        assert_eq!(filtered.count_by_field_ops("value", &in_sorted).unwrap(), 3);
+        // Or зависит от порядка вместе с And - отдельная запись
+        let mixed = [(FieldOperation::lt(100u64), Op::And), (FieldOperation::eq(4_000u64), Op::Or)];
+        assert_eq!(filtered.count_by_field_ops("value", &mixed).unwrap(), 102);
+        let stats = filtered.result_cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (3, 2, 3));
+        assert!(stats.bytes > 0 && stats.max_bytes == Some(1 << 20));
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let products: Vec<Product> = (0..1000u64)
+            .map(|id| Product { id, price: id % 50, category: if id % 4 == 0 { "a" } else { "b" }.to_string(), in_stock: true })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.register_computed_field("price_scan", |p: &Product| p.price);
+
+        // Все строки: 20 × (0 + ... + 49)
+        assert_eq!(filtered.aggregate("price", AggregateKind::Sum).unwrap(), Some(24_500.0));
+        assert_eq!(filtered.aggregate("price", AggregateKind::Count).unwrap(), Some(1000.0));
+        assert_eq!(filtered.aggregate("price", AggregateKind::Avg).unwrap(), Some(24.5));
+
+        // Индексный путь совпадает с проходом по строкам
+        filtered.filter_by_field_ops("category", &[(FieldOperation::eq("a"), Op::And)]).unwrap();
+        let by_index = filtered.aggregate_field("price").unwrap();
+        let by_scan = filtered.aggregate_field("price_scan").unwrap();
+        assert_eq!(by_index, by_scan);
+        assert_eq!((by_index.count, by_index.min, by_index.max), (250, Some(0.0), Some(48.0)));
+
+        let values = filtered.aggregate_many(&[("price", AggregateKind::Min), ("price", AggregateKind::Max), ("price_scan", AggregateKind::Sum)]).unwrap();
+        assert_eq!(values, vec![Some(0.0), Some(48.0), Some(by_scan.sum)]);
+        assert!(filtered.aggregate("category", AggregateKind::Avg).unwrap().is_none());
+        assert!(filtered.aggregate("missing", AggregateKind::Sum).is_err());
+
+        // precompute_value_sums, sum_field / avg_field - тот же путь
+        filtered.precompute_value_sums("price").unwrap();
+        assert_eq!(filtered.aggregate_field("price").unwrap(), by_scan);
+        assert_eq!(filtered.sum_field("price").unwrap(), by_scan.sum);
+        assert_eq!(filtered.sum_field("price_scan").unwrap(), by_scan.sum);
+        assert_eq!(filtered.avg_field("price").unwrap(), by_scan.avg());
+        assert_eq!(filtered.aggregate("price", AggregateKind::Count).unwrap(), Some(250.0));
+    }
+
+    #[test]
+    fn test_facet_counts() {
+        let products: Vec<Product> = (0..1000u64)
+            .map(|id| Product { id, price: id, category: ["books", "games", "music"][(id % 3) as usize].to_string(), in_stock: id % 2 == 0 })
+            .collect();
+        let filtered = products.into_filtered();
+        filtered.create_field_index("price", |p: &Product| p.price).unwrap();
+        filtered.create_field_index("category", |p: &Product| p.category.clone()).unwrap();
+        filtered.register_computed_field("category_scan", |p: &Product| p.category.clone());
+
+        let all = filtered.facet_counts("category").unwrap();
+        assert_eq!(all, vec![
+            (FieldValue::from("books"), 334),
+            (FieldValue::from("games"), 333),
+            (FieldValue::from("music"), 333),
+        ]);
+
+        // Только текущая выборка, без изменения уровня; значения без строк пропущены
+        filtered.filter_by_field_ops("price", &[(FieldOperation::lt(4u64), Op::And)]).unwrap();
+        let level = filtered.current_level();
+        let facets = filtered.facet_counts("category").unwrap();
+        assert_eq!(facets, vec![
+            (FieldValue::from("books"), 2),
+            (FieldValue::from("games"), 1),
+            (FieldValue::from("music"), 1),
+        ]);
+        assert_eq!(facets, filtered.facet_counts("category_scan").unwrap());
+        filtered.filter_by_field_ops("category", &[(FieldOperation::eq("books"), Op::And)]).unwrap();
+        assert_eq!(filtered.facet_counts("category").unwrap().len(), 1);
+        assert_eq!(filtered.current_level(), level + 1);
+        assert!(filtered.facet_counts("missing").is_err());
+    }
+
+    #[test]
+    fn test_facet_counts_match_count_by() {
+        // Каждая пятая строка без значения
+        let data: Vec<(u64, Option<u32>)> = (0..2_000u64)
+            .map(|id| (id, (id % 5 != 0).then_some((id % 7) as u32)))
+            .collect();
+        let filtered = data.into_filtered();
+        filtered.create_field_index("id", |row: &(u64, Option<u32>)| row.0).unwrap();
+        filtered.create_nullable_field_index("bucket", |row: &(u64, Option<u32>)| row.1).unwrap();
+        filtered.create_field_index_lazy("bucket_lazy", |row: &(u64, Option<u32>)| row.1.unwrap_or(0)).unwrap();
+        filtered.filter_by_field_ops("id", &[(FieldOperation::lt(700u64), Op::And)]).unwrap();
+
+        let as_u64 = |counts: Vec<(FieldValue, usize)>| counts
+            .into_iter()
+            .map(|(value, count)| (value, count as u64))
+            .collect::<Vec<_>>();
+        let counts = filtered.count_by("bucket").unwrap();
+        assert_eq!(filtered.facet_counts("bucket").unwrap(), as_u64(counts.clone()));
+        // null не учитывается: 700 строк выборки, из них 140 без значения
+        assert!(counts.iter().all(|(value, _)| !value.is_null()));
+        assert_eq!(counts.iter().map(|(_, count)| count).sum::<usize>(), 560);
+
+        // Predicate path (индекс еще не построен) считает ту же выборку
+        let pending = filtered.count_by("bucket_lazy").unwrap();
+        assert_eq!(pending.iter().map(|(_, count)| count).sum::<usize>(), 700);
+        assert_eq!(filtered.facet_counts("bucket_lazy").unwrap(), as_u64(pending));
+    }
+}
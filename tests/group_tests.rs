@@ -1,10 +1,11 @@
 #[cfg(test)]
 mod group_data_tests {
     use tree_man::{
-        group::GroupData,
+        group::{GroupData, diff::DiffStatus},
         filter::FilterData,
         Op, FieldOperation,
         result::GlobalResult,
+        OrderedFloat,
     };
     use std::{
         sync::Arc,
@@ -488,4 +489,343 @@ mod group_data_tests {
         println!("Boolean operation: {:?}", start.elapsed());
         println!("== Performance Indicators == complete");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_aggregate_pipeline() {
+        let products = create_test_products(300);
+        let root = GroupData::new_root("Root".to_string(), products.clone(), "All");
+        root.data.create_field_index("price", |p| OrderedFloat(p.price)).unwrap();
+        root.data.create_field_index("stock", |p| p.stock).unwrap();
+        root.group_by(|p| p.category.clone(), "Category").unwrap();
+
+        let table = root.aggregate()
+            .count()
+            .sum("price")
+            .avg("price")
+            .max("stock")
+            .having(|agg| agg.count >= 100)
+            .collect()
+            .unwrap();
+        assert_eq!(table.columns, vec!["count", "sum(price)", "avg(price)", "max(stock)"]);
+        assert_eq!(table.len(), 3);
+        for row in &table.rows {
+            let expected: Vec<&Product> = products.iter().filter(|p| p.category == row.key).collect();
+            let sum: f64 = expected.iter().map(|p| p.price).sum();
+            assert_eq!(row.count, expected.len());
+            assert_eq!(row.get("count"), Some(expected.len() as f64));
+            assert!((row.sum("price").unwrap() - sum).abs() < 1e-6);
+            assert!((row.avg("price").unwrap() - sum / expected.len() as f64).abs() < 1e-6);
+            assert_eq!(row.max("stock"), Some(49.0));
+            assert_eq!(row.min("stock"), None);
+        }
+        // Ключи в порядке подгрупп
+        let keys: Vec<String> = table.rows.iter().map(|row| row.key.clone()).collect();
+        assert_eq!(keys, root.subgroups_keys());
+
+        // having отсекает подгруппы
+        root.get_subgroup(&"Phones".to_string()).unwrap().filter(|p| p.stock < 10).unwrap();
+        let table = root.aggregate()
+            .count()
+            .having(|agg| agg.count >= 100)
+            .collect()
+            .unwrap();
+        assert_eq!(table.len(), 2);
+        assert!(table.get(&"Phones".to_string()).is_none());
+
+        // Неизвестное поле - ошибка
+        assert!(root.aggregate().sum("unknown").collect().is_err());
+    }
+
+    #[test]
+    fn test_subgroup_pagination() {
+        let products = create_test_products(1_000);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.group_by(|p| format!("{:03}", p.stock), "By stock").unwrap();
+        assert_eq!(root.subgroup_count(), 50);
+
+        let first_page = root.subgroup_keys(0, 20);
+        assert_eq!(first_page.len(), 20);
+        assert_eq!(first_page[0], "000");
+        let last_page = root.subgroup_keys(40, 20);
+        assert_eq!(last_page.len(), 10);
+        assert_eq!(last_page.last().unwrap(), "049");
+        assert!(root.subgroup_keys(60, 20).is_empty());
+
+        let page = root.subgroups_page(20, 5);
+        let page_keys: Vec<String> = page.iter().map(|group| group.key.clone()).collect();
+        assert_eq!(page_keys, root.subgroup_keys(20, 5));
+
+        let next = root.subgroup_keys_after(&"019".to_string(), 3);
+        assert_eq!(next, vec!["020", "021", "022"]);
+
+        let window = root.subgroups_range("010".to_string().."015".to_string());
+        assert_eq!(window.len(), 5);
+        assert!(window.iter().all(|group| group.data.len() == 20));
+    }
+
+    #[test]
+    fn test_group_tree_diff() {
+        let yesterday_products = create_test_products(120);
+        // Сегодня: Tablets исчезли, появились Monitors, Phones стало больше
+        let mut today_products: Vec<Product> = create_test_products(120)
+            .into_iter()
+            .filter(|p| p.category != "Tablets")
+            .collect();
+        today_products.extend(create_test_products(6).into_iter().map(|mut p| {
+            p.category = if p.id % 2 == 0 { "Phones".to_string() } else { "Monitors".to_string() };
+            p
+        }));
+
+        let build = |products: Vec<Product>| {
+            let root = GroupData::new_root("Root".to_string(), products, "All");
+            root.data.create_field_index("stock", |p: &Product| p.stock).unwrap();
+            root.group_by(|p| p.category.clone(), "By category").unwrap();
+            root.get_subgroup(&"Phones".to_string()).unwrap()
+                .group_by(|p| p.brand.clone(), "By brand").unwrap();
+            root
+        };
+        let yesterday = build(yesterday_products);
+        let today = build(today_products);
+
+        let diff = yesterday.diff_with_sums(&today, &["stock"]).unwrap();
+        assert!(diff.has_changes());
+        let root = diff.get(&[]).unwrap();
+        assert_eq!(root.status, DiffStatus::Changed);
+        assert_eq!(root.delta_count(), 6 - 40);
+
+        let removed: Vec<&Vec<String>> = diff.removed().iter().map(|node| &node.path).collect();
+        assert_eq!(removed, vec![&vec!["Tablets".to_string()]]);
+        let monitors = diff.get(&["Monitors".to_string()]).unwrap();
+        assert_eq!(monitors.status, DiffStatus::Added);
+        assert_eq!(monitors.count_after, 3);
+
+        let phones = diff.get(&["Phones".to_string()]).unwrap();
+        assert_eq!(phones.status, DiffStatus::Changed);
+        assert_eq!(phones.delta_count(), 3);
+        // Добавлены id 0, 2, 4 со stock 0, 2, 4
+        assert_eq!(phones.delta_sum("stock"), Some(6.0));
+        let laptops = diff.get(&["Laptops".to_string()]).unwrap();
+        assert_eq!(laptops.status, DiffStatus::Unchanged);
+        assert!(diff.get(&["Phones".to_string(), "Apple".to_string()]).is_some());
+
+        // Дерево без изменений
+        assert!(!yesterday.diff(&yesterday).unwrap().has_changes());
+        assert!(yesterday.diff_with_sums(&today, &["missing"]).is_err());
+    }
+
+    #[test]
+    fn test_filter_subgroups_by_field_ops_counts() {
+        let products = create_test_products(1_200);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.data.create_field_index("stock", |p: &Product| p.stock).unwrap();
+        root.group_by(|p| p.category.clone(), "By category").unwrap();
+        // Индекс только у одной подгруппы - остальные считаются extractor-ом корня
+        root.get_subgroup(&"Phones".to_string()).unwrap()
+            .create_field_index("stock", |p: &Product| p.stock).unwrap();
+
+        let low_stock = [(FieldOperation::lt(10u32), Op::And)];
+        let counts = root.filter_subgroups_by_field_ops("stock", &low_stock).unwrap();
+        let expected: Vec<(String, usize)> = ["Laptops", "Phones", "Tablets"]
+            .iter()
+            .map(|category| {
+                let subgroup = root.get_subgroup(&category.to_string()).unwrap();
+                let count = subgroup.data.items().iter().filter(|p| p.stock < 10).count();
+                (category.to_string(), count)
+            })
+            .collect();
+        assert_eq!(counts.into_iter().collect::<Vec<_>>(), expected);
+        // Уровни подгрупп не изменились
+        assert!(root.get_all_subgroups().iter().all(|group| group.data.current_level() == 0));
+        assert!(root.filter_subgroups_by_field_ops("missing", &low_stock).is_err());
+    }
+
+    #[test]
+    fn test_find_subgroups_by_key_pattern() {
+        let services = ["payments-api", "payments-worker", "billing-api", "search"];
+        let products: Vec<Product> = create_test_products(400)
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut p)| {
+                p.category = services[i % services.len()].to_string();
+                p
+            })
+            .collect();
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.group_by(|p| p.category.clone(), "By service").unwrap();
+        for subgroup in root.get_all_subgroups() {
+            subgroup.group_by(|p| format!("{}-{}", p.brand.to_lowercase(), p.id % 2), "By brand").unwrap();
+        }
+
+        let keys = |groups: Vec<Arc<GroupData<String, Product>>>| -> Vec<String> {
+            groups.iter().map(|group| group.key.clone()).collect()
+        };
+        assert_eq!(keys(root.find_subgroups_matching("payments-*")), vec!["payments-api", "payments-worker"]);
+        assert_eq!(keys(root.find_subgroups_matching("*-api")), vec!["billing-api", "payments-api"]);
+        assert!(root.find_subgroups_matching("payments").is_empty());
+        assert_eq!(keys(root.find_subgroups(|key| key.len() == 6)), vec!["search"]);
+
+        // Рекурсивно: подгруппы второго уровня с ключом вида apple-0
+        let apple = root.find_subgroups_matching_recursive("apple-?");
+        assert!(!apple.is_empty());
+        assert!(apple.iter().all(|group| group.depth() == 2 && group.key.starts_with("apple-")));
+        assert_eq!(root.find_subgroups_recursive(|key| key.starts_with("payments")).len(), 2);
+    }
+
+    #[test]
+    fn test_search_text_in_subgroups() {
+        let products = create_test_products(600);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.data.create_text_index("title", |p: &Product| format!("{} {}", p.brand, p.category)).unwrap();
+        root.group_by(|p| p.category.clone(), "By category").unwrap();
+        // Собственный индекс только у одной подгруппы
+        root.get_subgroup(&"Laptops".to_string()).unwrap()
+            .create_text_index("title", |p: &Product| format!("{} {}", p.brand, p.category)).unwrap();
+
+        let counts = root.search_text_in_subgroups("title", "apple").unwrap();
+        for (category, count) in &counts {
+            let subgroup = root.get_subgroup(category).unwrap();
+            let expected = subgroup.data.items().iter().filter(|p| p.brand == "Apple").count();
+            assert_eq!(*count, expected, "category {category}");
+        }
+        assert_eq!(counts.values().sum::<usize>(), 150);
+        assert!(root.get_all_subgroups().iter().all(|group| group.data.current_level() == 0));
+        assert!(root.search_text_in_subgroups("missing", "apple").is_err());
+    }
+
+    #[test]
+    fn test_group_by_computed_field() {
+        let products = create_test_products(300);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.data.register_computed_field("stock_band", |p: &Product| p.stock / 10);
+        root.group_by_field("stock_band", "By stock band").unwrap();
+        assert_eq!(root.subgroups_keys(), vec!["0", "1", "2", "3", "4"]);
+        let band = root.get_subgroup(&"2".to_string()).unwrap();
+        assert!(band.data.items().iter().all(|p| p.stock / 10 == 2));
+        assert!(root.group_by_field("missing", "By missing").is_err());
+    }
+
+    #[test]
+    fn test_subgroup_text_indexes_share_ngram_dictionary() {
+        let products = create_test_products(300);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.group_by_with_indexes(|p| p.category.clone(), "Categories", |fd| {
+            fd.create_text_index("brand", |p: &Product| p.brand.clone())?;
+            Ok(())
+        }).unwrap();
+        // Бренды одинаковы во всех категориях - n-граммы хранятся один раз
+        let single = FilterData::from_vec(create_test_products(300));
+        single.create_text_index("brand", |p: &Product| p.brand.clone()).unwrap();
+        assert_eq!(root.data.ngram_dictionary_len(), single.ngram_dictionary_len());
+
+        let phones = root.get_subgroup(&"Phones".to_string()).unwrap();
+        assert_eq!(phones.data.ngram_dictionary_len(), root.data.ngram_dictionary_len());
+        phones.data.search_with_text("brand", "sams").unwrap();
+        assert!(phones.data.items().iter().all(|p| p.brand == "Samsung" && p.category == "Phones"));
+        assert_eq!(phones.data.len(), 25);
+    }
+
+    #[test]
+    fn test_par_leaves() {
+        use rayon::prelude::*;
+        let products = create_test_products(120);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        // Без подгрупп единственный лист - сам корень
+        let leaves: Vec<_> = root.par_leaves().collect();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].0, vec!["Root".to_string()]);
+
+        root.group_by(|p| p.category.clone(), "Categories").unwrap();
+        root.get_subgroup(&"Phones".to_string()).unwrap()
+            .group_by(|p| p.brand.clone(), "Brands").unwrap();
+        let leaves: Vec<(Vec<String>, usize)> = root
+            .par_leaves()
+            .map(|(path, data)| (path, data.len()))
+            .collect();
+        let paths: Vec<String> = leaves.iter().map(|(path, _)| path.join("/")).collect();
+        assert_eq!(paths, vec![
+            "Root/Laptops",
+            "Root/Phones/Apple",
+            "Root/Phones/Dell",
+            "Root/Phones/Lenovo",
+            "Root/Phones/Samsung",
+            "Root/Tablets",
+        ]);
+        assert_eq!(leaves.iter().map(|(_, len)| len).sum::<usize>(), 120);
+
+        let phones = root.get_subgroup(&"Phones".to_string()).unwrap();
+        let total: usize = phones.par_leaves().map(|(_, data)| data.len()).sum();
+        assert_eq!(total, 40);
+    }
+
+    #[test]
+    fn test_locate_row_in_tree() {
+        let products = create_test_products(120);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        assert_eq!(root.locate(5), vec![vec!["Root".to_string()]]);
+        assert!(root.locate(500).is_empty());
+
+        root.group_by(|p| p.category.clone(), "Categories").unwrap();
+        root.get_subgroup(&"Phones".to_string()).unwrap()
+            .group_by(|p| p.brand.clone(), "Brands").unwrap();
+        // id 4: Laptops / Apple; id 3: Phones / Lenovo
+        assert_eq!(root.locate(4), vec![vec!["Root".to_string(), "Laptops".to_string()]]);
+        assert_eq!(root.locate(3), vec![vec!["Root".to_string(), "Phones".to_string(), "Lenovo".to_string()]]);
+
+        let phones = root.get_subgroup(&"Phones".to_string()).unwrap();
+        assert!(phones.locate(4).is_empty());
+        let path = phones.locate(3).remove(0);
+        let mut group = root.clone();
+        for key in &path[1..] {
+            group = group.get_subgroup(key).unwrap();
+        }
+        assert!(group.data.items_with_ids().iter().any(|(row_id, p)| *row_id == 3 && p.id == 3));
+    }
+
+    #[test]
+    fn test_locate_row_in_unsorted_view() {
+        let source = FilterData::from_vec(create_test_products(20));
+        let parent = source.parent_data().unwrap();
+        // Корень - view с произвольным порядком строк
+        let root = GroupData::new_root_with_indexes("Root".to_string(), Vec::new(), "View", |_| {
+            Ok(FilterData::from_indices(&parent, vec![9, 2, 7, 4]))
+        }).unwrap();
+        assert_eq!(root.locate(9), vec![vec!["Root".to_string()]]);
+        assert_eq!(root.locate(2), vec![vec!["Root".to_string()]]);
+        assert!(root.locate(3).is_empty());
+    }
+
+    #[test]
+    fn test_group_cursor_restore() {
+        let products = create_test_products(120);
+        let root = GroupData::new_root("Root".to_string(), products, "All");
+        root.group_by(|p| p.category.clone(), "Categories").unwrap();
+        let phones = root.get_subgroup(&"Phones".to_string()).unwrap();
+        phones.group_by(|p| p.brand.clone(), "Brands").unwrap();
+        let lenovo = phones.get_subgroup(&"Lenovo".to_string()).unwrap();
+        lenovo.filter(|p| p.price > 1_000.0).unwrap();
+        assert_eq!(lenovo.data.len(), 6);
+
+        let cursor = lenovo.cursor();
+        assert_eq!(cursor.path, vec!["Root".to_string(), "Phones".to_string(), "Lenovo".to_string()]);
+        assert_eq!(cursor.level, 1);
+        assert_eq!(cursor.depth(), 2);
+
+        // Пользователь ушел назад - курсор возвращает уровень
+        lenovo.filter(|p| p.stock > 10).unwrap();
+        lenovo.data.up();
+        let restored = phones.restore_cursor(&cursor).unwrap();
+        assert!(Arc::ptr_eq(&restored, &lenovo));
+        assert_eq!(restored.data.current_level(), 1);
+        assert_eq!(restored.data.len(), 6);
+
+        // Уровень перефильтрован другим запросом - молча не восстанавливается
+        lenovo.data.up();
+        lenovo.filter(|p| p.price > 1_200.0).unwrap();
+        assert!(root.restore_cursor(&cursor).is_err());
+
+        let mut missing = cursor.clone();
+        missing.path[1] = "Unknown".to_string();
+        let Err(error) = root.restore_cursor(&missing) else { panic!("path must not resolve") };
+        assert_eq!(error.to_string(), "group path not found: Root/Unknown/Lenovo");
+    }
+}
@@ -4,7 +4,7 @@ mod memory_leak_tests{
     use tree_man::{
         group::GroupData,
         filter::FilterData,
-        Op, FieldOperation,
+        Op, FieldOperation, Rows,
     };
     use serial_test::serial;
     use std::{
@@ -384,9 +384,9 @@ mod memory_leak_tests{
         
         {
             let products = create_tracked_products(50);
-            let parent_data = Arc::new(
+            let parent_data = Arc::new(Rows::from(
                 products.into_iter().map(Arc::new).collect::<Vec<_>>()
-            );
+            ));
             
             // Создаем Indexed FilterData
             let indices = vec![0, 5, 10, 15, 20, 25, 30, 35, 40, 45];